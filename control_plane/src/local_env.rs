@@ -164,6 +164,12 @@ pub struct NeonStorageControllerConf {
     /// Threshold for auto-splitting a tenant into shards
     pub split_threshold: Option<u64>,
 
+    /// Resident size threshold for auto-splitting a tenant into shards
+    pub split_resident_size_threshold: Option<u64>,
+
+    /// WAL ingest rate threshold for auto-splitting a tenant into shards, in bytes per second
+    pub split_wal_ingest_rate_threshold: Option<u64>,
+
     pub max_secondary_lag_bytes: Option<u64>,
 
     #[serde(with = "humantime_serde")]
@@ -191,6 +197,8 @@ fn default() -> Self {
             start_as_candidate: false,
             database_url: None,
             split_threshold: None,
+            split_resident_size_threshold: None,
+            split_wal_ingest_rate_threshold: None,
             max_secondary_lag_bytes: None,
             heartbeat_interval: Self::DEFAULT_HEARTBEAT_INTERVAL,
             long_reconcile_threshold: None,
@@ -513,6 +513,14 @@ pub async fn start(&self, start_args: NeonStorageControllerStartArgs) -> anyhow:
             args.push(format!("--split-threshold={split_threshold}"))
         }
 
+        if let Some(threshold) = self.config.split_resident_size_threshold.as_ref() {
+            args.push(format!("--split-resident-size-threshold={threshold}"))
+        }
+
+        if let Some(threshold) = self.config.split_wal_ingest_rate_threshold.as_ref() {
+            args.push(format!("--split-wal-ingest-rate-threshold={threshold}"))
+        }
+
         if let Some(lag) = self.config.max_secondary_lag_bytes.as_ref() {
             args.push(format!("--max-secondary-lag-bytes={lag}"))
         }
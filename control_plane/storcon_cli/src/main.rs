@@ -4,8 +4,13 @@
 use clap::{Parser, Subcommand};
 use pageserver_api::{
     controller_api::{
-        AvailabilityZone, NodeAvailabilityWrapper, NodeDescribeResponse, NodeShardResponse,
-        ShardSchedulingPolicy, TenantCreateRequest, TenantDescribeResponse, TenantPolicyRequest,
+        AuditRecord, AutoSplitDecisionsResponse, AvailabilityZone, BulkMigratePlanRequest,
+        ChaosConfig, ChaosScenario, ChaosStatus, MigrationPlan, NodeAvailabilityWrapper,
+        NodeDescribeResponse, NodeShardResponse, OperationStartResponse, OperationState,
+        OperationStatus, PlacementConstraints, RestartSignal, RollingRestartRequest,
+        RollingRestartStatus, ShardSchedulingPolicy, ShardSplitPolicy, SpreadConstraint,
+        TenantCreateRequest, TenantDescribeResponse, TenantMergeRequest, TenantMergeResponse,
+        TenantPolicyRequest,
     },
     models::{
         EvictionPolicy, EvictionPolicyLayerAccessThreshold, LocationConfigSecondary,
@@ -43,6 +48,9 @@ enum Command {
         listen_http_port: u16,
         #[arg(long)]
         availability_zone_id: String,
+        /// Label for the node in the form `key=value`, may be repeated
+        #[arg(long)]
+        label: Vec<LabelArg>,
     },
 
     /// Modify a node's configuration in the storage controller
@@ -76,6 +84,33 @@ enum Command {
         /// unavailable, and are only for use in emergencies.
         #[arg(long)]
         scheduling: Option<ShardSchedulingPolicyArg>,
+        /// Disable automatic splitting of this tenant.  Setting any of the split options replaces the
+        /// tenant's whole split policy.
+        #[arg(long)]
+        split_disabled: Option<bool>,
+        /// Don't automatically split this tenant beyond this many shards.  Must be a power of two
+        /// multiple of the tenant's shard count.
+        #[arg(long)]
+        split_max_shard_count: Option<ShardCountArg>,
+        /// Stripe size to use when automatically splitting this tenant while it is unsharded
+        #[arg(long)]
+        split_stripe_size: Option<u32>,
+        /// Only place this tenant on nodes with this label, in the form `key=value`.  May be repeated.
+        /// Setting any of the placement constraint options replaces the tenant's whole constraints.
+        #[arg(long)]
+        require_label: Vec<LabelArg>,
+        /// Prefer nodes with this label, in the form `key=value`.  May be repeated.
+        #[arg(long)]
+        prefer_label: Vec<LabelArg>,
+        /// Spread this tenant's shards away from those of other tenants in the same group, across
+        /// nodes with different values of `--spread-label`
+        #[arg(long, requires = "spread_label")]
+        spread_group: Option<String>,
+        #[arg(long, requires = "spread_group")]
+        spread_label: Option<String>,
+        /// Remove all placement constraints from the tenant
+        #[arg(long)]
+        clear_constraints: bool,
     },
     /// List nodes known to the storage controller
     Nodes {},
@@ -84,6 +119,8 @@ enum Command {
         /// If this field is set, it will list the tenants on a specific node
         node_id: Option<NodeId>,
     },
+    /// Show the storage controller's most recent decisions about automatically splitting tenants
+    AutoSplitDecisions {},
     /// Create a new tenant in the storage controller, and by extension on pageservers.
     TenantCreate {
         #[arg(long)]
@@ -104,6 +141,14 @@ enum Command {
         #[arg(long)]
         stripe_size: Option<u32>,
     },
+    /// Merge an existing tenant's shards into a lower number of shards, which must divide its
+    /// current shard count.  A shard count of 1 makes the tenant unsharded again.
+    TenantShardMerge {
+        #[arg(long)]
+        tenant_id: TenantId,
+        #[arg(long)]
+        shard_count: u8,
+    },
     /// Migrate the attached location for a tenant shard to a specific pageserver.
     TenantShardMigrate {
         #[arg(long)]
@@ -173,6 +218,9 @@ enum Command {
     StartDrain {
         #[arg(long)]
         node_id: NodeId,
+        /// Print the shards the drain would move instead of starting it
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Cancel draining the specified pageserver and wait for `timeout`
     /// for the operation to be canceled. May be retried.
@@ -187,6 +235,9 @@ enum Command {
     StartFill {
         #[arg(long)]
         node_id: NodeId,
+        /// Print the shards the fill would move instead of starting it
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Cancel filling the specified pageserver and wait for `timeout`
     /// for the operation to be canceled. May be retried.
@@ -196,6 +247,89 @@ enum Command {
         #[arg(long)]
         timeout: humantime::Duration,
     },
+    /// Start a rolling restart: drain, wait for a restart of, and fill each of the nodes
+    /// (or all nodes), a few at a time per availability zone.
+    RollingRestart {
+        #[arg(long)]
+        nodes: Vec<NodeId>,
+        /// How many nodes of an availability zone may be out of service at once (default 1)
+        #[arg(long)]
+        max_concurrent_per_az: Option<usize>,
+        /// Wait for each restart to be reported with `node-restarted`, rather than only for
+        /// the node to re-attach
+        #[arg(long)]
+        external_signal: bool,
+        /// How long to wait for a drained node to restart (default 30m)
+        #[arg(long)]
+        restart_timeout: Option<humantime::Duration>,
+    },
+    /// Show the progress of the current or most recent rolling restart
+    RollingRestartStatus {},
+    /// Stop the rolling restart from starting more nodes
+    RollingRestartPause {},
+    /// Resume a paused or failed rolling restart
+    RollingRestartResume {},
+    /// Abort the rolling restart, returning nodes it took out of service to service
+    RollingRestartAbort {},
+    /// Report that a node drained by a rolling restart has been restarted
+    NodeRestarted {
+        #[arg(long)]
+        node_id: NodeId,
+    },
+    /// List recent long-running operations, newest first
+    Operations {
+        #[arg(long)]
+        tenant_id: Option<TenantId>,
+        #[arg(long)]
+        node_id: Option<NodeId>,
+        /// Only list operations which are still running
+        #[arg(long)]
+        running: bool,
+        #[arg(long)]
+        limit: Option<i64>,
+    },
+    /// Follow the progress of an operation until it finishes
+    OperationWatch {
+        #[arg(long)]
+        operation_id: i64,
+        /// How often to poll the operation (default 2s)
+        #[arg(long)]
+        interval: Option<humantime::Duration>,
+    },
+    /// Cancel a running operation
+    OperationCancel {
+        #[arg(long)]
+        operation_id: i64,
+    },
+    /// List recent mutating API calls from the audit log, newest first
+    Audit {
+        #[arg(long)]
+        tenant_id: Option<TenantId>,
+        #[arg(long)]
+        node_id: Option<NodeId>,
+        #[arg(long)]
+        limit: Option<i64>,
+        /// Print the request body and the state before and after each call
+        #[arg(long)]
+        verbose: bool,
+    },
+    /// Start injecting faults, or reconfigure the running chaos injector
+    ChaosStart {
+        /// How often to inject a fault
+        #[arg(long)]
+        interval: humantime::Duration,
+        /// How many shards to interfere with each time (default 128)
+        #[arg(long)]
+        batch_size: Option<usize>,
+        /// Weight of a scenario in the form `scenario=weight`, e.g. `node_offline=1`.  May be
+        /// repeated.
+        #[arg(long, required = true)]
+        weight: Vec<ChaosWeightArg>,
+    },
+    /// Stop injecting faults
+    ChaosStop {},
+    /// Show the configuration of the chaos injector and the faults it injected
+    ChaosStatus {},
 }
 
 #[derive(Parser)]
@@ -267,6 +401,54 @@ fn from_str(s: &str) -> Result<Self, Self::Err> {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+struct ShardCountArg(u8);
+
+impl FromStr for ShardCountArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Tenants are split by a power of two at a time, starting from one shard
+        let count = s.parse::<u8>()?;
+        if !count.is_power_of_two() {
+            anyhow::bail!("Invalid shard count '{s}', must be a power of two");
+        }
+        Ok(Self(count))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LabelArg((String, String));
+
+impl FromStr for LabelArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((k, v)) if !k.is_empty() => Ok(Self((k.to_string(), v.to_string()))),
+            _ => Err(anyhow::anyhow!(
+                "Invalid label '{s}', a valid example is 'rack=r1'"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ChaosWeightArg((ChaosScenario, u32));
+
+impl FromStr for ChaosWeightArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((scenario, weight)) = s.split_once('=') else {
+            anyhow::bail!("Invalid weight '{s}', a valid example is 'node_offline=1'");
+        };
+        let scenario = ChaosScenario::from_str(scenario)
+            .map_err(|_| anyhow::anyhow!("Unknown chaos scenario '{scenario}'"))?;
+        Ok(Self((scenario, weight.parse()?)))
+    }
+}
+
 #[derive(Debug, Clone)]
 struct NodeAvailabilityArg(NodeAvailabilityWrapper);
 
@@ -310,6 +492,117 @@ async fn wait_for_scheduling_policy<F>(
     Ok(waiter.await??)
 }
 
+fn print_rolling_restart(status: &RollingRestartStatus) {
+    println!(
+        "Rolling restart {} is {:?} (started {}, updated {})",
+        status.id, status.state, status.started_at, status.updated_at
+    );
+
+    let mut table = comfy_table::Table::new();
+    table.set_header(["Id", "AZ", "Phase", "Since", "Error"]);
+    for node in &status.nodes {
+        table.add_row([
+            format!("{}", node.node_id),
+            format!("{}", node.availability_zone_id),
+            format!("{:?}", node.phase),
+            node.phase_started_at
+                .map(|t| t.to_string())
+                .unwrap_or_default(),
+            node.error.clone().unwrap_or_default(),
+        ]);
+    }
+    println!("{table}");
+}
+
+fn print_migration_plan(plan: &MigrationPlan) {
+    let size = |size: Option<u64>| size.map(|s| s.to_string()).unwrap_or("?".to_string());
+
+    let mut table = comfy_table::Table::new();
+    table.set_header(["Shard", "From", "To", "Secondary", "Resident", "Download"]);
+    for mv in &plan.moves {
+        table.add_row([
+            format!("{}", mv.tenant_shard_id),
+            format!("{}", mv.from),
+            format!("{}", mv.to),
+            format!("{}", mv.to_secondary),
+            size(mv.resident_size),
+            size(mv.download_size),
+        ]);
+    }
+    println!("{table}");
+
+    for skipped in &plan.skipped {
+        println!("Skipping {}: {}", skipped.tenant_shard_id, skipped.reason);
+    }
+    println!(
+        "Planned {} moves of {} resident bytes, downloading {} bytes. Estimated warmup: {}",
+        plan.moves.len(),
+        plan.resident_size,
+        plan.download_size,
+        humantime::format_duration(plan.estimated_warmup)
+    );
+}
+
+fn print_chaos_status(status: &ChaosStatus) {
+    match &status.config {
+        Some(config) => println!(
+            "Injecting chaos every {} into up to {} shards",
+            humantime::format_duration(config.interval),
+            config.batch_size
+        ),
+        None => println!("Not injecting chaos"),
+    }
+    if status.split_kill_armed {
+        println!("The next shard split will be killed");
+    }
+
+    let mut table = comfy_table::Table::new();
+    table.set_header(["Scenario", "Weight", "Injected"]);
+    let mut scenarios = status.injected.keys().cloned().collect::<Vec<_>>();
+    if let Some(config) = &status.config {
+        scenarios.extend(config.weights.keys().cloned());
+    }
+    scenarios.sort();
+    scenarios.dedup();
+    for scenario in scenarios {
+        let weight = status
+            .config
+            .as_ref()
+            .and_then(|c| c.weights.get(&scenario).cloned())
+            .unwrap_or_default();
+        table.add_row([
+            scenario.to_string(),
+            format!("{weight}"),
+            format!(
+                "{}",
+                status.injected.get(&scenario).cloned().unwrap_or_default()
+            ),
+        ]);
+    }
+    println!("{table}");
+}
+
+fn print_audit_record(record: &AuditRecord) {
+    println!(
+        "{} {} {} {} -> {} by {}",
+        record.id,
+        record.recorded_at,
+        record.method,
+        record.path,
+        record.status,
+        record.subject.as_deref().unwrap_or("<anonymous>")
+    );
+    for (name, value) in [
+        ("Request", &record.request),
+        ("Before", &record.state_before),
+        ("After", &record.state_after),
+    ] {
+        if let Some(value) = value {
+            println!("  {name}: {}", serde_json::to_string_pretty(value).unwrap());
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
@@ -328,6 +621,7 @@ async fn main() -> anyhow::Result<()> {
             listen_http_addr,
             listen_http_port,
             availability_zone_id,
+            label,
         } => {
             storcon_client
                 .dispatch::<_, ()>(
@@ -340,6 +634,7 @@ async fn main() -> anyhow::Result<()> {
                         listen_http_addr,
                         listen_http_port,
                         availability_zone_id: AvailabilityZone(availability_zone_id),
+                        labels: label.into_iter().map(|l| l.0).collect(),
                     }),
                 )
                 .await?;
@@ -377,13 +672,18 @@ async fn main() -> anyhow::Result<()> {
             resp.sort_by(|a, b| a.listen_http_addr.cmp(&b.listen_http_addr));
 
             let mut table = comfy_table::Table::new();
-            table.set_header(["Id", "Hostname", "Scheduling", "Availability"]);
+            table.set_header(["Id", "Hostname", "Scheduling", "Availability", "Labels"]);
             for node in resp {
                 table.add_row([
                     format!("{}", node.id),
                     node.listen_http_addr,
                     format!("{:?}", node.scheduling),
                     format!("{:?}", node.availability),
+                    node.labels
+                        .iter()
+                        .map(|(k, v)| format!("{k}={v}"))
+                        .collect::<Vec<_>>()
+                        .join(","),
                 ]);
             }
             println!("{table}");
@@ -476,10 +776,48 @@ async fn main() -> anyhow::Result<()> {
             tenant_id,
             placement,
             scheduling,
+            split_disabled,
+            split_max_shard_count,
+            split_stripe_size,
+            require_label,
+            prefer_label,
+            spread_group,
+            spread_label,
+            clear_constraints,
         } => {
+            let split = if split_disabled.is_some()
+                || split_max_shard_count.is_some()
+                || split_stripe_size.is_some()
+            {
+                Some(ShardSplitPolicy {
+                    disabled: split_disabled.unwrap_or(false),
+                    max_shard_count: split_max_shard_count.map(|c| c.0),
+                    stripe_size: split_stripe_size.map(ShardStripeSize),
+                })
+            } else {
+                None
+            };
+            let constraints = if clear_constraints {
+                Some(PlacementConstraints::default())
+            } else if !require_label.is_empty()
+                || !prefer_label.is_empty()
+                || spread_group.is_some()
+            {
+                Some(PlacementConstraints {
+                    required_labels: require_label.into_iter().map(|l| l.0).collect(),
+                    preferred_labels: prefer_label.into_iter().map(|l| l.0).collect(),
+                    spread: spread_group
+                        .zip(spread_label)
+                        .map(|(group, label)| SpreadConstraint { group, label }),
+                })
+            } else {
+                None
+            };
             let req = TenantPolicyRequest {
                 scheduling: scheduling.map(|s| s.0),
                 placement: placement.map(|p| p.0),
+                split,
+                constraints,
             };
             storcon_client
                 .dispatch::<_, ()>(
@@ -489,6 +827,33 @@ async fn main() -> anyhow::Result<()> {
                 )
                 .await?;
         }
+        Command::AutoSplitDecisions {} => {
+            let resp = storcon_client
+                .dispatch::<(), AutoSplitDecisionsResponse>(
+                    Method::GET,
+                    "control/v1/autosplit".to_string(),
+                    None,
+                )
+                .await?;
+
+            let Some(decided_at) = resp.decided_at else {
+                println!("No automatic split decisions have been made");
+                return Ok(());
+            };
+            println!("Decided at {decided_at}");
+
+            let mut table = comfy_table::Table::new();
+            table.set_header(["TenantId", "ShardCount", "Triggers", "Outcome"]);
+            for decision in resp.decisions {
+                table.add_row([
+                    format!("{}", decision.tenant_id),
+                    format!("{}", decision.shard_count),
+                    format!("{:?}", decision.triggers),
+                    format!("{:?}", decision.outcome),
+                ]);
+            }
+            println!("{table}");
+        }
         Command::TenantShardSplit {
             tenant_id,
             shard_count,
@@ -518,6 +883,33 @@ async fn main() -> anyhow::Result<()> {
                     .join(",")
             );
         }
+        Command::TenantShardMerge {
+            tenant_id,
+            shard_count,
+        } => {
+            let req = TenantMergeRequest {
+                new_shard_count: shard_count,
+            };
+
+            let response = storcon_client
+                .dispatch::<TenantMergeRequest, TenantMergeResponse>(
+                    Method::PUT,
+                    format!("control/v1/tenant/{tenant_id}/shard_merge"),
+                    Some(req),
+                )
+                .await?;
+            println!(
+                "Merged tenant {} into {} shards: {}",
+                tenant_id,
+                shard_count,
+                response
+                    .new_shards
+                    .iter()
+                    .map(|s| format!("{:?}", s))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+        }
         Command::TenantShardMigrate {
             tenant_shard_id,
             node,
@@ -731,49 +1123,32 @@ async fn main() -> anyhow::Result<()> {
             max_shards,
             dry_run,
         } => {
-            // Load the list of nodes, split them up into the drained and filled sets,
-            // and validate that draining is possible.
-            let node_descs = storcon_client
-                .dispatch::<(), Vec<NodeDescribeResponse>>(
-                    Method::GET,
-                    "control/v1/node".to_string(),
-                    None,
+            // Plan the moves on the storage controller: it spreads the shards attached to the
+            // drained nodes round robin over the other active nodes which satisfy their placement
+            // constraints. Sizes are only needed to show a dry run's plan.
+            let plan = storcon_client
+                .dispatch::<BulkMigratePlanRequest, MigrationPlan>(
+                    Method::POST,
+                    "control/v1/bulk_migrate/plan".to_string(),
+                    Some(BulkMigratePlanRequest {
+                        nodes: nodes.clone(),
+                        max_shards,
+                        sizes: dry_run == Some(true),
+                    }),
                 )
                 .await?;
 
-            let mut node_to_drain_descs = Vec::new();
-            let mut node_to_fill_descs = Vec::new();
-
-            for desc in node_descs {
-                let to_drain = nodes.iter().any(|id| *id == desc.id);
-                if to_drain {
-                    node_to_drain_descs.push(desc);
-                } else {
-                    node_to_fill_descs.push(desc);
-                }
-            }
-
-            if nodes.len() != node_to_drain_descs.len() {
-                anyhow::bail!("Bulk migration requested away from node which doesn't exist.")
-            }
-
-            node_to_fill_descs.retain(|desc| {
-                matches!(desc.availability, NodeAvailabilityWrapper::Active)
-                    && matches!(
-                        desc.scheduling,
-                        NodeSchedulingPolicy::Active | NodeSchedulingPolicy::Filling
-                    )
-            });
-
-            if node_to_fill_descs.is_empty() {
-                anyhow::bail!("There are no nodes to migrate to")
+            if dry_run == Some(true) {
+                println!("Dryrun requested.");
+                print_migration_plan(&plan);
+                return Ok(());
             }
 
             // Set the node scheduling policy to draining for the nodes which
             // we plan to drain.
-            for node_desc in node_to_drain_descs.iter() {
+            for node_id in nodes.iter() {
                 let req = NodeConfigureRequest {
-                    node_id: node_desc.id,
+                    node_id: *node_id,
                     availability: None,
                     scheduling: Some(NodeSchedulingPolicy::Draining),
                 };
@@ -781,82 +1156,19 @@ async fn main() -> anyhow::Result<()> {
                 storcon_client
                     .dispatch::<_, ()>(
                         Method::PUT,
-                        format!("control/v1/node/{}/config", node_desc.id),
+                        format!("control/v1/node/{}/config", node_id),
                         Some(req),
                     )
                     .await?;
             }
 
-            // Perform the migration: move each tenant shard scheduled on a node to
-            // be drained to a node which is being filled. A simple round robin
-            // strategy is used to pick the new node.
-            let tenants = storcon_client
-                .dispatch::<(), Vec<TenantDescribeResponse>>(
-                    Method::GET,
-                    "control/v1/tenant".to_string(),
-                    None,
-                )
-                .await?;
-
-            let mut selected_node_idx = 0;
-
-            struct MigrationMove {
-                tenant_shard_id: TenantShardId,
-                from: NodeId,
-                to: NodeId,
-            }
-
-            let mut moves: Vec<MigrationMove> = Vec::new();
-
-            let shards = tenants
-                .into_iter()
-                .flat_map(|tenant| tenant.shards.into_iter());
-            for shard in shards {
-                if let Some(max_shards) = max_shards {
-                    if moves.len() >= max_shards {
-                        println!(
-                            "Stop planning shard moves since the requested maximum was reached"
-                        );
-                        break;
-                    }
-                }
-
-                let should_migrate = {
-                    if let Some(attached_to) = shard.node_attached {
-                        node_to_drain_descs
-                            .iter()
-                            .map(|desc| desc.id)
-                            .any(|id| id == attached_to)
-                    } else {
-                        false
-                    }
-                };
-
-                if !should_migrate {
-                    continue;
-                }
-
-                moves.push(MigrationMove {
-                    tenant_shard_id: shard.tenant_shard_id,
-                    from: shard
-                        .node_attached
-                        .expect("We only migrate attached tenant shards"),
-                    to: node_to_fill_descs[selected_node_idx].id,
-                });
-                selected_node_idx = (selected_node_idx + 1) % node_to_fill_descs.len();
+            for skipped in &plan.skipped {
+                println!("Skipping {}: {}", skipped.tenant_shard_id, skipped.reason);
             }
 
+            let moves = plan.moves;
             let total_moves = moves.len();
 
-            if dry_run == Some(true) {
-                println!("Dryrun requested. Planned {total_moves} moves:");
-                for mv in &moves {
-                    println!("{}: {} -> {}", mv.tenant_shard_id, mv.from, mv.to)
-                }
-
-                return Ok(());
-            }
-
             const DEFAULT_MIGRATE_CONCURRENCY: usize = 8;
             let mut stream = futures::stream::iter(moves)
                 .map(|mv| {
@@ -913,15 +1225,34 @@ struct MigrationMove {
                 failure
             );
         }
-        Command::StartDrain { node_id } => {
-            storcon_client
-                .dispatch::<(), ()>(
+        Command::StartDrain {
+            node_id,
+            dry_run: true,
+        } => {
+            let plan = storcon_client
+                .dispatch::<(), MigrationPlan>(
+                    Method::GET,
+                    format!("control/v1/node/{node_id}/drain/plan"),
+                    None,
+                )
+                .await?;
+            print_migration_plan(&plan);
+        }
+        Command::StartDrain {
+            node_id,
+            dry_run: false,
+        } => {
+            let resp = storcon_client
+                .dispatch::<(), OperationStartResponse>(
                     Method::PUT,
                     format!("control/v1/node/{node_id}/drain"),
                     None,
                 )
                 .await?;
-            println!("Drain started for {node_id}");
+            println!(
+                "Drain started for {node_id} as operation {}",
+                resp.operation_id
+            );
         }
         Command::CancelDrain { node_id, timeout } => {
             storcon_client
@@ -945,12 +1276,35 @@ struct MigrationMove {
                 "Drain was cancelled for node {node_id}. Schedulling policy is now {final_policy:?}"
             );
         }
-        Command::StartFill { node_id } => {
-            storcon_client
-                .dispatch::<(), ()>(Method::PUT, format!("control/v1/node/{node_id}/fill"), None)
+        Command::StartFill {
+            node_id,
+            dry_run: true,
+        } => {
+            let plan = storcon_client
+                .dispatch::<(), MigrationPlan>(
+                    Method::GET,
+                    format!("control/v1/node/{node_id}/fill/plan"),
+                    None,
+                )
+                .await?;
+            print_migration_plan(&plan);
+        }
+        Command::StartFill {
+            node_id,
+            dry_run: false,
+        } => {
+            let resp = storcon_client
+                .dispatch::<(), OperationStartResponse>(
+                    Method::PUT,
+                    format!("control/v1/node/{node_id}/fill"),
+                    None,
+                )
                 .await?;
 
-            println!("Fill started for {node_id}");
+            println!(
+                "Fill started for {node_id} as operation {}",
+                resp.operation_id
+            );
         }
         Command::CancelFill { node_id, timeout } => {
             storcon_client
@@ -974,6 +1328,250 @@ struct MigrationMove {
                 "Fill was cancelled for node {node_id}. Schedulling policy is now {final_policy:?}"
             );
         }
+        Command::RollingRestart {
+            nodes,
+            max_concurrent_per_az,
+            external_signal,
+            restart_timeout,
+        } => {
+            let defaults = RollingRestartRequest::default();
+            let req = RollingRestartRequest {
+                nodes,
+                max_concurrent_per_az: max_concurrent_per_az
+                    .unwrap_or(defaults.max_concurrent_per_az),
+                restart_signal: if external_signal {
+                    RestartSignal::External
+                } else {
+                    RestartSignal::ReAttach
+                },
+                restart_timeout: restart_timeout
+                    .map(|t| *t)
+                    .unwrap_or(defaults.restart_timeout),
+            };
+            let status = storcon_client
+                .dispatch::<RollingRestartRequest, RollingRestartStatus>(
+                    Method::POST,
+                    "control/v1/rolling_restart".to_string(),
+                    Some(req),
+                )
+                .await?;
+            print_rolling_restart(&status);
+        }
+        Command::RollingRestartStatus {} => {
+            let status = storcon_client
+                .dispatch::<(), RollingRestartStatus>(
+                    Method::GET,
+                    "control/v1/rolling_restart".to_string(),
+                    None,
+                )
+                .await?;
+            print_rolling_restart(&status);
+        }
+        Command::RollingRestartPause {} => {
+            let status = storcon_client
+                .dispatch::<(), RollingRestartStatus>(
+                    Method::PUT,
+                    "control/v1/rolling_restart/pause".to_string(),
+                    None,
+                )
+                .await?;
+            print_rolling_restart(&status);
+        }
+        Command::RollingRestartResume {} => {
+            let status = storcon_client
+                .dispatch::<(), RollingRestartStatus>(
+                    Method::PUT,
+                    "control/v1/rolling_restart/resume".to_string(),
+                    None,
+                )
+                .await?;
+            print_rolling_restart(&status);
+        }
+        Command::RollingRestartAbort {} => {
+            let status = storcon_client
+                .dispatch::<(), RollingRestartStatus>(
+                    Method::PUT,
+                    "control/v1/rolling_restart/abort".to_string(),
+                    None,
+                )
+                .await?;
+            print_rolling_restart(&status);
+        }
+        Command::Operations {
+            tenant_id,
+            node_id,
+            running,
+            limit,
+        } => {
+            let mut query = vec![format!("running={running}")];
+            if let Some(tenant_id) = tenant_id {
+                query.push(format!("tenant_id={tenant_id}"));
+            }
+            if let Some(node_id) = node_id {
+                query.push(format!("node_id={node_id}"));
+            }
+            if let Some(limit) = limit {
+                query.push(format!("limit={limit}"));
+            }
+
+            let operations = storcon_client
+                .dispatch::<(), Vec<OperationStatus>>(
+                    Method::GET,
+                    format!("control/v1/operations?{}", query.join("&")),
+                    None,
+                )
+                .await?;
+
+            let mut table = comfy_table::Table::new();
+            table.set_header([
+                "Id", "Kind", "State", "Tenant", "Node", "Progress", "Started", "Error",
+            ]);
+            for op in operations {
+                table.add_row([
+                    format!("{}", op.id),
+                    format!("{}", op.kind),
+                    format!("{}", op.state),
+                    op.tenant_id.map(|t| t.to_string()).unwrap_or_default(),
+                    op.node_id.map(|n| n.to_string()).unwrap_or_default(),
+                    op.progress
+                        .map(|p| format!("{}/{}", p.completed, p.total))
+                        .unwrap_or_default(),
+                    format!("{}", op.started_at),
+                    op.error.unwrap_or_default(),
+                ]);
+            }
+            println!("{table}");
+        }
+        Command::OperationWatch {
+            operation_id,
+            interval,
+        } => {
+            let interval = interval.map(|i| *i).unwrap_or(Duration::from_secs(2));
+            loop {
+                let op = storcon_client
+                    .dispatch::<(), OperationStatus>(
+                        Method::GET,
+                        format!("control/v1/operations/{operation_id}"),
+                        None,
+                    )
+                    .await?;
+
+                let progress = op
+                    .progress
+                    .map(|p| format!(" ({}/{})", p.completed, p.total))
+                    .unwrap_or_default();
+                println!("{} {} is {}{progress}", op.kind, op.id, op.state);
+
+                if op.state != OperationState::Running {
+                    if let Some(error) = op.error {
+                        println!("Error: {error}");
+                    }
+                    break;
+                }
+
+                tokio::time::sleep(interval).await;
+            }
+        }
+        Command::OperationCancel { operation_id } => {
+            storcon_client
+                .dispatch::<(), OperationStatus>(
+                    Method::DELETE,
+                    format!("control/v1/operations/{operation_id}"),
+                    None,
+                )
+                .await?;
+            println!("Cancellation of operation {operation_id} requested");
+        }
+        Command::NodeRestarted { node_id } => {
+            storcon_client
+                .dispatch::<(), RollingRestartStatus>(
+                    Method::PUT,
+                    format!("control/v1/node/{node_id}/restarted"),
+                    None,
+                )
+                .await?;
+            println!("Restart of node {node_id} reported");
+        }
+        Command::Audit {
+            tenant_id,
+            node_id,
+            limit,
+            verbose,
+        } => {
+            let mut query = Vec::new();
+            if let Some(tenant_id) = tenant_id {
+                query.push(format!("tenant_id={tenant_id}"));
+            }
+            if let Some(node_id) = node_id {
+                query.push(format!("node_id={node_id}"));
+            }
+            if let Some(limit) = limit {
+                query.push(format!("limit={limit}"));
+            }
+
+            let records = storcon_client
+                .dispatch::<(), Vec<AuditRecord>>(
+                    Method::GET,
+                    format!("control/v1/audit?{}", query.join("&")),
+                    None,
+                )
+                .await?;
+
+            if verbose {
+                for record in records {
+                    print_audit_record(&record);
+                }
+            } else {
+                let mut table = comfy_table::Table::new();
+                table.set_header([
+                    "Id", "Time", "Subject", "Method", "Path", "Tenant", "Node", "Status",
+                ]);
+                for record in records {
+                    table.add_row([
+                        format!("{}", record.id),
+                        format!("{}", record.recorded_at),
+                        record.subject.unwrap_or_default(),
+                        record.method,
+                        record.path,
+                        record.tenant_id.map(|t| t.to_string()).unwrap_or_default(),
+                        record.node_id.map(|n| n.to_string()).unwrap_or_default(),
+                        format!("{}", record.status),
+                    ]);
+                }
+                println!("{table}");
+            }
+        }
+        Command::ChaosStart {
+            interval,
+            batch_size,
+            weight,
+        } => {
+            let mut config = ChaosConfig::new(*interval);
+            if let Some(batch_size) = batch_size {
+                config.batch_size = batch_size;
+            }
+            config.weights = weight.into_iter().map(|w| w.0).collect();
+            let status = storcon_client
+                .dispatch::<ChaosConfig, ChaosStatus>(
+                    Method::PUT,
+                    "debug/v1/chaos".to_string(),
+                    Some(config),
+                )
+                .await?;
+            print_chaos_status(&status);
+        }
+        Command::ChaosStop {} => {
+            let status = storcon_client
+                .dispatch::<(), ChaosStatus>(Method::DELETE, "debug/v1/chaos".to_string(), None)
+                .await?;
+            print_chaos_status(&status);
+        }
+        Command::ChaosStatus {} => {
+            let status = storcon_client
+                .dispatch::<(), ChaosStatus>(Method::GET, "debug/v1/chaos".to_string(), None)
+                .await?;
+            print_chaos_status(&status);
+        }
     }
 
     Ok(())
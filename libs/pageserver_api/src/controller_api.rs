@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
@@ -7,7 +7,7 @@
 /// API (`/control/v1` prefix).  Implemented by the server
 /// in [`storage_controller::http`]
 use serde::{Deserialize, Serialize};
-use utils::id::{NodeId, TenantId};
+use utils::id::{NodeId, TenantId, TimelineId};
 
 use crate::models::PageserverUtilization;
 use crate::{
@@ -59,6 +59,11 @@ pub struct NodeRegisterRequest {
     pub listen_http_port: u16,
 
     pub availability_zone_id: AvailabilityZone,
+
+    /// Free-form labels describing the node, such as its hardware class or rack, which
+    /// tenants' [`PlacementConstraints`] may refer to.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -73,9 +78,116 @@ pub struct NodeConfigureRequest {
 pub struct TenantPolicyRequest {
     pub placement: Option<PlacementPolicy>,
     pub scheduling: Option<ShardSchedulingPolicy>,
+    pub split: Option<ShardSplitPolicy>,
+    pub constraints: Option<PlacementConstraints>,
+}
+
+/// Constraints on which nodes a tenant's shards may be placed, in terms of node labels.  These
+/// apply in addition to the [`PlacementPolicy`] and preferred AZ.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct PlacementConstraints {
+    /// Only place shards on nodes that have all of these labels.  Shards already on other
+    /// nodes are moved off them.
+    pub required_labels: BTreeMap<String, String>,
+    /// Prefer nodes with as many of these labels as possible, but use other nodes if needed.
+    pub preferred_labels: BTreeMap<String, String>,
+    /// Spread the shards of all tenants in the same group across nodes with different values of
+    /// a label.
+    pub spread: Option<SpreadConstraint>,
+}
+
+impl PlacementConstraints {
+    pub fn is_satisfied_by(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.required_labels
+            .iter()
+            .all(|(k, v)| labels.get(k) == Some(v))
+    }
+
+    /// How many of the preferred labels a node with these labels is missing
+    pub fn preferred_labels_missing(&self, labels: &BTreeMap<String, String>) -> usize {
+        self.preferred_labels
+            .iter()
+            .filter(|(k, v)| labels.get(k.as_str()) != Some(*v))
+            .count()
+    }
+
+    pub fn spread_group(&self) -> Option<&str> {
+        self.spread.as_ref().map(|s| s.group.as_str())
+    }
+}
+
+/// Anti-affinity between the tenants in a group: for example, `{"group": "customer-x", "label": "rack"}`
+/// avoids placing shards of customer-x's tenants in the same rack where other racks are available.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct SpreadConstraint {
+    pub group: String,
+    pub label: String,
+}
+
+/// Controls how the storage controller automatically splits a tenant's shards.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct ShardSplitPolicy {
+    /// Never split this tenant automatically.
+    pub disabled: bool,
+    /// Don't automatically split beyond this many shards.  The storage controller's own
+    /// maximum still applies.
+    pub max_shard_count: Option<u8>,
+    /// Stripe size when automatically splitting an unsharded tenant.  Sharded tenants keep
+    /// their stripe size.
+    pub stripe_size: Option<ShardStripeSize>,
+}
+
+/// Why a tenant was considered for automatic splitting.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitTrigger {
+    /// The largest logical size of a timeline exceeds the threshold
+    LogicalSize { size: u64, threshold: u64 },
+    /// The resident size of a shard exceeds the threshold
+    ResidentSize { size: u64, threshold: u64 },
+    /// The WAL ingest rate in bytes per second exceeds the threshold
+    WalIngestRate { rate: u64, threshold: u64 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoSplitOutcome {
+    /// The split was started
+    Split {
+        new_shard_count: u8,
+        new_stripe_size: Option<ShardStripeSize>,
+    },
+    /// The tenant's split policy disables automatic splitting
+    DisabledByPolicy,
+    /// The tenant already has as many shards as it may be split to
+    AtMaxShardCount { max_shard_count: u8 },
+    /// The tenant is splitting already, or its shards are not all attached
+    NotReady,
+    /// Too many splits are in progress on a node of the tenant
+    NodeConcurrencyLimit { node_id: NodeId },
+    /// Too many splits are in progress in an availability zone of the tenant
+    AzConcurrencyLimit { az: AvailabilityZone },
+}
+
+/// Explanation of the storage controller's decision about automatically splitting a tenant.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AutoSplitDecision {
+    pub tenant_id: TenantId,
+    pub shard_count: u8,
+    pub triggers: Vec<SplitTrigger>,
+    pub outcome: AutoSplitOutcome,
 }
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+/// Decisions of the most recent automatic split pass, for tenants which crossed a split threshold.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AutoSplitDecisionsResponse {
+    pub decided_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub decisions: Vec<AutoSplitDecision>,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
 pub struct AvailabilityZone(pub String);
 
 impl Display for AvailabilityZone {
@@ -84,6 +196,355 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+/// What tells the storage controller that a drained node has been restarted during a rolling
+/// restart.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartSignal {
+    /// The node re-attaches to the storage controller and passes heartbeats
+    #[default]
+    ReAttach,
+    /// Additionally wait for a deploy tool to report the restart via the API
+    External,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RollingRestartRequest {
+    /// Nodes to restart, in this order.  All nodes are restarted if empty.
+    #[serde(default)]
+    pub nodes: Vec<NodeId>,
+    /// How many nodes of an availability zone may be out of service at the same time
+    #[serde(default = "RollingRestartRequest::default_max_concurrent_per_az")]
+    pub max_concurrent_per_az: usize,
+    #[serde(default)]
+    pub restart_signal: RestartSignal,
+    /// A node which has not restarted this long after being drained fails the operation
+    #[serde(
+        default = "RollingRestartRequest::default_restart_timeout",
+        with = "humantime_serde"
+    )]
+    pub restart_timeout: Duration,
+}
+
+impl RollingRestartRequest {
+    fn default_max_concurrent_per_az() -> usize {
+        1
+    }
+
+    fn default_restart_timeout() -> Duration {
+        Duration::from_secs(30 * 60)
+    }
+}
+
+impl Default for RollingRestartRequest {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            max_concurrent_per_az: Self::default_max_concurrent_per_az(),
+            restart_signal: RestartSignal::default(),
+            restart_timeout: Self::default_restart_timeout(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RollingRestartState {
+    /// Nodes are being restarted
+    Running,
+    /// Nodes in progress are finished, but no further nodes are started
+    Paused,
+    /// A node failed: nodes in progress are finished, and the operation waits to be resumed or aborted
+    Failed,
+    /// The operation was aborted, and the nodes in progress returned to service
+    Aborted,
+    /// All nodes were restarted
+    Completed,
+}
+
+impl RollingRestartState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Aborted | Self::Completed)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RollingRestartPhase {
+    Pending,
+    Draining,
+    AwaitingRestart,
+    Filling,
+    Done,
+}
+
+impl RollingRestartPhase {
+    /// Is the node out of service for the rolling restart?
+    pub fn is_in_progress(&self) -> bool {
+        matches!(self, Self::Draining | Self::AwaitingRestart | Self::Filling)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RollingRestartNode {
+    pub node_id: NodeId,
+    pub availability_zone_id: AvailabilityZone,
+    pub phase: RollingRestartPhase,
+    pub phase_started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether the restart of the node was reported via the API
+    #[serde(default)]
+    pub restart_signalled: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RollingRestartStatus {
+    pub id: i64,
+    pub state: RollingRestartState,
+    pub max_concurrent_per_az: usize,
+    pub restart_signal: RestartSignal,
+    #[serde(with = "humantime_serde")]
+    pub restart_timeout: Duration,
+    pub nodes: Vec<RollingRestartNode>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Long-running actions of the storage controller, which are recorded as operations
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Debug,
+    strum_macros::EnumString,
+    strum_macros::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum OperationKind {
+    ShardSplit,
+    ShardMerge,
+    ShardMigrate,
+    TimelineDetachAncestor,
+    TenantImport,
+    NodeDrain,
+    NodeFill,
+    SafekeeperDrain,
+    RollingRestart,
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Debug,
+    strum_macros::EnumString,
+    strum_macros::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum OperationState {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub struct OperationProgress {
+    pub completed: u64,
+    pub total: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OperationStatus {
+    pub id: i64,
+    pub kind: OperationKind,
+    pub state: OperationState,
+    /// The tenant the operation acts on, if any
+    pub tenant_id: Option<TenantId>,
+    /// The pageserver or safekeeper the operation acts on, if any
+    pub node_id: Option<NodeId>,
+    pub progress: Option<OperationProgress>,
+    pub error: Option<String>,
+    /// Whether the operation may be cancelled through the operations API
+    pub cancellable: bool,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Response to requests which start an operation in the background
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OperationStartResponse {
+    pub operation_id: i64,
+}
+
+/// A mutating call to the storage controller API, as recorded in its audit log
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
+    /// The subject of the caller's token, if the controller authenticates requests
+    pub subject: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub tenant_id: Option<TenantId>,
+    pub node_id: Option<NodeId>,
+    /// HTTP status of the response
+    pub status: u16,
+    /// The body of the request, if it was JSON
+    pub request: Option<serde_json::Value>,
+    /// The state of the tenant and node before and after the call, as an [`AuditState`]
+    pub state_before: Option<serde_json::Value>,
+    pub state_after: Option<serde_json::Value>,
+}
+
+/// What the audit log records of the state of the tenant and node touched by a call
+#[derive(Serialize, Deserialize, Default)]
+pub struct AuditState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<TenantDescribeResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<NodeDescribeResponse>,
+}
+
+/// Request to plan moving the attached shards away from some nodes, without moving anything
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BulkMigratePlanRequest {
+    /// Nodes to move attached shards away from.  The shards are spread round robin over the
+    /// other active nodes which satisfy their placement constraints.
+    pub nodes: Vec<NodeId>,
+    /// Plan at most this many moves
+    #[serde(default)]
+    pub max_shards: Option<usize>,
+    /// Whether to ask the pageservers for the sizes of the shards to move.  Plans which are
+    /// only carried out, rather than shown, don't need them.
+    #[serde(default = "BulkMigratePlanRequest::default_sizes")]
+    pub sizes: bool,
+}
+
+impl BulkMigratePlanRequest {
+    fn default_sizes() -> bool {
+        true
+    }
+}
+
+/// A move of a shard's attachment which an operation would make
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlannedShardMove {
+    pub tenant_shard_id: TenantShardId,
+    pub from: NodeId,
+    pub to: NodeId,
+    /// Whether the destination already holds a secondary location of the shard
+    pub to_secondary: bool,
+    /// Size of the shard's layers on the disk of its attached location, if known
+    pub resident_size: Option<u64>,
+    /// Size of the shard's layers in remote storage, if known
+    pub physical_size: Option<u64>,
+    /// How much the destination has to download before it is warm, if known
+    pub download_size: Option<u64>,
+}
+
+/// A shard which an operation would leave where it is
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SkippedShardMove {
+    pub tenant_shard_id: TenantShardId,
+    pub reason: String,
+}
+
+/// What a drain, fill or bulk migration would do if it started now.  The totals only count
+/// moves whose sizes are known.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MigrationPlan {
+    pub moves: Vec<PlannedShardMove>,
+    pub skipped: Vec<SkippedShardMove>,
+    pub resident_size: u64,
+    pub download_size: u64,
+    /// How long the destinations would take to download what they need, each downloading at
+    /// the bandwidth given in the request
+    #[serde(with = "humantime_serde")]
+    pub estimated_warmup: Duration,
+}
+
+/// A kind of fault which the storage controller's chaos injector may inject
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Debug,
+    strum_macros::EnumString,
+    strum_macros::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ChaosScenario {
+    /// Migrate shards to one of their secondary locations
+    MigrateToSecondary,
+    /// Migrate shards to a node which holds no location of them, skipping secondary warmup
+    MigrateToNonSecondary,
+    /// Mark a node offline, as if it had missed heartbeats.  The next successful heartbeat
+    /// brings it back.
+    NodeOffline,
+    /// Make the next reconcile of shards fail
+    ReconcileFailure,
+    /// Make the next reconcile of shards sleep before doing anything
+    ReconcileDelay,
+    /// Make the next compute notification of shards fail
+    ComputeHookFailure,
+    /// Make the next shard split fail at a random phase, after it has started
+    SplitKill,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChaosConfig {
+    /// How often to inject a fault
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// How many shards to interfere with each time, for scenarios which act on shards
+    #[serde(default = "ChaosConfig::default_batch_size")]
+    pub batch_size: usize,
+    /// Each time, a scenario is picked at random with a probability proportional to its weight
+    pub weights: BTreeMap<ChaosScenario, u32>,
+}
+
+impl ChaosConfig {
+    fn default_batch_size() -> usize {
+        128
+    }
+
+    /// Only migrate shards to their secondaries, as the chaos injector used to
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            batch_size: Self::default_batch_size(),
+            weights: BTreeMap::from([(ChaosScenario::MigrateToSecondary, 1)]),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChaosStatus {
+    /// The configuration of the running chaos injector, if any
+    pub config: Option<ChaosConfig>,
+    /// How many times each scenario was picked since the controller started
+    pub injected: BTreeMap<ChaosScenario, u64>,
+    /// Whether the next shard split will be killed
+    pub split_kill_armed: bool,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ShardsPreferredAzsRequest {
     #[serde(flatten)]
@@ -120,6 +581,10 @@ pub struct TenantDescribeResponse {
     pub stripe_size: ShardStripeSize,
     pub policy: PlacementPolicy,
     pub config: TenantConfig,
+    #[serde(default)]
+    pub split_policy: ShardSplitPolicy,
+    #[serde(default)]
+    pub placement_constraints: PlacementConstraints,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -149,6 +614,9 @@ pub struct NodeDescribeResponse {
 
     pub listen_pg_addr: String,
     pub listen_pg_port: u16,
+
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -309,6 +777,52 @@ fn from(value: NodeSchedulingPolicy) -> String {
     }
 }
 
+/// Scheduling policy of a safekeeper, analogous to [`NodeSchedulingPolicy`] for pageservers.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SkSchedulingPolicy {
+    /// New timelines may be placed on this safekeeper.
+    Active,
+    /// Existing timelines stay, but no new timelines are placed here.
+    Pause,
+    /// Timelines are being migrated away from this safekeeper.
+    Draining,
+}
+
+impl FromStr for SkSchedulingPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Self::Active),
+            "pause" => Ok(Self::Pause),
+            "draining" => Ok(Self::Draining),
+            _ => Err(anyhow::anyhow!("Unknown scheduling state '{s}'")),
+        }
+    }
+}
+
+impl From<SkSchedulingPolicy> for String {
+    fn from(value: SkSchedulingPolicy) -> String {
+        use SkSchedulingPolicy::*;
+        match value {
+            Active => "active",
+            Pause => "pause",
+            Draining => "draining",
+        }
+        .to_string()
+    }
+}
+
+/// The set of safekeepers a timeline is placed on, as persisted by the storage controller.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimelineSafekeepersResponse {
+    pub tenant_id: TenantId,
+    pub timeline_id: TimelineId,
+    /// Incremented every time the safekeeper set changes.
+    pub generation: u32,
+    pub safekeepers: Vec<NodeId>,
+}
+
 /// Controls how tenant shards are mapped to locations on pageservers, e.g. whether
 /// to create secondary locations.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -328,6 +842,18 @@ pub enum PlacementPolicy {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TenantShardMigrateResponse {}
 
+/// Request to merge a tenant's shards into fewer shards.  `new_shard_count` must divide the
+/// tenant's current shard count: a count of 1 merges the tenant back into an unsharded tenant.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantMergeRequest {
+    pub new_shard_count: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantMergeResponse {
+    pub new_shards: Vec<TenantShardId>,
+}
+
 /// Metadata health record posted from scrubber.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MetadataHealthRecord {
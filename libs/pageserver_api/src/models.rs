@@ -242,6 +242,32 @@ pub struct TenantShardSplitResponse {
     pub new_shards: Vec<TenantShardId>,
 }
 
+/// A source shard of a shard merge, and the generation it is attached in.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TenantShardMergeSource {
+    pub tenant_shard_id: TenantShardId,
+    pub generation: u32,
+}
+
+/// Create a merged shard from its source shards. This is sent to the pageserver holding the
+/// source shard with the same shard number as the merged shard, after all the sources have
+/// been prepared for the merge.
+#[derive(Serialize, Deserialize)]
+pub struct TenantShardMergeRequest {
+    pub new_shard_count: u8,
+
+    /// The generation to attach the merged shard in
+    pub generation: u32,
+
+    /// All the source shards of the merged shard
+    pub sources: Vec<TenantShardMergeSource>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TenantShardMergeResponse {
+    pub new_shard: TenantShardId,
+}
+
 /// Parameters that apply to all shards in a tenant.  Used during tenant creation.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
@@ -927,6 +953,7 @@ pub struct TenantScanRemoteStorageResponse {
 pub enum TenantSorting {
     ResidentSize,
     MaxLogicalSize,
+    WalIngestRate,
 }
 
 impl Default for TenantSorting {
@@ -964,6 +991,11 @@ pub struct TopTenantShardItem {
 
     /// The largest logical size of a timeline within this tenant
     pub max_logical_size: u64,
+
+    /// Bytes of WAL per second ingested by all timelines within this tenant, averaged over
+    /// at least a minute
+    #[serde(default)]
+    pub wal_ingest_rate: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
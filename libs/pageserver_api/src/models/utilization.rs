@@ -37,6 +37,15 @@ pub struct PageserverUtilization {
     #[serde(default)]
     pub max_shard_count: u32,
 
+    /// Resident size of the tenant shards on this pageserver: unlike `disk_wanted_bytes`, this
+    /// reflects how much of their data is actually in use (not evicted).
+    #[serde(serialize_with = "ser_saturating_u63", default)]
+    pub resident_bytes: u64,
+
+    /// Bytes of WAL per second ingested by the tenant shards attached to this pageserver.
+    #[serde(serialize_with = "ser_saturating_u63", default)]
+    pub wal_ingest_bytes_per_sec: u64,
+
     /// Cached result of [`Self::score`]
     pub utilization_score: Option<u64>,
 
@@ -76,6 +85,52 @@ pub fn score(&self) -> RawScore {
         std::cmp::max(disk_utilization_score, shard_utilization_score)
     }
 
+    /// Like [`Self::score`], but the load of the node is a weighted average of its wanted disk space,
+    /// its resident size and its WAL ingest rate, rather than only the wanted disk space.  The default
+    /// weights give the same result as [`Self::score`].
+    pub fn weighted_score(&self, weights: &UtilizationWeights) -> RawScore {
+        let shard_utilization_score =
+            Self::fraction(self.shard_count as u64, self.max_shard_count as u64);
+
+        let total_weight = weights.disk_wanted + weights.resident + weights.wal_ingest;
+        if total_weight == 0 {
+            return shard_utilization_score;
+        }
+
+        let disk_usable_capacity = ((self.disk_usage_bytes + self.free_space_bytes)
+            * self.disk_usable_pct.get() as u64)
+            / 100;
+        let load_score = (weights.disk_wanted
+            * Self::fraction(self.disk_wanted_bytes, disk_usable_capacity)
+            + weights.resident * Self::fraction(self.resident_bytes, disk_usable_capacity)
+            + weights.wal_ingest
+                * Self::fraction(self.wal_ingest_bytes_per_sec, weights.wal_ingest_capacity))
+            / total_weight;
+
+        std::cmp::max(load_score, shard_utilization_score)
+    }
+
+    /// `numerator / denominator` as a fraction of [`Self::UTILIZATION_FULL`], saturating rather
+    /// than overflowing for very large inputs, and treating a zero denominator as full.
+    fn fraction(numerator: u64, denominator: u64) -> RawScore {
+        if denominator == 0 {
+            return Self::UTILIZATION_FULL;
+        }
+        let f = numerator as u128 * Self::UTILIZATION_FULL as u128 / denominator as u128;
+        // Cap at a multiple of full which is far beyond overloaded, so that weighted sums cannot overflow
+        std::cmp::min(f, 1000 * Self::UTILIZATION_FULL as u128) as RawScore
+    }
+
+    /// What fraction of the disk is used, as a percentage.  This is physical usage, unlike the
+    /// wanted bytes that the score is based on, so it includes anything else on the disk.
+    pub fn disk_usage_pct(&self) -> u64 {
+        let total = self.disk_usage_bytes + self.free_space_bytes;
+        if total == 0 {
+            return 0;
+        }
+        self.disk_usage_bytes * 100 / total
+    }
+
     pub fn cached_score(&mut self) -> RawScore {
         match self.utilization_score {
             None => {
@@ -123,12 +178,36 @@ pub fn full() -> Self {
             disk_usable_pct: Percent::new(100).unwrap(),
             shard_count: 1,
             max_shard_count: 1,
+            resident_bytes: 1,
+            wal_ingest_bytes_per_sec: 0,
             utilization_score: Some(Self::UTILIZATION_FULL),
             captured_at: serde_system_time::SystemTime(SystemTime::now()),
         }
     }
 }
 
+/// Relative importance of the components of a [`PageserverUtilization`], for
+/// [`PageserverUtilization::weighted_score`].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtilizationWeights {
+    pub disk_wanted: u64,
+    pub resident: u64,
+    pub wal_ingest: u64,
+    /// WAL ingest rate, in bytes per second, at which a node is considered fully utilized
+    pub wal_ingest_capacity: u64,
+}
+
+impl Default for UtilizationWeights {
+    fn default() -> Self {
+        Self {
+            disk_wanted: 1,
+            resident: 0,
+            wal_ingest: 0,
+            wal_ingest_capacity: 256 * 1024 * 1024,
+        }
+    }
+}
+
 /// Test helper
 pub mod test_utilization {
     use super::PageserverUtilization;
@@ -154,6 +233,8 @@ pub fn simple(shard_count: u32, disk_wanted_bytes: u64) -> PageserverUtilization
             disk_usable_pct: Percent::new(100).unwrap(),
             shard_count,
             max_shard_count: TEST_SHARDS_MAX,
+            resident_bytes: disk_wanted_bytes,
+            wal_ingest_bytes_per_sec: 0,
             utilization_score: None,
             captured_at: serde_system_time::SystemTime(SystemTime::now()),
         }
@@ -190,6 +271,8 @@ fn u64_max_is_serialized_as_u63_max() {
             disk_usable_pct: Percent::new(90).unwrap(),
             shard_count: 100,
             max_shard_count: 200,
+            resident_bytes: u64::MAX,
+            wal_ingest_bytes_per_sec: 0,
             captured_at: serde_system_time::SystemTime(
                 std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1708509779),
             ),
@@ -197,8 +280,50 @@ fn u64_max_is_serialized_as_u63_max() {
 
         let s = serde_json::to_string(&doc).unwrap();
 
-        let expected = "{\"disk_usage_bytes\":9223372036854775807,\"free_space_bytes\":0,\"disk_wanted_bytes\":9223372036854775807,\"disk_usable_pct\":90,\"shard_count\":100,\"max_shard_count\":200,\"utilization_score\":13,\"captured_at\":\"2024-02-21T10:02:59.000Z\"}";
+        let expected = "{\"disk_usage_bytes\":9223372036854775807,\"free_space_bytes\":0,\"disk_wanted_bytes\":9223372036854775807,\"disk_usable_pct\":90,\"shard_count\":100,\"max_shard_count\":200,\"resident_bytes\":9223372036854775807,\"wal_ingest_bytes_per_sec\":0,\"utilization_score\":13,\"captured_at\":\"2024-02-21T10:02:59.000Z\"}";
 
         assert_eq!(s, expected);
     }
+
+    #[test]
+    fn weighted_score() {
+        const GIB: u64 = 1024 * 1024 * 1024;
+
+        // The default weights reproduce the unweighted score
+        let u = test_utilization::simple(10, 100 * GIB);
+        assert_eq!(u.weighted_score(&UtilizationWeights::default()), u.score());
+
+        // Nodes that only differ in resident size are only told apart by a resident weight
+        let mut cold = test_utilization::simple(10, 100 * GIB);
+        cold.resident_bytes = 10 * GIB;
+        let hot = test_utilization::simple(10, 100 * GIB);
+        let weights = UtilizationWeights {
+            disk_wanted: 1,
+            resident: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            cold.weighted_score(&UtilizationWeights::default()),
+            hot.weighted_score(&UtilizationWeights::default())
+        );
+        assert!(cold.weighted_score(&weights) < hot.weighted_score(&weights));
+
+        // Ingest is scored against the configured capacity
+        let mut ingesting = test_utilization::simple(10, 0);
+        ingesting.wal_ingest_bytes_per_sec = 50;
+        let weights = UtilizationWeights {
+            disk_wanted: 0,
+            resident: 0,
+            wal_ingest: 1,
+            wal_ingest_capacity: 100,
+        };
+        assert_eq!(
+            ingesting.weighted_score(&weights),
+            PageserverUtilization::UTILIZATION_FULL / 2
+        );
+        ingesting.wal_ingest_bytes_per_sec = 400;
+        assert!(PageserverUtilization::is_overloaded(
+            ingesting.weighted_score(&weights)
+        ));
+    }
 }
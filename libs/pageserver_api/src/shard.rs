@@ -214,7 +214,7 @@ pub fn is_shard_zero(&self) -> bool {
 
 /// Whether this key is always held on shard 0 (e.g. shard 0 holds all SLRU keys
 /// in order to be able to serve basebackup requests without peer communication).
-fn key_is_shard0(key: &Key) -> bool {
+pub fn key_is_shard0(key: &Key) -> bool {
     // To decide what to shard out to shards >0, we apply a simple rule that only
     // relation pages are distributed to shards other than shard zero. Everything else gets
     // stored on shard 0.  This guarantees that shard 0 can independently serve basebackup
@@ -487,6 +487,36 @@ fn shard_mapping() {
         assert_eq!(shard, ShardNumber(8));
     }
 
+    #[test]
+    fn shard_index_contains() {
+        let index = |number, count| ShardIndex::new(ShardNumber(number), ShardCount(count));
+
+        // A shard contains itself, and unsharded/single-shard tenants contain everything
+        assert!(index(1, 4).contains(&index(1, 4)));
+        assert!(ShardIndex::unsharded().contains(&index(3, 8)));
+        assert!(index(0, 1).contains(&index(5, 8)));
+
+        // Shard 1 of 2 contains the odd numbered shards of 8, and none of the even ones
+        assert!(index(1, 2).contains(&index(1, 8)));
+        assert!(index(1, 2).contains(&index(7, 8)));
+        assert!(!index(1, 2).contains(&index(2, 8)));
+
+        // Containment only goes from lower to higher shard counts, along split lines
+        assert!(!index(1, 8).contains(&index(1, 2)));
+        assert!(!index(0, 4).contains(&index(0, 6)));
+
+        // Consistent with the children produced by a split
+        let tenant_id = TenantId::generate();
+        let parent = TenantShardId {
+            tenant_id,
+            shard_number: ShardNumber(2),
+            shard_count: ShardCount(4),
+        };
+        for child in parent.split(ShardCount(16)) {
+            assert!(parent.to_index().contains(&child.to_index()));
+        }
+    }
+
     #[test]
     fn shard_id_split() {
         let tenant_id = TenantId::generate();
@@ -73,3 +73,25 @@ pub struct TimelineTermBumpResponse {
     pub previous_term: u64,
     pub current_term: u64,
 }
+
+/// pull_timeline request body.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PullTimelineRequest {
+    pub tenant_id: TenantId,
+    pub timeline_id: TimelineId,
+    pub http_hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PullTimelineResponse {
+    // Donor safekeeper host
+    pub safekeeper_host: String,
+    // TODO: add more fields?
+}
+
+/// Request to override the WAL ingest rate limit of a tenant.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TenantWalIngestRateLimitRequest {
+    /// Limit in bytes per second, 0 for unlimited. None resets the tenant to the default.
+    pub bytes_per_sec: Option<u64>,
+}
@@ -48,11 +48,19 @@ pub struct Claims {
     #[serde(default)]
     pub tenant_id: Option<TenantId>,
     pub scope: Scope,
+    /// Who the token was issued to.  Recorded by services which keep an audit log of the
+    /// requests made to them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
 }
 
 impl Claims {
     pub fn new(tenant_id: Option<TenantId>, scope: Scope) -> Self {
-        Self { tenant_id, scope }
+        Self {
+            tenant_id,
+            scope,
+            sub: None,
+        }
     }
 }
 
@@ -203,6 +211,7 @@ fn test_decode() {
         let expected_claims = Claims {
             tenant_id: Some(TenantId::from_str("3d1f7595b468230304e0b73cecbcb081").unwrap()),
             scope: Scope::Tenant,
+            sub: None,
         };
 
         // A test token containing the following payload, signed using TEST_PRIV_KEY_ED25519:
@@ -229,6 +238,7 @@ fn test_encode() {
         let claims = Claims {
             tenant_id: Some(TenantId::from_str("3d1f7595b468230304e0b73cecbcb081").unwrap()),
             scope: Scope::Tenant,
+            sub: None,
         };
 
         let encoded = encode_from_key_file(&claims, TEST_PRIV_KEY_ED25519).unwrap();
@@ -239,4 +249,21 @@ fn test_encode() {
 
         assert_eq!(decoded.claims, claims);
     }
+
+    #[test]
+    fn test_subject() {
+        let claims = Claims {
+            tenant_id: None,
+            scope: Scope::Admin,
+            sub: Some("alice".to_string()),
+        };
+
+        let encoded = encode_from_key_file(&claims, TEST_PRIV_KEY_ED25519).unwrap();
+        let auth = JwtAuth::new(vec![DecodingKey::from_ed_pem(TEST_PUB_KEY_ED25519).unwrap()]);
+        assert_eq!(auth.decode(&encoded).unwrap().claims, claims);
+
+        // Tokens without a subject do not mention it
+        let claims = Claims::new(None, Scope::Admin);
+        assert!(!serde_json::to_string(&claims).unwrap().contains("sub"));
+    }
 }
@@ -254,6 +254,15 @@ pub fn is_unsharded(&self) -> bool {
         self.shard_number == ShardNumber(0) && self.shard_count == ShardCount(0)
     }
 
+    /// Whether every key owned by `other` is also owned by this shard: true if `other` is this
+    /// shard itself, or one of the shards that this shard would be split into.  Shard merges rely
+    /// on this to know which source shards a merged shard takes its layers from.
+    pub fn contains(&self, other: &ShardIndex) -> bool {
+        let count = std::cmp::max(self.shard_count.0, 1);
+        let other_count = std::cmp::max(other.shard_count.0, 1);
+        other_count % count == 0 && other.shard_number.0 % count == self.shard_number.0
+    }
+
     /// For use in constructing remote storage paths: concatenate this with a TenantId
     /// to get a fully qualified TenantShardId.
     ///
@@ -498,6 +498,34 @@ pub async fn tenant_shard_split(
             .map_err(Error::ReceiveBody)
     }
 
+    pub async fn tenant_shard_merge_prepare(&self, tenant_shard_id: TenantShardId) -> Result<()> {
+        let uri = format!(
+            "{}/v1/tenant/{}/shard_merge_prepare",
+            self.mgmt_api_endpoint, tenant_shard_id
+        );
+        self.request(Method::PUT, &uri, ())
+            .await?
+            .json()
+            .await
+            .map_err(Error::ReceiveBody)
+    }
+
+    pub async fn tenant_shard_merge(
+        &self,
+        tenant_shard_id: TenantShardId,
+        req: TenantShardMergeRequest,
+    ) -> Result<TenantShardMergeResponse> {
+        let uri = format!(
+            "{}/v1/tenant/{}/shard_merge",
+            self.mgmt_api_endpoint, tenant_shard_id
+        );
+        self.request(Method::PUT, &uri, req)
+            .await?
+            .json()
+            .await
+            .map_err(Error::ReceiveBody)
+    }
+
     pub async fn timeline_list(
         &self,
         tenant_shard_id: &TenantShardId,
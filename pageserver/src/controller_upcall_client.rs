@@ -163,6 +163,19 @@ async fn re_attach(
                         panic!("Availablity zone id could not be inferred from metadata.json or pageserver config");
                     }
 
+                    // Optional labels for the storage controller's placement constraints, e.g. {"rack": "r1"}
+                    let labels = m
+                        .other
+                        .get("labels")
+                        .and_then(|jv| jv.as_object())
+                        .map(|labels| {
+                            labels
+                                .iter()
+                                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_owned())))
+                                .collect()
+                        })
+                        .unwrap_or_default();
+
                     Some(NodeRegisterRequest {
                         node_id: conf.id,
                         listen_pg_addr: m.postgres_host,
@@ -170,6 +183,7 @@ async fn re_attach(
                         listen_http_addr: m.http_host,
                         listen_http_port: m.http_port,
                         availability_zone_id: az_id.expect("Checked above"),
+                        labels,
                     })
                 }
                 Err(e) => {
@@ -34,6 +34,8 @@
 use pageserver_api::models::TenantScanRemoteStorageResponse;
 use pageserver_api::models::TenantScanRemoteStorageShard;
 use pageserver_api::models::TenantShardLocation;
+use pageserver_api::models::TenantShardMergeRequest;
+use pageserver_api::models::TenantShardMergeResponse;
 use pageserver_api::models::TenantShardSplitRequest;
 use pageserver_api::models::TenantShardSplitResponse;
 use pageserver_api::models::TenantSorting;
@@ -1177,6 +1179,63 @@ async fn tenant_shard_split_handler(
     json_response(StatusCode::OK, TenantShardSplitResponse { new_shards })
 }
 
+async fn tenant_shard_merge_prepare_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let state = get_state(&request);
+    let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Warn);
+
+    let tenant = state
+        .tenant_manager
+        .get_attached_tenant_shard(tenant_shard_id)?;
+    tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
+
+    state
+        .tenant_manager
+        .shard_merge_prepare(tenant, &ctx)
+        .await
+        .map_err(ApiError::InternalServerError)?;
+
+    json_response(StatusCode::OK, ())
+}
+
+async fn tenant_shard_merge_handler(
+    mut request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let req: TenantShardMergeRequest = json_request(&mut request).await?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let state = get_state(&request);
+    let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Warn);
+
+    let tenant = state
+        .tenant_manager
+        .get_attached_tenant_shard(tenant_shard_id)?;
+    tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
+
+    let sources = req
+        .sources
+        .into_iter()
+        .map(|s| (s.tenant_shard_id, Generation::new(s.generation)))
+        .collect();
+    let new_shard = state
+        .tenant_manager
+        .shard_merge(
+            tenant,
+            ShardCount::new(req.new_shard_count),
+            Generation::new(req.generation),
+            sources,
+            &ctx,
+        )
+        .await
+        .map_err(ApiError::InternalServerError)?;
+
+    json_response(StatusCode::OK, TenantShardMergeResponse { new_shard })
+}
+
 async fn layer_map_info_handler(
     request: Request<Body>,
     _cancel: CancellationToken,
@@ -2531,6 +2590,7 @@ fn get_size_metric(sizes: &TopTenantShardItem, order_by: &TenantSorting) -> u64
         match order_by {
             TenantSorting::ResidentSize => sizes.resident_size,
             TenantSorting::MaxLogicalSize => sizes.max_logical_size,
+            TenantSorting::WalIngestRate => sizes.wal_ingest_rate,
         }
     }
 
@@ -2950,6 +3010,12 @@ pub fn make_router(
         .put("/v1/tenant/:tenant_shard_id/shard_split", |r| {
             api_handler(r, tenant_shard_split_handler)
         })
+        .put("/v1/tenant/:tenant_shard_id/shard_merge_prepare", |r| {
+            api_handler(r, tenant_shard_merge_prepare_handler)
+        })
+        .put("/v1/tenant/:tenant_shard_id/shard_merge", |r| {
+            api_handler(r, tenant_shard_merge_handler)
+        })
         .get("/v1/tenant/:tenant_shard_id/config", |r| {
             api_handler(r, get_tenant_config_handler)
         })
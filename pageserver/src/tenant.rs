@@ -66,6 +66,7 @@
 use self::metadata::TimelineMetadata;
 use self::mgr::GetActiveTenantError;
 use self::mgr::GetTenantError;
+use self::remote_timeline_client::download::download_index_part;
 use self::remote_timeline_client::upload::upload_index_part;
 use self::remote_timeline_client::RemoteTimelineClient;
 use self::timeline::uninit::TimelineCreateGuard;
@@ -355,6 +356,19 @@ pub struct Tenant {
     pub(crate) gc_block: gc_block::GcBlock,
 
     l0_flush_global_state: L0FlushGlobalState,
+
+    /// Previous sample of the ingested WAL, to report the ingest rate in [`Tenant::get_sizes`].
+    wal_ingest_sample: std::sync::Mutex<Option<WalIngestSample>>,
+}
+
+/// How long to average the WAL ingest rate over, at least.
+const WAL_INGEST_SAMPLE_PERIOD: Duration = Duration::from_secs(60);
+
+struct WalIngestSample {
+    at: Instant,
+    last_record_lsns: HashMap<TimelineId, Lsn>,
+    /// Bytes per second since the sample before this one.
+    rate: u64,
 }
 
 impl std::fmt::Debug for Tenant {
@@ -2725,15 +2739,92 @@ pub(crate) async fn split_prepare(
         Ok(())
     }
 
+    /// Prepare this shard to be one of the sources of a shard merge: flush and upload all its
+    /// layers, then stop remote storage operations so that the layers referenced by the uploaded
+    /// index are not deleted before the merged shard has taken them over.
+    pub(crate) async fn merge_prepare(&self) -> anyhow::Result<()> {
+        let timelines = self.timelines.lock().unwrap().clone();
+        for timeline in timelines.values() {
+            // Flushing is not required for correctness (the merged shard re-ingests WAL above
+            // what is in the index), but it keeps the amount of WAL to re-ingest small.
+            tracing::info!(timeline_id=%timeline.timeline_id, "Flushing layers");
+            timeline.freeze_and_flush().await?;
+
+            tracing::info!(timeline_id=%timeline.timeline_id, "Uploading index");
+            timeline
+                .remote_client
+                .schedule_index_upload_for_file_changes()?;
+            timeline.remote_client.wait_completion().await?;
+
+            tracing::info!(timeline_id=%timeline.timeline_id, "Shutting down remote storage client");
+            timeline.remote_client.shutdown().await;
+        }
+
+        Ok(())
+    }
+
+    /// Write the index of a merged shard, composed from the indices of its source shards.  This is
+    /// called on the source shard whose shard number is the same as the merged shard's, after
+    /// [`Self::merge_prepare`] has run on all the sources.
+    pub(crate) async fn merge_create(
+        &self,
+        merged_shard: &TenantShardId,
+        merged_generation: Generation,
+        sources: &[(TenantShardId, Generation)],
+    ) -> anyhow::Result<()> {
+        let timeline_ids = self
+            .timelines
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        for timeline_id in timeline_ids {
+            let mut source_indices = Vec::with_capacity(sources.len());
+            for (source_shard, source_generation) in sources {
+                tracing::info!(%timeline_id, "Downloading index_part from source {}", source_shard.to_index());
+                let (index_part, _) = download_index_part(
+                    &self.remote_storage,
+                    source_shard,
+                    &timeline_id,
+                    *source_generation,
+                    &self.cancel,
+                )
+                .instrument(info_span!("download_index_part", tenant_id=%source_shard.tenant_id, shard_id=%source_shard.shard_slug(), %timeline_id))
+                .await?;
+                source_indices.push(index_part);
+            }
+
+            let index_part = IndexPart::merge(&source_indices)?;
+
+            tracing::info!(%timeline_id, "Uploading index_part for merged shard {}", merged_shard.to_index());
+            upload_index_part(
+                &self.remote_storage,
+                merged_shard,
+                &timeline_id,
+                merged_generation,
+                &index_part,
+                &self.cancel,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn get_sizes(&self) -> TopTenantShardItem {
         let mut result = TopTenantShardItem {
             id: self.tenant_shard_id,
             resident_size: 0,
             physical_size: 0,
             max_logical_size: 0,
+            wal_ingest_rate: 0,
         };
 
+        let mut last_record_lsns = HashMap::new();
         for timeline in self.timelines.lock().unwrap().values() {
+            last_record_lsns.insert(timeline.timeline_id, timeline.get_last_record_lsn());
+
             result.resident_size += timeline.metrics.resident_physical_size_gauge.get();
 
             result.physical_size += timeline
@@ -2746,9 +2837,40 @@ pub(crate) fn get_sizes(&self) -> TopTenantShardItem {
                 timeline.metrics.current_logical_size_gauge.get(),
             );
         }
+        result.wal_ingest_rate = self.sample_wal_ingest_rate(last_record_lsns);
 
         result
     }
+
+    /// Bytes of WAL ingested per second, by all timelines together. Samples are taken
+    /// at most every [`WAL_INGEST_SAMPLE_PERIOD`], so this is the average over at least that period.
+    fn sample_wal_ingest_rate(&self, last_record_lsns: HashMap<TimelineId, Lsn>) -> u64 {
+        let now = Instant::now();
+        let mut sample = self.wal_ingest_sample.lock().unwrap();
+        if let Some(prev) = sample.as_ref() {
+            if now.duration_since(prev.at) < WAL_INGEST_SAMPLE_PERIOD {
+                return prev.rate;
+            }
+        }
+        let rate = sample.as_ref().map_or(0, |prev| {
+            // Timelines created since the previous sample start at their ancestor's LSN,
+            // so only count what they ingest from the next sample on.
+            let ingested: u64 = last_record_lsns
+                .iter()
+                .filter_map(|(timeline_id, lsn)| {
+                    let prev_lsn = prev.last_record_lsns.get(timeline_id)?;
+                    Some(lsn.0.saturating_sub(prev_lsn.0))
+                })
+                .sum();
+            (ingested as f64 / now.duration_since(prev.at).as_secs_f64()) as u64
+        });
+        *sample = Some(WalIngestSample {
+            at: now,
+            last_record_lsns,
+            rate,
+        });
+        rate
+    }
 }
 
 /// Given a Vec of timelines and their ancestors (timeline_id, ancestor_id),
@@ -3119,6 +3241,7 @@ fn new(
             ongoing_timeline_detach: std::sync::Mutex::default(),
             gc_block: Default::default(),
             l0_flush_global_state,
+            wal_ingest_sample: std::sync::Mutex::new(None),
         }
     }
 
@@ -52,8 +52,9 @@
 use crate::tenant::storage_layer::InMemoryLayer;
 use anyhow::Result;
 use pageserver_api::keyspace::{KeySpace, KeySpaceAccum};
+use pageserver_api::shard::{key_is_shard0, ShardIdentity, ShardIndex, ShardNumber};
 use range_set_blaze::{CheckSortedDisjoint, RangeSetBlaze};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::iter::Peekable;
 use std::ops::Range;
 use std::sync::Arc;
@@ -62,7 +63,7 @@
 use historic_layer_coverage::BufferedHistoricLayerCoverage;
 pub use historic_layer_coverage::LayerKey;
 
-use super::storage_layer::{LayerVisibilityHint, PersistentLayerDesc};
+use super::storage_layer::{LayerVisibilityHint, PersistentLayerDesc, PersistentLayerKey};
 
 ///
 /// LayerMap tracks what layers exist on a timeline.
@@ -94,6 +95,337 @@ pub struct LayerMap {
     /// L0 layers have key range Key::MIN..Key::MAX, and locating them using R-Tree search is very inefficient.
     /// So L0 layers are held in l0_delta_layers vector, in addition to the R-tree.
     l0_delta_layers: Vec<Arc<PersistentLayerDesc>>,
+
+    /// For shards created by a shard merge, the index of historic layers for each source
+    /// shard. See [`MergedLayerCoverage`].
+    merged: Option<MergedLayerCoverage>,
+}
+
+/// The source shards of a shard created by merging shards, as found from the layers it
+/// inherited from them.
+///
+/// Each of those layers only holds the keys of the source that wrote it, and the sources had
+/// written layers up to different LSNs: the merged shard resumes ingesting WAL from the lowest
+/// of them, and must not ingest the WAL of a source's keys again below the end of its layers.
+#[derive(Clone, Debug)]
+pub struct MergeSources {
+    /// Identity of the source shard with the same shard number as this one. Keys that
+    /// aren't distributed by hash are read from it.
+    primary: ShardIdentity,
+
+    /// For each source shard, the last LSN of the layers it wrote, if any are left.
+    floors: BTreeMap<ShardNumber, Option<Lsn>>,
+}
+
+impl MergeSources {
+    /// Returns `None` if none of the layers were written by the source shards of a merge.
+    pub fn new<'a>(
+        shard_identity: &ShardIdentity,
+        layers: impl Iterator<Item = &'a PersistentLayerDesc>,
+    ) -> Option<Self> {
+        let partial_layers = layers.filter(|desc| desc.is_partial()).collect::<Vec<_>>();
+        let source_count = partial_layers
+            .iter()
+            .map(|desc| desc.shard.shard_count)
+            .max()?;
+
+        let own = shard_identity.shard_index();
+        let floors = (0..source_count.count())
+            .map(|number| ShardIndex::new(ShardNumber(number), source_count))
+            .filter(|source| own.contains(source))
+            .map(|source| {
+                let floor = partial_layers
+                    .iter()
+                    .filter(|desc| desc.shard.contains(&source))
+                    .map(|desc| Lsn(desc.lsn_range.end.0 - 1))
+                    .max();
+                (source.shard_number, floor)
+            })
+            .collect();
+
+        let primary = ShardIdentity::new(
+            shard_identity.number,
+            source_count,
+            shard_identity.stripe_size,
+        )
+        .expect("source shards have a higher shard count");
+
+        Some(Self { primary, floors })
+    }
+
+    /// The last LSN of the layers written by the source shard owning `key`: the WAL for the
+    /// key up to there is already in layers, and must not be ingested again.
+    pub fn ingest_floor(&self, key: &Key) -> Option<Lsn> {
+        let (source, _) = self.source_of(*key);
+        self.floors.get(&source).copied().flatten()
+    }
+
+    /// Get the source shard owning a key, and the end of the range of keys following it that
+    /// the same source owns.
+    fn source_of(&self, key: Key) -> (ShardNumber, Key) {
+        if key.field1 != 0x00 {
+            // Relation blocks all sort before any key with a non-zero field1.
+            return (self.primary.number, Key::MAX);
+        }
+
+        if key_is_shard0(&key) {
+            // This is a relation size key, the last key of its fork, or within an initfork.
+            let fork_end = Key {
+                field6: u32::MAX,
+                ..key
+            };
+            return (self.primary.number, fork_end.next());
+        }
+
+        // Relation blocks are distributed by stripe.
+        let stripe_size = self.primary.stripe_size.0 as u64;
+        let stripe_end = (key.field6 as u64 / stripe_size + 1) * stripe_size;
+        let end = Key {
+            field6: std::cmp::min(stripe_end, u32::MAX as u64) as u32,
+            ..key
+        };
+
+        let source = self.primary.get_shard_number(&key);
+        if self.floors.contains_key(&source) {
+            (source, end)
+        } else {
+            // Not a key of this shard: any source will do.
+            (self.primary.number, end)
+        }
+    }
+
+    /// Split a key range into the sub-ranges owned by each source shard, in key order.
+    fn split_by_source(&self, key_range: Range<Key>) -> Vec<(ShardNumber, Range<Key>)> {
+        let mut result: Vec<(ShardNumber, Range<Key>)> = Vec::new();
+        let mut start = key_range.start;
+        while start < key_range.end {
+            let (source, end) = self.source_of(start);
+            let end = std::cmp::min(end, key_range.end);
+            match result.last_mut() {
+                Some((last, range)) if *last == source => range.end = end,
+                _ => result.push((source, start..end)),
+            }
+            start = end;
+        }
+
+        result
+    }
+
+    /// How a layer is indexed for the keys of a source shard.
+    ///
+    /// Delta layers written by this shard after the merge, below the end of a source's layers,
+    /// only hold the source's keys above that end: they are indexed from there, so that they
+    /// are read after the source's own layers. Returns `None` if the layer holds none of the
+    /// source's keys.
+    fn source_layer(
+        &self,
+        source: ShardNumber,
+        layer_desc: &Arc<PersistentLayerDesc>,
+    ) -> Option<Arc<PersistentLayerDesc>> {
+        let floor = self.floors.get(&source).copied().flatten();
+        // The merged shard resumed after the lowest of the floors: layers of this shard which
+        // start below it were written before the shards it was merged from were split.
+        let merge_lsn = self.floors.values().flatten().min().copied();
+        let (Some(floor), Some(merge_lsn)) = (floor, merge_lsn) else {
+            return Some(layer_desc.clone());
+        };
+
+        let lsn_range = &layer_desc.lsn_range;
+        if !layer_desc.is_delta()
+            || layer_desc.is_partial()
+            || lsn_range.start <= merge_lsn
+            || lsn_range.start > floor
+        {
+            Some(layer_desc.clone())
+        } else if lsn_range.end <= floor + 1 {
+            None
+        } else {
+            Some(Arc::new(PersistentLayerDesc {
+                lsn_range: (floor + 1)..lsn_range.end,
+                ..layer_desc.as_ref().clone()
+            }))
+        }
+    }
+}
+
+/// The historic layers of a shard created by merging shards, indexed per source shard (see
+/// [`MergeSources`]). `LayerMap::historic` can't be used to find the layers holding a key: an
+/// image layer of one source does not hold the pages of another. Instead, searches are routed
+/// to the index of the source owning the keys.
+struct MergedLayerCoverage {
+    sources: MergeSources,
+
+    /// Index of the historic layers holding the keys of each source shard
+    coverages: BTreeMap<ShardNumber, BufferedHistoricLayerCoverage<Arc<PersistentLayerDesc>>>,
+
+    /// Layers indexed with a shortened LSN range for some source, by their shortened key.
+    shortened: HashMap<PersistentLayerKey, Arc<PersistentLayerDesc>>,
+}
+
+impl MergedLayerCoverage {
+    fn new(sources: MergeSources) -> Self {
+        let coverages = sources
+            .floors
+            .keys()
+            .map(|number| (*number, BufferedHistoricLayerCoverage::new()))
+            .collect();
+
+        Self {
+            sources,
+            coverages,
+            shortened: HashMap::new(),
+        }
+    }
+
+    /// The source layers to index a layer as: one for each source whose keys it holds.
+    fn source_layers(
+        &self,
+        layer_desc: &Arc<PersistentLayerDesc>,
+    ) -> Vec<(ShardNumber, Arc<PersistentLayerDesc>)> {
+        let source_count = self.sources.primary.count;
+        self.coverages
+            .keys()
+            .filter(|number| {
+                layer_desc
+                    .shard
+                    .contains(&ShardIndex::new(**number, source_count))
+            })
+            .filter_map(|number| {
+                self.sources
+                    .source_layer(*number, layer_desc)
+                    .map(|source_layer| (*number, source_layer))
+            })
+            .collect()
+    }
+
+    /// Within a source's index, layers hold all the keys of their range: only deltas are incremental.
+    fn layer_key(layer_desc: &PersistentLayerDesc) -> LayerKey {
+        LayerKey {
+            is_image: !layer_desc.is_delta(),
+            ..LayerKey::from(layer_desc)
+        }
+    }
+
+    fn insert(&mut self, layer_desc: &Arc<PersistentLayerDesc>) {
+        for (source, source_layer) in self.source_layers(layer_desc) {
+            if source_layer.lsn_range != layer_desc.lsn_range {
+                self.shortened
+                    .insert(source_layer.key(), layer_desc.clone());
+            }
+            self.coverages
+                .get_mut(&source)
+                .unwrap()
+                .insert(Self::layer_key(&source_layer), source_layer);
+        }
+    }
+
+    fn remove(&mut self, layer_desc: &Arc<PersistentLayerDesc>) {
+        for (source, source_layer) in self.source_layers(layer_desc) {
+            self.shortened.remove(&source_layer.key());
+            self.coverages
+                .get_mut(&source)
+                .unwrap()
+                .remove(Self::layer_key(&source_layer));
+        }
+    }
+
+    fn rebuild(&mut self) {
+        for coverage in self.coverages.values_mut() {
+            coverage.rebuild();
+        }
+    }
+
+    /// The layer a source layer was indexed for.
+    fn original(&self, layer: Arc<PersistentLayerDesc>) -> Arc<PersistentLayerDesc> {
+        match self.shortened.get(&layer.key()) {
+            Some(original) => original.clone(),
+            None => layer,
+        }
+    }
+
+    fn search(&self, key: Key, end_lsn: Lsn) -> Option<SearchResult> {
+        let (source, _) = self.sources.source_of(key);
+        let SearchResult { layer, lsn_floor } =
+            LayerMap::search_coverage(&self.coverages[&source], key, end_lsn)?;
+        Some(SearchResult {
+            layer: self.original(layer),
+            lsn_floor,
+        })
+    }
+
+    fn image_layer_exists(&self, key: &Range<Key>, lsn: &Range<Lsn>) -> bool {
+        self.sources
+            .split_by_source(key.clone())
+            .into_iter()
+            .all(|(source, range)| {
+                LayerMap::image_layer_exists_coverage(&self.coverages[&source], &range, lsn)
+            })
+    }
+
+    fn image_coverage(
+        &self,
+        key_range: &Range<Key>,
+        lsn: Lsn,
+    ) -> Vec<(Range<Key>, Option<Arc<PersistentLayerDesc>>)> {
+        let mut coverage: Vec<(Range<Key>, Option<Arc<PersistentLayerDesc>>)> = vec![];
+        for (source, range) in self.sources.split_by_source(key_range.clone()) {
+            for (kr, image) in LayerMap::image_coverage_in(&self.coverages[&source], &range, lsn) {
+                // Layers of this shard hold the keys of all sources: don't split their ranges.
+                match coverage.last_mut() {
+                    Some((last_kr, last_image))
+                        if last_kr.end == kr.start
+                            && last_image.as_ref().map(|l| l.key())
+                                == image.as_ref().map(|l| l.key()) =>
+                    {
+                        last_kr.end = kr.end
+                    }
+                    _ => coverage.push((kr, image)),
+                }
+            }
+        }
+
+        coverage
+    }
+
+    fn count_deltas(&self, key: &Range<Key>, lsn: &Range<Lsn>, limit: Option<usize>) -> usize {
+        if lsn.is_empty() || key.is_empty() || limit == Some(0) {
+            return 0;
+        }
+
+        self.sources
+            .split_by_source(key.clone())
+            .into_iter()
+            .map(|(source, range)| {
+                LayerMap::count_deltas_coverage(&self.coverages[&source], key, &range, lsn, limit)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn range_search(&self, key_range: Range<Key>, end_lsn: Lsn) -> RangeSearchResult {
+        let mut result = RangeSearchResult::new();
+        for (source, range) in self.sources.split_by_source(key_range) {
+            let source_result =
+                LayerMap::range_search_coverage(&self.coverages[&source], range, end_lsn);
+
+            // Sub-ranges are searched in key order, so the ranges can be appended as they are.
+            for (SearchResult { layer, lsn_floor }, keyspace_accum) in source_result.found {
+                let layer = self.original(layer);
+                let accum = result
+                    .found
+                    .entry(SearchResult { layer, lsn_floor })
+                    .or_default();
+                for range in keyspace_accum.to_keyspace().ranges {
+                    accum.add_range(range);
+                }
+            }
+            for range in source_result.not_found.to_keyspace().ranges {
+                result.not_found.add_range(range);
+            }
+        }
+
+        result
+    }
 }
 
 /// The primary update API for the layer map.
@@ -380,7 +712,18 @@ impl LayerMap {
     /// 'open' and 'frozen' layers!
     ///
     pub fn search(&self, key: Key, end_lsn: Lsn) -> Option<SearchResult> {
-        let version = self.historic.get().unwrap().get_version(end_lsn.0 - 1)?;
+        match &self.merged {
+            Some(merged) => merged.search(key, end_lsn),
+            None => Self::search_coverage(&self.historic, key, end_lsn),
+        }
+    }
+
+    fn search_coverage(
+        historic: &BufferedHistoricLayerCoverage<Arc<PersistentLayerDesc>>,
+        key: Key,
+        end_lsn: Lsn,
+    ) -> Option<SearchResult> {
+        let version = historic.get().unwrap().get_version(end_lsn.0 - 1)?;
         let latest_delta = version.delta_coverage.query(key.to_i128());
         let latest_image = version.image_coverage.query(key.to_i128());
 
@@ -392,7 +735,7 @@ fn select_layer(
         image_layer: Option<Arc<PersistentLayerDesc>>,
         end_lsn: Lsn,
     ) -> Option<SearchResult> {
-        assert!(delta_layer.as_ref().map_or(true, |l| l.is_delta()));
+        assert!(delta_layer.as_ref().map_or(true, |l| l.is_incremental()));
         assert!(image_layer.as_ref().map_or(true, |l| !l.is_delta()));
 
         match (delta_layer, image_layer) {
@@ -433,7 +776,18 @@ fn select_layer(
     }
 
     pub fn range_search(&self, key_range: Range<Key>, end_lsn: Lsn) -> RangeSearchResult {
-        let version = match self.historic.get().unwrap().get_version(end_lsn.0 - 1) {
+        match &self.merged {
+            Some(merged) => merged.range_search(key_range, end_lsn),
+            None => Self::range_search_coverage(&self.historic, key_range, end_lsn),
+        }
+    }
+
+    fn range_search_coverage(
+        historic: &BufferedHistoricLayerCoverage<Arc<PersistentLayerDesc>>,
+        key_range: Range<Key>,
+        end_lsn: Lsn,
+    ) -> RangeSearchResult {
+        let version = match historic.get().unwrap().get_version(end_lsn.0 - 1) {
             Some(version) => version,
             None => {
                 let mut result = RangeSearchResult::new();
@@ -455,6 +809,13 @@ pub fn batch_update(&mut self) -> BatchedUpdates<'_> {
         BatchedUpdates { layer_map: self }
     }
 
+    /// Index layers by the source shards this shard was merged from. Must be called before
+    /// any layers are inserted.
+    pub fn set_merge_sources(&mut self, sources: MergeSources) {
+        assert_eq!(self.historic.len(), 0);
+        self.merged = Some(MergedLayerCoverage::new(sources));
+    }
+
     ///
     /// Insert an on-disk layer
     ///
@@ -464,13 +825,20 @@ pub fn batch_update(&mut self) -> BatchedUpdates<'_> {
     pub(self) fn insert_historic_noflush(&mut self, layer_desc: PersistentLayerDesc) {
         // TODO: See #3869, resulting #4088, attempted fix and repro #4094
 
-        if Self::is_l0(&layer_desc.key_range, layer_desc.is_delta) {
+        // Partial L0 layers of merge sources overlap each other: they are not compacted, and are
+        // eventually garbage collected once there are images of this shard above them.
+        if Self::is_l0(&layer_desc.key_range, layer_desc.is_delta) && !layer_desc.is_partial() {
             self.l0_delta_layers.push(layer_desc.clone().into());
         }
 
+        let layer_desc = Arc::new(layer_desc);
+        if let Some(merged) = self.merged.as_mut() {
+            merged.insert(&layer_desc);
+        }
+
         self.historic.insert(
-            historic_layer_coverage::LayerKey::from(&layer_desc),
-            layer_desc.into(),
+            historic_layer_coverage::LayerKey::from(layer_desc.as_ref()),
+            layer_desc,
         );
     }
 
@@ -482,8 +850,11 @@ pub(self) fn insert_historic_noflush(&mut self, layer_desc: PersistentLayerDesc)
     pub fn remove_historic_noflush(&mut self, layer_desc: &PersistentLayerDesc) {
         self.historic
             .remove(historic_layer_coverage::LayerKey::from(layer_desc));
+        if let Some(merged) = self.merged.as_mut() {
+            merged.remove(&Arc::new(layer_desc.clone()));
+        }
         let layer_key = layer_desc.key();
-        if Self::is_l0(&layer_desc.key_range, layer_desc.is_delta) {
+        if Self::is_l0(&layer_desc.key_range, layer_desc.is_delta) && !layer_desc.is_partial() {
             let len_before = self.l0_delta_layers.len();
             let mut l0_delta_layers = std::mem::take(&mut self.l0_delta_layers);
             l0_delta_layers.retain(|other| other.key() != layer_key);
@@ -502,6 +873,9 @@ pub fn remove_historic_noflush(&mut self, layer_desc: &PersistentLayerDesc) {
     /// Helper function for BatchedUpdates::drop.
     pub(self) fn flush_updates(&mut self) {
         self.historic.rebuild();
+        if let Some(merged) = self.merged.as_mut() {
+            merged.rebuild();
+        }
     }
 
     /// Is there a newer image layer for given key- and LSN-range? Or a set
@@ -511,12 +885,23 @@ pub(self) fn flush_updates(&mut self) {
     /// This is used for garbage collection, to determine if an old layer can
     /// be deleted.
     pub fn image_layer_exists(&self, key: &Range<Key>, lsn: &Range<Lsn>) -> bool {
+        match &self.merged {
+            Some(merged) => merged.image_layer_exists(key, lsn),
+            None => Self::image_layer_exists_coverage(&self.historic, key, lsn),
+        }
+    }
+
+    fn image_layer_exists_coverage(
+        historic: &BufferedHistoricLayerCoverage<Arc<PersistentLayerDesc>>,
+        key: &Range<Key>,
+        lsn: &Range<Lsn>,
+    ) -> bool {
         if key.is_empty() {
             // Vacuously true. There's a newer image for all 0 of the kerys in the range.
             return true;
         }
 
-        let version = match self.historic.get().unwrap().get_version(lsn.end.0 - 1) {
+        let version = match historic.get().unwrap().get_version(lsn.end.0 - 1) {
             Some(v) => v,
             None => return false,
         };
@@ -571,7 +956,18 @@ pub fn image_coverage(
         key_range: &Range<Key>,
         lsn: Lsn,
     ) -> Vec<(Range<Key>, Option<Arc<PersistentLayerDesc>>)> {
-        let version = match self.historic.get().unwrap().get_version(lsn.0) {
+        match &self.merged {
+            Some(merged) => merged.image_coverage(key_range, lsn),
+            None => Self::image_coverage_in(&self.historic, key_range, lsn),
+        }
+    }
+
+    fn image_coverage_in(
+        historic: &BufferedHistoricLayerCoverage<Arc<PersistentLayerDesc>>,
+        key_range: &Range<Key>,
+        lsn: Lsn,
+    ) -> Vec<(Range<Key>, Option<Arc<PersistentLayerDesc>>)> {
+        let version = match historic.get().unwrap().get_version(lsn.0) {
             Some(v) => v,
             None => return vec![],
         };
@@ -649,6 +1045,22 @@ pub fn is_reimage_worthy(layer: &PersistentLayerDesc, partition_range: &Range<Ke
     /// we'll need to visit for any page reconstruction in this region.
     /// We use this heuristic to decide whether to create an image layer.
     pub fn count_deltas(&self, key: &Range<Key>, lsn: &Range<Lsn>, limit: Option<usize>) -> usize {
+        match &self.merged {
+            Some(merged) => merged.count_deltas(key, lsn, limit),
+            None => Self::count_deltas_coverage(&self.historic, key, key, lsn, limit),
+        }
+    }
+
+    /// Helper for [`Self::count_deltas`]. The reimage-worthiness of deltas is decided against
+    /// `partition_range`, which only differs from `key` when a merged shard splits `key` by
+    /// source shard.
+    fn count_deltas_coverage(
+        historic: &BufferedHistoricLayerCoverage<Arc<PersistentLayerDesc>>,
+        partition_range: &Range<Key>,
+        key: &Range<Key>,
+        lsn: &Range<Lsn>,
+        limit: Option<usize>,
+    ) -> usize {
         // We get the delta coverage of the region, and for each part of the coverage
         // we recurse right underneath the delta. The recursion depth is limited by
         // the largest result this function could return, which is in practice between
@@ -658,7 +1070,7 @@ pub fn count_deltas(&self, key: &Range<Key>, lsn: &Range<Lsn>, limit: Option<usi
             return 0;
         }
 
-        let version = match self.historic.get().unwrap().get_version(lsn.end.0 - 1) {
+        let version = match historic.get().unwrap().get_version(lsn.end.0 - 1) {
             Some(v) => v,
             None => return 0,
         };
@@ -679,9 +1091,10 @@ pub fn count_deltas(&self, key: &Range<Key>, lsn: &Range<Lsn>, limit: Option<usi
                     let kr = Key::from_i128(current_key)..Key::from_i128(change_key);
                     let lr = lsn.start..val.get_lsn_range().start;
                     if !kr.is_empty() {
-                        let base_count = Self::is_reimage_worthy(val, key) as usize;
+                        let base_count = Self::is_reimage_worthy(val, partition_range) as usize;
                         let new_limit = limit.map(|l| l - base_count);
-                        let max_stacked_deltas_underneath = self.count_deltas(&kr, &lr, new_limit);
+                        let max_stacked_deltas_underneath =
+                            Self::count_deltas_coverage(historic, &kr, &kr, &lr, new_limit);
                         max_stacked_deltas = std::cmp::max(
                             max_stacked_deltas,
                             base_count + max_stacked_deltas_underneath,
@@ -701,9 +1114,10 @@ pub fn count_deltas(&self, key: &Range<Key>, lsn: &Range<Lsn>, limit: Option<usi
                 let lr = lsn.start..val.get_lsn_range().start;
 
                 if !kr.is_empty() {
-                    let base_count = Self::is_reimage_worthy(val, key) as usize;
+                    let base_count = Self::is_reimage_worthy(val, partition_range) as usize;
                     let new_limit = limit.map(|l| l - base_count);
-                    let max_stacked_deltas_underneath = self.count_deltas(&kr, &lr, new_limit);
+                    let max_stacked_deltas_underneath =
+                        Self::count_deltas_coverage(historic, &kr, &kr, &lr, new_limit);
                     max_stacked_deltas = std::cmp::max(
                         max_stacked_deltas,
                         base_count + max_stacked_deltas_underneath,
@@ -1176,6 +1590,164 @@ fn ranged_search() {
         }
     }
 
+    #[test]
+    fn merged_shard_range_search() {
+        use pageserver_api::shard::{ShardCount, ShardStripeSize};
+
+        let tenant_shard_id = TenantShardId {
+            tenant_id: TenantId::generate(),
+            shard_number: ShardNumber(0),
+            shard_count: ShardCount(1),
+        };
+        let timeline_id = TimelineId::generate();
+        let shard_identity =
+            ShardIdentity::new(ShardNumber(0), ShardCount(1), ShardStripeSize(8)).unwrap();
+        let source_identity =
+            ShardIdentity::new(ShardNumber(0), ShardCount(2), ShardStripeSize(8)).unwrap();
+
+        let rel_block = |blkno| Key {
+            field1: 0x00,
+            field2: 0x67f,
+            field3: 0x5,
+            field4: 0x400c,
+            field5: 0x00,
+            field6: blkno,
+        };
+        let key_range = rel_block(0)..rel_block(64);
+        let source_layer = |source, mut layer_desc: PersistentLayerDesc| {
+            layer_desc.shard = ShardIndex::new(ShardNumber(source), ShardCount(2));
+            assert!(layer_desc.is_partial());
+            layer_desc
+        };
+
+        // Each source wrote an image of its own pages, and then deltas up to different LSNs.
+        // The merged shard then resumed from the lowest of those.
+        let layers = vec![
+            source_layer(
+                0,
+                PersistentLayerDesc::new_img(
+                    tenant_shard_id,
+                    timeline_id,
+                    key_range.clone(),
+                    Lsn(0x10),
+                    0,
+                ),
+            ),
+            source_layer(
+                1,
+                PersistentLayerDesc::new_img(
+                    tenant_shard_id,
+                    timeline_id,
+                    key_range.clone(),
+                    Lsn(0x10),
+                    0,
+                ),
+            ),
+            source_layer(
+                0,
+                PersistentLayerDesc::new_delta(
+                    tenant_shard_id,
+                    timeline_id,
+                    key_range.clone(),
+                    Lsn(0x11)..Lsn(0x31),
+                    0,
+                ),
+            ),
+            source_layer(
+                1,
+                PersistentLayerDesc::new_delta(
+                    tenant_shard_id,
+                    timeline_id,
+                    key_range.clone(),
+                    Lsn(0x11)..Lsn(0x51),
+                    0,
+                ),
+            ),
+            PersistentLayerDesc::new_delta(
+                tenant_shard_id,
+                timeline_id,
+                key_range.clone(),
+                Lsn(0x31)..Lsn(0x61),
+                0,
+            ),
+        ];
+
+        let merge_sources = MergeSources::new(&shard_identity, layers.iter()).unwrap();
+        assert_eq!(merge_sources.ingest_floor(&Key::MAX), Some(Lsn(0x30)));
+        let mut layer_map = LayerMap::default();
+        layer_map.set_merge_sources(merge_sources.clone());
+        let mut updates = layer_map.batch_update();
+        for layer_desc in layers {
+            updates.insert_historic(layer_desc);
+        }
+        updates.flush();
+
+        // Keys are found in the layers holding them: the merged shard's delta only holds
+        // the WAL of the second source above the end of that source's delta.
+        let result = layer_map.range_search(key_range.clone(), Lsn(0x70));
+        assert!(result.not_found.to_keyspace().ranges.is_empty());
+        assert_eq!(result.found.len(), 2);
+        for (search_result, accum) in result.found {
+            assert!(!search_result.layer.is_partial());
+            assert_eq!(search_result.layer.lsn_range, Lsn(0x31)..Lsn(0x61));
+            for range in accum.to_keyspace().ranges {
+                let mut key = range.start;
+                while key < range.end {
+                    let (source, floor) = match source_identity.get_shard_number(&key) {
+                        ShardNumber(0) => (0, Lsn(0x30)),
+                        _ => (1, Lsn(0x50)),
+                    };
+                    assert_eq!(search_result.lsn_floor, floor + 1, "source {source}");
+                    assert_eq!(merge_sources.ingest_floor(&key), Some(floor));
+                    key = key.next();
+                }
+            }
+        }
+
+        // Below the merge, every key is found in the image of the source that owned it, even
+        // though both images cover the whole range.
+        let result = layer_map.range_search(key_range.clone(), Lsn(0x11));
+        assert_eq!(result.found.len(), 2);
+        for (search_result, accum) in result.found {
+            let writer = search_result.layer.shard.shard_number;
+            for range in accum.to_keyspace().ranges {
+                let mut key = range.start;
+                while key < range.end {
+                    assert_eq!(source_identity.get_shard_number(&key), writer);
+                    key = key.next();
+                }
+            }
+        }
+
+        // Point searches agree with range searches.
+        let mut key = key_range.start;
+        while key < key_range.end {
+            let search_result = layer_map.search(key, Lsn(0x70)).unwrap();
+            assert_eq!(search_result.layer.lsn_range, Lsn(0x31)..Lsn(0x61));
+            let floor = merge_sources.ingest_floor(&key).unwrap();
+            assert_eq!(search_result.lsn_floor, floor + 1);
+            key = key.next();
+        }
+
+        // Each source's image only covers that source's keys, but together they cover them all.
+        assert!(layer_map.image_layer_exists(&key_range, &(Lsn(0x5)..Lsn(0x70))));
+        assert!(!layer_map.image_layer_exists(&key_range, &(Lsn(0x11)..Lsn(0x70))));
+        for (range, image) in layer_map.image_coverage(&key_range, Lsn(0x70)) {
+            let writer = image.unwrap().shard.shard_number;
+            let mut key = range.start;
+            while key < range.end {
+                assert_eq!(source_identity.get_shard_number(&key), writer);
+                key = key.next();
+            }
+        }
+
+        // Above its image, each source's keys are in its own delta and the merged shard's.
+        assert_eq!(
+            layer_map.count_deltas(&key_range, &(Lsn(0x11)..Lsn(0x70)), None),
+            2
+        );
+    }
+
     #[test]
     fn layer_visibility_basic() {
         // A simple synthetic input, as a smoke test.
@@ -1365,6 +1937,7 @@ fn layer_visibility_realistic() {
                     timeline_id,
                     is_delta: false,
                     file_size: layer_metadata.file_size,
+                    shard: tenant_shard_id.to_index(),
                 },
                 LayerName::Delta(layer_name) => PersistentLayerDesc {
                     key_range: layer_name.key_range,
@@ -1373,6 +1946,7 @@ fn layer_visibility_realistic() {
                     timeline_id,
                     is_delta: true,
                     file_size: layer_metadata.file_size,
+                    shard: tenant_shard_id.to_index(),
                 },
             };
             updates.insert_historic(layer_desc);
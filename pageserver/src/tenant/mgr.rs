@@ -1716,6 +1716,170 @@ pub(crate) async fn do_shard_split(
         Ok(child_shards)
     }
 
+    /// First phase of a shard merge, run on each of the source shards: see [`Tenant::merge_prepare`].
+    #[instrument(skip_all, fields(tenant_id=%tenant.get_tenant_shard_id().tenant_id, shard_id=%tenant.get_tenant_shard_id().shard_slug()))]
+    pub(crate) async fn shard_merge_prepare(
+        &self,
+        tenant: Arc<Tenant>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        let tenant_shard_id = *tenant.get_tenant_shard_id();
+
+        fail::fail_point!("shard-merge-pre-prepare", |_| Err(anyhow::anyhow!(
+            "failpoint"
+        )));
+
+        let r = tenant.merge_prepare().await;
+        if let Err(e) = &r {
+            // Like in a split, the shard's remote timeline clients may have been shut down: reset it
+            // so that it keeps working as a normal shard.
+            tracing::warn!("Resetting after shard merge prepare failure: {e}");
+            drop(tenant);
+            if let Err(e) = self.reset_tenant(tenant_shard_id, false, ctx).await {
+                tracing::error!("Failed to reset: {e}");
+            }
+        }
+
+        r
+    }
+
+    /// Second phase of a shard merge, run on the source shard with the same shard number as the
+    /// merged shard, once all the sources have been prepared.  Writes the merged shard's index
+    /// files and attaches the merged shard on this pageserver, in `generation`.  The source shards
+    /// are left alone: it is up to the caller to detach them.
+    #[instrument(skip_all, fields(tenant_id=%tenant.get_tenant_shard_id().tenant_id, shard_id=%tenant.get_tenant_shard_id().shard_slug(), new_shard_count=%new_shard_count.literal()))]
+    pub(crate) async fn shard_merge(
+        &self,
+        tenant: Arc<Tenant>,
+        new_shard_count: ShardCount,
+        generation: Generation,
+        sources: Vec<(TenantShardId, Generation)>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<TenantShardId> {
+        let tenant_shard_id = *tenant.get_tenant_shard_id();
+        let source_count = tenant_shard_id.shard_count;
+
+        // Validate the incoming request
+        if new_shard_count.count() >= source_count.count() {
+            anyhow::bail!("Requested shard count is not a decrease");
+        }
+        if source_count.count() % std::cmp::max(new_shard_count.count(), 1) != 0 {
+            anyhow::bail!("Requested shard count does not divide the current shard count");
+        }
+        let merged_shard = TenantShardId {
+            tenant_id: tenant_shard_id.tenant_id,
+            shard_number: tenant_shard_id.shard_number,
+            shard_count: new_shard_count,
+        };
+        if tenant_shard_id.shard_number.0 >= std::cmp::max(new_shard_count.count(), 1) {
+            anyhow::bail!(
+                "Shard {} is not the primary source of a merged shard",
+                tenant_shard_id.to_index()
+            );
+        }
+        let mut expect_sources = merged_shard.split(source_count);
+        let mut request_sources = sources.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        expect_sources.sort();
+        request_sources.sort();
+        if expect_sources != request_sources {
+            anyhow::bail!(
+                "Shard {} merges from {}, not from the requested sources",
+                merged_shard.to_index(),
+                expect_sources
+                    .iter()
+                    .map(|id| format!("{}", id.to_index()))
+                    .join(",")
+            );
+        }
+
+        tracing::info!(
+            "Shards {} merge into {}",
+            request_sources
+                .iter()
+                .map(|id| format!("{}", id.to_index()))
+                .join(","),
+            merged_shard.to_index()
+        );
+
+        // Phase 1: Write out the merged shard's remote index files, in its own generation
+        tenant
+            .merge_create(&merged_shard, generation, &sources)
+            .await?;
+
+        fail::fail_point!("shard-merge-post-create", |_| Err(anyhow::anyhow!(
+            "failpoint"
+        )));
+
+        // Take a snapshot of where this source's WAL ingest had got to: we will wait for the
+        // merged shard to reach this point.
+        let mut target_lsns = HashMap::new();
+        for timeline in tenant.timelines.lock().unwrap().clone().values() {
+            target_lsns.insert(timeline.timeline_id, timeline.get_last_record_lsn());
+        }
+
+        // Phase 2: Spawn the merged shard
+        let mut merged_shard_identity = tenant.shard_identity;
+        merged_shard_identity.count = merged_shard.shard_count;
+        merged_shard_identity.number = merged_shard.shard_number;
+        let merged_location_conf = LocationConf {
+            mode: LocationMode::Attached(AttachedLocationConfig {
+                generation,
+                attach_mode: AttachmentMode::Single,
+            }),
+            shard: merged_shard_identity,
+            tenant_conf: tenant.get_tenant_conf(),
+        };
+        drop(tenant);
+
+        let merged = self
+            .upsert_location(
+                merged_shard,
+                merged_location_conf,
+                None,
+                SpawnMode::Eager,
+                ctx,
+            )
+            .await?;
+
+        // Phase 3: wait for the merged shard's WAL ingest to catch up to target LSN.  As in a split,
+        // this only makes the cut-over smoother for clients: the merged shard is already durable.
+        if let Some(merged) = merged {
+            if let Err(e) = merged.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await {
+                tracing::warn!("Failed to wait for shard {merged_shard} to activate: {e}");
+                return Ok(merged_shard);
+            }
+
+            let timelines = merged.timelines.lock().unwrap().clone();
+            for timeline in timelines.values() {
+                let Some(target_lsn) = target_lsns.get(&timeline.timeline_id) else {
+                    continue;
+                };
+
+                tracing::info!(
+                    "Waiting for merged shard {}/{} to reach target lsn {}...",
+                    merged_shard,
+                    timeline.timeline_id,
+                    target_lsn
+                );
+                if let Err(e) = timeline
+                    .wait_lsn(
+                        *target_lsn,
+                        crate::tenant::timeline::WaitLsnWaiter::Tenant,
+                        ctx,
+                    )
+                    .await
+                {
+                    tracing::warn!(
+                        "Failed to wait for timeline {} to reach lsn {target_lsn}: {e}",
+                        timeline.timeline_id
+                    );
+                }
+            }
+        }
+
+        Ok(merged_shard)
+    }
+
     /// Part of [`Self::shard_split`]: hard link parent shard layers into child shards, as an optimization
     /// to avoid the children downloading them again.
     ///
@@ -2156,7 +2320,7 @@ pub(crate) fn resolve_attached_shard(
     ///
     /// This function is quite expensive: callers are expected to cache the result and
     /// limit how often they call it.
-    pub(crate) fn calculate_utilization(&self) -> Result<(u64, u32), TenantMapListError> {
+    pub(crate) fn calculate_utilization(&self) -> Result<TenantsUtilization, TenantMapListError> {
         let tenants = self.tenants.read().unwrap();
         let m = match &*tenants {
             TenantsMap::Initializing => return Err(TenantMapListError::Initializing),
@@ -2164,6 +2328,8 @@ pub(crate) fn calculate_utilization(&self) -> Result<(u64, u32), TenantMapListEr
         };
         let shard_count = m.len();
         let mut wanted_bytes = 0;
+        let mut resident_bytes = 0;
+        let mut wal_ingest_bytes_per_sec = 0;
 
         for tenant_slot in m.values() {
             match tenant_slot {
@@ -2178,8 +2344,12 @@ pub(crate) fn calculate_utilization(&self) -> Result<(u64, u32), TenantMapListEr
                 }
                 TenantSlot::Attached(tenant) => {
                     wanted_bytes += tenant.local_storage_wanted();
+                    let sizes = tenant.get_sizes();
+                    resident_bytes += sizes.resident_size;
+                    wal_ingest_bytes_per_sec += sizes.wal_ingest_rate;
                 }
                 TenantSlot::Secondary(secondary) => {
+                    resident_bytes += secondary.resident_size_metric.get();
                     let progress = secondary.progress.lock().unwrap();
                     wanted_bytes += if progress.heatmap_mtime.is_some() {
                         // If we have heatmap info, then we will 'want' the sum
@@ -2195,7 +2365,12 @@ pub(crate) fn calculate_utilization(&self) -> Result<(u64, u32), TenantMapListEr
             }
         }
 
-        Ok((wanted_bytes, shard_count as u32))
+        Ok(TenantsUtilization {
+            disk_wanted_bytes: wanted_bytes,
+            resident_bytes,
+            wal_ingest_bytes_per_sec,
+            shard_count: shard_count as u32,
+        })
     }
 
     #[instrument(skip_all, fields(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), %timeline_id))]
@@ -2345,6 +2520,14 @@ pub(crate) enum TenantStateError {
     Other(#[from] anyhow::Error),
 }
 
+/// Totals over the tenant shards on this pageserver, from [`TenantManager::calculate_utilization`]
+pub(crate) struct TenantsUtilization {
+    pub(crate) disk_wanted_bytes: u64,
+    pub(crate) resident_bytes: u64,
+    pub(crate) wal_ingest_bytes_per_sec: u64,
+    pub(crate) shard_count: u32,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum TenantMapListError {
     #[error("tenant map is still initiailizing")]
@@ -3,6 +3,7 @@
 //! Able to restore itself from the storage index parts, that are located in every timeline's remote directory and contain all data about
 //! remote timeline layers and its metadata.
 
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
 use chrono::NaiveDateTime;
@@ -134,6 +135,74 @@ pub(crate) fn example() -> Self {
         Self::empty(TimelineMetadata::example())
     }
 
+    /// Compose the index of a shard created by merging shards, from the indices of its source
+    /// shards: the merged shard references the layers of all of its sources.
+    ///
+    /// The sources have persisted layers up to different LSNs. The merged shard resumes from the
+    /// lowest of them, and keeps the layers of the other sources above it: it does not ingest
+    /// the WAL of their keys again until past the end of their layers.
+    pub(crate) fn merge(sources: &[IndexPart]) -> anyhow::Result<IndexPart> {
+        let Some(base) = sources
+            .iter()
+            .min_by_key(|source| source.metadata.disk_consistent_lsn())
+        else {
+            anyhow::bail!("No source indices to merge");
+        };
+        if sources.iter().any(|source| source.deleted_at.is_some()) {
+            anyhow::bail!("Timeline is deleted in a source shard");
+        }
+
+        let disk_consistent_lsn = base.metadata.disk_consistent_lsn();
+        let latest_gc_cutoff_lsn = sources
+            .iter()
+            .map(|source| source.metadata.latest_gc_cutoff_lsn())
+            .max()
+            .unwrap();
+        if latest_gc_cutoff_lsn > disk_consistent_lsn {
+            anyhow::bail!(
+                "A source shard is only consistent at {disk_consistent_lsn}, below the GC cutoff {latest_gc_cutoff_lsn}"
+            );
+        }
+
+        let mut layer_metadata = HashMap::new();
+        for source in sources {
+            for (name, metadata) in &source.layer_metadata {
+                match layer_metadata.entry(name.clone()) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(metadata.clone());
+                    }
+                    Entry::Occupied(entry) => {
+                        if entry.get() != metadata {
+                            anyhow::bail!("Layer {name} differs between source shards");
+                        }
+                    }
+                }
+            }
+        }
+
+        let metadata = TimelineMetadata::new(
+            disk_consistent_lsn,
+            base.metadata.prev_record_lsn(),
+            base.metadata.ancestor_timeline(),
+            base.metadata.ancestor_lsn(),
+            latest_gc_cutoff_lsn,
+            base.metadata.initdb_lsn(),
+            base.metadata.pg_version(),
+        );
+
+        Ok(IndexPart {
+            version: Self::LATEST_VERSION,
+            deleted_at: None,
+            archived_at: base.archived_at,
+            layer_metadata,
+            disk_consistent_lsn,
+            metadata,
+            lineage: base.lineage.clone(),
+            gc_blocking: sources.iter().find_map(|source| source.gc_blocking.clone()),
+            last_aux_file_policy: base.last_aux_file_policy,
+        })
+    }
+
     pub(crate) fn last_aux_file_policy(&self) -> Option<AuxFilePolicy> {
         self.last_aux_file_policy
     }
@@ -843,6 +912,65 @@ fn v9_indexpart_is_parsed() {
         assert_eq!(part, expected);
     }
 
+    #[test]
+    fn merge_source_indices() {
+        let source_index = |disk_consistent_lsn: &str, layers: &[(&str, u8)]| {
+            let mut index = IndexPart::empty(TimelineMetadata::new(
+                Lsn::from_str(disk_consistent_lsn).unwrap(),
+                None,
+                None,
+                Lsn::INVALID,
+                Lsn::from_str("0/1000000").unwrap(),
+                Lsn::from_str("0/1000000").unwrap(),
+                16,
+            ));
+            for (name, shard_number) in layers {
+                index.layer_metadata.insert(
+                    name.parse().unwrap(),
+                    LayerFileMetadata::new(
+                        1000,
+                        Generation::new(1),
+                        ShardIndex::new(
+                            pageserver_api::shard::ShardNumber(*shard_number),
+                            pageserver_api::shard::ShardCount(2),
+                        ),
+                    ),
+                );
+            }
+            index
+        };
+
+        let shared = "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001000000-0000000001100001";
+        let ahead = "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001100001-0000000001300001";
+        let behind = "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001100001-0000000001200001";
+
+        let sources = [
+            source_index("0/1300000", &[(shared, 0), (ahead, 0)]),
+            source_index("0/1200000", &[(shared, 0), (behind, 1)]),
+        ];
+        let merged = IndexPart::merge(&sources).unwrap();
+
+        // The merged shard resumes from the source that is furthest behind, with the layers of all sources
+        assert_eq!(
+            merged.metadata.disk_consistent_lsn(),
+            Lsn::from_str("0/1200000").unwrap()
+        );
+        assert_eq!(
+            merged.disk_consistent_lsn,
+            merged.metadata.disk_consistent_lsn()
+        );
+        assert_eq!(merged.layer_metadata.len(), 3);
+
+        // Layers with the same name must be the same layer
+        let conflicting = source_index("0/1300000", &[(shared, 1)]);
+        assert!(IndexPart::merge(&[sources[0].clone(), conflicting]).is_err());
+
+        // Deleted timelines can't be merged
+        let mut deleted = sources[1].clone();
+        deleted.deleted_at = Some(parse_naive_datetime("2024-07-19T09:00:00.123000000"));
+        assert!(IndexPart::merge(&[sources[0].clone(), deleted]).is_err());
+    }
+
     fn parse_naive_datetime(s: &str) -> NaiveDateTime {
         chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S.%f").unwrap()
     }
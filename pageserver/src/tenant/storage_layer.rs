@@ -114,7 +114,7 @@ pub(crate) struct ValuesReconstructState {
     keys_done: KeySpaceRandomAccum,
 
     /// The keys covered by the image layers
-    keys_with_image_coverage: Option<Range<Key>>,
+    keys_with_image_coverage: Option<KeySpace>,
 
     // Statistics that are still accessible as a caller of `get_vectored_impl`.
     layers_visited: u32,
@@ -182,10 +182,13 @@ pub(crate) fn on_lsn_advanced(&mut self, keyspace: &KeySpace, advanced_to: Lsn)
         }
     }
 
-    /// On hitting image layer, we can mark all keys in this range as done, because
+    /// On hitting image layer, we can mark all keys we searched for in it as done, because
     /// if the image layer does not contain a key, it is deleted/never added.
-    pub(crate) fn on_image_layer_visited(&mut self, key_range: &Range<Key>) {
-        let prev_val = self.keys_with_image_coverage.replace(key_range.clone());
+    ///
+    /// Only the searched keys are covered, rather than the whole key range of the layer: the
+    /// image layers of a merged shard's sources only hold the keys of the source that wrote them.
+    pub(crate) fn on_image_layer_visited(&mut self, keyspace: &KeySpace) {
+        let prev_val = self.keys_with_image_coverage.replace(keyspace.clone());
         assert_eq!(
             prev_val, None,
             "should consume the keyspace before the next iteration"
@@ -255,7 +258,7 @@ pub(crate) fn get_cached_lsn(&self, key: &Key) -> Option<Lsn> {
     /// Returns the key space describing the keys that have
     /// been marked as completed since the last call to this function.
     /// Returns individual keys done, and the image layer coverage.
-    pub(crate) fn consume_done_keys(&mut self) -> (KeySpace, Option<Range<Key>>) {
+    pub(crate) fn consume_done_keys(&mut self) -> (KeySpace, Option<KeySpace>) {
         (
             self.keys_done.consume_keyspace(),
             self.keys_with_image_coverage.take(),
@@ -442,14 +442,14 @@ pub(super) async fn get_values_reconstruct_data(
         ctx: &RequestContext,
     ) -> Result<(), GetVectoredError> {
         let reads = self
-            .plan_reads(keyspace, None, ctx)
+            .plan_reads(keyspace.clone(), None, ctx)
             .await
             .map_err(GetVectoredError::Other)?;
 
         self.do_reads_and_update_state(reads, reconstruct_state, ctx)
             .await;
 
-        reconstruct_state.on_image_layer_visited(&self.key_range);
+        reconstruct_state.on_image_layer_visited(&keyspace);
 
         Ok(())
     }
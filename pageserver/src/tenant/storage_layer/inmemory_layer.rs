@@ -30,7 +30,7 @@
 use crate::metrics::TIMELINE_EPHEMERAL_BYTES;
 use std::cmp::Ordering;
 use std::fmt::Write;
-use std::ops::Range;
+use std::ops::{Bound, Range};
 use std::sync::atomic::Ordering as AtomicOrdering;
 use std::sync::atomic::{AtomicU64, AtomicUsize};
 use tokio::sync::RwLock;
@@ -396,6 +396,14 @@ pub(crate) fn get_lsn_range(&self) -> Range<Lsn> {
         self.start_lsn..self.end_lsn_or_max()
     }
 
+    /// The range of keys with values in this layer, if any.
+    pub(crate) async fn key_range(&self) -> Option<Range<Key>> {
+        let inner = self.inner.read().await;
+        let first = inner.index.keys().next()?;
+        let last = inner.index.keys().next_back()?;
+        Some(Key::from_compact(*first)..Key::from_compact(*last).next())
+    }
+
     /// debugging function to print out the contents of the layer
     ///
     /// this is likely completly unused
@@ -755,7 +763,7 @@ pub async fn freeze(&self, end_lsn: Lsn) {
 
     /// Write this frozen in-memory layer to disk. If `key_range` is set, the delta
     /// layer will only contain the key range the user specifies, and may return `None`
-    /// if there are no matching keys.
+    /// if there are no matching keys. Otherwise, it is written as an L0 layer.
     ///
     /// Returns a new delta layer with all the same data as this in-memory layer
     pub async fn write_to_disk(
@@ -782,18 +790,14 @@ pub async fn write_to_disk(
 
         let end_lsn = *self.end_lsn.get().unwrap();
 
-        let key_count = if let Some(key_range) = key_range {
-            let key_range = key_range.start.to_compact()..key_range.end.to_compact();
-
-            inner
-                .index
-                .iter()
-                .filter(|(k, _)| key_range.contains(k))
-                .count()
-        } else {
-            inner.index.len()
-        };
-        if key_count == 0 {
+        let index = inner.index.range(match &key_range {
+            Some(key_range) => (
+                Bound::Included(key_range.start.to_compact()),
+                Bound::Excluded(key_range.end.to_compact()),
+            ),
+            None => (Bound::Unbounded, Bound::Unbounded),
+        });
+        if index.clone().next().is_none() {
             return Ok(None);
         }
 
@@ -801,7 +805,9 @@ pub async fn write_to_disk(
             self.conf,
             self.timeline_id,
             self.tenant_shard_id,
-            Key::MIN,
+            key_range
+                .as_ref()
+                .map_or(Key::MIN, |key_range| key_range.start),
             self.start_lsn..end_lsn,
             ctx,
         )
@@ -813,7 +819,7 @@ pub async fn write_to_disk(
 
                 let file_contents = Bytes::from(file_contents);
 
-                for (key, vec_map) in inner.index.iter() {
+                for (key, vec_map) in index {
                     // Write all page versions
                     for (lsn, entry) in vec_map
                         .as_slice()
@@ -842,7 +848,8 @@ pub async fn write_to_disk(
         }
 
         // MAX is used here because we identify L0 layers by full key range
-        let (desc, path) = delta_layer_writer.finish(Key::MAX, ctx).await?;
+        let key_end = key_range.map_or(Key::MAX, |key_range| key_range.end);
+        let (desc, path) = delta_layer_writer.finish(key_end, ctx).await?;
 
         // Hold the permit until all the IO is done, including the fsync in `delta_layer_writer.finish()``.
         //
@@ -791,11 +791,14 @@ fn new(
         conf: &'static PageServerConf,
         timeline: &Arc<Timeline>,
         local_path: Utf8PathBuf,
-        desc: PersistentLayerDesc,
+        mut desc: PersistentLayerDesc,
         downloaded: Option<Arc<DownloadedLayer>>,
         generation: Generation,
         shard: ShardIndex,
     ) -> Self {
+        // The layer map needs to know which shard wrote the layer to tell apart partial layers.
+        desc.shard = shard;
+
         let (inner, version, init_status) = if let Some(inner) = downloaded {
             let version = inner.version;
             let resident = ResidentOrWantedEvicted::Resident(inner);
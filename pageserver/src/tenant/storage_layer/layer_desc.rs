@@ -1,5 +1,5 @@
 use core::fmt::Display;
-use pageserver_api::shard::TenantShardId;
+use pageserver_api::shard::{ShardIndex, TenantShardId};
 use std::ops::Range;
 use utils::{id::TimelineId, lsn::Lsn};
 
@@ -30,9 +30,13 @@ pub struct PersistentLayerDesc {
     ///   range start
     /// - An image layer represents snapshot at one LSN, so end_lsn is always the snapshot LSN + 1
     pub lsn_range: Range<Lsn>,
-    /// Whether this is a delta layer, and also, is this incremental.
+    /// Whether this is a delta layer.
     pub is_delta: bool,
     pub file_size: u64,
+    /// The shard that wrote this layer. This is the timeline's own shard, or an ancestor
+    /// of it after a shard split, unless the tenant has been merged from several shards:
+    /// then this may be one of the source shards, whose layers only hold its own keys.
+    pub shard: ShardIndex,
 }
 
 /// A unique identifier of a persistent layer within the context of one timeline.
@@ -79,6 +83,7 @@ pub fn new_test(key_range: Range<Key>, lsn_range: Range<Lsn>, is_delta: bool) ->
             lsn_range,
             is_delta,
             file_size: 0,
+            shard: ShardIndex::unsharded(),
         }
     }
 
@@ -96,6 +101,7 @@ pub fn new_img(
             lsn_range: Self::image_layer_lsn_range(lsn),
             is_delta: false,
             file_size,
+            shard: tenant_shard_id.to_index(),
         }
     }
 
@@ -113,6 +119,7 @@ pub fn new_delta(
             lsn_range,
             is_delta: true,
             file_size,
+            shard: tenant_shard_id.to_index(),
         }
     }
 
@@ -197,8 +204,17 @@ pub fn get_timeline_id(&self) -> TimelineId {
     /// or does it contain a version of every page? This is important to know
     /// for garbage collecting old layers: an incremental layer depends on
     /// the previous non-incremental layer.
+    ///
+    /// Partial image layers are incremental: they hold every page of their writer
+    /// shard, but not of this one.
     pub fn is_incremental(&self) -> bool {
-        self.is_delta
+        self.is_delta || self.is_partial()
+    }
+
+    /// Was this layer written by a shard which only held some of this shard's keys? This
+    /// is the case for layers inherited from the source shards of a shard merge.
+    pub fn is_partial(&self) -> bool {
+        !self.shard.contains(&self.tenant_shard_id.to_index())
     }
 
     pub fn is_delta(&self) -> bool {
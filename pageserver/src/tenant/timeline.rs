@@ -68,7 +68,7 @@
     aux_file::AuxFileSizeEstimator,
     tenant::{
         config::AttachmentMode,
-        layer_map::{LayerMap, SearchResult},
+        layer_map::{LayerMap, MergeSources, SearchResult},
         metadata::TimelineMetadata,
         storage_layer::{inmemory_layer::IndexEntry, PersistentLayerDesc},
     },
@@ -241,6 +241,10 @@ pub struct Timeline {
     /// to shards, and is constant through the lifetime of this Timeline.
     shard_identity: ShardIdentity,
 
+    /// For shards created by a shard merge: the source shards, whose layers hold some of the
+    /// WAL that this shard would ingest again. Set when loading the layer map.
+    merge_sources: std::sync::OnceLock<MergeSources>,
+
     pub pg_version: u32,
 
     /// The tuple has two elements.
@@ -2195,6 +2199,7 @@ pub(super) fn new(
                 tenant_shard_id,
                 generation,
                 shard_identity,
+                merge_sources: std::sync::OnceLock::new(),
                 pg_version,
                 layers: Default::default(),
 
@@ -2496,8 +2501,12 @@ pub(super) async fn load_layer_map(
                     );
                 }
 
-                let decided =
-                    init::reconcile(discovered_layers, index_part.as_ref(), disk_consistent_lsn);
+                let decided = init::reconcile(
+                    discovered_layers,
+                    index_part.as_ref(),
+                    disk_consistent_lsn,
+                    this.tenant_shard_id.to_index(),
+                );
 
                 let mut loaded_layers = Vec::new();
                 let mut needs_cleanup = Vec::new();
@@ -2529,9 +2538,16 @@ pub(super) async fn load_layer_map(
                         }
                     };
 
+                    // Only layers of the source shards of a merge may be above disk_consistent_lsn
+                    let is_merge_source_layer = init::is_merge_source_layer(
+                        decision.metadata(),
+                        this.tenant_shard_id.to_index(),
+                    );
                     match &name {
-                        Delta(d) => assert!(d.lsn_range.end <= disk_consistent_lsn + 1),
-                        Image(i) => assert!(i.lsn <= disk_consistent_lsn),
+                        Delta(d) => assert!(
+                            is_merge_source_layer || d.lsn_range.end <= disk_consistent_lsn + 1
+                        ),
+                        Image(i) => assert!(is_merge_source_layer || i.lsn <= disk_consistent_lsn),
                     }
 
                     tracing::debug!(layer=%name, ?decision, "applied");
@@ -2556,10 +2572,19 @@ pub(super) async fn load_layer_map(
 
         let num_layers = loaded_layers.len();
 
+        let merge_sources = MergeSources::new(
+            &self.shard_identity,
+            loaded_layers.iter().map(|layer| layer.layer_desc()),
+        );
+        if let Some(merge_sources) = &merge_sources {
+            info!("Loaded layers of a merged shard: {merge_sources:?}");
+            let _ = self.merge_sources.set(merge_sources.clone());
+        }
+
         guard
             .open_mut()
             .expect("layermanager must be open during init")
-            .initialize_local_layers(loaded_layers, disk_consistent_lsn + 1);
+            .initialize_local_layers(loaded_layers, disk_consistent_lsn + 1, merge_sources);
 
         self.remote_client
             .schedule_layer_file_deletion(&needs_cleanup)?;
@@ -3232,9 +3257,8 @@ async fn get_vectored_reconstruct_data_timeline(
             unmapped_keyspace.remove_overlapping_with(&keys_done_last_step);
             completed_keyspace.merge(&keys_done_last_step);
             if let Some(keys_with_image_coverage) = keys_with_image_coverage {
-                unmapped_keyspace
-                    .remove_overlapping_with(&KeySpace::single(keys_with_image_coverage.clone()));
-                image_covered_keyspace.add_range(keys_with_image_coverage);
+                unmapped_keyspace.remove_overlapping_with(&keys_with_image_coverage);
+                image_covered_keyspace.add_keyspace(keys_with_image_coverage);
             }
 
             // Do not descent any further if the last layer we visited
@@ -3698,8 +3722,24 @@ async fn flush_frozen_layer(
             // Normal case, write out a L0 delta layer file.
             // `create_delta_layer` will not modify the layer map.
             // We will remove frozen layer and add delta layer in one atomic operation later.
+            //
+            // After a shard merge, a source shard may have written an L0 layer with the same LSN
+            // range. Layer names don't include the shard, so ours would replace it: write a delta
+            // layer of just the keys we hold instead.
+            let l0_key = PersistentLayerKey {
+                key_range: Key::MIN..Key::MAX,
+                lsn_range: lsn_range.clone(),
+                is_delta: true,
+            };
+            let key_range = if self.layers.read().await.contains_key(&l0_key) {
+                let key_range = frozen_layer.key_range().await;
+                info!("L0 layer {l0_key} already exists, writing delta of keys {key_range:?}");
+                key_range
+            } else {
+                None
+            };
             let Some(layer) = self
-                .create_delta_layer(&frozen_layer, None, ctx)
+                .create_delta_layer(&frozen_layer, key_range, ctx)
                 .await
                 .map_err(|e| FlushLayerError::from_anyhow(self, e))?
             else {
@@ -4294,15 +4334,21 @@ async fn create_image_layers(
                     continue;
                 }
             }
-            if let ImageLayerCreationMode::Force = mode {
-                // When forced to create image layers, we might try and create them where they already
-                // exist.  This mode is only used in tests/debug.
+            {
+                // We might try and create image layers where they already exist: when forced to
+                // create them (only used in tests/debug), or where one of the source shards of a
+                // shard merge created one. Layer names don't include the shard, so ours would
+                // replace it.
                 let layers = self.layers.read().await;
-                if layers.contains_key(&PersistentLayerKey {
+                let key = PersistentLayerKey {
                     key_range: img_range.clone(),
                     lsn_range: PersistentLayerDesc::image_layer_lsn_range(lsn),
                     is_delta: false,
-                }) {
+                };
+                if layers.contains_key(&key)
+                    && (mode == ImageLayerCreationMode::Force
+                        || layers.get_from_key(&key).layer_desc().is_partial())
+                {
                     tracing::info!(
                         "Skipping image layer at {lsn} {}..{}, already exists",
                         img_range.start,
@@ -5757,6 +5803,19 @@ pub(crate) async fn put_batch(
         batch: Vec<(CompactKey, Lsn, usize, Value)>,
         ctx: &RequestContext,
     ) -> anyhow::Result<()> {
+        // After a shard merge, the layers of the source shards may already hold some of this WAL.
+        let batch = match self.merge_sources.get() {
+            Some(merge_sources) => batch
+                .into_iter()
+                .filter(|(key, lsn, _, _)| {
+                    merge_sources
+                        .ingest_floor(&Key::from_compact(*key))
+                        .map_or(true, |floor| *lsn > floor)
+                })
+                .collect(),
+            None => batch,
+        };
+
         if batch.is_empty() {
             return Ok(());
         }
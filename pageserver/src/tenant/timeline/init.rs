@@ -11,6 +11,7 @@
 };
 use anyhow::Context;
 use camino::{Utf8Path, Utf8PathBuf};
+use pageserver_api::shard::ShardIndex;
 use std::{
     collections::{hash_map, HashMap},
     str::FromStr,
@@ -103,6 +104,15 @@ pub(super) enum Decision {
     },
 }
 
+impl Decision {
+    pub(super) fn metadata(&self) -> &LayerFileMetadata {
+        match self {
+            Decision::Evicted(remote) => remote,
+            Decision::Resident { remote, .. } => remote,
+        }
+    }
+}
+
 /// A layer needs to be left out of the layer map.
 #[derive(Debug)]
 pub(super) enum DismissedLayer {
@@ -127,6 +137,7 @@ pub(super) fn reconcile(
     local_layers: Vec<(LayerName, LocalLayerFileMetadata)>,
     index_part: Option<&IndexPart>,
     disk_consistent_lsn: Lsn,
+    shard: ShardIndex,
 ) -> Vec<(LayerName, Result<Decision, DismissedLayer>)> {
     let Some(index_part) = index_part else {
         // If we have no remote metadata, no local layer files are considered valid to load
@@ -175,9 +186,12 @@ pub(super) fn reconcile(
         });
 
     // For layers that were found in authoritative remote metadata, apply a final check that they are within
-    // the disk_consistent_lsn.
+    // the disk_consistent_lsn. Layers of the source shards of a shard merge are exempt: the merged shard does
+    // not ingest the WAL they hold again (see [`IndexPart::merge`]).
     result.extend(remote_layers.into_iter().map(|(name, decision)| {
-        if name.is_in_future(disk_consistent_lsn) {
+        if name.is_in_future(disk_consistent_lsn)
+            && !is_merge_source_layer(decision.metadata(), shard)
+        {
             match decision {
                 Decision::Evicted(_remote) => (name, Err(DismissedLayer::Future { local: None })),
                 Decision::Resident {
@@ -193,6 +207,13 @@ pub(super) fn reconcile(
     result
 }
 
+/// Was this layer written by one of the shards that `shard` was merged from? Only those layers
+/// may extend above the disk_consistent_lsn of `shard`: layers written by the shard itself or by
+/// the shards it was split from may not, and layers of any other shard don't belong to it at all.
+pub(super) fn is_merge_source_layer(metadata: &LayerFileMetadata, shard: ShardIndex) -> bool {
+    metadata.shard != shard && shard.contains(&metadata.shard)
+}
+
 pub(super) fn cleanup(path: &Utf8Path, kind: &str) -> anyhow::Result<()> {
     let file_name = path.file_name().expect("must be file path");
     tracing::debug!(kind, ?file_name, "cleaning up");
@@ -13,7 +13,7 @@
     context::RequestContext,
     metrics::TimelineMetrics,
     tenant::{
-        layer_map::{BatchedUpdates, LayerMap},
+        layer_map::{BatchedUpdates, LayerMap, MergeSources},
         storage_layer::{
             AsLayerDesc, InMemoryLayer, Layer, PersistentLayerDesc, PersistentLayerKey,
             ResidentLayer,
@@ -157,7 +157,16 @@ impl OpenLayerManager {
     /// Called from `load_layer_map`. Initialize the layer manager with:
     /// 1. all on-disk layers
     /// 2. next open layer (with disk disk_consistent_lsn LSN)
-    pub(crate) fn initialize_local_layers(&mut self, layers: Vec<Layer>, next_open_layer_at: Lsn) {
+    pub(crate) fn initialize_local_layers(
+        &mut self,
+        layers: Vec<Layer>,
+        next_open_layer_at: Lsn,
+        merge_sources: Option<MergeSources>,
+    ) {
+        if let Some(merge_sources) = merge_sources {
+            self.layer_map.set_merge_sources(merge_sources);
+        }
+
         let mut updates = self.layer_map.batch_update();
         for layer in layers {
             Self::insert_historic_layer(layer, &mut updates, &mut self.layer_fmgr);
@@ -40,7 +40,7 @@ pub(crate) fn regenerate(
     let captured_at = std::time::SystemTime::now();
 
     // Calculate aggregate utilization from tenants on this pageserver
-    let (disk_wanted_bytes, shard_count) = tenant_manager.calculate_utilization()?;
+    let tenants_utilization = tenant_manager.calculate_utilization()?;
 
     // Fetch the fraction of disk space which may be used
     let disk_usable_pct = match conf.disk_usage_based_eviction.clone() {
@@ -54,10 +54,12 @@ pub(crate) fn regenerate(
     let mut doc = PageserverUtilization {
         disk_usage_bytes: used,
         free_space_bytes: free,
-        disk_wanted_bytes,
+        disk_wanted_bytes: tenants_utilization.disk_wanted_bytes,
         disk_usable_pct,
-        shard_count,
+        shard_count: tenants_utilization.shard_count,
         max_shard_count: MAX_SHARDS,
+        resident_bytes: tenants_utilization.resident_bytes,
+        wal_ingest_bytes_per_sec: tenants_utilization.wal_ingest_bytes_per_sec,
         utilization_score: None,
         captured_at: utils::serde_system_time::SystemTime(captured_at),
     };
@@ -1,10 +1,11 @@
-use super::{ComputeCredentials, ComputeUserInfo};
+use super::jwt::FetchAuthRules;
+use super::{oidc, ComputeCredentials, ComputeUserInfo};
 use crate::{
-    auth::{self, backend::ComputeCredentialKeys, AuthFlow},
+    auth::{self, backend::ComputeCredentialKeys, AuthFlow, ScramOrToken, ScramOrTokenOutcome},
     compute,
     config::AuthenticationConfig,
     context::RequestMonitoring,
-    control_plane::AuthSecret,
+    control_plane::{self, AuthSecret},
     sasl,
     stream::{PqStream, Stream},
 };
@@ -13,6 +14,7 @@
 
 pub(super) async fn authenticate(
     ctx: &RequestMonitoring,
+    api: &(impl control_plane::Api + FetchAuthRules),
     creds: ComputeUserInfo,
     client: &mut PqStream<Stream<impl AsyncRead + AsyncWrite + Unpin>>,
     config: &'static AuthenticationConfig,
@@ -32,14 +34,23 @@ pub(super) async fn authenticate(
             let auth_outcome = tokio::time::timeout(
                 config.scram_protocol_timeout,
                 async {
-
-                    flow.begin(scram).await.map_err(|error| {
-                        warn!(?error, "error sending scram acknowledgement");
-                        error
-                    })?.authenticate().await.map_err(|error| {
-                        warn!(?error, "error processing scram messages");
-                        error
-                    })
+                    if config.accept_oidc_tokens {
+                        flow.begin(ScramOrToken(scram)).await.map_err(|error| {
+                            warn!(?error, "error sending scram acknowledgement");
+                            error
+                        })?.authenticate().await.map_err(|error| {
+                            warn!(?error, "error processing scram messages");
+                            error
+                        })
+                    } else {
+                        flow.begin(scram).await.map_err(|error| {
+                            warn!(?error, "error sending scram acknowledgement");
+                            error
+                        })?.authenticate().await.map_err(|error| {
+                            warn!(?error, "error processing scram messages");
+                            error
+                        }).map(ScramOrTokenOutcome::Scram)
+                    }
                 }
             )
             .await
@@ -49,8 +60,13 @@ pub(super) async fn authenticate(
             })??;
 
             let client_key = match auth_outcome {
-                sasl::Outcome::Success(key) => key,
-                sasl::Outcome::Failure(reason) => {
+                ScramOrTokenOutcome::Token(token) => {
+                    let creds = oidc::authenticate(ctx, api, creds, &token, config).await?;
+                    client.write_message_noflush(&pq_proto::BeMessage::AuthenticationOk)?;
+                    return Ok(creds);
+                }
+                ScramOrTokenOutcome::Scram(sasl::Outcome::Success(key)) => key,
+                ScramOrTokenOutcome::Scram(sasl::Outcome::Failure(reason)) => {
                     info!("auth backend failed with an error: {reason}");
                     return Err(auth::AuthError::auth_failed(&*creds.user));
                 }
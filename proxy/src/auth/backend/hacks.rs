@@ -54,6 +54,24 @@ pub(crate) async fn authenticate_cleartext(
     Ok(ComputeCredentials { info, keys })
 }
 
+/// Like [`authenticate_cleartext`], but the password is only received here,
+/// as it might be an OIDC access token.
+pub(crate) async fn get_password_or_token(
+    ctx: &RequestMonitoring,
+    client: &mut stream::PqStream<Stream<impl AsyncRead + AsyncWrite + Unpin>>,
+) -> auth::Result<Vec<u8>> {
+    ctx.set_auth_method(crate::context::AuthMethod::Cleartext);
+
+    // pause the timer while we communicate with the client
+    let _paused = ctx.latency_timer_pause(crate::metrics::Waiting::Client);
+
+    AuthFlow::new(client)
+        .begin(auth::PasswordOrToken)
+        .await?
+        .get_password()
+        .await
+}
+
 /// Workaround for clients which don't provide an endpoint (project) name.
 /// Similar to [`authenticate_cleartext`], but there's a specific password format,
 /// and passwords are not yet validated (we don't know how to validate them!)
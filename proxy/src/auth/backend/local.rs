@@ -65,6 +65,8 @@ async fn fetch_auth_rules(
                 jwks_url: setting.jwks_url.clone(),
                 audience: setting.jwt_audience.clone(),
                 role_names: setting.role_names.clone(),
+                role_claim: setting.role_claim.clone(),
+                role_mapping: setting.role_mapping.clone(),
             });
         }
 
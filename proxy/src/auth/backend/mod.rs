@@ -3,6 +3,7 @@
 mod hacks;
 pub mod jwt;
 pub mod local;
+pub(crate) mod oidc;
 
 use std::net::IpAddr;
 use std::sync::Arc;
@@ -11,23 +12,25 @@
 pub use console_redirect::ConsoleRedirectBackend;
 pub(crate) use console_redirect::WebAuthError;
 use ipnet::{Ipv4Net, Ipv6Net};
+use jwt::FetchAuthRules;
 use local::LocalBackend;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_postgres::config::AuthKeys;
 use tracing::{info, warn};
 
 use crate::auth::credentials::check_peer_addr_is_in_list;
+use crate::auth::lockout::LockoutKey;
 use crate::auth::{validate_password_and_exchange, AuthError};
 use crate::cache::Cached;
 use crate::context::RequestMonitoring;
 use crate::control_plane::errors::GetAuthInfoError;
-use crate::control_plane::provider::{CachedRoleSecret, ControlPlaneBackend};
+use crate::control_plane::provider::{CachedQueryPolicy, CachedRoleSecret, ControlPlaneBackend};
 use crate::control_plane::AuthSecret;
 use crate::intern::EndpointIdInt;
 use crate::metrics::Metrics;
 use crate::proxy::connect_compute::ComputeConnectBackend;
 use crate::proxy::NeonOptions;
-use crate::rate_limiter::{BucketRateLimiter, EndpointRateLimiter, RateBucketInfo};
+use crate::rate_limiter::{BucketRateLimiter, DistributedKey, EndpointRateLimiter, RateBucketInfo};
 use crate::stream::Stream;
 use crate::{
     auth::{self, ComputeUserInfoMaybeEndpoint},
@@ -169,7 +172,6 @@ pub(crate) fn endpoint_cache_key(&self) -> EndpointCacheKey {
 
 #[cfg_attr(test, derive(Debug))]
 pub(crate) enum ComputeCredentialKeys {
-    #[cfg(any(test, feature = "testing"))]
     Password(Vec<u8>),
     AuthKeys(AuthKeys),
     JwtPayload(Vec<u8>),
@@ -214,6 +216,12 @@ fn new(value: IpAddr, prefix: u8) -> Self {
 // This can't be just per IP because that would limit some PaaS that share IP addresses
 pub type AuthRateLimiter = BucketRateLimiter<(EndpointIdInt, MaskedIp)>;
 
+impl DistributedKey for (EndpointIdInt, MaskedIp) {
+    fn redis_key(&self) -> String {
+        format!("{}:{}", self.0, self.1 .0)
+    }
+}
+
 impl RateBucketInfo {
     /// All of these are per endpoint-maskedip pair.
     /// Context: 4096 rounds of pbkdf2 take about 1ms of cpu time to execute (1 milli-cpu-second or 1mcpus).
@@ -288,7 +296,7 @@ pub(crate) fn check_rate_limit(
 /// All authentication flows will emit an AuthenticationOk message if successful.
 async fn auth_quirks(
     ctx: &RequestMonitoring,
-    api: &impl control_plane::Api,
+    api: &(impl control_plane::Api + FetchAuthRules),
     user_info: ComputeUserInfoMaybeEndpoint,
     client: &mut stream::PqStream<Stream<impl AsyncRead + AsyncWrite + Unpin>>,
     allow_cleartext: bool,
@@ -321,6 +329,9 @@ async fn auth_quirks(
     if !endpoint_rate_limiter.check(info.endpoint.clone().into(), 1) {
         return Err(AuthError::too_many_connections());
     }
+    let lockout_key = LockoutKey::new(&info.endpoint, &info.user, ctx.peer_addr());
+    config.auth_lockout.check(&lockout_key)?;
+
     let cached_secret = match maybe_secret {
         Some(secret) => secret,
         None => api.get_role_secret(ctx, &info).await?,
@@ -344,6 +355,7 @@ async fn auth_quirks(
 
     match authenticate_with_secret(
         ctx,
+        api,
         secret,
         info,
         client,
@@ -353,19 +365,25 @@ async fn auth_quirks(
     )
     .await
     {
-        Ok(keys) => Ok(keys),
+        Ok(keys) => {
+            config.auth_lockout.record_success(&lockout_key);
+            Ok(keys)
+        }
         Err(e) => {
             if e.is_auth_failed() {
                 // The password could have been changed, so we invalidate the cache.
                 cached_entry.invalidate();
+                config.auth_lockout.record_failure(lockout_key);
             }
             Err(e)
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn authenticate_with_secret(
     ctx: &RequestMonitoring,
+    api: &(impl control_plane::Api + FetchAuthRules),
     secret: AuthSecret,
     info: ComputeUserInfo,
     client: &mut stream::PqStream<Stream<impl AsyncRead + AsyncWrite + Unpin>>,
@@ -374,22 +392,7 @@ async fn authenticate_with_secret(
     config: &'static AuthenticationConfig,
 ) -> auth::Result<ComputeCredentials> {
     if let Some(password) = unauthenticated_password {
-        let ep = EndpointIdInt::from(&info.endpoint);
-
-        let auth_outcome =
-            validate_password_and_exchange(&config.thread_pool, ep, &password, secret).await?;
-        let keys = match auth_outcome {
-            crate::sasl::Outcome::Success(key) => key,
-            crate::sasl::Outcome::Failure(reason) => {
-                info!("auth backend failed with an error: {reason}");
-                return Err(auth::AuthError::auth_failed(&*info.user));
-            }
-        };
-
-        // we have authenticated the password
-        client.write_message_noflush(&pq_proto::BeMessage::AuthenticationOk)?;
-
-        return Ok(ComputeCredentials { info, keys });
+        return authenticate_password(ctx, api, secret, info, client, &password, config).await;
     }
 
     // -- the remaining flows are self-authenticating --
@@ -397,12 +400,51 @@ async fn authenticate_with_secret(
     // Perform cleartext auth if we're allowed to do that.
     // Currently, we use it for websocket connections (latency).
     if allow_cleartext {
+        if config.accept_oidc_tokens {
+            let password = hacks::get_password_or_token(ctx, client).await?;
+            return authenticate_password(ctx, api, secret, info, client, &password, config).await;
+        }
         ctx.set_auth_method(crate::context::AuthMethod::Cleartext);
         return hacks::authenticate_cleartext(ctx, info, client, secret, config).await;
     }
 
     // Finally, proceed with the main auth flow (SCRAM-based).
-    classic::authenticate(ctx, info, client, config, secret).await
+    classic::authenticate(ctx, api, info, client, config, secret).await
+}
+
+/// Authenticate the password received from the client, or the OIDC access
+/// token sent in its place.
+async fn authenticate_password(
+    ctx: &RequestMonitoring,
+    api: &(impl control_plane::Api + FetchAuthRules),
+    secret: AuthSecret,
+    info: ComputeUserInfo,
+    client: &mut stream::PqStream<Stream<impl AsyncRead + AsyncWrite + Unpin>>,
+    password: &[u8],
+    config: &'static AuthenticationConfig,
+) -> auth::Result<ComputeCredentials> {
+    let token = oidc::password_as_token(password).filter(|_| config.accept_oidc_tokens);
+    let credentials = if let Some(token) = token {
+        oidc::authenticate(ctx, api, info, token, config).await?
+    } else {
+        let ep = EndpointIdInt::from(&info.endpoint);
+
+        let auth_outcome =
+            validate_password_and_exchange(&config.thread_pool, ep, password, secret).await?;
+        let keys = match auth_outcome {
+            crate::sasl::Outcome::Success(key) => key,
+            crate::sasl::Outcome::Failure(reason) => {
+                info!("auth backend failed with an error: {reason}");
+                return Err(auth::AuthError::auth_failed(&*info.user));
+            }
+        };
+        ComputeCredentials { info, keys }
+    };
+
+    // we have authenticated the password
+    client.write_message_noflush(&pq_proto::BeMessage::AuthenticationOk)?;
+
+    Ok(credentials)
 }
 
 impl<'a> Backend<'a, ComputeUserInfoMaybeEndpoint> {
@@ -476,6 +518,17 @@ pub(crate) async fn get_allowed_ips_and_secret(
             Self::Local(_) => Ok((Cached::new_uncached(Arc::new(vec![])), None)),
         }
     }
+
+    pub(crate) async fn get_query_policy(
+        &self,
+        ctx: &RequestMonitoring,
+    ) -> Result<CachedQueryPolicy, GetAuthInfoError> {
+        match self {
+            Self::ControlPlane(api, user_info) => api.get_query_policy(ctx, user_info).await,
+            // The auth broker enforces the policy in front of local_proxy.
+            Self::Local(_) => Ok(Cached::new_uncached(Arc::default())),
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -518,7 +571,7 @@ mod tests {
         context::RequestMonitoring,
         control_plane::{
             self,
-            provider::{self, CachedAllowedIps, CachedRoleSecret},
+            provider::{self, CachedAllowedIps, CachedQueryPolicy, CachedRoleSecret},
             CachedNodeInfo,
         },
         proxy::NeonOptions,
@@ -527,13 +580,25 @@ mod tests {
         stream::{PqStream, Stream},
     };
 
-    use super::{auth_quirks, jwt::JwkCache, AuthRateLimiter};
+    use super::{auth_quirks, jwt::FetchAuthRules, jwt::JwkCache, AuthRateLimiter};
+    use crate::auth::lockout::AuthLockout;
 
+    #[derive(Clone)]
     struct Auth {
         ips: Vec<IpPattern>,
         secret: AuthSecret,
     }
 
+    impl FetchAuthRules for Auth {
+        async fn fetch_auth_rules(
+            &self,
+            _ctx: &RequestMonitoring,
+            _endpoint: crate::EndpointId,
+        ) -> anyhow::Result<Vec<super::jwt::AuthRule>> {
+            unimplemented!()
+        }
+    }
+
     impl control_plane::Api for Auth {
         async fn get_role_secret(
             &self,
@@ -557,6 +622,14 @@ async fn get_allowed_ips_and_secret(
             ))
         }
 
+        async fn get_query_policy(
+            &self,
+            _ctx: &RequestMonitoring,
+            _user_info: &super::ComputeUserInfo,
+        ) -> Result<CachedQueryPolicy, control_plane::errors::GetAuthInfoError> {
+            Ok(CachedQueryPolicy::new_uncached(Arc::default()))
+        }
+
         async fn get_endpoint_jwks(
             &self,
             _ctx: &RequestMonitoring,
@@ -565,6 +638,14 @@ async fn get_endpoint_jwks(
             unimplemented!()
         }
 
+        async fn get_session_password(
+            &self,
+            _ctx: &RequestMonitoring,
+            _user_info: &super::ComputeUserInfo,
+        ) -> Result<Box<str>, control_plane::errors::GetAuthInfoError> {
+            unimplemented!()
+        }
+
         async fn wake_compute(
             &self,
             _ctx: &RequestMonitoring,
@@ -581,9 +662,11 @@ async fn wake_compute(
         rate_limiter_enabled: true,
         rate_limiter: AuthRateLimiter::new(&RateBucketInfo::DEFAULT_AUTH_SET),
         rate_limit_ip_subnet: 64,
+        auth_lockout: AuthLockout::new(None),
         ip_allowlist_check_enabled: true,
         is_auth_broker: false,
         accept_jwts: false,
+        accept_oidc_tokens: false,
         webauth_confirmation_timeout: std::time::Duration::from_secs(5),
     });
 
@@ -1,5 +1,6 @@
 //! Main authentication flow.
 
+use super::backend::oidc::{self, OAUTHBEARER};
 use super::{backend::ComputeCredentialKeys, AuthErrorImpl, PasswordHackPayload};
 use crate::{
     config::TlsServerEndPoint,
@@ -45,6 +46,33 @@ fn first_message(&self, channel_binding: bool) -> BeMessage<'_> {
     }
 }
 
+/// Use [SCRAM](crate::scram)-based auth in [`AuthFlow`], also offering
+/// OAUTHBEARER for clients authenticating with an OIDC access token.
+pub(crate) struct ScramOrToken<'a>(pub(crate) Scram<'a>);
+
+const METHODS_WITH_TOKEN: &[&str] = &[SCRAM_SHA_256_PLUS, SCRAM_SHA_256, OAUTHBEARER];
+const METHODS_WITH_TOKEN_WITHOUT_PLUS: &[&str] = &[SCRAM_SHA_256, OAUTHBEARER];
+
+impl AuthMethod for ScramOrToken<'_> {
+    #[inline(always)]
+    fn first_message(&self, channel_binding: bool) -> BeMessage<'_> {
+        if channel_binding {
+            Be::AuthenticationSasl(BeAuthenticationSaslMessage::Methods(METHODS_WITH_TOKEN))
+        } else {
+            Be::AuthenticationSasl(BeAuthenticationSaslMessage::Methods(
+                METHODS_WITH_TOKEN_WITHOUT_PLUS,
+            ))
+        }
+    }
+}
+
+/// Outcome of [`ScramOrToken`] auth.
+pub(crate) enum ScramOrTokenOutcome {
+    Scram(sasl::Outcome<scram::ScramKey>),
+    /// The token is not validated yet.
+    Token(String),
+}
+
 /// Use an ad hoc auth flow (for clients which don't support SNI) proposed in
 /// <https://github.com/neondatabase/cloud/issues/1620#issuecomment-1165332290>.
 pub(crate) struct PasswordHack;
@@ -71,6 +99,17 @@ fn first_message(&self, _channel_binding: bool) -> BeMessage<'_> {
     }
 }
 
+/// Use clear-text password auth, accepting an OIDC access token in place of
+/// the password.
+pub(crate) struct PasswordOrToken;
+
+impl AuthMethod for PasswordOrToken {
+    #[inline(always)]
+    fn first_message(&self, _channel_binding: bool) -> BeMessage<'_> {
+        Be::AuthenticationCleartextPassword
+    }
+}
+
 /// This wrapper for [`PqStream`] performs client authentication.
 #[must_use]
 pub(crate) struct AuthFlow<'a, S, State> {
@@ -127,6 +166,18 @@ pub(crate) async fn get_password(self) -> super::Result<PasswordHackPayload> {
     }
 }
 
+impl<S: AsyncRead + AsyncWrite + Unpin> AuthFlow<'_, S, PasswordOrToken> {
+    /// Get the password or the token, which are validated by the caller.
+    pub(crate) async fn get_password(self) -> super::Result<Vec<u8>> {
+        let msg = self.stream.read_password_message().await?;
+        let password = msg
+            .strip_suffix(&[0])
+            .ok_or(AuthErrorImpl::MalformedPassword("missing terminator"))?;
+
+        Ok(password.to_vec())
+    }
+}
+
 impl<S: AsyncRead + AsyncWrite + Unpin> AuthFlow<'_, S, CleartextPassword> {
     /// Perform user authentication. Raise an error in case authentication failed.
     pub(crate) async fn authenticate(self) -> super::Result<sasl::Outcome<ComputeCredentialKeys>> {
@@ -165,32 +216,68 @@ pub(crate) async fn authenticate(self) -> super::Result<sasl::Outcome<scram::Scr
         let sasl = sasl::FirstMessage::parse(&msg)
             .ok_or(AuthErrorImpl::MalformedPassword("bad sasl message"))?;
 
-        // Currently, the only supported SASL method is SCRAM.
-        if !scram::METHODS.contains(&sasl.method) {
-            return Err(super::AuthError::bad_auth_method(sasl.method));
-        }
+        authenticate_scram(self.stream, secret, ctx, sasl, self.tls_server_end_point).await
+    }
+}
 
-        match sasl.method {
-            SCRAM_SHA_256 => ctx.set_auth_method(crate::context::AuthMethod::ScramSha256),
-            SCRAM_SHA_256_PLUS => ctx.set_auth_method(crate::context::AuthMethod::ScramSha256Plus),
-            _ => {}
-        }
-        info!("client chooses {}", sasl.method);
+impl<S: AsyncRead + AsyncWrite + Unpin> AuthFlow<'_, S, ScramOrToken<'_>> {
+    /// Perform SCRAM authentication, or get the token if the client chose OAUTHBEARER.
+    pub(crate) async fn authenticate(self) -> super::Result<ScramOrTokenOutcome> {
+        let Scram(secret, ctx) = self.state.0;
 
-        let outcome = sasl::SaslStream::new(self.stream, sasl.message)
-            .authenticate(scram::Exchange::new(
-                secret,
-                rand::random,
-                self.tls_server_end_point,
-            ))
-            .await?;
+        // pause the timer while we communicate with the client
+        let _paused = ctx.latency_timer_pause(crate::metrics::Waiting::Client);
 
-        if let sasl::Outcome::Success(_) = &outcome {
-            self.stream.write_message_noflush(&Be::AuthenticationOk)?;
+        // Initial client message contains the chosen auth method's name.
+        let msg = self.stream.read_password_message().await?;
+        let sasl = sasl::FirstMessage::parse(&msg)
+            .ok_or(AuthErrorImpl::MalformedPassword("bad sasl message"))?;
+
+        if sasl.method == OAUTHBEARER {
+            info!("client chooses {}", sasl.method);
+            let token = oidc::parse_oauthbearer(sasl.message)
+                .ok_or(AuthErrorImpl::MalformedPassword("bad oauthbearer message"))?;
+            return Ok(ScramOrTokenOutcome::Token(token.to_owned()));
         }
 
-        Ok(outcome)
+        let outcome =
+            authenticate_scram(self.stream, secret, ctx, sasl, self.tls_server_end_point).await?;
+        Ok(ScramOrTokenOutcome::Scram(outcome))
+    }
+}
+
+async fn authenticate_scram<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut PqStream<Stream<S>>,
+    secret: &scram::ServerSecret,
+    ctx: &RequestMonitoring,
+    sasl: sasl::FirstMessage<'_>,
+    tls_server_end_point: TlsServerEndPoint,
+) -> super::Result<sasl::Outcome<scram::ScramKey>> {
+    // Currently, the only supported SASL method is SCRAM.
+    if !scram::METHODS.contains(&sasl.method) {
+        return Err(super::AuthError::bad_auth_method(sasl.method));
+    }
+
+    match sasl.method {
+        SCRAM_SHA_256 => ctx.set_auth_method(crate::context::AuthMethod::ScramSha256),
+        SCRAM_SHA_256_PLUS => ctx.set_auth_method(crate::context::AuthMethod::ScramSha256Plus),
+        _ => {}
     }
+    info!("client chooses {}", sasl.method);
+
+    let outcome = sasl::SaslStream::new(stream, sasl.message)
+        .authenticate(scram::Exchange::new(
+            secret,
+            rand::random,
+            tls_server_end_point,
+        ))
+        .await?;
+
+    if let sasl::Outcome::Success(_) = &outcome {
+        stream.write_message_noflush(&Be::AuthenticationOk)?;
+    }
+
+    Ok(outcome)
 }
 
 pub(crate) async fn validate_password_and_exchange(
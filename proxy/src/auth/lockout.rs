@@ -0,0 +1,295 @@
+//! Lockout of authentication after repeated failures.
+//!
+//! Rate limits bound the password hashing work per endpoint and IP, but
+//! still allow a steady stream of guesses. After `max_failures` failed
+//! password attempts for the same endpoint, role and client IP within
+//! `window`, further attempts are rejected for `duration` without checking
+//! the password. Lockouts are published through redis so that other proxies
+//! reject the attempts as well.
+
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Mutex, time::Instant};
+use tracing::{info, warn};
+
+use super::{AuthError, Result};
+use crate::{
+    intern::{EndpointIdInt, RoleNameInt},
+    metrics::Metrics,
+    redis::cancellation_publisher::RedisPublisherClient,
+    EndpointId, RoleName,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct LockoutConfig {
+    pub max_failures: u32,
+    pub window: Duration,
+    pub duration: Duration,
+}
+
+/// Attempts are counted per endpoint, role and client IP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct LockoutKey {
+    pub(crate) endpoint_id: EndpointIdInt,
+    pub(crate) role_name: RoleNameInt,
+    pub(crate) ip: IpAddr,
+}
+
+impl LockoutKey {
+    pub(crate) fn new(endpoint: &EndpointId, role: &RoleName, ip: IpAddr) -> Self {
+        Self {
+            endpoint_id: endpoint.into(),
+            role_name: role.into(),
+            ip,
+        }
+    }
+}
+
+/// Lockout of a key by another proxy.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct LockoutNotification {
+    #[serde(flatten)]
+    pub(crate) key: LockoutKey,
+    pub(crate) duration_ms: u64,
+}
+
+struct Failures {
+    start: Instant,
+    count: u32,
+}
+
+pub struct AuthLockout {
+    /// Lockout is disabled without a config.
+    config: Option<LockoutConfig>,
+    failures: DashMap<LockoutKey, Failures, ahash::RandomState>,
+    locked_until: DashMap<LockoutKey, Instant, ahash::RandomState>,
+    access_count: AtomicUsize,
+    publisher: OnceLock<Arc<Mutex<RedisPublisherClient>>>,
+}
+
+impl AuthLockout {
+    pub fn new(config: Option<LockoutConfig>) -> Self {
+        if let Some(config) = &config {
+            info!(?config, "authentication lockout");
+        }
+        Self {
+            config,
+            failures: DashMap::with_hasher_and_shard_amount(ahash::RandomState::new(), 64),
+            locked_until: DashMap::with_hasher_and_shard_amount(ahash::RandomState::new(), 64),
+            access_count: AtomicUsize::new(1), // start from 1 to avoid GC on the first request
+            publisher: OnceLock::new(),
+        }
+    }
+
+    /// Publish lockouts to other proxies through the publisher.
+    pub fn set_publisher(&self, publisher: Arc<Mutex<RedisPublisherClient>>) {
+        if self.publisher.set(publisher).is_err() {
+            warn!("authentication lockout publisher is already set");
+        }
+    }
+
+    /// Check that attempts for the key are not locked out.
+    pub(crate) fn check(&self, key: &LockoutKey) -> Result<()> {
+        if self.config.is_none() {
+            return Ok(());
+        }
+        self.maybe_gc();
+
+        let Some(until) = self.locked_until.get(key).map(|until| *until) else {
+            return Ok(());
+        };
+        if Instant::now() < until {
+            Metrics::get().proxy.requests_auth_lockouts_total.inc();
+            return Err(AuthError::locked_out(key.role_name.as_str()));
+        }
+        self.locked_until
+            .remove_if(key, |_, until| *until <= Instant::now());
+        Ok(())
+    }
+
+    /// Reset the failures of the key after a successful attempt.
+    pub(crate) fn record_success(&self, key: &LockoutKey) {
+        if self.config.is_some() {
+            self.failures.remove(key);
+        }
+    }
+
+    /// Count a failed attempt, locking out the key once it failed too often.
+    pub(crate) fn record_failure(&self, key: LockoutKey) {
+        let Some(config) = self.config else {
+            return;
+        };
+
+        let now = Instant::now();
+        let failures = {
+            let mut entry = self.failures.entry(key).or_insert(Failures {
+                start: now,
+                count: 0,
+            });
+            if now - entry.start > config.window {
+                *entry = Failures {
+                    start: now,
+                    count: 0,
+                };
+            }
+            entry.count += 1;
+            entry.count
+        };
+        if failures < config.max_failures {
+            return;
+        }
+
+        self.failures.remove(&key);
+        self.lock(key, config.duration);
+
+        Metrics::get().proxy.auth_lockouts_total.inc();
+        warn!(
+            event = "auth_lockout",
+            endpoint_id = %key.endpoint_id,
+            role = %key.role_name,
+            ip = %key.ip,
+            failures,
+            lockout = %humantime::format_duration(config.duration),
+            "locking out authentication after repeated failures",
+        );
+
+        if let Some(publisher) = self.publisher.get() {
+            let publisher = publisher.clone();
+            let notification = LockoutNotification {
+                key,
+                duration_ms: config.duration.as_millis() as u64,
+            };
+            tokio::spawn(async move {
+                if let Err(e) = publisher
+                    .lock()
+                    .await
+                    .try_publish_lockout(notification)
+                    .await
+                {
+                    warn!("failed to publish authentication lockout: {e}");
+                }
+            });
+        }
+    }
+
+    /// Lock out the key after a notification from another proxy.
+    pub(crate) fn handle_notification(&self, notification: LockoutNotification) {
+        if self.config.is_some() {
+            self.lock(
+                notification.key,
+                Duration::from_millis(notification.duration_ms),
+            );
+        }
+    }
+
+    fn lock(&self, key: LockoutKey, duration: Duration) {
+        let until = Instant::now() + duration;
+        self.locked_until
+            .entry(key)
+            .and_modify(|u| *u = (*u).max(until))
+            .or_insert(until);
+    }
+
+    /// Clean up expired state every 2k attempts.
+    fn maybe_gc(&self) {
+        let Some(config) = self.config else {
+            return;
+        };
+        if self.access_count.fetch_add(1, Ordering::AcqRel) % 2048 != 0 {
+            return;
+        }
+        let now = Instant::now();
+        self.failures
+            .retain(|_, failures| now - failures.start <= config.window);
+        self.locked_until.retain(|_, until| now < *until);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn key(ip: u8) -> LockoutKey {
+        LockoutKey::new(
+            &EndpointId::from("ep-lockout"),
+            &RoleName::from("alice"),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, ip)),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lockout_after_failures() {
+        let lockout = AuthLockout::new(Some(LockoutConfig {
+            max_failures: 3,
+            window: Duration::from_secs(60),
+            duration: Duration::from_secs(600),
+        }));
+
+        // failures outside of the window are forgotten
+        lockout.record_failure(key(1));
+        lockout.record_failure(key(1));
+        tokio::time::advance(Duration::from_secs(61)).await;
+        lockout.record_failure(key(1));
+        lockout.check(&key(1)).unwrap();
+
+        // a success resets the failures
+        lockout.record_failure(key(1));
+        lockout.record_success(&key(1));
+        lockout.record_failure(key(1));
+        lockout.record_failure(key(1));
+        lockout.check(&key(1)).unwrap();
+
+        lockout.record_failure(key(1));
+        let err = lockout.check(&key(1)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Too many failed authentication attempts"));
+        // other IPs are not affected
+        lockout.check(&key(2)).unwrap();
+
+        tokio::time::advance(Duration::from_secs(600)).await;
+        lockout.check(&key(1)).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lockout_notification() {
+        let lockout = AuthLockout::new(Some(LockoutConfig {
+            max_failures: 3,
+            window: Duration::from_secs(60),
+            duration: Duration::from_secs(600),
+        }));
+        let notification = LockoutNotification {
+            key: key(1),
+            duration_ms: 1000,
+        };
+        let text = serde_json::to_string(&notification).unwrap();
+        assert_eq!(
+            serde_json::from_str::<LockoutNotification>(&text).unwrap(),
+            notification
+        );
+
+        lockout.handle_notification(notification);
+        lockout.check(&key(1)).unwrap_err();
+        tokio::time::advance(Duration::from_secs(1)).await;
+        lockout.check(&key(1)).unwrap();
+
+        // disabled lockout ignores notifications
+        let disabled = AuthLockout::new(None);
+        disabled.handle_notification(LockoutNotification {
+            key: key(1),
+            duration_ms: 1000,
+        });
+        disabled.check(&key(1)).unwrap();
+    }
+}
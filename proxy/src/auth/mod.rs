@@ -15,6 +15,8 @@
 
 mod flow;
 pub(crate) use flow::*;
+
+pub mod lockout;
 use tokio::time::error::Elapsed;
 
 use crate::{
@@ -71,6 +73,9 @@ pub(crate) enum AuthErrorImpl {
     #[error("Too many connections to this endpoint. Please try again later.")]
     TooManyConnections,
 
+    #[error("Too many failed authentication attempts for user '{0}'. Please try again later.")]
+    LockedOut(Box<str>),
+
     #[error("Authentication timed out")]
     UserTimeout(Elapsed),
 
@@ -99,6 +104,10 @@ pub(crate) fn too_many_connections() -> Self {
         AuthErrorImpl::TooManyConnections.into()
     }
 
+    pub(crate) fn locked_out(user: impl Into<Box<str>>) -> Self {
+        AuthErrorImpl::LockedOut(user.into()).into()
+    }
+
     pub(crate) fn is_auth_failed(&self) -> bool {
         matches!(self.0.as_ref(), AuthErrorImpl::AuthFailed(_))
     }
@@ -131,6 +140,7 @@ fn to_string_client(&self) -> String {
             AuthErrorImpl::Io(_) => "Internal error".to_string(),
             AuthErrorImpl::IpAddressNotAllowed(_) => self.to_string(),
             AuthErrorImpl::TooManyConnections => self.to_string(),
+            AuthErrorImpl::LockedOut(_) => self.to_string(),
             AuthErrorImpl::UserTimeout(_) => self.to_string(),
             AuthErrorImpl::ConfirmationTimeout(_) => self.to_string(),
         }
@@ -150,6 +160,7 @@ fn get_error_kind(&self) -> crate::error::ErrorKind {
             AuthErrorImpl::Io(_) => crate::error::ErrorKind::ClientDisconnect,
             AuthErrorImpl::IpAddressNotAllowed(_) => crate::error::ErrorKind::User,
             AuthErrorImpl::TooManyConnections => crate::error::ErrorKind::RateLimit,
+            AuthErrorImpl::LockedOut(_) => crate::error::ErrorKind::RateLimit,
             AuthErrorImpl::UserTimeout(_) => crate::error::ErrorKind::User,
             AuthErrorImpl::ConfirmationTimeout(_) => crate::error::ErrorKind::User,
         }
@@ -1,4 +1,6 @@
-use std::{net::SocketAddr, pin::pin, str::FromStr, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap, net::SocketAddr, pin::pin, str::FromStr, sync::Arc, time::Duration,
+};
 
 use anyhow::{bail, ensure, Context};
 use camino::{Utf8Path, Utf8PathBuf};
@@ -12,6 +14,7 @@
             jwt::JwkCache,
             local::{LocalBackend, JWKS_ROLE_MAP},
         },
+        lockout::AuthLockout,
     },
     cancellation::CancellationHandlerMain,
     config::{self, AuthenticationConfig, HttpConfig, ProxyConfig, RetryConfig},
@@ -258,6 +261,8 @@ fn build_config(args: &LocalProxyCliArgs) -> anyhow::Result<&'static ProxyConfig
         client_conn_threshold: args.sql_over_http.sql_over_http_client_conn_threshold,
         max_request_size_bytes: args.sql_over_http.sql_over_http_max_request_size_bytes,
         max_response_size_bytes: args.sql_over_http.sql_over_http_max_response_size_bytes,
+        // Statements are cached only on connections pooled by the proxy.
+        max_prepared_statements: 0,
     };
 
     Ok(Box::leak(Box::new(ProxyConfig {
@@ -272,13 +277,17 @@ fn build_config(args: &LocalProxyCliArgs) -> anyhow::Result<&'static ProxyConfig
             rate_limiter_enabled: false,
             rate_limiter: BucketRateLimiter::new(vec![]),
             rate_limit_ip_subnet: 64,
+            auth_lockout: AuthLockout::new(None),
             ip_allowlist_check_enabled: true,
             is_auth_broker: false,
             accept_jwts: true,
+            accept_oidc_tokens: false,
             webauth_confirmation_timeout: Duration::ZERO,
         },
         proxy_protocol_v2: config::ProxyProtocolV2::Rejected,
+        tcp_pooling_mode: config::TcpPoolingMode::Session,
         handshake_timeout: Duration::from_secs(10),
+        session_drain_timeout: None,
         region: "local".into(),
         wake_compute_retry_config: RetryConfig::parse(RetryConfig::WAKE_COMPUTE_DEFAULT_VALUES)?,
         connect_compute_locks,
@@ -372,6 +381,8 @@ async fn refresh_config_inner(path: &Utf8Path) -> anyhow::Result<()> {
                 .map(RoleName::from)
                 .map(|s| RoleNameInt::from(&s))
                 .collect(),
+            role_claim: None,
+            role_mapping: HashMap::new(),
         })
     }
 
@@ -6,12 +6,14 @@
 use aws_config::provider_config::ProviderConfig;
 use aws_config::web_identity_token::WebIdentityTokenCredentialsProvider;
 use aws_config::Region;
+use camino::Utf8PathBuf;
 use futures::future::Either;
 use proxy::auth;
 use proxy::auth::backend::jwt::JwkCache;
 use proxy::auth::backend::AuthRateLimiter;
 use proxy::auth::backend::ConsoleRedirectBackend;
 use proxy::auth::backend::MaybeOwned;
+use proxy::auth::lockout::{AuthLockout, LockoutConfig};
 use proxy::cancellation::CancelMap;
 use proxy::cancellation::CancellationHandler;
 use proxy::config::remote_storage_from_toml;
@@ -20,11 +22,14 @@
 use proxy::config::HttpConfig;
 use proxy::config::ProjectInfoCacheOptions;
 use proxy::config::ProxyProtocolV2;
+use proxy::config::TcpPoolingMode;
 use proxy::context::parquet::ParquetUploadArgs;
 use proxy::control_plane;
+use proxy::drain::Listeners;
 use proxy::http;
 use proxy::http::health_server::AppMetrics;
 use proxy::metrics::Metrics;
+use proxy::rate_limiter::DistributedRateLimiter;
 use proxy::rate_limiter::EndpointRateLimiter;
 use proxy::rate_limiter::LeakyBucketConfig;
 use proxy::rate_limiter::RateBucketInfo;
@@ -45,7 +50,6 @@
 use std::net::SocketAddr;
 use std::pin::pin;
 use std::sync::Arc;
-use tokio::net::TcpListener;
 use tokio::sync::Mutex;
 use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
@@ -108,6 +112,9 @@ struct ProxyCliArgs {
     /// if this is not local proxy, this toggles whether we accept jwt or passwords for http
     #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
     is_auth_broker: bool,
+    /// toggles whether postgres clients can authenticate with OIDC access tokens
+    #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
+    accept_oidc_tokens: bool,
     /// path to TLS key for client postgres connections
     ///
     /// tls-key and tls-cert are for backwards compatibility, we can put all certs in one dir
@@ -121,9 +128,21 @@ struct ProxyCliArgs {
     /// path to directory with TLS certificates for client postgres connections
     #[clap(long)]
     certs_dir: Option<String>,
+    /// how often to check TLS certificate files for changes to reload them.
+    /// Certificates are also reloaded on SIGHUP
+    #[clap(long, default_value = "1m", value_parser = humantime::parse_duration)]
+    tls_certs_reload_interval: tokio::time::Duration,
     /// timeout for the TLS handshake
     #[clap(long, default_value = "15s", value_parser = humantime::parse_duration)]
     handshake_timeout: tokio::time::Duration,
+    /// how long client sessions may continue after the proxy started shutting down,
+    /// before they are closed. Sessions are not limited if not set.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    session_drain_timeout: Option<tokio::time::Duration>,
+    /// unix socket to take over the listening sockets from the previous proxy process,
+    /// and to hand them over to the next one
+    #[clap(long)]
+    listener_handover_socket: Option<Utf8PathBuf>,
     /// http endpoint to receive periodic metric updates
     #[clap(long)]
     metric_collection_endpoint: Option<String>,
@@ -171,9 +190,26 @@ struct ProxyCliArgs {
     /// The IP subnet to use when considering whether two IP addresses are considered the same.
     #[clap(long, default_value_t = 64)]
     auth_rate_limit_ip_subnet: u8,
+    /// Number of failed password attempts for an endpoint, role and IP within the lockout window,
+    /// after which further attempts are rejected. 0 disables the lockout.
+    #[clap(long, default_value_t = 0)]
+    auth_lockout_max_failures: u32,
+    /// Window in which failed password attempts are counted towards the lockout.
+    #[clap(long, default_value = "5m", value_parser = humantime::parse_duration)]
+    auth_lockout_window: std::time::Duration,
+    /// How long attempts are rejected after the lockout.
+    #[clap(long, default_value = "15m", value_parser = humantime::parse_duration)]
+    auth_lockout_duration: std::time::Duration,
     /// Redis rate limiter max number of requests per second.
     #[clap(long, default_values_t = RateBucketInfo::DEFAULT_SET)]
     redis_rps_limit: Vec<RateBucketInfo>,
+    /// Whether the endpoint and authentication rate limits are shared with other proxies through the regional redis.
+    /// Only the local limits apply while redis is unavailable.
+    #[clap(long, default_value_t = false, value_parser = clap::builder::BoolishValueParser::new(), action = clap::ArgAction::Set)]
+    distributed_rate_limit_enabled: bool,
+    /// How often the shared rate limits are synced with redis.
+    #[clap(long, default_value = "1s", value_parser = humantime::parse_duration)]
+    distributed_rate_limit_sync_interval: std::time::Duration,
     /// cache for `allowed_ips` (use `size=0` to disable)
     #[clap(long, default_value = config::CacheOptions::CACHE_DEFAULT_OPTIONS)]
     allowed_ips_cache: String,
@@ -238,6 +274,11 @@ struct ProxyCliArgs {
     #[clap(value_enum, long, default_value_t = ProxyProtocolV2::Supported)]
     proxy_protocol_v2: ProxyProtocolV2,
 
+    /// How compute connections of TCP clients are pooled. In transaction mode,
+    /// sizing of the pool is shared with the sql-over-http pool options.
+    #[clap(value_enum, long, default_value_t = TcpPoolingMode::Session)]
+    tcp_pooling_mode: TcpPoolingMode,
+
     /// Time the proxy waits for the webauth session to be confirmed by the control plane.
     #[clap(long, default_value = "2m", value_parser = humantime::parse_duration)]
     webauth_confirmation_timeout: std::time::Duration,
@@ -287,6 +328,10 @@ struct SqlOverHttpArgs {
 
     #[clap(long, default_value_t = 10 * 1024 * 1024)] // 10 MiB
     sql_over_http_max_response_size_bytes: usize,
+
+    /// How many prepared statements to cache for each pooled connection. 0 disables the cache.
+    #[clap(long, default_value_t = 64)]
+    sql_over_http_max_prepared_statements: usize,
 }
 
 #[tokio::main]
@@ -389,20 +434,22 @@ async fn main() -> anyhow::Result<()> {
         regional_redis_client.clone()
     };
 
+    let mut listeners = Listeners::inherit(args.listener_handover_socket.as_deref()).await?;
+
     // Check that we can bind to address before further initialization
     let http_address: SocketAddr = args.http.parse()?;
     info!("Starting http on {http_address}");
-    let http_listener = TcpListener::bind(http_address).await?.into_std()?;
+    let http_listener = listeners.bind("http", http_address).await?.into_std()?;
 
     let mgmt_address: SocketAddr = args.mgmt.parse()?;
     info!("Starting mgmt on {mgmt_address}");
-    let mgmt_listener = TcpListener::bind(mgmt_address).await?;
+    let mgmt_listener = listeners.bind("mgmt", mgmt_address).await?;
 
     let proxy_listener = if !args.is_auth_broker {
         let proxy_address: SocketAddr = args.proxy.parse()?;
         info!("Starting proxy on {proxy_address}");
 
-        Some(TcpListener::bind(proxy_address).await?)
+        Some(listeners.bind("proxy", proxy_address).await?)
     } else {
         None
     };
@@ -412,7 +459,7 @@ async fn main() -> anyhow::Result<()> {
     let serverless_listener = if let Some(serverless_address) = args.wss {
         let serverless_address: SocketAddr = serverless_address.parse()?;
         info!("Starting wss on {serverless_address}");
-        Some(TcpListener::bind(serverless_address).await?)
+        Some(listeners.bind("wss", serverless_address).await?)
     } else if args.is_auth_broker {
         bail!("wss arg must be present for auth-broker")
     } else {
@@ -434,6 +481,12 @@ async fn main() -> anyhow::Result<()> {
         )?))),
         None => None,
     };
+    if let Some(redis_publisher) = &redis_publisher {
+        config
+            .authentication_config
+            .auth_lockout
+            .set_publisher(redis_publisher.clone());
+    }
     let cancellation_handler = Arc::new(CancellationHandler::<
         Option<Arc<tokio::sync::Mutex<RedisPublisherClient>>>,
     >::new(
@@ -456,10 +509,17 @@ async fn main() -> anyhow::Result<()> {
         .map(|x| x.rps())
         .min_by(f64::total_cmp)
         .unwrap_or(EndpointRateLimiter::DEFAULT.rps);
-    let endpoint_rate_limiter = Arc::new(EndpointRateLimiter::new_with_shards(
-        LeakyBucketConfig { rps, max },
-        64,
-    ));
+    let mut endpoint_rate_limiter =
+        EndpointRateLimiter::new_with_shards(LeakyBucketConfig { rps, max }, 64);
+    if args.distributed_rate_limit_enabled {
+        endpoint_rate_limiter =
+            endpoint_rate_limiter.with_distributed(Arc::new(DistributedRateLimiter::new(
+                "endpoint",
+                args.endpoint_rps_limit.clone(),
+                args.distributed_rate_limit_sync_interval,
+            )));
+    }
+    let endpoint_rate_limiter = Arc::new(endpoint_rate_limiter);
 
     // client facing tasks. these will exit on error or on cancellation
     // cancellation returns Ok(())
@@ -508,7 +568,24 @@ async fn main() -> anyhow::Result<()> {
 
     // maintenance tasks. these never return unless there's an error
     let mut maintenance_tasks = JoinSet::new();
-    maintenance_tasks.spawn(proxy::handle_signals(cancellation_token.clone(), || {}));
+    let cert_resolver = config
+        .tls_config
+        .as_ref()
+        .map(|tls| tls.cert_resolver.clone());
+    maintenance_tasks.spawn(proxy::handle_signals(cancellation_token.clone(), {
+        let cert_resolver = cert_resolver.clone();
+        move || {
+            if let Some(cert_resolver) = &cert_resolver {
+                cert_resolver.reload();
+            }
+        }
+    }));
+    if let Some(cert_resolver) = cert_resolver {
+        maintenance_tasks.spawn(config::watch_tls_certs(
+            cert_resolver,
+            args.tls_certs_reload_interval,
+        ));
+    }
     maintenance_tasks.spawn(http::health_server::task_main(
         http_listener,
         AppMetrics {
@@ -519,6 +596,10 @@ async fn main() -> anyhow::Result<()> {
     ));
     maintenance_tasks.spawn(control_plane::mgmt::task_main(mgmt_listener));
 
+    if let Some(path) = args.listener_handover_socket.clone() {
+        maintenance_tasks.spawn(listeners.serve_handover(path, cancellation_token.clone()));
+    }
+
     if let Some(metrics_config) = &config.metric_collection {
         // TODO: Add gc regardles of the metric collection being enabled.
         maintenance_tasks.spawn(usage_metrics::task_main(metrics_config));
@@ -528,6 +609,21 @@ async fn main() -> anyhow::Result<()> {
         ));
     }
 
+    if args.distributed_rate_limit_enabled {
+        if let Some(client) = &regional_redis_client {
+            if let Some(limiter) = config.authentication_config.rate_limiter.distributed() {
+                let (limiter, con) = (limiter.clone(), client.clone());
+                maintenance_tasks.spawn(async move { limiter.sync(con).await });
+            }
+            if let Some(limiter) = endpoint_rate_limiter.distributed() {
+                let (limiter, con) = (limiter.clone(), client.clone());
+                maintenance_tasks.spawn(async move { limiter.sync(con).await });
+            }
+        } else {
+            warn!("distributed rate limits require the regional redis, only local limits apply");
+        }
+    }
+
     if let Either::Left(auth::Backend::ControlPlane(api, _)) = &auth_backend {
         if let proxy::control_plane::provider::ControlPlaneBackend::Management(api) = &**api {
             match (redis_notifications_client, regional_redis_client.clone()) {
@@ -539,6 +635,7 @@ async fn main() -> anyhow::Result<()> {
                             client,
                             cache.clone(),
                             cancel_map.clone(),
+                            &config.authentication_config.auth_lockout,
                             args.region.clone(),
                         ));
                     }
@@ -547,6 +644,7 @@ async fn main() -> anyhow::Result<()> {
                             client,
                             cache.clone(),
                             cancel_map.clone(),
+                            &config.authentication_config.auth_lockout,
                             args.region.clone(),
                         ));
                     }
@@ -631,6 +729,9 @@ fn build_config(args: &ProxyCliArgs) -> anyhow::Result<&'static ProxyConfig> {
     if !args.disable_dynamic_rate_limiter {
         bail!("dynamic rate limiter should be disabled");
     }
+    if args.distributed_rate_limit_sync_interval.is_zero() {
+        bail!("distributed-rate-limit-sync-interval must be positive");
+    }
 
     let config::ConcurrencyLockOptions {
         shards,
@@ -667,17 +768,34 @@ fn build_config(args: &ProxyCliArgs) -> anyhow::Result<&'static ProxyConfig> {
         client_conn_threshold: args.sql_over_http.sql_over_http_client_conn_threshold,
         max_request_size_bytes: args.sql_over_http.sql_over_http_max_request_size_bytes,
         max_response_size_bytes: args.sql_over_http.sql_over_http_max_response_size_bytes,
+        max_prepared_statements: args.sql_over_http.sql_over_http_max_prepared_statements,
     };
+    let mut rate_limiter = AuthRateLimiter::new(args.auth_rate_limit.clone());
+    if args.distributed_rate_limit_enabled {
+        rate_limiter = rate_limiter.with_distributed(Arc::new(DistributedRateLimiter::new(
+            "auth",
+            args.auth_rate_limit.clone(),
+            args.distributed_rate_limit_sync_interval,
+        )));
+    }
     let authentication_config = AuthenticationConfig {
         jwks_cache: JwkCache::default(),
         thread_pool,
         scram_protocol_timeout: args.scram_protocol_timeout,
         rate_limiter_enabled: args.auth_rate_limit_enabled,
-        rate_limiter: AuthRateLimiter::new(args.auth_rate_limit.clone()),
+        rate_limiter,
         rate_limit_ip_subnet: args.auth_rate_limit_ip_subnet,
+        auth_lockout: AuthLockout::new((args.auth_lockout_max_failures > 0).then_some(
+            LockoutConfig {
+                max_failures: args.auth_lockout_max_failures,
+                window: args.auth_lockout_window,
+                duration: args.auth_lockout_duration,
+            },
+        )),
         ip_allowlist_check_enabled: !args.is_private_access_proxy,
         is_auth_broker: args.is_auth_broker,
         accept_jwts: args.is_auth_broker,
+        accept_oidc_tokens: args.accept_oidc_tokens,
         webauth_confirmation_timeout: args.webauth_confirmation_timeout,
     };
 
@@ -688,7 +806,9 @@ fn build_config(args: &ProxyCliArgs) -> anyhow::Result<&'static ProxyConfig> {
         http_config,
         authentication_config,
         proxy_protocol_v2: args.proxy_protocol_v2,
+        tcp_pooling_mode: args.tcp_pooling_mode,
         handshake_timeout: args.handshake_timeout,
+        session_drain_timeout: args.session_drain_timeout,
         region: args.region.clone(),
         wake_compute_retry_config: config::RetryConfig::parse(&args.wake_compute_retry)?,
         connect_compute_locks,
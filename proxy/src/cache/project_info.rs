@@ -16,7 +16,7 @@
 use crate::{
     auth::IpPattern,
     config::ProjectInfoCacheOptions,
-    control_plane::AuthSecret,
+    control_plane::{messages::QueryPolicy, AuthSecret},
     intern::{EndpointIdInt, ProjectIdInt, RoleNameInt},
     EndpointId, RoleName,
 };
@@ -27,6 +27,7 @@
 pub(crate) trait ProjectInfoCache {
     fn invalidate_allowed_ips_for_project(&self, project_id: ProjectIdInt);
     fn invalidate_role_secret_for_project(&self, project_id: ProjectIdInt, role_name: RoleNameInt);
+    fn invalidate_query_policies_for_project(&self, project_id: ProjectIdInt);
     async fn decrement_active_listeners(&self);
     async fn increment_active_listeners(&self);
 }
@@ -55,6 +56,7 @@ fn from(value: T) -> Self {
 struct EndpointInfo {
     secret: std::collections::HashMap<RoleNameInt, Entry<Option<AuthSecret>>>,
     allowed_ips: Option<Entry<Arc<Vec<IpPattern>>>>,
+    query_policy: std::collections::HashMap<RoleNameInt, Entry<Arc<QueryPolicy>>>,
 }
 
 impl EndpointInfo {
@@ -96,12 +98,35 @@ pub(crate) fn get_allowed_ips(
         }
         None
     }
+
+    pub(crate) fn get_query_policy(
+        &self,
+        role_name: RoleNameInt,
+        valid_since: Instant,
+        ignore_cache_since: Option<Instant>,
+    ) -> Option<(Arc<QueryPolicy>, bool)> {
+        if let Some(policy) = self.query_policy.get(&role_name) {
+            if valid_since < policy.created_at {
+                return Some((
+                    policy.value.clone(),
+                    Self::check_ignore_cache(ignore_cache_since, policy.created_at),
+                ));
+            }
+        }
+        None
+    }
     pub(crate) fn invalidate_allowed_ips(&mut self) {
         self.allowed_ips = None;
     }
     pub(crate) fn invalidate_role_secret(&mut self, role_name: RoleNameInt) {
         self.secret.remove(&role_name);
     }
+    pub(crate) fn invalidate_query_policy(&mut self, role_name: RoleNameInt) {
+        self.query_policy.remove(&role_name);
+    }
+    pub(crate) fn invalidate_query_policies(&mut self) {
+        self.query_policy.clear();
+    }
 }
 
 /// Cache for project info.
@@ -153,6 +178,19 @@ fn invalidate_role_secret_for_project(&self, project_id: ProjectIdInt, role_name
             }
         }
     }
+    fn invalidate_query_policies_for_project(&self, project_id: ProjectIdInt) {
+        info!("invalidating query policies for project `{}`", project_id);
+        let endpoints = self
+            .project2ep
+            .get(&project_id)
+            .map(|kv| kv.value().clone())
+            .unwrap_or_default();
+        for endpoint_id in endpoints {
+            if let Some(mut endpoint_info) = self.cache.get_mut(&endpoint_id) {
+                endpoint_info.invalidate_query_policies();
+            }
+        }
+    }
     async fn decrement_active_listeners(&self) {
         let mut listeners_guard = self.active_listeners_lock.lock().await;
         if *listeners_guard == 0 {
@@ -230,6 +268,29 @@ pub(crate) fn get_allowed_ips(
         }
         Some(Cached::new_uncached(value))
     }
+    pub(crate) fn get_query_policy(
+        &self,
+        endpoint_id: &EndpointId,
+        role_name: &RoleName,
+    ) -> Option<Cached<&Self, Arc<QueryPolicy>>> {
+        let endpoint_id = EndpointIdInt::get(endpoint_id)?;
+        let role_name = RoleNameInt::get(role_name)?;
+        let (valid_since, ignore_cache_since) = self.get_cache_times();
+        let endpoint_info = self.cache.get(&endpoint_id)?;
+        let (value, ignore_cache) =
+            endpoint_info.get_query_policy(role_name, valid_since, ignore_cache_since)?;
+        if !ignore_cache {
+            let cached = Cached {
+                token: Some((
+                    self,
+                    CachedLookupInfo::new_query_policy(endpoint_id, role_name),
+                )),
+                value,
+            };
+            return Some(cached);
+        }
+        Some(Cached::new_uncached(value))
+    }
     pub(crate) fn insert_role_secret(
         &self,
         project_id: ProjectIdInt,
@@ -260,6 +321,23 @@ pub(crate) fn insert_allowed_ips(
         self.insert_project2endpoint(project_id, endpoint_id);
         self.cache.entry(endpoint_id).or_default().allowed_ips = Some(allowed_ips.into());
     }
+    pub(crate) fn insert_query_policy(
+        &self,
+        project_id: ProjectIdInt,
+        endpoint_id: EndpointIdInt,
+        role_name: RoleNameInt,
+        policy: Arc<QueryPolicy>,
+    ) {
+        if self.cache.len() >= self.config.size {
+            // If there are too many entries, wait until the next gc cycle.
+            return;
+        }
+        self.insert_project2endpoint(project_id, endpoint_id);
+        let mut entry = self.cache.entry(endpoint_id).or_default();
+        if entry.query_policy.len() < self.config.max_roles {
+            entry.query_policy.insert(role_name, policy.into());
+        }
+    }
     fn insert_project2endpoint(&self, project_id: ProjectIdInt, endpoint_id: EndpointIdInt) {
         if let Some(mut endpoints) = self.project2ep.get_mut(&project_id) {
             endpoints.insert(endpoint_id);
@@ -338,11 +416,18 @@ pub(self) fn new_allowed_ips(endpoint_id: EndpointIdInt) -> Self {
             lookup_type: LookupType::AllowedIps,
         }
     }
+    pub(self) fn new_query_policy(endpoint_id: EndpointIdInt, role_name: RoleNameInt) -> Self {
+        Self {
+            endpoint_id,
+            lookup_type: LookupType::QueryPolicy(role_name),
+        }
+    }
 }
 
 enum LookupType {
     RoleSecret(RoleNameInt),
     AllowedIps,
+    QueryPolicy(RoleNameInt),
 }
 
 impl Cache for ProjectInfoCacheImpl {
@@ -364,6 +449,11 @@ fn invalidate(&self, key: &Self::LookupInfo<SmolStr>) {
                     endpoint_info.invalidate_allowed_ips();
                 }
             }
+            LookupType::QueryPolicy(role_name) => {
+                if let Some(mut endpoint_info) = self.cache.get_mut(&key.endpoint_id) {
+                    endpoint_info.invalidate_query_policy(*role_name);
+                }
+            }
         }
     }
 }
@@ -504,6 +594,23 @@ async fn test_project_info_cache_invalidations() {
         let cached = cache.get_allowed_ips(&endpoint_id).unwrap();
         assert!(!cached.cached());
         assert_eq!(cached.value, allowed_ips);
+
+        let policy = Arc::new(QueryPolicy {
+            read_only: true,
+            deny: vec![],
+        });
+        cache.insert_query_policy(
+            (&project_id).into(),
+            (&endpoint_id).into(),
+            (&user1).into(),
+            policy.clone(),
+        );
+        let cached = cache.get_query_policy(&endpoint_id, &user1).unwrap();
+        assert_eq!(cached.value, policy);
+        cache.invalidate_query_policies_for_project((&project_id).into());
+        assert!(cache.get_query_policy(&endpoint_id, &user1).is_none());
+        // Other data of the project is not affected.
+        assert!(cache.get_role_secret(&endpoint_id, &user1).is_some());
     }
 
     #[tokio::test]
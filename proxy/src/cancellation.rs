@@ -189,6 +189,13 @@ pub(crate) fn enable_query_cancellation(&self, cancel_closure: CancelClosure) ->
 
         self.key
     }
+
+    /// Forget the cancel token, e.g. because the compute connection is given
+    /// to another client. Cancel requests with this session's key are ignored
+    /// until cancellation is enabled again.
+    pub(crate) fn disable_query_cancellation(&self) {
+        self.cancellation_handler.map.insert(self.key, None);
+    }
 }
 
 impl<P> Drop for Session<P> {
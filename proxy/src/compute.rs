@@ -263,6 +263,8 @@ pub(crate) struct PostgresConnection {
     >,
     /// PostgreSQL connection parameters.
     pub(crate) params: std::collections::HashMap<String, String>,
+    /// Process id of the backend serving the connection.
+    pub(crate) pid: i32,
     /// Query cancellation token.
     pub(crate) cancel_closure: CancelClosure,
     /// Labels for proxy's metrics.
@@ -327,6 +329,7 @@ pub(crate) async fn connect(
         let connection = PostgresConnection {
             stream,
             params,
+            pid: client.get_process_id(),
             cancel_closure,
             aux,
             _guage: Metrics::get().proxy.db_connections.guard(ctx.protocol()),
@@ -337,7 +340,7 @@ pub(crate) async fn connect(
 }
 
 /// Retrieve `options` from a startup message, dropping all proxy-secific flags.
-fn filtered_options(params: &StartupMessageParams) -> Option<String> {
+pub(crate) fn filtered_options(params: &StartupMessageParams) -> Option<String> {
     #[allow(unstable_name_collisions)]
     let options: String = params
         .options_raw()?
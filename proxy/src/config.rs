@@ -1,12 +1,17 @@
 use crate::{
-    auth::backend::{jwt::JwkCache, AuthRateLimiter},
+    auth::{
+        backend::{jwt::JwkCache, AuthRateLimiter},
+        lockout::AuthLockout,
+    },
     control_plane::locks::ApiLocks,
+    metrics::{Metrics, Outcome, TlsCertificate},
     rate_limiter::{RateBucketInfo, RateLimitAlgorithm, RateLimiterConfig},
     scram::threadpool::ThreadPool,
     serverless::{cancel_set::CancelSet, GlobalConnPoolOptions},
     Host,
 };
 use anyhow::{bail, ensure, Context, Ok};
+use arc_swap::ArcSwap;
 use clap::ValueEnum;
 use itertools::Itertools;
 use remote_storage::RemoteStorageConfig;
@@ -17,9 +22,11 @@
 use sha2::{Digest, Sha256};
 use std::{
     collections::{HashMap, HashSet},
+    convert::Infallible,
+    path::PathBuf,
     str::FromStr,
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use tracing::{error, info};
 use x509_parser::oid_registry;
@@ -31,8 +38,11 @@ pub struct ProxyConfig {
     pub http_config: HttpConfig,
     pub authentication_config: AuthenticationConfig,
     pub proxy_protocol_v2: ProxyProtocolV2,
+    pub tcp_pooling_mode: TcpPoolingMode,
     pub region: String,
     pub handshake_timeout: Duration,
+    /// How long sessions may continue once the proxy started draining. Unbounded if not set.
+    pub session_drain_timeout: Option<Duration>,
     pub wake_compute_retry_config: RetryConfig,
     pub connect_compute_locks: ApiLocks<Host>,
     pub connect_to_compute_retry_config: RetryConfig,
@@ -48,6 +58,16 @@ pub enum ProxyProtocolV2 {
     Rejected,
 }
 
+#[derive(Copy, Clone, Debug, ValueEnum, PartialEq)]
+pub enum TcpPoolingMode {
+    /// Each client gets a dedicated compute connection for the whole session.
+    Session,
+    /// Compute connections are shared between clients and are held by a client
+    /// only while it's in a transaction. Session state, like settings or SQL
+    /// level prepared statements, is reset between transactions.
+    Transaction,
+}
+
 #[derive(Debug)]
 pub struct MetricCollectionConfig {
     pub endpoint: reqwest::Url,
@@ -57,7 +77,6 @@ pub struct MetricCollectionConfig {
 
 pub struct TlsConfig {
     pub config: Arc<rustls::ServerConfig>,
-    pub common_names: HashSet<String>,
     pub cert_resolver: Arc<CertResolver>,
 }
 
@@ -68,6 +87,7 @@ pub struct HttpConfig {
     pub client_conn_threshold: u64,
     pub max_request_size_bytes: u64,
     pub max_response_size_bytes: usize,
+    pub max_prepared_statements: usize,
 }
 
 pub struct AuthenticationConfig {
@@ -76,10 +96,12 @@ pub struct AuthenticationConfig {
     pub rate_limiter_enabled: bool,
     pub rate_limiter: AuthRateLimiter,
     pub rate_limit_ip_subnet: u8,
+    pub auth_lockout: AuthLockout,
     pub ip_allowlist_check_enabled: bool,
     pub jwks_cache: JwkCache,
     pub is_auth_broker: bool,
     pub accept_jwts: bool,
+    pub accept_oidc_tokens: bool,
     pub webauth_confirmation_timeout: tokio::time::Duration,
 }
 
@@ -87,6 +109,17 @@ impl TlsConfig {
     pub fn to_server_config(&self) -> Arc<rustls::ServerConfig> {
         self.config.clone()
     }
+
+    /// Server config for handshakes of Postgres clients, along with the
+    /// certificates it presents. See [`HandshakeConfig`].
+    pub fn handshake_config(&self) -> Arc<HandshakeConfig> {
+        self.cert_resolver.handshake_config()
+    }
+
+    /// Common names of the currently loaded certificates.
+    pub fn common_names(&self) -> Arc<HashSet<String>> {
+        self.cert_resolver.common_names()
+    }
 }
 
 /// <https://github.com/postgres/postgres/blob/ca481d3c9ab7bf69ff0c8d71ad3951d407f6a33c/src/include/libpq/pqcomm.h#L159>
@@ -98,50 +131,55 @@ pub fn configure_tls(
     cert_path: &str,
     certs_dir: Option<&String>,
 ) -> anyhow::Result<TlsConfig> {
-    let mut cert_resolver = CertResolver::new();
-
-    // add default certificate
-    cert_resolver.add_cert_path(key_path, cert_path, true)?;
-
-    // add extra certificates
-    if let Some(certs_dir) = certs_dir {
-        for entry in std::fs::read_dir(certs_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                // file names aligned with default cert-manager names
-                let key_path = path.join("tls.key");
-                let cert_path = path.join("tls.crt");
-                if key_path.exists() && cert_path.exists() {
-                    cert_resolver.add_cert_path(
-                        &key_path.to_string_lossy(),
-                        &cert_path.to_string_lossy(),
-                        false,
-                    )?;
-                }
-            }
-        }
-    }
-
-    let common_names = cert_resolver.get_common_names();
+    let cert_resolver = Arc::new(CertResolver::from_paths(
+        key_path,
+        cert_path,
+        certs_dir.cloned(),
+    )?);
 
-    let cert_resolver = Arc::new(cert_resolver);
+    Ok(TlsConfig {
+        config: Arc::new(server_config(cert_resolver.clone())),
+        cert_resolver,
+    })
+}
 
+fn server_config(
+    cert_resolver: Arc<dyn rustls::server::ResolvesServerCert>,
+) -> rustls::ServerConfig {
     // allow TLS 1.2 to be compatible with older client libraries
     let mut config = rustls::ServerConfig::builder_with_protocol_versions(&[
         &rustls::version::TLS13,
         &rustls::version::TLS12,
     ])
     .with_no_client_auth()
-    .with_cert_resolver(cert_resolver.clone());
+    .with_cert_resolver(cert_resolver);
 
     config.alpn_protocols = vec![PG_ALPN_PROTOCOL.to_vec()];
 
-    Ok(TlsConfig {
-        config: Arc::new(config),
-        common_names,
-        cert_resolver,
-    })
+    config
+}
+
+/// Reload certificates when files they are loaded from change. Reload on
+/// SIGHUP is done with [`CertResolver::reload`] directly.
+pub async fn watch_tls_certs(
+    cert_resolver: Arc<CertResolver>,
+    interval: Duration,
+) -> anyhow::Result<Infallible> {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut last_mtimes = cert_resolver.source_mtimes();
+    loop {
+        ticker.tick().await;
+        let mtimes = cert_resolver.source_mtimes();
+        if mtimes == last_mtimes {
+            continue;
+        }
+        info!("TLS certificate files changed, reloading");
+        // On failure, e.g. if the files are caught in the middle of an
+        // update, keep the old certificates and retry on the next change.
+        cert_resolver.reload();
+        last_mtimes = mtimes;
+    }
 }
 
 /// Channel binding parameter
@@ -204,17 +242,83 @@ pub fn supported(&self) -> bool {
     }
 }
 
-#[derive(Default, Debug)]
-pub struct CertResolver {
-    certs: HashMap<String, (Arc<rustls::sign::CertifiedKey>, TlsServerEndPoint)>,
-    default: Option<(Arc<rustls::sign::CertifiedKey>, TlsServerEndPoint)>,
+/// Where certificates are loaded from.
+#[derive(Debug, Clone)]
+struct CertSource {
+    key_path: String,
+    cert_path: String,
+    certs_dir: Option<String>,
 }
 
-impl CertResolver {
-    pub fn new() -> Self {
-        Self::default()
+impl CertSource {
+    fn load(&self) -> anyhow::Result<Certs> {
+        let mut certs = Certs::default();
+
+        // add default certificate
+        certs.add_cert_path(&self.key_path, &self.cert_path, true)?;
+
+        // add extra certificates
+        if let Some(certs_dir) = &self.certs_dir {
+            for entry in std::fs::read_dir(certs_dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                if path.is_dir() {
+                    // file names aligned with default cert-manager names
+                    let key_path = path.join("tls.key");
+                    let cert_path = path.join("tls.crt");
+                    if key_path.exists() && cert_path.exists() {
+                        certs.add_cert_path(
+                            &key_path.to_string_lossy(),
+                            &cert_path.to_string_lossy(),
+                            false,
+                        )?;
+                    }
+                }
+            }
+        }
+
+        Ok(certs)
     }
 
+    /// Modification times of all certificate files, to detect changes. Symlinks
+    /// are followed, as mounted kubernetes secrets are updated by swapping them.
+    fn mtimes(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let mut paths = vec![
+            PathBuf::from(&self.key_path),
+            PathBuf::from(&self.cert_path),
+        ];
+        if let Some(certs_dir) = &self.certs_dir {
+            for entry in std::fs::read_dir(certs_dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    paths.push(path.join("tls.key"));
+                    paths.push(path.join("tls.crt"));
+                }
+            }
+        }
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| {
+                let mtime = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, mtime)
+            })
+            .collect()
+    }
+}
+
+/// Certificates by common name, along with their channel binding, which are
+/// swapped together on reload.
+#[derive(Default, Debug, Clone)]
+struct Certs {
+    certs: HashMap<String, (Arc<rustls::sign::CertifiedKey>, TlsServerEndPoint)>,
+    default: Option<(Arc<rustls::sign::CertifiedKey>, TlsServerEndPoint)>,
+    common_names: Arc<HashSet<String>>,
+    /// Expiration time by common name, in seconds since the epoch.
+    not_after: HashMap<String, i64>,
+}
+
+impl Certs {
     fn add_cert_path(
         &mut self,
         key_path: &str,
@@ -248,7 +352,7 @@ fn add_cert_path(
         self.add_cert(priv_key, cert_chain, is_default)
     }
 
-    pub fn add_cert(
+    fn add_cert(
         &mut self,
         priv_key: PrivateKeyDer<'static>,
         cert_chain: Vec<CertificateDer<'static>>,
@@ -256,13 +360,14 @@ pub fn add_cert(
     ) -> anyhow::Result<()> {
         let key = sign::any_supported_type(&priv_key).context("invalid private key")?;
 
-        let first_cert = &cert_chain[0];
+        let first_cert = cert_chain.first().context("missing certificate")?;
         let tls_server_end_point = TlsServerEndPoint::new(first_cert)?;
         let pem = x509_parser::parse_x509_certificate(first_cert)
             .context("Failed to parse PEM object from cerficiate")?
             .1;
 
         let common_name = pem.subject().to_string();
+        let not_after = pem.validity().not_after.timestamp();
 
         // We need to get the canonical name for this certificate so we can match them against any domain names
         // seen within the proxy codebase.
@@ -291,13 +396,122 @@ pub fn add_cert(
             self.default = Some((cert.clone(), tls_server_end_point));
         }
 
+        self.not_after.insert(common_name.clone(), not_after);
         self.certs.insert(common_name, (cert, tls_server_end_point));
+        self.common_names = Arc::new(self.certs.keys().cloned().collect());
 
         Ok(())
     }
+}
+
+/// Selects certificate by SNI. Certificates can be reloaded from the files
+/// they were loaded from without restart.
+#[derive(Default, Debug)]
+pub struct CertResolver {
+    current: ArcSwap<HandshakeConfig>,
+    source: Option<CertSource>,
+}
 
-    pub fn get_common_names(&self) -> HashSet<String> {
-        self.certs.keys().map(|s| s.to_string()).collect()
+impl CertResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the default certificate and, if given, certificates from
+    /// subdirectories of `certs_dir`.
+    pub fn from_paths(
+        key_path: &str,
+        cert_path: &str,
+        certs_dir: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let source = CertSource {
+            key_path: key_path.to_owned(),
+            cert_path: cert_path.to_owned(),
+            certs_dir,
+        };
+        let resolver = CertResolver {
+            current: ArcSwap::from_pointee(HandshakeConfig::new(source.load()?)),
+            source: Some(source),
+        };
+        resolver.report_expiry(None);
+        Ok(resolver)
+    }
+
+    pub fn add_cert(
+        &mut self,
+        priv_key: PrivateKeyDer<'static>,
+        cert_chain: Vec<CertificateDer<'static>>,
+        is_default: bool,
+    ) -> anyhow::Result<()> {
+        let mut certs = Certs::clone(&self.current.load().certs);
+        certs.add_cert(priv_key, cert_chain, is_default)?;
+        self.current.store(Arc::new(HandshakeConfig::new(certs)));
+        Ok(())
+    }
+
+    /// Reload certificates from their files. If any of them fails to load,
+    /// the current ones are kept. Returns whether certificates were reloaded.
+    pub fn reload(&self) -> bool {
+        let Some(source) = &self.source else {
+            return false;
+        };
+        let outcome = match source.load() {
+            std::result::Result::Ok(certs) => {
+                info!(common_names = ?certs.common_names, "reloaded TLS certificates");
+                let previous = self.current.swap(Arc::new(HandshakeConfig::new(certs)));
+                self.report_expiry(Some(&previous.certs));
+                Outcome::Success
+            }
+            Err(e) => {
+                error!("failed to reload TLS certificates, keeping the current ones: {e:#}");
+                Outcome::Failed
+            }
+        };
+        Metrics::get()
+            .proxy
+            .tls_certificate_reloads_total
+            .inc(outcome);
+        matches!(outcome, Outcome::Success)
+    }
+
+    fn source_mtimes(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        self.source
+            .as_ref()
+            .map(CertSource::mtimes)
+            .unwrap_or_default()
+    }
+
+    /// Set expiry gauges of the loaded certificates, removing the ones of
+    /// `previous` certificates which are not loaded anymore.
+    fn report_expiry(&self, previous: Option<&Certs>) {
+        let metric = &Metrics::get()
+            .proxy
+            .tls_certificate_expiry_timestamp_seconds;
+        let certs = &self.current.load().certs;
+        for (common_name, not_after) in &certs.not_after {
+            metric.set(TlsCertificate { common_name }, *not_after);
+        }
+        for common_name in previous.iter().flat_map(|p| p.not_after.keys()) {
+            if !certs.not_after.contains_key(common_name) {
+                metric.remove_metric(metric.with_labels(TlsCertificate { common_name }));
+            }
+        }
+    }
+
+    pub fn common_names(&self) -> Arc<HashSet<String>> {
+        self.current.load().certs.common_names.clone()
+    }
+
+    /// Server config presenting the current certificates.
+    pub fn handshake_config(&self) -> Arc<HandshakeConfig> {
+        self.current.load_full()
+    }
+
+    pub fn resolve(
+        &self,
+        server_name: Option<&str>,
+    ) -> Option<(Arc<rustls::sign::CertifiedKey>, TlsServerEndPoint)> {
+        self.current.load().certs.resolve(server_name)
     }
 }
 
@@ -310,8 +524,8 @@ fn resolve(
     }
 }
 
-impl CertResolver {
-    pub fn resolve(
+impl Certs {
+    fn resolve(
         &self,
         server_name: Option<&str>,
     ) -> Option<(Arc<rustls::sign::CertifiedKey>, TlsServerEndPoint)> {
@@ -348,6 +562,49 @@ pub fn resolve(
     }
 }
 
+impl rustls::server::ResolvesServerCert for Certs {
+    fn resolve(
+        &self,
+        client_hello: rustls::server::ClientHello<'_>,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        self.resolve(client_hello.server_name()).map(|x| x.0)
+    }
+}
+
+/// Server config resolving certificates from a fixed set of them. It is built
+/// once per reload and shared by handshakes until the next one, so SCRAM can
+/// bind to the certificate the client has got rather than to the current one.
+#[derive(Debug)]
+pub struct HandshakeConfig {
+    pub server_config: Arc<rustls::ServerConfig>,
+    certs: Arc<Certs>,
+}
+
+impl HandshakeConfig {
+    fn new(certs: Certs) -> Self {
+        let certs = Arc::new(certs);
+        HandshakeConfig {
+            server_config: Arc::new(server_config(certs.clone())),
+            certs,
+        }
+    }
+
+    /// Channel binding of the certificate presented in a handshake with this
+    /// config. A resumed session doesn't resolve a certificate, so it gets the
+    /// one which would be presented for the server name.
+    pub fn tls_server_end_point(&self, server_name: Option<&str>) -> Option<TlsServerEndPoint> {
+        self.certs
+            .resolve(server_name)
+            .map(|(_, end_point)| end_point)
+    }
+}
+
+impl Default for HandshakeConfig {
+    fn default() -> Self {
+        HandshakeConfig::new(Certs::default())
+    }
+}
+
 #[derive(Debug)]
 pub struct EndpointCacheConfig {
     /// Batch size to receive all endpoints on the startup.
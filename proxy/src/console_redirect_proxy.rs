@@ -6,6 +6,7 @@
 use crate::{
     cancellation::{CancellationHandlerMain, CancellationHandlerMainInternal},
     context::RequestMonitoring,
+    drain,
     error::ReportableError,
     metrics::{Metrics, NumClientConnectionsGuard},
     protocol2::read_proxy_protocol,
@@ -53,6 +54,8 @@ pub async fn task_main(
         let cancellation_handler = Arc::clone(&cancellation_handler);
 
         tracing::info!(protocol = "tcp", %session_id, "accepted new TCP connection");
+        let mut deadline =
+            drain::session_deadline(cancellation_token.clone(), config.session_drain_timeout);
 
         connections.spawn(async move {
             let (socket, peer_addr) = match read_proxy_protocol(socket).await {
@@ -99,7 +102,9 @@ pub async fn task_main(
                 )
                 .instrument(span.clone()),
             );
-            let res = startup.await;
+            let Some(res) = drain::before_deadline(&mut deadline, startup).await else {
+                return;
+            };
 
             match res {
                 Err(e) => {
@@ -113,7 +118,7 @@ pub async fn task_main(
                 Ok(Some(p)) => {
                     ctx.set_success();
                     ctx.log_connect();
-                    match p.proxy_pass().instrument(span.clone()).await {
+                    match p.proxy_pass(&ctx, deadline).instrument(span.clone()).await {
                         Ok(()) => {}
                         Err(ErrorSource::Client(e)) => {
                             error!(parent: &span, "per-client task finished with an IO error from the client: {e:#}");
@@ -210,6 +215,7 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
         client: stream,
         aux: node.aux.clone(),
         compute: node,
+        pooling: None,
         _req: request_gauge,
         _conn: conn_gauge,
         _cancel: session,
@@ -77,6 +77,8 @@ pub(crate) enum AuthMethod {
     ScramSha256,
     ScramSha256Plus,
     Cleartext,
+    /// OIDC access token, as the password or through SASL OAUTHBEARER.
+    Oidc,
 }
 
 impl Clone for RequestMonitoring {
@@ -139,6 +139,7 @@ fn from(value: &RequestMonitoringInner) -> Self {
                 super::AuthMethod::ScramSha256 => "scram_sha_256",
                 super::AuthMethod::ScramSha256Plus => "scram_sha_256_plus",
                 super::AuthMethod::Cleartext => "cleartext",
+                super::AuthMethod::Oidc => "oidc",
             }),
             protocol: value.protocol.as_str(),
             region: value.region,
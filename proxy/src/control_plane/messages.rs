@@ -1,5 +1,6 @@
 use measured::FixedCardinalityLabel;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::{self, Display};
 
 use crate::auth::IpPattern;
@@ -224,6 +225,8 @@ pub(crate) struct GetRoleSecret {
     pub(crate) role_secret: Box<str>,
     pub(crate) allowed_ips: Option<Vec<IpPattern>>,
     pub(crate) project_id: Option<ProjectIdInt>,
+    #[serde(default)]
+    pub(crate) query_policy: QueryPolicy,
 }
 
 // Manually implement debug to omit sensitive info.
@@ -233,6 +236,67 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     }
 }
 
+/// Response which holds a short-lived password of the role for a session.
+/// Returned by the `/proxy_get_session_password` API method.
+#[derive(Deserialize)]
+pub(crate) struct GetSessionPassword {
+    pub(crate) password: Box<str>,
+}
+
+// Manually implement debug to omit sensitive info.
+impl fmt::Debug for GetSessionPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GetSessionPassword").finish_non_exhaustive()
+    }
+}
+
+/// Restrictions on queries a role may run over SQL-over-HTTP,
+/// see [`crate::serverless::query_policy`].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct QueryPolicy {
+    /// Run all queries in read-only transactions.
+    #[serde(default)]
+    pub(crate) read_only: bool,
+    /// Classes of statements the role is not allowed to run.
+    #[serde(default)]
+    pub(crate) deny: Vec<StatementClass>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, FixedCardinalityLabel)]
+#[serde(rename_all = "snake_case")]
+#[label(singleton = "class")]
+pub enum StatementClass {
+    /// Schema and privilege changes: CREATE, ALTER, DROP, GRANT, SET ROLE
+    /// and so on, and maintenance like VACUUM or REFRESH MATERIALIZED VIEW.
+    Ddl,
+    /// Data changes: INSERT, UPDATE, DELETE and MERGE, and explicit LOCK.
+    Dml,
+    /// COPY to or from a program running on the compute.
+    CopyProgram,
+    /// DO, CALL and EXECUTE, which can run statements of any class,
+    /// so they should be denied together with other classes.
+    Procedural,
+    /// Transaction control and transaction characteristics.
+    /// Always denied for read-only roles.
+    Transaction,
+    /// Statements proxy doesn't recognize. Denied whenever the role is
+    /// read-only or denied any class.
+    Unrecognized,
+}
+
+impl StatementClass {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            StatementClass::Ddl => "ddl",
+            StatementClass::Dml => "dml",
+            StatementClass::CopyProgram => "copy_program",
+            StatementClass::Procedural => "procedural",
+            StatementClass::Transaction => "transaction",
+            StatementClass::Unrecognized => "unrecognized",
+        }
+    }
+}
+
 /// Response which holds compute node's `host:port` pair.
 /// Returned by the `/proxy_wake_compute` API method.
 #[derive(Debug, Deserialize)]
@@ -358,6 +422,12 @@ pub struct JwksSettings {
     pub provider_name: String,
     pub jwt_audience: Option<String>,
     pub role_names: Vec<RoleNameInt>,
+    /// Claim of the token which must grant the role.
+    #[serde(default)]
+    pub role_claim: Option<String>,
+    /// Role granted by each value of the role claim.
+    #[serde(default)]
+    pub role_mapping: HashMap<String, RoleNameInt>,
 }
 
 #[cfg(test)]
@@ -474,6 +544,22 @@ fn parse_get_role_secret() -> anyhow::Result<()> {
             "project_id": "project",
         });
         serde_json::from_str::<GetRoleSecret>(&json.to_string())?;
+        let json = json!({
+            "role_secret": "secret",
+            "project_id": "project",
+            "query_policy": {
+                "read_only": true,
+                "deny": ["ddl", "copy_program"],
+            },
+        });
+        let body = serde_json::from_str::<GetRoleSecret>(&json.to_string())?;
+        assert_eq!(
+            body.query_policy,
+            QueryPolicy {
+                read_only: true,
+                deny: vec![StatementClass::Ddl, StatementClass::CopyProgram],
+            }
+        );
 
         Ok(())
     }
@@ -11,13 +11,14 @@
 use crate::{auth::IpPattern, cache::Cached};
 use crate::{
     control_plane::{
-        messages::MetricsAuxInfo,
-        provider::{CachedAllowedIps, CachedRoleSecret},
+        messages::{MetricsAuxInfo, QueryPolicy},
+        provider::{CachedAllowedIps, CachedQueryPolicy, CachedRoleSecret},
     },
     BranchId, EndpointId, ProjectId,
 };
 use futures::TryFutureExt;
-use std::{str::FromStr, sync::Arc};
+use rand::Rng;
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 use thiserror::Error;
 use tokio_postgres::{config::SslMode, Client};
 use tracing::{error, info, info_span, warn, Instrument};
@@ -117,6 +118,7 @@ async fn do_get_auth_info(
             secret,
             allowed_ips,
             project_id: None,
+            query_policy: QueryPolicy::default(),
         })
     }
 
@@ -144,6 +146,8 @@ async fn do_get_endpoint_jwks(&self, endpoint: EndpointId) -> anyhow::Result<Vec
                     .map(RoleName::from)
                     .map(|s| RoleNameInt::from(&s))
                     .collect(),
+                role_claim: None,
+                role_mapping: HashMap::new(),
             });
         }
 
@@ -153,6 +157,33 @@ async fn do_get_endpoint_jwks(&self, endpoint: EndpointId) -> anyhow::Result<Vec
         Ok(rows)
     }
 
+    /// Set a new random password of the role, there's no one else to issue it.
+    async fn do_get_session_password(
+        &self,
+        user_info: &ComputeUserInfo,
+    ) -> Result<Box<str>, GetAuthInfoError> {
+        let (client, connection) =
+            tokio_postgres::connect(self.endpoint.as_str(), tokio_postgres::NoTls).await?;
+
+        tokio::spawn(connection);
+
+        let password: String = rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        let statement: String = client
+            .query_one(
+                "select format('alter role %I password %L', $1::text, $2::text)",
+                &[&&*user_info.user, &password],
+            )
+            .await?
+            .get(0);
+        client.batch_execute(&statement).await?;
+
+        Ok(password.into())
+    }
+
     async fn do_wake_compute(&self) -> Result<NodeInfo, WakeComputeError> {
         let mut config = compute::ConnCfg::new();
         config
@@ -220,6 +251,14 @@ async fn get_allowed_ips_and_secret(
         ))
     }
 
+    async fn get_query_policy(
+        &self,
+        _ctx: &RequestMonitoring,
+        _user_info: &ComputeUserInfo,
+    ) -> Result<CachedQueryPolicy, GetAuthInfoError> {
+        Ok(CachedQueryPolicy::new_uncached(Arc::default()))
+    }
+
     async fn get_endpoint_jwks(
         &self,
         _ctx: &RequestMonitoring,
@@ -228,6 +267,14 @@ async fn get_endpoint_jwks(
         self.do_get_endpoint_jwks(endpoint).await
     }
 
+    async fn get_session_password(
+        &self,
+        _ctx: &RequestMonitoring,
+        user_info: &ComputeUserInfo,
+    ) -> Result<Box<str>, GetAuthInfoError> {
+        self.do_get_session_password(user_info).await
+    }
+
     #[tracing::instrument(skip_all)]
     async fn wake_compute(
         &self,
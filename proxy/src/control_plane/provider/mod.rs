@@ -2,7 +2,7 @@
 pub mod mock;
 pub mod neon;
 
-use super::messages::{ControlPlaneError, MetricsAuxInfo};
+use super::messages::{ControlPlaneError, MetricsAuxInfo, QueryPolicy};
 use crate::{
     auth::{
         backend::{
@@ -266,6 +266,8 @@ pub(crate) struct AuthInfo {
     pub(crate) allowed_ips: Vec<IpPattern>,
     /// Project ID. This is used for cache invalidation.
     pub(crate) project_id: Option<ProjectIdInt>,
+    /// Restrictions on queries of the role.
+    pub(crate) query_policy: QueryPolicy,
 }
 
 /// Info for establishing a connection to a compute node.
@@ -306,7 +308,6 @@ pub(crate) fn reuse_settings(&mut self, other: Self) {
 
     pub(crate) fn set_keys(&mut self, keys: &ComputeCredentialKeys) {
         match keys {
-            #[cfg(any(test, feature = "testing"))]
             ComputeCredentialKeys::Password(password) => self.config.password(password),
             ComputeCredentialKeys::AuthKeys(auth_keys) => self.config.auth_keys(*auth_keys),
             ComputeCredentialKeys::JwtPayload(_) | ComputeCredentialKeys::None => &mut self.config,
@@ -319,6 +320,7 @@ pub(crate) fn set_keys(&mut self, keys: &ComputeCredentialKeys) {
 pub(crate) type CachedNodeInfo = Cached<&'static NodeInfoCache, NodeInfo>;
 pub(crate) type CachedRoleSecret = Cached<&'static ProjectInfoCacheImpl, Option<AuthSecret>>;
 pub(crate) type CachedAllowedIps = Cached<&'static ProjectInfoCacheImpl, Arc<Vec<IpPattern>>>;
+pub(crate) type CachedQueryPolicy = Cached<&'static ProjectInfoCacheImpl, Arc<QueryPolicy>>;
 
 /// This will allocate per each call, but the http requests alone
 /// already require a few allocations, so it should be fine.
@@ -338,12 +340,27 @@ async fn get_allowed_ips_and_secret(
         user_info: &ComputeUserInfo,
     ) -> Result<(CachedAllowedIps, Option<CachedRoleSecret>), errors::GetAuthInfoError>;
 
+    /// Get restrictions on queries of the role.
+    async fn get_query_policy(
+        &self,
+        ctx: &RequestMonitoring,
+        user_info: &ComputeUserInfo,
+    ) -> Result<CachedQueryPolicy, errors::GetAuthInfoError>;
+
     async fn get_endpoint_jwks(
         &self,
         ctx: &RequestMonitoring,
         endpoint: EndpointId,
     ) -> anyhow::Result<Vec<AuthRule>>;
 
+    /// Get a short-lived password of the role for a session which the proxy
+    /// authenticated by itself, e.g. with an OIDC access token.
+    async fn get_session_password(
+        &self,
+        ctx: &RequestMonitoring,
+        user_info: &ComputeUserInfo,
+    ) -> Result<Box<str>, errors::GetAuthInfoError>;
+
     /// Wake up the compute node and return the corresponding connection info.
     async fn wake_compute(
         &self,
@@ -397,6 +414,20 @@ async fn get_allowed_ips_and_secret(
         }
     }
 
+    async fn get_query_policy(
+        &self,
+        ctx: &RequestMonitoring,
+        user_info: &ComputeUserInfo,
+    ) -> Result<CachedQueryPolicy, errors::GetAuthInfoError> {
+        match self {
+            Self::Management(api) => api.get_query_policy(ctx, user_info).await,
+            #[cfg(any(test, feature = "testing"))]
+            Self::PostgresMock(api) => api.get_query_policy(ctx, user_info).await,
+            #[cfg(test)]
+            Self::Test(_) => Ok(CachedQueryPolicy::new_uncached(Arc::default())),
+        }
+    }
+
     async fn get_endpoint_jwks(
         &self,
         ctx: &RequestMonitoring,
@@ -411,6 +442,22 @@ async fn get_endpoint_jwks(
         }
     }
 
+    async fn get_session_password(
+        &self,
+        ctx: &RequestMonitoring,
+        user_info: &ComputeUserInfo,
+    ) -> Result<Box<str>, errors::GetAuthInfoError> {
+        match self {
+            Self::Management(api) => api.get_session_password(ctx, user_info).await,
+            #[cfg(any(test, feature = "testing"))]
+            Self::PostgresMock(api) => api.get_session_password(ctx, user_info).await,
+            #[cfg(test)]
+            Self::Test(_) => {
+                unreachable!("this function should never be called in the test backend")
+            }
+        }
+    }
+
     async fn wake_compute(
         &self,
         ctx: &RequestMonitoring,
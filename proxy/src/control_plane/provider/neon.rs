@@ -1,10 +1,10 @@
 //! Production console backend.
 
 use super::{
-    super::messages::{ControlPlaneError, GetRoleSecret, WakeCompute},
+    super::messages::{ControlPlaneError, GetRoleSecret, GetSessionPassword, WakeCompute},
     errors::{ApiError, GetAuthInfoError, WakeComputeError},
-    ApiCaches, ApiLocks, AuthInfo, AuthSecret, CachedAllowedIps, CachedNodeInfo, CachedRoleSecret,
-    NodeInfo,
+    ApiCaches, ApiLocks, AuthInfo, AuthSecret, CachedAllowedIps, CachedNodeInfo, CachedQueryPolicy,
+    CachedRoleSecret, NodeInfo,
 };
 use crate::{
     auth::backend::{jwt::AuthRule, ComputeUserInfo},
@@ -126,6 +126,7 @@ async fn do_get_auth_info(
                 secret,
                 allowed_ips,
                 project_id: body.project_id,
+                query_policy: body.query_policy,
             })
         }
         .map_err(crate::error::log_error)
@@ -178,6 +179,8 @@ async fn do_get_endpoint_jwks(
                     jwks_url: jwks.jwks_url,
                     audience: jwks.jwt_audience,
                     role_names: jwks.role_names,
+                    role_claim: jwks.role_claim,
+                    role_mapping: jwks.role_mapping,
                 })
                 .collect();
 
@@ -188,6 +191,40 @@ async fn do_get_endpoint_jwks(
         .await
     }
 
+    async fn do_get_session_password(
+        &self,
+        ctx: &RequestMonitoring,
+        user_info: &ComputeUserInfo,
+    ) -> Result<Box<str>, GetAuthInfoError> {
+        let request_id = ctx.session_id().to_string();
+        async {
+            let request = self
+                .endpoint
+                .get_path("proxy_get_session_password")
+                .header(X_REQUEST_ID, &request_id)
+                .header(AUTHORIZATION, format!("Bearer {}", &self.jwt))
+                .query(&[("session_id", ctx.session_id())])
+                .query(&[
+                    ("project", user_info.endpoint.as_str()),
+                    ("role", user_info.user.as_str()),
+                ])
+                .build()?;
+
+            info!(url = request.url().as_str(), "sending http request");
+            let start = Instant::now();
+            let pause = ctx.latency_timer_pause(crate::metrics::Waiting::Cplane);
+            let response = self.endpoint.execute(request).await?;
+            drop(pause);
+            info!(duration = ?start.elapsed(), "received http response");
+            let body = parse_body::<GetSessionPassword>(response).await?;
+
+            Ok(body.password)
+        }
+        .map_err(crate::error::log_error)
+        .instrument(info_span!("http", id = request_id))
+        .await
+    }
+
     async fn do_wake_compute(
         &self,
         ctx: &RequestMonitoring,
@@ -273,6 +310,12 @@ async fn get_role_secret(
                 user.into(),
                 auth_info.secret.clone(),
             );
+            self.caches.project_info.insert_query_policy(
+                project_id,
+                normalized_ep_int,
+                user.into(),
+                Arc::new(auth_info.query_policy),
+            );
             self.caches.project_info.insert_allowed_ips(
                 project_id,
                 normalized_ep_int,
@@ -312,6 +355,12 @@ async fn get_allowed_ips_and_secret(
                 user.into(),
                 auth_info.secret.clone(),
             );
+            self.caches.project_info.insert_query_policy(
+                project_id,
+                normalized_ep_int,
+                user.into(),
+                Arc::new(auth_info.query_policy),
+            );
             self.caches.project_info.insert_allowed_ips(
                 project_id,
                 normalized_ep_int,
@@ -325,6 +374,47 @@ async fn get_allowed_ips_and_secret(
         ))
     }
 
+    #[tracing::instrument(skip_all)]
+    async fn get_query_policy(
+        &self,
+        ctx: &RequestMonitoring,
+        user_info: &ComputeUserInfo,
+    ) -> Result<CachedQueryPolicy, GetAuthInfoError> {
+        let normalized_ep = &user_info.endpoint.normalize();
+        let user = &user_info.user;
+        if let Some(policy) = self
+            .caches
+            .project_info
+            .get_query_policy(normalized_ep, user)
+        {
+            return Ok(policy);
+        }
+        let auth_info = self.do_get_auth_info(ctx, user_info).await?;
+        let policy = Arc::new(auth_info.query_policy);
+        if let Some(project_id) = auth_info.project_id {
+            let normalized_ep_int = normalized_ep.into();
+            self.caches.project_info.insert_role_secret(
+                project_id,
+                normalized_ep_int,
+                user.into(),
+                auth_info.secret,
+            );
+            self.caches.project_info.insert_allowed_ips(
+                project_id,
+                normalized_ep_int,
+                Arc::new(auth_info.allowed_ips),
+            );
+            self.caches.project_info.insert_query_policy(
+                project_id,
+                normalized_ep_int,
+                user.into(),
+                policy.clone(),
+            );
+            ctx.set_project_id(project_id);
+        }
+        Ok(Cached::new_uncached(policy))
+    }
+
     #[tracing::instrument(skip_all)]
     async fn get_endpoint_jwks(
         &self,
@@ -334,6 +424,15 @@ async fn get_endpoint_jwks(
         self.do_get_endpoint_jwks(ctx, endpoint).await
     }
 
+    #[tracing::instrument(skip_all)]
+    async fn get_session_password(
+        &self,
+        ctx: &RequestMonitoring,
+        user_info: &ComputeUserInfo,
+    ) -> Result<Box<str>, GetAuthInfoError> {
+        self.do_get_session_password(ctx, user_info).await
+    }
+
     #[tracing::instrument(skip_all)]
     async fn wake_compute(
         &self,
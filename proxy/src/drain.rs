@@ -0,0 +1,524 @@
+//! Draining of client sessions on shutdown, and handover of the listening
+//! sockets to a new proxy process.
+//!
+//! On shutdown the proxy stops accepting connections, asks HTTP clients to
+//! go away, and waits for the existing sessions to finish. Sessions which are
+//! still running `session_drain_timeout` after the drain started see EOF from
+//! their client, which shuts down both the client and the compute connection
+//! cleanly: TCP clients get a FIN, websocket clients get a close frame. Before
+//! that, the client gets the same `FATAL` admin_shutdown error that postgres
+//! sends on shutdown, at the next message boundary of what's sent to it.
+//!
+//! To not refuse connections during a deploy, the new proxy process can take
+//! over the listening sockets of the old one. The old process serves its
+//! sockets on a unix socket, and the new process receives them from it with
+//! `SCM_RIGHTS` on startup. Once the new process has the sockets, the old
+//! process starts draining, and the new process serves the handover for the
+//! next deploy. Both processes accept on the same sockets, so connections
+//! in the accept queue are not lost. The http and mgmt sockets are handed
+//! over as well; the old process keeps serving them until it exits.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    future::Future,
+    io::{IoSlice, IoSliceMut, Read, Write},
+    net::SocketAddr,
+    os::{
+        fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::net::UnixStream,
+    },
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use anyhow::{bail, ensure, Context as _};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use camino::{Utf8Path, Utf8PathBuf};
+use nix::sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags, UnixAddr};
+use pin_project_lite::pin_project;
+use pq_proto::SQLSTATE_ADMIN_SHUTDOWN;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, UnixListener},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{metrics::Metrics, proxy::run_until_cancelled};
+
+/// Resolves `timeout` after the proxy started draining, or never without a timeout.
+pub(crate) fn session_deadline(
+    token: CancellationToken,
+    timeout: Option<Duration>,
+) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(async move {
+        token.cancelled().await;
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    })
+}
+
+/// Run the startup of a session, unless the drain deadline passes first.
+pub(crate) async fn before_deadline<F: Future>(
+    deadline: &mut Pin<Box<dyn Future<Output = ()> + Send>>,
+    startup: F,
+) -> Option<F::Output> {
+    tokio::select! {
+        res = startup => Some(res),
+        () = deadline => {
+            Metrics::get().proxy.drained_sessions_total.inc();
+            info!("closing the session after the drain timeout");
+            None
+        }
+    }
+}
+
+pin_project! {
+    /// Client stream after the startup, which reads EOF once the drain
+    /// deadline has passed. The client is sent an admin_shutdown error at the
+    /// next message boundary, and nothing after it.
+    pub(crate) struct DrainingStream<S> {
+        #[pin]
+        inner: S,
+        deadline: Pin<Box<dyn Future<Output = ()> + Send>>,
+        state: DrainState,
+        framing: Framing,
+    }
+}
+
+enum DrainState {
+    Running,
+    /// The rest of the error to send to the client.
+    Expired(Bytes),
+    Closed,
+}
+
+impl<S> DrainingStream<S> {
+    pub(crate) fn new(inner: S, deadline: Pin<Box<dyn Future<Output = ()> + Send>>) -> Self {
+        Self {
+            inner,
+            deadline,
+            state: DrainState::Running,
+            framing: Framing::default(),
+        }
+    }
+}
+
+impl<S: AsyncWrite> DrainingStream<S> {
+    /// Finish sending the error to the client.
+    fn poll_goodbye(
+        mut inner: Pin<&mut S>,
+        state: &mut DrainState,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if let DrainState::Expired(goodbye) = state {
+            while goodbye.has_remaining() {
+                let n = ready!(inner.as_mut().poll_write(cx, goodbye))?;
+                if n == 0 {
+                    return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+                }
+                goodbye.advance(n);
+            }
+            ready!(inner.poll_flush(cx))?;
+            *state = DrainState::Closed;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncRead for DrainingStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.project();
+        if let DrainState::Running = this.state {
+            if this.deadline.as_mut().poll(cx).is_ready() {
+                *this.state = DrainState::Expired(admin_shutdown());
+                Metrics::get().proxy.drained_sessions_total.inc();
+                info!("closing the session after the drain timeout");
+            }
+        }
+        if let DrainState::Running = this.state {
+            return this.inner.poll_read(cx, buf);
+        }
+        if this.framing.at_boundary() {
+            ready!(Self::poll_goodbye(this.inner, this.state, cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for DrainingStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.project();
+        let buf = match this.state {
+            DrainState::Running => buf,
+            // finish the message the client is in the middle of
+            _ if !this.framing.at_boundary() => &buf[..buf.len().min(this.framing.message_end())],
+            _ => {
+                ready!(Self::poll_goodbye(this.inner, this.state, cx))?;
+                return Poll::Ready(Ok(buf.len()));
+            }
+        };
+        let n = ready!(this.inner.poll_write(cx, buf))?;
+        this.framing.advance(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let mut this = self.project();
+        if this.framing.at_boundary() {
+            ready!(Self::poll_goodbye(this.inner.as_mut(), this.state, cx))?;
+        }
+        this.inner.poll_shutdown(cx)
+    }
+}
+
+/// Tracks where the messages sent to the client begin and end.
+#[derive(Default)]
+struct Framing {
+    /// Tag and length of the current message, while they are incomplete.
+    header: [u8; 5],
+    header_len: usize,
+    /// Rest of the current message body.
+    remaining: usize,
+}
+
+impl Framing {
+    fn at_boundary(&self) -> bool {
+        self.header_len == 0 && self.remaining == 0
+    }
+
+    /// How much of the current message or its header is left.
+    fn message_end(&self) -> usize {
+        if self.remaining > 0 {
+            self.remaining
+        } else {
+            self.header.len() - self.header_len
+        }
+    }
+
+    fn advance(&mut self, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            if self.remaining > 0 {
+                let n = self.remaining.min(buf.len());
+                self.remaining -= n;
+                buf = &buf[n..];
+                continue;
+            }
+            let n = (self.header.len() - self.header_len).min(buf.len());
+            self.header[self.header_len..self.header_len + n].copy_from_slice(&buf[..n]);
+            self.header_len += n;
+            buf = &buf[n..];
+            if self.header_len == self.header.len() {
+                let [_, len @ ..] = self.header;
+                self.remaining = (u32::from_be_bytes(len) as usize).saturating_sub(4);
+                self.header_len = 0;
+            }
+        }
+    }
+}
+
+/// The error postgres sends to its clients when it shuts down.
+fn admin_shutdown() -> Bytes {
+    let mut body = BytesMut::new();
+    body.put_u8(b'S');
+    body.put_slice(b"FATAL\0");
+    body.put_u8(b'V');
+    body.put_slice(b"FATAL\0");
+    body.put_u8(b'C');
+    body.put_slice(SQLSTATE_ADMIN_SHUTDOWN);
+    body.put_u8(0);
+    body.put_u8(b'M');
+    body.put_slice(b"terminating connection due to administrator command\0");
+    body.put_u8(0);
+
+    let mut msg = BytesMut::with_capacity(5 + body.len());
+    msg.put_u8(b'E');
+    msg.put_u32(4 + body.len() as u32);
+    msg.put_slice(&body);
+    msg.freeze()
+}
+
+/// Upper bound of listening sockets in a handover.
+const MAX_LISTENERS: usize = 8;
+
+/// Listening sockets of the proxy, bound or taken over from the previous process.
+#[derive(Default)]
+pub struct Listeners {
+    inherited: HashMap<String, std::net::TcpListener>,
+    bound: Vec<(String, OwnedFd)>,
+}
+
+impl Listeners {
+    /// Take over the listening sockets of the process serving the handover
+    /// socket, if there is one.
+    pub async fn inherit(handover_socket: Option<&Utf8Path>) -> anyhow::Result<Self> {
+        let Some(path) = handover_socket else {
+            return Ok(Self::default());
+        };
+        let stream = match tokio::net::UnixStream::connect(path).await {
+            Ok(stream) => stream.into_std()?,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+                ) =>
+            {
+                info!(%path, "no listeners to take over: {e}");
+                return Ok(Self::default());
+            }
+            Err(e) => return Err(e).with_context(|| format!("connecting to {path}")),
+        };
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(HANDOVER_TIMEOUT))?;
+        stream.set_write_timeout(Some(HANDOVER_TIMEOUT))?;
+
+        let inherited = tokio::task::spawn_blocking(move || receive_listeners(stream)).await??;
+        for (name, listener) in &inherited {
+            info!(name, addr = ?listener.local_addr()?, "took over listener");
+        }
+        Ok(Self {
+            inherited,
+            bound: vec![],
+        })
+    }
+
+    /// Use the listener taken over from the previous process, or bind a new one.
+    pub async fn bind(&mut self, name: &str, addr: SocketAddr) -> anyhow::Result<TcpListener> {
+        let listener = match self.inherited.remove(name) {
+            Some(listener) if listener.local_addr()? == addr => {
+                listener.set_nonblocking(true)?;
+                TcpListener::from_std(listener)?
+            }
+            inherited => {
+                if let Some(listener) = inherited {
+                    warn!(name, addr = ?listener.local_addr()?, "inherited listener address does not match {addr}, binding a new one");
+                }
+                TcpListener::bind(addr).await?
+            }
+        };
+        ensure!(
+            self.bound.len() < MAX_LISTENERS,
+            "too many listeners to hand over"
+        );
+        self.bound
+            .push((name.to_owned(), listener.as_fd().try_clone_to_owned()?));
+        Ok(listener)
+    }
+
+    /// Hand over the listeners to the next process connecting to the handover
+    /// socket, and start draining once it took them.
+    pub async fn serve_handover(
+        self,
+        path: Utf8PathBuf,
+        cancellation_token: CancellationToken,
+    ) -> anyhow::Result<Infallible> {
+        for name in self.inherited.keys() {
+            warn!(name, "inherited listener is not used");
+        }
+
+        // the previous process doesn't need the socket after the handover
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("removing {path}")),
+        }
+        let listener = UnixListener::bind(&path).with_context(|| format!("binding {path}"))?;
+        info!(%path, "serving listener handover");
+
+        // the fds stay open until the process exits, as the new process
+        // might still be taking them.
+        let (names, owned_fds): (Vec<String>, Vec<OwnedFd>) = self.bound.into_iter().unzip();
+        let names = serde_json::to_vec(&names)?;
+        let fds: Vec<RawFd> = owned_fds.iter().map(AsRawFd::as_raw_fd).collect();
+
+        while let Some(res) = run_until_cancelled(listener.accept(), &cancellation_token).await {
+            let stream = match res.and_then(|(stream, _)| stream.into_std()) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("failed to accept listener handover: {e}");
+                    continue;
+                }
+            };
+
+            let (names, fds) = (names.clone(), fds.clone());
+            let res = tokio::task::spawn_blocking(move || send_listeners(stream, &names, &fds))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|res| res);
+            match res {
+                Ok(()) => {
+                    warn!("handed over the listeners, draining");
+                    cancellation_token.cancel();
+                }
+                Err(e) => warn!("failed to hand over the listeners: {e:#}"),
+            }
+        }
+
+        Ok(std::future::pending().await)
+    }
+}
+
+const HANDOVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Send the listener names and fds, and wait for the receiver to confirm it took them.
+fn send_listeners(mut stream: UnixStream, names: &[u8], fds: &[RawFd]) -> anyhow::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HANDOVER_TIMEOUT))?;
+    stream.set_write_timeout(Some(HANDOVER_TIMEOUT))?;
+
+    let iov = [IoSlice::new(names)];
+    let cmsgs = [ControlMessage::ScmRights(fds)];
+    let sent = sendmsg::<UnixAddr>(stream.as_raw_fd(), &iov, &cmsgs, MsgFlags::empty(), None)?;
+    ensure!(sent == names.len(), "short write of the listener names");
+
+    let mut ack = [0; 1];
+    stream
+        .read_exact(&mut ack)
+        .context("waiting for the handover confirmation")?;
+    Ok(())
+}
+
+fn receive_listeners(
+    mut stream: UnixStream,
+) -> anyhow::Result<HashMap<String, std::net::TcpListener>> {
+    let mut buf = vec![0; 4096];
+    let mut cmsg_buffer = nix::cmsg_space!([RawFd; MAX_LISTENERS]);
+    let (len, fds) = {
+        let mut iov = [IoSliceMut::new(&mut buf)];
+        let msg = recvmsg::<UnixAddr>(
+            stream.as_raw_fd(),
+            &mut iov,
+            Some(&mut cmsg_buffer),
+            MsgFlags::MSG_CMSG_CLOEXEC,
+        )?;
+        let mut fds = vec![];
+        for cmsg in msg.cmsgs() {
+            if let ControlMessageOwned::ScmRights(received) = cmsg {
+                fds.extend(received);
+            }
+        }
+        (msg.bytes, fds)
+    };
+    let listeners: Vec<std::net::TcpListener> = fds
+        .into_iter()
+        // SAFETY: the fds were just received with SCM_RIGHTS, so we own them.
+        .map(|fd| unsafe { std::net::TcpListener::from_raw_fd(fd) })
+        .collect();
+
+    let names: Vec<String> =
+        serde_json::from_slice(&buf[..len]).context("parsing the listener names")?;
+    if names.len() != listeners.len() {
+        bail!(
+            "received {} listener names but {} fds",
+            names.len(),
+            listeners.len()
+        );
+    }
+
+    stream.write_all(&[1]).context("confirming the handover")?;
+    Ok(names.into_iter().zip(listeners).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn session_reads_eof_after_drain_timeout() {
+        let token = CancellationToken::new();
+        let (session, mut client) = tokio::io::duplex(1024);
+        let mut stream = DrainingStream::new(
+            session,
+            session_deadline(token.clone(), Some(Duration::from_secs(10))),
+        );
+
+        let mut buf = [0; 8];
+        let read = tokio::time::timeout(Duration::from_secs(60), stream.read(&mut buf));
+        assert!(read.await.is_err(), "session must not end before draining");
+
+        token.cancel();
+        let read = tokio::time::timeout(Duration::from_secs(11), stream.read(&mut buf));
+        assert_eq!(read.await.unwrap().unwrap(), 0);
+
+        // nothing is sent after the error
+        stream.write_all(b"Z\0\0\0\x05I").await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut received = vec![];
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, admin_shutdown());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn client_receives_admin_shutdown_after_the_current_message() {
+        let token = CancellationToken::new();
+        let (session, mut client) = tokio::io::duplex(1024);
+        let mut stream = DrainingStream::new(
+            session,
+            session_deadline(token.clone(), Some(Duration::ZERO)),
+        );
+        let ready_for_query = b"Z\0\0\0\x05I";
+
+        stream.write_all(&ready_for_query[..3]).await.unwrap();
+        token.cancel();
+        assert_eq!(stream.read(&mut [0; 8]).await.unwrap(), 0);
+
+        stream.write_all(&ready_for_query[3..]).await.unwrap();
+        stream.write_all(ready_for_query).await.unwrap();
+        stream.shutdown().await.unwrap();
+
+        let mut received = vec![];
+        client.read_to_end(&mut received).await.unwrap();
+        let (message, error) = received.split_at(ready_for_query.len());
+        assert_eq!(message, ready_for_query);
+        assert_eq!(error[0], b'E');
+        assert!(error.windows(7).any(|field| field == b"SFATAL\0"));
+        assert!(error.windows(7).any(|field| field == b"C57P01\0"));
+        assert_eq!(error, admin_shutdown());
+    }
+
+    #[tokio::test]
+    async fn listener_handover() {
+        let dir = camino_tempfile::tempdir().unwrap();
+        let path = dir.path().join("handover.sock");
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+        // nothing to take over yet
+        let mut listeners = Listeners::inherit(Some(path.as_path())).await.unwrap();
+        let old = listeners.bind("proxy", addr).await.unwrap();
+        let addr = old.local_addr().unwrap();
+
+        let token = CancellationToken::new();
+        let server = tokio::spawn(listeners.serve_handover(path.clone(), token.clone()));
+        while !path.exists() {
+            tokio::task::yield_now().await;
+        }
+
+        let mut listeners = Listeners::inherit(Some(path.as_path())).await.unwrap();
+        let new = listeners.bind("proxy", addr).await.unwrap();
+        assert_eq!(new.local_addr().unwrap(), addr);
+        token.cancelled().await;
+
+        drop(old);
+        let _client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        new.accept().await.unwrap();
+        server.abort();
+    }
+}
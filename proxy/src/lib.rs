@@ -98,6 +98,7 @@
 pub mod console_redirect_proxy;
 pub mod context;
 pub mod control_plane;
+pub mod drain;
 pub mod error;
 pub mod http;
 pub mod intern;
@@ -4,14 +4,14 @@
 use measured::{
     label::{FixedCardinalitySet, LabelGroupSet, LabelName, LabelSet, LabelValue, StaticLabelSet},
     metric::{histogram::Thresholds, name::MetricName},
-    Counter, CounterVec, FixedCardinalityLabel, Gauge, Histogram, HistogramVec, LabelGroup,
-    MetricGroup,
+    Counter, CounterVec, FixedCardinalityLabel, Gauge, GaugeVec, Histogram, HistogramVec,
+    LabelGroup, MetricGroup,
 };
 use metrics::{CounterPairAssoc, CounterPairVec, HyperLogLog, HyperLogLogVec};
 
 use tokio::time::{self, Instant};
 
-use crate::control_plane::messages::ColdStartInfo;
+use crate::control_plane::messages::{ColdStartInfo, StatementClass};
 
 #[derive(MetricGroup)]
 #[metric(new(thread_pool: Arc<ThreadPoolMetrics>))]
@@ -115,9 +115,30 @@ pub struct ProxyMetrics {
     /// Number of TLS handshake failures
     pub tls_handshake_failures: Counter,
 
+    /// Expiration time of loaded TLS certificates, in seconds since the epoch (per common name).
+    pub tls_certificate_expiry_timestamp_seconds: GaugeVec<TlsCertificateSet>,
+
+    /// Number of TLS certificate reloads (per outcome).
+    pub tls_certificate_reloads_total: CounterVec<StaticLabelSet<Outcome>>,
+
     /// Number of connection requests affected by authentication rate limits
     pub requests_auth_rate_limits_total: Counter,
 
+    /// Number of authentication attempts rejected due to a lockout after repeated failures
+    pub requests_auth_lockouts_total: Counter,
+
+    /// Number of lockouts after repeated authentication failures
+    pub auth_lockouts_total: Counter,
+
+    /// Number of client sessions closed after the drain timeout on shutdown
+    pub drained_sessions_total: Counter,
+
+    /// Number of SQL-over-HTTP queries denied by query policies (per statement class).
+    pub http_denied_queries_total: CounterVec<StaticLabelSet<StatementClass>>,
+
+    /// Number of SQL-over-HTTP queries run as cached prepared statements (per outcome of the cache lookup).
+    pub http_statement_cache_lookups_total: CounterVec<StaticLabelSet<StatementCacheOutcome>>,
+
     /// HLL approximate cardinality of endpoints that are connecting
     pub connecting_endpoints: HyperLogLogVec<StaticLabelSet<Protocol>, 32>,
 
@@ -173,6 +194,13 @@ pub enum HttpDirection {
     Response,
 }
 
+#[derive(FixedCardinalityLabel, Copy, Clone)]
+#[label(singleton = "outcome")]
+pub enum StatementCacheOutcome {
+    Hit,
+    Miss,
+}
+
 #[derive(FixedCardinalityLabel, Copy, Clone)]
 #[label(singleton = "direction")]
 pub enum Direction {
@@ -233,6 +261,13 @@ pub struct ConsoleRequest<'a> {
     pub request: &'a str,
 }
 
+#[derive(LabelGroup)]
+#[label(set = TlsCertificateSet)]
+pub struct TlsCertificate<'a> {
+    #[label(dynamic_with = ThreadedRodeo, default)]
+    pub common_name: &'a str,
+}
+
 #[derive(MetricGroup, Default)]
 pub struct HttpEndpointPools {
     /// Number of endpoints we have registered pools for
@@ -565,6 +600,8 @@ pub enum RedisEventsCount {
     CancelSession,
     PasswordUpdate,
     AllowedIpsUpdate,
+    QueryPolicyUpdate,
+    AuthLockout,
 }
 
 pub struct ThreadPoolWorkers(usize);
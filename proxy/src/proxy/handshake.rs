@@ -115,8 +115,10 @@ pub(crate) async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
 
                         let mut read_buf = read_buf.reader();
                         let mut res = Ok(());
-                        let accept = tokio_rustls::TlsAcceptor::from(tls.to_server_config())
-                            .accept_with(raw, |session| {
+                        let handshake_config = tls.handshake_config();
+                        let config = handshake_config.server_config.clone();
+                        let accept =
+                            tokio_rustls::TlsAcceptor::from(config).accept_with(raw, |session| {
                                 // push the early data to the tls session
                                 while !read_buf.get_ref().is_empty() {
                                     match session.read_tls(&mut read_buf) {
@@ -147,7 +149,7 @@ pub(crate) async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
                         // try parse endpoint
                         let ep = conn_info
                             .server_name()
-                            .and_then(|sni| endpoint_sni(sni, &tls.common_names).ok().flatten());
+                            .and_then(|sni| endpoint_sni(sni, &tls.common_names()).ok().flatten());
                         if let Some(ep) = ep {
                             ctx.set_endpoint_id(ep);
                         }
@@ -162,9 +164,8 @@ pub(crate) async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
                             }
                         }
 
-                        let (_, tls_server_end_point) = tls
-                            .cert_resolver
-                            .resolve(conn_info.server_name())
+                        let tls_server_end_point = handshake_config
+                            .tls_server_end_point(conn_info.server_name())
                             .ok_or(HandshakeError::MissingCertificate)?;
 
                         stream = PqStream {
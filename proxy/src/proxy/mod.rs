@@ -6,22 +6,25 @@
 pub(crate) mod handshake;
 pub(crate) mod passthrough;
 pub(crate) mod retry;
+pub(crate) mod txn_pool;
 pub(crate) mod wake_compute;
 pub use copy_bidirectional::copy_bidirectional_client_compute;
 pub use copy_bidirectional::ErrorSource;
 
-use crate::config::ProxyProtocolV2;
+use crate::config::{ProxyProtocolV2, TcpPoolingMode};
 use crate::{
     auth,
     cancellation::{self, CancellationHandlerMain, CancellationHandlerMainInternal},
     compute,
     config::{ProxyConfig, TlsConfig},
     context::RequestMonitoring,
+    drain,
     error::ReportableError,
     metrics::{Metrics, NumClientConnectionsGuard},
     protocol2::read_proxy_protocol,
     proxy::handshake::{handshake, HandshakeData},
     rate_limiter::EndpointRateLimiter,
+    serverless::conn_pool::GlobalConnPool,
     stream::{PqStream, Stream},
     EndpointCacheKey,
 };
@@ -29,6 +32,7 @@
 use itertools::Itertools;
 use once_cell::sync::OnceCell;
 use pq_proto::{BeMessage as Be, StartupMessageParams};
+use rand::{rngs::StdRng, SeedableRng};
 use regex::Regex;
 use smol_str::{format_smolstr, SmolStr};
 use std::sync::Arc;
@@ -40,6 +44,7 @@
 use self::{
     connect_compute::{connect_to_compute, TcpMechanism},
     passthrough::ProxyPassthrough,
+    txn_pool::{TcpConnPool, TransactionPooling},
 };
 
 const ERR_INSECURE_CONNECTION: &str = "connection is insecure (try using `sslmode=require`)";
@@ -75,6 +80,18 @@ pub async fn task_main(
     // will be inherited by all accepted client sockets.
     socket2::SockRef::from(&listener).set_keepalive(true)?;
 
+    let tcp_pool = match config.tcp_pooling_mode {
+        TcpPoolingMode::Session => None,
+        TcpPoolingMode::Transaction => {
+            let pool: Arc<TcpConnPool> = GlobalConnPool::new(&config.http_config);
+            let gc_pool = Arc::clone(&pool);
+            tokio::spawn(async move {
+                gc_pool.gc_worker(StdRng::from_entropy()).await;
+            });
+            Some(pool)
+        }
+    };
+
     let connections = tokio_util::task::task_tracker::TaskTracker::new();
 
     while let Some(accept_result) =
@@ -92,6 +109,9 @@ pub async fn task_main(
 
         tracing::info!(protocol = "tcp", %session_id, "accepted new TCP connection");
         let endpoint_rate_limiter2 = endpoint_rate_limiter.clone();
+        let pool = tcp_pool.clone();
+        let mut deadline =
+            drain::session_deadline(cancellation_token.clone(), config.session_drain_timeout);
 
         connections.spawn(async move {
             let (socket, peer_addr) = match read_proxy_protocol(socket).await {
@@ -134,13 +154,15 @@ pub async fn task_main(
                     &ctx,
                     cancellation_handler,
                     socket,
-                    ClientMode::Tcp,
+                    ClientMode::Tcp { pool },
                     endpoint_rate_limiter2,
                     conn_gauge,
                 )
                 .instrument(span.clone()),
             );
-            let res = startup.await;
+            let Some(res) = drain::before_deadline(&mut deadline, startup).await else {
+                return;
+            };
 
             match res {
                 Err(e) => {
@@ -154,7 +176,7 @@ pub async fn task_main(
                 Ok(Some(p)) => {
                     ctx.set_success();
                     ctx.log_connect();
-                    match p.proxy_pass().instrument(span.clone()).await {
+                    match p.proxy_pass(&ctx, deadline).instrument(span.clone()).await {
                         Ok(()) => {}
                         Err(ErrorSource::Client(e)) => {
                             warn!(parent: &span, "per-client task finished with an IO error from the client: {e:#}");
@@ -174,40 +196,49 @@ pub async fn task_main(
     // Drain connections
     connections.wait().await;
 
+    if let Some(pool) = tcp_pool {
+        tokio::task::spawn_blocking(move || pool.shutdown()).await?;
+    }
+
     Ok(())
 }
 
 pub(crate) enum ClientMode {
-    Tcp,
-    Websockets { hostname: Option<String> },
+    /// `pool` is set if compute connections are pooled in transaction mode.
+    Tcp {
+        pool: Option<Arc<TcpConnPool>>,
+    },
+    Websockets {
+        hostname: Option<String>,
+    },
 }
 
 /// Abstracts the logic of handling TCP vs WS clients
 impl ClientMode {
     pub(crate) fn allow_cleartext(&self) -> bool {
         match self {
-            ClientMode::Tcp => false,
+            ClientMode::Tcp { .. } => false,
             ClientMode::Websockets { .. } => true,
         }
     }
 
     pub(crate) fn allow_self_signed_compute(&self, config: &ProxyConfig) -> bool {
         match self {
-            ClientMode::Tcp => config.allow_self_signed_compute,
+            ClientMode::Tcp { .. } => config.allow_self_signed_compute,
             ClientMode::Websockets { .. } => false,
         }
     }
 
     fn hostname<'a, S>(&'a self, s: &'a Stream<S>) -> Option<&'a str> {
         match self {
-            ClientMode::Tcp => s.sni_hostname(),
+            ClientMode::Tcp { .. } => s.sni_hostname(),
             ClientMode::Websockets { hostname } => hostname.as_deref(),
         }
     }
 
     fn handshake_tls<'a>(&self, tls: Option<&'a TlsConfig>) -> Option<&'a TlsConfig> {
         match self {
-            ClientMode::Tcp => tls,
+            ClientMode::Tcp { .. } => tls,
             // TLS is None here if using websockets, because the connection is already encrypted.
             ClientMode::Websockets { .. } => None,
         }
@@ -286,12 +317,19 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
 
     let hostname = mode.hostname(stream.get_ref());
 
-    let common_names = tls.map(|tls| &tls.common_names);
+    let common_names = tls.map(|tls| tls.common_names());
 
     // Extract credentials which we're going to use for auth.
     let result = auth_backend
         .as_ref()
-        .map(|()| auth::ComputeUserInfoMaybeEndpoint::parse(ctx, &params, hostname, common_names))
+        .map(|()| {
+            auth::ComputeUserInfoMaybeEndpoint::parse(
+                ctx,
+                &params,
+                hostname,
+                common_names.as_deref(),
+            )
+        })
         .transpose();
 
     let user_info = match result {
@@ -337,17 +375,33 @@ pub(crate) async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
     let session = cancellation_handler.get_session();
     prepare_client_connection(&node, &session, &mut stream).await?;
 
+    let mut pooling = match &mode {
+        ClientMode::Tcp { pool: Some(pool) } if txn_pool::is_poolable(&params) => {
+            TransactionPooling::new(Arc::clone(pool), config, user_info, params)
+        }
+        ClientMode::Tcp { pool: Some(_) } => {
+            info!("startup parameters are not supported by transaction pooling, using a dedicated connection");
+            None
+        }
+        _ => None,
+    };
+
     // Before proxy passing, forward to compute whatever data is left in the
     // PqStream input buffer. Normally there is none, but our serverless npm
     // driver in pipeline mode sends startup, password and first query
-    // immediately after opening the connection.
+    // immediately after opening the connection. With transaction pooling,
+    // these are the first requests of the client.
     let (stream, read_buf) = stream.into_inner();
-    node.stream.write_all(&read_buf).await?;
+    match &mut pooling {
+        Some(pooling) => pooling.read_buf = read_buf,
+        None => node.stream.write_all(&read_buf).await?,
+    }
 
     Ok(Some(ProxyPassthrough {
         client: stream,
         aux: node.aux.clone(),
         compute: node,
+        pooling,
         _req: request_gauge,
         _conn: conn_gauge,
         _cancel: session,
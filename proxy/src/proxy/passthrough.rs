@@ -1,7 +1,11 @@
+use std::{future::Future, pin::Pin};
+
 use crate::{
     cancellation,
     compute::PostgresConnection,
+    context::RequestMonitoring,
     control_plane::messages::MetricsAuxInfo,
+    drain::DrainingStream,
     metrics::{Direction, Metrics, NumClientConnectionsGuard, NumConnectionRequestsGuard},
     stream::Stream,
     usage_metrics::{Ids, MetricCounterRecorder, USAGE_METRICS},
@@ -11,6 +15,7 @@
 use utils::measured_stream::MeasuredStream;
 
 use super::copy_bidirectional::ErrorSource;
+use super::txn_pool::TransactionPooling;
 
 /// Forward bytes in both directions (client <-> compute).
 #[tracing::instrument(skip_all)]
@@ -61,6 +66,8 @@ pub(crate) struct ProxyPassthrough<P, S> {
     pub(crate) client: Stream<S>,
     pub(crate) compute: PostgresConnection,
     pub(crate) aux: MetricsAuxInfo,
+    /// Set if compute connections are shared with other clients.
+    pub(crate) pooling: Option<TransactionPooling>,
 
     pub(crate) _req: NumConnectionRequestsGuard<'static>,
     pub(crate) _conn: NumClientConnectionsGuard<'static>,
@@ -68,8 +75,18 @@ pub(crate) struct ProxyPassthrough<P, S> {
 }
 
 impl<P, S: AsyncRead + AsyncWrite + Unpin> ProxyPassthrough<P, S> {
-    pub(crate) async fn proxy_pass(self) -> Result<(), ErrorSource> {
-        let res = proxy_pass(self.client, self.compute.stream, self.aux).await;
+    pub(crate) async fn proxy_pass(
+        self,
+        ctx: &RequestMonitoring,
+        drain_deadline: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ) -> Result<(), ErrorSource> {
+        let client = DrainingStream::new(self.client, drain_deadline);
+        if let Some(pooling) = self.pooling {
+            return pooling
+                .proxy_pass(ctx, client, self.compute, &self._cancel)
+                .await;
+        }
+        let res = proxy_pass(client, self.compute.stream, self.aux).await;
         if let Err(err) = self.compute.cancel_closure.try_cancel_query().await {
             tracing::warn!(?err, "could not cancel the query in the database");
         }
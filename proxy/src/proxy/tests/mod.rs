@@ -97,11 +97,8 @@ fn generate_tls_config<'a>(
         let mut cert_resolver = CertResolver::new();
         cert_resolver.add_cert(key, vec![cert], true)?;
 
-        let common_names = cert_resolver.get_common_names();
-
         TlsConfig {
             config,
-            common_names,
             cert_resolver: Arc::new(cert_resolver),
         }
     };
@@ -121,6 +118,53 @@ fn generate_tls_config<'a>(
     Ok((client_config, tls_config))
 }
 
+#[test]
+fn reload_tls_certs() -> anyhow::Result<()> {
+    let dir = camino_tempfile::tempdir()?;
+    let key_path = dir.path().join("tls.key");
+    let cert_path = dir.path().join("tls.crt");
+    let write_cert = |common_name: &str| -> anyhow::Result<()> {
+        let cert = rcgen::Certificate::from_params({
+            let mut params = rcgen::CertificateParams::new(vec![common_name.into()]);
+            params.distinguished_name = rcgen::DistinguishedName::new();
+            params
+                .distinguished_name
+                .push(rcgen::DnType::CommonName, common_name);
+            params
+        })?;
+        std::fs::write(&key_path, cert.serialize_private_key_pem())?;
+        std::fs::write(&cert_path, cert.serialize_pem()?)?;
+        Ok(())
+    };
+
+    write_cert("a.localhost")?;
+    let cert_resolver = CertResolver::from_paths(key_path.as_str(), cert_path.as_str(), None)?;
+    assert!(cert_resolver.common_names().contains("a.localhost"));
+    // The server config is built once per reload, not per handshake.
+    let handshake_config = cert_resolver.handshake_config();
+    assert!(Arc::ptr_eq(
+        &handshake_config,
+        &cert_resolver.handshake_config()
+    ));
+
+    write_cert("b.localhost")?;
+    assert!(cert_resolver.reload());
+    assert!(!Arc::ptr_eq(
+        &handshake_config,
+        &cert_resolver.handshake_config()
+    ));
+    assert!(cert_resolver.common_names().contains("b.localhost"));
+    assert!(!cert_resolver.common_names().contains("a.localhost"));
+    assert!(cert_resolver.resolve(Some("ep.b.localhost")).is_some());
+
+    // Certificates are kept if new ones fail to load.
+    std::fs::write(&cert_path, "garbage")?;
+    assert!(!cert_resolver.reload());
+    assert!(cert_resolver.common_names().contains("b.localhost"));
+
+    Ok(())
+}
+
 #[async_trait]
 trait TestAuth: Sized {
     async fn authenticate<S: AsyncRead + AsyncWrite + Unpin + Send>(
@@ -0,0 +1,765 @@
+//! Transaction-mode pooling of compute connections for TCP clients.
+//!
+//! Instead of a dedicated compute connection per client, a client holds a
+//! connection from [`TcpConnPool`] only while it has a transaction in progress.
+//! Transaction boundaries are found by following the protocol messages: the
+//! connection is returned to the pool once the compute reports the idle
+//! transaction status and the client has no unfinished requests.
+//!
+//! Session state doesn't survive moving to another connection, so every
+//! connection is reset with `DISCARD ALL` when it's returned to the pool. The
+//! reset is pipelined, its responses are skipped by the next holder. The reset
+//! brings `application_name` back to the one the connection was started with,
+//! so the next holder sets its own if it differs. Named prepared statements of
+//! the extended protocol are remembered and prepared again on the connection
+//! currently held, when they are used. Other session state, like settings, SQL
+//! level prepared statements, temporary tables, advisory locks or `LISTEN`, is
+//! not supported.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures::FutureExt;
+use pq_proto::{BeMessage as Be, StartupMessageParams};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::{info, warn};
+use utils::measured_stream::MeasuredStream;
+
+use super::connect_compute::{connect_to_compute, TcpMechanism};
+use super::copy_bidirectional::ErrorSource;
+use crate::auth::backend::ComputeCredentials;
+use crate::cancellation;
+use crate::compute::{self, PostgresConnection};
+use crate::config::ProxyConfig;
+use crate::context::RequestMonitoring;
+use crate::error::UserFacingError;
+use crate::metrics::{Direction, Metrics};
+use crate::serverless::conn_pool::{pool_client, Client, ClientInnerExt, ConnInfo, GlobalConnPool};
+use crate::usage_metrics::{Ids, MetricCounterRecorder, USAGE_METRICS};
+use crate::{auth, DbName};
+
+pub(crate) type TcpConnPool = GlobalConnPool<PooledBackend>;
+
+/// Resets session state of a connection before it's used by another client.
+const DISCARD_ALL: &[u8] = b"Q\0\0\0\x10DISCARD ALL\0";
+
+/// Whether a client with these startup parameters can share compute
+/// connections with other clients of the same role and database. Startup
+/// parameters apply to the whole session, so they must not differ.
+pub(crate) fn is_poolable(params: &StartupMessageParams) -> bool {
+    params.iter().all(|(name, value)| match name {
+        "user" | "database" | "application_name" => true,
+        "client_encoding" => value.eq_ignore_ascii_case("UTF8"),
+        // Only options which are consumed by proxy itself.
+        "options" => compute::filtered_options(params).is_none(),
+        _ => false,
+    })
+}
+
+/// Compute connection in [`TcpConnPool`].
+pub(crate) struct PooledBackend {
+    conn: PostgresConnection,
+    /// Data read from the connection, but not processed yet.
+    buf: BytesMut,
+    /// Number of `DISCARD ALL` queries the responses to which are not read yet.
+    pending_resets: usize,
+    /// `application_name` the connection was started with, which `DISCARD ALL`
+    /// resets it to.
+    application_name: String,
+    /// The compute closed the connection.
+    closed: bool,
+}
+
+impl ClientInnerExt for PooledBackend {
+    fn is_closed(&self) -> bool {
+        // Nothing reads from the connection while it's in the pool, so this is
+        // found out by `is_alive` when it's returned to the pool, and checked
+        // again when it's taken out.
+        self.closed
+    }
+    fn get_process_id(&self) -> i32 {
+        self.conn.pid
+    }
+}
+
+impl PooledBackend {
+    /// Check that the compute didn't close the connection while it was idle.
+    fn is_alive(&mut self) -> bool {
+        match self.conn.stream.read_buf(&mut self.buf).now_or_never() {
+            // Responses to the reset might be there already.
+            None | Some(Ok(1..)) => {}
+            Some(Ok(0) | Err(_)) => self.closed = true,
+        }
+        !self.closed
+    }
+
+    async fn read_message(&mut self) -> io::Result<Option<Message>> {
+        read_message(&mut self.conn.stream, &mut self.buf).await
+    }
+}
+
+/// Parameters to connect to compute again once the client needs a connection.
+pub(crate) struct TransactionPooling {
+    pub(crate) pool: Arc<TcpConnPool>,
+    pub(crate) config: &'static ProxyConfig,
+    pub(crate) user_info: auth::Backend<'static, ComputeCredentials>,
+    pub(crate) conn_info: ConnInfo,
+    pub(crate) params: StartupMessageParams,
+    /// Data received from the client after the startup.
+    pub(crate) read_buf: BytesMut,
+}
+
+impl TransactionPooling {
+    pub(crate) fn new(
+        pool: Arc<TcpConnPool>,
+        config: &'static ProxyConfig,
+        user_info: auth::Backend<'static, ComputeCredentials>,
+        params: StartupMessageParams,
+    ) -> Option<Self> {
+        let auth::Backend::ControlPlane(_, creds) = &user_info else {
+            return None;
+        };
+        let dbname: DbName = params.get("database").unwrap_or(&creds.info.user).into();
+        let conn_info = ConnInfo {
+            user_info: creds.info.clone(),
+            dbname,
+        };
+        Some(Self {
+            pool,
+            config,
+            user_info,
+            conn_info,
+            params,
+            read_buf: BytesMut::new(),
+        })
+    }
+
+    /// Serve the client, starting with the connection used for authentication.
+    pub(crate) async fn proxy_pass<P>(
+        mut self,
+        ctx: &RequestMonitoring,
+        client: impl AsyncRead + AsyncWrite + Unpin,
+        compute: PostgresConnection,
+        session: &cancellation::Session<P>,
+    ) -> Result<(), ErrorSource> {
+        let aux = compute.aux.clone();
+        let usage = USAGE_METRICS.register(Ids {
+            endpoint_id: aux.endpoint_id,
+            branch_id: aux.branch_id,
+        });
+        let metrics = &Metrics::get().proxy.io_bytes;
+        let m_sent = metrics.with_labels(Direction::Tx);
+        let m_recv = metrics.with_labels(Direction::Rx);
+        let mut client = MeasuredStream::new(
+            client,
+            |cnt| {
+                // Number of bytes the client sent to the compute node (inbound).
+                metrics.get_metric(m_recv).inc_by(cnt as u64);
+            },
+            |cnt| {
+                // Number of bytes we sent to the client (outbound).
+                metrics.get_metric(m_sent).inc_by(cnt as u64);
+                usage.record_egress(cnt as u64);
+            },
+        );
+        let mut client_buf = std::mem::take(&mut self.read_buf);
+
+        info!("performing the proxy pass in transaction pooling mode...");
+        let mut state = SessionState::default();
+        let mut held = Some(self.pooled(ctx, compute));
+        loop {
+            let Some(conn) = &mut held else {
+                // Wait for the client's next request before taking a connection.
+                let Some(msg) = read_message(&mut client, &mut client_buf)
+                    .await
+                    .map_err(ErrorSource::Client)?
+                else {
+                    return Ok(());
+                };
+                if msg.tag == b'X' {
+                    return Ok(());
+                }
+                let mut conn = match self.acquire(ctx).await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        let mut buf = BytesMut::new();
+                        let msg = e.to_string_client();
+                        if Be::write(&mut buf, &Be::ErrorResponse(&msg, None)).is_ok() {
+                            let _ = client.write_all(&buf).await;
+                            let _ = client.flush().await;
+                        }
+                        return Err(ErrorSource::Compute(io::Error::new(
+                            io::ErrorKind::Other,
+                            e,
+                        )));
+                    }
+                };
+                let (backend, mut discard) = conn.inner();
+                session.enable_query_cancellation(backend.conn.cancel_closure.clone());
+                for _ in 0..std::mem::take(&mut backend.pending_resets) {
+                    state.expected.push_back(Expected::Reset);
+                }
+                let mut out = BytesMut::new();
+                if backend.application_name != self.application_name() {
+                    // Its responses are skipped, like the ones to the reset.
+                    set_application_name(self.application_name(), &mut out);
+                    state.expected.push_back(Expected::Reset);
+                }
+                state.on_frontend(&msg, &mut out);
+                if let Err(e) = write_flush(&mut backend.conn.stream, &out).await {
+                    discard.discard();
+                    return Err(ErrorSource::Compute(e));
+                }
+                held = Some(conn);
+                continue;
+            };
+
+            let (backend, _) = conn.inner();
+            let event = tokio::select! {
+                msg = read_message(&mut client, &mut client_buf) => Event::Client(msg),
+                msg = backend.read_message() => Event::Compute(msg),
+            };
+            match event {
+                Event::Client(msg) => {
+                    // Forward everything the client has sent so far at once.
+                    let mut out = BytesMut::new();
+                    let mut next = msg.map_err(ErrorSource::Client)?;
+                    let mut terminated = next.is_none();
+                    while let Some(msg) = next {
+                        if msg.tag == b'X' {
+                            terminated = true;
+                            break;
+                        }
+                        state.on_frontend(&msg, &mut out);
+                        next = Message::parse(&mut client_buf).map_err(ErrorSource::Client)?;
+                    }
+                    let (backend, mut discard) = conn.inner();
+                    if let Err(e) = write_flush(&mut backend.conn.stream, &out).await {
+                        discard.discard();
+                        return Err(ErrorSource::Compute(e));
+                    }
+                    if terminated {
+                        // The client is gone, but the connection can be reused
+                        // if it's not in the middle of a transaction.
+                        let conn = held.take().expect("connection is held");
+                        self.release_or_discard(conn, &state, session).await;
+                        return Ok(());
+                    }
+                }
+                Event::Compute(msg) => {
+                    let mut next = match msg {
+                        Ok(None) => Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "compute closed the connection",
+                        )),
+                        msg => msg,
+                    };
+                    let mut out = BytesMut::new();
+                    while let Ok(Some(msg)) = next {
+                        if state.on_backend(&msg) {
+                            out.extend_from_slice(&msg.raw);
+                        }
+                        next = Message::parse(&mut backend.buf);
+                    }
+                    if let Err(e) = next {
+                        conn.inner().1.discard();
+                        return Err(ErrorSource::Compute(e));
+                    }
+                    if let Err(e) = write_flush(&mut client, &out).await {
+                        let conn = held.take().expect("connection is held");
+                        self.release_or_discard(conn, &state, session).await;
+                        return Err(ErrorSource::Client(e));
+                    }
+                    if state.can_release() {
+                        let conn = held.take().expect("connection is held");
+                        self.release(conn, session).await;
+                        state.prepared.clear();
+                    }
+                }
+            }
+        }
+    }
+
+    fn application_name(&self) -> &str {
+        self.params.get("application_name").unwrap_or("")
+    }
+
+    fn pooled(&self, ctx: &RequestMonitoring, conn: PostgresConnection) -> Client<PooledBackend> {
+        let aux = conn.aux.clone();
+        let backend = PooledBackend {
+            conn,
+            buf: BytesMut::new(),
+            pending_resets: 0,
+            application_name: self.application_name().to_owned(),
+            closed: false,
+        };
+        let conn_id = uuid::Uuid::new_v4();
+        pool_client(
+            &self.pool,
+            ctx,
+            self.conn_info.clone(),
+            backend,
+            conn_id,
+            aux,
+        )
+    }
+
+    /// Take an idle connection from the pool or connect to compute.
+    async fn acquire(
+        &self,
+        ctx: &RequestMonitoring,
+    ) -> Result<Client<PooledBackend>, compute::ConnectionError> {
+        while let Ok(Some(mut conn)) = self.pool.get(ctx, &self.conn_info) {
+            let (backend, mut discard) = conn.inner();
+            if backend.is_alive() {
+                return Ok(conn);
+            }
+            info!("pool: cached connection '{}' is closed", self.conn_info);
+            discard.discard();
+        }
+
+        let conn = connect_to_compute(
+            ctx,
+            &TcpMechanism {
+                params: &self.params,
+                locks: &self.config.connect_compute_locks,
+            },
+            &self.user_info,
+            self.config.allow_self_signed_compute,
+            self.config.wake_compute_retry_config,
+            self.config.connect_to_compute_retry_config,
+        )
+        .await?;
+        Ok(self.pooled(ctx, conn))
+    }
+
+    /// Reset the connection and give it back to the pool, unless the compute
+    /// closed it.
+    async fn release<P>(
+        &self,
+        mut conn: Client<PooledBackend>,
+        session: &cancellation::Session<P>,
+    ) {
+        session.disable_query_cancellation();
+        let (backend, mut discard) = conn.inner();
+        match write_flush(&mut backend.conn.stream, DISCARD_ALL).await {
+            Ok(()) => {
+                backend.pending_resets += 1;
+                // A closed connection is thrown away by the pool.
+                backend.is_alive();
+            }
+            Err(e) => {
+                warn!("could not reset compute connection: {e}");
+                discard.discard();
+            }
+        }
+    }
+
+    async fn release_or_discard<P>(
+        &self,
+        conn: Client<PooledBackend>,
+        state: &SessionState,
+        session: &cancellation::Session<P>,
+    ) {
+        if state.can_release() {
+            self.release(conn, session).await;
+        } else {
+            self.discard(conn).await;
+        }
+    }
+
+    /// Drop the connection which is in unknown state, cancelling its query.
+    async fn discard(&self, mut conn: Client<PooledBackend>) {
+        let (backend, mut discard) = conn.inner();
+        discard.discard();
+        if let Err(err) = backend.conn.cancel_closure.clone().try_cancel_query().await {
+            warn!(?err, "could not cancel the query in the database");
+        }
+    }
+}
+
+enum Event {
+    Client(io::Result<Option<Message>>),
+    Compute(io::Result<Option<Message>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expected {
+    /// `ParseComplete`, which the client doesn't expect if the statement was
+    /// prepared again by us.
+    ParseComplete { injected: bool },
+    /// `ReadyForQuery` ending a client's request.
+    ReadyForQuery,
+    /// Responses to `DISCARD ALL` or to setting `application_name` up to
+    /// the `ReadyForQuery`, all skipped.
+    Reset,
+}
+
+/// Protocol state of a client session, following both directions to find
+/// transaction boundaries.
+struct SessionState {
+    /// Named prepared statements of the client and their Parse messages.
+    statements: HashMap<Bytes, Bytes>,
+    /// Statements prepared on the connection currently held.
+    prepared: HashSet<Bytes>,
+    /// Responses the compute owes, in order.
+    expected: VecDeque<Expected>,
+    /// Client sent extended query messages not followed by Sync yet.
+    in_batch: bool,
+    /// Transaction status from the last ReadyForQuery.
+    status: u8,
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        Self {
+            statements: HashMap::new(),
+            prepared: HashSet::new(),
+            expected: VecDeque::new(),
+            in_batch: false,
+            status: b'I',
+        }
+    }
+}
+
+impl SessionState {
+    /// Whether the held connection can be given to another client.
+    fn can_release(&self) -> bool {
+        self.status == b'I' && self.expected.is_empty() && !self.in_batch
+    }
+
+    /// Process a client message, appending what is to be sent to compute.
+    fn on_frontend(&mut self, msg: &Message, out: &mut BytesMut) {
+        let body = msg.body();
+        match msg.tag {
+            // Parse
+            b'P' => {
+                let (name, _) = cstr(body).unwrap_or_default();
+                if !name.is_empty() {
+                    let name = msg.raw.slice_ref(name);
+                    self.statements.insert(name.clone(), msg.raw.clone());
+                    self.prepared.insert(name);
+                }
+                self.expected
+                    .push_back(Expected::ParseComplete { injected: false });
+                self.in_batch = true;
+            }
+            // Bind
+            b'B' => {
+                let statement = cstr(body).and_then(|(_portal, rest)| cstr(rest));
+                let (statement, _) = statement.unwrap_or_default();
+                self.ensure_prepared(statement, out);
+                self.in_batch = true;
+            }
+            // Describe, Close
+            b'D' | b'C' => {
+                let target = body.split_first().filter(|(kind, _)| **kind == b'S');
+                if let Some((name, _)) = target.and_then(|(_, rest)| cstr(rest)) {
+                    if msg.tag == b'D' {
+                        self.ensure_prepared(name, out);
+                    } else {
+                        self.statements.remove(name);
+                        self.prepared.remove(name);
+                    }
+                }
+                self.in_batch = true;
+            }
+            // Execute, Flush
+            b'E' | b'H' => self.in_batch = true,
+            // Sync
+            b'S' => {
+                self.expected.push_back(Expected::ReadyForQuery);
+                self.in_batch = false;
+            }
+            // Query
+            b'Q' => {
+                let (query, _) = cstr(body).unwrap_or_default();
+                if deallocates_all(query) {
+                    self.statements.clear();
+                    self.prepared.clear();
+                }
+                self.expected.push_back(Expected::ReadyForQuery);
+            }
+            // FunctionCall
+            b'F' => self.expected.push_back(Expected::ReadyForQuery),
+            _ => {}
+        }
+        out.extend_from_slice(&msg.raw);
+    }
+
+    /// Prepare the client's statement on the held connection if it's not yet.
+    fn ensure_prepared(&mut self, name: &[u8], out: &mut BytesMut) {
+        if name.is_empty() || self.prepared.contains(name) {
+            return;
+        }
+        if let Some((name, parse)) = self.statements.get_key_value(name) {
+            out.extend_from_slice(parse);
+            self.prepared.insert(name.clone());
+            self.expected
+                .push_back(Expected::ParseComplete { injected: true });
+        }
+    }
+
+    /// Process a compute message, returning whether to forward it to the client.
+    fn on_backend(&mut self, msg: &Message) -> bool {
+        if self.expected.front() == Some(&Expected::Reset) {
+            if msg.tag == b'Z' {
+                self.expected.pop_front();
+            }
+            return false;
+        }
+        match msg.tag {
+            // ParseComplete
+            b'1' => match self.expected.front() {
+                Some(&Expected::ParseComplete { injected }) => {
+                    self.expected.pop_front();
+                    !injected
+                }
+                _ => true,
+            },
+            // ErrorResponse: the rest of messages up to Sync are skipped.
+            b'E' => {
+                while let Some(Expected::ParseComplete { .. }) = self.expected.front() {
+                    self.expected.pop_front();
+                }
+                true
+            }
+            // ReadyForQuery
+            b'Z' => {
+                if self.expected.front() == Some(&Expected::ReadyForQuery) {
+                    self.expected.pop_front();
+                }
+                self.status = msg.body().first().copied().unwrap_or(b'I');
+                true
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Append a query setting `application_name`.
+fn set_application_name(name: &str, out: &mut BytesMut) {
+    // Escape string syntax doesn't depend on standard_conforming_strings.
+    let name = name.replace('\\', "\\\\").replace('\'', "''");
+    let query = format!("SET application_name = E'{name}'\0");
+    out.extend_from_slice(b"Q");
+    out.extend_from_slice(&(query.len() as u32 + 4).to_be_bytes());
+    out.extend_from_slice(query.as_bytes());
+}
+
+/// Whether the query removes all prepared statements of the session.
+fn deallocates_all(query: &[u8]) -> bool {
+    let query = String::from_utf8_lossy(query).to_ascii_uppercase();
+    let words: Vec<_> = query
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .collect();
+    matches!(
+        words.as_slice(),
+        ["DISCARD", "ALL"] | ["DEALLOCATE", "ALL"] | ["DEALLOCATE", "PREPARE", "ALL"]
+    )
+}
+
+/// Split a null-terminated string off the start of the buffer, returning the
+/// string without the terminator and the rest of the buffer.
+fn cstr(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = buf.iter().position(|&b| b == 0)?;
+    Some((&buf[..end], &buf[end + 1..]))
+}
+
+/// Protocol message with its type and length.
+struct Message {
+    tag: u8,
+    raw: Bytes,
+}
+
+impl Message {
+    /// Split a whole message off the buffer, if it's there.
+    fn parse(buf: &mut BytesMut) -> io::Result<Option<Message>> {
+        if buf.len() < 5 {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes(buf[1..5].try_into().expect("4 bytes")) as usize;
+        if len < 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid message length {len}"),
+            ));
+        }
+        if buf.len() < len + 1 {
+            return Ok(None);
+        }
+        let raw = buf.split_to(len + 1).freeze();
+        Ok(Some(Message { tag: raw[0], raw }))
+    }
+
+    fn body(&self) -> &[u8] {
+        &self.raw[5..]
+    }
+}
+
+/// Read a whole message, None if the stream is closed. Cancellation safe:
+/// partially read data stays in the buffer.
+async fn read_message(
+    stream: &mut (impl AsyncRead + Unpin),
+    buf: &mut BytesMut,
+) -> io::Result<Option<Message>> {
+    loop {
+        if let Some(msg) = Message::parse(buf)? {
+            return Ok(Some(msg));
+        }
+        if stream.read_buf(buf).await? == 0 {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed in the middle of a message",
+            ));
+        }
+    }
+}
+
+async fn write_flush(stream: &mut (impl AsyncWrite + Unpin), buf: &[u8]) -> io::Result<()> {
+    stream.write_all(buf).await?;
+    stream.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(tag: u8, body: &[u8]) -> Message {
+        let mut raw = BytesMut::new();
+        raw.extend_from_slice(&[tag]);
+        raw.extend_from_slice(&(body.len() as u32 + 4).to_be_bytes());
+        raw.extend_from_slice(body);
+        Message {
+            tag,
+            raw: raw.freeze(),
+        }
+    }
+
+    fn frontend(state: &mut SessionState, msgs: &[Message]) -> Vec<u8> {
+        let mut out = BytesMut::new();
+        for m in msgs {
+            state.on_frontend(m, &mut out);
+        }
+        out.to_vec()
+    }
+
+    fn parse(name: &str) -> Message {
+        msg(b'P', format!("{name}\0select 1\0\0\0").as_bytes())
+    }
+
+    fn bind(name: &str) -> Message {
+        msg(b'B', format!("\0{name}\0\0\0\0\0\0\0").as_bytes())
+    }
+
+    #[test]
+    fn transaction_boundaries() {
+        let mut state = SessionState::default();
+        frontend(&mut state, &[msg(b'Q', b"begin\0")]);
+        assert!(!state.can_release());
+        assert!(state.on_backend(&msg(b'C', b"BEGIN\0")));
+        assert!(state.on_backend(&msg(b'Z', b"T")));
+        assert!(!state.can_release());
+
+        frontend(&mut state, &[msg(b'Q', b"commit\0")]);
+        assert!(state.on_backend(&msg(b'Z', b"I")));
+        assert!(state.can_release());
+
+        // Pipelined requests: released only after the last one.
+        frontend(
+            &mut state,
+            &[msg(b'Q', b"select 1\0"), msg(b'Q', b"select 2\0")],
+        );
+        assert!(state.on_backend(&msg(b'Z', b"I")));
+        assert!(!state.can_release());
+        assert!(state.on_backend(&msg(b'Z', b"I")));
+        assert!(state.can_release());
+
+        // Extended query without Sync yet.
+        frontend(&mut state, &[parse(""), bind(""), msg(b'H', b"")]);
+        assert!(state.on_backend(&msg(b'1', b"")));
+        assert!(!state.can_release());
+        frontend(&mut state, &[msg(b'S', b"")]);
+        assert!(state.on_backend(&msg(b'Z', b"I")));
+        assert!(state.can_release());
+    }
+
+    #[test]
+    fn prepared_statements_on_new_connection() {
+        let mut state = SessionState::default();
+        frontend(&mut state, &[parse("s1"), msg(b'S', b"")]);
+        assert!(state.on_backend(&msg(b'1', b"")));
+        assert!(state.on_backend(&msg(b'Z', b"I")));
+
+        // Moved to another connection, which was reset.
+        state.prepared.clear();
+        state.expected.push_back(Expected::Reset);
+        let out = frontend(&mut state, &[bind("s1"), msg(b'S', b"")]);
+        let expected = [parse("s1").raw, bind("s1").raw, msg(b'S', b"").raw].concat();
+        assert_eq!(out, expected);
+
+        // Reset responses and the injected ParseComplete are hidden.
+        assert!(!state.on_backend(&msg(b'C', b"DISCARD ALL\0")));
+        assert!(!state.on_backend(&msg(b'Z', b"I")));
+        assert!(!state.on_backend(&msg(b'1', b"")));
+        assert!(state.on_backend(&msg(b'2', b"")));
+        assert!(state.on_backend(&msg(b'Z', b"I")));
+        assert!(state.can_release());
+
+        // Already prepared on this connection.
+        let out = frontend(&mut state, &[bind("s1")]);
+        assert_eq!(out, bind("s1").raw.to_vec());
+        frontend(&mut state, &[msg(b'S', b"")]);
+        state.on_backend(&msg(b'Z', b"I"));
+
+        // Closed statements and DISCARD ALL are forgotten.
+        frontend(&mut state, &[msg(b'C', b"Ss1\0"), parse("s2")]);
+        assert!(!state.statements.contains_key(b"s1".as_slice()));
+        frontend(&mut state, &[msg(b'Q', b"discard all;\0")]);
+        assert!(state.statements.is_empty());
+    }
+
+    #[test]
+    fn error_skips_pending_parses() {
+        let mut state = SessionState::default();
+        frontend(
+            &mut state,
+            &[parse("a"), parse("b"), msg(b'S', b""), parse("c")],
+        );
+        assert!(state.on_backend(&msg(b'E', b"\0")));
+        assert!(state.on_backend(&msg(b'Z', b"I")));
+        assert_eq!(
+            state.expected,
+            [Expected::ParseComplete { injected: false }]
+        );
+    }
+
+    #[test]
+    fn set_application_name_query() {
+        let mut out = BytesMut::new();
+        set_application_name("it's a\\b", &mut out);
+        let query = msg(b'Q', b"SET application_name = E'it''s a\\\\b'\0");
+        assert_eq!(out.freeze(), query.raw);
+    }
+
+    #[test]
+    fn poolable_startup_params() {
+        let params = StartupMessageParams::new([
+            ("user", "alice"),
+            ("database", "db"),
+            ("options", "endpoint=ep-foo-123"),
+        ]);
+        assert!(is_poolable(&params));
+        let params = StartupMessageParams::new([("user", "alice"), ("search_path", "s")]);
+        assert!(!is_poolable(&params));
+        let params = StartupMessageParams::new([("user", "alice"), ("options", "-c geqo=off")]);
+        assert!(!is_poolable(&params));
+    }
+}
@@ -0,0 +1,356 @@
+//! Rate limits shared by all proxy instances through redis.
+//!
+//! Rate limiters keep their state per process, so clients spread across N
+//! proxies would get N times the budget. The distributed limiter counts
+//! requests per key in the fixed windows of each bucket, aligned to the wall
+//! clock so that all proxies agree on them. Adding to the counts in redis on
+//! every request would add a round trip to it, so the counts are
+//! pre-aggregated instead: a background task periodically adds the local
+//! counts of recently used keys to redis and reads back the totals of all
+//! proxies. Requests are checked against the last known totals plus what
+//! this proxy counted since, so limits are enforced up to one sync interval
+//! late.
+//!
+//! When redis is unavailable, the totals are stale and only the local limits
+//! apply, until the next successful sync. Keys whose windows have all expired
+//! are cleaned up on a separate tick, so that the map doesn't grow while
+//! redis is unavailable.
+
+use std::{
+    borrow::Cow,
+    convert::Infallible,
+    hash::Hash,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use ahash::RandomState;
+use dashmap::DashMap;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn};
+
+use super::RateBucketInfo;
+
+/// How often keys with only expired windows are removed.
+const GC_INTERVAL: Duration = Duration::from_secs(10);
+use crate::{
+    intern::EndpointIdInt,
+    metrics::{Metrics, RedisErrors},
+    redis::connection_with_credentials_provider::ConnectionWithCredentialsProvider,
+};
+
+/// Key of a distributed rate limit.
+pub trait DistributedKey: Hash + Eq + Clone + Send + Sync + 'static {
+    /// Identifies the key in redis.
+    fn redis_key(&self) -> String;
+}
+
+impl DistributedKey for EndpointIdInt {
+    fn redis_key(&self) -> String {
+        self.as_str().to_owned()
+    }
+}
+
+pub struct DistributedRateLimiter<K> {
+    /// Name of the limiter, to separate its keys in redis.
+    name: &'static str,
+    map: DashMap<K, Vec<Window>, RandomState>,
+    info: Cow<'static, [RateBucketInfo]>,
+    sync_interval: Duration,
+    /// Whether the last sync with redis succeeded.
+    synced: AtomicBool,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Window {
+    id: u64,
+    /// Requests counted since the last sync.
+    pending: u32,
+    /// Requests counted by all proxies as of the last sync.
+    total: u32,
+}
+
+impl<K: Hash + Eq + Clone> DistributedRateLimiter<K> {
+    pub fn new(
+        name: &'static str,
+        info: impl Into<Cow<'static, [RateBucketInfo]>>,
+        sync_interval: Duration,
+    ) -> Self {
+        let info = info.into();
+        info!(name, buckets = ?info, "distributed rate limiter");
+        Self {
+            name,
+            map: DashMap::with_hasher_and_shard_amount(RandomState::new(), 64),
+            info,
+            sync_interval,
+            synced: AtomicBool::new(false),
+        }
+    }
+
+    /// Check that the requests of all proxies for the key are below the limits.
+    /// Allows all requests if the totals couldn't be synced with redis.
+    pub(crate) fn check(&self, key: &K, n: u32) -> bool {
+        self.check_at(key, n, SystemTime::now())
+    }
+
+    fn check_at(&self, key: &K, n: u32, now: SystemTime) -> bool {
+        let mut windows = self
+            .map
+            .entry(key.clone())
+            .or_insert_with(|| vec![Window::default(); self.info.len()]);
+
+        for (window, info) in windows.iter_mut().zip(&*self.info) {
+            let id = window_id(now, info);
+            if window.id != id {
+                *window = Window {
+                    id,
+                    ..Window::default()
+                };
+            }
+        }
+
+        // keep counting while redis is unavailable, so that the totals are
+        // accurate once it's back.
+        let should_allow_request = !self.synced.load(Ordering::Relaxed)
+            || windows.iter().zip(&*self.info).all(|(window, info)| {
+                window
+                    .total
+                    .saturating_add(window.pending)
+                    .saturating_add(n)
+                    <= info.max_rpi
+            });
+
+        if should_allow_request {
+            windows
+                .iter_mut()
+                .for_each(|w| w.pending = w.pending.saturating_add(n));
+        }
+
+        should_allow_request
+    }
+
+    /// Remove the keys which only have expired windows.
+    fn gc(&self, now: SystemTime) {
+        let ids: Vec<u64> = self.info.iter().map(|info| window_id(now, info)).collect();
+        self.map.retain(|_, windows| {
+            windows
+                .iter()
+                .zip(&ids)
+                .any(|(window, id)| window.id == *id)
+        });
+    }
+
+    async fn gc_loop(&self) -> Infallible {
+        let mut interval = tokio::time::interval(GC_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            self.gc(SystemTime::now());
+        }
+    }
+}
+
+impl<K: DistributedKey> DistributedRateLimiter<K> {
+    /// Periodically sync the counts with redis.
+    pub async fn sync(&self, con: ConnectionWithCredentialsProvider) -> anyhow::Result<Infallible> {
+        tokio::select! {
+            res = self.sync_loop(con) => res,
+            never = self.gc_loop() => match never {},
+        }
+    }
+
+    async fn sync_loop(
+        &self,
+        mut con: ConnectionWithCredentialsProvider,
+    ) -> anyhow::Result<Infallible> {
+        let mut interval = tokio::time::interval(self.sync_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+
+            let res = if self.synced.load(Ordering::Relaxed) {
+                self.sync_once(&mut con).await
+            } else {
+                match con.connect().await {
+                    Ok(()) => self.sync_once(&mut con).await,
+                    Err(e) => Err(e),
+                }
+            };
+
+            match res {
+                Ok(()) => {
+                    if !self.synced.swap(true, Ordering::Relaxed) {
+                        info!(
+                            name = self.name,
+                            "distributed rate limits are synced with redis"
+                        );
+                    }
+                }
+                Err(e) => {
+                    Metrics::get().proxy.redis_errors_total.inc(RedisErrors {
+                        channel: "rate_limiter",
+                    });
+                    if self.synced.swap(false, Ordering::Relaxed) {
+                        warn!(
+                            name = self.name,
+                            "failed to sync distributed rate limits, falling back to local limits: {e:?}"
+                        );
+                    } else {
+                        debug!(
+                            name = self.name,
+                            "failed to sync distributed rate limits: {e:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    async fn sync_once(&self, con: &mut ConnectionWithCredentialsProvider) -> anyhow::Result<()> {
+        let now = SystemTime::now();
+
+        // Take the counts of keys used since the last sync.
+        let ids: Vec<u64> = self.info.iter().map(|info| window_id(now, info)).collect();
+        let mut batch = vec![];
+        for mut entry in self.map.iter_mut() {
+            let used = entry
+                .iter()
+                .zip(&ids)
+                .any(|(window, id)| window.id == *id && window.pending > 0);
+            if !used {
+                continue;
+            }
+            let key = entry.key().clone();
+            for (bucket, (window, id)) in entry.iter_mut().zip(&ids).enumerate() {
+                if window.id == *id {
+                    let pending = std::mem::take(&mut window.pending);
+                    batch.push((key.clone(), bucket, window.id, pending));
+                }
+            }
+        }
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        for (key, bucket, id, pending) in &batch {
+            let info = &self.info[*bucket];
+            let interval = info.interval.as_millis() as u64;
+            let redis_key = format!(
+                "proxy:rate_limit:{}:{}:{interval}:{id}",
+                self.name,
+                key.redis_key()
+            );
+            pipe.cmd("INCRBY")
+                .arg(&redis_key)
+                .arg(*pending)
+                .cmd("PEXPIRE")
+                .arg(&redis_key)
+                .arg(interval * 2)
+                .ignore();
+        }
+
+        let res: anyhow::Result<Vec<u64>> =
+            match tokio::time::timeout(self.sync_interval, pipe.query_async(con)).await {
+                Ok(res) => res.map_err(Into::into),
+                Err(e) => Err(e.into()),
+            };
+
+        match res {
+            Ok(totals) => {
+                for ((key, bucket, id, _), total) in batch.into_iter().zip(totals) {
+                    if let Some(mut windows) = self.map.get_mut(&key) {
+                        let window = &mut windows[bucket];
+                        if window.id == id {
+                            window.total = u32::try_from(total).unwrap_or(u32::MAX);
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Err(e) => {
+                // put the counts back to add them with the next sync
+                for (key, bucket, id, pending) in batch {
+                    if let Some(mut windows) = self.map.get_mut(&key) {
+                        let window = &mut windows[bucket];
+                        if window.id == id {
+                            window.pending = window.pending.saturating_add(pending);
+                        }
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Windows of the bucket are aligned to the wall clock, to match between proxies.
+fn window_id(now: SystemTime, info: &RateBucketInfo) -> u64 {
+    let since_epoch = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    (since_epoch.as_millis() / info.interval.as_millis().max(1)) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    #[test]
+    fn distributed_limits() {
+        let limiter =
+            DistributedRateLimiter::new("test", vec!["20@1s".parse().unwrap()], Duration::ZERO);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+
+        // not synced with redis, only local limits apply
+        for _ in 0..30 {
+            assert!(limiter.check_at(&1, 1, now));
+        }
+
+        // as if other proxies made 15 requests
+        limiter.synced.store(true, Ordering::Relaxed);
+        for window in limiter.map.get_mut(&1).unwrap().iter_mut() {
+            window.pending = 0;
+            window.total = 15;
+        }
+        for _ in 0..5 {
+            assert!(limiter.check_at(&1, 1, now));
+        }
+        assert!(!limiter.check_at(&1, 1, now));
+        assert!(!limiter.check_at(&1, 1, now + Duration::from_millis(999)));
+        // other keys are not affected
+        assert!(limiter.check_at(&2, 10, now));
+
+        // the next window starts from zero
+        let next = now + Duration::from_secs(1);
+        for _ in 0..20 {
+            assert!(limiter.check_at(&1, 1, next));
+        }
+        assert!(!limiter.check_at(&1, 1, next));
+    }
+
+    #[test]
+    fn gc_removes_expired_keys() {
+        let limiter =
+            DistributedRateLimiter::new("test", vec!["20@1s".parse().unwrap()], Duration::ZERO);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+
+        assert!(limiter.check_at(&1, 1, now));
+        assert!(limiter.check_at(&2, 1, now + Duration::from_secs(1)));
+
+        limiter.gc(now + Duration::from_millis(1500));
+        assert!(!limiter.map.contains_key(&1));
+        assert!(limiter.map.contains_key(&2));
+    }
+
+    #[test]
+    fn windows_are_aligned() {
+        let info: RateBucketInfo = "10@1m".parse().unwrap();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(600);
+        assert_eq!(window_id(start, &info), 10);
+        assert_eq!(window_id(start + Duration::from_secs(59), &info), 10);
+        assert_eq!(window_id(start + Duration::from_secs(60), &info), 11);
+    }
+}
@@ -1,6 +1,9 @@
 use std::{
     hash::Hash,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use ahash::RandomState;
@@ -10,6 +13,7 @@
 use tracing::info;
 use utils::leaky_bucket::LeakyBucketState;
 
+use super::DistributedRateLimiter;
 use crate::intern::EndpointIdInt;
 
 // Simple per-endpoint rate limiter.
@@ -19,9 +23,10 @@ pub struct LeakyBucketRateLimiter<Key> {
     map: DashMap<Key, LeakyBucketState, RandomState>,
     config: utils::leaky_bucket::LeakyBucketConfig,
     access_count: AtomicUsize,
+    distributed: Option<Arc<DistributedRateLimiter<Key>>>,
 }
 
-impl<K: Hash + Eq> LeakyBucketRateLimiter<K> {
+impl<K: Hash + Eq + Clone> LeakyBucketRateLimiter<K> {
     pub const DEFAULT: LeakyBucketConfig = LeakyBucketConfig {
         rps: 600.0,
         max: 1500.0,
@@ -32,9 +37,21 @@ pub fn new_with_shards(config: LeakyBucketConfig, shards: usize) -> Self {
             map: DashMap::with_hasher_and_shard_amount(RandomState::new(), shards),
             config: config.into(),
             access_count: AtomicUsize::new(0),
+            distributed: None,
         }
     }
 
+    /// Also enforce the limits across proxies.
+    #[must_use]
+    pub fn with_distributed(mut self, distributed: Arc<DistributedRateLimiter<K>>) -> Self {
+        self.distributed = Some(distributed);
+        self
+    }
+
+    pub fn distributed(&self) -> Option<&Arc<DistributedRateLimiter<K>>> {
+        self.distributed.as_ref()
+    }
+
     /// Check that number of connections to the endpoint is below `max_rps` rps.
     pub(crate) fn check(&self, key: K, n: u32) -> bool {
         let now = Instant::now();
@@ -48,7 +65,21 @@ pub(crate) fn check(&self, key: K, n: u32) -> bool {
             .entry(key)
             .or_insert_with(|| LeakyBucketState { empty_at: now });
 
-        entry.add_tokens(&self.config, now, n as f64).is_ok()
+        let empty_at = entry.empty_at;
+        if entry.add_tokens(&self.config, now, n as f64).is_err() {
+            return false;
+        }
+
+        let should_allow_request = self
+            .distributed
+            .as_ref()
+            .map_or(true, |d| d.check(entry.key(), n));
+        if !should_allow_request {
+            // only take the tokens if the request will actually be accepted
+            entry.empty_at = empty_at;
+        }
+
+        should_allow_request
     }
 
     fn do_gc(&self, now: Instant) {
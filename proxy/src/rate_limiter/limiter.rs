@@ -4,7 +4,7 @@
     hash::{BuildHasher, Hash},
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Mutex,
+        Arc, Mutex,
     },
 };
 
@@ -15,6 +15,7 @@
 use tokio::time::{Duration, Instant};
 use tracing::info;
 
+use super::DistributedRateLimiter;
 use crate::intern::EndpointIdInt;
 
 pub(crate) struct GlobalRateLimiter {
@@ -68,6 +69,7 @@ pub struct BucketRateLimiter<Key, Rand = StdRng, Hasher = RandomState> {
     info: Cow<'static, [RateBucketInfo]>,
     access_count: AtomicUsize,
     rand: Mutex<Rand>,
+    distributed: Option<Arc<DistributedRateLimiter<Key>>>,
 }
 
 #[derive(Clone, Copy)]
@@ -169,13 +171,13 @@ pub const fn new(max_rps: u32, interval: Duration) -> Self {
     }
 }
 
-impl<K: Hash + Eq> BucketRateLimiter<K> {
+impl<K: Hash + Eq + Clone> BucketRateLimiter<K> {
     pub fn new(info: impl Into<Cow<'static, [RateBucketInfo]>>) -> Self {
         Self::new_with_rand_and_hasher(info, StdRng::from_entropy(), RandomState::new())
     }
 }
 
-impl<K: Hash + Eq, R: Rng, S: BuildHasher + Clone> BucketRateLimiter<K, R, S> {
+impl<K: Hash + Eq + Clone, R: Rng, S: BuildHasher + Clone> BucketRateLimiter<K, R, S> {
     fn new_with_rand_and_hasher(
         info: impl Into<Cow<'static, [RateBucketInfo]>>,
         rand: R,
@@ -188,9 +190,21 @@ fn new_with_rand_and_hasher(
             map: DashMap::with_hasher_and_shard_amount(hasher, 64),
             access_count: AtomicUsize::new(1), // start from 1 to avoid GC on the first request
             rand: Mutex::new(rand),
+            distributed: None,
         }
     }
 
+    /// Also enforce the limits across proxies.
+    #[must_use]
+    pub fn with_distributed(mut self, distributed: Arc<DistributedRateLimiter<K>>) -> Self {
+        self.distributed = Some(distributed);
+        self
+    }
+
+    pub fn distributed(&self) -> Option<&Arc<DistributedRateLimiter<K>>> {
+        self.distributed.as_ref()
+    }
+
     /// Check that number of connections to the endpoint is below `max_rps` rps.
     pub(crate) fn check(&self, key: K, n: u32) -> bool {
         // do a partial GC every 2k requests. This cleans up ~ 1/64th of the map.
@@ -215,7 +229,11 @@ pub(crate) fn check(&self, key: K, n: u32) -> bool {
         let should_allow_request = entry
             .iter_mut()
             .zip(&*self.info)
-            .all(|(bucket, info)| bucket.should_allow_request(info, now, n));
+            .all(|(bucket, info)| bucket.should_allow_request(info, now, n))
+            && self
+                .distributed
+                .as_ref()
+                .map_or(true, |d| d.check(entry.key(), n));
 
         if should_allow_request {
             // only increment the bucket counts if the request will actually be accepted
@@ -1,3 +1,4 @@
+mod distributed;
 mod leaky_bucket;
 mod limit_algorithm;
 mod limiter;
@@ -10,5 +11,6 @@
 };
 pub(crate) use limiter::GlobalRateLimiter;
 
+pub use distributed::{DistributedKey, DistributedRateLimiter};
 pub use leaky_bucket::{EndpointRateLimiter, LeakyBucketConfig, LeakyBucketRateLimiter};
 pub use limiter::{BucketRateLimiter, RateBucketInfo, WakeComputeRateLimiter};
@@ -5,6 +5,7 @@
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use crate::auth::lockout::LockoutNotification;
 use crate::rate_limiter::{GlobalRateLimiter, RateBucketInfo};
 
 use super::{
@@ -96,16 +97,8 @@ pub fn new(
         })
     }
 
-    async fn publish(
-        &mut self,
-        cancel_key_data: CancelKeyData,
-        session_id: Uuid,
-    ) -> anyhow::Result<()> {
-        let payload = serde_json::to_string(&Notification::Cancel(CancelSession {
-            region_id: Some(self.region_id.clone()),
-            cancel_key_data,
-            session_id,
-        }))?;
+    async fn publish(&mut self, notification: &Notification) -> anyhow::Result<()> {
+        let payload = serde_json::to_string(notification)?;
         let _: () = self.client.publish(PROXY_CHANNEL_NAME, payload).await?;
         Ok(())
     }
@@ -119,16 +112,12 @@ pub(crate) async fn try_connect(&mut self) -> anyhow::Result<()> {
         }
         Ok(())
     }
-    async fn try_publish_internal(
-        &mut self,
-        cancel_key_data: CancelKeyData,
-        session_id: Uuid,
-    ) -> anyhow::Result<()> {
+    async fn try_publish_internal(&mut self, notification: Notification) -> anyhow::Result<()> {
         if !self.limiter.check() {
-            tracing::info!("Rate limit exceeded. Skipping cancellation message");
+            tracing::info!("Rate limit exceeded. Skipping message");
             return Err(anyhow::anyhow!("Rate limit exceeded"));
         }
-        match self.publish(cancel_key_data, session_id).await {
+        match self.publish(&notification).await {
             Ok(()) => return Ok(()),
             Err(e) => {
                 tracing::error!("failed to publish a message: {e}");
@@ -136,7 +125,16 @@ async fn try_publish_internal(
         }
         tracing::info!("Publisher is disconnected. Reconnectiong...");
         self.try_connect().await?;
-        self.publish(cancel_key_data, session_id).await
+        self.publish(&notification).await
+    }
+
+    pub(crate) async fn try_publish_lockout(
+        &mut self,
+        lockout: LockoutNotification,
+    ) -> anyhow::Result<()> {
+        tracing::info!("publishing authentication lockout to Redis");
+        self.try_publish_internal(Notification::AuthLockout(lockout))
+            .await
     }
 }
 
@@ -147,7 +145,12 @@ async fn try_publish(
         session_id: Uuid,
     ) -> anyhow::Result<()> {
         tracing::info!("publishing cancellation key to Redis");
-        match self.try_publish_internal(cancel_key_data, session_id).await {
+        let notification = Notification::Cancel(CancelSession {
+            region_id: Some(self.region_id.clone()),
+            cancel_key_data,
+            session_id,
+        });
+        match self.try_publish_internal(notification).await {
             Ok(()) => {
                 tracing::info!("cancellation key successfuly published to Redis");
                 Ok(())
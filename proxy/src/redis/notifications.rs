@@ -9,6 +9,7 @@
 
 use super::connection_with_credentials_provider::ConnectionWithCredentialsProvider;
 use crate::{
+    auth::lockout::{AuthLockout, LockoutNotification},
     cache::project_info::ProjectInfoCache,
     cancellation::{CancelMap, CancellationHandler},
     intern::{ProjectIdInt, RoleNameInt},
@@ -44,8 +45,17 @@ pub(crate) enum Notification {
         deserialize_with = "deserialize_json_string"
     )]
     PasswordUpdate { password_update: PasswordUpdate },
+    #[serde(
+        rename = "/query_policy_updated",
+        deserialize_with = "deserialize_json_string"
+    )]
+    QueryPolicyUpdate {
+        query_policy_update: QueryPolicyUpdate,
+    },
     #[serde(rename = "/cancel_session")]
     Cancel(CancelSession),
+    #[serde(rename = "/auth_lockout")]
+    AuthLockout(LockoutNotification),
 }
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub(crate) struct AllowedIpsUpdate {
@@ -57,6 +67,10 @@ pub(crate) struct PasswordUpdate {
     role_name: RoleNameInt,
 }
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct QueryPolicyUpdate {
+    project_id: ProjectIdInt,
+}
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub(crate) struct CancelSession {
     pub(crate) region_id: Option<String>,
     pub(crate) cancel_key_data: CancelKeyData,
@@ -75,6 +89,7 @@ fn deserialize_json_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 struct MessageHandler<C: ProjectInfoCache + Send + Sync + 'static> {
     cache: Arc<C>,
     cancellation_handler: Arc<CancellationHandler<()>>,
+    auth_lockout: &'static AuthLockout,
     region_id: String,
 }
 
@@ -83,6 +98,7 @@ fn clone(&self) -> Self {
         Self {
             cache: self.cache.clone(),
             cancellation_handler: self.cancellation_handler.clone(),
+            auth_lockout: self.auth_lockout,
             region_id: self.region_id.clone(),
         }
     }
@@ -92,11 +108,13 @@ impl<C: ProjectInfoCache + Send + Sync + 'static> MessageHandler<C> {
     pub(crate) fn new(
         cache: Arc<C>,
         cancellation_handler: Arc<CancellationHandler<()>>,
+        auth_lockout: &'static AuthLockout,
         region_id: String,
     ) -> Self {
         Self {
             cache,
             cancellation_handler,
+            auth_lockout,
             region_id,
         }
     }
@@ -150,7 +168,16 @@ async fn handle_message(&self, msg: redis::Msg) -> anyhow::Result<()> {
                     }
                 }
             }
-            Notification::AllowedIpsUpdate { .. } | Notification::PasswordUpdate { .. } => {
+            Notification::AuthLockout(lockout) => {
+                Metrics::get()
+                    .proxy
+                    .redis_events_count
+                    .inc(RedisEventsCount::AuthLockout);
+                self.auth_lockout.handle_notification(lockout);
+            }
+            Notification::AllowedIpsUpdate { .. }
+            | Notification::PasswordUpdate { .. }
+            | Notification::QueryPolicyUpdate { .. } => {
                 invalidate_cache(self.cache.clone(), msg.clone());
                 if matches!(msg, Notification::AllowedIpsUpdate { .. }) {
                     Metrics::get()
@@ -162,6 +189,11 @@ async fn handle_message(&self, msg: redis::Msg) -> anyhow::Result<()> {
                         .proxy
                         .redis_events_count
                         .inc(RedisEventsCount::PasswordUpdate);
+                } else if matches!(msg, Notification::QueryPolicyUpdate { .. }) {
+                    Metrics::get()
+                        .proxy
+                        .redis_events_count
+                        .inc(RedisEventsCount::QueryPolicyUpdate);
                 }
                 // It might happen that the invalid entry is on the way to be cached.
                 // To make sure that the entry is invalidated, let's repeat the invalidation in INVALIDATION_LAG seconds.
@@ -188,7 +220,12 @@ fn invalidate_cache<C: ProjectInfoCache>(cache: Arc<C>, msg: Notification) {
                 password_update.project_id,
                 password_update.role_name,
             ),
-        Notification::Cancel(_) => unreachable!("cancel message should be handled separately"),
+        Notification::QueryPolicyUpdate {
+            query_policy_update,
+        } => cache.invalidate_query_policies_for_project(query_policy_update.project_id),
+        Notification::Cancel(_) | Notification::AuthLockout(_) => {
+            unreachable!("proxy messages should be handled separately")
+        }
     }
 }
 
@@ -238,6 +275,7 @@ pub async fn task_main<C>(
     redis: ConnectionWithCredentialsProvider,
     cache: Arc<C>,
     cancel_map: CancelMap,
+    auth_lockout: &'static AuthLockout,
     region_id: String,
 ) -> anyhow::Result<Infallible>
 where
@@ -247,7 +285,7 @@ pub async fn task_main<C>(
         cancel_map,
         crate::metrics::CancellationSource::FromRedis,
     ));
-    let handler = MessageHandler::new(cache, cancellation_handler, region_id);
+    let handler = MessageHandler::new(cache, cancellation_handler, auth_lockout, region_id);
     // 6h - 1m.
     // There will be 1 minute overlap between two tasks. But at least we can be sure that no message is lost.
     let mut interval = tokio::time::interval(std::time::Duration::from_secs(6 * 60 * 60 - 60));
@@ -325,6 +363,30 @@ fn parse_password_updated() -> anyhow::Result<()> {
 
         Ok(())
     }
+
+    #[test]
+    fn parse_query_policy_updated() -> anyhow::Result<()> {
+        let project_id: ProjectId = "new_project".into();
+        let data = format!("{{\"project_id\": \"{project_id}\"}}");
+        let text = json!({
+            "type": "message",
+            "topic": "/query_policy_updated",
+            "data": data,
+        })
+        .to_string();
+
+        let result: Notification = serde_json::from_str(&text)?;
+        assert_eq!(
+            result,
+            Notification::QueryPolicyUpdate {
+                query_policy_update: QueryPolicyUpdate {
+                    project_id: (&project_id).into()
+                }
+            }
+        );
+
+        Ok(())
+    }
     #[test]
     fn parse_cancel_session() -> anyhow::Result<()> {
         let cancel_key_data = CancelKeyData {
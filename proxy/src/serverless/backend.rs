@@ -10,7 +10,9 @@
     auth::{
         self,
         backend::{local::StaticAuthRules, ComputeCredentials, ComputeUserInfo},
-        check_peer_addr_is_in_list, AuthError,
+        check_peer_addr_is_in_list,
+        lockout::LockoutKey,
+        AuthError,
     },
     compute,
     config::ProxyConfig,
@@ -18,6 +20,7 @@
     control_plane::{
         errors::{GetAuthInfoError, WakeComputeError},
         locks::ApiLocks,
+        messages::QueryPolicy,
         provider::ApiLockError,
         CachedNodeInfo,
     },
@@ -67,6 +70,10 @@ pub(crate) async fn authenticate_with_password(
         {
             return Err(AuthError::too_many_connections());
         }
+        let auth_lockout = &self.config.authentication_config.auth_lockout;
+        let lockout_key = LockoutKey::new(&user_info.endpoint, &user_info.user, ctx.peer_addr());
+        auth_lockout.check(&lockout_key)?;
+
         let cached_secret = match maybe_secret {
             Some(secret) => secret,
             None => backend.get_role_secret(ctx).await?,
@@ -82,6 +89,7 @@ pub(crate) async fn authenticate_with_password(
             None => {
                 // If we don't have an authentication secret, for the http flow we can just return an error.
                 info!("authentication info not found");
+                auth_lockout.record_failure(lockout_key);
                 return Err(AuthError::auth_failed(&*user_info.user));
             }
         };
@@ -96,10 +104,12 @@ pub(crate) async fn authenticate_with_password(
         let res = match auth_outcome {
             crate::sasl::Outcome::Success(key) => {
                 info!("user successfully authenticated");
+                auth_lockout.record_success(&lockout_key);
                 Ok(key)
             }
             crate::sasl::Outcome::Failure(reason) => {
                 info!("auth backend failed with an error: {reason}");
+                auth_lockout.record_failure(lockout_key);
                 Err(AuthError::auth_failed(&*user_info.user))
             }
         };
@@ -109,6 +119,16 @@ pub(crate) async fn authenticate_with_password(
         })
     }
 
+    /// Get restrictions on queries of the role.
+    pub(crate) async fn get_query_policy(
+        &self,
+        ctx: &RequestMonitoring,
+        user_info: &ComputeUserInfo,
+    ) -> Result<Arc<QueryPolicy>, AuthError> {
+        let backend = self.auth_backend.as_ref().map(|()| user_info.clone());
+        Ok(backend.get_query_policy(ctx).await?.value)
+    }
+
     pub(crate) async fn authenticate_with_jwt(
         &self,
         ctx: &RequestMonitoring,
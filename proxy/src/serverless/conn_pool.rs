@@ -28,6 +28,7 @@
 use tracing::{info, info_span, Instrument};
 
 use super::backend::HttpConnError;
+use super::statement_cache::StatementCache;
 
 #[derive(Debug, Clone)]
 pub(crate) struct ConnInfoWithAuth {
@@ -483,24 +484,16 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
     aux: MetricsAuxInfo,
 ) -> Client<C> {
     let conn_gauge = Metrics::get().proxy.db_connections.guard(ctx.protocol());
-    let mut session_id = ctx.session_id();
-    let (tx, mut rx) = tokio::sync::watch::channel(session_id);
-
-    let span = info_span!(parent: None, "connection", %conn_id);
-    let cold_start_info = ctx.cold_start_info();
-    span.in_scope(|| {
-        info!(cold_start_info = cold_start_info.as_str(), %conn_info, %session_id, "new connection");
-    });
-    let pool = match conn_info.endpoint_cache_key() {
-        Some(endpoint) => Arc::downgrade(&global_pool.get_or_create_endpoint_pool(&endpoint)),
-        None => Weak::new(),
-    };
-    let pool_clone = pool.clone();
-
-    let db_user = conn_info.db_and_user();
-    let idle = global_pool.get_idle_timeout();
-    let cancel = CancellationToken::new();
-    let cancelled = cancel.clone().cancelled_owned();
+    let (client, task) = new_client(&global_pool, ctx, conn_info, client, conn_id, aux);
+    let ConnectionTask {
+        span,
+        mut session_id,
+        mut rx,
+        pool,
+        db_user,
+        idle,
+        cancelled,
+    } = task;
 
     tokio::spawn(
     async move {
@@ -531,13 +524,7 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
             if idle_timeout.as_mut().poll(cx).is_ready() {
                 idle_timeout.as_mut().reset(Instant::now() + idle);
                 info!("connection idle");
-                if let Some(pool) = pool.clone().upgrade() {
-                    // remove client from pool - should close the connection if it's idle.
-                    // does nothing if the client is currently checked-out and in-use
-                    if pool.write().remove_client(db_user.clone(), conn_id) {
-                        info!("idle connection removed");
-                    }
-                }
+                remove_idle_client(&pool, &db_user, conn_id);
             }
 
             loop {
@@ -576,14 +563,124 @@ pub(crate) fn poll_client<C: ClientInnerExt>(
 
     }
     .instrument(span));
+    client
+}
+
+/// Wrap a connection which is not driven by a `tokio_postgres::Connection`,
+/// e.g. a raw compute connection pooled for TCP clients. There are no messages
+/// to poll in the background, so only the idle timeout is enforced here.
+pub(crate) fn pool_client<C: ClientInnerExt>(
+    global_pool: &Arc<GlobalConnPool<C>>,
+    ctx: &RequestMonitoring,
+    conn_info: ConnInfo,
+    client: C,
+    conn_id: uuid::Uuid,
+    aux: MetricsAuxInfo,
+) -> Client<C> {
+    let (client, task) = new_client(global_pool, ctx, conn_info, client, conn_id, aux);
+    let ConnectionTask {
+        span,
+        mut rx,
+        pool,
+        db_user,
+        idle,
+        cancelled,
+        ..
+    } = task;
+
+    tokio::spawn(
+        async move {
+            let mut cancelled = pin!(cancelled);
+            loop {
+                tokio::select! {
+                    () = &mut cancelled => break,
+                    changed = rx.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        let session_id = *rx.borrow_and_update();
+                        info!(%session_id, "changed session");
+                    }
+                    () = tokio::time::sleep(idle) => {
+                        info!("connection idle");
+                        remove_idle_client(&pool, &db_user, conn_id);
+                    }
+                }
+            }
+            info!("connection dropped");
+        }
+        .instrument(span),
+    );
+    client
+}
+
+/// What the background task of a pooled connection needs to follow its client.
+struct ConnectionTask<C: ClientInnerExt> {
+    span: Span,
+    session_id: uuid::Uuid,
+    rx: tokio::sync::watch::Receiver<uuid::Uuid>,
+    pool: Weak<RwLock<EndpointConnPool<C>>>,
+    db_user: (DbName, RoleName),
+    idle: Duration,
+    cancelled: tokio_util::sync::WaitForCancellationFutureOwned,
+}
+
+/// Create a client for a new connection to compute, together with the state
+/// for its background task, which is spawned by the caller.
+fn new_client<C: ClientInnerExt>(
+    global_pool: &Arc<GlobalConnPool<C>>,
+    ctx: &RequestMonitoring,
+    conn_info: ConnInfo,
+    client: C,
+    conn_id: uuid::Uuid,
+    aux: MetricsAuxInfo,
+) -> (Client<C>, ConnectionTask<C>) {
+    let session_id = ctx.session_id();
+    let (tx, rx) = tokio::sync::watch::channel(session_id);
+
+    let span = info_span!(parent: None, "connection", %conn_id);
+    let cold_start_info = ctx.cold_start_info();
+    span.in_scope(|| {
+        info!(cold_start_info = cold_start_info.as_str(), %conn_info, %session_id, "new connection");
+    });
+    let pool = match conn_info.endpoint_cache_key() {
+        Some(endpoint) => Arc::downgrade(&global_pool.get_or_create_endpoint_pool(&endpoint)),
+        None => Weak::new(),
+    };
+
+    let cancel = CancellationToken::new();
+    let task = ConnectionTask {
+        span,
+        session_id,
+        rx,
+        pool: pool.clone(),
+        db_user: conn_info.db_and_user(),
+        idle: global_pool.get_idle_timeout(),
+        cancelled: cancel.clone().cancelled_owned(),
+    };
     let inner = ClientInner {
         inner: client,
         session: tx,
         cancel,
         aux,
         conn_id,
+        statements: StatementCache::new(global_pool.config.max_prepared_statements),
     };
-    Client::new(inner, conn_info, pool_clone)
+    (Client::new(inner, conn_info, pool), task)
+}
+
+/// Remove an idle client from the pool, which should close the connection.
+/// Does nothing if the client is currently checked-out and in-use.
+fn remove_idle_client<C: ClientInnerExt>(
+    pool: &Weak<RwLock<EndpointConnPool<C>>>,
+    db_user: &(DbName, RoleName),
+    conn_id: uuid::Uuid,
+) {
+    if let Some(pool) = pool.upgrade() {
+        if pool.write().remove_client(db_user.clone(), conn_id) {
+            info!("idle connection removed");
+        }
+    }
 }
 
 struct ClientInner<C: ClientInnerExt> {
@@ -592,6 +689,8 @@ struct ClientInner<C: ClientInnerExt> {
     cancel: CancellationToken,
     aux: MetricsAuxInfo,
     conn_id: uuid::Uuid,
+    /// Statements prepared on the connection, kept while it is pooled.
+    statements: StatementCache,
 }
 
 impl<C: ClientInnerExt> Drop for ClientInner<C> {
@@ -666,6 +765,13 @@ pub(crate) fn inner(&mut self) -> (&mut C, Discard<'_, C>) {
         let inner = inner.as_mut().expect("client inner should not be removed");
         (&mut inner.inner, Discard { conn_info, pool })
     }
+    pub(crate) fn statements(&mut self) -> &mut StatementCache {
+        &mut self
+            .inner
+            .as_mut()
+            .expect("client inner should not be removed")
+            .statements
+    }
 }
 
 impl<C: ClientInnerExt> Discard<'_, C> {
@@ -763,6 +869,7 @@ fn create_inner_with(client: MockClient) -> ClientInner<MockClient> {
                 cold_start_info: crate::control_plane::messages::ColdStartInfo::Warm,
             },
             conn_id: uuid::Uuid::new_v4(),
+            statements: StatementCache::new(0),
         }
     }
 
@@ -783,6 +890,7 @@ async fn test_pool() {
             client_conn_threshold: u64::MAX,
             max_request_size_bytes: u64::MAX,
             max_response_size_bytes: usize::MAX,
+            max_prepared_statements: 0,
         }));
         let pool = GlobalConnPool::new(config);
         let conn_info = ConnInfo {
@@ -1,4 +1,6 @@
+use bytes::Bytes;
 use dashmap::DashMap;
+use http_body_util::combinators::BoxBody;
 use hyper::client::conn::http2;
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use parking_lot::RwLock;
@@ -18,9 +20,9 @@
 
 use super::conn_pool::ConnInfo;
 
-pub(crate) type Send = http2::SendRequest<hyper::body::Incoming>;
+pub(crate) type Send = http2::SendRequest<BoxBody<Bytes, hyper::Error>>;
 pub(crate) type Connect =
-    http2::Connection<TokioIo<TcpStream>, hyper::body::Incoming, TokioExecutor>;
+    http2::Connection<TokioIo<TcpStream>, BoxBody<Bytes, hyper::Error>, TokioExecutor>;
 
 #[derive(Clone)]
 struct ConnPoolEntry {
@@ -2,6 +2,7 @@
 use serde_json::Value;
 use tokio_postgres::types::Kind;
 use tokio_postgres::types::Type;
+use tokio_postgres::Column;
 use tokio_postgres::Row;
 
 //
@@ -74,6 +75,21 @@ pub(crate) enum JsonConversionError {
     UnbalancedArray,
 }
 
+//
+// Describe a result column the way node-postgres does
+//
+pub(crate) fn column_to_json(c: &Column) -> Value {
+    serde_json::json!({
+        "name": c.name(),
+        "dataTypeID": c.type_().oid(),
+        "tableID": c.table_oid(),
+        "columnID": c.column_id(),
+        "dataTypeSize": c.type_size(),
+        "dataTypeModifier": c.type_modifier(),
+        "format": "text",
+    })
+}
+
 //
 // Convert postgres row with text-encoded values to JSON object
 //
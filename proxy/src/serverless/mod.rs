@@ -4,12 +4,15 @@
 
 mod backend;
 pub mod cancel_set;
-mod conn_pool;
+pub(crate) mod conn_pool;
 mod http_conn_pool;
 mod http_util;
 mod json;
 mod local_conn_pool;
+mod query_policy;
 mod sql_over_http;
+mod statement_cache;
+mod streaming;
 mod websocket;
 
 use async_trait::async_trait;
@@ -36,6 +39,7 @@
 use crate::cancellation::CancellationHandlerMain;
 use crate::config::ProxyConfig;
 use crate::context::RequestMonitoring;
+use crate::drain;
 use crate::metrics::Metrics;
 use crate::protocol2::{read_proxy_protocol, ChainRW};
 use crate::proxy::run_until_cancelled;
@@ -305,6 +309,7 @@ async fn connection_handler(
     let _cancel_connection = http_cancellation_token.clone().drop_guard();
 
     let server = Builder::new(TokioExecutor::new());
+    let ws_cancellation_token = cancellation_token.clone();
     let conn = server.serve_connection_with_upgrades(
         hyper_util::rt::TokioIo::new(conn),
         hyper::service::service_fn(move |req: hyper::Request<Incoming>| {
@@ -329,6 +334,7 @@ async fn connection_handler(
                     session_id,
                     peer_addr,
                     http_request_token,
+                    ws_cancellation_token.clone(),
                     endpoint_rate_limiter.clone(),
                 )
                 .in_current_span()
@@ -342,12 +348,22 @@ async fn connection_handler(
         }),
     );
 
-    // On cancellation, trigger the HTTP connection handler to shut down.
+    // On cancellation, trigger the HTTP connection handler to shut down,
+    // and drop the connection with its in-flight requests after the drain timeout.
+    let deadline =
+        drain::session_deadline(cancellation_token.clone(), config.session_drain_timeout);
     let res = match select(pin!(cancellation_token.cancelled()), pin!(conn)).await {
         Either::Left((_cancelled, mut conn)) => {
             tracing::debug!(%peer_addr, "cancelling connection");
             conn.as_mut().graceful_shutdown();
-            conn.await
+            match select(deadline, conn).await {
+                Either::Left(((), _)) => {
+                    Metrics::get().proxy.drained_sessions_total.inc();
+                    tracing::info!(%peer_addr, "closing HTTP connection after the drain timeout");
+                    return;
+                }
+                Either::Right((res, _)) => res,
+            }
         }
         Either::Right((res, _)) => res,
     };
@@ -369,6 +385,8 @@ async fn request_handler(
     peer_addr: IpAddr,
     // used to cancel in-flight HTTP requests. not used to cancel websockets
     http_cancellation_token: CancellationToken,
+    // used to drain websockets on shutdown
+    ws_cancellation_token: CancellationToken,
     endpoint_rate_limiter: Arc<EndpointRateLimiter>,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, ApiError> {
     let host = request
@@ -394,6 +412,7 @@ async fn request_handler(
 
         let (response, websocket) = framed_websockets::upgrade::upgrade(&mut request)
             .map_err(|e| ApiError::BadRequest(e.into()))?;
+        let deadline = drain::session_deadline(ws_cancellation_token, config.session_drain_timeout);
 
         ws_connections.spawn(
             async move {
@@ -405,6 +424,7 @@ async fn request_handler(
                     cancellation_handler,
                     endpoint_rate_limiter,
                     host,
+                    deadline,
                 )
                 .await
                 {
@@ -434,7 +454,7 @@ async fn request_handler(
             .header("Access-Control-Allow-Origin", "*")
             .header(
                 "Access-Control-Allow-Headers",
-                "Authorization, Neon-Connection-String, Neon-Raw-Text-Output, Neon-Array-Mode, Neon-Pool-Opt-In, Neon-Batch-Read-Only, Neon-Batch-Isolation-Level",
+                "Authorization, Neon-Connection-String, Neon-Raw-Text-Output, Neon-Array-Mode, Neon-Pool-Opt-In, Neon-Batch-Read-Only, Neon-Batch-Isolation-Level, Neon-Response-Format, Neon-Statement-Cache-Opt-Out",
             )
             .header("Access-Control-Max-Age", "86400" /* 24 hours */)
             .status(StatusCode::OK) // 204 is also valid, but see: https://developer.mozilla.org/en-US/docs/Web/HTTP/Methods/OPTIONS#status_code
@@ -0,0 +1,364 @@
+//! Enforcement of per-role query policies for SQL-over-HTTP.
+//!
+//! Control plane can mark a role read-only or deny it classes of statements,
+//! see [`QueryPolicy`]. Read-only roles run all queries in read-only
+//! transactions, which postgres enforces itself. Denied statement classes
+//! are recognized here by the leading keywords of the statement, so only
+//! top-level statements are classified: functions can still run anything
+//! their owner allows.
+
+use crate::control_plane::messages::{QueryPolicy, StatementClass};
+
+/// Check that the policy allows running the query, returning the denied class otherwise.
+pub(crate) fn check(policy: &QueryPolicy, query: &str) -> Result<(), StatementClass> {
+    let Some(class) = classify(query) else {
+        return Ok(());
+    };
+    // Transaction control could end the read-only transaction we run queries
+    // in, or make it read-write before the first query.
+    if policy.deny.contains(&class) || (policy.read_only && class == StatementClass::Transaction) {
+        return Err(class);
+    }
+    // A statement we can't classify might belong to any denied class.
+    let restricted = policy.read_only || !policy.deny.is_empty();
+    if restricted && class == StatementClass::Unrecognized {
+        return Err(class);
+    }
+    Ok(())
+}
+
+/// Classify a single statement, None if it doesn't belong to any class.
+pub(super) fn classify(query: &str) -> Option<StatementClass> {
+    classify_words(&keywords(query))
+}
+
+/// Statements which can follow a WITH clause.
+const WITH_STATEMENTS: &[&str] = &[
+    "SELECT", "INSERT", "UPDATE", "DELETE", "MERGE", "VALUES", "TABLE",
+];
+
+fn classify_words(words: &[String]) -> Option<StatementClass> {
+    // Skip parentheses around a query.
+    let words = &words[words.iter().take_while(|w| *w == "(").count()..];
+    let contains = |keywords: &[&str]| words.iter().any(|w| keywords.contains(&w.as_str()));
+    let first = words.first()?;
+    match first.as_str() {
+        // SELECT ... INTO creates a table.
+        "SELECT" if contains(&["INTO"]) => Some(StatementClass::Ddl),
+        // Queries and commands which don't change anything.
+        "SELECT" | "VALUES" | "TABLE" | "SHOW" | "FETCH" | "MOVE" | "CLOSE" | "DEALLOCATE"
+        | "LISTEN" | "UNLISTEN" => None,
+        "CREATE" | "ALTER" | "DROP" | "TRUNCATE" | "COMMENT" | "GRANT" | "REVOKE" | "SECURITY"
+        | "REASSIGN" | "IMPORT" | "REINDEX" | "CLUSTER" | "VACUUM" | "ANALYZE" | "ANALYSE"
+        | "REFRESH" => Some(StatementClass::Ddl),
+        "INSERT" | "UPDATE" | "DELETE" | "MERGE" | "LOCK" => Some(StatementClass::Dml),
+        "COPY" => {
+            // Direction is the first TO or FROM outside of a query or a column list.
+            let mut depth = 0;
+            let direction = words.iter().position(|w| {
+                match w.as_str() {
+                    "(" => depth += 1,
+                    ")" => depth -= 1,
+                    "TO" | "FROM" => return depth == 0,
+                    _ => {}
+                }
+                false
+            })?;
+            if words.get(direction + 1).is_some_and(|w| w == "PROGRAM") {
+                Some(StatementClass::CopyProgram)
+            } else if words[direction] == "FROM" {
+                Some(StatementClass::Dml)
+            } else {
+                None
+            }
+        }
+        "WITH" => classify_with(&words[1..]),
+        // EXPLAIN ANALYZE runs the statement.
+        "EXPLAIN" => {
+            let mut rest = &words[1..];
+            if rest.first().is_some_and(|w| w == "(") {
+                rest = &rest[closing_paren(rest)? + 1..];
+            }
+            let options = rest
+                .iter()
+                .take_while(|w| matches!(w.as_str(), "ANALYZE" | "ANALYSE" | "VERBOSE"))
+                .count();
+            classify_words(&rest[options..])
+        }
+        // Prepared statement is denied if its body is.
+        "PREPARE" if words.get(1).map(String::as_str) == Some("TRANSACTION") => {
+            Some(StatementClass::Transaction)
+        }
+        "PREPARE" => {
+            let body = words.iter().position(|w| w == "AS")?;
+            classify_words(&words[body + 1..])
+        }
+        // So is a cursor.
+        "DECLARE" => {
+            let query = words.iter().position(|w| w == "FOR")?;
+            classify_words(&words[query + 1..])
+        }
+        "DO" | "CALL" | "EXECUTE" => Some(StatementClass::Procedural),
+        "BEGIN" | "START" | "COMMIT" | "END" | "ROLLBACK" | "ABORT" | "SAVEPOINT" | "RELEASE" => {
+            Some(StatementClass::Transaction)
+        }
+        "SET" | "RESET" => {
+            let name = words[1..]
+                .iter()
+                .map(String::as_str)
+                .find(|w| !matches!(*w, "SESSION" | "LOCAL"));
+            if matches!(name, Some("ROLE" | "AUTHORIZATION")) {
+                // Switching to another role escapes the policy.
+                Some(StatementClass::Ddl)
+            } else if contains(&[
+                "TRANSACTION",
+                "CHARACTERISTICS",
+                "TRANSACTION_READ_ONLY",
+                "DEFAULT_TRANSACTION_READ_ONLY",
+                "ALL",
+            ]) {
+                Some(StatementClass::Transaction)
+            } else {
+                None
+            }
+        }
+        _ => Some(StatementClass::Unrecognized),
+    }
+}
+
+/// Classify the statement after WITH: by the statement itself, or by the
+/// queries of the WITH clause, which might modify data.
+fn classify_with(words: &[String]) -> Option<StatementClass> {
+    let Some((statement, queries)) = split_with(words) else {
+        return Some(StatementClass::Unrecognized);
+    };
+    classify_words(statement).or_else(|| queries.into_iter().find_map(classify_words))
+}
+
+/// Split words after WITH into the statement and the queries of the clause.
+fn split_with(words: &[String]) -> Option<(&[String], Vec<&[String]>)> {
+    let mut rest = words;
+    if rest.first().is_some_and(|w| w == "RECURSIVE") {
+        rest = &rest[1..];
+    }
+    let mut queries = Vec::new();
+    // Each query is `name [(columns)] AS [[NOT] MATERIALIZED] (query)`.
+    loop {
+        // After the first query, either another one or the statement follows.
+        let another = !WITH_STATEMENTS.contains(&rest.first()?.as_str())
+            && matches!(rest.get(1).map(String::as_str), Some("AS" | "("));
+        if !queries.is_empty() && !another {
+            return Some((rest, queries));
+        }
+        rest = &rest[1..];
+        if rest.first().is_some_and(|w| w == "(") {
+            rest = &rest[closing_paren(rest)? + 1..];
+        }
+        let body = rest.iter().position(|w| w == "(")?;
+        let end = closing_paren(&rest[body..])? + body;
+        queries.push(&rest[body + 1..end]);
+        rest = &rest[end + 1..];
+    }
+}
+
+/// Position of the parenthesis closing the one `words` start with.
+fn closing_paren(words: &[String]) -> Option<usize> {
+    let mut depth = 0;
+    words.iter().position(|w| {
+        match w.as_str() {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            _ => {}
+        }
+        depth == 0
+    })
+}
+
+/// Uppercased keywords and identifiers of the query along with parentheses,
+/// skipping comments, literals, quoted identifiers and parameters.
+pub(super) fn keywords(query: &str) -> Vec<String> {
+    let bytes = query.as_bytes();
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if bytes[i..].starts_with(b"--") {
+            i = find(bytes, i, b"\n").map_or(bytes.len(), |end| end + 1);
+        } else if bytes[i..].starts_with(b"/*") {
+            // Block comments nest.
+            let mut depth = 0;
+            while i < bytes.len() {
+                if bytes[i..].starts_with(b"/*") {
+                    depth += 1;
+                    i += 2;
+                } else if bytes[i..].starts_with(b"*/") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        } else if c == b'\'' || c == b'"' {
+            i = skip_quoted(bytes, i + 1, c, false);
+        } else if c == b'$' {
+            // Dollar-quoted string, unless it's a parameter like $1.
+            let tag_end = bytes[i + 1..]
+                .iter()
+                .position(|b| !(b.is_ascii_alphanumeric() || *b == b'_'))
+                .map(|p| i + 1 + p);
+            let is_param = bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+            match tag_end {
+                Some(end) if bytes[end] == b'$' && !is_param => {
+                    let tag = &bytes[i..=end];
+                    i = find(bytes, end + 1, tag).map_or(bytes.len(), |close| close + tag.len());
+                }
+                _ => i += 1,
+            }
+        } else if c.is_ascii_alphabetic() || c == b'_' || !c.is_ascii() {
+            let start = i;
+            while i < bytes.len() && (is_ident_char(bytes[i]) || !bytes[i].is_ascii()) {
+                i += 1;
+            }
+            let word = &query[start..i];
+            // E'...' strings support backslash escapes.
+            if bytes.get(i) == Some(&b'\'') && word.eq_ignore_ascii_case("e") {
+                i = skip_quoted(bytes, i + 1, b'\'', true);
+                continue;
+            }
+            words.push(word.to_ascii_uppercase());
+        } else {
+            if c == b'(' || c == b')' {
+                words.push((c as char).to_string());
+            }
+            i += 1;
+        }
+    }
+    words
+}
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$'
+}
+
+/// Skip a literal or quoted identifier starting at `i` until the closing
+/// `quote`, returning the position after it.
+fn skip_quoted(bytes: &[u8], mut i: usize, quote: u8, backslash_escapes: bool) -> usize {
+    while i < bytes.len() {
+        if backslash_escapes && bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i] == quote {
+            // Doubled quote is an escaped one.
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+            } else {
+                return i + 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes[from..]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| from + p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_statements() {
+        use StatementClass::*;
+        let cases = [
+            ("select 1", None),
+            (
+                "  -- comment\n /* nested /* comment */ */ CREATE TABLE t(x int)",
+                Some(Ddl),
+            ),
+            ("drop table t", Some(Ddl)),
+            ("select * into t2 from t", Some(Ddl)),
+            ("insert into t values ($1)", Some(Dml)),
+            ("(select 'delete' from t)", None),
+            (
+                "with x as (delete from t returning *) select * from x",
+                Some(Dml),
+            ),
+            ("with x as (select 'update') select * from x", None),
+            ("explain analyze update t set x = 1", Some(Dml)),
+            ("explain analyze create table t as select 1", Some(Ddl)),
+            ("explain (analyze, buffers) delete from t", Some(Dml)),
+            ("explain select 1", None),
+            ("with a as (select 1) select * into t from a", Some(Ddl)),
+            (
+                "with recursive a(x) as not materialized (select 1), b as (select 2) \
+                 insert into t select * from a, b",
+                Some(Dml),
+            ),
+            ("with a as (select 1) values (1)", None),
+            ("declare c cursor for select 1", None),
+            ("refresh materialized view v", Some(Ddl)),
+            ("vacuum t", Some(Ddl)),
+            ("analyze t", Some(Ddl)),
+            ("lock table t in access exclusive mode", Some(Dml)),
+            ("set role admin", Some(Ddl)),
+            ("set local role admin", Some(Ddl)),
+            ("set session authorization admin", Some(Ddl)),
+            ("reset role", Some(Ddl)),
+            ("checkpoint", Some(Unrecognized)),
+            ("with a as (select 1", Some(Unrecognized)),
+            ("copy t to stdout", None),
+            ("copy t (x, y) from stdin", Some(Dml)),
+            ("copy (select x from t) to stdout", None),
+            ("COPY t TO PROGRAM 'cat'", Some(CopyProgram)),
+            ("copy (select 'to program') to stdout", None),
+            ("copy (select $$ to program $$) to stdout", None),
+            ("copy t from program e'\\' to program '", Some(CopyProgram)),
+            ("select E'\\' program ' from t", None),
+            ("prepare p as drop table t", Some(Ddl)),
+            ("execute p", Some(Procedural)),
+            ("do $body$ begin drop table t; end $body$", Some(Procedural)),
+            ("begin", Some(Transaction)),
+            ("set transaction read write", Some(Transaction)),
+            ("set default_transaction_read_only = off", Some(Transaction)),
+            ("reset all", Some(Transaction)),
+            ("set search_path = public", None),
+            ("select \"drop\" from t", None),
+        ];
+        for (query, class) in cases {
+            assert_eq!(classify(query), class, "{query}");
+        }
+    }
+
+    #[test]
+    fn check_policy() {
+        let policy = QueryPolicy {
+            read_only: true,
+            deny: vec![StatementClass::Ddl, StatementClass::CopyProgram],
+        };
+        assert_eq!(check(&policy, "select 1"), Ok(()));
+        // Writes are stopped by postgres in a read-only transaction.
+        assert_eq!(check(&policy, "insert into t values (1)"), Ok(()));
+        assert_eq!(
+            check(&policy, "alter table t add column y int"),
+            Err(StatementClass::Ddl)
+        );
+        assert_eq!(check(&policy, "commit"), Err(StatementClass::Transaction));
+        assert_eq!(
+            check(&policy, "checkpoint"),
+            Err(StatementClass::Unrecognized)
+        );
+
+        let policy = QueryPolicy::default();
+        assert_eq!(check(&policy, "drop table t"), Ok(()));
+        assert_eq!(check(&policy, "begin"), Ok(()));
+        assert_eq!(check(&policy, "checkpoint"), Ok(()));
+    }
+}
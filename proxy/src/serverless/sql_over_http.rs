@@ -7,12 +7,15 @@
 use futures::future::Either;
 use futures::StreamExt;
 use futures::TryFutureExt;
+use futures::TryStreamExt;
 use http::header::AUTHORIZATION;
 use http::Method;
 use http_body_util::combinators::BoxBody;
 use http_body_util::BodyExt;
 use http_body_util::Full;
+use http_body_util::StreamBody;
 use hyper::body::Body;
+use hyper::body::Frame;
 use hyper::body::Incoming;
 use hyper::header;
 use hyper::http::HeaderName;
@@ -23,6 +26,7 @@
 use pq_proto::StartupMessageParamsBuilder;
 use serde::Serialize;
 use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time;
 use tokio_postgres::error::DbError;
 use tokio_postgres::error::ErrorPosition;
@@ -35,6 +39,8 @@
 use tokio_util::sync::CancellationToken;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
+use tracing::Instrument;
 use typed_json::json;
 use url::Url;
 use urlencoding;
@@ -49,11 +55,13 @@
 use crate::config::ProxyConfig;
 use crate::config::TlsConfig;
 use crate::context::RequestMonitoring;
+use crate::control_plane::messages::{QueryPolicy, StatementClass};
 use crate::error::ErrorKind;
 use crate::error::ReportableError;
 use crate::error::UserFacingError;
 use crate::metrics::HttpDirection;
 use crate::metrics::Metrics;
+use crate::metrics::StatementCacheOutcome;
 use crate::proxy::run_until_cancelled;
 use crate::proxy::NeonOptions;
 use crate::serverless::backend::HttpConnError;
@@ -69,12 +77,16 @@
 use super::conn_pool::ConnInfo;
 use super::conn_pool::ConnInfoWithAuth;
 use super::http_util::json_response;
+use super::json::column_to_json;
 use super::json::json_to_pg_text;
 use super::json::pg_text_row_to_json;
 use super::json::JsonConversionError;
 use super::local_conn_pool;
+use super::query_policy;
+use super::statement_cache::{self, Lookup, StatementCache};
+use super::streaming::{EncodeError, ResponseFormat, RowEncoder};
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct QueryData {
     query: String,
@@ -82,14 +94,18 @@ struct QueryData {
     params: Vec<Option<String>>,
     #[serde(default)]
     array_mode: Option<bool>,
+    /// Result fields of the query when it runs a prepared statement, as
+    /// `EXECUTE` doesn't report their source table and column.
+    #[serde(skip)]
+    fields: Option<statement_cache::Fields>,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, Clone)]
 struct BatchQueryData {
     queries: Vec<QueryData>,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, Clone)]
 #[serde(untagged)]
 enum Payload {
     Single(QueryData),
@@ -103,6 +119,12 @@ enum Payload {
 static TXN_ISOLATION_LEVEL: HeaderName = HeaderName::from_static("neon-batch-isolation-level");
 static TXN_READ_ONLY: HeaderName = HeaderName::from_static("neon-batch-read-only");
 static TXN_DEFERRABLE: HeaderName = HeaderName::from_static("neon-batch-deferrable");
+static RESPONSE_FORMAT: HeaderName = HeaderName::from_static("neon-response-format");
+static STATEMENT_CACHE_OPT_OUT: HeaderName =
+    HeaderName::from_static("neon-statement-cache-opt-out");
+
+/// Number of chunks of a streaming response buffered before reading from postgres pauses.
+const STREAM_BUFFER_CHUNKS: usize = 4;
 
 static HEADER_VALUE_TRUE: HeaderValue = HeaderValue::from_static("true");
 
@@ -229,7 +251,7 @@ fn get_conn_info(
     let endpoint = match connection_url.host() {
         Some(url::Host::Domain(hostname)) => {
             if let Some(tls) = tls {
-                endpoint_sni(hostname, &tls.common_names)?
+                endpoint_sni(hostname, &tls.common_names())?
                     .ok_or(ConnInfoError::MalformedEndpoint)?
             } else {
                 hostname
@@ -395,12 +417,20 @@ pub(crate) enum SqlOverHttpError {
     ResponseTooLarge(usize),
     #[error("invalid isolation level")]
     InvalidIsolationLevel,
+    #[error("invalid response format")]
+    InvalidResponseFormat,
+    #[error("streaming response formats are not supported for batch queries")]
+    StreamingBatch,
     #[error("{0}")]
     Postgres(#[from] tokio_postgres::Error),
     #[error("{0}")]
     JsonConversion(#[from] JsonConversionError),
     #[error("{0}")]
+    Encode(#[from] EncodeError),
+    #[error("{0}")]
     Cancelled(SqlOverHttpCancel),
+    #[error("{} statements are not allowed for this role", .0.as_str())]
+    QueryDenied(StatementClass),
 }
 
 impl ReportableError for SqlOverHttpError {
@@ -412,9 +442,13 @@ fn get_error_kind(&self) -> ErrorKind {
             SqlOverHttpError::RequestTooLarge(_) => ErrorKind::User,
             SqlOverHttpError::ResponseTooLarge(_) => ErrorKind::User,
             SqlOverHttpError::InvalidIsolationLevel => ErrorKind::User,
+            SqlOverHttpError::InvalidResponseFormat => ErrorKind::User,
+            SqlOverHttpError::StreamingBatch => ErrorKind::User,
             SqlOverHttpError::Postgres(p) => p.get_error_kind(),
             SqlOverHttpError::JsonConversion(_) => ErrorKind::Postgres,
+            SqlOverHttpError::Encode(_) => ErrorKind::Postgres,
             SqlOverHttpError::Cancelled(c) => c.get_error_kind(),
+            SqlOverHttpError::QueryDenied(_) => ErrorKind::User,
         }
     }
 }
@@ -428,9 +462,13 @@ fn to_string_client(&self) -> String {
             SqlOverHttpError::RequestTooLarge(_) => self.to_string(),
             SqlOverHttpError::ResponseTooLarge(_) => self.to_string(),
             SqlOverHttpError::InvalidIsolationLevel => self.to_string(),
+            SqlOverHttpError::InvalidResponseFormat => self.to_string(),
+            SqlOverHttpError::StreamingBatch => self.to_string(),
             SqlOverHttpError::Postgres(p) => p.to_string(),
             SqlOverHttpError::JsonConversion(_) => "could not parse postgres response".to_string(),
+            SqlOverHttpError::Encode(_) => "could not encode postgres response".to_string(),
             SqlOverHttpError::Cancelled(_) => self.to_string(),
+            SqlOverHttpError::QueryDenied(_) => self.to_string(),
         }
     }
 }
@@ -476,6 +514,8 @@ struct HttpHeaders {
     txn_isolation_level: Option<IsolationLevel>,
     txn_read_only: bool,
     txn_deferrable: bool,
+    response_format: ResponseFormat,
+    statement_cache_opt_out: bool,
 }
 
 impl HttpHeaders {
@@ -496,12 +536,22 @@ fn try_parse(headers: &hyper::http::HeaderMap) -> Result<Self, SqlOverHttpError>
         let txn_read_only = headers.get(&TXN_READ_ONLY) == Some(&HEADER_VALUE_TRUE);
         let txn_deferrable = headers.get(&TXN_DEFERRABLE) == Some(&HEADER_VALUE_TRUE);
 
+        let response_format = match headers.get(&RESPONSE_FORMAT) {
+            Some(x) => ResponseFormat::parse(x).ok_or(SqlOverHttpError::InvalidResponseFormat)?,
+            None => ResponseFormat::Json,
+        };
+
+        let statement_cache_opt_out =
+            headers.get(&STATEMENT_CACHE_OPT_OUT) == Some(&HEADER_VALUE_TRUE);
+
         Ok(Self {
             raw_output,
             default_array_mode,
             txn_isolation_level,
             txn_read_only,
             txn_deferrable,
+            response_format,
+            statement_cache_opt_out,
         })
     }
 }
@@ -555,7 +605,7 @@ async fn handle_inner(
 
     match conn_info.auth {
         AuthData::Jwt(jwt) if config.authentication_config.is_auth_broker => {
-            handle_auth_broker_inner(ctx, request, conn_info.conn_info, jwt, backend).await
+            handle_auth_broker_inner(config, ctx, request, conn_info.conn_info, jwt, backend).await
         }
         auth => {
             handle_db_inner(
@@ -591,7 +641,7 @@ async fn handle_db_inner(
     let allow_pool = !config.http_config.pool_options.opt_in
         || headers.get(&ALLOW_POOL) == Some(&HEADER_VALUE_TRUE);
 
-    let parsed_headers = HttpHeaders::try_parse(headers)?;
+    let mut parsed_headers = HttpHeaders::try_parse(headers)?;
 
     let request_content_length = match request.body().size_hint().upper() {
         Some(v) => v,
@@ -638,6 +688,8 @@ async fn handle_db_inner(
                 }
             };
 
+            let query_policy = backend.get_query_policy(ctx, &conn_info.user_info).await?;
+
             let client = match keys.keys {
                 ComputeCredentialKeys::JwtPayload(payload) if is_local_proxy => {
                     let mut client = backend.connect_to_local_postgres(ctx, conn_info).await?;
@@ -655,12 +707,12 @@ async fn handle_db_inner(
             // not strictly necessary to mark success here,
             // but it's just insurance for if we forget it somewhere else
             ctx.success();
-            Ok::<_, HttpConnError>(client)
+            Ok::<_, HttpConnError>((client, query_policy))
         }
         .map_err(SqlOverHttpError::from),
     );
 
-    let (payload, mut client) = match run_until_cancelled(
+    let (mut payload, (mut client, query_policy)) = match run_until_cancelled(
         // Run both operations in parallel
         try_join(
             pin!(fetch_and_process_request),
@@ -674,35 +726,121 @@ async fn handle_db_inner(
         None => return Err(SqlOverHttpError::Cancelled(SqlOverHttpCancel::Connect)),
     };
 
+    // local_proxy is only reached through the auth broker, which checks the
+    // role's policy itself and marks requests of read-only roles as such.
+    let is_local_proxy = matches!(backend.auth_backend, crate::auth::Backend::Local(_));
+    let query_policy = if is_local_proxy && parsed_headers.txn_read_only {
+        Arc::new(QueryPolicy {
+            read_only: true,
+            ..QueryPolicy::clone(&query_policy)
+        })
+    } else {
+        query_policy
+    };
+
+    check_query_policy(&query_policy, &payload)?;
+    if query_policy.read_only {
+        parsed_headers.txn_read_only = true;
+    }
+    let queries = payload.queries();
+
+    // Prepared statements might not work after schema or search_path changes,
+    // so they are not used for such requests and dropped after them.
+    let deallocates_all = queries
+        .iter()
+        .any(|stmt| statement_cache::deallocates_all(&stmt.query));
+    let changes_schema = queries.iter().any(|stmt| {
+        query_policy::classify(&stmt.query) == Some(StatementClass::Ddl)
+            || statement_cache::changes_search_path(&stmt.query)
+    });
+
+    if parsed_headers.response_format != ResponseFormat::Json {
+        let Payload::Single(stmt) = payload else {
+            return Err(SqlOverHttpError::StreamingBatch);
+        };
+        // The client is moved to the streaming task, so drop the statements
+        // upfront. Statements deallocated by the query fail to deallocate again,
+        // which only forgets them.
+        if deallocates_all || changes_schema {
+            if let Some(statements) = client.statements() {
+                statements.invalidate();
+            }
+        }
+        return stream_query(cancel, client, stmt, parsed_headers, query_policy.read_only).await;
+    }
+
+    // Streamed queries don't use prepared statements, as their parsing time is
+    // negligible compared to reading the results.
+    let use_statement_cache = allow_pool && !parsed_headers.statement_cache_opt_out;
+    let prepare = use_statement_cache && !deallocates_all && !changes_schema;
+    // Kept to run the queries again if their prepared statements are stale.
+    let unprepared = prepare.then(|| payload.clone());
+    if prepare {
+        prepare_statements(&cancel, &mut client, payload.queries_mut()).await?;
+    }
+
     let mut response = Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "application/json");
+    if let Payload::Batch(_) = payload {
+        if parsed_headers.txn_read_only {
+            response = response.header(TXN_READ_ONLY.clone(), &HEADER_VALUE_TRUE);
+        }
+        if parsed_headers.txn_deferrable {
+            response = response.header(TXN_DEFERRABLE.clone(), &HEADER_VALUE_TRUE);
+        }
+        if let Some(txn_isolation_level) = parsed_headers
+            .txn_isolation_level
+            .and_then(map_isolation_level_to_headers)
+        {
+            response = response.header(TXN_ISOLATION_LEVEL.clone(), txn_isolation_level);
+        }
+    }
 
     // Now execute the query and return the result.
-    let json_output = match payload {
-        Payload::Single(stmt) => {
-            stmt.process(&config.http_config, cancel, &mut client, parsed_headers)
-                .await?
+    let read_only = query_policy.read_only;
+    let mut result = payload
+        .process(
+            &config.http_config,
+            cancel.clone(),
+            &mut client,
+            parsed_headers,
+            read_only,
+        )
+        .await;
+
+    // A failed statement didn't run, and a failed batch is rolled back, so it
+    // is safe to run the queries again once with freshly prepared statements.
+    if let Some(mut payload) =
+        unprepared.filter(|_| result.as_ref().is_err_and(is_stale_statement_error))
+    {
+        info!("prepared statements are stale, preparing them again");
+        if let Some(statements) = client.statements() {
+            statements.invalidate();
         }
-        Payload::Batch(statements) => {
-            if parsed_headers.txn_read_only {
-                response = response.header(TXN_READ_ONLY.clone(), &HEADER_VALUE_TRUE);
-            }
-            if parsed_headers.txn_deferrable {
-                response = response.header(TXN_DEFERRABLE.clone(), &HEADER_VALUE_TRUE);
-            }
-            if let Some(txn_isolation_level) = parsed_headers
-                .txn_isolation_level
-                .and_then(map_isolation_level_to_headers)
-            {
-                response = response.header(TXN_ISOLATION_LEVEL.clone(), txn_isolation_level);
-            }
+        prepare_statements(&cancel, &mut client, payload.queries_mut()).await?;
+        result = payload
+            .process(
+                &config.http_config,
+                cancel,
+                &mut client,
+                parsed_headers,
+                read_only,
+            )
+            .await;
+    }
 
-            statements
-                .process(&config.http_config, cancel, &mut client, parsed_headers)
-                .await?
+    if let Some(statements) = client.statements() {
+        if deallocates_all && result.is_ok() {
+            statements.clear();
+        } else if deallocates_all
+            || changes_schema
+            || result.as_ref().is_err_and(is_stale_statement_error)
+        {
+            statements.invalidate();
         }
-    };
+    }
+    let json_output = result?;
 
     let metrics = client.metrics();
 
@@ -738,7 +876,21 @@ async fn handle_db_inner(
     &TXN_DEFERRABLE,
 ];
 
+/// Check all queries of the request against the role's policy before running
+/// any of them.
+fn check_query_policy(policy: &QueryPolicy, payload: &Payload) -> Result<(), SqlOverHttpError> {
+    for stmt in payload.queries() {
+        if let Err(class) = query_policy::check(policy, &stmt.query) {
+            info!(class = class.as_str(), "query denied by policy");
+            Metrics::get().proxy.http_denied_queries_total.inc(class);
+            return Err(SqlOverHttpError::QueryDenied(class));
+        }
+    }
+    Ok(())
+}
+
 async fn handle_auth_broker_inner(
+    config: &'static ProxyConfig,
     ctx: &RequestMonitoring,
     request: Request<Incoming>,
     conn_info: ConnInfo,
@@ -749,12 +901,47 @@ async fn handle_auth_broker_inner(
         .authenticate_with_jwt(ctx, &conn_info.user_info, jwt)
         .await
         .map_err(HttpConnError::from)?;
+    let query_policy = backend
+        .get_query_policy(ctx, &conn_info.user_info)
+        .await
+        .map_err(HttpConnError::from)?;
+
+    let (mut parts, body) = request.into_parts();
+
+    // local_proxy has no policies of its own, so the queries are checked here.
+    // Read-only roles get the read-only header whatever the client has sent,
+    // which makes local_proxy run all of their queries read-only.
+    let body = if *query_policy == QueryPolicy::default() {
+        body.boxed()
+    } else {
+        let request_content_length = body
+            .size_hint()
+            .upper()
+            .unwrap_or(config.http_config.max_request_size_bytes + 1);
+        if request_content_length > config.http_config.max_request_size_bytes {
+            return Err(SqlOverHttpError::RequestTooLarge(
+                config.http_config.max_request_size_bytes,
+            ));
+        }
+        let body = body
+            .collect()
+            .await
+            .map_err(ReadPayloadError::from)?
+            .to_bytes();
+        let payload: Payload = serde_json::from_slice(&body).map_err(ReadPayloadError::from)?;
+        check_query_policy(&query_policy, &payload)?;
+        if query_policy.read_only {
+            parts
+                .headers
+                .insert(TXN_READ_ONLY.clone(), HEADER_VALUE_TRUE.clone());
+        }
+        Full::new(body).map_err(|x| match x {}).boxed()
+    };
 
     let mut client = backend.connect_to_local_proxy(ctx, conn_info).await?;
 
     let local_proxy_uri = ::http::Uri::from_static("http://proxy.local/sql");
 
-    let (mut parts, body) = request.into_parts();
     let mut req = Request::builder().method(Method::POST).uri(local_proxy_uri);
 
     // todo(conradludgate): maybe auth-broker should parse these and re-serialize
@@ -781,6 +968,44 @@ async fn handle_auth_broker_inner(
         .map(|b| b.boxed()))
 }
 
+impl Payload {
+    fn queries(&self) -> &[QueryData] {
+        match self {
+            Payload::Single(stmt) => std::slice::from_ref(stmt),
+            Payload::Batch(statements) => &statements.queries,
+        }
+    }
+
+    fn queries_mut(&mut self) -> &mut [QueryData] {
+        match self {
+            Payload::Single(stmt) => std::slice::from_mut(stmt),
+            Payload::Batch(statements) => &mut statements.queries,
+        }
+    }
+
+    async fn process(
+        self,
+        config: &'static HttpConfig,
+        cancel: CancellationToken,
+        client: &mut Client,
+        parsed_headers: HttpHeaders,
+        read_only: bool,
+    ) -> Result<String, SqlOverHttpError> {
+        match self {
+            Payload::Single(stmt) if read_only => {
+                stmt.process_read_only(config, cancel, client, parsed_headers)
+                    .await
+            }
+            Payload::Single(stmt) => stmt.process(config, cancel, client, parsed_headers).await,
+            Payload::Batch(statements) => {
+                statements
+                    .process(config, cancel, client, parsed_headers)
+                    .await
+            }
+        }
+    }
+}
+
 impl QueryData {
     async fn process(
         self,
@@ -853,6 +1078,55 @@ async fn process(
         };
         res
     }
+
+    /// Run the query in a read-only transaction, as required by the role's query policy.
+    async fn process_read_only(
+        self,
+        config: &'static HttpConfig,
+        cancel: CancellationToken,
+        client: &mut Client,
+        parsed_headers: HttpHeaders,
+    ) -> Result<String, SqlOverHttpError> {
+        let (inner, mut discard) = client.inner();
+        let cancel_token = inner.cancel_token();
+        let transaction = inner
+            .build_transaction()
+            .read_only(true)
+            .start()
+            .await
+            .inspect_err(|_| {
+                // if we cannot start a transaction, we should return immediately
+                // and not return to the pool. connection is clearly broken
+                discard.discard();
+            })?;
+
+        let query = query_to_json(config, &transaction, self, &mut 0, parsed_headers);
+        match run_until_cancelled(query, &cancel).await {
+            Some(Ok((_, results))) => {
+                let json_output =
+                    serde_json::to_string(&results).expect("json serialization should not fail");
+                let status = transaction.commit().await.inspect_err(|_| {
+                    discard.discard();
+                })?;
+                discard.check_idle(status);
+                Ok(json_output)
+            }
+            Some(Err(err)) => {
+                let status = transaction.rollback().await.inspect_err(|_| {
+                    discard.discard();
+                })?;
+                discard.check_idle(status);
+                Err(err)
+            }
+            None => {
+                if let Err(err) = cancel_token.cancel_query(NoTls).await {
+                    tracing::warn!(?err, "could not cancel query");
+                }
+                discard.discard();
+                Err(SqlOverHttpError::Cancelled(SqlOverHttpCancel::Postgres))
+            }
+        }
+    }
 }
 
 impl BatchQueryData {
@@ -999,16 +1273,7 @@ async fn query_to_json<T: GenericClient>(
 
     // grab the command tag and number of rows affected
     let command_tag = row_stream.command_tag().unwrap_or_default();
-    let mut command_tag_split = command_tag.split(' ');
-    let command_tag_name = command_tag_split.next().unwrap_or_default();
-    let command_tag_count = if command_tag_name == "INSERT" {
-        // INSERT returns OID first and then number of rows
-        command_tag_split.nth(1)
-    } else {
-        // other commands return number of rows (if any)
-        command_tag_split.next()
-    }
-    .and_then(|s| s.parse::<i64>().ok());
+    let (command_tag_name, command_tag_count) = parse_command_tag(&command_tag);
 
     info!(
         rows = rows.len(),
@@ -1021,16 +1286,13 @@ async fn query_to_json<T: GenericClient>(
     let mut fields = Vec::with_capacity(columns_len);
     let mut columns = Vec::with_capacity(columns_len);
 
-    for c in row_stream.columns() {
-        fields.push(json!({
-            "name": c.name().to_owned(),
-            "dataTypeID": c.type_().oid(),
-            "tableID": c.table_oid(),
-            "columnID": c.column_id(),
-            "dataTypeSize": c.type_size(),
-            "dataTypeModifier": c.type_modifier(),
-            "format": "text",
-        }));
+    for (i, c) in row_stream.columns().iter().enumerate() {
+        let mut field = column_to_json(c);
+        if let Some(prepared) = data.fields.as_ref().and_then(|f| f.get(i)) {
+            field["tableID"] = prepared["tableID"].clone();
+            field["columnID"] = prepared["columnID"].clone();
+        }
+        fields.push(field);
         columns.push(client.get_type(c.type_oid()).await?);
     }
 
@@ -1054,6 +1316,352 @@ async fn query_to_json<T: GenericClient>(
     Ok((ready, results))
 }
 
+/// Rewrite the queries to run statements prepared on the connection,
+/// preparing the statements not cached yet.
+///
+/// Queries are prepared with `PREPARE` sent as an unnamed statement, so a
+/// query can't smuggle in more statements, and executed with `EXECUTE`,
+/// passing the parameters as literals. Queries which fail to prepare run as
+/// unnamed statements, reporting the error as usual.
+async fn prepare_statements(
+    cancel: &CancellationToken,
+    client: &mut Client,
+    queries: &mut [QueryData],
+) -> Result<(), SqlOverHttpError> {
+    let Client::Remote(client) = client else {
+        return Ok(());
+    };
+
+    let prepare = async {
+        let stale = client.statements().take_stale();
+        if !stale.is_empty() {
+            let sql = statement_cache::deallocate_statements(&stale);
+            let (inner, _) = client.inner();
+            if let Err(err) = inner.batch_execute(&sql).await {
+                // Statements which don't exist can't be used anyway.
+                warn!(?err, "could not deallocate prepared statements");
+                client.statements().clear();
+            }
+        }
+
+        for stmt in queries.iter_mut() {
+            let (name, fields) = match client.statements().lookup(&stmt.query) {
+                Lookup::Prepared(name, fields) => {
+                    Metrics::get()
+                        .proxy
+                        .http_statement_cache_lookups_total
+                        .inc(StatementCacheOutcome::Hit);
+                    (name, fields)
+                }
+                Lookup::Prepare(name) => {
+                    Metrics::get()
+                        .proxy
+                        .http_statement_cache_lookups_total
+                        .inc(StatementCacheOutcome::Miss);
+                    let (inner, _) = client.inner();
+                    let prepared = async {
+                        // Describe the query for the source of its fields.
+                        let described = inner.prepare(&stmt.query).await?;
+                        let fields: statement_cache::Fields =
+                            described.columns().iter().map(column_to_json).collect();
+                        let sql = format!("PREPARE {name} AS {}", stmt.query);
+                        inner
+                            .query_raw_txt(&sql, Vec::<Option<String>>::new())
+                            .await?
+                            .try_collect::<Vec<_>>()
+                            .await?;
+                        Ok::<_, tokio_postgres::Error>(fields)
+                    }
+                    .await;
+                    match prepared {
+                        Ok(fields) => {
+                            client.statements().insert_prepared(
+                                stmt.query.clone(),
+                                name.clone(),
+                                fields.clone(),
+                            );
+                            (name, fields)
+                        }
+                        Err(err) => {
+                            // Other errors, like a missing table, might go away.
+                            if err.code() == Some(&SqlState::SYNTAX_ERROR) {
+                                client.statements().insert_unpreparable(stmt.query.clone());
+                            }
+                            continue;
+                        }
+                    }
+                }
+                Lookup::Unnamed => continue,
+            };
+            stmt.query = statement_cache::execute_statement(&name, &stmt.params);
+            stmt.params.clear();
+            stmt.fields = Some(fields);
+        }
+    };
+
+    if run_until_cancelled(prepare, cancel).await.is_none() {
+        let (inner, mut discard) = client.inner();
+        if let Err(err) = inner.cancel_token().cancel_query(NoTls).await {
+            warn!(?err, "could not cancel query");
+        }
+        discard.discard();
+        return Err(SqlOverHttpError::Cancelled(SqlOverHttpCancel::Postgres));
+    }
+    Ok(())
+}
+
+/// Whether the error means the cached prepared statements can't be used.
+fn is_stale_statement_error(err: &SqlOverHttpError) -> bool {
+    let SqlOverHttpError::Postgres(err) = err else {
+        return false;
+    };
+    err.as_db_error().is_some_and(|e| {
+        *e.code() == SqlState::INVALID_SQL_STATEMENT_NAME
+            || (*e.code() == SqlState::FEATURE_NOT_SUPPORTED
+                && e.message()
+                    .starts_with("cached plan must not change result type"))
+    })
+}
+
+/// Split the command tag into the command name and the number of rows affected.
+fn parse_command_tag(command_tag: &str) -> (&str, Option<i64>) {
+    let mut command_tag_split = command_tag.split(' ');
+    let command_tag_name = command_tag_split.next().unwrap_or_default();
+    let command_tag_count = if command_tag_name == "INSERT" {
+        // INSERT returns OID first and then number of rows
+        command_tag_split.nth(1)
+    } else {
+        // other commands return number of rows (if any)
+        command_tag_split.next()
+    }
+    .and_then(|s| s.parse::<i64>().ok());
+    (command_tag_name, command_tag_count)
+}
+
+/// How streaming of query results ended.
+enum StreamOutcome {
+    /// All rows are sent, the connection has the given status.
+    Done(ReadyForQueryStatus),
+    /// The query failed, the error is sent to the client.
+    Failed,
+    /// The client went away or the request was cancelled.
+    Aborted,
+}
+
+/// Run a single query, streaming its rows in the requested format. Errors
+/// are returned as usual until the first chunk of the response is ready.
+async fn stream_query(
+    cancel: CancellationToken,
+    mut client: Client,
+    data: QueryData,
+    parsed_headers: HttpHeaders,
+    read_only: bool,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, SqlOverHttpError> {
+    let (started_tx, started_rx) = oneshot::channel();
+    let (chunks_tx, chunks_rx) = mpsc::channel(STREAM_BUFFER_CHUNKS);
+    let metrics = client.metrics();
+
+    tokio::spawn(
+        async move {
+            let (inner, mut discard) = client.inner();
+            let cancel_token = inner.cancel_token();
+            let outcome = if read_only {
+                match inner.build_transaction().read_only(true).start().await {
+                    Ok(transaction) => {
+                        let outcome = stream_rows(
+                            &transaction,
+                            data,
+                            parsed_headers,
+                            started_tx,
+                            &chunks_tx,
+                            &cancel,
+                        )
+                        .await;
+                        match outcome {
+                            StreamOutcome::Done(_) => transaction
+                                .commit()
+                                .await
+                                .map_or(StreamOutcome::Failed, StreamOutcome::Done),
+                            outcome => outcome,
+                        }
+                    }
+                    Err(e) => {
+                        let _ = started_tx.send(Err(e.into()));
+                        StreamOutcome::Failed
+                    }
+                }
+            } else {
+                stream_rows(
+                    &*inner,
+                    data,
+                    parsed_headers,
+                    started_tx,
+                    &chunks_tx,
+                    &cancel,
+                )
+                .await
+            };
+
+            match outcome {
+                StreamOutcome::Done(status) => discard.check_idle(status),
+                StreamOutcome::Failed => discard.discard(),
+                StreamOutcome::Aborted => {
+                    tracing::info!("cancelling query");
+                    if let Err(err) = cancel_token.cancel_query(NoTls).await {
+                        tracing::warn!(?err, "could not cancel query");
+                    }
+                    discard.discard();
+                }
+            }
+        }
+        .in_current_span(),
+    );
+
+    match started_rx.await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return Err(SqlOverHttpError::Cancelled(SqlOverHttpCancel::Postgres)),
+    }
+
+    let body = futures::stream::unfold(chunks_rx, move |mut chunks_rx| {
+        let metrics = metrics.clone();
+        async move {
+            let chunk = chunks_rx.recv().await?;
+            // count the egress bytes as they are sent
+            metrics.record_egress(chunk.len() as u64);
+            Some((Ok::<_, hyper::Error>(Frame::data(chunk)), chunks_rx))
+        }
+    });
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            header::CONTENT_TYPE,
+            parsed_headers.response_format.content_type(),
+        )
+        .body(StreamBody::new(body).boxed())
+        // only fails if invalid status code or invalid header/values are given.
+        // these are not user configurable so it cannot fail dynamically
+        .expect("building response payload should not fail");
+    Ok(response)
+}
+
+async fn stream_rows<T: GenericClient>(
+    client: &T,
+    data: QueryData,
+    parsed_headers: HttpHeaders,
+    started: oneshot::Sender<Result<(), SqlOverHttpError>>,
+    chunks: &mpsc::Sender<Bytes>,
+    cancel: &CancellationToken,
+) -> StreamOutcome {
+    let start = async {
+        info!("executing query");
+        let row_stream = client.query_raw_txt(&data.query, data.params).await?;
+        let mut types = Vec::with_capacity(row_stream.columns().len());
+        for c in row_stream.columns() {
+            types.push(client.get_type(c.type_oid()).await?);
+        }
+        let array_mode = data.array_mode.unwrap_or(parsed_headers.default_array_mode);
+        let (encoder, header) = RowEncoder::new(
+            parsed_headers.response_format,
+            row_stream.columns(),
+            types,
+            parsed_headers.raw_output,
+            array_mode,
+        )?;
+        Ok::<_, SqlOverHttpError>((row_stream, encoder, header))
+    };
+    let (row_stream, mut encoder, header) = match run_until_cancelled(start, cancel).await {
+        Some(Ok(started)) => started,
+        Some(Err(e)) => {
+            let _ = started.send(Err(e));
+            return StreamOutcome::Failed;
+        }
+        None => {
+            let _ = started.send(Err(SqlOverHttpError::Cancelled(
+                SqlOverHttpCancel::Postgres,
+            )));
+            return StreamOutcome::Aborted;
+        }
+    };
+    if started.send(Ok(())).is_err() {
+        return StreamOutcome::Aborted;
+    }
+
+    let mut row_stream = pin!(row_stream);
+    let mut rows = 0u64;
+    let mut chunk = Some(header);
+    loop {
+        if let Some(chunk) = chunk.take() {
+            if !send_chunk(chunks, chunk, cancel).await {
+                return StreamOutcome::Aborted;
+            }
+        }
+        let row = tokio::select! {
+            row = row_stream.next() => row,
+            () = chunks.closed() => return StreamOutcome::Aborted,
+            () = cancel.cancelled() => return StreamOutcome::Aborted,
+        };
+        let res = match row {
+            Some(Ok(row)) => encoder.push(&row).map_err(SqlOverHttpError::from),
+            Some(Err(e)) => Err(SqlOverHttpError::from(e)),
+            None => break,
+        };
+        match res {
+            Ok(next) => chunk = next,
+            Err(e) => {
+                let (message, code) = error_message_and_code(&e);
+                tracing::info!(error = %e, rows, "query failed while streaming rows");
+                send_chunk(chunks, encoder.fail(&message, code), cancel).await;
+                return StreamOutcome::Failed;
+            }
+        }
+        rows += 1;
+    }
+
+    let ready = row_stream.ready_status();
+    let command_tag = row_stream.command_tag().unwrap_or_default();
+    let (command_tag_name, command_tag_count) = parse_command_tag(&command_tag);
+    info!(rows, ?ready, command_tag, "finished streaming rows");
+    match encoder.finish(command_tag_name, command_tag_count) {
+        Ok(last) => {
+            if !send_chunk(chunks, last, cancel).await {
+                return StreamOutcome::Aborted;
+            }
+            StreamOutcome::Done(ready)
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "could not finish streaming response");
+            StreamOutcome::Failed
+        }
+    }
+}
+
+/// Send a chunk of the response, returns false if the client is gone.
+async fn send_chunk(
+    chunks: &mpsc::Sender<Bytes>,
+    chunk: Bytes,
+    cancel: &CancellationToken,
+) -> bool {
+    matches!(
+        run_until_cancelled(chunks.send(chunk), cancel).await,
+        Some(Ok(()))
+    )
+}
+
+/// Message and SQLSTATE code of an error, as returned to the client.
+fn error_message_and_code(e: &SqlOverHttpError) -> (String, &str) {
+    let db_error = match e {
+        SqlOverHttpError::ConnectCompute(HttpConnError::PostgresConnectionError(e))
+        | SqlOverHttpError::Postgres(e) => e.as_db_error(),
+        _ => None,
+    };
+    match db_error {
+        Some(db_error) => (db_error.message().to_owned(), db_error.code().code()),
+        None => (e.to_string_client(), ""),
+    }
+}
+
 enum Client {
     Remote(conn_pool::Client<tokio_postgres::Client>),
     Local(local_conn_pool::LocalClient<tokio_postgres::Client>),
@@ -1072,6 +1680,14 @@ fn metrics(&self) -> Arc<MetricCounter> {
         }
     }
 
+    /// Statements prepared on the connection, if they are cached for it.
+    fn statements(&mut self) -> Option<&mut StatementCache> {
+        match self {
+            Client::Remote(client) => Some(client.statements()),
+            Client::Local(_) => None,
+        }
+    }
+
     fn inner(&mut self) -> (&mut tokio_postgres::Client, Discard<'_>) {
         match self {
             Client::Remote(client) => {
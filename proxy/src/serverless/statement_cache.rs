@@ -0,0 +1,261 @@
+//! Cache of statements prepared on a pooled compute connection.
+//!
+//! Queries are sent as unnamed statements, so postgres parses and analyzes
+//! them on every execution. Repeated queries are instead prepared once per
+//! connection with `PREPARE` and run with `EXECUTE`, passing parameters as
+//! literals of unknown type, which postgres coerces to the parameter types
+//! just like text parameters of the extended protocol.
+//!
+//! Only the statements `PREPARE` supports are cached. Postgres replans
+//! prepared statements after schema changes by itself, but fails them if
+//! their result type changes. The cache is invalidated then and the request
+//! is run once more with freshly prepared statements. The cache is also
+//! invalidated after DDL, `DISCARD ALL` or changes of `search_path`, which
+//! prepared statements keep from the time they were prepared.
+//!
+//! Postgres doesn't report the source table and column of result fields of
+//! `EXECUTE`, so they are taken from the description of the query when it's
+//! prepared. Requests can still opt out of the cache with the
+//! `Neon-Statement-Cache-Opt-Out: true` header.
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use hashlink::LruCache;
+use serde_json::Value;
+
+use super::query_policy::keywords;
+
+/// Result fields of a prepared statement, as reported for the query.
+pub(crate) type Fields = Arc<[Value]>;
+
+enum Entry {
+    Prepared(String, Fields),
+    /// `PREPARE` failed, the query runs as an unnamed statement.
+    Unpreparable,
+}
+
+/// How to run a query on the connection.
+pub(crate) enum Lookup {
+    /// Run the statement prepared under this name.
+    Prepared(String, Fields),
+    /// Prepare the statement under this name first.
+    Prepare(String),
+    /// Run the query as an unnamed statement.
+    Unnamed,
+}
+
+pub(crate) struct StatementCache {
+    statements: LruCache<String, Entry>,
+    next_id: u64,
+    /// Statements evicted from the cache but still prepared on the connection.
+    stale: Vec<String>,
+}
+
+impl StatementCache {
+    /// Create a cache of up to `capacity` statements, 0 disables caching.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            statements: LruCache::new(capacity),
+            next_id: 0,
+            stale: Vec::new(),
+        }
+    }
+
+    pub(crate) fn lookup(&mut self, query: &str) -> Lookup {
+        if self.statements.capacity() == 0 {
+            return Lookup::Unnamed;
+        }
+        match self.statements.get(query) {
+            Some(Entry::Prepared(name, fields)) => Lookup::Prepared(name.clone(), fields.clone()),
+            Some(Entry::Unpreparable) => Lookup::Unnamed,
+            None if is_preparable(query) => {
+                self.next_id += 1;
+                Lookup::Prepare(format!("neon_http_stmt_{}", self.next_id))
+            }
+            None => Lookup::Unnamed,
+        }
+    }
+
+    pub(crate) fn insert_prepared(&mut self, query: String, name: String, fields: Fields) {
+        self.insert(query, Entry::Prepared(name, fields));
+    }
+
+    pub(crate) fn insert_unpreparable(&mut self, query: String) {
+        self.insert(query, Entry::Unpreparable);
+    }
+
+    fn insert(&mut self, query: String, entry: Entry) {
+        // Evict here, as the cache would drop the name of the evicted statement.
+        if !self.statements.contains_key(&query)
+            && self.statements.len() >= self.statements.capacity()
+        {
+            self.remove_lru();
+        }
+        if let Some(Entry::Prepared(name, _)) = self.statements.insert(query, entry) {
+            self.stale.push(name);
+        }
+    }
+
+    fn remove_lru(&mut self) -> bool {
+        match self.statements.remove_lru() {
+            Some((_, Entry::Prepared(name, _))) => {
+                self.stale.push(name);
+                true
+            }
+            Some((_, Entry::Unpreparable)) => true,
+            None => false,
+        }
+    }
+
+    /// Forget all statements, they will be deallocated before the next use of the connection.
+    pub(crate) fn invalidate(&mut self) {
+        while self.remove_lru() {}
+    }
+
+    /// Forget all statements after they were deallocated on the connection.
+    pub(crate) fn clear(&mut self) {
+        self.statements.clear();
+        self.stale.clear();
+    }
+
+    /// Take the statements to deallocate on the connection.
+    pub(crate) fn take_stale(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.stale)
+    }
+}
+
+/// Whether `PREPARE` supports the query.
+fn is_preparable(query: &str) -> bool {
+    let words = keywords(query);
+    let first = words.iter().find(|w| *w != "(");
+    matches!(
+        first.map(String::as_str),
+        Some("SELECT" | "INSERT" | "UPDATE" | "DELETE" | "MERGE" | "VALUES" | "WITH" | "TABLE")
+    )
+}
+
+/// Whether the query drops all prepared statements of the session.
+pub(crate) fn deallocates_all(query: &str) -> bool {
+    let words = keywords(query);
+    match words.first().map(String::as_str) {
+        Some("DISCARD") => words.get(1).is_some_and(|w| w == "ALL"),
+        Some("DEALLOCATE") => words.last().is_some_and(|w| w == "ALL"),
+        _ => false,
+    }
+}
+
+/// Whether the query changes `search_path`, which would make prepared
+/// statements resolve names differently from the query.
+pub(crate) fn changes_search_path(query: &str) -> bool {
+    let words = keywords(query);
+    match words.first().map(String::as_str) {
+        Some("SET" | "RESET") => {
+            let name = words[1..]
+                .iter()
+                .map(String::as_str)
+                .find(|w| !matches!(*w, "SESSION" | "LOCAL"));
+            matches!(name, Some("SEARCH_PATH" | "SCHEMA" | "ALL"))
+        }
+        // The setting name is a literal, which isn't looked into.
+        _ => words.iter().any(|w| w == "SET_CONFIG"),
+    }
+}
+
+/// `EXECUTE` statement running the prepared statement with the parameters.
+pub(crate) fn execute_statement(name: &str, params: &[Option<String>]) -> String {
+    let mut sql = format!("EXECUTE {name}");
+    for (i, param) in params.iter().enumerate() {
+        sql.push_str(if i == 0 { "(" } else { ", " });
+        match param {
+            Some(value) => {
+                // Escape string syntax doesn't depend on standard_conforming_strings.
+                sql.push_str("E'");
+                for c in value.chars() {
+                    match c {
+                        '\\' => sql.push_str("\\\\"),
+                        '\'' => sql.push_str("''"),
+                        c => sql.push(c),
+                    }
+                }
+                sql.push('\'');
+            }
+            None => sql.push_str("NULL"),
+        }
+    }
+    if !params.is_empty() {
+        sql.push(')');
+    }
+    sql
+}
+
+/// `DEALLOCATE` statements for the prepared statements.
+pub(crate) fn deallocate_statements(names: &[String]) -> String {
+    let mut sql = String::new();
+    for name in names {
+        let _ = write!(sql, "DEALLOCATE {name};");
+    }
+    sql
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statement_cache() {
+        let mut cache = StatementCache::new(2);
+        let Lookup::Prepare(name1) = cache.lookup("select $1") else {
+            panic!("statement should be prepared");
+        };
+        let fields: Fields = Arc::new([serde_json::json!({"tableID": 1})]);
+        cache.insert_prepared("select $1".to_owned(), name1.clone(), fields.clone());
+        assert!(matches!(
+            cache.lookup("select $1"),
+            Lookup::Prepared(name, cached) if name == name1 && cached == fields
+        ));
+
+        assert!(matches!(cache.lookup("begin"), Lookup::Unnamed));
+        cache.insert_unpreparable("select bad".to_owned());
+        assert!(matches!(cache.lookup("select bad"), Lookup::Unnamed));
+
+        // Evicted statements are deallocated later.
+        let Lookup::Prepare(name2) = cache.lookup("select 2") else {
+            panic!("statement should be prepared");
+        };
+        cache.insert_prepared("select 2".to_owned(), name2.clone(), fields);
+        assert_eq!(cache.take_stale(), vec![name1]);
+
+        cache.invalidate();
+        assert_eq!(cache.take_stale(), vec![name2]);
+        assert!(matches!(cache.lookup("select 2"), Lookup::Prepare(_)));
+
+        let mut disabled = StatementCache::new(0);
+        assert!(matches!(disabled.lookup("select 1"), Lookup::Unnamed));
+    }
+
+    #[test]
+    fn statements_sql() {
+        assert_eq!(execute_statement("s1", &[]), "EXECUTE s1");
+        assert_eq!(
+            execute_statement("s1", &[Some("it's \\".to_owned()), None]),
+            "EXECUTE s1(E'it''s \\\\', NULL)"
+        );
+        assert_eq!(
+            deallocate_statements(&["s1".to_owned(), "s2".to_owned()]),
+            "DEALLOCATE s1;DEALLOCATE s2;"
+        );
+        assert!(deallocates_all("discard all"));
+        assert!(deallocates_all("DEALLOCATE PREPARE ALL"));
+        assert!(!deallocates_all("discard plans"));
+        assert!(changes_search_path("set search_path = s"));
+        assert!(changes_search_path("SET LOCAL search_path TO s"));
+        assert!(changes_search_path("set schema 's'"));
+        assert!(changes_search_path("reset all"));
+        assert!(changes_search_path(
+            "select set_config('search_path', 's', false)"
+        ));
+        assert!(!changes_search_path("set statement_timeout = 0"));
+        assert!(!changes_search_path("select 'set search_path'"));
+    }
+}
@@ -0,0 +1,331 @@
+//! Streaming responses for SQL-over-HTTP.
+//!
+//! By default the whole result set of a request is buffered and returned as
+//! one JSON document, limited by `max_response_size_bytes`. With the
+//! `Neon-Response-Format` header a single query streams its rows instead,
+//! without a size limit:
+//!
+//! * `ndjson`: newline-delimited JSON. The first line holds `fields` and
+//!   `rowAsArray` as in the JSON response, followed by a line per row. The last
+//!   line holds `command` and `rowCount` if the query succeeded, or `error`
+//!   with `message` and `code` if it failed after the response started.
+//! * `arrow`: Apache Arrow IPC stream with a nullable string column per result
+//!   column, holding the postgres text representation of values. The type OID
+//!   of the column is in the `pg_type_oid` field metadata. If the query fails
+//!   after the response started, the stream ends without the end-of-stream
+//!   marker.
+//!
+//! Rows are encoded into chunks which are sent to the client through a small
+//! buffer, so rows are read from postgres only as fast as the client consumes
+//! them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::builder::StringBuilder;
+use arrow_array::{ArrayRef, RecordBatch, RecordBatchOptions};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use bytes::Bytes;
+use hyper::http::HeaderValue;
+use serde_json::Value;
+use tokio_postgres::types::Type;
+use tokio_postgres::{Column, Row};
+
+use super::json::{column_to_json, pg_text_row_to_json, JsonConversionError};
+
+/// Rows are sent to the client in chunks of about this size.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Arrow record batches are flushed after this many rows, or after the chunk size.
+const ARROW_BATCH_ROWS: usize = 8192;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ResponseFormat {
+    /// Single buffered JSON document.
+    Json,
+    Ndjson,
+    Arrow,
+}
+
+impl ResponseFormat {
+    pub(crate) fn parse(value: &HeaderValue) -> Option<Self> {
+        match value.as_bytes() {
+            b"json" => Some(Self::Json),
+            b"ndjson" => Some(Self::Ndjson),
+            b"arrow" => Some(Self::Arrow),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+            Self::Arrow => "application/vnd.apache.arrow.stream",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum EncodeError {
+    #[error("{0}")]
+    Json(#[from] JsonConversionError),
+    #[error("{0}")]
+    Arrow(#[from] ArrowError),
+    #[error("internal error compute returned invalid data: {0}")]
+    AsText(tokio_postgres::Error),
+}
+
+/// Encoder of rows into chunks of a streaming response.
+pub(crate) enum RowEncoder {
+    Ndjson(NdjsonEncoder),
+    Arrow(ArrowEncoder),
+}
+
+impl RowEncoder {
+    /// Create an encoder for the result columns, returning it with the first chunk.
+    pub(crate) fn new(
+        format: ResponseFormat,
+        columns: &[Column],
+        types: Vec<Type>,
+        raw_output: bool,
+        array_mode: bool,
+    ) -> Result<(Self, Bytes), EncodeError> {
+        match format {
+            ResponseFormat::Ndjson => {
+                let (encoder, header) = NdjsonEncoder::new(columns, types, raw_output, array_mode);
+                Ok((Self::Ndjson(encoder), header))
+            }
+            ResponseFormat::Arrow => {
+                let fields = columns.iter().map(|c| (c.name(), c.type_oid()));
+                let (encoder, header) = ArrowEncoder::new(fields)?;
+                Ok((Self::Arrow(encoder), header))
+            }
+            ResponseFormat::Json => unreachable!("json responses are not streamed"),
+        }
+    }
+
+    /// Add a row, returning a chunk if enough data is buffered.
+    pub(crate) fn push(&mut self, row: &Row) -> Result<Option<Bytes>, EncodeError> {
+        match self {
+            Self::Ndjson(encoder) => encoder.push(row),
+            Self::Arrow(encoder) => {
+                let values = (0..row.len())
+                    .map(|i| row.as_text(i))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(EncodeError::AsText)?;
+                encoder.push(&values, row.body_len())
+            }
+        }
+    }
+
+    /// Finish the successful response, returning the last chunk.
+    pub(crate) fn finish(
+        self,
+        command: &str,
+        row_count: Option<i64>,
+    ) -> Result<Bytes, EncodeError> {
+        match self {
+            Self::Ndjson(encoder) => Ok(encoder.finish(command, row_count)),
+            Self::Arrow(encoder) => encoder.finish(),
+        }
+    }
+
+    /// Finish the response after the query failed, returning the last chunk.
+    pub(crate) fn fail(self, message: &str, code: &str) -> Bytes {
+        match self {
+            Self::Ndjson(encoder) => encoder.fail(message, code),
+            // Readers notice the missing end-of-stream marker.
+            Self::Arrow(mut encoder) => encoder.take_buffered(),
+        }
+    }
+}
+
+pub(crate) struct NdjsonEncoder {
+    types: Vec<Type>,
+    raw_output: bool,
+    array_mode: bool,
+    buf: Vec<u8>,
+}
+
+impl NdjsonEncoder {
+    fn new(
+        columns: &[Column],
+        types: Vec<Type>,
+        raw_output: bool,
+        array_mode: bool,
+    ) -> (Self, Bytes) {
+        let fields: Vec<Value> = columns.iter().map(column_to_json).collect();
+        let header = serde_json::json!({ "fields": fields, "rowAsArray": array_mode });
+        let encoder = Self {
+            types,
+            raw_output,
+            array_mode,
+            buf: Vec::new(),
+        };
+        (encoder, line(&header))
+    }
+
+    fn push(&mut self, row: &Row) -> Result<Option<Bytes>, EncodeError> {
+        let value = pg_text_row_to_json(row, &self.types, self.raw_output, self.array_mode)?;
+        serde_json::to_writer(&mut self.buf, &value).expect("json serialization should not fail");
+        self.buf.push(b'\n');
+        if self.buf.len() < CHUNK_SIZE {
+            return Ok(None);
+        }
+        Ok(Some(std::mem::take(&mut self.buf).into()))
+    }
+
+    fn finish(mut self, command: &str, row_count: Option<i64>) -> Bytes {
+        let summary = serde_json::json!({ "command": command, "rowCount": row_count });
+        self.buf.extend_from_slice(&line(&summary));
+        self.buf.into()
+    }
+
+    fn fail(mut self, message: &str, code: &str) -> Bytes {
+        let error = serde_json::json!({ "error": { "message": message, "code": code } });
+        self.buf.extend_from_slice(&line(&error));
+        self.buf.into()
+    }
+}
+
+fn line(value: &Value) -> Bytes {
+    let mut buf = serde_json::to_vec(value).expect("json serialization should not fail");
+    buf.push(b'\n');
+    buf.into()
+}
+
+pub(crate) struct ArrowEncoder {
+    schema: SchemaRef,
+    builders: Vec<StringBuilder>,
+    rows: usize,
+    batch_bytes: usize,
+    writer: StreamWriter<Vec<u8>>,
+}
+
+impl ArrowEncoder {
+    fn new<'a>(fields: impl Iterator<Item = (&'a str, u32)>) -> Result<(Self, Bytes), EncodeError> {
+        let fields: Vec<Field> = fields
+            .map(|(name, oid)| {
+                Field::new(name, DataType::Utf8, true)
+                    .with_metadata(HashMap::from([("pg_type_oid".to_owned(), oid.to_string())]))
+            })
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+        let builders = schema
+            .fields()
+            .iter()
+            .map(|_| StringBuilder::new())
+            .collect();
+        let mut encoder = Self {
+            writer: StreamWriter::try_new(Vec::new(), &schema)?,
+            schema,
+            builders,
+            rows: 0,
+            batch_bytes: 0,
+        };
+        let header = encoder.take_buffered();
+        Ok((encoder, header))
+    }
+
+    fn push(&mut self, values: &[Option<&str>], size: usize) -> Result<Option<Bytes>, EncodeError> {
+        for (builder, value) in self.builders.iter_mut().zip(values) {
+            builder.append_option(*value);
+        }
+        self.rows += 1;
+        self.batch_bytes += size;
+        if self.rows < ARROW_BATCH_ROWS && self.batch_bytes < CHUNK_SIZE {
+            return Ok(None);
+        }
+        self.write_batch()?;
+        Ok(Some(self.take_buffered()))
+    }
+
+    fn finish(mut self) -> Result<Bytes, EncodeError> {
+        if self.rows > 0 {
+            self.write_batch()?;
+        }
+        self.writer.finish()?;
+        Ok(self.take_buffered())
+    }
+
+    fn write_batch(&mut self) -> Result<(), EncodeError> {
+        let columns: Vec<ArrayRef> = self
+            .builders
+            .iter_mut()
+            .map(|b| Arc::new(b.finish()) as ArrayRef)
+            .collect();
+        // Row count is needed for results without columns.
+        let options = RecordBatchOptions::new().with_row_count(Some(self.rows));
+        let batch = RecordBatch::try_new_with_options(self.schema.clone(), columns, &options)?;
+        self.writer.write(&batch)?;
+        self.rows = 0;
+        self.batch_bytes = 0;
+        Ok(())
+    }
+
+    fn take_buffered(&mut self) -> Bytes {
+        std::mem::take(self.writer.get_mut()).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Array, StringArray};
+    use arrow_ipc::reader::StreamReader;
+
+    use super::*;
+
+    #[test]
+    fn parse_response_format() {
+        let parse = |v| ResponseFormat::parse(&HeaderValue::from_static(v));
+        assert_eq!(parse("json"), Some(ResponseFormat::Json));
+        assert_eq!(parse("ndjson"), Some(ResponseFormat::Ndjson));
+        assert_eq!(parse("arrow"), Some(ResponseFormat::Arrow));
+        assert_eq!(parse("csv"), None);
+    }
+
+    #[test]
+    fn arrow_stream() {
+        let (mut encoder, header) =
+            ArrowEncoder::new([("id", 23), ("name", 25)].into_iter()).unwrap();
+        let mut stream = header.to_vec();
+        for i in 0..ARROW_BATCH_ROWS + 10 {
+            let id = i.to_string();
+            let name = (i % 2 == 0).then_some("even");
+            if let Some(chunk) = encoder.push(&[Some(&id), name], 10).unwrap() {
+                stream.extend_from_slice(&chunk);
+            }
+        }
+        stream.extend_from_slice(&encoder.finish().unwrap());
+
+        let reader = StreamReader::try_new(stream.as_slice(), None).unwrap();
+        let schema = reader.schema();
+        assert_eq!(schema.field(0).name(), "id");
+        assert_eq!(schema.field(1).metadata()["pg_type_oid"], "25");
+        let batches: Vec<RecordBatch> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].num_rows(), ARROW_BATCH_ROWS);
+        assert_eq!(batches[1].num_rows(), 10);
+        let names = batches[1]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(0), "even");
+        assert!(names.is_null(1));
+    }
+
+    #[test]
+    fn arrow_stream_without_columns() {
+        let (mut encoder, header) = ArrowEncoder::new(std::iter::empty()).unwrap();
+        assert!(encoder.push(&[], 0).unwrap().is_none());
+        let mut stream = header.to_vec();
+        stream.extend_from_slice(&encoder.finish().unwrap());
+
+        let reader = StreamReader::try_new(stream.as_slice(), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(batches[0].num_rows(), 1);
+    }
+}
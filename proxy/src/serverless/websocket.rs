@@ -3,6 +3,7 @@
     cancellation::CancellationHandlerMain,
     config::ProxyConfig,
     context::RequestMonitoring,
+    drain,
     error::{io_error, ReportableError},
     metrics::Metrics,
     proxy::{handle_client, ClientMode},
@@ -17,6 +18,7 @@
 use pin_project_lite::pin_project;
 
 use std::{
+    future::Future,
     pin::Pin,
     sync::Arc,
     task::{ready, Context, Poll},
@@ -127,6 +129,7 @@ fn consume(self: Pin<&mut Self>, amount: usize) {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn serve_websocket(
     config: &'static ProxyConfig,
     auth_backend: &'static crate::auth::Backend<'static, ()>,
@@ -135,6 +138,7 @@ pub(crate) async fn serve_websocket(
     cancellation_handler: Arc<CancellationHandlerMain>,
     endpoint_rate_limiter: Arc<EndpointRateLimiter>,
     hostname: Option<String>,
+    mut drain_deadline: Pin<Box<dyn Future<Output = ()> + Send>>,
 ) -> anyhow::Result<()> {
     let websocket = websocket.await?;
     let websocket = WebSocketServer::after_handshake(TokioIo::new(websocket));
@@ -144,7 +148,7 @@ pub(crate) async fn serve_websocket(
         .client_connections
         .guard(crate::metrics::Protocol::Ws);
 
-    let res = Box::pin(handle_client(
+    let startup = Box::pin(handle_client(
         config,
         auth_backend,
         &ctx,
@@ -153,8 +157,10 @@ pub(crate) async fn serve_websocket(
         ClientMode::Websockets { hostname },
         endpoint_rate_limiter,
         conn_gauge,
-    ))
-    .await;
+    ));
+    let Some(res) = drain::before_deadline(&mut drain_deadline, startup).await else {
+        return Ok(());
+    };
 
     match res {
         Err(e) => {
@@ -169,7 +175,7 @@ pub(crate) async fn serve_websocket(
         Ok(Some(p)) => {
             ctx.set_success();
             ctx.log_connect();
-            match p.proxy_pass().await {
+            match p.proxy_pass(&ctx, drain_deadline).await {
                 Ok(()) => Ok(()),
                 Err(ErrorSource::Client(err)) => Err(err).context("client"),
                 Err(ErrorSource::Compute(err)) => Err(err).context("compute"),
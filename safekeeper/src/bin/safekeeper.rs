@@ -29,7 +29,7 @@
 use safekeeper::defaults::{
     DEFAULT_CONTROL_FILE_SAVE_INTERVAL, DEFAULT_EVICTION_MIN_RESIDENT, DEFAULT_HEARTBEAT_TIMEOUT,
     DEFAULT_HTTP_LISTEN_ADDR, DEFAULT_MAX_OFFLOADER_LAG_BYTES, DEFAULT_PARTIAL_BACKUP_CONCURRENCY,
-    DEFAULT_PARTIAL_BACKUP_TIMEOUT, DEFAULT_PG_LISTEN_ADDR,
+    DEFAULT_PARTIAL_BACKUP_TIMEOUT, DEFAULT_PG_LISTEN_ADDR, DEFAULT_WAL_READ_CACHE_TOTAL_BYTES,
 };
 use safekeeper::http;
 use safekeeper::wal_service;
@@ -38,6 +38,7 @@
 use safekeeper::{broker, WAL_SERVICE_RUNTIME};
 use safekeeper::{control_file, BROKER_RUNTIME};
 use safekeeper::{wal_backup, HTTP_RUNTIME};
+use safekeeper::{wal_backup_retention, wal_verify, WAL_BACKUP_RUNTIME};
 use storage_broker::DEFAULT_ENDPOINT;
 use utils::auth::{JwtAuth, Scope, SwappableJwtAuth};
 use utils::{
@@ -195,6 +196,40 @@ struct Args {
     /// if it weren't for `eviction_min_resident` preventing that.
     #[arg(long, value_parser = humantime::parse_duration, default_value = DEFAULT_EVICTION_MIN_RESIDENT)]
     eviction_min_resident: Duration,
+    /// Maximum rate of WAL ingested from computes per tenant, in bytes per second. Replies to
+    /// computes of a tenant exceeding it are delayed. 0 disables the limit.
+    #[arg(long, default_value = "0")]
+    wal_ingest_rate_limit_bytes: u64,
+    /// Amount of WAL a tenant may ingest at once before the rate limit applies, in bytes.
+    /// 0 means one second worth of WAL at the rate limit.
+    #[arg(long, default_value = "0")]
+    wal_ingest_burst_bytes: u64,
+    /// Size of the in-memory cache of WAL read by walsenders, per timeline, in bytes. Lets
+    /// readers at similar positions, e.g. pageservers of different shards, read WAL from disk
+    /// once. 0 disables the cache.
+    #[arg(long, default_value = "0")]
+    wal_read_cache_timeline_bytes: usize,
+    /// Limit on the total size of WAL read caches of all timelines, in bytes.
+    #[arg(long, default_value = DEFAULT_WAL_READ_CACHE_TOTAL_BYTES)]
+    wal_read_cache_total_bytes: usize,
+    /// Periodically compare committed WAL of timelines with peer safekeepers with this
+    /// interval, passed as a human readable duration. Verification is disabled if not set.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    wal_verify_interval: Option<Duration>,
+    /// Overwrite local WAL which differs from WAL of the majority of peers with theirs.
+    /// By default divergence is only reported.
+    #[arg(long, default_value = "false", action=ArgAction::Set)]
+    wal_verify_repair: bool,
+    /// Keep WAL archived in remote storage for at least this period, passed as a human
+    /// readable duration, even if pageservers don't need it anymore, and even after the
+    /// timeline is deleted. If either retention limit is set, segments outside of both
+    /// are deleted once pageservers and peer safekeepers don't need them.
+    #[arg(long, value_parser = humantime::parse_duration, verbatim_doc_comment)]
+    wal_archive_retention_period: Option<Duration>,
+    /// Keep at least this many bytes of the newest WAL archived in remote storage, even if
+    /// pageservers don't need it anymore. See --wal-archive-retention-period.
+    #[arg(long)]
+    wal_archive_retention_bytes: Option<u64>,
 }
 
 // Like PathBufValueParser, but allows empty string.
@@ -358,6 +393,14 @@ async fn main() -> anyhow::Result<()> {
         control_file_save_interval: args.control_file_save_interval,
         partial_backup_concurrency: args.partial_backup_concurrency,
         eviction_min_resident: args.eviction_min_resident,
+        wal_ingest_rate_limit_bytes: args.wal_ingest_rate_limit_bytes,
+        wal_ingest_burst_bytes: args.wal_ingest_burst_bytes,
+        wal_read_cache_timeline_bytes: args.wal_read_cache_timeline_bytes,
+        wal_read_cache_total_bytes: args.wal_read_cache_total_bytes,
+        wal_verify_interval: args.wal_verify_interval,
+        wal_verify_repair: args.wal_verify_repair,
+        wal_archive_retention_period: args.wal_archive_retention_period,
+        wal_archive_retention_bytes: args.wal_archive_retention_bytes,
     };
 
     // initialize sentry if SENTRY_DSN is provided
@@ -485,6 +528,33 @@ async fn start_safekeeper(conf: SafeKeeperConf) -> Result<()> {
         tasks_handles.push(Box::pin(wal_service_handle));
     }
 
+    if let Some(interval) = conf.wal_verify_interval {
+        let conf_ = conf.clone();
+        let wal_verify_handle = current_thread_rt
+            .as_ref()
+            .unwrap_or_else(|| WAL_BACKUP_RUNTIME.handle())
+            .spawn(wal_verify::task_main(conf_, interval).instrument(info_span!("wal_verify")))
+            .map(|res| ("WAL verification".to_owned(), res));
+        tasks_handles.push(Box::pin(wal_verify_handle));
+    }
+
+    if let Some(policy) = wal_backup_retention::RetentionPolicy::from_conf(&conf) {
+        if conf.is_wal_backup_enabled() {
+            let conf_ = conf.clone();
+            let retention_handle = current_thread_rt
+                .as_ref()
+                .unwrap_or_else(|| WAL_BACKUP_RUNTIME.handle())
+                .spawn(
+                    wal_backup_retention::task_main(conf_, policy)
+                        .instrument(info_span!("wal_backup_retention")),
+                )
+                .map(|res| ("WAL archive retention".to_owned(), res));
+            tasks_handles.push(Box::pin(retention_handle));
+        } else {
+            warn!("WAL archive retention is configured, but WAL backup is disabled");
+        }
+    }
+
     let conf_ = conf.clone();
     let http_handle = current_thread_rt
         .as_ref()
@@ -380,7 +380,8 @@ pub async fn calculate_digest(
         bail!("requested LSN is before the start of the timeline");
     }
 
-    let mut wal_reader = tli.get_walreader(request.from_lsn).await?;
+    // Bypass the cache to check what is actually stored.
+    let mut wal_reader = tli.get_uncached_walreader(request.from_lsn).await?;
 
     let mut hasher = Sha256::new();
     let mut buf = [0u8; MAX_SEND_SIZE];
@@ -12,9 +12,11 @@
     http::error::HttpErrorBody,
     id::{NodeId, TenantId, TimelineId},
     logging::SecretString,
+    lsn::Lsn,
 };
 
 use super::routes::TimelineStatus;
+use crate::debug_dump::TimelineDigest;
 
 #[derive(Debug, Clone)]
 pub struct Client {
@@ -106,6 +108,21 @@ pub async fn snapshot(
         self.get(&uri).await
     }
 
+    pub async fn timeline_digest(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        from_lsn: Lsn,
+        until_lsn: Lsn,
+    ) -> Result<TimelineDigest> {
+        let uri = format!(
+            "{}/v1/tenant/{}/timeline/{}/digest?from_lsn={}&until_lsn={}",
+            self.mgmt_api_endpoint, tenant_id, timeline_id, from_lsn, until_lsn
+        );
+        let resp = self.get(&uri).await?;
+        resp.json().await.map_err(Error::ReceiveBody)
+    }
+
     async fn get<U: IntoUrl>(&self, uri: U) -> Result<reqwest::Response> {
         self.request(Method::GET, uri, ()).await
     }
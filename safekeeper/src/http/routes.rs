@@ -18,8 +18,10 @@
 use utils::http::request::parse_query_param;
 
 use postgres_ffi::WAL_SEGMENT_SIZE;
-use safekeeper_api::models::{SkTimelineInfo, TimelineCopyRequest};
-use safekeeper_api::models::{TimelineCreateRequest, TimelineTermBumpRequest};
+use safekeeper_api::models::{PullTimelineRequest, SkTimelineInfo, TimelineCopyRequest};
+use safekeeper_api::models::{
+    TenantWalIngestRateLimitRequest, TimelineCreateRequest, TimelineTermBumpRequest,
+};
 use utils::{
     auth::SwappableJwtAuth,
     http::{
@@ -135,6 +137,17 @@ async fn tenant_delete_handler(mut request: Request<Body>) -> Result<Response<Bo
     )
 }
 
+/// Override the WAL ingest rate limit of a tenant. Overrides are not persisted.
+async fn tenant_wal_ingest_rate_limit_handler(
+    mut request: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_id = parse_request_param(&request, "tenant_id")?;
+    check_permission(&request, None)?;
+    let request_data: TenantWalIngestRateLimitRequest = json_request(&mut request).await?;
+    GlobalTimelines::set_ingest_rate_limit(tenant_id, request_data.bytes_per_sec);
+    json_response(StatusCode::OK, ())
+}
+
 async fn timeline_create_handler(mut request: Request<Body>) -> Result<Response<Body>, ApiError> {
     let request_data: TimelineCreateRequest = json_request(&mut request).await?;
 
@@ -243,7 +256,7 @@ async fn timeline_delete_handler(mut request: Request<Body>) -> Result<Response<
 async fn timeline_pull_handler(mut request: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permission(&request, None)?;
 
-    let data: pull_timeline::Request = json_request(&mut request).await?;
+    let data: PullTimelineRequest = json_request(&mut request).await?;
     let conf = get_conf(&request);
 
     let resp = pull_timeline::handle_request(data, conf.sk_auth_token.clone())
@@ -617,6 +630,9 @@ pub fn make_router(conf: SafeKeeperConf) -> RouterBuilder<hyper::Body, ApiError>
         .delete("/v1/tenant/:tenant_id", |r| {
             request_span(r, tenant_delete_handler)
         })
+        .put("/v1/tenant/:tenant_id/wal_ingest_rate_limit", |r| {
+            request_span(r, tenant_wal_ingest_rate_limit_handler)
+        })
         // Will be used in the future instead of implicit timeline creation
         .post("/v1/tenant/timeline", |r| {
             request_span(r, timeline_create_handler)
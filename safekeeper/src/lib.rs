@@ -38,8 +38,11 @@
 pub mod timelines_set;
 pub mod wal_backup;
 pub mod wal_backup_partial;
+pub mod wal_backup_retention;
+pub mod wal_cache;
 pub mod wal_service;
 pub mod wal_storage;
+pub mod wal_verify;
 
 mod timelines_global_map;
 use std::sync::Arc;
@@ -58,6 +61,7 @@ pub mod defaults {
     pub const DEFAULT_CONTROL_FILE_SAVE_INTERVAL: &str = "300s";
     pub const DEFAULT_PARTIAL_BACKUP_CONCURRENCY: &str = "5";
     pub const DEFAULT_EVICTION_CONCURRENCY: usize = 2;
+    pub const DEFAULT_WAL_READ_CACHE_TOTAL_BYTES: &str = "268435456"; // 256 MiB
 
     // By default, our required residency before eviction is the same as the period that passes
     // before uploading a partial segment, so that in normal operation the eviction can happen
@@ -103,6 +107,22 @@ pub struct SafeKeeperConf {
     pub control_file_save_interval: Duration,
     pub partial_backup_concurrency: usize,
     pub eviction_min_resident: Duration,
+    /// Default limit on the rate of WAL ingested per tenant, 0 means unlimited.
+    pub wal_ingest_rate_limit_bytes: u64,
+    /// Burst allowed on top of the WAL ingest rate limit, 0 means one second worth of WAL.
+    pub wal_ingest_burst_bytes: u64,
+    /// Size of the WAL read cache of each timeline, 0 disables the cache.
+    pub wal_read_cache_timeline_bytes: usize,
+    /// Limit on the total size of WAL read caches of all timelines.
+    pub wal_read_cache_total_bytes: usize,
+    /// How often committed WAL is compared with peers, None disables verification.
+    pub wal_verify_interval: Option<Duration>,
+    /// Whether to overwrite local WAL found to differ from the majority of peers.
+    pub wal_verify_repair: bool,
+    /// Keep WAL archived in remote storage for this period.
+    pub wal_archive_retention_period: Option<Duration>,
+    /// Keep this many bytes of the newest WAL archived in remote storage.
+    pub wal_archive_retention_bytes: Option<u64>,
 }
 
 impl SafeKeeperConf {
@@ -146,6 +166,14 @@ fn dummy() -> Self {
             control_file_save_interval: Duration::from_secs(1),
             partial_backup_concurrency: 1,
             eviction_min_resident: Duration::ZERO,
+            wal_ingest_rate_limit_bytes: 0,
+            wal_ingest_burst_bytes: 0,
+            wal_read_cache_timeline_bytes: 0,
+            wal_read_cache_total_bytes: 0,
+            wal_verify_interval: None,
+            wal_verify_repair: false,
+            wal_archive_retention_period: None,
+            wal_archive_retention_bytes: None,
         }
     }
 }
@@ -133,6 +133,27 @@
     )
     .expect("Failed to register safekeeper_backed_up_segments_total counter")
 });
+pub static WAL_ARCHIVE_RETENTION_DELETED_SEGMENTS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "safekeeper_wal_archive_retention_deleted_segments_total",
+        "Number of WAL segments deleted from the S3 by the archive retention policy"
+    )
+    .expect("Failed to register safekeeper_wal_archive_retention_deleted_segments_total counter")
+});
+pub static WAL_ARCHIVE_RETAINED_SEGMENTS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "safekeeper_wal_archive_retained_segments",
+        "Number of archived WAL segments kept by the archive retention policy, as of the last pass"
+    )
+    .expect("Failed to register safekeeper_wal_archive_retained_segments gauge")
+});
+pub static WAL_ARCHIVE_RETAINED_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "safekeeper_wal_archive_retained_bytes",
+        "Size of archived WAL kept by the archive retention policy, as of the last pass"
+    )
+    .expect("Failed to register safekeeper_wal_archive_retained_bytes gauge")
+});
 pub static BACKUP_ERRORS: Lazy<IntCounter> = Lazy::new(|| {
     register_int_counter!(
         "safekeeper_backup_errors_total",
@@ -204,6 +225,64 @@
     )
     .expect("Failed to register safekeeper_wal_backup_tasks_finished_total counter")
 });
+pub static WAL_INGEST_THROTTLED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "safekeeper_wal_ingest_throttled_total",
+        "Number of times replies to computes were delayed by the WAL ingest rate limit"
+    )
+    .expect("Failed to register safekeeper_wal_ingest_throttled_total counter")
+});
+pub static WAL_INGEST_THROTTLED_USECS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "safekeeper_wal_ingest_throttled_usecs_total",
+        "Total time replies to computes were delayed by the WAL ingest rate limit, in microseconds"
+    )
+    .expect("Failed to register safekeeper_wal_ingest_throttled_usecs_total counter")
+});
+pub static WAL_READ_CACHE_HITS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "safekeeper_wal_read_cache_hits_total",
+        "Number of WAL chunks read from the WAL read cache"
+    )
+    .expect("Failed to register safekeeper_wal_read_cache_hits_total counter")
+});
+pub static WAL_READ_CACHE_MISSES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "safekeeper_wal_read_cache_misses_total",
+        "Number of WAL chunks read from disk or remote storage by readers using the WAL read cache"
+    )
+    .expect("Failed to register safekeeper_wal_read_cache_misses_total counter")
+});
+pub static WAL_READ_CACHE_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "safekeeper_wal_read_cache_bytes",
+        "Bytes of WAL held in the WAL read caches of all timelines"
+    )
+    .expect("Failed to register safekeeper_wal_read_cache_bytes gauge")
+});
+pub static WAL_VERIFY_BYTES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "safekeeper_wal_verify_bytes_total",
+        "Bytes of committed WAL verified to match peer safekeepers"
+    )
+    .expect("Failed to register safekeeper_wal_verify_bytes_total counter")
+});
+pub static WAL_VERIFY_DIVERGENCES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "safekeeper_wal_verify_divergences_total",
+        "Number of committed WAL ranges found to differ between safekeepers, by the diverged replica",
+        &["replica"]
+    )
+    .expect("Failed to register safekeeper_wal_verify_divergences_total counter")
+});
+pub static WAL_VERIFY_REPAIRS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "safekeeper_wal_verify_repairs_total",
+        "Number of attempts to repair diverged local WAL from a peer, by result",
+        &["result"]
+    )
+    .expect("Failed to register safekeeper_wal_verify_repairs_total counter")
+});
 
 // Metrics collected on operations on the storage repository.
 #[derive(strum_macros::EnumString, strum_macros::Display, strum_macros::IntoStaticStr)]
@@ -1,8 +1,13 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use rand::Rng;
+use utils::id::TenantId;
+use utils::leaky_bucket;
 
-use crate::metrics::MISC_OPERATION_SECONDS;
+use crate::metrics::{
+    MISC_OPERATION_SECONDS, WAL_INGEST_THROTTLED_TOTAL, WAL_INGEST_THROTTLED_USECS,
+};
 
 /// Global rate limiter for background tasks.
 #[derive(Clone)]
@@ -42,8 +47,126 @@ pub fn try_acquire_eviction(&self) -> Option<tokio::sync::OwnedSemaphorePermit>
     }
 }
 
+/// Per-tenant limits on the rate of WAL ingested from computes. Each tenant gets its own
+/// leaky bucket, shared by all of its timelines, with the rate taken from the per-tenant
+/// override if one was set, or from the default otherwise. A rate of 0 means unlimited.
+pub struct WalIngestLimits {
+    default_bytes_per_sec: u64,
+    burst_bytes: u64,
+    overrides: HashMap<TenantId, u64>,
+    limiters: HashMap<TenantId, (u64, Arc<leaky_bucket::RateLimiter>)>,
+}
+
+impl WalIngestLimits {
+    /// Create per-tenant limits.
+    /// - `default_bytes_per_sec`: rate for tenants without an override, 0 disables the limit.
+    /// - `burst_bytes`: how much WAL may be accepted at once before throttling kicks in,
+    ///   0 means one second worth of WAL.
+    pub fn new(default_bytes_per_sec: u64, burst_bytes: u64) -> Self {
+        Self {
+            default_bytes_per_sec,
+            burst_bytes,
+            overrides: HashMap::new(),
+            limiters: HashMap::new(),
+        }
+    }
+
+    /// Get the limiter of the tenant, or None if its ingest is not limited.
+    pub fn get(&mut self, tenant_id: TenantId) -> Option<IngestLimiter> {
+        let rate = self
+            .overrides
+            .get(&tenant_id)
+            .copied()
+            .unwrap_or(self.default_bytes_per_sec);
+        if rate == 0 {
+            self.limiters.remove(&tenant_id);
+            return None;
+        }
+
+        if let Some((limiter_rate, limiter)) = self.limiters.get(&tenant_id) {
+            if *limiter_rate == rate {
+                return Some(IngestLimiter(limiter.clone()));
+            }
+        }
+
+        let burst = if self.burst_bytes == 0 {
+            rate
+        } else {
+            self.burst_bytes
+        };
+        let limiter = Arc::new(leaky_bucket::RateLimiter::with_initial_tokens(
+            leaky_bucket::LeakyBucketConfig::new(rate as f64, burst as f64),
+            0.0,
+        ));
+        self.limiters.insert(tenant_id, (rate, limiter.clone()));
+        Some(IngestLimiter(limiter))
+    }
+
+    /// Set or, with None, clear the rate override of the tenant.
+    pub fn set_override(&mut self, tenant_id: TenantId, bytes_per_sec: Option<u64>) {
+        match bytes_per_sec {
+            Some(rate) => self.overrides.insert(tenant_id, rate),
+            None => self.overrides.remove(&tenant_id),
+        };
+    }
+
+    /// Forget everything about the tenant, e.g. when it is deleted.
+    pub fn remove(&mut self, tenant_id: &TenantId) {
+        self.overrides.remove(tenant_id);
+        self.limiters.remove(tenant_id);
+    }
+}
+
+/// Handle to the WAL ingest limiter of a single tenant.
+#[derive(Clone)]
+pub struct IngestLimiter(Arc<leaky_bucket::RateLimiter>);
+
+impl IngestLimiter {
+    /// Account `bytes` of ingested WAL, sleeping if the tenant is over its rate.
+    pub async fn throttle(&self, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        let started = std::time::Instant::now();
+        if self.0.acquire(bytes).await {
+            WAL_INGEST_THROTTLED_TOTAL.inc();
+            WAL_INGEST_THROTTLED_USECS.inc_by(started.elapsed().as_micros() as u64);
+        }
+    }
+}
+
 /// Generate a random duration that is a fraction of the given duration.
 pub fn rand_duration(duration: &std::time::Duration) -> std::time::Duration {
     let randf64 = rand::thread_rng().gen_range(0.0..1.0);
     duration.mul_f64(randf64)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wal_ingest_limit_overrides() {
+        let tenant_a = TenantId::generate();
+        let tenant_b = TenantId::generate();
+        let mut limits = WalIngestLimits::new(0, 0);
+
+        // Unlimited by default
+        assert!(limits.get(tenant_a).is_none());
+
+        limits.set_override(tenant_a, Some(1024));
+        let limiter = limits.get(tenant_a).expect("tenant is limited");
+        assert!((limiter.0.steady_rps() - 1024.0).abs() < 1.0);
+        assert!(limits.get(tenant_b).is_none());
+
+        // Limiter is reused while the rate stays the same
+        let again = limits.get(tenant_a).unwrap();
+        assert!(Arc::ptr_eq(&limiter.0, &again.0));
+
+        limits.set_override(tenant_a, Some(0));
+        assert!(limits.get(tenant_a).is_none());
+
+        limits.set_override(tenant_a, None);
+        assert!(limits.get(tenant_a).is_none());
+    }
+}
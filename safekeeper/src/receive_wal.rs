@@ -517,7 +517,20 @@ async fn run(&mut self) -> anyhow::Result<()> {
                 // Note: this will need to be rewritten if we want to read non-AppendRequest messages here.
                 // Otherwise, we might end up in a situation where we read a message, but don't
                 // process it.
+                //
+                // If the tenant ingests WAL faster than allowed, we sleep before writing each
+                // message. This holds back the AppendResponse, so the compute sees its flush
+                // position advance at the permitted rate, and msg_rx fills up and pushes back
+                // on the network. Throttling per message rather than per batch keeps the
+                // keepalive replies going while throttled.
+                let ingest_limiter = match self.conn_id {
+                    Some(_) => GlobalTimelines::get_ingest_limiter(self.tli.ttid.tenant_id),
+                    None => None, // don't throttle recovery
+                };
                 while let ProposerAcceptorMessage::AppendRequest(append_request) = next_msg {
+                    if let Some(limiter) = &ingest_limiter {
+                        limiter.throttle(append_request.wal_data.len()).await;
+                    }
                     let noflush_msg = ProposerAcceptorMessage::NoFlushAppendRequest(append_request);
 
                     if let Some(reply) = self.tli.process_msg(&noflush_msg).await? {
@@ -1,10 +1,12 @@
 //! This module implements pulling WAL from peer safekeepers if compute can't
 //! provide it, i.e. safekeeper lags too much.
 
+use std::cmp::min;
 use std::time::SystemTime;
 use std::{fmt, pin::pin};
 
 use anyhow::{bail, Context};
+use bytes::{Bytes, BytesMut};
 use futures::StreamExt;
 use postgres_protocol::message::backend::ReplicationMessage;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
@@ -17,7 +19,11 @@
 use tokio_postgres::replication::ReplicationStream;
 use tokio_postgres::types::PgLsn;
 use tracing::*;
-use utils::{id::NodeId, lsn::Lsn, postgres_client::wal_stream_connection_config};
+use utils::{
+    id::{NodeId, TenantTimelineId},
+    lsn::Lsn,
+    postgres_client::wal_stream_connection_config,
+};
 
 use crate::receive_wal::{WalAcceptor, REPLY_QUEUE_SIZE};
 use crate::safekeeper::{AppendRequest, AppendRequestHeader};
@@ -392,6 +398,73 @@ async fn recovery_stream(
     }
 }
 
+/// Fetch committed WAL `from_lsn`..`until_lsn` from the donor, used to repair
+/// local WAL which is found corrupted. Unlike recovery, no term is passed, so
+/// the donor streams only committed WAL.
+pub async fn fetch_committed_wal(
+    ttid: TenantTimelineId,
+    donor: &Donor,
+    from_lsn: Lsn,
+    until_lsn: Lsn,
+    conf: &SafeKeeperConf,
+) -> anyhow::Result<Bytes> {
+    let auth_token = conf.sk_auth_token.as_ref().map(|t| t.get_contents());
+    let cfg = wal_stream_connection_config(ttid, &donor.pg_connstr, auth_token, None)?;
+    let mut cfg = cfg.to_tokio_postgres_config();
+    cfg.application_name(&format!("safekeeper_{}", conf.my_id));
+    cfg.replication_mode(tokio_postgres::config::ReplicationMode::Physical);
+
+    let connect_timeout = Duration::from_millis(10000);
+    let (client, connection) = match time::timeout(connect_timeout, cfg.connect(postgres::NoTls))
+        .await
+    {
+        Ok(client_and_conn) => client_and_conn?,
+        Err(_elapsed) => {
+            bail!("timed out while waiting {connect_timeout:?} for connection to peer safekeeper to open");
+        }
+    };
+    tokio::spawn(async move {
+        if let Err(e) = connection
+            .instrument(info_span!("WAL fetch connection poll", ttid = %ttid))
+            .await
+        {
+            trace!(
+                "tokio_postgres connection object finished with error: {}",
+                e
+            );
+        }
+    });
+
+    let query = format!("START_REPLICATION PHYSICAL {}", from_lsn);
+    let copy_stream = client.copy_both_simple(&query).await?;
+    let mut physical_stream = pin!(ReplicationStream::new(copy_stream));
+
+    let no_data_timeout = Duration::from_millis(30000);
+    let mut buf = BytesMut::with_capacity((until_lsn.0 - from_lsn.0) as usize);
+    while from_lsn + (buf.len() as u64) < until_lsn {
+        let msg = match timeout(no_data_timeout, physical_stream.next()).await {
+            Ok(Some(msg)) => msg.context("get replication message")?,
+            Ok(None) => bail!("unexpected end of replication stream"),
+            Err(_) => bail!("no message received within {:?}", no_data_timeout),
+        };
+        if let ReplicationMessage::XLogData(xlog_data) = msg {
+            let expected_lsn = from_lsn + buf.len() as u64;
+            if Lsn(xlog_data.wal_start()) != expected_lsn {
+                bail!(
+                    "unexpected WAL start {}, expected {}",
+                    Lsn(xlog_data.wal_start()),
+                    expected_lsn
+                );
+            }
+            let wanted = (until_lsn.0 - expected_lsn.0) as usize;
+            let data = xlog_data.data();
+            buf.extend_from_slice(&data[..min(wanted, data.len())]);
+        }
+    }
+    // Dropping the stream makes tokio_postgres send CopyFail to the donor.
+    Ok(buf.freeze())
+}
+
 // Perform network part of streaming: read data and push it to msg_tx, send KA
 // to make sender hear from us. If there is nothing coming for a while, check
 // for termination.
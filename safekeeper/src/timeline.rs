@@ -40,9 +40,11 @@
 use crate::timelines_set::TimelinesSet;
 use crate::wal_backup::{self, remote_timeline_path};
 use crate::wal_backup_partial::PartialRemoteSegment;
+use crate::wal_backup_retention::{self, RetentionPolicy};
 use crate::{control_file, safekeeper::UNKNOWN_SERVER_VERSION};
 
 use crate::metrics::{FullTimelineInfo, WalStorageMetrics, MISC_OPERATION_SECONDS};
+use crate::wal_cache::WalCache;
 use crate::wal_storage::{Storage as wal_storage_iface, WalReader};
 use crate::{debug_dump, timeline_manager, wal_storage};
 use crate::{GlobalTimelines, SafeKeeperConf};
@@ -501,6 +503,8 @@ pub struct Timeline {
     walreceivers: Arc<WalReceivers>,
     timeline_dir: Utf8PathBuf,
     manager_ctl: ManagerCtl,
+    /// WAL read by walsenders, shared between them. None if disabled.
+    wal_cache: Option<Arc<WalCache>>,
 
     /// Delete/cancel will trigger this, background tasks should drop out as soon as it fires
     pub(crate) cancel: CancellationToken,
@@ -543,6 +547,7 @@ pub fn load_timeline(conf: &SafeKeeperConf, ttid: TenantTimelineId) -> Result<Ti
             cancel: CancellationToken::default(),
             timeline_dir: get_timeline_dir(conf, &ttid),
             manager_ctl: ManagerCtl::new(),
+            wal_cache: WalCache::new(conf),
             broker_active: AtomicBool::new(false),
             wal_backup_active: AtomicBool::new(false),
             last_removed_segno: AtomicU64::new(0),
@@ -583,6 +588,7 @@ pub fn create_empty(
             cancel: CancellationToken::default(),
             timeline_dir: get_timeline_dir(conf, &ttid),
             manager_ctl: ManagerCtl::new(),
+            wal_cache: WalCache::new(conf),
             broker_active: AtomicBool::new(false),
             wal_backup_active: AtomicBool::new(false),
             last_removed_segno: AtomicU64::new(0),
@@ -682,7 +688,12 @@ pub async fn delete(
             // Note: we concurrently delete remote storage data from multiple
             // safekeepers. That's ok, s3 replies 200 if object doesn't exist and we
             // do some retries anyway.
-            wal_backup::delete_timeline(&self.ttid).await?;
+            match RetentionPolicy::from_conf(&conf) {
+                Some(policy) => {
+                    wal_backup_retention::delete_timeline(&self.ttid, &policy, &conf).await?
+                }
+                None => wal_backup::delete_timeline(&self.ttid).await?,
+            }
         }
         let dir_existed = delete_dir(&self.timeline_dir).await?;
         Ok(dir_existed)
@@ -1006,6 +1017,16 @@ pub async fn process_msg(
     }
 
     pub async fn get_walreader(&self, start_lsn: Lsn) -> Result<WalReader> {
+        let reader = self.get_uncached_walreader(start_lsn).await?;
+        Ok(match &self.wal_cache {
+            Some(cache) => reader.with_cache(cache.clone(), self.commit_lsn_watch_rx.clone()),
+            None => reader,
+        })
+    }
+
+    /// Get WAL reader bypassing the WAL read cache, i.e. always reading WAL
+    /// from disk (or remote storage).
+    pub async fn get_uncached_walreader(&self, start_lsn: Lsn) -> Result<WalReader> {
         let (_, persisted_state) = self.get_state().await;
         let enable_remote_read = GlobalTimelines::get_global_config().is_wal_backup_enabled();
 
@@ -1018,6 +1039,29 @@ pub async fn get_walreader(&self, start_lsn: Lsn) -> Result<WalReader> {
         )
     }
 
+    /// Overwrite committed WAL at `pos` with `buf` fetched from a peer, after
+    /// local WAL was found to be corrupted.
+    pub async fn repair_wal(&self, pos: Lsn, buf: &[u8]) -> Result<()> {
+        let mut shared_state = self.write_shared_state().await;
+        let commit_lsn = shared_state.sk.state().inmem.commit_lsn;
+        if pos + buf.len() as u64 > commit_lsn {
+            bail!(
+                "refusing to repair WAL {}-{} beyond commit_lsn {}",
+                pos,
+                pos + buf.len() as u64,
+                commit_lsn
+            );
+        }
+        match &mut shared_state.sk {
+            StateSK::Loaded(sk) => sk.wal_store.repair_wal(pos, buf).await?,
+            _ => bail!("cannot repair WAL of offloaded timeline"),
+        }
+        if let Some(cache) = &self.wal_cache {
+            cache.clear();
+        }
+        Ok(())
+    }
+
     pub fn get_timeline_dir(&self) -> Utf8PathBuf {
         self.timeline_dir.clone()
     }
@@ -3,7 +3,7 @@
 //! all from the disk on startup and keeping them in memory.
 
 use crate::defaults::DEFAULT_EVICTION_CONCURRENCY;
-use crate::rate_limit::RateLimiter;
+use crate::rate_limit::{IngestLimiter, RateLimiter, WalIngestLimits};
 use crate::safekeeper::ServerInfo;
 use crate::timeline::{get_tenant_dir, get_timeline_dir, Timeline, TimelineError};
 use crate::timelines_set::TimelinesSet;
@@ -33,6 +33,7 @@ struct GlobalTimelinesState {
     broker_active_set: Arc<TimelinesSet>,
     load_lock: Arc<tokio::sync::Mutex<TimelineLoadLock>>,
     global_rate_limiter: RateLimiter,
+    ingest_limits: WalIngestLimits,
 }
 
 // Used to prevent concurrent timeline loading.
@@ -87,6 +88,7 @@ fn delete(&mut self, ttid: TenantTimelineId) {
         broker_active_set: Arc::new(TimelinesSet::default()),
         load_lock: Arc::new(tokio::sync::Mutex::new(TimelineLoadLock)),
         global_rate_limiter: RateLimiter::new(1, 1),
+        ingest_limits: WalIngestLimits::new(0, 0),
     })
 });
 
@@ -104,6 +106,10 @@ pub async fn init(conf: SafeKeeperConf) -> Result<()> {
                 conf.partial_backup_concurrency,
                 DEFAULT_EVICTION_CONCURRENCY,
             );
+            state.ingest_limits = WalIngestLimits::new(
+                conf.wal_ingest_rate_limit_bytes,
+                conf.wal_ingest_burst_bytes,
+            );
             state.conf = Some(conf);
 
             // Iterate through all directories and load tenants for all directories
@@ -465,6 +471,12 @@ pub async fn delete_force_all_for_tenant(
             return Err(e);
         }
 
+        TIMELINES_STATE
+            .lock()
+            .unwrap()
+            .ingest_limits
+            .remove(tenant_id);
+
         // There may be broken timelines on disk, so delete the whole tenant dir as well.
         // Note that we could concurrently create new timelines while we were deleting them,
         // so the directory may be not empty. In this case timelines will have bad state
@@ -477,6 +489,21 @@ pub async fn delete_force_all_for_tenant(
         Ok(deleted)
     }
 
+    /// Get the WAL ingest limiter of the tenant, or None if its ingest is not limited.
+    pub fn get_ingest_limiter(tenant_id: TenantId) -> Option<IngestLimiter> {
+        TIMELINES_STATE.lock().unwrap().ingest_limits.get(tenant_id)
+    }
+
+    /// Override the WAL ingest rate limit of the tenant, or reset it to the default with None.
+    /// The override is kept in memory only, so it must be set again after restart.
+    pub fn set_ingest_rate_limit(tenant_id: TenantId, bytes_per_sec: Option<u64>) {
+        TIMELINES_STATE
+            .lock()
+            .unwrap()
+            .ingest_limits
+            .set_override(tenant_id, bytes_per_sec);
+    }
+
     pub fn housekeeping(tombstone_ttl: &Duration) {
         let mut state = TIMELINES_STATE.lock().unwrap();
 
@@ -18,7 +18,7 @@
 use postgres_ffi::XLogFileName;
 use postgres_ffi::{XLogSegNo, PG_TLI};
 use remote_storage::{
-    DownloadOpts, GenericRemoteStorage, ListingMode, RemotePath, StorageMetadata,
+    DownloadOpts, GenericRemoteStorage, ListingMode, ListingObject, RemotePath, StorageMetadata,
 };
 use tokio::fs::File;
 
@@ -123,7 +123,7 @@ async fn shut_down_task(entry: &mut Option<WalBackupTaskHandle>) {
 /// So we deterministically choose among the reasonably caught up candidates.
 /// TODO: take into account failed attempts to deal with hypothetical situation
 /// where s3 is unreachable only for some sks.
-fn determine_offloader(
+pub(crate) fn determine_offloader(
     alive_peers: &[PeerInfo],
     wal_backup_lsn: Lsn,
     ttid: TenantTimelineId,
@@ -589,7 +589,19 @@ pub async fn delete_timeline(ttid: &TenantTimelineId) -> Result<()> {
     Ok(())
 }
 
-/// Used by wal_backup_partial.
+/// List all objects of the timeline in remote storage. Remote storage must be
+/// configured when called.
+pub async fn list_timeline_objects(ttid: &TenantTimelineId) -> Result<Vec<ListingObject>> {
+    let cancel = CancellationToken::new(); // not really used
+    let storage = get_configured_remote_storage();
+    let remote_path = remote_timeline_path(ttid)?;
+    let listing = storage
+        .list(Some(&remote_path), ListingMode::NoDelimiter, None, &cancel)
+        .await?;
+    Ok(listing.keys)
+}
+
+/// Used by wal_backup_partial and wal_backup_retention.
 pub async fn delete_objects(paths: &[RemotePath]) -> Result<()> {
     let cancel = CancellationToken::new(); // not really used
     let storage = get_configured_remote_storage();
@@ -0,0 +1,395 @@
+//! Retention of WAL archived in remote storage.
+//!
+//! Without a retention policy, segments offloaded by wal_backup stay in remote
+//! storage until the timeline is deleted. A policy keeps archived WAL for the
+//! configured time and/or up to the configured size of the newest WAL, so that
+//! restoring to any point in this window is possible from the archive alone,
+//! regardless of pageserver progress. WAL within the window is never deleted,
+//! not even together with the timeline.
+//!
+//! A background pass applies the window: segments of live timelines outside of
+//! it are deleted once neither the pageserver nor peer safekeepers need them,
+//! by the safekeeper elected to offload WAL of the timeline. Deleted timelines
+//! which still have WAL in the window are remembered in a file in the workdir,
+//! and their remaining WAL is deleted as it falls out of the window.
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use camino::Utf8PathBuf;
+use once_cell::sync::Lazy;
+use postgres_ffi::v14::xlog_utils::IsXLogFileName;
+use postgres_ffi::{XLogFileName, PG_TLI};
+use remote_storage::{ListingObject, RemotePath, MAX_KEYS_PER_DELETE};
+use tokio::sync::Mutex;
+use tracing::*;
+use utils::crashsafe::durable_rename;
+use utils::id::TenantTimelineId;
+use utils::lsn::Lsn;
+
+use crate::metrics::{
+    WAL_ARCHIVE_RETAINED_BYTES, WAL_ARCHIVE_RETAINED_SEGMENTS,
+    WAL_ARCHIVE_RETENTION_DELETED_SEGMENTS,
+};
+use crate::timeline::Timeline;
+use crate::wal_backup;
+use crate::{GlobalTimelines, SafeKeeperConf};
+
+/// How often archived WAL of timelines is checked against the policy.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Deleted timelines with WAL kept in remote storage, one per line.
+const RETAINED_TIMELINES_FILE_NAME: &str = "wal_archive_retained";
+
+/// Serializes updates of the retained timelines file by timeline deletions and
+/// the retention pass.
+static RETAINED_TIMELINES_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Archived object is kept if it satisfies any of the limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Keep objects archived within this period.
+    pub period: Option<Duration>,
+    /// Keep this many bytes of the newest objects.
+    pub max_bytes: Option<u64>,
+}
+
+/// Result of applying the policy to objects of a timeline.
+#[derive(Debug, Default)]
+pub struct Selection {
+    /// Objects to delete, in the order of their names.
+    pub to_delete: Vec<RemotePath>,
+    pub retained_objects: u64,
+    pub retained_bytes: u64,
+}
+
+impl RetentionPolicy {
+    /// Get the policy from the config, None if it is not configured.
+    pub fn from_conf(conf: &SafeKeeperConf) -> Option<RetentionPolicy> {
+        if conf.wal_archive_retention_period.is_none() && conf.wal_archive_retention_bytes.is_none()
+        {
+            return None;
+        }
+        Some(RetentionPolicy {
+            period: conf.wal_archive_retention_period,
+            max_bytes: conf.wal_archive_retention_bytes,
+        })
+    }
+
+    /// Select objects of a timeline which are not protected by the policy.
+    /// Objects named `horizon` or later are still needed and are kept
+    /// regardless of it.
+    pub fn select_for_deletion(
+        &self,
+        mut objects: Vec<ListingObject>,
+        horizon: Option<&str>,
+        now: SystemTime,
+    ) -> Selection {
+        // Segment names are fixed width hex, so they sort by LSN, and the size
+        // limit keeps the newest WAL, so go from it.
+        objects.sort_by(|a, b| b.key.cmp(&a.key));
+
+        let mut selection = Selection::default();
+        let mut window_bytes = 0;
+        for object in objects {
+            window_bytes += object.size;
+            let within_size = self.max_bytes.is_some_and(|max| window_bytes <= max);
+            let within_period = self.period.is_some_and(|period| {
+                now.duration_since(object.last_modified).unwrap_or_default() < period
+            });
+            let needed =
+                horizon.is_some_and(|horizon| object.key.object_name().unwrap_or("") >= horizon);
+            if within_size || within_period || needed {
+                selection.retained_objects += 1;
+                selection.retained_bytes += object.size;
+            } else {
+                selection.to_delete.push(object.key);
+            }
+        }
+        selection.to_delete.reverse();
+        selection
+    }
+}
+
+pub async fn task_main(conf: SafeKeeperConf, policy: RetentionPolicy) -> Result<()> {
+    info!("started, policy {:?}", policy);
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        let mut retained_objects = 0;
+        let mut retained_bytes = 0;
+        for tli in GlobalTimelines::get_all() {
+            let ttid = tli.ttid;
+            match enforce_retention(&tli, &policy, &conf)
+                .instrument(info_span!("timeline", %ttid))
+                .await
+            {
+                Ok(selection) => {
+                    retained_objects += selection.retained_objects;
+                    retained_bytes += selection.retained_bytes;
+                }
+                Err(e) => warn!("failed to apply WAL archive retention: {:#}", e),
+            }
+        }
+
+        for ttid in load_retained_timelines(&conf).await {
+            match expire_deleted_timeline(&ttid, &policy, &conf)
+                .instrument(info_span!("deleted_timeline", %ttid))
+                .await
+            {
+                Ok(selection) => {
+                    retained_objects += selection.retained_objects;
+                    retained_bytes += selection.retained_bytes;
+                }
+                Err(e) => warn!("failed to apply WAL archive retention: {:#}", e),
+            }
+        }
+
+        WAL_ARCHIVE_RETAINED_SEGMENTS.set(retained_objects as i64);
+        WAL_ARCHIVE_RETAINED_BYTES.set(retained_bytes as i64);
+    }
+}
+
+/// Delete archived segments of the timeline which are out of the retention
+/// window and not needed anymore.
+async fn enforce_retention(
+    tli: &Arc<Timeline>,
+    policy: &RetentionPolicy,
+    conf: &SafeKeeperConf,
+) -> Result<Selection> {
+    let (offloader, horizon_lsn, wal_seg_size) = {
+        let ss = tli.read_shared_state().await;
+        let state = ss.sk.state();
+        let peers = ss.get_peers(conf.heartbeat_timeout);
+        let (offloader, _) =
+            wal_backup::determine_offloader(&peers, state.inmem.backup_lsn, tli.ttid, conf);
+
+        // WAL is needed by the pageserver since remote_consistent_lsn and by
+        // lagging peers which might recover from the archive. Segments not yet
+        // uploaded are not in remote storage anyway.
+        let mut horizon_lsn = std::cmp::min(state.remote_consistent_lsn, state.backup_lsn);
+        for peer in &peers {
+            horizon_lsn = std::cmp::min(horizon_lsn, peer.flush_lsn);
+        }
+        (offloader, horizon_lsn, ss.get_wal_seg_size())
+    };
+    if offloader != Some(conf.my_id) || horizon_lsn == Lsn::INVALID || wal_seg_size == 0 {
+        return Ok(Selection::default());
+    }
+
+    // Skip partial segments, they are managed by wal_backup_partial.
+    let objects = wal_backup::list_timeline_objects(&tli.ttid)
+        .await?
+        .into_iter()
+        .filter(|o| {
+            o.key
+                .object_name()
+                .map(OsStr::new)
+                .is_some_and(IsXLogFileName)
+        })
+        .collect();
+
+    // Segments before the one containing horizon_lsn are not needed.
+    let horizon = XLogFileName(
+        PG_TLI,
+        horizon_lsn.segment_number(wal_seg_size),
+        wal_seg_size,
+    );
+    let selection = policy.select_for_deletion(objects, Some(&horizon), SystemTime::now());
+    delete_selected(&selection).await?;
+    Ok(selection)
+}
+
+/// Delete WAL of a deleted timeline which fell out of the retention window,
+/// forgetting about the timeline once nothing is left.
+async fn expire_deleted_timeline(
+    ttid: &TenantTimelineId,
+    policy: &RetentionPolicy,
+    conf: &SafeKeeperConf,
+) -> Result<Selection> {
+    let objects = wal_backup::list_timeline_objects(ttid).await?;
+    let selection = policy.select_for_deletion(objects, None, SystemTime::now());
+    delete_selected(&selection).await?;
+    if selection.retained_objects == 0 {
+        info!("all archived WAL expired");
+        update_retained_timelines(conf, |ttids| ttids.remove(ttid)).await?;
+    }
+    Ok(selection)
+}
+
+/// Delete WAL of the timeline which is out of the retention window, as part of
+/// the timeline deletion. If some WAL is kept, the timeline is remembered so
+/// that the background pass deletes it later. Remote storage must be
+/// configured when called.
+pub async fn delete_timeline(
+    ttid: &TenantTimelineId,
+    policy: &RetentionPolicy,
+    conf: &SafeKeeperConf,
+) -> Result<()> {
+    // Remember the timeline first: if deletion fails midway, WAL which is
+    // left is still expired eventually.
+    update_retained_timelines(conf, |ttids| ttids.insert(*ttid)).await?;
+    expire_deleted_timeline(ttid, policy, conf).await?;
+    Ok(())
+}
+
+async fn delete_selected(selection: &Selection) -> Result<()> {
+    let to_delete = &selection.to_delete;
+    if to_delete.is_empty() {
+        return Ok(());
+    }
+    info!(
+        "deleting {} archived WAL files [{}-{}] out of retention, keeping {}",
+        to_delete.len(),
+        to_delete.first().unwrap().object_name().unwrap_or(""),
+        to_delete.last().unwrap().object_name().unwrap_or(""),
+        selection.retained_objects
+    );
+    for batch in to_delete.chunks(MAX_KEYS_PER_DELETE) {
+        wal_backup::delete_objects(batch).await?;
+        WAL_ARCHIVE_RETENTION_DELETED_SEGMENTS.inc_by(batch.len() as u64);
+    }
+    Ok(())
+}
+
+fn retained_timelines_path(conf: &SafeKeeperConf) -> Utf8PathBuf {
+    conf.workdir.join(RETAINED_TIMELINES_FILE_NAME)
+}
+
+async fn load_retained_timelines(conf: &SafeKeeperConf) -> BTreeSet<TenantTimelineId> {
+    let _guard = RETAINED_TIMELINES_LOCK.lock().await;
+    read_retained_timelines(conf).await
+}
+
+async fn read_retained_timelines(conf: &SafeKeeperConf) -> BTreeSet<TenantTimelineId> {
+    let path = retained_timelines_path(conf);
+    match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content
+            .lines()
+            .filter_map(|line| match line.parse() {
+                Ok(ttid) => Some(ttid),
+                Err(e) => {
+                    warn!("failed to parse {:?} in {}: {:?}", line, path, e);
+                    None
+                }
+            })
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+        Err(e) => {
+            warn!("failed to read {}: {}", path, e);
+            BTreeSet::new()
+        }
+    }
+}
+
+/// Apply `f` to the set of deleted timelines with retained WAL, persisting it
+/// if `f` changed it.
+async fn update_retained_timelines(
+    conf: &SafeKeeperConf,
+    f: impl FnOnce(&mut BTreeSet<TenantTimelineId>) -> bool,
+) -> Result<()> {
+    let _guard = RETAINED_TIMELINES_LOCK.lock().await;
+    let mut ttids = read_retained_timelines(conf).await;
+    if !f(&mut ttids) {
+        return Ok(());
+    }
+    let path = retained_timelines_path(conf);
+    let tmp_path = path.with_extension("tmp");
+    let content: String = ttids.iter().map(|ttid| format!("{}\n", ttid)).collect();
+    tokio::fs::write(&tmp_path, content).await?;
+    durable_rename(&tmp_path, &path, !conf.no_sync).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEG_SIZE: u64 = 16 * 1024 * 1024;
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    /// Segments 1..=10, segment N archived N hours after the epoch.
+    fn objects() -> Vec<ListingObject> {
+        (1..=10)
+            .map(|segno| ListingObject {
+                key: RemotePath::from_string(&format!("tenant/timeline/{:024X}", segno)).unwrap(),
+                last_modified: SystemTime::UNIX_EPOCH + HOUR * segno as u32,
+                size: SEG_SIZE,
+            })
+            .collect()
+    }
+
+    fn deleted(policy: RetentionPolicy, horizon_segno: Option<u64>) -> Vec<String> {
+        let now = SystemTime::UNIX_EPOCH + HOUR * 10;
+        let horizon = horizon_segno.map(|segno| format!("{:024X}", segno));
+        let selection = policy.select_for_deletion(objects(), horizon.as_deref(), now);
+        assert_eq!(
+            selection.retained_objects as usize + selection.to_delete.len(),
+            10
+        );
+        assert_eq!(
+            selection.retained_bytes,
+            selection.retained_objects * SEG_SIZE
+        );
+        selection
+            .to_delete
+            .iter()
+            .map(|p| p.object_name().unwrap().to_owned())
+            .collect()
+    }
+
+    fn names(segnos: std::ops::RangeInclusive<u64>) -> Vec<String> {
+        segnos.map(|segno| format!("{:024X}", segno)).collect()
+    }
+
+    #[test]
+    fn retention_by_size() {
+        let policy = RetentionPolicy {
+            period: None,
+            max_bytes: Some(3 * SEG_SIZE),
+        };
+        assert_eq!(deleted(policy, None), names(1..=7));
+        // Segments still needed are kept regardless of the policy.
+        assert_eq!(deleted(policy, Some(5)), names(1..=4));
+    }
+
+    #[test]
+    fn retention_by_period() {
+        let policy = RetentionPolicy {
+            period: Some(HOUR * 4),
+            max_bytes: None,
+        };
+        // Segments archived within last 4 hours are 7..=10.
+        assert_eq!(deleted(policy, None), names(1..=6));
+    }
+
+    #[test]
+    fn retention_keeps_union() {
+        let policy = RetentionPolicy {
+            period: Some(HOUR * 2),
+            max_bytes: Some(5 * SEG_SIZE),
+        };
+        assert_eq!(deleted(policy, None), names(1..=5));
+    }
+
+    #[tokio::test]
+    async fn retained_timelines_roundtrip() {
+        let dir = camino_tempfile::tempdir().unwrap();
+        let conf = SafeKeeperConf {
+            workdir: dir.path().to_owned(),
+            ..SafeKeeperConf::dummy()
+        };
+        let ttid = TenantTimelineId::generate();
+        update_retained_timelines(&conf, |ttids| ttids.insert(ttid))
+            .await
+            .unwrap();
+        assert_eq!(load_retained_timelines(&conf).await, BTreeSet::from([ttid]));
+        update_retained_timelines(&conf, |ttids| ttids.remove(&ttid))
+            .await
+            .unwrap();
+        assert!(load_retained_timelines(&conf).await.is_empty());
+    }
+}
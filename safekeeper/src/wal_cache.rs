@@ -0,0 +1,363 @@
+//! Cache of WAL read by walsenders, shared by all readers of a timeline.
+//!
+//! With sharded tenants, pageserver of every shard streams the same WAL from
+//! the safekeeper, and when they lag, each of them would read it from disk (or
+//! remote storage) separately. The cache keeps recently read chunks of WAL, so
+//! that readers at similar positions read it only once.
+//!
+//! Only committed WAL is cached, as it is never truncated or overwritten.
+//! Memory is bounded per timeline, by evicting its oldest chunks, and
+//! globally, by evicting the oldest chunks of any timeline, so that idle
+//! timelines do not keep active ones from caching.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use anyhow::Result;
+use bytes::Bytes;
+use once_cell::sync::Lazy;
+use tokio::sync::OnceCell;
+use utils::lsn::Lsn;
+
+use crate::metrics::{WAL_READ_CACHE_BYTES, WAL_READ_CACHE_HITS, WAL_READ_CACHE_MISSES};
+use crate::SafeKeeperConf;
+
+/// WAL is cached in chunks of this size, aligned to it. Segment size is always
+/// a multiple of it.
+pub const WAL_CACHE_CHUNK_SIZE: usize = 128 * 1024;
+
+/// Stale entries allowed in [`SharedState::order`] on top of one per cached chunk,
+/// before it is compacted.
+const ORDER_COMPACTION_SLACK: usize = 1024;
+
+/// Chunk is inserted before it is loaded, so that concurrent readers of it
+/// wait for a single load.
+type Chunk = Arc<OnceCell<Bytes>>;
+
+/// State shared by the caches of all timelines.
+#[derive(Default)]
+struct SharedState {
+    /// Bytes cached across all timelines.
+    bytes: AtomicUsize,
+    /// Chunks cached across all timelines, loaded or not.
+    chunks: AtomicUsize,
+    /// Chunks of all timelines in the order of insertion, oldest first. Chunks
+    /// evicted by their own timeline leave stale entries behind, which are
+    /// skipped.
+    ///
+    /// Lock order: this lock is taken before the lock of any timeline's cache.
+    order: Mutex<VecDeque<OrderEntry>>,
+}
+
+static SHARED: Lazy<Arc<SharedState>> = Lazy::new(Default::default);
+
+struct OrderEntry {
+    cache: Weak<Mutex<WalCacheInner>>,
+    chunk_start: Lsn,
+    chunk: Weak<OnceCell<Bytes>>,
+}
+
+impl OrderEntry {
+    /// Run `f` on the timeline's cache, if the chunk is still cached in it.
+    fn with_cached<R>(&self, f: impl FnOnce(&mut WalCacheInner) -> R) -> Option<R> {
+        let cache = self.cache.upgrade()?;
+        let mut inner = cache.lock().unwrap();
+        let (cached, _) = inner.chunks.get(&self.chunk_start)?;
+        if Arc::as_ptr(cached) != self.chunk.as_ptr() {
+            return None;
+        }
+        Some(f(&mut inner))
+    }
+}
+
+pub struct WalCache {
+    max_chunks: usize,
+    max_total_bytes: usize,
+    shared: Arc<SharedState>,
+    inner: Arc<Mutex<WalCacheInner>>,
+}
+
+struct WalCacheInner {
+    /// Chunks by start LSN, along with the number of bytes accounted for them.
+    chunks: HashMap<Lsn, (Chunk, usize)>,
+    /// Start LSNs of chunks in the order of insertion, oldest first.
+    order: VecDeque<Lsn>,
+    /// Bytes accounted in the shared state by this cache.
+    bytes: usize,
+    shared: Arc<SharedState>,
+}
+
+impl WalCache {
+    /// Create the cache for a timeline, or None if caching is disabled.
+    pub fn new(conf: &SafeKeeperConf) -> Option<Arc<WalCache>> {
+        let max_chunks = conf.wal_read_cache_timeline_bytes / WAL_CACHE_CHUNK_SIZE;
+        if max_chunks == 0 {
+            return None;
+        }
+        Some(Arc::new(WalCache::with_shared(
+            max_chunks,
+            conf.wal_read_cache_total_bytes,
+            SHARED.clone(),
+        )))
+    }
+
+    fn with_shared(max_chunks: usize, max_total_bytes: usize, shared: Arc<SharedState>) -> Self {
+        WalCache {
+            max_chunks,
+            max_total_bytes,
+            inner: Arc::new(Mutex::new(WalCacheInner::new(shared.clone()))),
+            shared,
+        }
+    }
+
+    /// Get the chunk starting at `chunk_start`, loading it with `load` if it is
+    /// not cached. The caller must ensure that the chunk is committed WAL.
+    pub async fn get_or_load<F, Fut>(&self, chunk_start: Lsn, load: F) -> Result<Bytes>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Bytes>>,
+    {
+        let cached = self
+            .inner
+            .lock()
+            .unwrap()
+            .chunks
+            .get(&chunk_start)
+            .map(|(cell, _)| cell.clone());
+        let cell = match cached {
+            Some(cell) => Some(cell),
+            None => self.insert(chunk_start),
+        };
+
+        let Some(cell) = cell else {
+            // Global limit is reached, read without caching.
+            WAL_READ_CACHE_MISSES.inc();
+            return load().await;
+        };
+
+        let mut loaded = false;
+        let bytes = cell
+            .get_or_try_init(|| {
+                loaded = true;
+                load()
+            })
+            .await?
+            .clone();
+
+        if loaded {
+            WAL_READ_CACHE_MISSES.inc();
+            self.inner
+                .lock()
+                .unwrap()
+                .account(chunk_start, &cell, bytes.len());
+        } else {
+            WAL_READ_CACHE_HITS.inc();
+        }
+        Ok(bytes)
+    }
+
+    /// Insert a not yet loaded chunk, evicting the oldest chunks of any timeline
+    /// if the global limit is reached. Returns None if no room can be made, as
+    /// the cached memory belongs to chunks still being loaded.
+    fn insert(&self, chunk_start: Lsn) -> Option<Chunk> {
+        let mut order = self.shared.order.lock().unwrap();
+        while self.shared.bytes.load(Ordering::Relaxed) + WAL_CACHE_CHUNK_SIZE
+            > self.max_total_bytes
+        {
+            let oldest = order.pop_front()?;
+            oldest.with_cached(|inner| inner.remove(oldest.chunk_start));
+        }
+
+        let cell = {
+            let mut inner = self.inner.lock().unwrap();
+            // Another reader may have inserted it meanwhile
+            if let Some((cell, _)) = inner.chunks.get(&chunk_start) {
+                return Some(cell.clone());
+            }
+            let cell = Chunk::default();
+            inner.insert(chunk_start, cell.clone(), self.max_chunks);
+            cell
+        };
+
+        order.push_back(OrderEntry {
+            cache: Arc::downgrade(&self.inner),
+            chunk_start,
+            chunk: Arc::downgrade(&cell),
+        });
+        if order.len() > 2 * self.shared.chunks.load(Ordering::Relaxed) + ORDER_COMPACTION_SLACK {
+            order.retain(|entry| entry.with_cached(|_| ()).is_some());
+        }
+        Some(cell)
+    }
+
+    /// Drop all cached chunks, e.g. after cached WAL was rewritten on disk.
+    /// Chunks being loaded concurrently are not accounted then.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let shared = inner.shared.clone();
+        inner.release();
+        *inner = WalCacheInner::new(shared);
+    }
+}
+
+impl Drop for WalCache {
+    fn drop(&mut self) {
+        self.inner.lock().unwrap().release();
+    }
+}
+
+impl WalCacheInner {
+    fn new(shared: Arc<SharedState>) -> Self {
+        WalCacheInner {
+            chunks: HashMap::new(),
+            order: VecDeque::new(),
+            bytes: 0,
+            shared,
+        }
+    }
+
+    /// Insert not yet loaded chunk, evicting the oldest ones if the cache is full.
+    fn insert(&mut self, chunk_start: Lsn, cell: Chunk, max_chunks: usize) {
+        while self.order.len() >= max_chunks {
+            let Some(evicted) = self.order.pop_front() else {
+                break;
+            };
+            if let Some((_, accounted)) = self.chunks.remove(&evicted) {
+                self.unaccount(accounted);
+            }
+        }
+        self.chunks.insert(chunk_start, (cell, 0));
+        self.order.push_back(chunk_start);
+        self.shared.chunks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Evict a chunk on behalf of another timeline.
+    fn remove(&mut self, chunk_start: Lsn) {
+        if let Some((_, accounted)) = self.chunks.remove(&chunk_start) {
+            self.order.retain(|lsn| *lsn != chunk_start);
+            self.unaccount(accounted);
+        }
+    }
+
+    /// Account memory of a loaded chunk, unless it was evicted while loading.
+    fn account(&mut self, chunk_start: Lsn, cell: &Chunk, len: usize) {
+        if let Some((cached, accounted)) = self.chunks.get_mut(&chunk_start) {
+            if Arc::ptr_eq(cached, cell) && *accounted == 0 {
+                *accounted = len;
+                self.bytes += len;
+                self.shared.bytes.fetch_add(len, Ordering::Relaxed);
+                WAL_READ_CACHE_BYTES.add(len as i64);
+            }
+        }
+    }
+
+    /// Release the accounting of a chunk which was removed.
+    fn unaccount(&mut self, accounted: usize) {
+        self.bytes -= accounted;
+        self.shared.bytes.fetch_sub(accounted, Ordering::Relaxed);
+        self.shared.chunks.fetch_sub(1, Ordering::Relaxed);
+        WAL_READ_CACHE_BYTES.sub(accounted as i64);
+    }
+
+    /// Release the accounting of all chunks.
+    fn release(&mut self) {
+        self.shared.bytes.fetch_sub(self.bytes, Ordering::Relaxed);
+        self.shared
+            .chunks
+            .fetch_sub(self.chunks.len(), Ordering::Relaxed);
+        WAL_READ_CACHE_BYTES.sub(self.bytes as i64);
+        self.bytes = 0;
+        self.chunks.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_cache(max_chunks: usize) -> WalCache {
+        WalCache::with_shared(max_chunks, usize::MAX / 2, Arc::default())
+    }
+
+    #[tokio::test]
+    async fn loads_chunk_once() {
+        let cache = test_cache(2);
+        let chunk = Lsn(WAL_CACHE_CHUNK_SIZE as u64);
+
+        let first = cache
+            .get_or_load(chunk, || async { Ok(Bytes::from_static(b"wal")) })
+            .await
+            .unwrap();
+        assert_eq!(&first[..], b"wal");
+
+        let second = cache
+            .get_or_load(chunk, || async { panic!("chunk must be cached") })
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn evicts_oldest_chunk() {
+        let cache = test_cache(2);
+        for i in 0..3u64 {
+            let lsn = Lsn(i * WAL_CACHE_CHUNK_SIZE as u64);
+            cache
+                .get_or_load(lsn, || async { Ok(Bytes::from_static(b"wal")) })
+                .await
+                .unwrap();
+        }
+
+        let inner = cache.inner.lock().unwrap();
+        assert_eq!(inner.chunks.len(), 2);
+        assert!(!inner.chunks.contains_key(&Lsn(0)));
+        assert_eq!(inner.bytes, 6);
+    }
+
+    #[tokio::test]
+    async fn failed_load_is_retried() {
+        let cache = test_cache(2);
+        let res = cache
+            .get_or_load(Lsn(0), || async { anyhow::bail!("read failed") })
+            .await;
+        assert!(res.is_err());
+
+        let bytes = cache
+            .get_or_load(Lsn(0), || async { Ok(Bytes::from_static(b"wal")) })
+            .await
+            .unwrap();
+        assert_eq!(&bytes[..], b"wal");
+    }
+
+    #[tokio::test]
+    async fn evicts_other_timelines_at_global_limit() {
+        let shared = Arc::<SharedState>::default();
+        let idle = WalCache::with_shared(4, 2 * WAL_CACHE_CHUNK_SIZE, shared.clone());
+        let active = WalCache::with_shared(4, 2 * WAL_CACHE_CHUNK_SIZE, shared.clone());
+        let chunk = || async { Ok(Bytes::from(vec![0; WAL_CACHE_CHUNK_SIZE])) };
+
+        idle.get_or_load(Lsn(0), chunk).await.unwrap();
+        active.get_or_load(Lsn(0), chunk).await.unwrap();
+        assert_eq!(
+            shared.bytes.load(Ordering::Relaxed),
+            2 * WAL_CACHE_CHUNK_SIZE
+        );
+
+        // The idle timeline's chunk is the oldest, and makes room for the active one
+        let lsn = Lsn(WAL_CACHE_CHUNK_SIZE as u64);
+        active.get_or_load(lsn, chunk).await.unwrap();
+        assert!(idle.inner.lock().unwrap().chunks.is_empty());
+        assert_eq!(active.inner.lock().unwrap().chunks.len(), 2);
+        assert_eq!(
+            shared.bytes.load(Ordering::Relaxed),
+            2 * WAL_CACHE_CHUNK_SIZE
+        );
+
+        drop(active);
+        assert_eq!(shared.bytes.load(Ordering::Relaxed), 0);
+        assert_eq!(shared.chunks.load(Ordering::Relaxed), 0);
+    }
+}
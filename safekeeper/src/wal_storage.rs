@@ -18,9 +18,11 @@
 use std::future::Future;
 use std::io::{self, SeekFrom};
 use std::pin::Pin;
+use std::sync::Arc;
 use tokio::fs::{self, remove_file, File, OpenOptions};
 use tokio::io::{AsyncRead, AsyncWriteExt};
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::watch;
 use tracing::*;
 use utils::crashsafe::durable_rename;
 
@@ -29,6 +31,7 @@
 };
 use crate::state::TimelinePersistentState;
 use crate::wal_backup::{read_object, remote_timeline_path};
+use crate::wal_cache::{WalCache, WAL_CACHE_CHUNK_SIZE};
 use crate::SafeKeeperConf;
 use postgres_ffi::waldecoder::WalStreamDecoder;
 use postgres_ffi::XLogFileName;
@@ -339,6 +342,47 @@ async fn write_exact(&mut self, pos: Lsn, mut buf: &[u8]) -> Result<()> {
 
         Ok(())
     }
+
+    /// Overwrite already flushed WAL at `pos` with `buf`, which may span
+    /// several segments, after it was found to be corrupted. Unlike regular
+    /// writes, this never creates segments and doesn't move any of the write
+    /// positions, so it fails if the range is not fully on disk.
+    pub async fn repair_wal(&mut self, mut pos: Lsn, mut buf: &[u8]) -> Result<()> {
+        let end_pos = pos + buf.len() as u64;
+        if end_pos > self.flush_record_lsn {
+            bail!(
+                "cannot repair WAL {}-{} beyond flush_lsn {}",
+                pos,
+                end_pos,
+                self.flush_record_lsn
+            );
+        }
+
+        while !buf.is_empty() {
+            let segno = pos.segment_number(self.wal_seg_size);
+            let xlogoff = pos.segment_offset(self.wal_seg_size);
+            let bytes_write = min(self.wal_seg_size - xlogoff, buf.len());
+
+            let (wal_file_path, wal_file_partial_path) =
+                wal_file_paths(&self.timeline_dir, segno, self.wal_seg_size);
+            let mut file = match OpenOptions::new().write(true).open(&wal_file_path).await {
+                Ok(file) => file,
+                Err(_) => OpenOptions::new()
+                    .write(true)
+                    .open(&wal_file_partial_path)
+                    .await
+                    .with_context(|| format!("failed to open WAL file {:#}", wal_file_path))?,
+            };
+            file.seek(SeekFrom::Start(xlogoff as u64)).await?;
+            file.write_all(&buf[..bytes_write]).await?;
+            file.flush().await?;
+            self.fdatasync_file(&file).await?;
+
+            pos += bytes_write as u64;
+            buf = &buf[bytes_write..];
+        }
+        Ok(())
+    }
 }
 
 impl Storage for PhysicalStorage {
@@ -562,12 +606,18 @@ async fn remove_segments_from_disk(
     Ok(())
 }
 
+type WalSegmentReader = Pin<Box<dyn AsyncRead + Send + Sync>>;
+
 pub struct WalReader {
     remote_path: RemotePath,
     timeline_dir: Utf8PathBuf,
     wal_seg_size: usize,
     pos: Lsn,
-    wal_segment: Option<Pin<Box<dyn AsyncRead + Send + Sync>>>,
+    /// Open segment along with the position it points to.
+    wal_segment: Option<(Lsn, WalSegmentReader)>,
+    /// Cache shared with other readers of the timeline, and commit_lsn of the
+    /// timeline, which bounds what may be cached.
+    cache: Option<(Arc<WalCache>, watch::Receiver<Lsn>)>,
 
     // S3 will be used to read WAL if LSN is not available locally
     enable_remote_read: bool,
@@ -618,6 +668,7 @@ pub fn new(
             wal_seg_size: state.server.wal_seg_size as usize,
             pos: start_pos,
             wal_segment: None,
+            cache: None,
             enable_remote_read,
             local_start_lsn: state.local_start_lsn,
             timeline_start_lsn: state.timeline_start_lsn,
@@ -627,6 +678,12 @@ pub fn new(
         })
     }
 
+    /// Read committed WAL through the cache shared with other readers.
+    pub fn with_cache(mut self, cache: Arc<WalCache>, commit_lsn_rx: watch::Receiver<Lsn>) -> Self {
+        self.cache = Some((cache, commit_lsn_rx));
+        self
+    }
+
     /// Read WAL at current position into provided buf, returns number of bytes
     /// read. It can be smaller than buf size only if segment boundary is
     /// reached.
@@ -682,9 +739,13 @@ pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
             return Ok(len);
         }
 
+        if let Some(read) = self.read_cached(buf).await? {
+            return Ok(read);
+        }
+
         let mut wal_segment = match self.wal_segment.take() {
-            Some(reader) => reader,
-            None => self.open_segment().await?,
+            Some((pos, reader)) if pos == self.pos => reader,
+            _ => self.open_segment(self.pos).await?,
         };
 
         // How much to read and send in message? We cannot cross the WAL file
@@ -700,20 +761,76 @@ pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
         // Decide whether to reuse this file. If we don't set wal_segment here
         // a new reader will be opened next time.
         if self.pos.segment_offset(self.wal_seg_size) != 0 {
-            self.wal_segment = Some(wal_segment);
+            self.wal_segment = Some((self.pos, wal_segment));
         }
 
         Ok(send_size)
     }
 
-    /// Open WAL segment at the current position of the reader.
-    async fn open_segment(&self) -> Result<Pin<Box<dyn AsyncRead + Send + Sync>>> {
-        let xlogoff = self.pos.segment_offset(self.wal_seg_size);
-        let segno = self.pos.segment_number(self.wal_seg_size);
+    /// Read WAL at current position through the cache. Returns None if there
+    /// is no cache or the chunk at current position can't be cached.
+    async fn read_cached(&mut self, buf: &mut [u8]) -> Result<Option<usize>> {
+        let Some((cache, commit_lsn_rx)) = &self.cache else {
+            return Ok(None);
+        };
+        let cache = cache.clone();
+
+        let chunk_size = WAL_CACHE_CHUNK_SIZE as u64;
+        let chunk_start = Lsn(self.pos.0 - self.pos.0 % chunk_size);
+        let chunk_end = chunk_start + chunk_size;
+
+        // Only committed WAL never changes. Also, the chunk must not straddle
+        // local_start_lsn, as then the parts of it are read from different
+        // places.
+        let straddles_local_start =
+            chunk_start < self.local_start_lsn && self.local_start_lsn < chunk_end;
+        if chunk_end > *commit_lsn_rx.borrow() || straddles_local_start {
+            return Ok(None);
+        }
+
+        let mut wal_segment = self.wal_segment.take();
+        let chunk = cache
+            .get_or_load(chunk_start, || {
+                self.load_chunk(&mut wal_segment, chunk_start)
+            })
+            .await?;
+        self.wal_segment = wal_segment;
+
+        let offset = (self.pos.0 - chunk_start.0) as usize;
+        let len = min(buf.len(), chunk.len() - offset);
+        buf[..len].copy_from_slice(&chunk[offset..offset + len]);
+        self.pos += len as u64;
+        Ok(Some(len))
+    }
+
+    /// Read the whole chunk starting at `chunk_start`, reusing the open segment
+    /// if it points there.
+    async fn load_chunk(
+        &self,
+        wal_segment: &mut Option<(Lsn, WalSegmentReader)>,
+        chunk_start: Lsn,
+    ) -> Result<Bytes> {
+        let mut reader = match wal_segment.take() {
+            Some((pos, reader)) if pos == chunk_start => reader,
+            _ => self.open_segment(chunk_start).await?,
+        };
+        let mut chunk = vec![0u8; WAL_CACHE_CHUNK_SIZE];
+        reader.read_exact(&mut chunk).await?;
+        let chunk_end = chunk_start + WAL_CACHE_CHUNK_SIZE as u64;
+        if chunk_end.segment_offset(self.wal_seg_size) != 0 {
+            *wal_segment = Some((chunk_end, reader));
+        }
+        Ok(Bytes::from(chunk))
+    }
+
+    /// Open WAL segment at the given position.
+    async fn open_segment(&self, pos: Lsn) -> Result<WalSegmentReader> {
+        let xlogoff = pos.segment_offset(self.wal_seg_size);
+        let segno = pos.segment_number(self.wal_seg_size);
         let wal_file_name = XLogFileName(PG_TLI, segno, self.wal_seg_size);
 
         // Try to open local file, if we may have WAL locally
-        if self.pos >= self.local_start_lsn {
+        if pos >= self.local_start_lsn {
             let res = open_wal_file(&self.timeline_dir, segno, self.wal_seg_size).await;
             match res {
                 Ok((mut file, _)) => {
@@ -0,0 +1,384 @@
+//! Periodic verification of committed WAL against peer safekeepers.
+//!
+//! All safekeepers of a timeline must store identical committed WAL, but disk
+//! corruption could silently break this and be noticed only when the damaged
+//! replica is needed. This task compares digests of committed WAL with peers,
+//! segment by segment. If local WAL differs from the one the majority of
+//! safekeepers agrees on, it is fetched from a healthy peer and overwritten
+//! (only if `wal_verify_repair` is enabled). Divergence which can't be
+//! attributed to a replica is only reported.
+//!
+//! Position up to which WAL is verified is kept in a file in the timeline
+//! directory, so verification resumes from it after restart.
+
+use std::cmp::{max, min};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use camino::Utf8PathBuf;
+use sha2::{Digest, Sha256};
+use tracing::*;
+use utils::crashsafe::durable_rename;
+use utils::id::{NodeId, TenantTimelineId};
+use utils::lsn::Lsn;
+
+use crate::debug_dump::{calculate_digest, TimelineDigestRequest};
+use crate::http::client::Client;
+use crate::metrics::{WAL_VERIFY_BYTES, WAL_VERIFY_DIVERGENCES, WAL_VERIFY_REPAIRS};
+use crate::recovery::{fetch_committed_wal, Donor};
+use crate::timeline::{PeerInfo, StateSK, Timeline, WalResidentTimeline};
+use crate::{GlobalTimelines, SafeKeeperConf};
+
+/// Limit on segments verified per timeline in one round, to spread IO of
+/// catching up with a long timeline over several rounds.
+const MAX_SEGMENTS_PER_ROUND: usize = 16;
+
+/// Name of the file in the timeline directory storing the verified LSN.
+const VERIFIED_LSN_FILE_NAME: &str = "wal_verified_lsn";
+
+pub async fn task_main(conf: SafeKeeperConf, interval: Duration) -> Result<()> {
+    info!("started, interval {:?}", interval);
+    // Position up to which WAL of each timeline is verified.
+    let mut verified: HashMap<TenantTimelineId, Lsn> = HashMap::new();
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let timelines = GlobalTimelines::get_all();
+        let ttids: HashSet<_> = timelines.iter().map(|tli| tli.ttid).collect();
+        verified.retain(|ttid, _| ttids.contains(ttid));
+
+        for tli in timelines {
+            let ttid = tli.ttid;
+            async {
+                if !verified.contains_key(&ttid) {
+                    verified.insert(ttid, load_verified_lsn(&tli).await);
+                }
+                let verified_lsn = verified.get_mut(&ttid).expect("inserted above");
+                let prev_verified_lsn = *verified_lsn;
+                if let Err(e) = verify_timeline(&tli, verified_lsn, &conf).await {
+                    warn!("failed to verify WAL: {:#}", e);
+                }
+                if *verified_lsn > prev_verified_lsn {
+                    if let Err(e) = persist_verified_lsn(&tli, *verified_lsn, &conf).await {
+                        warn!("failed to persist verified LSN: {:#}", e);
+                    }
+                }
+            }
+            .instrument(info_span!("timeline", %ttid))
+            .await
+        }
+    }
+}
+
+fn verified_lsn_path(tli: &Timeline) -> Utf8PathBuf {
+    tli.get_timeline_dir().join(VERIFIED_LSN_FILE_NAME)
+}
+
+/// Read the persisted verified LSN, starting from scratch if there is none.
+async fn load_verified_lsn(tli: &Timeline) -> Lsn {
+    let path = verified_lsn_path(tli);
+    match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content.trim().parse().unwrap_or_else(|e| {
+            warn!("failed to parse {}: {:?}", path, e);
+            Lsn::INVALID
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Lsn::INVALID,
+        Err(e) => {
+            warn!("failed to read {}: {}", path, e);
+            Lsn::INVALID
+        }
+    }
+}
+
+async fn persist_verified_lsn(tli: &Timeline, lsn: Lsn, conf: &SafeKeeperConf) -> Result<()> {
+    let path = verified_lsn_path(tli);
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, lsn.to_string()).await?;
+    durable_rename(&tmp_path, &path, !conf.no_sync).await?;
+    Ok(())
+}
+
+/// Verify committed WAL of the timeline after `verified_lsn`, advancing it.
+async fn verify_timeline(
+    tli: &Arc<Timeline>,
+    verified_lsn: &mut Lsn,
+    conf: &SafeKeeperConf,
+) -> Result<()> {
+    let (mut peers, members, commit_lsn, start_lsn, wal_seg_size) = {
+        let ss = tli.read_shared_state().await;
+        // Don't bring evicted timelines back just to verify them.
+        if !matches!(ss.sk, StateSK::Loaded(_)) {
+            return Ok(());
+        }
+        let state = ss.sk.state();
+        // Majority is counted over all known members of the timeline, not
+        // only the ones which are alive now.
+        let mut members: HashSet<NodeId> = state.peers.0.iter().map(|(id, _)| *id).collect();
+        members.extend(ss.peers_info.0.iter().map(|p| p.sk_id));
+        members.insert(conf.my_id);
+        (
+            ss.get_peers(conf.heartbeat_timeout),
+            members.len(),
+            state.inmem.commit_lsn,
+            max(state.local_start_lsn, state.timeline_start_lsn),
+            ss.get_wal_seg_size(),
+        )
+    };
+    peers.retain(|p| p.sk_id != conf.my_id);
+
+    let mut from_lsn = max(*verified_lsn, start_lsn);
+    if peers.is_empty() || from_lsn >= commit_lsn {
+        return Ok(());
+    }
+    let tli = tli.wal_residence_guard().await?;
+
+    for _ in 0..MAX_SEGMENTS_PER_ROUND {
+        let segment_end = Lsn((from_lsn.segment_number(wal_seg_size) + 1) * wal_seg_size as u64);
+        let until_lsn = min(segment_end, commit_lsn);
+        if from_lsn >= until_lsn {
+            break;
+        }
+        if !verify_range(&tli, &peers, members, from_lsn, until_lsn, conf).await? {
+            break;
+        }
+        WAL_VERIFY_BYTES.inc_by(until_lsn.0 - from_lsn.0);
+        *verified_lsn = until_lsn;
+        from_lsn = until_lsn;
+    }
+    Ok(())
+}
+
+/// Compare local WAL `from_lsn`..`until_lsn` with peers, repairing it if
+/// needed. Returns whether local WAL is known to be good now.
+async fn verify_range(
+    tli: &WalResidentTimeline,
+    peers: &[PeerInfo],
+    members: usize,
+    from_lsn: Lsn,
+    until_lsn: Lsn,
+    conf: &SafeKeeperConf,
+) -> Result<bool> {
+    let request = TimelineDigestRequest {
+        from_lsn,
+        until_lsn,
+    };
+    let local = calculate_digest(tli, request).await?.sha256;
+
+    let mut peer_digests = Vec::new();
+    for peer in peers {
+        // The peer must have committed the range and store it locally.
+        if peer.commit_lsn < until_lsn || peer.local_start_lsn > from_lsn {
+            continue;
+        }
+        let client = Client::new(
+            format!("http://{}", peer.http_connstr),
+            conf.sk_auth_token.clone(),
+        );
+        match client
+            .timeline_digest(
+                tli.ttid.tenant_id,
+                tli.ttid.timeline_id,
+                from_lsn,
+                until_lsn,
+            )
+            .await
+        {
+            Ok(digest) => peer_digests.push((peer.sk_id, digest.sha256)),
+            Err(e) => warn!(
+                "failed to get digest of WAL {}-{} from safekeeper {}: {}",
+                from_lsn, until_lsn, peer.sk_id, e
+            ),
+        }
+    }
+    if peer_digests.is_empty() {
+        // Nothing to compare with yet.
+        return Ok(false);
+    }
+
+    match verdict(&local, &peer_digests, members) {
+        Verdict::Match => Ok(true),
+        Verdict::NoQuorum => {
+            debug!(
+                "too few of {} safekeepers reported WAL {}-{} digest to decide",
+                members, from_lsn, until_lsn
+            );
+            Ok(false)
+        }
+        Verdict::PeersDiverged(diverged) => {
+            // Peers detect and repair this themselves.
+            for sk_id in diverged {
+                WAL_VERIFY_DIVERGENCES.with_label_values(&["peer"]).inc();
+                error!(
+                    "WAL {}-{} of safekeeper {} differs from the majority",
+                    from_lsn, until_lsn, sk_id
+                );
+            }
+            Ok(true)
+        }
+        Verdict::LocalDiverged { healthy, digest } => {
+            WAL_VERIFY_DIVERGENCES.with_label_values(&["local"]).inc();
+            error!(
+                "local WAL {}-{} differs from the majority, digest {} instead of {}",
+                from_lsn, until_lsn, local, digest
+            );
+            if !conf.wal_verify_repair {
+                return Ok(false);
+            }
+            let donor = peers
+                .iter()
+                .find(|p| p.sk_id == healthy)
+                .expect("digest is fetched from a known peer");
+            match repair(tli, donor, from_lsn, until_lsn, &digest, conf).await {
+                Ok(()) => {
+                    WAL_VERIFY_REPAIRS.with_label_values(&["success"]).inc();
+                    info!(
+                        "repaired WAL {}-{} from safekeeper {}",
+                        from_lsn, until_lsn, healthy
+                    );
+                    Ok(true)
+                }
+                Err(e) => {
+                    WAL_VERIFY_REPAIRS.with_label_values(&["failure"]).inc();
+                    Err(e.context(format!("repairing WAL from safekeeper {}", healthy)))
+                }
+            }
+        }
+        Verdict::NoMajority => {
+            WAL_VERIFY_DIVERGENCES.with_label_values(&["unknown"]).inc();
+            error!(
+                "WAL {}-{} differs between safekeepers and there is no majority: local {}, peers {:?}",
+                from_lsn, until_lsn, local, peer_digests
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// Overwrite local WAL `from_lsn`..`until_lsn` with WAL of the donor, which
+/// must have `expected` digest.
+async fn repair(
+    tli: &WalResidentTimeline,
+    donor: &PeerInfo,
+    from_lsn: Lsn,
+    until_lsn: Lsn,
+    expected: &str,
+    conf: &SafeKeeperConf,
+) -> Result<()> {
+    let wal = fetch_committed_wal(tli.ttid, &Donor::from(donor), from_lsn, until_lsn, conf).await?;
+    // Check what we got before overwriting anything.
+    let fetched = hex::encode(Sha256::digest(&wal));
+    if fetched != expected {
+        bail!("fetched WAL has digest {}, expected {}", fetched, expected);
+    }
+
+    tli.repair_wal(from_lsn, &wal).await?;
+
+    let request = TimelineDigestRequest {
+        from_lsn,
+        until_lsn,
+    };
+    let repaired = calculate_digest(tli, request).await?.sha256;
+    if repaired != expected {
+        bail!("WAL still has digest {} after repair", repaired);
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    /// Digests reported by the majority of members are equal.
+    Match,
+    /// Too few members reported digests to pick a majority.
+    NoQuorum,
+    /// Local WAL agrees with the majority, but these peers don't.
+    PeersDiverged(Vec<NodeId>),
+    /// Local WAL differs from the majority, `healthy` is one of the peers in it.
+    LocalDiverged { healthy: NodeId, digest: String },
+    /// Replicas disagree and no digest is shared by the majority of them.
+    NoMajority,
+}
+
+/// Decide which replica is diverged. Digest is considered correct only if it
+/// is shared by the majority of all `members` of the timeline, including us,
+/// whether they reported a digest or not.
+fn verdict(local: &str, peers: &[(NodeId, String)], members: usize) -> Verdict {
+    let members = max(members, peers.len() + 1);
+    let peers_agreeing = |digest: &str| peers.iter().filter(|(_, d)| d == digest).count();
+    if (peers_agreeing(local) + 1) * 2 > members {
+        let diverged: Vec<NodeId> = peers
+            .iter()
+            .filter(|(_, digest)| digest != local)
+            .map(|(sk_id, _)| *sk_id)
+            .collect();
+        if diverged.is_empty() {
+            return Verdict::Match;
+        }
+        return Verdict::PeersDiverged(diverged);
+    }
+    for (sk_id, digest) in peers {
+        if peers_agreeing(digest) * 2 > members {
+            return Verdict::LocalDiverged {
+                healthy: *sk_id,
+                digest: digest.clone(),
+            };
+        }
+    }
+    // Reporting replicas can't outvote the silent ones yet.
+    if peers.iter().all(|(_, d)| d == local) {
+        return Verdict::NoQuorum;
+    }
+    Verdict::NoMajority
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peers(digests: &[(u64, &str)]) -> Vec<(NodeId, String)> {
+        digests
+            .iter()
+            .map(|(id, d)| (NodeId(*id), d.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn wal_verify_verdict() {
+        assert_eq!(
+            verdict("a", &peers(&[(2, "a"), (3, "a")]), 3),
+            Verdict::Match
+        );
+        assert_eq!(
+            verdict("a", &peers(&[(2, "a"), (3, "b")]), 3),
+            Verdict::PeersDiverged(vec![NodeId(3)])
+        );
+        assert_eq!(
+            verdict("b", &peers(&[(2, "a"), (3, "a")]), 3),
+            Verdict::LocalDiverged {
+                healthy: NodeId(2),
+                digest: "a".to_owned()
+            }
+        );
+        // With a single peer it is unknown who is right.
+        assert_eq!(verdict("a", &peers(&[(2, "b")]), 2), Verdict::NoMajority);
+        assert_eq!(
+            verdict("a", &peers(&[(2, "b"), (3, "c")]), 3),
+            Verdict::NoMajority
+        );
+        // Silent members count: two of five can't decide anything.
+        assert_eq!(verdict("a", &peers(&[(2, "a")]), 5), Verdict::NoQuorum);
+        assert_eq!(verdict("a", &peers(&[(2, "b")]), 5), Verdict::NoMajority);
+        assert_eq!(
+            verdict("b", &peers(&[(2, "a"), (3, "a")]), 5),
+            Verdict::NoMajority
+        );
+        assert_eq!(
+            verdict("b", &peers(&[(2, "a"), (3, "a"), (4, "a")]), 5),
+            Verdict::LocalDiverged {
+                healthy: NodeId(2),
+                digest: "a".to_owned()
+            }
+        );
+    }
+}
@@ -188,6 +188,14 @@ pub fn run_server(os: NodeOs, disk: Arc<SafekeeperDisk>) -> Result<()> {
         control_file_save_interval: Duration::from_secs(1),
         partial_backup_concurrency: 1,
         eviction_min_resident: Duration::ZERO,
+        wal_ingest_rate_limit_bytes: 0,
+        wal_ingest_burst_bytes: 0,
+        wal_read_cache_timeline_bytes: 0,
+        wal_read_cache_total_bytes: 0,
+        wal_verify_interval: None,
+        wal_verify_repair: false,
+        wal_archive_retention_period: None,
+        wal_archive_retention_bytes: None,
     };
 
     let mut global = GlobalMap::new(disk, conf.clone())?;
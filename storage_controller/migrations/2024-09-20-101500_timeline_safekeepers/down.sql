@@ -0,0 +1,2 @@
+DROP TABLE timelines;
+ALTER TABLE safekeepers DROP scheduling_policy;
@@ -0,0 +1,14 @@
+ALTER TABLE safekeepers ADD scheduling_policy VARCHAR NOT NULL DEFAULT 'active';
+
+-- Placement of timelines onto safekeepers. `generation` is incremented every time
+-- the set changes, so that concurrent migrations can be detected. While a timeline is
+-- migrated between safekeepers, `migrating_to` is the member of `sk_set` that joined it.
+CREATE TABLE timelines (
+	tenant_id VARCHAR NOT NULL,
+	timeline_id VARCHAR NOT NULL,
+	generation INTEGER NOT NULL,
+	sk_set BIGINT[] NOT NULL,
+	migrating_to BIGINT,
+	PRIMARY KEY(tenant_id, timeline_id)
+);
+CREATE INDEX timelines_sk_set_idx ON timelines USING GIN (sk_set);
@@ -0,0 +1 @@
+ALTER TABLE tenant_shards DROP split_policy;
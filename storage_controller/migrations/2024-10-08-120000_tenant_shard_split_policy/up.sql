@@ -0,0 +1 @@
+ALTER TABLE tenant_shards ADD split_policy VARCHAR NOT NULL DEFAULT '{}';
@@ -0,0 +1,2 @@
+ALTER TABLE nodes DROP labels;
+ALTER TABLE tenant_shards DROP placement_constraints;
@@ -0,0 +1,2 @@
+ALTER TABLE nodes ADD labels VARCHAR NOT NULL DEFAULT '{}';
+ALTER TABLE tenant_shards ADD placement_constraints VARCHAR NOT NULL DEFAULT '{}';
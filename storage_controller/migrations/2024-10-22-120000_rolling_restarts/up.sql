@@ -0,0 +1,8 @@
+-- Rolling restarts of pageservers, so that a new controller can carry on with an operation
+-- started by its predecessor.  `status` is the JSON encoded progress of the operation.
+CREATE TABLE rolling_restarts (
+	id BIGSERIAL PRIMARY KEY,
+	status VARCHAR NOT NULL,
+	started_at TIMESTAMPTZ NOT NULL,
+	updated_at TIMESTAMPTZ NOT NULL
+);
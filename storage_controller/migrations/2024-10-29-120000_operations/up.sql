@@ -0,0 +1,17 @@
+-- Long-running operations, such as shard splits and node drains.  Rows are written when an
+-- operation starts and when it finishes: progress is only persisted at the end.
+CREATE TABLE operations (
+	id BIGSERIAL PRIMARY KEY,
+	kind VARCHAR NOT NULL,
+	state VARCHAR NOT NULL,
+	tenant_id VARCHAR,
+	node_id BIGINT,
+	progress_completed BIGINT,
+	progress_total BIGINT,
+	error VARCHAR,
+	started_at TIMESTAMPTZ NOT NULL,
+	updated_at TIMESTAMPTZ NOT NULL,
+	finished_at TIMESTAMPTZ
+);
+CREATE INDEX operations_tenant_id_idx ON operations (tenant_id);
+CREATE INDEX operations_node_id_idx ON operations (node_id);
@@ -0,0 +1,17 @@
+-- Mutating API calls, with the state of the tenant and node they touched before and after
+-- the call.  Rows are only ever inserted.
+CREATE TABLE audit_log (
+	id BIGSERIAL PRIMARY KEY,
+	recorded_at TIMESTAMPTZ NOT NULL,
+	subject VARCHAR,
+	method VARCHAR NOT NULL,
+	path VARCHAR NOT NULL,
+	tenant_id VARCHAR,
+	node_id BIGINT,
+	status INTEGER NOT NULL,
+	request VARCHAR,
+	state_before VARCHAR,
+	state_after VARCHAR
+);
+CREATE INDEX audit_log_tenant_id_idx ON audit_log (tenant_id);
+CREATE INDEX audit_log_node_id_idx ON audit_log (node_id);
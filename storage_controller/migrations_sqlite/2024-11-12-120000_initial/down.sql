@@ -0,0 +1,9 @@
+DROP TABLE audit_log;
+DROP TABLE operations;
+DROP TABLE rolling_restarts;
+DROP TABLE timelines;
+DROP TABLE safekeepers;
+DROP TABLE controllers;
+DROP TABLE metadata_health;
+DROP TABLE nodes;
+DROP TABLE tenant_shards;
@@ -0,0 +1,110 @@
+-- The schema of the Postgres migrations, for SQLite.  Timestamps are RFC 3339 text in UTC, and
+-- arrays are JSON text: see `persistence::portable`.
+CREATE TABLE tenant_shards (
+	tenant_id VARCHAR NOT NULL,
+	shard_number INTEGER NOT NULL,
+	shard_count INTEGER NOT NULL,
+	shard_stripe_size INTEGER NOT NULL,
+	generation INTEGER,
+	generation_pageserver BIGINT,
+	placement_policy VARCHAR NOT NULL,
+	splitting SMALLINT NOT NULL,
+	-- config is JSON encoded, opaque to the database.
+	config TEXT NOT NULL,
+	scheduling_policy VARCHAR NOT NULL DEFAULT '"Active"',
+	preferred_az_id VARCHAR,
+	split_policy VARCHAR NOT NULL DEFAULT '{}',
+	placement_constraints VARCHAR NOT NULL DEFAULT '{}',
+	PRIMARY KEY(tenant_id, shard_number, shard_count)
+);
+CREATE INDEX tenant_shards_tenant_id ON tenant_shards (tenant_id);
+
+CREATE TABLE nodes (
+	node_id BIGINT PRIMARY KEY NOT NULL,
+	scheduling_policy VARCHAR NOT NULL,
+	listen_http_addr VARCHAR NOT NULL,
+	listen_http_port INTEGER NOT NULL,
+	listen_pg_addr VARCHAR NOT NULL,
+	listen_pg_port INTEGER NOT NULL,
+	availability_zone_id VARCHAR NOT NULL,
+	labels VARCHAR NOT NULL DEFAULT '{}'
+);
+
+CREATE TABLE metadata_health (
+	tenant_id VARCHAR NOT NULL,
+	shard_number INTEGER NOT NULL,
+	shard_count INTEGER NOT NULL,
+	healthy BOOLEAN NOT NULL DEFAULT TRUE,
+	last_scrubbed_at TEXT NOT NULL,
+	PRIMARY KEY(tenant_id, shard_number, shard_count),
+	-- Rely on cascade behavior for delete
+	FOREIGN KEY(tenant_id, shard_number, shard_count)
+		REFERENCES tenant_shards (tenant_id, shard_number, shard_count) ON DELETE CASCADE
+);
+
+CREATE TABLE controllers (
+	address VARCHAR NOT NULL,
+	started_at TEXT NOT NULL,
+	PRIMARY KEY(address, started_at)
+);
+
+CREATE TABLE safekeepers (
+	id BIGINT PRIMARY KEY,
+	region_id TEXT NOT NULL,
+	version BIGINT NOT NULL,
+	host TEXT NOT NULL,
+	port INTEGER NOT NULL,
+	active BOOLEAN NOT NULL DEFAULT false,
+	http_port INTEGER NOT NULL,
+	availability_zone_id TEXT NOT NULL,
+	scheduling_policy VARCHAR NOT NULL DEFAULT 'active'
+);
+
+CREATE TABLE timelines (
+	tenant_id VARCHAR NOT NULL,
+	timeline_id VARCHAR NOT NULL,
+	generation INTEGER NOT NULL,
+	-- JSON array of safekeeper IDs
+	sk_set TEXT NOT NULL,
+	migrating_to BIGINT,
+	PRIMARY KEY(tenant_id, timeline_id)
+);
+
+CREATE TABLE rolling_restarts (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	status VARCHAR NOT NULL,
+	started_at TEXT NOT NULL,
+	updated_at TEXT NOT NULL
+);
+
+CREATE TABLE operations (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	kind VARCHAR NOT NULL,
+	state VARCHAR NOT NULL,
+	tenant_id VARCHAR,
+	node_id BIGINT,
+	progress_completed BIGINT,
+	progress_total BIGINT,
+	error VARCHAR,
+	started_at TEXT NOT NULL,
+	updated_at TEXT NOT NULL,
+	finished_at TEXT
+);
+CREATE INDEX operations_tenant_id_idx ON operations (tenant_id);
+CREATE INDEX operations_node_id_idx ON operations (node_id);
+
+CREATE TABLE audit_log (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	recorded_at TEXT NOT NULL,
+	subject VARCHAR,
+	method VARCHAR NOT NULL,
+	path VARCHAR NOT NULL,
+	tenant_id VARCHAR,
+	node_id BIGINT,
+	status INTEGER NOT NULL,
+	request VARCHAR,
+	state_before VARCHAR,
+	state_after VARCHAR
+);
+CREATE INDEX audit_log_tenant_id_idx ON audit_log (tenant_id);
+CREATE INDEX audit_log_node_id_idx ON audit_log (node_id);
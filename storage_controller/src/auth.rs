@@ -7,3 +7,15 @@ pub fn check_permission(claims: &Claims, required_scope: Scope) -> Result<(), Au
 
     Ok(())
 }
+
+/// Who made a request, as recorded in the audit log: the subject of the token, or its scope
+/// for tokens issued without one.
+pub fn subject(claims: &Claims) -> String {
+    match &claims.sub {
+        Some(sub) => sub.clone(),
+        None => match serde_json::to_value(claims.scope) {
+            Ok(serde_json::Value::String(scope)) => format!("scope:{scope}"),
+            _ => format!("scope:{:?}", claims.scope),
+        },
+    }
+}
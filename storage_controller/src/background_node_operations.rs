@@ -15,10 +15,24 @@ pub(crate) struct Fill {
     pub(crate) node_id: NodeId,
 }
 
+/// Migrate all timelines away from a safekeeper
+#[derive(Copy, Clone)]
+pub(crate) struct SafekeeperDrain {
+    pub(crate) safekeeper_id: NodeId,
+}
+
+/// Drain, restart and fill a sequence of nodes
+#[derive(Copy, Clone)]
+pub(crate) struct RollingRestart {
+    pub(crate) id: i64,
+}
+
 #[derive(Copy, Clone)]
 pub(crate) enum Operation {
     Drain(Drain),
     Fill(Fill),
+    SafekeeperDrain(SafekeeperDrain),
+    RollingRestart(RollingRestart),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -29,6 +43,8 @@ pub(crate) enum OperationError {
     FinalizeError(Cow<'static, str>),
     #[error("Operation cancelled")]
     Cancelled,
+    #[error("Operation timed out: {0}")]
+    Timeout(Cow<'static, str>),
 }
 
 pub(crate) struct OperationHandler {
@@ -49,11 +65,25 @@ fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     }
 }
 
+impl Display for SafekeeperDrain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "safekeeper drain {}", self.safekeeper_id)
+    }
+}
+
+impl Display for RollingRestart {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "rolling restart {}", self.id)
+    }
+}
+
 impl Display for Operation {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Operation::Drain(op) => write!(f, "{op}"),
             Operation::Fill(op) => write!(f, "{op}"),
+            Operation::SafekeeperDrain(op) => write!(f, "{op}"),
+            Operation::RollingRestart(op) => write!(f, "{op}"),
         }
     }
 }
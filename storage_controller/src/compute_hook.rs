@@ -12,7 +12,7 @@
 use tracing::{info_span, Instrument};
 use utils::{
     backoff::{self},
-    id::{NodeId, TenantId},
+    id::{NodeId, TenantId, TimelineId},
 };
 
 use crate::service::Config;
@@ -153,6 +153,23 @@ struct ComputeHookNotifyRequest {
     shards: Vec<ComputeHookNotifyRequestShard>,
 }
 
+/// Request body that we send to the control plane to notify it of the safekeepers which a
+/// timeline's compute streams WAL to
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+struct NotifySafekeepersRequest {
+    tenant_id: TenantId,
+    timeline_id: TimelineId,
+    generation: u32,
+    safekeepers: Vec<NotifySafekeeper>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub(crate) struct NotifySafekeeper {
+    pub(crate) id: NodeId,
+    pub(crate) host: String,
+    pub(crate) port: u16,
+}
+
 /// Error type for attempts to call into the control plane compute notification hook
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum NotifyError {
@@ -308,6 +325,24 @@ pub(super) fn new(config: Config) -> Self {
         }
     }
 
+    /// For test environments: load neon_local's LocalEnv, or None if we cannot, in which case
+    /// compute updates are skipped
+    fn load_local_env(&self) -> Option<LocalEnv> {
+        let Some(repo_dir) = self.config.neon_local_repo_dir.as_deref() else {
+            tracing::warn!(
+                "neon_local_repo_dir not set, likely a bug in neon_local; skipping compute update"
+            );
+            return None;
+        };
+        match LocalEnv::load_config(repo_dir) {
+            Ok(e) => Some(e),
+            Err(e) => {
+                tracing::warn!("Couldn't load neon_local config, skipping compute update ({e})");
+                None
+            }
+        }
+    }
+
     /// For test environments: use neon_local's LocalEnv to update compute
     async fn do_notify_local(
         &self,
@@ -317,19 +352,9 @@ async fn do_notify_local(
         // all calls to this function
         let _locked = self.neon_local_lock.lock().await;
 
-        let Some(repo_dir) = self.config.neon_local_repo_dir.as_deref() else {
-            tracing::warn!(
-                "neon_local_repo_dir not set, likely a bug in neon_local; skipping compute update"
-            );
+        let Some(env) = self.load_local_env() else {
             return Ok(());
         };
-        let env = match LocalEnv::load_config(repo_dir) {
-            Ok(e) => e,
-            Err(e) => {
-                tracing::warn!("Couldn't load neon_local config, skipping compute update ({e})");
-                return Ok(());
-            }
-        };
         let cplane =
             ComputeControlPlane::load(env.clone()).expect("Error loading compute control plane");
         let ComputeHookNotifyRequest {
@@ -363,10 +388,46 @@ async fn do_notify_local(
         Ok(())
     }
 
-    async fn do_notify_iteration(
+    /// For test environments: use neon_local's LocalEnv to point the timeline's computes at
+    /// its new safekeepers
+    async fn do_notify_safekeepers_local(
+        &self,
+        request: &NotifySafekeepersRequest,
+    ) -> Result<(), NotifyError> {
+        let _locked = self.neon_local_lock.lock().await;
+
+        let Some(env) = self.load_local_env() else {
+            return Ok(());
+        };
+        let cplane =
+            ComputeControlPlane::load(env.clone()).expect("Error loading compute control plane");
+        let safekeepers = request
+            .safekeepers
+            .iter()
+            .map(|sk| sk.id)
+            .collect::<Vec<_>>();
+
+        for (endpoint_name, endpoint) in &cplane.endpoints {
+            if endpoint.tenant_id == request.tenant_id
+                && endpoint.timeline_id == request.timeline_id
+                && endpoint.status() == EndpointStatus::Running
+            {
+                tracing::info!("Reconfiguring safekeepers of endpoint {}", endpoint_name);
+                // Without pageservers, the endpoint looks up its current ones
+                endpoint
+                    .reconfigure(Vec::new(), None, Some(safekeepers.clone()))
+                    .await
+                    .map_err(NotifyError::NeonLocal)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn do_notify_iteration<R: Serialize + std::fmt::Debug>(
         &self,
         url: &String,
-        reconfigure_request: &ComputeHookNotifyRequest,
+        reconfigure_request: &R,
         cancel: &CancellationToken,
     ) -> Result<(), NotifyError> {
         let req = self.client.request(reqwest::Method::PUT, url);
@@ -441,10 +502,10 @@ async fn do_notify_iteration(
         }
     }
 
-    async fn do_notify(
+    async fn do_notify<R: Serialize + std::fmt::Debug>(
         &self,
         url: &String,
-        reconfigure_request: &ComputeHookNotifyRequest,
+        reconfigure_request: &R,
         cancel: &CancellationToken,
     ) -> Result<(), NotifyError> {
         // We hold these semaphore units across all retries, rather than only across each
@@ -646,6 +707,45 @@ pub(super) async fn notify(
             .await
     }
 
+    /// Call this to notify the compute (postgres) tier of a timeline of the safekeepers it
+    /// should stream WAL to, after the timeline's safekeeper membership changed.  Unlike
+    /// pageserver notifications these are not coalesced: the caller sends one per membership
+    /// change, and must not remove a safekeeper from the timeline until it succeeded.
+    #[tracing::instrument(skip_all, fields(%tenant_id, %timeline_id, generation))]
+    pub(super) async fn notify_safekeepers(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        generation: u32,
+        safekeepers: Vec<NotifySafekeeper>,
+        cancel: &CancellationToken,
+    ) -> Result<(), NotifyError> {
+        let request = NotifySafekeepersRequest {
+            tenant_id,
+            timeline_id,
+            generation,
+            safekeepers,
+        };
+
+        // Without a dedicated hook, safekeeper changes go to the same control plane endpoint as
+        // pageserver changes: the request body tells them apart.
+        match self
+            .config
+            .compute_safekeepers_hook_url
+            .as_ref()
+            .or(self.config.compute_hook_url.as_ref())
+        {
+            Some(notify_url) => self.do_notify(notify_url, &request, cancel).await,
+            None => self
+                .do_notify_safekeepers_local(&request)
+                .await
+                .map_err(|e| {
+                    tracing::error!("neon_local notification hook failed: {e}");
+                    NotifyError::Fatal(StatusCode::INTERNAL_SERVER_ERROR)
+                }),
+        }
+    }
+
     /// Reflect a detach for a particular shard in the compute hook state.
     ///
     /// The goal is to avoid sending compute notifications with stale information (i.e.
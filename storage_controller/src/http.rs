@@ -3,7 +3,7 @@
     HttpRequestLatencyLabelGroup, HttpRequestStatusLabelGroup, PageserverRequestLabelGroup,
     METRICS_REGISTRY,
 };
-use crate::persistence::SafekeeperPersistence;
+use crate::persistence::{AuditFilter, NewAuditRecord, OperationFilter, SafekeeperPersistence};
 use crate::reconciler::ReconcileError;
 use crate::service::{LeadershipStatus, Service, RECONCILE_TIMEOUT, STARTUP_RECONCILE_TIMEOUT};
 use anyhow::Context;
@@ -13,8 +13,9 @@
 use hyper::{StatusCode, Uri};
 use metrics::{BuildInfo, NeonMetrics};
 use pageserver_api::controller_api::{
-    MetadataHealthListOutdatedRequest, MetadataHealthListOutdatedResponse,
-    MetadataHealthListUnhealthyResponse, MetadataHealthUpdateRequest, MetadataHealthUpdateResponse,
+    BulkMigratePlanRequest, ChaosConfig, MetadataHealthListOutdatedRequest,
+    MetadataHealthListOutdatedResponse, MetadataHealthListUnhealthyResponse,
+    MetadataHealthUpdateRequest, MetadataHealthUpdateResponse, RollingRestartRequest,
     ShardsPreferredAzsRequest, TenantCreateRequest,
 };
 use pageserver_api::models::{
@@ -27,7 +28,7 @@
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
-use utils::auth::{Scope, SwappableJwtAuth};
+use utils::auth::{Claims, Scope, SwappableJwtAuth};
 use utils::failpoint_support::failpoints_handler;
 use utils::http::endpoint::{auth_middleware, check_permission_with, request_span};
 use utils::http::request::{must_get_query_param, parse_query_param, parse_request_param};
@@ -44,8 +45,8 @@
 };
 
 use pageserver_api::controller_api::{
-    NodeAvailability, NodeConfigureRequest, NodeRegisterRequest, TenantPolicyRequest,
-    TenantShardMigrateRequest,
+    NodeAvailability, NodeConfigureRequest, NodeRegisterRequest, TenantMergeRequest,
+    TenantPolicyRequest, TenantShardMigrateRequest,
 };
 use pageserver_api::upcall_api::{ReAttachRequest, ValidateRequest};
 
@@ -792,9 +793,33 @@ async fn handle_node_drain(req: Request<Body>) -> Result<Response<Body>, ApiErro
     let state = get_state(&req);
     let node_id: NodeId = parse_request_param(&req, "node_id")?;
 
-    state.service.start_node_drain(node_id).await?;
+    json_response(
+        StatusCode::ACCEPTED,
+        state.service.start_node_drain(node_id).await?,
+    )
+}
 
-    json_response(StatusCode::ACCEPTED, ())
+async fn handle_node_drain_plan(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+    let download_bandwidth = parse_query_param(&req, "download_bandwidth")?;
+
+    json_response(
+        StatusCode::OK,
+        state
+            .service
+            .plan_node_drain(node_id, download_bandwidth)
+            .await?,
+    )
 }
 
 async fn handle_cancel_node_drain(req: Request<Body>) -> Result<Response<Body>, ApiError> {
@@ -828,9 +853,56 @@ async fn handle_node_fill(req: Request<Body>) -> Result<Response<Body>, ApiError
     let state = get_state(&req);
     let node_id: NodeId = parse_request_param(&req, "node_id")?;
 
-    state.service.start_node_fill(node_id).await?;
+    json_response(
+        StatusCode::ACCEPTED,
+        state.service.start_node_fill(node_id).await?,
+    )
+}
 
-    json_response(StatusCode::ACCEPTED, ())
+async fn handle_node_fill_plan(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+    let download_bandwidth = parse_query_param(&req, "download_bandwidth")?;
+
+    json_response(
+        StatusCode::OK,
+        state
+            .service
+            .plan_node_fill(node_id, download_bandwidth)
+            .await?,
+    )
+}
+
+async fn handle_bulk_migrate_plan(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let mut req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let plan_req = json_request::<BulkMigratePlanRequest>(&mut req).await?;
+    let state = get_state(&req);
+    let download_bandwidth = parse_query_param(&req, "download_bandwidth")?;
+
+    json_response(
+        StatusCode::OK,
+        state
+            .service
+            .plan_bulk_migrate(plan_req, download_bandwidth)
+            .await?,
+    )
 }
 
 async fn handle_cancel_node_fill(req: Request<Body>) -> Result<Response<Body>, ApiError> {
@@ -851,6 +923,190 @@ async fn handle_cancel_node_fill(req: Request<Body>) -> Result<Response<Body>, A
     json_response(StatusCode::ACCEPTED, ())
 }
 
+async fn handle_operation_get(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let operation_id: i64 = parse_request_param(&req, "operation_id")?;
+
+    json_response(
+        StatusCode::OK,
+        state.service.get_operation(operation_id).await?,
+    )
+}
+
+async fn handle_operation_cancel(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let operation_id: i64 = parse_request_param(&req, "operation_id")?;
+
+    json_response(
+        StatusCode::ACCEPTED,
+        state.service.cancel_operation(operation_id).await?,
+    )
+}
+
+async fn handle_operations_list(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    const DEFAULT_LIMIT: i64 = 100;
+    let filter = OperationFilter {
+        tenant_id: parse_query_param(&req, "tenant_id")?,
+        node_id: parse_query_param(&req, "node_id")?,
+        running_only: parse_query_param(&req, "running")?.unwrap_or(false),
+        limit: parse_query_param(&req, "limit")?.unwrap_or(DEFAULT_LIMIT),
+    };
+
+    let state = get_state(&req);
+    json_response(StatusCode::OK, state.service.list_operations(filter).await?)
+}
+
+async fn handle_audit_list(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    const DEFAULT_LIMIT: i64 = 100;
+    let filter = AuditFilter {
+        tenant_id: parse_query_param(&req, "tenant_id")?,
+        node_id: parse_query_param(&req, "node_id")?,
+        limit: parse_query_param(&req, "limit")?.unwrap_or(DEFAULT_LIMIT),
+    };
+
+    let state = get_state(&req);
+    json_response(
+        StatusCode::OK,
+        state.service.list_audit_records(filter).await?,
+    )
+}
+
+async fn handle_rolling_restart_start(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let mut req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let restart_req = json_request::<RollingRestartRequest>(&mut req).await?;
+    let state = get_state(&req);
+
+    json_response(
+        StatusCode::ACCEPTED,
+        state.service.start_rolling_restart(restart_req).await?,
+    )
+}
+
+async fn handle_rolling_restart_get(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    json_response(StatusCode::OK, state.service.get_rolling_restart().await?)
+}
+
+async fn handle_rolling_restart_pause(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    json_response(StatusCode::OK, state.service.pause_rolling_restart().await?)
+}
+
+async fn handle_rolling_restart_resume(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    json_response(
+        StatusCode::OK,
+        state.service.resume_rolling_restart().await?,
+    )
+}
+
+async fn handle_rolling_restart_abort(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    json_response(StatusCode::OK, state.service.abort_rolling_restart().await?)
+}
+
+async fn handle_node_restarted(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+
+    json_response(
+        StatusCode::OK,
+        state
+            .service
+            .rolling_restart_node_restarted(node_id)
+            .await?,
+    )
+}
+
 async fn handle_metadata_health_update(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Scrubber)?;
 
@@ -939,6 +1195,28 @@ async fn handle_tenant_shard_split(
     )
 }
 
+async fn handle_tenant_shard_merge(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let mut req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let merge_req = json_request::<TenantMergeRequest>(&mut req).await?;
+
+    json_response(
+        StatusCode::OK,
+        service.tenant_shard_merge(tenant_id, merge_req).await?,
+    )
+}
+
 async fn handle_tenant_shard_migrate(
     service: Arc<Service>,
     req: Request<Body>,
@@ -985,6 +1263,20 @@ async fn handle_tenant_update_policy(req: Request<Body>) -> Result<Response<Body
     )
 }
 
+async fn handle_autosplit_decisions(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    json_response(StatusCode::OK, state.service.autosplit_decisions())
+}
+
 async fn handle_update_preferred_azs(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
@@ -1113,6 +1405,52 @@ async fn handle_reconcile_all(req: Request<Body>) -> Result<Response<Body>, ApiE
     json_response(StatusCode::OK, state.service.reconcile_all_now().await?)
 }
 
+async fn handle_chaos_start(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let mut req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let config = json_request::<ChaosConfig>(&mut req).await?;
+    let state = get_state(&req);
+
+    json_response(StatusCode::OK, state.service.chaos_start(config)?)
+}
+
+async fn handle_chaos_stop(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+
+    json_response(StatusCode::OK, state.service.chaos_stop()?)
+}
+
+async fn handle_chaos_status(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+
+    json_response(StatusCode::OK, state.service.chaos_status())
+}
+
 /// Status endpoint is just used for checking that our HTTP listener is up
 async fn handle_status(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     match maybe_forward(req).await {
@@ -1212,6 +1550,81 @@ async fn handle_upsert_safekeeper(mut req: Request<Body>) -> Result<Response<Bod
         .unwrap())
 }
 
+async fn handle_list_safekeepers(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    json_response(StatusCode::OK, state.service.list_safekeepers().await?)
+}
+
+async fn handle_safekeeper_drain(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let safekeeper_id: NodeId = parse_request_param(&req, "id")?;
+
+    json_response(
+        StatusCode::ACCEPTED,
+        state.service.start_safekeeper_drain(safekeeper_id).await?,
+    )
+}
+
+async fn handle_cancel_safekeeper_drain(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let safekeeper_id: NodeId = parse_request_param(&req, "id")?;
+
+    state.service.cancel_safekeeper_drain(safekeeper_id).await?;
+
+    json_response(StatusCode::ACCEPTED, ())
+}
+
+/// Used by the control plane to learn which safekeepers a compute should connect to.
+async fn handle_tenant_timeline_safekeepers(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let timeline_id: TimelineId = parse_request_param(&req, "timeline_id")?;
+    check_permissions(&req, Scope::PageServerApi)?;
+
+    match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(_req) => {}
+    };
+
+    json_response(
+        StatusCode::OK,
+        service
+            .tenant_timeline_safekeepers(tenant_id, timeline_id)
+            .await?,
+    )
+}
+
 /// Common wrapper for request handlers that call into Service and will operate on tenants: they must only
 /// be allowed to run if Service has finished its initial reconciliation.
 async fn tenant_service_handler<R, H>(
@@ -1387,8 +1800,123 @@ async fn named_request_span<R, H>(
     R: Future<Output = Result<Response<Body>, ApiError>> + Send + 'static,
     H: FnOnce(Request<Body>) -> R + Send + Sync + 'static,
 {
+    let audited = is_audited(&request, &name);
     request.set_context(name);
-    request_span(request, handler).await
+    if audited {
+        request_span(request, |request| audited_request(request, handler)).await
+    } else {
+        request_span(request, handler).await
+    }
+}
+
+/// Requests which are not recorded in the audit log although they are not GETs: upcalls from
+/// pageservers, which are frequent and made on the pageservers' own behalf, and POSTs which only
+/// read state.
+const UNAUDITED_REQUESTS: &[&str] = &[
+    "upcall_v1_reattach",
+    "upcall_v1_validate",
+    "debug_v1_inspect",
+    "debug_v1_consistency_check",
+    "control_v1_metadata_health_list_outdated",
+    "control_v1_bulk_migrate_plan",
+];
+
+fn is_audited(request: &Request<Body>, name: &RequestName) -> bool {
+    !matches!(
+        *request.method(),
+        hyper::Method::GET | hyper::Method::HEAD | hyper::Method::OPTIONS
+    ) && !UNAUDITED_REQUESTS.contains(&name.0)
+}
+
+/// Run a request which may modify the controller's state, and record it in the audit log along
+/// with the state of the tenant and node it names, before and after.
+async fn audited_request<R, H>(
+    request: Request<Body>,
+    handler: H,
+) -> Result<Response<Body>, ApiError>
+where
+    R: Future<Output = Result<Response<Body>, ApiError>> + Send + 'static,
+    H: FnOnce(Request<Body>) -> R + Send + Sync + 'static,
+{
+    let service = get_state(&request).service.clone();
+
+    // A controller which has stepped down forwards requests to the leader, which records them.
+    if service.get_leadership_status() != LeadershipStatus::Leader {
+        return handler(request).await;
+    }
+
+    let recorded_at = chrono::Utc::now();
+    let subject = request
+        .context::<Claims>()
+        .map(|claims| crate::auth::subject(&claims));
+    let method = request.method().to_string();
+    let path = request.uri().to_string();
+    let (mut tenant_id, mut node_id) = audit_target_from_path(&request);
+
+    // Buffer the body so that it can be recorded: the handler reads it from the buffer.
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body)
+        .await
+        .map_err(|e| ApiError::BadRequest(e.into()))?;
+    let body_json = serde_json::from_slice::<serde_json::Value>(&body).ok();
+    if let Some(body_json) = &body_json {
+        let (body_tenant_id, body_node_id) = audit_target_from_body(body_json);
+        tenant_id = tenant_id.or(body_tenant_id);
+        node_id = node_id.or(body_node_id);
+    }
+    let request = Request::from_parts(parts, Body::from(body));
+
+    let state_before = service.audit_state(tenant_id, node_id);
+    let result = handler(request).await;
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(err) => err.status_code(),
+    };
+    let state_after = service.audit_state(tenant_id, node_id);
+
+    service
+        .record_audit(NewAuditRecord {
+            recorded_at: recorded_at.into(),
+            subject,
+            method,
+            path,
+            tenant_id: tenant_id.map(|tenant_id| tenant_id.to_string()),
+            node_id: node_id.map(|node_id| node_id.0 as i64),
+            status: status.as_u16() as i32,
+            request: body_json.map(|body_json| body_json.to_string()),
+            state_before,
+            state_after,
+        })
+        .await;
+
+    result
+}
+
+/// The tenant and node named in a request's path.  Safekeeper routes name their node `id`.
+fn audit_target_from_path(request: &Request<Body>) -> (Option<TenantId>, Option<NodeId>) {
+    let tenant_id = request
+        .param("tenant_shard_id")
+        .or(request.param("tenant_id"))
+        .and_then(|id| TenantShardId::from_str(id).ok())
+        .map(|id| id.tenant_id);
+    let node_id = request
+        .param("node_id")
+        .or(request.param("id"))
+        .and_then(|id| id.parse::<u64>().ok())
+        .map(NodeId);
+    (tenant_id, node_id)
+}
+
+/// The tenant and node named in a request's body, for requests such as tenant creation and
+/// node registration which do not name them in their path.
+fn audit_target_from_body(body: &serde_json::Value) -> (Option<TenantId>, Option<NodeId>) {
+    let tenant_id = ["tenant_id", "new_tenant_id"]
+        .iter()
+        .filter_map(|field| body.get(field)?.as_str())
+        .find_map(|id| TenantShardId::from_str(id).ok())
+        .map(|id| id.tenant_id);
+    let node_id = body.get("node_id").and_then(|id| id.as_u64()).map(NodeId);
+    (tenant_id, node_id)
 }
 
 enum ForwardOutcome {
@@ -1685,10 +2213,27 @@ pub fn make_router(
             )
         })
         .post("/debug/v1/reconcile_all", |r| {
-            request_span(r, handle_reconcile_all)
+            named_request_span(
+                r,
+                handle_reconcile_all,
+                RequestName("debug_v1_reconcile_all"),
+            )
+        })
+        .put("/debug/v1/chaos", |r| {
+            named_request_span(r, handle_chaos_start, RequestName("debug_v1_chaos_start"))
+        })
+        .delete("/debug/v1/chaos", |r| {
+            named_request_span(r, handle_chaos_stop, RequestName("debug_v1_chaos_stop"))
+        })
+        .get("/debug/v1/chaos", |r| {
+            named_request_span(r, handle_chaos_status, RequestName("debug_v1_chaos_status"))
         })
         .put("/debug/v1/failpoints", |r| {
-            request_span(r, |r| failpoints_handler(r, CancellationToken::new()))
+            named_request_span(
+                r,
+                |r| failpoints_handler(r, CancellationToken::new()),
+                RequestName("debug_v1_failpoints"),
+            )
         })
         // Node operations
         .post("/control/v1/node", |r| {
@@ -1730,6 +2275,13 @@ pub fn make_router(
                 RequestName("control_v1_cancel_node_drain"),
             )
         })
+        .get("/control/v1/node/:node_id/drain/plan", |r| {
+            named_request_span(
+                r,
+                handle_node_drain_plan,
+                RequestName("control_v1_node_drain_plan"),
+            )
+        })
         .put("/control/v1/node/:node_id/fill", |r| {
             named_request_span(r, handle_node_fill, RequestName("control_v1_node_fill"))
         })
@@ -1740,6 +2292,88 @@ pub fn make_router(
                 RequestName("control_v1_cancel_node_fill"),
             )
         })
+        .get("/control/v1/node/:node_id/fill/plan", |r| {
+            named_request_span(
+                r,
+                handle_node_fill_plan,
+                RequestName("control_v1_node_fill_plan"),
+            )
+        })
+        .post("/control/v1/bulk_migrate/plan", |r| {
+            named_request_span(
+                r,
+                handle_bulk_migrate_plan,
+                RequestName("control_v1_bulk_migrate_plan"),
+            )
+        })
+        .put("/control/v1/node/:node_id/restarted", |r| {
+            named_request_span(
+                r,
+                handle_node_restarted,
+                RequestName("control_v1_node_restarted"),
+            )
+        })
+        // Long-running operations
+        .get("/control/v1/operations", |r| {
+            named_request_span(
+                r,
+                handle_operations_list,
+                RequestName("control_v1_operations_list"),
+            )
+        })
+        .get("/control/v1/operations/:operation_id", |r| {
+            named_request_span(
+                r,
+                handle_operation_get,
+                RequestName("control_v1_operation_get"),
+            )
+        })
+        .delete("/control/v1/operations/:operation_id", |r| {
+            named_request_span(
+                r,
+                handle_operation_cancel,
+                RequestName("control_v1_operation_cancel"),
+            )
+        })
+        .get("/control/v1/audit", |r| {
+            named_request_span(r, handle_audit_list, RequestName("control_v1_audit_list"))
+        })
+        // Rolling restarts
+        .post("/control/v1/rolling_restart", |r| {
+            named_request_span(
+                r,
+                handle_rolling_restart_start,
+                RequestName("control_v1_rolling_restart_start"),
+            )
+        })
+        .get("/control/v1/rolling_restart", |r| {
+            named_request_span(
+                r,
+                handle_rolling_restart_get,
+                RequestName("control_v1_rolling_restart_get"),
+            )
+        })
+        .put("/control/v1/rolling_restart/pause", |r| {
+            named_request_span(
+                r,
+                handle_rolling_restart_pause,
+                RequestName("control_v1_rolling_restart_pause"),
+            )
+        })
+        .put("/control/v1/rolling_restart/resume", |r| {
+            named_request_span(
+                r,
+                handle_rolling_restart_resume,
+                RequestName("control_v1_rolling_restart_resume"),
+            )
+        })
+        .put("/control/v1/rolling_restart/abort", |r| {
+            named_request_span(
+                r,
+                handle_rolling_restart_abort,
+                RequestName("control_v1_rolling_restart_abort"),
+            )
+        })
         // Metadata health operations
         .post("/control/v1/metadata_health/update", |r| {
             named_request_span(
@@ -1777,6 +2411,13 @@ pub fn make_router(
                 RequestName("control_v1_tenant_shard_split"),
             )
         })
+        .put("/control/v1/tenant/:tenant_id/shard_merge", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_shard_merge,
+                RequestName("control_v1_tenant_shard_merge"),
+            )
+        })
         .get("/control/v1/tenant/:tenant_id", |r| {
             tenant_service_handler(
                 r,
@@ -1794,6 +2435,13 @@ pub fn make_router(
                 RequestName("control_v1_tenant_policy"),
             )
         })
+        .get("/control/v1/autosplit", |r| {
+            named_request_span(
+                r,
+                handle_autosplit_decisions,
+                RequestName("control_v1_autosplit"),
+            )
+        })
         .put("/control/v1/preferred_azs", |r| {
             named_request_span(
                 r,
@@ -1811,6 +2459,33 @@ pub fn make_router(
             // id is in the body
             named_request_span(r, handle_upsert_safekeeper, RequestName("v1_safekeeper"))
         })
+        .get("/control/v1/safekeeper", |r| {
+            named_request_span(r, handle_list_safekeepers, RequestName("v1_safekeeper"))
+        })
+        .put("/control/v1/safekeeper/:id/drain", |r| {
+            named_request_span(
+                r,
+                handle_safekeeper_drain,
+                RequestName("control_v1_safekeeper_drain"),
+            )
+        })
+        .delete("/control/v1/safekeeper/:id/drain", |r| {
+            named_request_span(
+                r,
+                handle_cancel_safekeeper_drain,
+                RequestName("control_v1_cancel_safekeeper_drain"),
+            )
+        })
+        .get(
+            "/control/v1/tenant/:tenant_id/timeline/:timeline_id/safekeepers",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_tenant_timeline_safekeepers,
+                    RequestName("control_v1_tenant_timeline_safekeepers"),
+                )
+            },
+        )
         // Tenant operations
         // The ^/v1/ endpoints act as a "Virtual Pageserver", enabling shard-naive clients to call into
         // this service to manage tenants that actually consist of many tenant shards, as if they are a single entity.
@@ -69,7 +69,7 @@ pub(crate) async fn become_leader(
             // after we update the k8s setup
             let proposed_leader = ControllerPersistence {
                 address: address_for_peers.to_string(),
-                started_at: chrono::Utc::now(),
+                started_at: chrono::Utc::now().into(),
             };
 
             self.persistence
@@ -3,22 +3,24 @@
 use hyper0::Uri;
 use metrics::launch_timestamp::LaunchTimestamp;
 use metrics::BuildInfo;
+use pageserver_api::controller_api::ChaosConfig;
+use pageserver_api::models::utilization::UtilizationWeights;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use storage_controller::http::make_router;
 use storage_controller::metrics::preinitialize_metrics;
 use storage_controller::persistence::Persistence;
-use storage_controller::service::chaos_injector::ChaosInjector;
 use storage_controller::service::{
-    Config, Service, HEARTBEAT_INTERVAL_DEFAULT, LONG_RECONCILE_THRESHOLD_DEFAULT,
-    MAX_OFFLINE_INTERVAL_DEFAULT, MAX_WARMING_UP_INTERVAL_DEFAULT, RECONCILER_CONCURRENCY_DEFAULT,
+    Config, Service, AUDIT_LOG_RETENTION_DEFAULT, HEARTBEAT_INTERVAL_DEFAULT,
+    LONG_RECONCILE_THRESHOLD_DEFAULT, MAX_OFFLINE_INTERVAL_DEFAULT,
+    MAX_WARMING_UP_INTERVAL_DEFAULT, OPERATIONS_RETENTION_DEFAULT, RECONCILER_CONCURRENCY_DEFAULT,
 };
 use tokio::signal::unix::SignalKind;
 use tokio_util::sync::CancellationToken;
-use tracing::Instrument;
 use utils::auth::{JwtAuth, SwappableJwtAuth};
 use utils::logging::{self, LogFormat};
+use utils::serde_percent::Percent;
 
 use utils::sentry_init::init_sentry;
 use utils::{project_build_tag, project_git_version, tcp_listener};
@@ -54,7 +56,15 @@ struct Cli {
     #[arg(long)]
     compute_hook_url: Option<String>,
 
+    /// URL to control plane endpoint notified of changes to the safekeepers of a timeline, if
+    /// not the one given by `compute_hook_url`
+    #[arg(long)]
+    compute_safekeepers_hook_url: Option<String>,
+
     /// URL to connect to postgres, like postgresql://localhost:1234/storage_controller
+    ///
+    /// Single-node deployments may use an SQLite database instead, like
+    /// sqlite:///var/lib/storage_controller.db
     #[arg(long)]
     database_url: Option<String>,
 
@@ -71,10 +81,56 @@ struct Cli {
     #[arg(long)]
     max_warming_up_interval: Option<humantime::Duration>,
 
-    /// Size threshold for automatically splitting shards (disabled by default)
+    /// Logical size threshold for automatically splitting shards (disabled by default)
     #[arg(long)]
     split_threshold: Option<u64>,
 
+    /// Resident size threshold for automatically splitting shards (disabled by default)
+    #[arg(long)]
+    split_resident_size_threshold: Option<u64>,
+
+    /// WAL ingest rate threshold in bytes per second for automatically splitting shards
+    /// (disabled by default)
+    #[arg(long)]
+    split_wal_ingest_rate_threshold: Option<u64>,
+
+    /// Shard count that tenants are automatically split to
+    #[arg(long, default_value = "8")]
+    split_max_shard_count: u8,
+
+    /// How many of the largest shards on each pageserver to consider for automatic splitting
+    #[arg(long, default_value = "10")]
+    split_candidates_per_node: usize,
+
+    /// Maximum number of automatic splits running at once on one pageserver
+    #[arg(long, default_value = "1")]
+    max_concurrent_splits_per_node: usize,
+
+    /// Maximum number of automatic splits running at once in one availability zone
+    #[arg(long, default_value = "2")]
+    max_concurrent_splits_per_az: usize,
+
+    /// Weight of the disk space wanted by a pageserver's shards in its scheduling score
+    #[arg(long, default_value = "1")]
+    utilization_weight_disk: u64,
+
+    /// Weight of the resident size of a pageserver's shards in its scheduling score
+    #[arg(long, default_value = "0")]
+    utilization_weight_resident: u64,
+
+    /// Weight of the WAL ingest rate of a pageserver's shards in its scheduling score
+    #[arg(long, default_value = "0")]
+    utilization_weight_wal_ingest: u64,
+
+    /// WAL ingest rate in bytes per second at which a pageserver counts as fully utilized
+    #[arg(long, default_value = "268435456")]
+    utilization_wal_ingest_capacity: u64,
+
+    /// Physical disk usage percentage above which shards are migrated off a pageserver
+    /// (disabled by default)
+    #[arg(long)]
+    disk_usage_watermark_pct: Option<u8>,
+
     /// Maximum number of reconcilers that may run in parallel
     #[arg(long)]
     reconciler_concurrency: Option<usize>,
@@ -96,7 +152,8 @@ struct Cli {
     #[arg(long)]
     neon_local_repo_dir: Option<PathBuf>,
 
-    /// Chaos testing
+    /// Chaos testing: migrate random shards to their secondaries this often.  Other chaos
+    /// scenarios may be started through the `/debug/v1/chaos` API.
     #[arg(long)]
     chaos_interval: Option<humantime::Duration>,
 
@@ -111,6 +168,22 @@ struct Cli {
 
     #[arg(long)]
     long_reconcile_threshold: Option<humantime::Duration>,
+
+    /// How long to keep finished operations in the database
+    #[arg(long)]
+    operations_retention: Option<humantime::Duration>,
+
+    /// How long to keep records of the audit log in the database
+    #[arg(long)]
+    audit_log_retention: Option<humantime::Duration>,
+
+    /// Token for authenticating this service with the safekeepers it controls
+    #[arg(long)]
+    safekeeper_jwt_token: Option<String>,
+
+    /// Place new timelines onto safekeepers, rather than leaving that to the control plane
+    #[arg(long, default_value = "false")]
+    timelines_onto_safekeepers: bool,
 }
 
 enum StrictMode {
@@ -137,6 +210,7 @@ struct Secrets {
     jwt_token: Option<String>,
     control_plane_jwt_token: Option<String>,
     peer_jwt_token: Option<String>,
+    safekeeper_jwt_token: Option<String>,
 }
 
 impl Secrets {
@@ -144,6 +218,7 @@ impl Secrets {
     const PAGESERVER_JWT_TOKEN_ENV: &'static str = "PAGESERVER_JWT_TOKEN";
     const CONTROL_PLANE_JWT_TOKEN_ENV: &'static str = "CONTROL_PLANE_JWT_TOKEN";
     const PEER_JWT_TOKEN_ENV: &'static str = "PEER_JWT_TOKEN";
+    const SAFEKEEPER_JWT_TOKEN_ENV: &'static str = "SAFEKEEPER_JWT_TOKEN";
     const PUBLIC_KEY_ENV: &'static str = "PUBLIC_KEY";
 
     /// Load secrets from, in order of preference:
@@ -171,6 +246,10 @@ async fn load(args: &Cli) -> anyhow::Result<Self> {
                 Self::CONTROL_PLANE_JWT_TOKEN_ENV,
             ),
             peer_jwt_token: Self::load_secret(&args.peer_jwt_token, Self::PEER_JWT_TOKEN_ENV),
+            safekeeper_jwt_token: Self::load_secret(
+                &args.safekeeper_jwt_token,
+                Self::SAFEKEEPER_JWT_TOKEN_ENV,
+            ),
         };
 
         Ok(this)
@@ -273,11 +352,20 @@ async fn async_main() -> anyhow::Result<()> {
         }
     }
 
+    let disk_usage_watermark_pct = args
+        .disk_usage_watermark_pct
+        .map(|pct| {
+            Percent::new(pct)
+                .ok_or_else(|| anyhow!("--disk-usage-watermark-pct must be at most 100"))
+        })
+        .transpose()?;
+
     let config = Config {
         jwt_token: secrets.jwt_token,
         control_plane_jwt_token: secrets.control_plane_jwt_token,
         peer_jwt_token: secrets.peer_jwt_token,
         compute_hook_url: args.compute_hook_url,
+        compute_safekeepers_hook_url: args.compute_safekeepers_hook_url,
         max_offline_interval: args
             .max_offline_interval
             .map(humantime::Duration::into)
@@ -290,6 +378,19 @@ async fn async_main() -> anyhow::Result<()> {
             .reconciler_concurrency
             .unwrap_or(RECONCILER_CONCURRENCY_DEFAULT),
         split_threshold: args.split_threshold,
+        split_resident_size_threshold: args.split_resident_size_threshold,
+        split_wal_ingest_rate_threshold: args.split_wal_ingest_rate_threshold,
+        split_max_shard_count: args.split_max_shard_count,
+        split_candidates_per_node: args.split_candidates_per_node,
+        max_concurrent_splits_per_node: args.max_concurrent_splits_per_node,
+        max_concurrent_splits_per_az: args.max_concurrent_splits_per_az,
+        utilization_weights: UtilizationWeights {
+            disk_wanted: args.utilization_weight_disk,
+            resident: args.utilization_weight_resident,
+            wal_ingest: args.utilization_weight_wal_ingest,
+            wal_ingest_capacity: args.utilization_wal_ingest_capacity,
+        },
+        disk_usage_watermark_pct,
         neon_local_repo_dir: args.neon_local_repo_dir,
         max_secondary_lag_bytes: args.max_secondary_lag_bytes,
         heartbeat_interval: args
@@ -300,9 +401,19 @@ async fn async_main() -> anyhow::Result<()> {
             .long_reconcile_threshold
             .map(humantime::Duration::into)
             .unwrap_or(LONG_RECONCILE_THRESHOLD_DEFAULT),
+        operations_retention: args
+            .operations_retention
+            .map(humantime::Duration::into)
+            .unwrap_or(OPERATIONS_RETENTION_DEFAULT),
+        audit_log_retention: args
+            .audit_log_retention
+            .map(humantime::Duration::into)
+            .unwrap_or(AUDIT_LOG_RETENTION_DEFAULT),
         address_for_peers: args.address_for_peers,
         start_as_candidate: args.start_as_candidate,
         http_service_port: args.listen.port() as i32,
+        safekeeper_jwt_token: secrets.safekeeper_jwt_token,
+        timelines_onto_safekeepers: args.timelines_onto_safekeepers,
     };
 
     // Validate that we can connect to the database
@@ -335,21 +446,9 @@ async fn async_main() -> anyhow::Result<()> {
     tracing::info!("Serving on {0}", args.listen);
     let server_task = tokio::task::spawn(server);
 
-    let chaos_task = args.chaos_interval.map(|interval| {
-        let service = service.clone();
-        let cancel = CancellationToken::new();
-        let cancel_bg = cancel.clone();
-        (
-            tokio::task::spawn(
-                async move {
-                    let mut chaos_injector = ChaosInjector::new(service, interval.into());
-                    chaos_injector.run(cancel_bg).await
-                }
-                .instrument(tracing::info_span!("chaos_injector")),
-            ),
-            cancel,
-        )
-    });
+    if let Some(interval) = args.chaos_interval {
+        service.chaos_start(ChaosConfig::new(interval.into()))?;
+    }
 
     // Wait until we receive a signal
     let mut sigint = tokio::signal::unix::signal(SignalKind::interrupt())?;
@@ -380,10 +479,7 @@ async fn async_main() -> anyhow::Result<()> {
     }
 
     // If we were injecting chaos, stop that so that we're not calling into Service while it shuts down
-    if let Some((chaos_jh, chaos_cancel)) = chaos_task {
-        chaos_cancel.cancel();
-        chaos_jh.await.ok();
-    }
+    service.chaos_stop().ok();
 
     service.shutdown().await;
     tracing::info!("Service shutdown complete");
@@ -1,4 +1,4 @@
-use std::{str::FromStr, time::Duration};
+use std::{collections::BTreeMap, str::FromStr, time::Duration};
 
 use pageserver_api::{
     controller_api::{
@@ -38,6 +38,9 @@ pub(crate) struct Node {
 
     availability_zone_id: AvailabilityZone,
 
+    // Free-form labels that tenants' placement constraints refer to
+    labels: BTreeMap<String, String>,
+
     // This cancellation token means "stop any RPCs in flight to this node, and don't start
     // any more". It is not related to process shutdown.
     #[serde(skip)]
@@ -68,6 +71,14 @@ pub(crate) fn get_availability_zone_id(&self) -> &AvailabilityZone {
         &self.availability_zone_id
     }
 
+    pub(crate) fn get_labels(&self) -> &BTreeMap<String, String> {
+        &self.labels
+    }
+
+    pub(crate) fn set_labels(&mut self, labels: BTreeMap<String, String>) {
+        self.labels = labels
+    }
+
     pub(crate) fn get_scheduling(&self) -> NodeSchedulingPolicy {
         self.scheduling
     }
@@ -78,6 +89,8 @@ pub(crate) fn set_scheduling(&mut self, scheduling: NodeSchedulingPolicy) {
 
     /// Does this registration request match `self`?  This is used when deciding whether a registration
     /// request should be allowed to update an existing record with the same node ID.
+    ///
+    /// Labels are not compared: a node may change its labels by registering again.
     pub(crate) fn registration_match(&self, register_req: &NodeRegisterRequest) -> bool {
         self.id == register_req.node_id
             && self.listen_http_addr == register_req.listen_http_addr
@@ -182,6 +195,7 @@ pub(crate) fn new(
         listen_pg_addr: String,
         listen_pg_port: u16,
         availability_zone_id: AvailabilityZone,
+        labels: BTreeMap<String, String>,
     ) -> Self {
         Self {
             id,
@@ -192,6 +206,7 @@ pub(crate) fn new(
             scheduling: NodeSchedulingPolicy::Active,
             availability: NodeAvailability::Offline,
             availability_zone_id,
+            labels,
             cancel: CancellationToken::new(),
         }
     }
@@ -205,6 +220,7 @@ pub(crate) fn to_persistent(&self) -> NodePersistence {
             listen_pg_addr: self.listen_pg_addr.clone(),
             listen_pg_port: self.listen_pg_port as i32,
             availability_zone_id: self.availability_zone_id.0.clone(),
+            labels: serde_json::to_string(&self.labels).unwrap(),
         }
     }
 
@@ -220,6 +236,7 @@ pub(crate) fn from_persistent(np: NodePersistence) -> Self {
             listen_pg_addr: np.listen_pg_addr,
             listen_pg_port: np.listen_pg_port as u16,
             availability_zone_id: AvailabilityZone(np.availability_zone_id),
+            labels: serde_json::from_str(&np.labels).expect("Bad labels in DB"),
             cancel: CancellationToken::new(),
         }
     }
@@ -303,6 +320,7 @@ pub(crate) fn describe(&self) -> NodeDescribeResponse {
             listen_http_port: self.listen_http_port,
             listen_pg_addr: self.listen_pg_addr.clone(),
             listen_pg_port: self.listen_pg_port,
+            labels: self.labels.clone(),
         }
     }
 }
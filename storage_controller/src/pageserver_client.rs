@@ -2,8 +2,9 @@
     models::{
         detach_ancestor::AncestorDetached, LocationConfig, LocationConfigListResponse,
         PageserverUtilization, SecondaryProgress, TenantScanRemoteStorageResponse,
-        TenantShardSplitRequest, TenantShardSplitResponse, TimelineArchivalConfigRequest,
-        TimelineCreateRequest, TimelineInfo, TopTenantShardsRequest, TopTenantShardsResponse,
+        TenantShardMergeRequest, TenantShardMergeResponse, TenantShardSplitRequest,
+        TenantShardSplitResponse, TimelineArchivalConfigRequest, TimelineCreateRequest,
+        TimelineInfo, TopTenantShardsRequest, TopTenantShardsResponse,
     },
     shard::TenantShardId,
 };
@@ -218,6 +219,31 @@ pub(crate) async fn tenant_shard_split(
         )
     }
 
+    pub(crate) async fn tenant_shard_merge_prepare(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<()> {
+        measured_request!(
+            "tenant_shard_merge_prepare",
+            crate::metrics::Method::Put,
+            &self.node_id_label,
+            self.inner.tenant_shard_merge_prepare(tenant_shard_id).await
+        )
+    }
+
+    pub(crate) async fn tenant_shard_merge(
+        &self,
+        tenant_shard_id: TenantShardId,
+        req: TenantShardMergeRequest,
+    ) -> Result<TenantShardMergeResponse> {
+        measured_request!(
+            "tenant_shard_merge",
+            crate::metrics::Method::Put,
+            &self.node_id_label,
+            self.inner.tenant_shard_merge(tenant_shard_id, req).await
+        )
+    }
+
     pub(crate) async fn timeline_list(
         &self,
         tenant_shard_id: &TenantShardId,
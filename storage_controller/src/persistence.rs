@@ -1,17 +1,30 @@
+pub(crate) mod portable;
 pub(crate) mod split_state;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 use std::time::Duration;
 use std::time::Instant;
 
+use self::portable::{Int8Vec, Timestamp};
 use self::split_state::SplitState;
+use diesel::connection::SimpleConnection;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool, R2D2Connection};
+use diesel::sqlite::SqliteConnection;
 use diesel::Connection;
 use itertools::Itertools;
+use pageserver_api::controller_api::AuditRecord;
 use pageserver_api::controller_api::AvailabilityZone;
 use pageserver_api::controller_api::MetadataHealthRecord;
+use pageserver_api::controller_api::OperationKind;
+use pageserver_api::controller_api::OperationProgress;
+use pageserver_api::controller_api::OperationState;
+use pageserver_api::controller_api::OperationStatus;
+use pageserver_api::controller_api::PlacementConstraints;
 use pageserver_api::controller_api::ShardSchedulingPolicy;
+use pageserver_api::controller_api::ShardSplitPolicy;
+use pageserver_api::controller_api::SkSchedulingPolicy;
 use pageserver_api::controller_api::{NodeSchedulingPolicy, PlacementPolicy};
 use pageserver_api::models::TenantConfig;
 use pageserver_api::shard::ShardConfigError;
@@ -20,7 +33,7 @@
 use pageserver_api::shard::{ShardCount, ShardNumber, TenantShardId};
 use serde::{Deserialize, Serialize};
 use utils::generation::Generation;
-use utils::id::{NodeId, TenantId};
+use utils::id::{NodeId, TenantId, TimelineId};
 
 use crate::metrics::{
     DatabaseQueryErrorLabelGroup, DatabaseQueryLatencyLabelGroup, METRICS_REGISTRY,
@@ -29,6 +42,31 @@
 
 use diesel_migrations::{embed_migrations, EmbeddedMigrations};
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations");
+/// The same schema as [`MIGRATIONS`], for SQLite.  Schema changes need a migration in both.
+const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations_sqlite");
+
+/// Run a closure with a connection from whichever backend `$persistence` uses, collecting
+/// metrics.  The closure is compiled once per backend, so its queries must be valid for both.
+macro_rules! with_measured_conn {
+    ($persistence:expr, $op:expr, $func:expr $(,)?) => {
+        match &$persistence.connection_pool {
+            ConnectionPool::Postgres(pool) => {
+                Persistence::with_measured_conn(pool, $op, $func).await
+            }
+            ConnectionPool::Sqlite(pool) => Persistence::with_measured_conn(pool, $op, $func).await,
+        }
+    };
+}
+
+/// Like `with_measured_conn!`, without metrics.
+macro_rules! with_conn {
+    ($persistence:expr, $func:expr $(,)?) => {
+        match &$persistence.connection_pool {
+            ConnectionPool::Postgres(pool) => Persistence::with_conn(pool, $func).await,
+            ConnectionPool::Sqlite(pool) => Persistence::with_conn(pool, $func).await,
+        }
+    };
+}
 
 /// ## What do we store?
 ///
@@ -57,8 +95,89 @@
 /// Database calls relating to nodes have low performance requirements, as they are very rarely
 /// updated, and reads of nodes are always from memory, not the database.  We only require that
 /// we can UPDATE a node's scheduling mode reasonably quickly to mark a bad node offline.
+///
+/// ## Backends
+///
+/// Postgres is what we run in production.  SQLite, selected with a `sqlite://<path>` database
+/// URL, is for single-node deployments and tests, where running a Postgres next to the storage
+/// controller is more trouble than it is worth.
 pub struct Persistence {
-    connection_pool: diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<PgConnection>>,
+    connection_pool: ConnectionPool,
+}
+
+enum ConnectionPool {
+    Postgres(Pool<ConnectionManager<PgConnection>>),
+    Sqlite(Pool<ConnectionManager<SqliteConnection>>),
+}
+
+/// The path of the database if `database_url` is for SQLite.  `sqlite://:memory:` is a database
+/// which only lives as long as the [`Persistence`].
+fn sqlite_path(database_url: &str) -> Option<&str> {
+    database_url.strip_prefix("sqlite://")
+}
+
+/// Settings which SQLite only takes per connection.
+#[derive(Debug)]
+struct SqliteConnectionCustomizer;
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for SqliteConnectionCustomizer {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        // Foreign keys are needed for the cascading delete of metadata_health rows
+        conn.batch_execute("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;")
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+/// What differs between the backends, beyond what diesel abstracts over.
+trait PersistenceConnection: R2D2Connection + Send + 'static {
+    /// Run the transactions of [`Persistence::with_conn`]
+    fn run_transaction<R, F>(&mut self, func: &F) -> DatabaseResult<R>
+    where
+        F: Fn(&mut Self) -> DatabaseResult<R>;
+
+    /// SQL for whether the `sk_set` of a timeline contains a safekeeper
+    fn sk_set_contains(safekeeper_id: i64) -> String;
+}
+
+impl PersistenceConnection for PgConnection {
+    fn run_transaction<R, F>(&mut self, func: &F) -> DatabaseResult<R>
+    where
+        F: Fn(&mut Self) -> DatabaseResult<R>,
+    {
+        self.build_transaction().serializable().run(|c| func(c))
+    }
+
+    fn sk_set_contains(safekeeper_id: i64) -> String {
+        // Written so that the GIN index on sk_set may be used
+        format!("timelines.sk_set @> ARRAY[{safekeeper_id}]::BIGINT[]")
+    }
+}
+
+impl PersistenceConnection for SqliteConnection {
+    fn run_transaction<R, F>(&mut self, func: &F) -> DatabaseResult<R>
+    where
+        F: Fn(&mut Self) -> DatabaseResult<R>,
+    {
+        // SQLite transactions are always serializable.  Take the write lock up front rather
+        // than failing to upgrade a read lock half way through the transaction.
+        self.immediate_transaction(|c| func(c))
+    }
+
+    fn sk_set_contains(safekeeper_id: i64) -> String {
+        format!(
+            "EXISTS (SELECT 1 FROM json_each(timelines.sk_set) \
+             WHERE json_each.value = {safekeeper_id})"
+        )
+    }
+}
+
+/// A filter on timelines for [`PersistenceConnection::sk_set_contains`].  The connection is only
+/// taken to pick the backend.
+fn sk_set_contains<C: PersistenceConnection>(
+    _conn: &mut C,
+    safekeeper_id: i64,
+) -> diesel::expression::SqlLiteral<diesel::sql_types::Bool> {
+    diesel::dsl::sql(&C::sk_set_contains(safekeeper_id))
 }
 
 /// Legacy format, for use in JSON compat objects in test environment
@@ -90,6 +209,8 @@ pub(crate) enum DatabaseOperation {
     BeginShardSplit,
     CompleteShardSplit,
     AbortShardSplit,
+    BeginShardMerge,
+    AbortShardMerge,
     Detach,
     ReAttach,
     IncrementGeneration,
@@ -107,6 +228,24 @@ pub(crate) enum DatabaseOperation {
     GetLeader,
     UpdateLeader,
     SetPreferredAzs,
+    ListSafekeepers,
+    UpdateSafekeeper,
+    InsertTimeline,
+    GetTimeline,
+    ListTimelines,
+    UpdateTimeline,
+    DeleteTimeline,
+    InsertRollingRestart,
+    UpdateRollingRestart,
+    GetRollingRestart,
+    InsertOperation,
+    FinishOperation,
+    GetOperation,
+    ListOperations,
+    InsertAuditRecord,
+    ListAuditRecords,
+    PruneOperations,
+    PruneAuditRecords,
 }
 
 #[must_use]
@@ -142,11 +281,15 @@ impl Persistence {
     const MAX_CONNECTION_LIFETIME: Duration = Duration::from_secs(60);
 
     pub fn new(database_url: String) -> Self {
-        let manager = diesel::r2d2::ConnectionManager::<PgConnection>::new(database_url);
+        if let Some(path) = sqlite_path(&database_url) {
+            return Self::new_sqlite(path);
+        }
+
+        let manager = ConnectionManager::<PgConnection>::new(database_url);
 
         // We will use a connection pool: this is primarily to _limit_ our connection count, rather than to optimize time
         // to execute queries (database queries are not generally on latency-sensitive paths).
-        let connection_pool = diesel::r2d2::Pool::builder()
+        let connection_pool = Pool::builder()
             .max_size(Self::MAX_CONNECTIONS)
             .max_lifetime(Some(Self::MAX_CONNECTION_LIFETIME))
             .idle_timeout(Some(Self::IDLE_CONNECTION_TIMEOUT))
@@ -156,7 +299,28 @@ pub fn new(database_url: String) -> Self {
             .build(manager)
             .expect("Could not build connection pool");
 
-        Self { connection_pool }
+        Self {
+            connection_pool: ConnectionPool::Postgres(connection_pool),
+        }
+    }
+
+    fn new_sqlite(path: &str) -> Self {
+        let manager = ConnectionManager::<SqliteConnection>::new(path);
+
+        // SQLite only allows one writer at a time, so more connections would only wait on each
+        // other.  The one connection is kept forever: an in-memory database goes away with it.
+        let connection_pool = Pool::builder()
+            .max_size(1)
+            .max_lifetime(None)
+            .idle_timeout(None)
+            .test_on_check_out(true)
+            .connection_customizer(Box::new(SqliteConnectionCustomizer))
+            .build(manager)
+            .expect("Could not build connection pool");
+
+        Self {
+            connection_pool: ConnectionPool::Sqlite(connection_pool),
+        }
     }
 
     /// A helper for use during startup, where we would like to tolerate concurrent restarts of the
@@ -167,8 +331,12 @@ pub async fn await_connection(
     ) -> Result<(), diesel::ConnectionError> {
         let started_at = Instant::now();
         loop {
-            match PgConnection::establish(database_url) {
-                Ok(_) => {
+            let result = match sqlite_path(database_url) {
+                Some(path) => SqliteConnection::establish(path).map(|_| ()),
+                None => PgConnection::establish(database_url).map(|_| ()),
+            };
+            match result {
+                Ok(()) => {
                     tracing::info!("Connected to database.");
                     return Ok(());
                 }
@@ -188,19 +356,37 @@ pub async fn await_connection(
     pub(crate) async fn migration_run(&self) -> DatabaseResult<()> {
         use diesel_migrations::{HarnessWithOutput, MigrationHarness};
 
-        self.with_conn(move |conn| -> DatabaseResult<()> {
-            HarnessWithOutput::write_to_stdout(conn)
-                .run_pending_migrations(MIGRATIONS)
-                .map(|_| ())
-                .map_err(|e| DatabaseError::Migration(e.to_string()))
-        })
-        .await
+        match &self.connection_pool {
+            ConnectionPool::Postgres(pool) => {
+                Self::with_conn(pool, move |conn| -> DatabaseResult<()> {
+                    HarnessWithOutput::write_to_stdout(conn)
+                        .run_pending_migrations(MIGRATIONS)
+                        .map(|_| ())
+                        .map_err(|e| DatabaseError::Migration(e.to_string()))
+                })
+                .await
+            }
+            ConnectionPool::Sqlite(pool) => {
+                Self::with_conn(pool, move |conn| -> DatabaseResult<()> {
+                    HarnessWithOutput::write_to_stdout(conn)
+                        .run_pending_migrations(SQLITE_MIGRATIONS)
+                        .map(|_| ())
+                        .map_err(|e| DatabaseError::Migration(e.to_string()))
+                })
+                .await
+            }
+        }
     }
 
     /// Wraps `with_conn` in order to collect latency and error metrics
-    async fn with_measured_conn<F, R>(&self, op: DatabaseOperation, func: F) -> DatabaseResult<R>
+    async fn with_measured_conn<C, F, R>(
+        pool: &Pool<ConnectionManager<C>>,
+        op: DatabaseOperation,
+        func: F,
+    ) -> DatabaseResult<R>
     where
-        F: Fn(&mut PgConnection) -> DatabaseResult<R> + Send + 'static,
+        C: PersistenceConnection,
+        F: Fn(&mut C) -> DatabaseResult<R> + Send + 'static,
         R: Send + 'static,
     {
         let latency = &METRICS_REGISTRY
@@ -208,7 +394,7 @@ async fn with_measured_conn<F, R>(&self, op: DatabaseOperation, func: F) -> Data
             .storage_controller_database_query_latency;
         let _timer = latency.start_timer(DatabaseQueryLatencyLabelGroup { operation: op });
 
-        let res = self.with_conn(func).await;
+        let res = Self::with_conn(pool, func).await;
 
         if let Err(err) = &res {
             let error_counter = &METRICS_REGISTRY
@@ -224,9 +410,10 @@ async fn with_measured_conn<F, R>(&self, op: DatabaseOperation, func: F) -> Data
     }
 
     /// Call the provided function in a tokio blocking thread, with a Diesel database connection.
-    async fn with_conn<F, R>(&self, func: F) -> DatabaseResult<R>
+    async fn with_conn<C, F, R>(pool: &Pool<ConnectionManager<C>>, func: F) -> DatabaseResult<R>
     where
-        F: Fn(&mut PgConnection) -> DatabaseResult<R> + Send + 'static,
+        C: PersistenceConnection,
+        F: Fn(&mut C) -> DatabaseResult<R> + Send + 'static,
         R: Send + 'static,
     {
         // A generous allowance for how many times we may retry serializable transactions
@@ -234,11 +421,13 @@ async fn with_conn<F, R>(&self, func: F) -> DatabaseResult<R>
         // somehow engineer a situation where duelling transactions might otherwise live-lock.
         const MAX_RETRIES: usize = 128;
 
-        let mut conn = self.connection_pool.get()?;
+        // Waiting for a connection blocks, like the queries do
+        let pool = pool.clone();
         tokio::task::spawn_blocking(move || -> DatabaseResult<R> {
+            let mut conn = pool.get()?;
             let mut retry_count = 0;
             loop {
-                match conn.build_transaction().serializable().run(|c| func(c)) {
+                match conn.run_transaction(&func) {
                     Ok(r) => break Ok(r),
                     Err(
                         err @ DatabaseError::Query(diesel::result::Error::DatabaseError(
@@ -273,7 +462,8 @@ async fn with_conn<F, R>(&self, func: F) -> DatabaseResult<R>
     /// When a node is first registered, persist it before using it for anything
     pub(crate) async fn insert_node(&self, node: &Node) -> DatabaseResult<()> {
         let np = node.to_persistent();
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::InsertNode,
             move |conn| -> DatabaseResult<()> {
                 diesel::insert_into(crate::schema::nodes::table)
@@ -282,19 +472,17 @@ pub(crate) async fn insert_node(&self, node: &Node) -> DatabaseResult<()> {
                 Ok(())
             },
         )
-        .await
     }
 
     /// At startup, populate the list of nodes which our shards may be placed on
     pub(crate) async fn list_nodes(&self) -> DatabaseResult<Vec<NodePersistence>> {
-        let nodes: Vec<NodePersistence> = self
-            .with_measured_conn(
-                DatabaseOperation::ListNodes,
-                move |conn| -> DatabaseResult<_> {
-                    Ok(crate::schema::nodes::table.load::<NodePersistence>(conn)?)
-                },
-            )
-            .await?;
+        let nodes: Vec<NodePersistence> = with_measured_conn!(
+            self,
+            DatabaseOperation::ListNodes,
+            move |conn| -> DatabaseResult<_> {
+                Ok(crate::schema::nodes::table.load::<NodePersistence>(conn)?)
+            },
+        )?;
 
         tracing::info!("list_nodes: loaded {} nodes", nodes.len());
 
@@ -307,15 +495,37 @@ pub(crate) async fn update_node(
         input_scheduling: NodeSchedulingPolicy,
     ) -> DatabaseResult<()> {
         use crate::schema::nodes::dsl::*;
-        let updated = self
-            .with_measured_conn(DatabaseOperation::UpdateNode, move |conn| {
-                let updated = diesel::update(nodes)
-                    .filter(node_id.eq(input_node_id.0 as i64))
-                    .set((scheduling_policy.eq(String::from(input_scheduling)),))
-                    .execute(conn)?;
-                Ok(updated)
-            })
-            .await?;
+        let updated = with_measured_conn!(self, DatabaseOperation::UpdateNode, move |conn| {
+            let updated = diesel::update(nodes)
+                .filter(node_id.eq(input_node_id.0 as i64))
+                .set((scheduling_policy.eq(String::from(input_scheduling)),))
+                .execute(conn)?;
+            Ok(updated)
+        })?;
+
+        if updated != 1 {
+            Err(DatabaseError::Logical(format!(
+                "Node {node_id:?} not found for update",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) async fn update_node_labels(
+        &self,
+        input_node_id: NodeId,
+        input_labels: &BTreeMap<String, String>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::nodes::dsl::*;
+        let input_labels = serde_json::to_string(input_labels).unwrap();
+        let updated = with_measured_conn!(self, DatabaseOperation::UpdateNode, move |conn| {
+            let updated = diesel::update(nodes)
+                .filter(node_id.eq(input_node_id.0 as i64))
+                .set(labels.eq(&input_labels))
+                .execute(conn)?;
+            Ok(updated)
+        })?;
 
         if updated != 1 {
             Err(DatabaseError::Logical(format!(
@@ -329,13 +539,13 @@ pub(crate) async fn update_node(
     /// At startup, load the high level state for shards, such as their config + policy.  This will
     /// be enriched at runtime with state discovered on pageservers.
     pub(crate) async fn list_tenant_shards(&self) -> DatabaseResult<Vec<TenantShardPersistence>> {
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::ListTenantShards,
             move |conn| -> DatabaseResult<_> {
                 Ok(crate::schema::tenant_shards::table.load::<TenantShardPersistence>(conn)?)
             },
         )
-        .await
     }
 
     /// Tenants must be persisted before we schedule them for the first time.  This enables us
@@ -356,11 +566,12 @@ pub(crate) async fn insert_tenant_shards(
                 shard_number: t.shard_number,
                 shard_count: t.shard_count,
                 healthy: true,
-                last_scrubbed_at: now,
+                last_scrubbed_at: now.into(),
             })
             .collect::<Vec<_>>();
 
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::InsertTenantShards,
             move |conn| -> DatabaseResult<()> {
                 diesel::insert_into(tenant_shards::table)
@@ -373,14 +584,14 @@ pub(crate) async fn insert_tenant_shards(
                 Ok(())
             },
         )
-        .await
     }
 
     /// Ordering: call this _after_ deleting the tenant on pageservers, but _before_ dropping state for
     /// the tenant from memory on this server.
     pub(crate) async fn delete_tenant(&self, del_tenant_id: TenantId) -> DatabaseResult<()> {
         use crate::schema::tenant_shards::dsl::*;
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::DeleteTenant,
             move |conn| -> DatabaseResult<()> {
                 // `metadata_health` status (if exists) is also deleted based on the cascade behavior.
@@ -390,12 +601,12 @@ pub(crate) async fn delete_tenant(&self, del_tenant_id: TenantId) -> DatabaseRes
                 Ok(())
             },
         )
-        .await
     }
 
     pub(crate) async fn delete_node(&self, del_node_id: NodeId) -> DatabaseResult<()> {
         use crate::schema::nodes::dsl::*;
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::DeleteNode,
             move |conn| -> DatabaseResult<()> {
                 diesel::delete(nodes)
@@ -405,7 +616,6 @@ pub(crate) async fn delete_node(&self, del_node_id: NodeId) -> DatabaseResult<()
                 Ok(())
             },
         )
-        .await
     }
 
     /// When a tenant invokes the /re-attach API, this function is responsible for doing an efficient
@@ -419,38 +629,36 @@ pub(crate) async fn re_attach(
         use crate::schema::nodes::dsl::scheduling_policy;
         use crate::schema::nodes::dsl::*;
         use crate::schema::tenant_shards::dsl::*;
-        let updated = self
-            .with_measured_conn(DatabaseOperation::ReAttach, move |conn| {
-                let rows_updated = diesel::update(tenant_shards)
-                    .filter(generation_pageserver.eq(input_node_id.0 as i64))
-                    .set(generation.eq(generation + 1))
-                    .execute(conn)?;
+        let updated = with_measured_conn!(self, DatabaseOperation::ReAttach, move |conn| {
+            let rows_updated = diesel::update(tenant_shards)
+                .filter(generation_pageserver.eq(input_node_id.0 as i64))
+                .set(generation.eq(generation + 1))
+                .execute(conn)?;
 
-                tracing::info!("Incremented {} tenants' generations", rows_updated);
+            tracing::info!("Incremented {} tenants' generations", rows_updated);
 
-                // TODO: UPDATE+SELECT in one query
+            // TODO: UPDATE+SELECT in one query
 
-                let updated = tenant_shards
-                    .filter(generation_pageserver.eq(input_node_id.0 as i64))
-                    .select(TenantShardPersistence::as_select())
-                    .load(conn)?;
+            let updated = tenant_shards
+                .filter(generation_pageserver.eq(input_node_id.0 as i64))
+                .select(TenantShardPersistence::as_select())
+                .load(conn)?;
 
-                // If the node went through a drain and restart phase before re-attaching,
-                // then reset it's node scheduling policy to active.
-                diesel::update(nodes)
-                    .filter(node_id.eq(input_node_id.0 as i64))
-                    .filter(
-                        scheduling_policy
-                            .eq(String::from(NodeSchedulingPolicy::PauseForRestart))
-                            .or(scheduling_policy.eq(String::from(NodeSchedulingPolicy::Draining)))
-                            .or(scheduling_policy.eq(String::from(NodeSchedulingPolicy::Filling))),
-                    )
-                    .set(scheduling_policy.eq(String::from(NodeSchedulingPolicy::Active)))
-                    .execute(conn)?;
+            // If the node went through a drain and restart phase before re-attaching,
+            // then reset it's node scheduling policy to active.
+            diesel::update(nodes)
+                .filter(node_id.eq(input_node_id.0 as i64))
+                .filter(
+                    scheduling_policy
+                        .eq(String::from(NodeSchedulingPolicy::PauseForRestart))
+                        .or(scheduling_policy.eq(String::from(NodeSchedulingPolicy::Draining)))
+                        .or(scheduling_policy.eq(String::from(NodeSchedulingPolicy::Filling))),
+                )
+                .set(scheduling_policy.eq(String::from(NodeSchedulingPolicy::Active)))
+                .execute(conn)?;
 
-                Ok(updated)
-            })
-            .await?;
+            Ok(updated)
+        })?;
 
         let mut result = HashMap::new();
         for tsp in updated {
@@ -483,8 +691,8 @@ pub(crate) async fn increment_generation(
         node_id: NodeId,
     ) -> anyhow::Result<Generation> {
         use crate::schema::tenant_shards::dsl::*;
-        let updated = self
-            .with_measured_conn(DatabaseOperation::IncrementGeneration, move |conn| {
+        let updated =
+            with_measured_conn!(self, DatabaseOperation::IncrementGeneration, move |conn| {
                 let updated = diesel::update(tenant_shards)
                     .filter(tenant_id.eq(tenant_shard_id.tenant_id.to_string()))
                     .filter(shard_number.eq(tenant_shard_id.shard_number.0 as i32))
@@ -498,8 +706,7 @@ pub(crate) async fn increment_generation(
                     .get_result(conn)?;
 
                 Ok(updated)
-            })
-            .await?;
+            })?;
 
         // Generation is always non-null in the rseult: if the generation column had been NULL, then we
         // should have experienced an SQL Confilict error while executing a query that tries to increment it.
@@ -527,16 +734,14 @@ pub(crate) async fn tenant_generations(
         filter_tenant_id: TenantId,
     ) -> Result<Vec<ShardGenerationState>, DatabaseError> {
         use crate::schema::tenant_shards::dsl::*;
-        let rows = self
-            .with_measured_conn(DatabaseOperation::TenantGenerations, move |conn| {
-                let result = tenant_shards
-                    .filter(tenant_id.eq(filter_tenant_id.to_string()))
-                    .select(TenantShardPersistence::as_select())
-                    .order(shard_number)
-                    .load(conn)?;
-                Ok(result)
-            })
-            .await?;
+        let rows = with_measured_conn!(self, DatabaseOperation::TenantGenerations, move |conn| {
+            let result = tenant_shards
+                .filter(tenant_id.eq(filter_tenant_id.to_string()))
+                .select(TenantShardPersistence::as_select())
+                .order(shard_number)
+                .load(conn)?;
+            Ok(result)
+        })?;
 
         Ok(rows
             .into_iter()
@@ -582,17 +787,20 @@ pub(crate) async fn shard_generations(
                 break;
             }
 
-            let chunk_rows = self
-                .with_measured_conn(DatabaseOperation::ShardGenerations, move |conn| {
+            let chunk_rows =
+                with_measured_conn!(self, DatabaseOperation::ShardGenerations, move |conn| {
                     // diesel doesn't support multi-column IN queries, so we compose raw SQL.  No escaping is required because
                     // the inputs are strongly typed and cannot carry any user-supplied raw string content.
-                    let result : Vec<TenantShardPersistence> = diesel::sql_query(
-                        format!("SELECT * from tenant_shards where (tenant_id, shard_number, shard_count) in ({in_clause});").as_str()
-                    ).load(conn)?;
+                    // SQLite only matches row values against a subquery, hence the VALUES.
+                    let query = format!(
+                        "SELECT * from tenant_shards where (tenant_id, shard_number, shard_count) \
+                         in (VALUES {in_clause});"
+                    );
+                    let result: Vec<TenantShardPersistence> =
+                        diesel::sql_query(query.as_str()).load(conn)?;
 
                     Ok(result)
-                })
-                .await?;
+                })?;
             rows.extend(chunk_rows.into_iter())
         }
 
@@ -621,10 +829,12 @@ pub(crate) async fn update_tenant_shard(
         input_config: Option<TenantConfig>,
         input_generation: Option<Generation>,
         input_scheduling_policy: Option<ShardSchedulingPolicy>,
+        input_split_policy: Option<ShardSplitPolicy>,
+        input_placement_constraints: Option<PlacementConstraints>,
     ) -> DatabaseResult<()> {
         use crate::schema::tenant_shards::dsl::*;
 
-        self.with_measured_conn(DatabaseOperation::UpdateTenantShard, move |conn| {
+        with_measured_conn!(self, DatabaseOperation::UpdateTenantShard, move |conn| {
             let query = match tenant {
                 TenantFilter::Shard(tenant_shard_id) => diesel::update(tenant_shards)
                     .filter(tenant_id.eq(tenant_shard_id.tenant_id.to_string()))
@@ -643,6 +853,8 @@ struct ShardUpdate {
                 placement_policy: Option<String>,
                 config: Option<String>,
                 scheduling_policy: Option<String>,
+                split_policy: Option<String>,
+                placement_constraints: Option<String>,
             }
 
             let update = ShardUpdate {
@@ -655,13 +867,16 @@ struct ShardUpdate {
                     .map(|c| serde_json::to_string(&c).unwrap()),
                 scheduling_policy: input_scheduling_policy
                     .map(|p| serde_json::to_string(&p).unwrap()),
+                split_policy: input_split_policy.map(|p| serde_json::to_string(&p).unwrap()),
+                placement_constraints: input_placement_constraints
+                    .as_ref()
+                    .map(|c| serde_json::to_string(c).unwrap()),
             };
 
             query.set(update).execute(conn)?;
 
             Ok(())
-        })
-        .await?;
+        })?;
 
         Ok(())
     }
@@ -672,7 +887,7 @@ pub(crate) async fn set_tenant_shard_preferred_azs(
     ) -> DatabaseResult<Vec<(TenantShardId, AvailabilityZone)>> {
         use crate::schema::tenant_shards::dsl::*;
 
-        self.with_measured_conn(DatabaseOperation::SetPreferredAzs, move |conn| {
+        with_measured_conn!(self, DatabaseOperation::SetPreferredAzs, move |conn| {
             let mut shards_updated = Vec::default();
 
             for (tenant_shard_id, preferred_az) in preferred_azs.iter() {
@@ -690,12 +905,11 @@ pub(crate) async fn set_tenant_shard_preferred_azs(
 
             Ok(shards_updated)
         })
-        .await
     }
 
     pub(crate) async fn detach(&self, tenant_shard_id: TenantShardId) -> anyhow::Result<()> {
         use crate::schema::tenant_shards::dsl::*;
-        self.with_measured_conn(DatabaseOperation::Detach, move |conn| {
+        with_measured_conn!(self, DatabaseOperation::Detach, move |conn| {
             let updated = diesel::update(tenant_shards)
                 .filter(tenant_id.eq(tenant_shard_id.tenant_id.to_string()))
                 .filter(shard_number.eq(tenant_shard_id.shard_number.0 as i32))
@@ -707,8 +921,7 @@ pub(crate) async fn detach(&self, tenant_shard_id: TenantShardId) -> anyhow::Res
                 .execute(conn)?;
 
             Ok(updated)
-        })
-        .await?;
+        })?;
 
         Ok(())
     }
@@ -725,67 +938,137 @@ pub(crate) async fn begin_shard_split(
         parent_to_children: Vec<(TenantShardId, Vec<TenantShardPersistence>)>,
     ) -> DatabaseResult<()> {
         use crate::schema::tenant_shards::dsl::*;
-        self.with_measured_conn(DatabaseOperation::BeginShardSplit, move |conn| -> DatabaseResult<()> {
-            // Mark parent shards as splitting
+        with_measured_conn!(
+            self,
+            DatabaseOperation::BeginShardSplit,
+            move |conn| -> DatabaseResult<()> {
+                // Mark parent shards as splitting
 
-            let updated = diesel::update(tenant_shards)
-                .filter(tenant_id.eq(split_tenant_id.to_string()))
-                .filter(shard_count.eq(old_shard_count.literal() as i32))
-                .set((splitting.eq(1),))
-                .execute(conn)?;
-            if u8::try_from(updated)
-                .map_err(|_| DatabaseError::Logical(
-                    format!("Overflow existing shard count {} while splitting", updated))
-                )? != old_shard_count.count() {
-                // Perhaps a deletion or another split raced with this attempt to split, mutating
-                // the parent shards that we intend to split. In this case the split request should fail.
-                return Err(DatabaseError::Logical(
+                let updated = diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(split_tenant_id.to_string()))
+                    .filter(shard_count.eq(old_shard_count.literal() as i32))
+                    .set((splitting.eq(1),))
+                    .execute(conn)?;
+                if u8::try_from(updated).map_err(|_| {
+                    DatabaseError::Logical(format!(
+                        "Overflow existing shard count {} while splitting",
+                        updated
+                    ))
+                })? != old_shard_count.count()
+                {
+                    // Perhaps a deletion or another split raced with this attempt to split, mutating
+                    // the parent shards that we intend to split. In this case the split request should fail.
+                    return Err(DatabaseError::Logical(
                     format!("Unexpected existing shard count {updated} when preparing tenant for split (expected {})", old_shard_count.count())
                 ));
+                }
+
+                // FIXME: spurious clone to sidestep closure move rules
+                let parent_to_children = parent_to_children.clone();
+
+                // Insert child shards
+                for (parent_shard_id, children) in parent_to_children {
+                    let mut parent = crate::schema::tenant_shards::table
+                        .filter(tenant_id.eq(parent_shard_id.tenant_id.to_string()))
+                        .filter(shard_number.eq(parent_shard_id.shard_number.0 as i32))
+                        .filter(shard_count.eq(parent_shard_id.shard_count.literal() as i32))
+                        .load::<TenantShardPersistence>(conn)?;
+                    let parent = if parent.len() != 1 {
+                        return Err(DatabaseError::Logical(format!(
+                            "Parent shard {parent_shard_id} not found"
+                        )));
+                    } else {
+                        parent.pop().unwrap()
+                    };
+                    for mut shard in children {
+                        // Carry the parent's generation into the child
+                        shard.generation = parent.generation;
+
+                        debug_assert!(shard.splitting == SplitState::Splitting);
+                        diesel::insert_into(tenant_shards)
+                            .values(shard)
+                            .execute(conn)?;
+                    }
+                }
+
+                Ok(())
             }
+        )
+    }
 
-            // FIXME: spurious clone to sidestep closure move rules
-            let parent_to_children = parent_to_children.clone();
-
-            // Insert child shards
-            for (parent_shard_id, children) in parent_to_children {
-                let mut parent = crate::schema::tenant_shards::table
-                    .filter(tenant_id.eq(parent_shard_id.tenant_id.to_string()))
-                    .filter(shard_number.eq(parent_shard_id.shard_number.0 as i32))
-                    .filter(shard_count.eq(parent_shard_id.shard_count.literal() as i32))
-                    .load::<TenantShardPersistence>(conn)?;
-                let parent = if parent.len() != 1 {
-                    return Err(DatabaseError::Logical(format!(
-                        "Parent shard {parent_shard_id} not found"
-                    )));
-                } else {
-                    parent.pop().unwrap()
-                };
-                for mut shard in children {
-                    // Carry the parent's generation into the child
-                    shard.generation = parent.generation;
+    // When we start a shard merge, we must durably mark the tenant so that on restart, we know
+    // that we must go through recovery.
+    //
+    // Merged shards are created here with the generation and pageserver of the source shard
+    // that has the same shard number, which is where the merged shard will be attached.
+    pub(crate) async fn begin_shard_merge(
+        &self,
+        old_shard_count: ShardCount,
+        merge_tenant_id: TenantId,
+        merged: Vec<TenantShardPersistence>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::tenant_shards::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::BeginShardMerge,
+            move |conn| -> DatabaseResult<()> {
+                // Mark source shards as merging
+                let updated = diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(merge_tenant_id.to_string()))
+                    .filter(shard_count.eq(old_shard_count.literal() as i32))
+                    .filter(splitting.eq(0))
+                    .set((splitting.eq(2),))
+                    .execute(conn)?;
+                if updated != old_shard_count.count() as usize {
+                    // A deletion, split or another merge raced with this one.
+                    return Err(DatabaseError::Logical(
+                    format!("Unexpected existing shard count {updated} when preparing tenant for merge (expected {})", old_shard_count.count())
+                ));
+                }
+
+                // FIXME: spurious clone to sidestep closure move rules
+                let merged = merged.clone();
+
+                for mut shard in merged {
+                    let mut source = crate::schema::tenant_shards::table
+                        .filter(tenant_id.eq(merge_tenant_id.to_string()))
+                        .filter(shard_number.eq(shard.shard_number))
+                        .filter(shard_count.eq(old_shard_count.literal() as i32))
+                        .load::<TenantShardPersistence>(conn)?;
+                    let source = if source.len() != 1 {
+                        return Err(DatabaseError::Logical(format!(
+                            "Source shard {} of merge not found",
+                            shard.shard_number
+                        )));
+                    } else {
+                        source.pop().unwrap()
+                    };
+                    shard.generation = source.generation;
+                    shard.generation_pageserver = source.generation_pageserver;
 
-                    debug_assert!(shard.splitting == SplitState::Splitting);
+                    debug_assert!(shard.splitting == SplitState::Merging);
                     diesel::insert_into(tenant_shards)
                         .values(shard)
                         .execute(conn)?;
                 }
-            }
 
-            Ok(())
-        })
-        .await
+                Ok(())
+            }
+        )
     }
 
     // When we finish shard splitting, we must atomically clean up the old shards
     // and insert the new shards, and clear the splitting marker.
+    //
+    // This also completes a shard merge, where the old shards are the merge's sources.
     pub(crate) async fn complete_shard_split(
         &self,
         split_tenant_id: TenantId,
         old_shard_count: ShardCount,
     ) -> DatabaseResult<()> {
         use crate::schema::tenant_shards::dsl::*;
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::CompleteShardSplit,
             move |conn| -> DatabaseResult<()> {
                 // Drop parent shards
@@ -804,7 +1087,6 @@ pub(crate) async fn complete_shard_split(
                 Ok(())
             },
         )
-        .await
     }
 
     /// Used when the remote part of a shard split failed: we will revert the database state to have only
@@ -815,7 +1097,8 @@ pub(crate) async fn abort_shard_split(
         new_shard_count: ShardCount,
     ) -> DatabaseResult<AbortShardSplitStatus> {
         use crate::schema::tenant_shards::dsl::*;
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::AbortShardSplit,
             move |conn| -> DatabaseResult<AbortShardSplitStatus> {
                 // Clear the splitting state on parent shards
@@ -848,7 +1131,53 @@ pub(crate) async fn abort_shard_split(
                 Ok(AbortShardSplitStatus::Aborted)
             },
         )
-        .await
+    }
+
+    /// Used when the remote part of a shard merge failed: we will revert the database state to have only
+    /// the source shards, with SplitState::Idle.
+    ///
+    /// The source shards' generations are incremented: preparing for a merge stops their uploads to
+    /// remote storage, and attaching them in a new generation is what makes their pageservers restart them.
+    pub(crate) async fn abort_shard_merge(
+        &self,
+        merge_tenant_id: TenantId,
+        new_shard_count: ShardCount,
+    ) -> DatabaseResult<AbortShardSplitStatus> {
+        use crate::schema::tenant_shards::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::AbortShardMerge,
+            move |conn| -> DatabaseResult<AbortShardSplitStatus> {
+                // Clear the merging state on source shards
+                let updated = diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(merge_tenant_id.to_string()))
+                    .filter(shard_count.ne(new_shard_count.literal() as i32))
+                    .set((splitting.eq(0), generation.eq(generation + 1)))
+                    .execute(conn)?;
+
+                // Source shards are already gone: we cannot abort.
+                if updated == 0 {
+                    return Ok(AbortShardSplitStatus::Complete);
+                }
+
+                // Sanity check: if source shards were present, there should be more of them
+                // than merged shards.
+                if updated <= new_shard_count.count() as usize {
+                    return Err(DatabaseError::Logical(format!(
+                        "Unexpected source shard count {updated} while aborting merge to \
+                            count {new_shard_count:?} on tenant {merge_tenant_id}"
+                    )));
+                }
+
+                // Erase merged shards
+                diesel::delete(tenant_shards)
+                    .filter(tenant_id.eq(merge_tenant_id.to_string()))
+                    .filter(shard_count.eq(new_shard_count.literal() as i32))
+                    .execute(conn)?;
+
+                Ok(AbortShardSplitStatus::Aborted)
+            },
+        )
     }
 
     /// Stores all the latest metadata health updates durably. Updates existing entry on conflict.
@@ -863,26 +1192,26 @@ pub(crate) async fn update_metadata_health_records(
     ) -> DatabaseResult<()> {
         use crate::schema::metadata_health::dsl::*;
 
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::UpdateMetadataHealth,
             move |conn| -> DatabaseResult<_> {
                 diesel::insert_into(metadata_health)
                     .values(&healthy_records)
                     .on_conflict((tenant_id, shard_number, shard_count))
                     .do_update()
-                    .set((healthy.eq(true), last_scrubbed_at.eq(now)))
+                    .set((healthy.eq(true), last_scrubbed_at.eq(Timestamp(now))))
                     .execute(conn)?;
 
                 diesel::insert_into(metadata_health)
                     .values(&unhealthy_records)
                     .on_conflict((tenant_id, shard_number, shard_count))
                     .do_update()
-                    .set((healthy.eq(false), last_scrubbed_at.eq(now)))
+                    .set((healthy.eq(false), last_scrubbed_at.eq(Timestamp(now))))
                     .execute(conn)?;
                 Ok(())
             },
         )
-        .await
     }
 
     /// Lists all the metadata health records.
@@ -890,7 +1219,8 @@ pub(crate) async fn update_metadata_health_records(
     pub(crate) async fn list_metadata_health_records(
         &self,
     ) -> DatabaseResult<Vec<MetadataHealthPersistence>> {
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::ListMetadataHealth,
             move |conn| -> DatabaseResult<_> {
                 Ok(
@@ -899,7 +1229,6 @@ pub(crate) async fn list_metadata_health_records(
                 )
             },
         )
-        .await
     }
 
     /// Lists all the metadata health records that is unhealthy.
@@ -908,7 +1237,8 @@ pub(crate) async fn list_unhealthy_metadata_health_records(
         &self,
     ) -> DatabaseResult<Vec<MetadataHealthPersistence>> {
         use crate::schema::metadata_health::dsl::*;
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::ListMetadataHealthUnhealthy,
             move |conn| -> DatabaseResult<_> {
                 Ok(crate::schema::metadata_health::table
@@ -916,7 +1246,6 @@ pub(crate) async fn list_unhealthy_metadata_health_records(
                     .load::<MetadataHealthPersistence>(conn)?)
             },
         )
-        .await
     }
 
     /// Lists all the metadata health records that have not been updated since an `earlier` time.
@@ -927,29 +1256,28 @@ pub(crate) async fn list_outdated_metadata_health_records(
     ) -> DatabaseResult<Vec<MetadataHealthPersistence>> {
         use crate::schema::metadata_health::dsl::*;
 
-        self.with_measured_conn(
+        with_measured_conn!(
+            self,
             DatabaseOperation::ListMetadataHealthOutdated,
             move |conn| -> DatabaseResult<_> {
-                let query = metadata_health.filter(last_scrubbed_at.lt(earlier));
+                let query = metadata_health.filter(last_scrubbed_at.lt(Timestamp(earlier)));
                 let res = query.load::<MetadataHealthPersistence>(conn)?;
 
                 Ok(res)
             },
         )
-        .await
     }
 
     /// Get the current entry from the `leader` table if one exists.
     /// It is an error for the table to contain more than one entry.
     pub(crate) async fn get_leader(&self) -> DatabaseResult<Option<ControllerPersistence>> {
-        let mut leader: Vec<ControllerPersistence> = self
-            .with_measured_conn(
-                DatabaseOperation::GetLeader,
-                move |conn| -> DatabaseResult<_> {
-                    Ok(crate::schema::controllers::table.load::<ControllerPersistence>(conn)?)
-                },
-            )
-            .await?;
+        let mut leader: Vec<ControllerPersistence> = with_measured_conn!(
+            self,
+            DatabaseOperation::GetLeader,
+            move |conn| -> DatabaseResult<_> {
+                Ok(crate::schema::controllers::table.load::<ControllerPersistence>(conn)?)
+            },
+        )?;
 
         if leader.len() > 1 {
             return Err(DatabaseError::Logical(format!(
@@ -970,28 +1298,27 @@ pub(crate) async fn update_leader(
     ) -> DatabaseResult<()> {
         use crate::schema::controllers::dsl::*;
 
-        let updated = self
-            .with_measured_conn(
-                DatabaseOperation::UpdateLeader,
-                move |conn| -> DatabaseResult<usize> {
-                    let updated = match &prev {
-                        Some(prev) => diesel::update(controllers)
-                            .filter(address.eq(prev.address.clone()))
-                            .filter(started_at.eq(prev.started_at))
-                            .set((
-                                address.eq(new.address.clone()),
-                                started_at.eq(new.started_at),
-                            ))
-                            .execute(conn)?,
-                        None => diesel::insert_into(controllers)
-                            .values(new.clone())
-                            .execute(conn)?,
-                    };
+        let updated = with_measured_conn!(
+            self,
+            DatabaseOperation::UpdateLeader,
+            move |conn| -> DatabaseResult<usize> {
+                let updated = match &prev {
+                    Some(prev) => diesel::update(controllers)
+                        .filter(address.eq(prev.address.clone()))
+                        .filter(started_at.eq(prev.started_at))
+                        .set((
+                            address.eq(new.address.clone()),
+                            started_at.eq(new.started_at),
+                        ))
+                        .execute(conn)?,
+                    None => diesel::insert_into(controllers)
+                        .values(new.clone())
+                        .execute(conn)?,
+                };
 
-                    Ok(updated)
-                },
-            )
-            .await?;
+                Ok(updated)
+            },
+        )?;
 
         if updated == 0 {
             return Err(DatabaseError::Logical(
@@ -1007,13 +1334,12 @@ pub(crate) async fn safekeeper_get(
         id: i64,
     ) -> Result<SafekeeperPersistence, DatabaseError> {
         use crate::schema::safekeepers::dsl::{id as id_column, safekeepers};
-        self.with_conn(move |conn| -> DatabaseResult<SafekeeperPersistence> {
+        with_conn!(self, move |conn| -> DatabaseResult<SafekeeperPersistence> {
             Ok(safekeepers
                 .filter(id_column.eq(&id))
                 .select(SafekeeperPersistence::as_select())
                 .get_result(conn)?)
         })
-        .await
     }
 
     pub(crate) async fn safekeeper_upsert(
@@ -1022,7 +1348,7 @@ pub(crate) async fn safekeeper_upsert(
     ) -> Result<(), DatabaseError> {
         use crate::schema::safekeepers::dsl::*;
 
-        self.with_conn(move |conn| -> DatabaseResult<()> {
+        with_conn!(self, move |conn| -> DatabaseResult<()> {
             let bind = record.as_insert_or_update();
 
             let inserted_updated = diesel::insert_into(safekeepers)
@@ -1041,7 +1367,471 @@ pub(crate) async fn safekeeper_upsert(
 
             Ok(())
         })
-        .await
+    }
+
+    pub(crate) async fn list_safekeepers(&self) -> DatabaseResult<Vec<SafekeeperPersistence>> {
+        with_measured_conn!(
+            self,
+            DatabaseOperation::ListSafekeepers,
+            move |conn| -> DatabaseResult<_> {
+                Ok(crate::schema::safekeepers::table
+                    .select(SafekeeperPersistence::as_select())
+                    .load(conn)?)
+            },
+        )
+    }
+
+    pub(crate) async fn set_safekeeper_scheduling_policy(
+        &self,
+        input_id: i64,
+        input_scheduling: SkSchedulingPolicy,
+    ) -> DatabaseResult<()> {
+        use crate::schema::safekeepers::dsl::*;
+        let updated =
+            with_measured_conn!(self, DatabaseOperation::UpdateSafekeeper, move |conn| {
+                let updated = diesel::update(safekeepers)
+                    .filter(id.eq(input_id))
+                    .set(scheduling_policy.eq(String::from(input_scheduling)))
+                    .execute(conn)?;
+                Ok(updated)
+            })?;
+
+        if updated != 1 {
+            Err(DatabaseError::Logical(format!(
+                "Safekeeper {input_id} not found for update",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Persist the initial placement of a timeline onto safekeepers.  This must happen before the
+    /// timeline is created on any safekeeper, so that a crash leaves a record we can clean up.
+    pub(crate) async fn insert_timeline(
+        &self,
+        timeline: TimelinePersistence,
+    ) -> DatabaseResult<()> {
+        with_measured_conn!(
+            self,
+            DatabaseOperation::InsertTimeline,
+            move |conn| -> DatabaseResult<()> {
+                diesel::insert_into(crate::schema::timelines::table)
+                    .values(&timeline)
+                    .execute(conn)?;
+                Ok(())
+            },
+        )
+    }
+
+    pub(crate) async fn get_timeline(
+        &self,
+        input_tenant_id: TenantId,
+        input_timeline_id: TimelineId,
+    ) -> DatabaseResult<Option<TimelinePersistence>> {
+        use crate::schema::timelines::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::GetTimeline,
+            move |conn| -> DatabaseResult<_> {
+                Ok(timelines
+                    .filter(tenant_id.eq(input_tenant_id.to_string()))
+                    .filter(timeline_id.eq(input_timeline_id.to_string()))
+                    .select(TimelinePersistence::as_select())
+                    .first(conn)
+                    .optional()?)
+            },
+        )
+    }
+
+    pub(crate) async fn list_tenant_timelines(
+        &self,
+        input_tenant_id: TenantId,
+    ) -> DatabaseResult<Vec<TimelinePersistence>> {
+        use crate::schema::timelines::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::ListTimelines,
+            move |conn| -> DatabaseResult<_> {
+                Ok(timelines
+                    .filter(tenant_id.eq(input_tenant_id.to_string()))
+                    .select(TimelinePersistence::as_select())
+                    .load(conn)?)
+            },
+        )
+    }
+
+    /// List all timelines which have the given safekeeper in their safekeeper set.
+    pub(crate) async fn list_timelines_on_safekeeper(
+        &self,
+        safekeeper_id: i64,
+    ) -> DatabaseResult<Vec<TimelinePersistence>> {
+        use crate::schema::timelines::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::ListTimelines,
+            move |conn| -> DatabaseResult<_> {
+                Ok(timelines
+                    .filter(sk_set_contains(conn, safekeeper_id))
+                    .select(TimelinePersistence::as_select())
+                    .load(conn)?)
+            },
+        )
+    }
+
+    /// Count how many timelines are placed on each safekeeper.  Safekeepers without
+    /// any timelines are omitted from the result.
+    pub(crate) async fn count_timelines_per_safekeeper(&self) -> DatabaseResult<HashMap<i64, u64>> {
+        let sets: Vec<Int8Vec> = with_measured_conn!(
+            self,
+            DatabaseOperation::ListTimelines,
+            move |conn| -> DatabaseResult<_> {
+                Ok(crate::schema::timelines::table
+                    .select(crate::schema::timelines::sk_set)
+                    .load(conn)?)
+            },
+        )?;
+
+        let mut result = HashMap::new();
+        for sk_id in sets.into_iter().flat_map(|set| set.0) {
+            *result.entry(sk_id).or_default() += 1;
+        }
+        Ok(result)
+    }
+
+    /// Replace the safekeeper set of a timeline with compare-exchange semantics on the
+    /// generation: if the generation in the database is not `prev_generation`, then somebody
+    /// else changed the set concurrently and the update fails.
+    pub(crate) async fn update_timeline_sk_set(
+        &self,
+        input_tenant_id: TenantId,
+        input_timeline_id: TimelineId,
+        prev_generation: i32,
+        new_sk_set: Vec<i64>,
+        new_migrating_to: Option<i64>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::timelines::dsl::*;
+        let updated = with_measured_conn!(self, DatabaseOperation::UpdateTimeline, move |conn| {
+            let updated = diesel::update(timelines)
+                .filter(tenant_id.eq(input_tenant_id.to_string()))
+                .filter(timeline_id.eq(input_timeline_id.to_string()))
+                .filter(generation.eq(prev_generation))
+                .set((
+                    generation.eq(generation + 1),
+                    sk_set.eq(Int8Vec(new_sk_set.clone())),
+                    migrating_to.eq(new_migrating_to),
+                ))
+                .execute(conn)?;
+            Ok(updated)
+        })?;
+
+        if updated != 1 {
+            Err(DatabaseError::Logical(format!(
+                "Timeline {input_tenant_id}/{input_timeline_id} not found in generation {prev_generation}",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Drop the safekeeper placement of one timeline, or of all timelines of a tenant
+    /// if `input_timeline_id` is None.
+    pub(crate) async fn delete_timelines(
+        &self,
+        input_tenant_id: TenantId,
+        input_timeline_id: Option<TimelineId>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::timelines::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::DeleteTimeline,
+            move |conn| -> DatabaseResult<()> {
+                let query = diesel::delete(timelines)
+                    .filter(tenant_id.eq(input_tenant_id.to_string()))
+                    .into_boxed();
+                let query = match input_timeline_id {
+                    Some(input_timeline_id) => {
+                        query.filter(timeline_id.eq(input_timeline_id.to_string()))
+                    }
+                    None => query,
+                };
+                query.execute(conn)?;
+                Ok(())
+            },
+        )
+    }
+
+    /// Record the start of a rolling restart, returning its ID.
+    pub(crate) async fn insert_rolling_restart(
+        &self,
+        input_status: String,
+        input_started_at: chrono::DateTime<chrono::Utc>,
+    ) -> DatabaseResult<i64> {
+        use crate::schema::rolling_restarts::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::InsertRollingRestart,
+            move |conn| -> DatabaseResult<i64> {
+                Ok(diesel::insert_into(rolling_restarts)
+                    .values((
+                        status.eq(&input_status),
+                        started_at.eq(Timestamp(input_started_at)),
+                        updated_at.eq(Timestamp(input_started_at)),
+                    ))
+                    .returning(id)
+                    .get_result(conn)?)
+            },
+        )
+    }
+
+    pub(crate) async fn update_rolling_restart(
+        &self,
+        input_id: i64,
+        input_status: String,
+        input_updated_at: chrono::DateTime<chrono::Utc>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::rolling_restarts::dsl::*;
+        let updated = with_measured_conn!(
+            self,
+            DatabaseOperation::UpdateRollingRestart,
+            move |conn| -> DatabaseResult<usize> {
+                Ok(diesel::update(rolling_restarts)
+                    .filter(id.eq(input_id))
+                    .set((
+                        status.eq(&input_status),
+                        updated_at.eq(Timestamp(input_updated_at)),
+                    ))
+                    .execute(conn)?)
+            },
+        )?;
+
+        if updated != 1 {
+            Err(DatabaseError::Logical(format!(
+                "Rolling restart {input_id} not found",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Load the most recently started rolling restart, if there ever was one.
+    pub(crate) async fn get_latest_rolling_restart(
+        &self,
+    ) -> DatabaseResult<Option<RollingRestartPersistence>> {
+        use crate::schema::rolling_restarts::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::GetRollingRestart,
+            move |conn| -> DatabaseResult<_> {
+                Ok(rolling_restarts
+                    .order(id.desc())
+                    .select(RollingRestartPersistence::as_select())
+                    .first(conn)
+                    .optional()?)
+            },
+        )
+    }
+
+    /// Record the start of a long-running operation, returning its ID.
+    pub(crate) async fn insert_operation(
+        &self,
+        input_kind: OperationKind,
+        input_tenant_id: Option<TenantId>,
+        input_node_id: Option<NodeId>,
+        input_started_at: chrono::DateTime<chrono::Utc>,
+    ) -> DatabaseResult<i64> {
+        use crate::schema::operations::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::InsertOperation,
+            move |conn| -> DatabaseResult<i64> {
+                Ok(diesel::insert_into(operations)
+                    .values((
+                        kind.eq(input_kind.to_string()),
+                        state.eq(OperationState::Running.to_string()),
+                        tenant_id.eq(input_tenant_id.map(|t| t.to_string())),
+                        node_id.eq(input_node_id.map(|n| n.0 as i64)),
+                        started_at.eq(Timestamp(input_started_at)),
+                        updated_at.eq(Timestamp(input_started_at)),
+                    ))
+                    .returning(id)
+                    .get_result(conn)?)
+            },
+        )
+    }
+
+    pub(crate) async fn finish_operation(
+        &self,
+        input_id: i64,
+        input_state: OperationState,
+        input_progress: Option<OperationProgress>,
+        input_error: Option<String>,
+        input_finished_at: chrono::DateTime<chrono::Utc>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::operations::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::FinishOperation,
+            move |conn| -> DatabaseResult<()> {
+                diesel::update(operations)
+                    .filter(id.eq(input_id))
+                    .set((
+                        state.eq(input_state.to_string()),
+                        progress_completed.eq(input_progress.map(|p| p.completed as i64)),
+                        progress_total.eq(input_progress.map(|p| p.total as i64)),
+                        error.eq(input_error.clone()),
+                        updated_at.eq(Timestamp(input_finished_at)),
+                        finished_at.eq(Timestamp(input_finished_at)),
+                    ))
+                    .execute(conn)?;
+                Ok(())
+            },
+        )
+    }
+
+    /// Operations which were running when the previous controller went away will never finish:
+    /// mark them failed.
+    pub(crate) async fn fail_interrupted_operations(
+        &self,
+        input_finished_at: chrono::DateTime<chrono::Utc>,
+    ) -> DatabaseResult<usize> {
+        use crate::schema::operations::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::FinishOperation,
+            move |conn| -> DatabaseResult<usize> {
+                Ok(diesel::update(operations)
+                    .filter(state.eq(OperationState::Running.to_string()))
+                    .set((
+                        state.eq(OperationState::Failed.to_string()),
+                        error.eq("Interrupted by a restart of the storage controller"),
+                        updated_at.eq(Timestamp(input_finished_at)),
+                        finished_at.eq(Timestamp(input_finished_at)),
+                    ))
+                    .execute(conn)?)
+            },
+        )
+    }
+
+    pub(crate) async fn get_operation(
+        &self,
+        input_id: i64,
+    ) -> DatabaseResult<Option<OperationPersistence>> {
+        use crate::schema::operations::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::GetOperation,
+            move |conn| -> DatabaseResult<_> {
+                Ok(operations
+                    .filter(id.eq(input_id))
+                    .select(OperationPersistence::as_select())
+                    .first(conn)
+                    .optional()?)
+            },
+        )
+    }
+
+    /// List the most recent operations matching the filter, newest first.
+    pub(crate) async fn list_operations(
+        &self,
+        filter: OperationFilter,
+    ) -> DatabaseResult<Vec<OperationPersistence>> {
+        use crate::schema::operations::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::ListOperations,
+            move |conn| -> DatabaseResult<_> {
+                let mut query = operations.into_boxed();
+                if let Some(filter_tenant_id) = filter.tenant_id {
+                    query = query.filter(tenant_id.eq(filter_tenant_id.to_string()));
+                }
+                if let Some(filter_node_id) = filter.node_id {
+                    query = query.filter(node_id.eq(filter_node_id.0 as i64));
+                }
+                if filter.running_only {
+                    query = query.filter(state.eq(OperationState::Running.to_string()));
+                }
+                Ok(query
+                    .order(id.desc())
+                    .limit(filter.limit)
+                    .select(OperationPersistence::as_select())
+                    .load(conn)?)
+            },
+        )
+    }
+
+    /// Delete operations which finished before the given time.  Running operations are kept.
+    pub(crate) async fn prune_operations(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> DatabaseResult<usize> {
+        use crate::schema::operations::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::PruneOperations,
+            move |conn| -> DatabaseResult<usize> {
+                Ok(diesel::delete(operations)
+                    .filter(finished_at.lt(Timestamp(before)))
+                    .execute(conn)?)
+            },
+        )
+    }
+
+    /// Append a record to the audit log.
+    pub(crate) async fn insert_audit_record(&self, record: NewAuditRecord) -> DatabaseResult<()> {
+        use crate::schema::audit_log;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::InsertAuditRecord,
+            move |conn| -> DatabaseResult<()> {
+                diesel::insert_into(audit_log::table)
+                    .values(&record)
+                    .execute(conn)?;
+                Ok(())
+            },
+        )
+    }
+
+    /// List the most recent audit records matching the filter, newest first.
+    pub(crate) async fn list_audit_records(
+        &self,
+        filter: AuditFilter,
+    ) -> DatabaseResult<Vec<AuditRecordPersistence>> {
+        use crate::schema::audit_log::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::ListAuditRecords,
+            move |conn| -> DatabaseResult<_> {
+                let mut query = audit_log.into_boxed();
+                if let Some(filter_tenant_id) = filter.tenant_id {
+                    query = query.filter(tenant_id.eq(filter_tenant_id.to_string()));
+                }
+                if let Some(filter_node_id) = filter.node_id {
+                    query = query.filter(node_id.eq(filter_node_id.0 as i64));
+                }
+                Ok(query
+                    .order(id.desc())
+                    .limit(filter.limit)
+                    .select(AuditRecordPersistence::as_select())
+                    .load(conn)?)
+            },
+        )
+    }
+
+    /// Delete audit records recorded before the given time.
+    pub(crate) async fn prune_audit_records(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> DatabaseResult<usize> {
+        use crate::schema::audit_log::dsl::*;
+        with_measured_conn!(
+            self,
+            DatabaseOperation::PruneAuditRecords,
+            move |conn| -> DatabaseResult<usize> {
+                Ok(diesel::delete(audit_log)
+                    .filter(recorded_at.lt(Timestamp(before)))
+                    .execute(conn)?)
+            },
+        )
     }
 }
 
@@ -1084,9 +1874,27 @@ pub(crate) struct TenantShardPersistence {
     // availability zone in order to minimise the chances of cross-AZ communication
     // with compute.
     pub(crate) preferred_az_id: Option<String>,
+
+    #[serde(default = "default_split_policy")]
+    pub(crate) split_policy: String,
+
+    #[serde(default = "default_placement_constraints")]
+    pub(crate) placement_constraints: String,
+}
+
+fn default_split_policy() -> String {
+    serde_json::to_string(&ShardSplitPolicy::default()).unwrap()
+}
+
+fn default_placement_constraints() -> String {
+    serde_json::to_string(&PlacementConstraints::default()).unwrap()
 }
 
 impl TenantShardPersistence {
+    pub(crate) fn get_placement_constraints(&self) -> PlacementConstraints {
+        serde_json::from_str(&self.placement_constraints).unwrap()
+    }
+
     pub(crate) fn get_shard_identity(&self) -> Result<ShardIdentity, ShardConfigError> {
         if self.shard_count == 0 {
             Ok(ShardIdentity::unsharded())
@@ -1119,6 +1927,7 @@ pub(crate) struct NodePersistence {
     pub(crate) listen_pg_addr: String,
     pub(crate) listen_pg_port: i32,
     pub(crate) availability_zone_id: String,
+    pub(crate) labels: String,
 }
 
 /// Tenant metadata health status that are stored durably.
@@ -1133,7 +1942,7 @@ pub(crate) struct MetadataHealthPersistence {
     pub(crate) shard_count: i32,
 
     pub(crate) healthy: bool,
-    pub(crate) last_scrubbed_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) last_scrubbed_at: Timestamp,
 }
 
 impl MetadataHealthPersistence {
@@ -1151,7 +1960,7 @@ pub fn new(
             shard_number,
             shard_count,
             healthy,
-            last_scrubbed_at,
+            last_scrubbed_at: last_scrubbed_at.into(),
         }
     }
 
@@ -1172,7 +1981,7 @@ fn from(value: MetadataHealthPersistence) -> Self {
                 .get_tenant_shard_id()
                 .expect("stored tenant id should be valid"),
             healthy: value.healthy,
-            last_scrubbed_at: value.last_scrubbed_at,
+            last_scrubbed_at: value.last_scrubbed_at.into(),
         }
     }
 }
@@ -1183,7 +1992,7 @@ fn from(value: MetadataHealthPersistence) -> Self {
 #[diesel(table_name = crate::schema::controllers)]
 pub(crate) struct ControllerPersistence {
     pub(crate) address: String,
-    pub(crate) started_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) started_at: Timestamp,
 }
 
 #[derive(Serialize, Deserialize, Queryable, Selectable, Eq, PartialEq, Debug, Clone)]
@@ -1200,9 +2009,31 @@ pub(crate) struct SafekeeperPersistence {
     pub(crate) active: bool,
     pub(crate) http_port: i32,
     pub(crate) availability_zone_id: String,
+    /// Owned by the storage controller: the upsert API never overwrites it.
+    #[serde(default = "default_sk_scheduling_policy")]
+    pub(crate) scheduling_policy: String,
+}
+
+fn default_sk_scheduling_policy() -> String {
+    String::from(SkSchedulingPolicy::Active)
 }
 
 impl SafekeeperPersistence {
+    pub(crate) fn get_scheduling_policy(&self) -> SkSchedulingPolicy {
+        SkSchedulingPolicy::from_str(&self.scheduling_policy).unwrap_or_else(|e| {
+            tracing::warn!("Bad scheduling policy on safekeeper {}: {e}", self.id);
+            SkSchedulingPolicy::Pause
+        })
+    }
+
+    pub(crate) fn get_node_id(&self) -> NodeId {
+        NodeId(self.id as u64)
+    }
+
+    pub(crate) fn base_url(&self) -> String {
+        format!("http://{}:{}", self.host, self.http_port)
+    }
+
     fn as_insert_or_update(&self) -> InsertUpdateSafekeeper<'_> {
         InsertUpdateSafekeeper {
             id: self.id,
@@ -1229,3 +2060,315 @@ struct InsertUpdateSafekeeper<'a> {
     http_port: i32,
     availability_zone_id: &'a str,
 }
+
+/// Placement of a timeline onto safekeepers, stored durably.
+#[derive(Queryable, Selectable, Insertable, Eq, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::timelines)]
+pub(crate) struct TimelinePersistence {
+    pub(crate) tenant_id: String,
+    pub(crate) timeline_id: String,
+    pub(crate) generation: i32,
+    pub(crate) sk_set: Int8Vec,
+    /// The safekeeper which joined `sk_set` to replace another one, while a migration is
+    /// in progress
+    pub(crate) migrating_to: Option<i64>,
+}
+
+impl TimelinePersistence {
+    pub(crate) fn get_sk_set(&self) -> Vec<NodeId> {
+        self.sk_set.0.iter().map(|id| NodeId(*id as u64)).collect()
+    }
+}
+
+/// A rolling restart, stored durably so that it survives controller failover.
+#[derive(Queryable, Selectable, Eq, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::rolling_restarts)]
+pub(crate) struct RollingRestartPersistence {
+    pub(crate) id: i64,
+    pub(crate) status: String,
+    pub(crate) started_at: Timestamp,
+    pub(crate) updated_at: Timestamp,
+}
+
+/// A long-running operation, as stored durably.
+#[derive(Queryable, Selectable, Eq, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::operations)]
+pub(crate) struct OperationPersistence {
+    pub(crate) id: i64,
+    pub(crate) kind: String,
+    pub(crate) state: String,
+    pub(crate) tenant_id: Option<String>,
+    pub(crate) node_id: Option<i64>,
+    pub(crate) progress_completed: Option<i64>,
+    pub(crate) progress_total: Option<i64>,
+    pub(crate) error: Option<String>,
+    pub(crate) started_at: Timestamp,
+    pub(crate) updated_at: Timestamp,
+    pub(crate) finished_at: Option<Timestamp>,
+}
+
+impl OperationPersistence {
+    pub(crate) fn into_status(self) -> OperationStatus {
+        OperationStatus {
+            id: self.id,
+            kind: OperationKind::from_str(&self.kind).expect("Bad operation kind in DB"),
+            state: OperationState::from_str(&self.state).expect("Bad operation state in DB"),
+            tenant_id: self
+                .tenant_id
+                .map(|t| TenantId::from_str(&t).expect("Bad tenant ID in DB")),
+            node_id: self.node_id.map(|n| NodeId(n as u64)),
+            progress: match (self.progress_completed, self.progress_total) {
+                (Some(completed), Some(total)) => Some(OperationProgress {
+                    completed: completed as u64,
+                    total: total as u64,
+                }),
+                _ => None,
+            },
+            error: self.error,
+            cancellable: false,
+            started_at: self.started_at.into(),
+            updated_at: self.updated_at.into(),
+            finished_at: self.finished_at.map(Into::into),
+        }
+    }
+}
+
+/// Which operations to list with [`Persistence::list_operations`]
+pub(crate) struct OperationFilter {
+    pub(crate) tenant_id: Option<TenantId>,
+    pub(crate) node_id: Option<NodeId>,
+    pub(crate) running_only: bool,
+    pub(crate) limit: i64,
+}
+
+/// A record to append to the audit log
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::audit_log)]
+pub(crate) struct NewAuditRecord {
+    pub(crate) recorded_at: Timestamp,
+    pub(crate) subject: Option<String>,
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) tenant_id: Option<String>,
+    pub(crate) node_id: Option<i64>,
+    pub(crate) status: i32,
+    pub(crate) request: Option<String>,
+    pub(crate) state_before: Option<String>,
+    pub(crate) state_after: Option<String>,
+}
+
+/// A record of the audit log, as stored durably.
+#[derive(Queryable, Selectable, Eq, PartialEq, Debug, Clone)]
+#[diesel(table_name = crate::schema::audit_log)]
+pub(crate) struct AuditRecordPersistence {
+    pub(crate) id: i64,
+    pub(crate) recorded_at: Timestamp,
+    pub(crate) subject: Option<String>,
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) tenant_id: Option<String>,
+    pub(crate) node_id: Option<i64>,
+    pub(crate) status: i32,
+    pub(crate) request: Option<String>,
+    pub(crate) state_before: Option<String>,
+    pub(crate) state_after: Option<String>,
+}
+
+impl AuditRecordPersistence {
+    pub(crate) fn into_record(self) -> AuditRecord {
+        // State is stored as we serialized it: tolerate anything else rather than failing
+        // the whole listing.
+        let parse = |s: Option<String>| s.and_then(|s| serde_json::from_str(&s).ok());
+        AuditRecord {
+            id: self.id,
+            recorded_at: self.recorded_at.into(),
+            subject: self.subject,
+            method: self.method,
+            path: self.path,
+            tenant_id: self
+                .tenant_id
+                .map(|t| TenantId::from_str(&t).expect("Bad tenant ID in DB")),
+            node_id: self.node_id.map(|n| NodeId(n as u64)),
+            status: self.status as u16,
+            request: parse(self.request),
+            state_before: parse(self.state_before),
+            state_after: parse(self.state_after),
+        }
+    }
+}
+
+/// Which records to list with [`Persistence::list_audit_records`]
+pub(crate) struct AuditFilter {
+    pub(crate) tenant_id: Option<TenantId>,
+    pub(crate) node_id: Option<NodeId>,
+    pub(crate) limit: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn sqlite_persistence() -> Persistence {
+        let persistence = Persistence::new("sqlite://:memory:".to_string());
+        persistence.migration_run().await.unwrap();
+        persistence
+    }
+
+    fn tenant_shard(tenant_shard_id: TenantShardId) -> TenantShardPersistence {
+        TenantShardPersistence {
+            tenant_id: tenant_shard_id.tenant_id.to_string(),
+            shard_number: tenant_shard_id.shard_number.0 as i32,
+            shard_count: tenant_shard_id.shard_count.literal() as i32,
+            shard_stripe_size: 0,
+            generation: Some(1),
+            generation_pageserver: None,
+            placement_policy: serde_json::to_string(&PlacementPolicy::Attached(0)).unwrap(),
+            splitting: SplitState::Idle,
+            config: serde_json::to_string(&TenantConfig::default()).unwrap(),
+            scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default()).unwrap(),
+            preferred_az_id: None,
+            split_policy: default_split_policy(),
+            placement_constraints: default_placement_constraints(),
+        }
+    }
+
+    #[tokio::test]
+    async fn sqlite_tenant_shards() {
+        let persistence = sqlite_persistence().await;
+        let tenant_id = TenantId::generate();
+        let shard_ids = (0..2)
+            .map(|n| TenantShardId {
+                tenant_id,
+                shard_number: ShardNumber(n),
+                shard_count: ShardCount::new(2),
+            })
+            .collect::<Vec<_>>();
+        persistence
+            .insert_tenant_shards(shard_ids.iter().copied().map(tenant_shard).collect())
+            .await
+            .unwrap();
+
+        let generation = persistence
+            .increment_generation(shard_ids[1], NodeId(1))
+            .await
+            .unwrap();
+        assert_eq!(generation, Generation::new(2));
+
+        let mut generations = persistence
+            .shard_generations(shard_ids.iter())
+            .await
+            .unwrap();
+        generations.sort_by_key(|(tenant_shard_id, _)| *tenant_shard_id);
+        assert_eq!(
+            generations,
+            vec![
+                (shard_ids[0], Some(Generation::new(1))),
+                (shard_ids[1], Some(Generation::new(2)))
+            ]
+        );
+
+        // Metadata health records go with their tenant shards
+        assert_eq!(
+            persistence
+                .list_metadata_health_records()
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        persistence.delete_tenant(tenant_id).await.unwrap();
+        assert!(persistence.list_tenant_shards().await.unwrap().is_empty());
+        assert!(persistence
+            .list_metadata_health_records()
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn sqlite_timelines_and_operations() {
+        let persistence = sqlite_persistence().await;
+        let tenant_id = TenantId::generate();
+        for sk_set in [vec![1, 2, 3], vec![2, 3, 4]] {
+            persistence
+                .insert_timeline(TimelinePersistence {
+                    tenant_id: tenant_id.to_string(),
+                    timeline_id: TimelineId::generate().to_string(),
+                    generation: 1,
+                    sk_set: sk_set.into(),
+                    migrating_to: None,
+                })
+                .await
+                .unwrap();
+        }
+        let on_safekeeper = |id| persistence.list_timelines_on_safekeeper(id);
+        assert_eq!(on_safekeeper(1).await.unwrap().len(), 1);
+        assert_eq!(on_safekeeper(3).await.unwrap().len(), 2);
+        assert_eq!(on_safekeeper(5).await.unwrap().len(), 0);
+
+        // Timestamps are stored with microsecond precision
+        let started_at = chrono::SubsecRound::trunc_subsecs(chrono::Utc::now(), 6);
+        let id = persistence
+            .insert_operation(OperationKind::NodeDrain, None, Some(NodeId(1)), started_at)
+            .await
+            .unwrap();
+        let finished_at = started_at + chrono::Duration::seconds(1);
+        persistence
+            .finish_operation(id, OperationState::Succeeded, None, None, finished_at)
+            .await
+            .unwrap();
+        let status = persistence
+            .get_operation(id)
+            .await
+            .unwrap()
+            .unwrap()
+            .into_status();
+        assert_eq!(status.state, OperationState::Succeeded);
+        assert_eq!(status.started_at, started_at);
+        assert_eq!(status.finished_at, Some(finished_at));
+
+        // Pruning removes finished operations, but never running ones
+        let running = persistence
+            .insert_operation(OperationKind::NodeFill, None, Some(NodeId(1)), started_at)
+            .await
+            .unwrap();
+        let later = finished_at + chrono::Duration::seconds(1);
+        assert_eq!(persistence.prune_operations(later).await.unwrap(), 1);
+        assert!(persistence.get_operation(id).await.unwrap().is_none());
+        assert!(persistence.get_operation(running).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn sqlite_audit_log_pruning() {
+        let persistence = sqlite_persistence().await;
+        let now = chrono::SubsecRound::trunc_subsecs(chrono::Utc::now(), 6);
+        for recorded_at in [now - chrono::Duration::days(2), now] {
+            persistence
+                .insert_audit_record(NewAuditRecord {
+                    recorded_at: Timestamp(recorded_at),
+                    subject: None,
+                    method: "PUT".to_string(),
+                    path: "/control/v1/node/1/drain".to_string(),
+                    tenant_id: None,
+                    node_id: Some(1),
+                    status: 200,
+                    request: None,
+                    state_before: None,
+                    state_after: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let before = now - chrono::Duration::days(1);
+        assert_eq!(persistence.prune_audit_records(before).await.unwrap(), 1);
+        let filter = AuditFilter {
+            tenant_id: None,
+            node_id: None,
+            limit: 10,
+        };
+        let records = persistence.list_audit_records(filter).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].recorded_at.0, now);
+    }
+}
@@ -0,0 +1,142 @@
+//! Column types whose representation differs between the database backends we support.
+//!
+//! Postgres has native types for timestamps with a time zone and for arrays.  SQLite has
+//! neither: there, timestamps are stored as RFC 3339 text in UTC with a fixed number of
+//! fractional digits, so that they sort in time order, and arrays are stored as JSON text.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{IsNull, Output, ToSql};
+use diesel::sqlite::{Sqlite, SqliteValue};
+use serde::{Deserialize, Serialize};
+
+/// SQL types for the columns of [`crate::schema`] which are not portable.
+pub mod sql_types {
+    use diesel::query_builder::QueryId;
+    use diesel::sql_types::SqlType;
+
+    #[derive(SqlType, QueryId)]
+    #[diesel(postgres_type(oid = 1184, array_oid = 1185))]
+    #[diesel(sqlite_type(name = "Text"))]
+    pub struct Timestamptz;
+
+    /// `BIGINT[]` in Postgres.  There are no arrays of arrays in Postgres, hence no array OID.
+    #[derive(SqlType, QueryId)]
+    #[diesel(postgres_type(oid = 1016, array_oid = 0))]
+    #[diesel(sqlite_type(name = "Text"))]
+    pub struct Int8Array;
+}
+
+/// A value of a [`sql_types::Timestamptz`] column
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    AsExpression,
+    FromSqlRow,
+)]
+#[serde(transparent)]
+#[diesel(sql_type = sql_types::Timestamptz)]
+pub(crate) struct Timestamp(pub(crate) DateTime<Utc>);
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(value: DateTime<Utc>) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Timestamp> for DateTime<Utc> {
+    fn from(value: Timestamp) -> Self {
+        value.0
+    }
+}
+
+impl ToSql<sql_types::Timestamptz, Pg> for Timestamp {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        ToSql::<diesel::sql_types::Timestamptz, Pg>::to_sql(&self.0, out)
+    }
+}
+
+impl FromSql<sql_types::Timestamptz, Pg> for Timestamp {
+    fn from_sql(value: PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        FromSql::<diesel::sql_types::Timestamptz, Pg>::from_sql(value).map(Self)
+    }
+}
+
+impl ToSql<sql_types::Timestamptz, Sqlite> for Timestamp {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(self.0.to_rfc3339_opts(SecondsFormat::Micros, true));
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::Timestamptz, Sqlite> for Timestamp {
+    fn from_sql(value: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let text: String = FromSql::<diesel::sql_types::Text, Sqlite>::from_sql(value)?;
+        Ok(Self(
+            DateTime::parse_from_rfc3339(&text)?.with_timezone(&Utc),
+        ))
+    }
+}
+
+/// A value of a [`sql_types::Int8Array`] column
+#[derive(Debug, Clone, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = sql_types::Int8Array)]
+pub(crate) struct Int8Vec(pub(crate) Vec<i64>);
+
+impl From<Vec<i64>> for Int8Vec {
+    fn from(value: Vec<i64>) -> Self {
+        Self(value)
+    }
+}
+
+impl ToSql<sql_types::Int8Array, Pg> for Int8Vec {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        ToSql::<diesel::sql_types::Array<diesel::sql_types::Int8>, Pg>::to_sql(&self.0, out)
+    }
+}
+
+impl FromSql<sql_types::Int8Array, Pg> for Int8Vec {
+    fn from_sql(value: PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        FromSql::<diesel::sql_types::Array<diesel::sql_types::Int8>, Pg>::from_sql(value).map(Self)
+    }
+}
+
+impl ToSql<sql_types::Int8Array, Sqlite> for Int8Vec {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> diesel::serialize::Result {
+        out.set_value(serde_json::to_string(&self.0)?);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::Int8Array, Sqlite> for Int8Vec {
+    fn from_sql(value: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let text: String = FromSql::<diesel::sql_types::Text, Sqlite>::from_sql(value)?;
+        Ok(Self(serde_json::from_str(&text)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqlite_timestamps_sort_in_time_order() {
+        let earlier = DateTime::parse_from_rfc3339("2024-11-12T10:00:00Z").unwrap();
+        let later = DateTime::parse_from_rfc3339("2024-11-12T10:00:00.5Z").unwrap();
+        let format = |t: DateTime<chrono::FixedOffset>| {
+            t.with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Micros, true)
+        };
+        assert!(format(earlier) < format(later));
+        assert_eq!(format(earlier), "2024-11-12T10:00:00.000000Z");
+    }
+}
@@ -1,4 +1,5 @@
 use diesel::pg::{Pg, PgValue};
+use diesel::sqlite::{Sqlite, SqliteValue};
 use diesel::{
     deserialize::FromSql, deserialize::FromSqlRow, expression::AsExpression, serialize::ToSql,
     sql_types::Int2,
@@ -11,6 +12,8 @@
 pub enum SplitState {
     Idle = 0,
     Splitting = 1,
+    /// Set on both the source shards and the merged shards of a shard merge while it is in progress.
+    Merging = 2,
 }
 
 impl Default for SplitState {
@@ -21,6 +24,17 @@ fn default() -> Self {
 
 type SplitStateSQLRepr = Int2;
 
+impl SplitState {
+    fn from_repr(value: i16) -> Option<Self> {
+        match value {
+            0 => Some(Self::Idle),
+            1 => Some(Self::Splitting),
+            2 => Some(Self::Merging),
+            _ => None,
+        }
+    }
+}
+
 impl ToSql<SplitStateSQLRepr, Pg> for SplitState {
     fn to_sql<'a>(
         &'a self,
@@ -34,13 +48,27 @@ fn to_sql<'a>(
 
 impl FromSql<SplitStateSQLRepr, Pg> for SplitState {
     fn from_sql(pg_value: PgValue) -> diesel::deserialize::Result<Self> {
-        match FromSql::<SplitStateSQLRepr, Pg>::from_sql(pg_value).map(|v| match v {
-            0 => Some(Self::Idle),
-            1 => Some(Self::Splitting),
-            _ => None,
-        })? {
+        match FromSql::<SplitStateSQLRepr, Pg>::from_sql(pg_value).map(Self::from_repr)? {
             Some(v) => Ok(v),
             None => Err(format!("Invalid SplitState value, was: {:?}", pg_value.as_bytes()).into()),
         }
     }
 }
+
+impl ToSql<SplitStateSQLRepr, Sqlite> for SplitState {
+    fn to_sql<'a>(
+        &'a self,
+        out: &mut diesel::serialize::Output<'a, '_, Sqlite>,
+    ) -> diesel::serialize::Result {
+        out.set_value(*self as i32);
+        Ok(diesel::serialize::IsNull::No)
+    }
+}
+
+impl FromSql<SplitStateSQLRepr, Sqlite> for SplitState {
+    fn from_sql(value: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        let raw_value: i16 = FromSql::<SplitStateSQLRepr, Sqlite>::from_sql(value)?;
+        Self::from_repr(raw_value)
+            .ok_or_else(|| format!("Invalid SplitState value, was: {raw_value}").into())
+    }
+}
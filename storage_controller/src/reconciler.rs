@@ -114,6 +114,16 @@ pub(crate) fn secondary_download_request_timeout(self, value: Duration) -> Self
         }
     }
 
+    /// Make this reconciler fail in some way, for chaos testing
+    pub(crate) fn injected_fault(self, value: InjectedFault) -> Self {
+        Self {
+            config: ReconcilerConfig {
+                injected_fault: Some(value),
+                ..self.config
+            },
+        }
+    }
+
     pub(crate) fn build(self) -> ReconcilerConfig {
         self.config
     }
@@ -128,6 +138,21 @@ pub(crate) struct ReconcilerConfig {
     // During live migrations this is the amount of time that
     // the pagserver will hold our poll.
     secondary_download_request_timeout: Option<Duration>,
+
+    // Injected by the chaos injector.  Each fault is consumed the first time it takes effect, so
+    // that retries within this reconciler (e.g. of compute notifications) may succeed.
+    injected_fault: Option<InjectedFault>,
+}
+
+/// A fault which the [`crate::service::chaos_injector::ChaosInjector`] injects into a reconciler
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum InjectedFault {
+    /// Fail before doing anything
+    Fail,
+    /// Sleep before doing anything
+    Delay(Duration),
+    /// Fail the first compute notification, as if the control plane were unavailable
+    ComputeNotifyFailure,
 }
 
 impl ReconcilerConfig {
@@ -690,6 +715,24 @@ async fn maybe_refresh_observed(&mut self) -> Result<(), ReconcileError> {
     /// general case reconciliation where we walk through the intent by pageserver
     /// and call out to the pageserver to apply the desired state.
     pub(crate) async fn reconcile(&mut self) -> Result<(), ReconcileError> {
+        match self.reconciler_config.injected_fault {
+            Some(InjectedFault::Fail) => {
+                self.reconciler_config.injected_fault = None;
+                return Err(ReconcileError::Other(anyhow::anyhow!(
+                    "Injected reconcile failure"
+                )));
+            }
+            Some(InjectedFault::Delay(delay)) => {
+                self.reconciler_config.injected_fault = None;
+                tracing::info!("Injected reconcile delay of {delay:?}");
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = self.cancel.cancelled() => return Err(ReconcileError::Cancel),
+                }
+            }
+            Some(InjectedFault::ComputeNotifyFailure) | None => {}
+        }
+
         // Prepare: if we have uncertain `observed` state for our would-be attachement location, then refresh it
         self.maybe_refresh_observed().await?;
 
@@ -826,15 +869,22 @@ pub(crate) async fn compute_notify(&mut self) -> Result<(), NotifyError> {
         // Whenever a particular Reconciler emits a notification, it is always notifying for the intended
         // destination.
         if let Some(node) = &self.intent.attached {
-            let result = self
-                .compute_hook
-                .notify(
-                    self.tenant_shard_id,
-                    node.get_id(),
-                    self.shard.stripe_size,
-                    &self.cancel,
-                )
-                .await;
+            let result = if matches!(
+                self.reconciler_config.injected_fault,
+                Some(InjectedFault::ComputeNotifyFailure)
+            ) {
+                self.reconciler_config.injected_fault = None;
+                Err(NotifyError::Unavailable(StatusCode::SERVICE_UNAVAILABLE))
+            } else {
+                self.compute_hook
+                    .notify(
+                        self.tenant_shard_id,
+                        node.get_id(),
+                        self.shard.stripe_size,
+                        &self.cancel,
+                    )
+                    .await
+            };
             if let Err(e) = &result {
                 // It is up to the caller whether they want to drop out on this error, but they don't have to:
                 // in general we should avoid letting unavailability of the cloud control plane stop us from
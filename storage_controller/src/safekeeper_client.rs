@@ -0,0 +1,139 @@
+use pageserver_client::mgmt_api::{Error, ResponseErrorMessageExt, Result};
+use reqwest::Method;
+use safekeeper_api::models::{PullTimelineRequest, PullTimelineResponse, TimelineCreateRequest};
+use utils::id::{NodeId, TenantId, TimelineId};
+
+/// Minimal client for the safekeeper management API, covering the calls the storage
+/// controller makes when placing and migrating timelines.  Errors reuse the pageserver
+/// client's error type, so that they may be handled in the same way.
+#[derive(Debug, Clone)]
+pub(crate) struct SafekeeperClient {
+    mgmt_api_endpoint: String,
+    authorization_header: Option<String>,
+    client: reqwest::Client,
+    node_id_label: String,
+}
+
+macro_rules! measured_request {
+    ($name:literal, $method:expr, $node_id: expr, $invoke:expr) => {{
+        let labels = crate::metrics::SafekeeperRequestLabelGroup {
+            safekeeper_id: $node_id,
+            path: $name,
+            method: $method,
+        };
+
+        let latency = &crate::metrics::METRICS_REGISTRY
+            .metrics_group
+            .storage_controller_safekeeper_request_latency;
+        let _timer_guard = latency.start_timer(labels.clone());
+
+        let res = $invoke;
+
+        if res.is_err() {
+            let error_counters = &crate::metrics::METRICS_REGISTRY
+                .metrics_group
+                .storage_controller_safekeeper_request_error;
+            error_counters.inc(labels)
+        }
+
+        res
+    }};
+}
+
+impl SafekeeperClient {
+    pub(crate) fn new(node_id: NodeId, mgmt_api_endpoint: String, jwt: Option<&str>) -> Self {
+        Self {
+            mgmt_api_endpoint,
+            authorization_header: jwt.map(|jwt| format!("Bearer {jwt}")),
+            client: reqwest::Client::new(),
+            node_id_label: node_id.0.to_string(),
+        }
+    }
+
+    pub(crate) async fn create_timeline(&self, req: &TimelineCreateRequest) -> Result<()> {
+        let uri = format!("{}/v1/tenant/timeline", self.mgmt_api_endpoint);
+        measured_request!(
+            "tenant_timeline",
+            crate::metrics::Method::Post,
+            &self.node_id_label,
+            self.request(Method::POST, uri, req).await.map(|_| ())
+        )
+    }
+
+    /// Delete the timeline's data on the safekeeper.  With `only_local`, the timeline's
+    /// WAL in remote storage is left in place.
+    pub(crate) async fn delete_timeline(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        only_local: bool,
+    ) -> Result<()> {
+        let uri = format!(
+            "{}/v1/tenant/{tenant_id}/timeline/{timeline_id}?only_local={only_local}",
+            self.mgmt_api_endpoint
+        );
+        measured_request!(
+            "tenant_timeline",
+            crate::metrics::Method::Delete,
+            &self.node_id_label,
+            self.request(Method::DELETE, uri, ()).await.map(|_| ())
+        )
+    }
+
+    /// Check whether the safekeeper holds the timeline: a missing timeline is reported as an
+    /// [`Error::ApiError`] with status 404.
+    pub(crate) async fn timeline_status(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+    ) -> Result<()> {
+        let uri = format!(
+            "{}/v1/tenant/{tenant_id}/timeline/{timeline_id}",
+            self.mgmt_api_endpoint
+        );
+        measured_request!(
+            "tenant_timeline",
+            crate::metrics::Method::Get,
+            &self.node_id_label,
+            self.request(Method::GET, uri, ()).await.map(|_| ())
+        )
+    }
+
+    /// Ask the safekeeper to fetch a copy of the timeline from the most advanced of the
+    /// peers listed in the request.
+    pub(crate) async fn pull_timeline(
+        &self,
+        req: &PullTimelineRequest,
+    ) -> Result<PullTimelineResponse> {
+        let uri = format!("{}/v1/pull_timeline", self.mgmt_api_endpoint);
+        measured_request!(
+            "pull_timeline",
+            crate::metrics::Method::Post,
+            &self.node_id_label,
+            async {
+                self.request(Method::POST, uri, req)
+                    .await?
+                    .json()
+                    .await
+                    .map_err(Error::ReceiveBody)
+            }
+            .await
+        )
+    }
+
+    async fn request<B: serde::Serialize, U: reqwest::IntoUrl>(
+        &self,
+        method: Method,
+        uri: U,
+        body: B,
+    ) -> Result<reqwest::Response> {
+        let req = self.client.request(method, uri);
+        let req = if let Some(value) = &self.authorization_header {
+            req.header(reqwest::header::AUTHORIZATION, value)
+        } else {
+            req
+        };
+        let res = req.json(&body).send().await.map_err(Error::SendRequest)?;
+        res.error_from_body().await
+    }
+}
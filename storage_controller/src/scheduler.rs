@@ -1,9 +1,17 @@
-use crate::{node::Node, tenant_shard::TenantShard};
+use crate::{node::Node, persistence::SafekeeperPersistence, tenant_shard::TenantShard};
 use itertools::Itertools;
-use pageserver_api::{controller_api::AvailabilityZone, models::PageserverUtilization};
+use pageserver_api::{
+    controller_api::{
+        AvailabilityZone, PlacementConstraints, SkSchedulingPolicy, SpreadConstraint,
+    },
+    models::{utilization::UtilizationWeights, PageserverUtilization},
+};
 use serde::Serialize;
-use std::{collections::HashMap, fmt::Debug};
-use utils::{http::error::ApiError, id::NodeId};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Debug,
+};
+use utils::{http::error::ApiError, id::NodeId, serde_percent::Percent};
 
 /// Scenarios in which we cannot find a suitable location for a tenant shard
 #[derive(thiserror::Error, Debug)]
@@ -12,6 +20,8 @@ pub enum ScheduleError {
     NoPageservers,
     #[error("No pageserver found matching constraint")]
     ImpossibleConstraint,
+    #[error("Not enough schedulable safekeepers")]
+    NotEnoughSafekeepers,
 }
 
 impl From<ScheduleError> for ApiError {
@@ -34,6 +44,11 @@ pub(crate) struct SchedulerNode {
     attached_shard_count: usize,
     /// Availability zone id in which the node resides
     az: AvailabilityZone,
+    /// The node's labels, which tenants' [`PlacementConstraints`] refer to
+    labels: BTreeMap<String, String>,
+    /// How many shards in each [`SpreadConstraint`] group are scheduled on this node, via their
+    /// [`crate::tenant_shard::IntentState`].
+    spread_group_shard_counts: HashMap<String, usize>,
 
     /// Whether this node is currently elegible to have new shards scheduled (this is derived
     /// from a node's availability state and scheduling policy).
@@ -45,7 +60,9 @@ fn generate(
         node_id: &NodeId,
         node: &mut SchedulerNode,
         preferred_az: &Option<AvailabilityZone>,
+        constraint_score: ConstraintScore,
         context: &ScheduleContext,
+        weights: &UtilizationWeights,
     ) -> Option<Self>;
     fn is_overloaded(&self) -> bool;
     fn node_id(&self) -> NodeId;
@@ -131,6 +148,17 @@ fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
     }
 }
 
+/// How well a node suits a tenant's [`PlacementConstraints`], apart from the required labels, which
+/// exclude nodes entirely.  Lower scores indicate a more suitable node.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub(crate) struct ConstraintScore {
+    /// How many of the tenant's preferred labels the node is missing
+    preferred_labels_missing: usize,
+    /// How many shards in the tenant's spread group are on nodes with the same value of the
+    /// spread label as this node.
+    spread_group_shards: usize,
+}
+
 /// Scheduling score of a given node for shard attachments.
 /// Lower scores indicate more suitable nodes.
 /// Ordering is given by member declaration order (top to bottom).
@@ -143,11 +171,14 @@ pub(crate) struct NodeAttachmentSchedulingScore {
     /// of the shard. For equal affinity scores, nodes in the matching AZ
     /// are considered first.
     az_match: AttachmentAzMatch,
+    /// How well the node suits the tenant's placement constraints
+    constraint_score: ConstraintScore,
     /// Size of [`ScheduleContext::attached_nodes`] for the current node.
     /// This normally tracks the number of attached shards belonging to the
     /// tenant being scheduled that are already on this node.
     attached_shards_in_context: usize,
-    /// Utilisation score that combines shard count and disk utilisation
+    /// Utilisation score that combines shard count, disk utilisation, resident size and
+    /// WAL ingest rate, according to [`Scheduler::utilization_weights`]
     utilization_score: u64,
     /// Total number of shards attached to this node. When nodes have identical utilisation, this
     /// acts as an anti-affinity between attached shards.
@@ -161,9 +192,11 @@ fn generate(
         node_id: &NodeId,
         node: &mut SchedulerNode,
         preferred_az: &Option<AvailabilityZone>,
+        constraint_score: ConstraintScore,
         context: &ScheduleContext,
+        weights: &UtilizationWeights,
     ) -> Option<Self> {
-        let utilization = match &mut node.may_schedule {
+        let utilization = match &node.may_schedule {
             MaySchedule::Yes(u) => u,
             MaySchedule::No => {
                 return None;
@@ -177,8 +210,9 @@ fn generate(
                 .copied()
                 .unwrap_or(AffinityScore::FREE),
             az_match: AttachmentAzMatch(AzMatch::new(&node.az, preferred_az.as_ref())),
+            constraint_score,
             attached_shards_in_context: context.attached_nodes.get(node_id).copied().unwrap_or(0),
-            utilization_score: utilization.cached_score(),
+            utilization_score: utilization.weighted_score(weights),
             total_attached_shard_count: node.attached_shard_count,
             node_id: *node_id,
         })
@@ -206,7 +240,10 @@ pub(crate) struct NodeSecondarySchedulingScore {
     /// The number of shards belonging to the tenant currently being
     /// scheduled that are attached to this node.
     affinity_score: AffinityScore,
-    /// Utilisation score that combines shard count and disk utilisation
+    /// How well the node suits the tenant's placement constraints
+    constraint_score: ConstraintScore,
+    /// Utilisation score that combines shard count, disk utilisation, resident size and
+    /// WAL ingest rate, according to [`Scheduler::utilization_weights`]
     utilization_score: u64,
     /// Total number of shards attached to this node. When nodes have identical utilisation, this
     /// acts as an anti-affinity between attached shards.
@@ -220,9 +257,11 @@ fn generate(
         node_id: &NodeId,
         node: &mut SchedulerNode,
         preferred_az: &Option<AvailabilityZone>,
+        constraint_score: ConstraintScore,
         context: &ScheduleContext,
+        weights: &UtilizationWeights,
     ) -> Option<Self> {
-        let utilization = match &mut node.may_schedule {
+        let utilization = match &node.may_schedule {
             MaySchedule::Yes(u) => u,
             MaySchedule::No => {
                 return None;
@@ -236,7 +275,8 @@ fn generate(
                 .get(node_id)
                 .copied()
                 .unwrap_or(AffinityScore::FREE),
-            utilization_score: utilization.cached_score(),
+            constraint_score,
+            utilization_score: utilization.weighted_score(weights),
             total_attached_shard_count: node.attached_shard_count,
             node_id: *node_id,
         })
@@ -262,6 +302,8 @@ fn eq(&self, other: &Self) -> bool {
             && self.shard_count == other.shard_count
             && self.attached_shard_count == other.attached_shard_count
             && self.az == other.az
+            && self.labels == other.labels
+            && self.spread_group_shard_counts == other.spread_group_shard_counts
     }
 }
 
@@ -275,6 +317,13 @@ impl Eq for SchedulerNode {}
 #[derive(Serialize)]
 pub(crate) struct Scheduler {
     nodes: HashMap<NodeId, SchedulerNode>,
+
+    /// How much each part of a node's [`PageserverUtilization`] counts in its scheduling score
+    utilization_weights: UtilizationWeights,
+
+    /// Nodes whose physical disk usage is at or above this watermark have shards migrated
+    /// away by the optimizer (see [`crate::tenant_shard::TenantShard::optimize_disk_usage`])
+    disk_usage_watermark: Option<Percent>,
 }
 
 /// Score for soft constraint scheduling: lower scores are preferred to higher scores.
@@ -377,12 +426,41 @@ pub(crate) fn new<'a>(nodes: impl Iterator<Item = &'a Node>) -> Self {
                     attached_shard_count: 0,
                     may_schedule: node.may_schedule(),
                     az: node.get_availability_zone_id().clone(),
+                    labels: node.get_labels().clone(),
+                    spread_group_shard_counts: HashMap::new(),
                 },
             );
         }
 
         Self {
             nodes: scheduler_nodes,
+            utilization_weights: UtilizationWeights::default(),
+            disk_usage_watermark: None,
+        }
+    }
+
+    pub(crate) fn set_utilization_policy(
+        &mut self,
+        weights: UtilizationWeights,
+        disk_usage_watermark: Option<Percent>,
+    ) {
+        self.utilization_weights = weights;
+        self.disk_usage_watermark = disk_usage_watermark;
+    }
+
+    /// Whether a node's physical disk usage is over the watermark above which we migrate shards
+    /// away from it.  Nodes that we may not schedule to (e.g. offline) never are: we would not
+    /// have up to date utilization for them, and moving shards off them is the job of other
+    /// mechanisms.
+    pub(crate) fn is_over_disk_watermark(&self, node_id: NodeId) -> bool {
+        let Some(watermark) = self.disk_usage_watermark else {
+            return false;
+        };
+        match self.nodes.get(&node_id).map(|n| &n.may_schedule) {
+            Some(MaySchedule::Yes(utilization)) => {
+                utilization.disk_usage_pct() >= watermark.get() as u64
+            }
+            Some(MaySchedule::No) | None => false,
         }
     }
 
@@ -404,16 +482,25 @@ pub(crate) fn consistency_check<'a>(
                     attached_shard_count: 0,
                     may_schedule: node.may_schedule(),
                     az: node.get_availability_zone_id().clone(),
+                    labels: node.get_labels().clone(),
+                    spread_group_shard_counts: HashMap::new(),
                 },
             );
         }
 
         for shard in shards {
+            let spread_group = shard.intent.get_spread_group();
             if let Some(node_id) = shard.intent.get_attached() {
                 match expect_nodes.get_mut(node_id) {
                     Some(node) => {
                         node.shard_count += 1;
                         node.attached_shard_count += 1;
+                        if let Some(group) = spread_group {
+                            *node
+                                .spread_group_shard_counts
+                                .entry(group.to_string())
+                                .or_default() += 1;
+                        }
                     }
                     None => anyhow::bail!(
                         "Tenant {} references nonexistent node {}",
@@ -425,7 +512,15 @@ pub(crate) fn consistency_check<'a>(
 
             for node_id in shard.intent.get_secondary() {
                 match expect_nodes.get_mut(node_id) {
-                    Some(node) => node.shard_count += 1,
+                    Some(node) => {
+                        node.shard_count += 1;
+                        if let Some(group) = spread_group {
+                            *node
+                                .spread_group_shard_counts
+                                .entry(group.to_string())
+                                .or_default() += 1;
+                        }
+                    }
                     None => anyhow::bail!(
                         "Tenant {} references nonexistent node {}",
                         shard.tenant_shard_id,
@@ -467,15 +562,38 @@ pub(crate) fn consistency_check<'a>(
     /// targets this node and the number of tenants shars whose IntentState is attached to this
     /// node.
     ///
+    /// `spread_group` is the [`SpreadConstraint`] group of the tenant shard, if any: we also count
+    /// the locations of the shards in each group on each node.
+    ///
     /// It is an error to call this for a node that is not known to the scheduler (i.e. passed into
     /// [`Self::new`] or [`Self::node_upsert`])
-    pub(crate) fn update_node_ref_counts(&mut self, node_id: NodeId, update: RefCountUpdate) {
+    pub(crate) fn update_node_ref_counts(
+        &mut self,
+        node_id: NodeId,
+        update: RefCountUpdate,
+        spread_group: Option<&str>,
+    ) {
         let Some(node) = self.nodes.get_mut(&node_id) else {
             debug_assert!(false);
             tracing::error!("Scheduler missing node {node_id}");
             return;
         };
 
+        if let Some(group) = spread_group {
+            match update {
+                RefCountUpdate::Attach | RefCountUpdate::AddSecondary => {
+                    *node
+                        .spread_group_shard_counts
+                        .entry(group.to_string())
+                        .or_default() += 1;
+                }
+                RefCountUpdate::Detach | RefCountUpdate::RemoveSecondary => {
+                    Self::spread_group_dec(node, group);
+                }
+                RefCountUpdate::PromoteSecondary | RefCountUpdate::DemoteAttached => {}
+            }
+        }
+
         match update {
             RefCountUpdate::PromoteSecondary => {
                 node.attached_shard_count += 1;
@@ -522,6 +640,76 @@ pub(crate) fn update_node_ref_counts(&mut self, node_id: NodeId, update: RefCoun
         }
     }
 
+    /// Move a shard's location on a node from one [`SpreadConstraint`] group to another, when its
+    /// tenant's placement constraints change.
+    pub(crate) fn update_node_spread_group(
+        &mut self,
+        node_id: NodeId,
+        old_group: Option<&str>,
+        new_group: Option<&str>,
+    ) {
+        let Some(node) = self.nodes.get_mut(&node_id) else {
+            debug_assert!(false);
+            tracing::error!("Scheduler missing node {node_id}");
+            return;
+        };
+
+        if let Some(old_group) = old_group {
+            Self::spread_group_dec(node, old_group);
+        }
+        if let Some(new_group) = new_group {
+            *node
+                .spread_group_shard_counts
+                .entry(new_group.to_string())
+                .or_default() += 1;
+        }
+    }
+
+    fn spread_group_dec(node: &mut SchedulerNode, group: &str) {
+        if let Some(count) = node.spread_group_shard_counts.get_mut(group) {
+            *count -= 1;
+            if *count == 0 {
+                // Drop empty groups, so that they don't accumulate as tenants come and go
+                node.spread_group_shard_counts.remove(group);
+            }
+        }
+    }
+
+    /// Whether a node has all the labels that a tenant's placement constraints require
+    pub(crate) fn node_satisfies(
+        &self,
+        node_id: NodeId,
+        constraints: &PlacementConstraints,
+    ) -> bool {
+        self.nodes
+            .get(&node_id)
+            .map(|n| constraints.is_satisfied_by(&n.labels))
+            .unwrap_or(false)
+    }
+
+    /// Whether any node has all the labels that a tenant's placement constraints require
+    pub(crate) fn any_node_satisfies(&self, constraints: &PlacementConstraints) -> bool {
+        self.nodes
+            .values()
+            .any(|n| constraints.is_satisfied_by(&n.labels))
+    }
+
+    /// Number of shards in a [`SpreadConstraint`]'s group on nodes with each value of its label.
+    /// Nodes without the label count as one more value.
+    fn spread_group_counts(&self, spread: &SpreadConstraint) -> HashMap<Option<String>, usize> {
+        let mut counts = HashMap::new();
+        for node in self.nodes.values() {
+            *counts
+                .entry(node.labels.get(&spread.label).cloned())
+                .or_default() += node
+                .spread_group_shard_counts
+                .get(&spread.group)
+                .copied()
+                .unwrap_or(0);
+        }
+        counts
+    }
+
     // Check if the number of shards attached to a given node is lagging below
     // the cluster average. If that's the case, the node should be filled.
     pub(crate) fn compute_fill_requirement(&self, node_id: NodeId) -> usize {
@@ -576,6 +764,7 @@ pub(crate) fn node_upsert(&mut self, node: &Node) {
                 }
 
                 entry.get_mut().may_schedule = may_schedule;
+                entry.get_mut().labels = node.get_labels().clone();
             }
             Vacant(entry) => {
                 entry.insert(SchedulerNode {
@@ -583,6 +772,8 @@ pub(crate) fn node_upsert(&mut self, node: &Node) {
                     attached_shard_count: 0,
                     may_schedule: node.may_schedule(),
                     az: node.get_availability_zone_id().clone(),
+                    labels: node.get_labels().clone(),
+                    spread_group_shard_counts: HashMap::new(),
                 });
             }
         }
@@ -624,23 +815,46 @@ pub(crate) fn node_preferred(&self, nodes: &[NodeId]) -> Option<NodeId> {
     }
 
     /// Compute a schedulling score for each node that the scheduler knows of
-    /// minus a set of hard excluded nodes.
+    /// minus a set of hard excluded nodes, and nodes without the labels that
+    /// the placement constraints require.
     fn compute_node_scores<Score>(
         &mut self,
         hard_exclude: &[NodeId],
         preferred_az: &Option<AvailabilityZone>,
+        constraints: &PlacementConstraints,
         context: &ScheduleContext,
     ) -> Vec<Score>
     where
         Score: NodeSchedulingScore,
     {
+        let spread_counts = constraints
+            .spread
+            .as_ref()
+            .map(|spread| (spread, self.spread_group_counts(spread)));
+
         self.nodes
             .iter_mut()
             .filter_map(|(k, v)| {
-                if hard_exclude.contains(k) {
+                if hard_exclude.contains(k) || !constraints.is_satisfied_by(&v.labels) {
                     None
                 } else {
-                    Score::generate(k, v, preferred_az, context)
+                    let constraint_score = ConstraintScore {
+                        preferred_labels_missing: constraints.preferred_labels_missing(&v.labels),
+                        spread_group_shards: spread_counts
+                            .as_ref()
+                            .and_then(|(spread, counts)| {
+                                counts.get(&v.labels.get(&spread.label).cloned()).copied()
+                            })
+                            .unwrap_or(0),
+                    };
+                    Score::generate(
+                        k,
+                        v,
+                        preferred_az,
+                        constraint_score,
+                        context,
+                        &self.utilization_weights,
+                    )
                 }
             })
             .collect()
@@ -651,6 +865,9 @@ fn compute_node_scores<Score>(
     /// as both attached and secondary location.  This is a hard constraint: if we cannot
     /// find any nodes that aren't in this list, then we will return a [`ScheduleError::ImpossibleConstraint`].
     ///
+    /// constraints: the tenant's required labels are a hard constraint like hard_exclude, while
+    /// its preferred labels and spread group are soft constraints.
+    ///
     /// context: we prefer to avoid using nodes identified in the context, according
     /// to their anti-affinity score.  We use this to prefeer to avoid placing shards in
     /// the same tenant on the same node.  This is a soft constraint: the context will never
@@ -659,14 +876,19 @@ pub(crate) fn schedule_shard<Tag: ShardTag>(
         &mut self,
         hard_exclude: &[NodeId],
         preferred_az: &Option<AvailabilityZone>,
+        constraints: &PlacementConstraints,
         context: &ScheduleContext,
     ) -> Result<NodeId, ScheduleError> {
         if self.nodes.is_empty() {
             return Err(ScheduleError::NoPageservers);
         }
 
-        let mut scores =
-            self.compute_node_scores::<Tag::Score>(hard_exclude, preferred_az, context);
+        let mut scores = self.compute_node_scores::<Tag::Score>(
+            hard_exclude,
+            preferred_az,
+            constraints,
+            context,
+        );
 
         // Exclude nodes whose utilization is critically high, if there are alternatives available.  This will
         // cause us to violate affinity rules if it is necessary to avoid critically overloading nodes: for example
@@ -694,7 +916,8 @@ pub(crate) fn schedule_shard<Tag: ShardTag>(
                 // schedule: this may help an engineer understand if some nodes are marked offline
                 // in a way that's preventing progress.
                 tracing::info!(
-                    "Scheduling failure, while excluding {hard_exclude:?}, node states:"
+                    "Scheduling failure, while excluding {hard_exclude:?} and requiring labels {:?}, node states:",
+                    constraints.required_labels
                 );
                 for (node_id, node) in &self.nodes {
                     tracing::info!(
@@ -726,7 +949,12 @@ pub(crate) fn schedule_shard<Tag: ShardTag>(
     /// Selects any available node. This is suitable for performing background work (e.g. S3
     /// deletions).
     pub(crate) fn any_available_node(&mut self) -> Result<NodeId, ScheduleError> {
-        self.schedule_shard::<AttachedShardTag>(&[], &None, &ScheduleContext::default())
+        self.schedule_shard::<AttachedShardTag>(
+            &[],
+            &None,
+            &PlacementConstraints::default(),
+            &ScheduleContext::default(),
+        )
     }
 
     /// Unit test access to internal state
@@ -741,6 +969,58 @@ pub(crate) fn get_node_attached_shard_count(&self, node_id: NodeId) -> usize {
     }
 }
 
+/// Pick `count` safekeepers to host a timeline.
+///
+/// Safekeepers are taken least loaded first (by number of timelines), and we spread across
+/// availability zones: a safekeeper in an AZ that is already used (including the `avoid_azs`
+/// of the timeline's other members) is only picked if there are not enough AZs to go around.
+pub(crate) fn schedule_safekeepers(
+    candidates: &[SafekeeperPersistence],
+    timeline_counts: &HashMap<i64, u64>,
+    exclude: &[i64],
+    avoid_azs: &[&str],
+    count: usize,
+) -> Result<Vec<SafekeeperPersistence>, ScheduleError> {
+    let mut eligible = candidates
+        .iter()
+        .filter(|sk| {
+            sk.active
+                && sk.get_scheduling_policy() == SkSchedulingPolicy::Active
+                && !exclude.contains(&sk.id)
+        })
+        .collect::<Vec<_>>();
+    eligible.sort_by_key(|sk| (timeline_counts.get(&sk.id).copied().unwrap_or(0), sk.id));
+
+    let mut used_azs = avoid_azs.iter().copied().collect::<HashSet<_>>();
+    let mut picked: Vec<&SafekeeperPersistence> = Vec::with_capacity(count);
+
+    // First pass: at most one safekeeper per AZ
+    for sk in &eligible {
+        if picked.len() == count {
+            break;
+        }
+        if used_azs.insert(sk.availability_zone_id.as_str()) {
+            picked.push(sk);
+        }
+    }
+
+    // Second pass: fill up from any AZ
+    for sk in &eligible {
+        if picked.len() == count {
+            break;
+        }
+        if !picked.iter().any(|p| p.id == sk.id) {
+            picked.push(sk);
+        }
+    }
+
+    if picked.len() < count {
+        return Err(ScheduleError::NotEnoughSafekeepers);
+    }
+
+    Ok(picked.into_iter().cloned().collect())
+}
+
 #[cfg(test)]
 pub(crate) mod test_utils {
 
@@ -749,7 +1029,7 @@ pub(crate) mod test_utils {
         controller_api::{AvailabilityZone, NodeAvailability},
         models::utilization::test_utilization,
     };
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
     use utils::id::NodeId;
 
     /// Test helper: synthesize the requested number of nodes, all in active state.
@@ -774,6 +1054,7 @@ pub(crate) fn make_test_nodes(n: u64, azs: &[AvailabilityZone]) -> HashMap<NodeI
                             .next()
                             .cloned()
                             .unwrap_or(AvailabilityZone("test-az".to_string())),
+                        BTreeMap::new(),
                     );
                     node.set_availability(NodeAvailability::Active(test_utilization::simple(0, 0)));
                     assert!(node.is_available());
@@ -801,9 +1082,19 @@ fn scheduler_basic() -> anyhow::Result<()> {
 
         let context = ScheduleContext::default();
 
-        let scheduled = scheduler.schedule_shard::<AttachedShardTag>(&[], &None, &context)?;
+        let scheduled = scheduler.schedule_shard::<AttachedShardTag>(
+            &[],
+            &None,
+            &PlacementConstraints::default(),
+            &context,
+        )?;
         t1_intent.set_attached(&mut scheduler, Some(scheduled));
-        let scheduled = scheduler.schedule_shard::<AttachedShardTag>(&[], &None, &context)?;
+        let scheduled = scheduler.schedule_shard::<AttachedShardTag>(
+            &[],
+            &None,
+            &PlacementConstraints::default(),
+            &context,
+        )?;
         t2_intent.set_attached(&mut scheduler, Some(scheduled));
 
         assert_eq!(scheduler.get_node_shard_count(NodeId(1)), 1);
@@ -815,6 +1106,7 @@ fn scheduler_basic() -> anyhow::Result<()> {
         let scheduled = scheduler.schedule_shard::<AttachedShardTag>(
             &t1_intent.all_pageservers(),
             &None,
+            &PlacementConstraints::default(),
             &context,
         )?;
         t1_intent.push_secondary(&mut scheduler, scheduled);
@@ -871,7 +1163,12 @@ fn assert_scheduler_chooses(
             context: &ScheduleContext,
         ) {
             let scheduled = scheduler
-                .schedule_shard::<AttachedShardTag>(&[], &None, context)
+                .schedule_shard::<AttachedShardTag>(
+                    &[],
+                    &None,
+                    &PlacementConstraints::default(),
+                    context,
+                )
                 .unwrap();
             let mut intent = IntentState::new();
             intent.set_attached(scheduler, Some(scheduled));
@@ -1004,7 +1301,12 @@ fn assert_scheduler_chooses<Tag: ShardTag>(
             context: &mut ScheduleContext,
         ) {
             let scheduled = scheduler
-                .schedule_shard::<Tag>(&[], &preferred_az, context)
+                .schedule_shard::<Tag>(
+                    &[],
+                    &preferred_az,
+                    &PlacementConstraints::default(),
+                    context,
+                )
                 .unwrap();
             let mut intent = IntentState::new();
             intent.set_attached(scheduler, Some(scheduled));
@@ -1074,4 +1376,241 @@ fn assert_scheduler_chooses<Tag: ShardTag>(
             intent.clear(&mut scheduler);
         }
     }
+
+    fn make_test_safekeeper(id: i64, az: &str) -> SafekeeperPersistence {
+        SafekeeperPersistence {
+            id,
+            region_id: "test-region".to_string(),
+            version: 1,
+            host: format!("sk-{id}"),
+            port: 5454,
+            active: true,
+            http_port: 7676,
+            availability_zone_id: az.to_string(),
+            scheduling_policy: String::from(SkSchedulingPolicy::Active),
+        }
+    }
+
+    #[test]
+    fn safekeepers_spread_across_azs() {
+        let mut safekeepers = vec![
+            make_test_safekeeper(1, "az-a"),
+            make_test_safekeeper(2, "az-a"),
+            make_test_safekeeper(3, "az-b"),
+            make_test_safekeeper(4, "az-b"),
+            make_test_safekeeper(5, "az-c"),
+            make_test_safekeeper(6, "az-c"),
+        ];
+        let timeline_counts = HashMap::from([(1, 10), (3, 5), (4, 1), (5, 2), (6, 3)]);
+
+        let ids = |picked: Vec<SafekeeperPersistence>| picked.iter().map(|sk| sk.id).collect_vec();
+
+        // One per AZ, least loaded within each AZ
+        let picked = schedule_safekeepers(&safekeepers, &timeline_counts, &[], &[], 3).unwrap();
+        assert_eq!(ids(picked), vec![2, 4, 5]);
+
+        // Replacing a member: avoid the AZs of the remaining members and the excluded safekeepers
+        let picked = schedule_safekeepers(
+            &safekeepers,
+            &timeline_counts,
+            &[2, 4, 5],
+            &["az-b", "az-c"],
+            1,
+        )
+        .unwrap();
+        assert_eq!(ids(picked), vec![1]);
+
+        // Draining and inactive safekeepers are not eligible
+        safekeepers[1].scheduling_policy = String::from(SkSchedulingPolicy::Draining);
+        safekeepers[3].active = false;
+        let picked = schedule_safekeepers(&safekeepers, &timeline_counts, &[], &[], 3).unwrap();
+        assert_eq!(ids(picked), vec![5, 3, 1]);
+
+        // When there are fewer AZs than replicas, fall back to sharing AZs
+        let picked =
+            schedule_safekeepers(&safekeepers[4..], &timeline_counts, &[], &[], 2).unwrap();
+        assert_eq!(ids(picked), vec![5, 6]);
+
+        assert!(matches!(
+            schedule_safekeepers(&safekeepers[4..], &timeline_counts, &[], &[], 3),
+            Err(ScheduleError::NotEnoughSafekeepers)
+        ));
+    }
+
+    #[test]
+    fn scheduler_disk_watermark() {
+        let mut nodes = test_utils::make_test_nodes(2, &[]);
+        let mut scheduler = Scheduler::new(nodes.values());
+
+        // Node 1 is 90% full, node 2 is empty
+        let disk_size = 1024 * 1024 * 1024 * 1024;
+        nodes
+            .get_mut(&NodeId(1))
+            .unwrap()
+            .set_availability(NodeAvailability::Active(test_utilization::simple(
+                0,
+                disk_size / 10 * 9,
+            )));
+        scheduler.node_upsert(nodes.get(&NodeId(1)).unwrap());
+
+        // Without a watermark, nothing is ever over it
+        assert!(!scheduler.is_over_disk_watermark(NodeId(1)));
+        assert!(!scheduler.is_over_disk_watermark(NodeId(2)));
+
+        scheduler.set_utilization_policy(UtilizationWeights::default(), Percent::new(85));
+        assert!(scheduler.is_over_disk_watermark(NodeId(1)));
+        assert!(!scheduler.is_over_disk_watermark(NodeId(2)));
+
+        // Offline nodes are not considered over the watermark
+        nodes
+            .get_mut(&NodeId(1))
+            .unwrap()
+            .set_availability(NodeAvailability::Offline);
+        scheduler.node_upsert(nodes.get(&NodeId(1)).unwrap());
+        assert!(!scheduler.is_over_disk_watermark(NodeId(1)));
+    }
+
+    #[test]
+    fn scheduler_utilization_weights() {
+        let mut nodes = test_utils::make_test_nodes(2, &[]);
+        let mut scheduler = Scheduler::new(nodes.values());
+        let context = ScheduleContext::default();
+
+        // Node 1 has a lot of ingest but little data, node 2 has more data but no ingest.
+        let mut busy = test_utilization::simple(0, 1024 * 1024);
+        busy.wal_ingest_bytes_per_sec = 200 * 1024 * 1024;
+        nodes
+            .get_mut(&NodeId(1))
+            .unwrap()
+            .set_availability(NodeAvailability::Active(busy));
+        scheduler.node_upsert(nodes.get(&NodeId(1)).unwrap());
+        nodes
+            .get_mut(&NodeId(2))
+            .unwrap()
+            .set_availability(NodeAvailability::Active(test_utilization::simple(
+                0,
+                1024 * 1024 * 1024,
+            )));
+        scheduler.node_upsert(nodes.get(&NodeId(2)).unwrap());
+
+        // By default only disk usage counts, so the node with less data wins
+        let scheduled = scheduler
+            .schedule_shard::<AttachedShardTag>(
+                &[],
+                &None,
+                &PlacementConstraints::default(),
+                &context,
+            )
+            .unwrap();
+        assert_eq!(scheduled, NodeId(1));
+
+        // Once ingest is weighted in, the busy node loses
+        scheduler.set_utilization_policy(
+            UtilizationWeights {
+                wal_ingest: 1,
+                ..Default::default()
+            },
+            None,
+        );
+        let scheduled = scheduler
+            .schedule_shard::<AttachedShardTag>(
+                &[],
+                &None,
+                &PlacementConstraints::default(),
+                &context,
+            )
+            .unwrap();
+        assert_eq!(scheduled, NodeId(2));
+    }
+
+    #[test]
+    fn scheduler_placement_constraints() {
+        // Four nodes in two racks, of which only nodes 3 and 4 have fast disks
+        let mut nodes = test_utils::make_test_nodes(4, &[]);
+        for (node_id, node) in nodes.iter_mut() {
+            let mut labels = BTreeMap::new();
+            labels.insert("rack".to_string(), format!("r{}", node_id.0 % 2));
+            if node_id.0 > 2 {
+                labels.insert("disk".to_string(), "nvme".to_string());
+            }
+            node.set_labels(labels);
+        }
+        let mut scheduler = Scheduler::new(nodes.values());
+        let context = ScheduleContext::default();
+
+        fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        }
+
+        // Required labels exclude other nodes, even though they are otherwise preferable
+        let required = PlacementConstraints {
+            required_labels: labels(&[("disk", "nvme")]),
+            ..Default::default()
+        };
+        let scheduled = scheduler
+            .schedule_shard::<AttachedShardTag>(&[], &None, &required, &context)
+            .unwrap();
+        assert_eq!(scheduled, NodeId(3));
+        assert!(matches!(
+            scheduler.schedule_shard::<AttachedShardTag>(
+                &[NodeId(3), NodeId(4)],
+                &None,
+                &required,
+                &context
+            ),
+            Err(ScheduleError::ImpossibleConstraint)
+        ));
+        assert!(!scheduler.node_satisfies(NodeId(1), &required));
+        assert!(scheduler.node_satisfies(NodeId(4), &required));
+
+        // Preferred labels are only a preference
+        let preferred = PlacementConstraints {
+            preferred_labels: labels(&[("disk", "nvme"), ("rack", "r0")]),
+            ..Default::default()
+        };
+        let scheduled = scheduler
+            .schedule_shard::<AttachedShardTag>(&[], &None, &preferred, &context)
+            .unwrap();
+        assert_eq!(scheduled, NodeId(4));
+        let scheduled = scheduler
+            .schedule_shard::<AttachedShardTag>(&[NodeId(4)], &None, &preferred, &context)
+            .unwrap();
+        assert_eq!(scheduled, NodeId(2));
+
+        // Shards of tenants in the same spread group avoid each other's racks
+        let spread = PlacementConstraints {
+            spread: Some(SpreadConstraint {
+                group: "customer".to_string(),
+                label: "rack".to_string(),
+            }),
+            ..Default::default()
+        };
+        let mut intents = Vec::new();
+        for expect_rack in [1, 0, 1, 0] {
+            let mut intent = IntentState::new();
+            intent.set_spread_group(&mut scheduler, spread.spread_group().map(str::to_string));
+            let scheduled = scheduler
+                .schedule_shard::<AttachedShardTag>(&[], &None, &spread, &context)
+                .unwrap();
+            assert_eq!(scheduled.0 % 2, expect_rack);
+            intent.set_attached(&mut scheduler, Some(scheduled));
+            intents.push(intent);
+        }
+
+        // Leaving the group releases the tenant's locations from the group's counts
+        for intent in &mut intents {
+            intent.set_spread_group(&mut scheduler, None);
+        }
+        assert!(scheduler
+            .nodes
+            .values()
+            .all(|n| n.spread_group_shard_counts.is_empty()));
+
+        for mut intent in intents {
+            intent.clear(&mut scheduler);
+        }
+    }
 }
@@ -1,6 +1,28 @@
 // @generated automatically by Diesel CLI.
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use crate::persistence::portable::sql_types::Timestamptz;
+
+    audit_log (id) {
+        id -> Int8,
+        recorded_at -> Timestamptz,
+        subject -> Nullable<Varchar>,
+        method -> Varchar,
+        path -> Varchar,
+        tenant_id -> Nullable<Varchar>,
+        node_id -> Nullable<Int8>,
+        status -> Int4,
+        request -> Nullable<Varchar>,
+        state_before -> Nullable<Varchar>,
+        state_after -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::persistence::portable::sql_types::Timestamptz;
+
     controllers (address, started_at) {
         address -> Varchar,
         started_at -> Timestamptz,
@@ -8,6 +30,9 @@
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use crate::persistence::portable::sql_types::Timestamptz;
+
     metadata_health (tenant_id, shard_number, shard_count) {
         tenant_id -> Varchar,
         shard_number -> Int4,
@@ -26,6 +51,38 @@
         listen_pg_addr -> Varchar,
         listen_pg_port -> Int4,
         availability_zone_id -> Varchar,
+        labels -> Varchar,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::persistence::portable::sql_types::Timestamptz;
+
+    operations (id) {
+        id -> Int8,
+        kind -> Varchar,
+        state -> Varchar,
+        tenant_id -> Nullable<Varchar>,
+        node_id -> Nullable<Int8>,
+        progress_completed -> Nullable<Int8>,
+        progress_total -> Nullable<Int8>,
+        error -> Nullable<Varchar>,
+        started_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::persistence::portable::sql_types::Timestamptz;
+
+    rolling_restarts (id) {
+        id -> Int8,
+        status -> Varchar,
+        started_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -42,10 +99,34 @@
         config -> Text,
         scheduling_policy -> Varchar,
         preferred_az_id -> Nullable<Varchar>,
+        split_policy -> Varchar,
+        placement_constraints -> Varchar,
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(controllers, metadata_health, nodes, tenant_shards,);
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::persistence::portable::sql_types::Int8Array;
+
+    timelines (tenant_id, timeline_id) {
+        tenant_id -> Varchar,
+        timeline_id -> Varchar,
+        generation -> Int4,
+        sk_set -> Int8Array,
+        migrating_to -> Nullable<Int8>,
+    }
+}
+
+diesel::allow_tables_to_appear_in_same_query!(
+    audit_log,
+    controllers,
+    metadata_health,
+    nodes,
+    operations,
+    rolling_restarts,
+    tenant_shards,
+    timelines,
+);
 
 diesel::table! {
     safekeepers {
@@ -58,5 +139,6 @@
         active -> Bool,
         http_port -> Int4,
         availability_zone_id -> Text,
+        scheduling_policy -> Varchar,
     }
 }
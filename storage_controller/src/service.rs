@@ -13,20 +13,23 @@
 
 use crate::{
     background_node_operations::{
-        Drain, Fill, Operation, OperationError, OperationHandler, MAX_RECONCILES_PER_OPERATION,
+        Drain, Fill, Operation, OperationError, OperationHandler, RollingRestart, SafekeeperDrain,
+        MAX_RECONCILES_PER_OPERATION,
     },
-    compute_hook::NotifyError,
+    compute_hook::{NotifyError, NotifySafekeeper},
     drain_utils::{self, TenantShardDrain, TenantShardIterator},
     id_lock_map::{trace_exclusive_lock, trace_shared_lock, IdLockMap, TracingExclusiveGuard},
     leadership::Leadership,
     metrics,
     peer_client::GlobalObservedState,
     persistence::{
-        AbortShardSplitStatus, ControllerPersistence, DatabaseResult, MetadataHealthPersistence,
-        ShardGenerationState, TenantFilter,
+        AbortShardSplitStatus, AuditFilter, ControllerPersistence, DatabaseResult,
+        MetadataHealthPersistence, NewAuditRecord, OperationFilter, SafekeeperPersistence,
+        ShardGenerationState, TenantFilter, TimelinePersistence,
     },
     reconciler::{ReconcileError, ReconcileUnits, ReconcilerConfig, ReconcilerConfigBuilder},
-    scheduler::{MaySchedule, ScheduleContext, ScheduleError, ScheduleMode},
+    safekeeper_client::SafekeeperClient,
+    scheduler::{schedule_safekeepers, MaySchedule, ScheduleContext, ScheduleError, ScheduleMode},
     tenant_shard::{
         MigrateAttachment, ObservedStateDelta, ReconcileNeeded, ReconcilerStatus,
         ScheduleOptimization, ScheduleOptimizationAction,
@@ -41,19 +44,28 @@
 use itertools::Itertools;
 use pageserver_api::{
     controller_api::{
-        MetadataHealthRecord, MetadataHealthUpdateRequest, NodeAvailability, NodeRegisterRequest,
-        NodeSchedulingPolicy, NodeShard, NodeShardResponse, PlacementPolicy, ShardSchedulingPolicy,
-        ShardsPreferredAzsRequest, ShardsPreferredAzsResponse, TenantCreateRequest,
+        AuditRecord, AuditState, AutoSplitDecisionsResponse, AutoSplitOutcome,
+        BulkMigratePlanRequest, ChaosConfig, ChaosStatus, MetadataHealthRecord,
+        MetadataHealthUpdateRequest, MigrationPlan, NodeAvailability, NodeRegisterRequest,
+        NodeSchedulingPolicy, NodeShard, NodeShardResponse, OperationKind, OperationStartResponse,
+        OperationState, OperationStatus, PlacementConstraints, PlacementPolicy, PlannedShardMove,
+        RestartSignal, RollingRestartPhase, RollingRestartRequest, RollingRestartState,
+        RollingRestartStatus, ShardSchedulingPolicy, ShardSplitPolicy, ShardsPreferredAzsRequest,
+        ShardsPreferredAzsResponse, SkSchedulingPolicy, SkippedShardMove, TenantCreateRequest,
         TenantCreateResponse, TenantCreateResponseShard, TenantDescribeResponse,
-        TenantDescribeResponseShard, TenantLocateResponse, TenantPolicyRequest,
-        TenantShardMigrateRequest, TenantShardMigrateResponse,
+        TenantDescribeResponseShard, TenantLocateResponse, TenantMergeRequest, TenantMergeResponse,
+        TenantPolicyRequest, TenantShardMigrateRequest, TenantShardMigrateResponse,
+        TimelineSafekeepersResponse,
     },
     models::{
-        SecondaryProgress, TenantConfigRequest, TimelineArchivalConfigRequest,
-        TopTenantShardsRequest,
+        utilization::UtilizationWeights, SecondaryProgress, TenantConfigRequest, TenantSorting,
+        TimelineArchivalConfigRequest, TopTenantShardsRequest,
     },
 };
 use reqwest::StatusCode;
+use safekeeper_api::models::{
+    PullTimelineRequest, TimelineCreateRequest as SafekeeperTimelineCreateRequest,
+};
 use tracing::{instrument, Instrument};
 
 use crate::pageserver_client::PageserverClient;
@@ -61,8 +73,9 @@
     models::{
         self, LocationConfig, LocationConfigListResponse, LocationConfigMode,
         PageserverUtilization, ShardParameters, TenantConfig, TenantLocationConfigRequest,
-        TenantLocationConfigResponse, TenantShardLocation, TenantShardSplitRequest,
-        TenantShardSplitResponse, TenantTimeTravelRequest, TimelineCreateRequest, TimelineInfo,
+        TenantLocationConfigResponse, TenantShardLocation, TenantShardMergeRequest,
+        TenantShardMergeSource, TenantShardSplitRequest, TenantShardSplitResponse,
+        TenantTimeTravelRequest, TimelineCreateRequest, TimelineInfo,
     },
     shard::{ShardCount, ShardIdentity, ShardNumber, ShardStripeSize, TenantShardId},
     upcall_api::{
@@ -79,6 +92,7 @@
     generation::Generation,
     http::error::ApiError,
     id::{NodeId, TenantId, TimelineId},
+    serde_percent::Percent,
     sync::gate::Gate,
 };
 
@@ -95,7 +109,11 @@
     },
 };
 
+mod autosplit;
 pub mod chaos_injector;
+mod migration_plan;
+mod operations;
+mod rolling_restart;
 
 // For operations that should be quick, like attaching a new tenant
 const SHORT_RECONCILE_TIMEOUT: Duration = Duration::from_secs(5);
@@ -104,6 +122,10 @@
 // some data in it.
 pub const RECONCILE_TIMEOUT: Duration = Duration::from_secs(30);
 
+// Drains skip shards whose secondaries have more than this left to download, unless configured
+// otherwise
+const MAX_SECONDARY_LAG_BYTES_DEFAULT: u64 = 256 * 1024 * 1024;
+
 // If we receive a call using Secondary mode initially, it will omit generation.  We will initialize
 // tenant shards into this generation, and as long as it remains in this generation, we will accept
 // input generation from future requests as authoritative.
@@ -131,6 +153,12 @@
 /// How long is too long for a reconciliation?
 pub const LONG_RECONCILE_THRESHOLD_DEFAULT: Duration = Duration::from_secs(120);
 
+/// How long to keep finished operations in the database?
+pub const OPERATIONS_RETENTION_DEFAULT: Duration = Duration::from_secs(30 * 24 * 3600);
+
+/// How long to keep records of the audit log in the database?
+pub const AUDIT_LOG_RETENTION_DEFAULT: Duration = Duration::from_secs(90 * 24 * 3600);
+
 #[derive(Clone, strum_macros::Display)]
 enum TenantOperations {
     Create,
@@ -140,6 +168,7 @@ enum TenantOperations {
     Delete,
     UpdatePolicy,
     ShardSplit,
+    ShardMerge,
     SecondaryDownload,
     TimelineCreate,
     TimelineDelete,
@@ -189,6 +218,9 @@ pub(crate) enum LeadershipStatus {
 // than they're being pushed onto the queue.
 const MAX_DELAYED_RECONCILES: usize = 10000;
 
+// How many safekeepers each timeline is placed on, when the storage controller places timelines
+const TIMELINE_SAFEKEEPER_COUNT: usize = 3;
+
 // Top level state available to all HTTP handlers
 struct ServiceState {
     leadership_status: LeadershipStatus,
@@ -318,6 +350,10 @@ pub struct Config {
     /// assume it is running in a test environment and try to update neon_local.
     pub compute_hook_url: Option<String>,
 
+    /// Where the compute hook should send notifications of changes to the safekeepers of a
+    /// timeline.  Defaults to `compute_hook_url`: if both are None, neon_local is updated instead.
+    pub compute_safekeepers_hook_url: Option<String>,
+
     /// Grace period within which a pageserver does not respond to heartbeats, but is still
     /// considered active. Once the grace period elapses, the next heartbeat failure will
     /// mark the pagseserver offline.
@@ -331,10 +367,37 @@ pub struct Config {
     /// How many Reconcilers may be spawned concurrently
     pub reconciler_concurrency: usize,
 
-    /// How large must a shard grow in bytes before we split it?
-    /// None disables auto-splitting.
+    /// How large must the logical size of a shard grow in bytes before we split it?
+    /// Auto-splitting is disabled if none of the split thresholds are set.
     pub split_threshold: Option<u64>,
 
+    /// How large must the resident size of a shard grow in bytes before we split it?
+    pub split_resident_size_threshold: Option<u64>,
+
+    /// How many bytes of WAL per second must a shard ingest before we split it?
+    pub split_wal_ingest_rate_threshold: Option<u64>,
+
+    /// Shard count that tenants are automatically split to, unless their split policy
+    /// sets a lower one.
+    pub split_max_shard_count: u8,
+
+    /// How many of its largest shards to request from each pageserver when looking for
+    /// tenants to split.
+    pub split_candidates_per_node: usize,
+
+    /// How many automatic splits may run concurrently on the same pageserver
+    pub max_concurrent_splits_per_node: usize,
+
+    /// How many automatic splits may run concurrently in the same availability zone
+    pub max_concurrent_splits_per_az: usize,
+
+    /// How the scheduler weighs the parts of pageservers' utilization when choosing a node
+    pub utilization_weights: UtilizationWeights,
+
+    /// Physical disk usage above which the optimizer migrates shards off a pageserver
+    /// (disabled if None)
+    pub disk_usage_watermark_pct: Option<Percent>,
+
     // TODO: make this cfg(feature  = "testing")
     pub neon_local_repo_dir: Option<PathBuf>,
 
@@ -353,6 +416,19 @@ pub struct Config {
     pub http_service_port: i32,
 
     pub long_reconcile_threshold: Duration,
+
+    /// Finished operations are deleted from the database once they are this old.
+    pub operations_retention: Duration,
+
+    /// Records of the audit log are deleted from the database once they are this old.
+    pub audit_log_retention: Duration,
+
+    // This JWT token will be used to authenticate this service to the safekeepers it manages.
+    pub safekeeper_jwt_token: Option<String>,
+
+    /// Whether the storage controller places new timelines onto safekeepers. When false,
+    /// safekeeper placement is left to an external orchestrator.
+    pub timelines_onto_safekeepers: bool,
 }
 
 impl From<DatabaseError> for ApiError {
@@ -397,6 +473,19 @@ pub struct Service {
     // that transition it to/from Active.
     node_op_locks: IdLockMap<NodeId, NodeOperations>,
 
+    // Automatic splits in progress, and the decisions of the last auto-split pass
+    autosplit: std::sync::Mutex<autosplit::AutoSplitState>,
+
+    // The current or most recent rolling restart.  Held across persisting its changes, so that
+    // they are written in order.
+    rolling_restart: tokio::sync::Mutex<Option<rolling_restart::RollingRestart>>,
+
+    // Long-running operations in progress, shared with their guards
+    operations: Arc<std::sync::Mutex<operations::RunningOperations>>,
+
+    // The chaos injector, if running, and the faults it armed
+    chaos: std::sync::Mutex<chaos_injector::ChaosState>,
+
     // Limit how many Reconcilers we will spawn concurrently
     reconciler_concurrency: Arc<tokio::sync::Semaphore>,
 
@@ -442,6 +531,7 @@ fn from(value: OperationError) -> Self {
                 ApiError::InternalServerError(anyhow::anyhow!(err))
             }
             OperationError::Cancelled => ApiError::Conflict("Operation was cancelled".into()),
+            OperationError::Timeout(err) => ApiError::Timeout(err),
         }
     }
 }
@@ -459,6 +549,8 @@ struct ShardSplitParams {
     targets: Vec<ShardSplitTarget>,
     policy: PlacementPolicy,
     config: TenantConfig,
+    split_policy: ShardSplitPolicy,
+    placement_constraints: PlacementConstraints,
     shard_ident: ShardIdentity,
 }
 
@@ -476,6 +568,31 @@ struct ShardSplitTarget {
     child_ids: Vec<TenantShardId>,
 }
 
+// When preparing for a shard merge, we may either choose to proceed with the merge,
+// or find that the work is already done and return NoOp.
+enum ShardMergeAction {
+    Merge {
+        old_shard_count: ShardCount,
+        targets: Vec<ShardMergeTarget>,
+    },
+    NoOp(TenantMergeResponse),
+}
+
+// A merged shard which will be created from some source shards
+struct ShardMergeTarget {
+    merged_id: TenantShardId,
+    /// The merged shard is created where the source with the same shard number is attached
+    node: Node,
+    sources: Vec<ShardMergeSource>,
+}
+
+#[derive(Clone)]
+struct ShardMergeSource {
+    id: TenantShardId,
+    node: Node,
+    generation: Generation,
+}
+
 /// When we tenant shard split operation fails, we may not be able to clean up immediately, because nodes
 /// might not be available.  We therefore use a queue of abort operations processed in the background.
 struct TenantShardSplitAbort {
@@ -948,6 +1065,44 @@ async fn background_reconcile(self: &Arc<Self>) {
             }
         }
     }
+    /// Long running background task that deletes finished operations and audit records once
+    /// they are older than their retention period, so that the tables do not grow forever.
+    #[instrument(skip_all)]
+    async fn prune_history(&self) {
+        const PRUNE_PERIOD: Duration = Duration::from_secs(3600);
+
+        let mut interval = tokio::time::interval(PRUNE_PERIOD);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = self.cancel.cancelled() => return,
+            }
+
+            // Retention periods too long to subtract from the current time keep everything
+            let now = chrono::Utc::now();
+            let cutoff = |period: Duration| {
+                chrono::Duration::from_std(period)
+                    .ok()
+                    .and_then(|period| now.checked_sub_signed(period))
+            };
+
+            if let Some(before) = cutoff(self.config.operations_retention) {
+                match self.persistence.prune_operations(before).await {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("Pruned {n} finished operations"),
+                    Err(err) => tracing::warn!("Failed to prune finished operations: {err}"),
+                }
+            }
+            if let Some(before) = cutoff(self.config.audit_log_retention) {
+                match self.persistence.prune_audit_records(before).await {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("Pruned {n} audit records"),
+                    Err(err) => tracing::warn!("Failed to prune audit records: {err}"),
+                }
+            }
+        }
+    }
+
     #[instrument(skip_all)]
     async fn spawn_heartbeat_driver(&self) {
         self.startup_complete.clone().wait().await;
@@ -1257,6 +1412,15 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
         // migrating.
         persistence.migration_run().await?;
 
+        let interrupted = persistence
+            .fail_interrupted_operations(chrono::Utc::now())
+            .await?;
+        if interrupted > 0 {
+            tracing::info!(
+                "Marked {interrupted} operations interrupted by a previous controller as failed"
+            );
+        }
+
         tracing::info!("Loading nodes from database...");
         let nodes = persistence
             .list_nodes()
@@ -1267,6 +1431,13 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
         let nodes: HashMap<NodeId, Node> = nodes.into_iter().map(|n| (n.get_id(), n)).collect();
         tracing::info!("Loaded {} nodes from database.", nodes.len());
 
+        let rolling_restart = persistence
+            .get_latest_rolling_restart()
+            .await?
+            .map(rolling_restart::RollingRestart::from_persistent)
+            .transpose()
+            .context("Bad rolling restart in DB")?;
+
         tracing::info!("Loading shards from database...");
         let mut tenant_shard_persistence = persistence.list_tenant_shards().await?;
         tracing::info!(
@@ -1274,7 +1445,7 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
             tenant_shard_persistence.len()
         );
 
-        // If any shard splits were in progress, reset the database state to abort them
+        // If any shard splits or merges were in progress, reset the database state to abort them
         let mut tenant_shard_count_min_max: HashMap<TenantId, (ShardCount, ShardCount)> =
             HashMap::new();
         for tsp in &mut tenant_shard_persistence {
@@ -1288,7 +1459,33 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
         }
 
         for (tenant_id, (count_min, count_max)) in tenant_shard_count_min_max {
-            if count_min != count_max {
+            let merging = tenant_shard_persistence.iter().any(|tsp| {
+                tsp.splitting == SplitState::Merging
+                    && TenantId::from_str(tsp.tenant_id.as_str()).unwrap() == tenant_id
+            });
+            if count_min != count_max && merging {
+                // A shard merge was in progress: the source shards are those with the higher count.  As
+                // for splits, startup reconciliation will drop the merged shards from pageservers.
+                tracing::info!("Aborting shard merge {tenant_id} {count_max:?} -> {count_min:?}");
+                let abort_status = persistence.abort_shard_merge(tenant_id, count_min).await?;
+                assert!(matches!(abort_status, AbortShardSplitStatus::Aborted));
+
+                // Reflect the database changes in-memory: the source shards are idle and in a new generation.
+                tenant_shard_persistence.iter_mut().for_each(|tsp| {
+                    let tsp_tenant_id = TenantId::from_str(tsp.tenant_id.as_str()).unwrap();
+                    if tsp_tenant_id == tenant_id
+                        && tsp.get_shard_identity().unwrap().count == count_max
+                    {
+                        tsp.splitting = SplitState::Idle;
+                        tsp.generation = tsp.generation.map(|g| g + 1);
+                    }
+                });
+
+                tenant_shard_persistence.retain(|tsp| {
+                    TenantId::from_str(tsp.tenant_id.as_str()).unwrap() != tenant_id
+                        || tsp.splitting == SplitState::Idle
+                });
+            } else if count_min != count_max {
                 // Aborting the split in the database and dropping the child shards is sufficient: the reconciliation in
                 // [`Self::startup_reconcile`] will implicitly drop the child shards on remote pageservers, or they'll
                 // be dropped later in [`Self::node_activate_reconcile`] if it isn't available right now.
@@ -1327,6 +1524,8 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
         let mut tenants = BTreeMap::new();
 
         let mut scheduler = Scheduler::new(nodes.values());
+        scheduler
+            .set_utilization_policy(config.utilization_weights, config.disk_usage_watermark_pct);
 
         #[cfg(feature = "testing")]
         {
@@ -1350,6 +1549,7 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
                     "".to_string(),
                     123,
                     AvailabilityZone("test_az".to_string()),
+                    BTreeMap::new(),
                 );
 
                 scheduler.node_upsert(&node);
@@ -1361,6 +1561,12 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
             // We will populate intent properly later in [`Self::startup_reconcile`], initially populate
             // it with what we can infer: the node for which a generation was most recently issued.
             let mut intent = IntentState::new();
+            intent.set_spread_group(
+                &mut scheduler,
+                tsp.get_placement_constraints()
+                    .spread_group()
+                    .map(str::to_string),
+            );
             if let Some(generation_pageserver) = tsp.generation_pageserver.map(|n| NodeId(n as u64))
             {
                 if nodes.contains_key(&generation_pageserver) {
@@ -1427,6 +1633,10 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
             reconcilers_gate: Gate::default(),
             tenant_op_locks: Default::default(),
             node_op_locks: Default::default(),
+            autosplit: Default::default(),
+            rolling_restart: tokio::sync::Mutex::new(rolling_restart),
+            operations: Default::default(),
+            chaos: Default::default(),
         });
 
         let result_task_this = this.clone();
@@ -1502,6 +1712,24 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
             }
         });
 
+        tokio::task::spawn({
+            let this = this.clone();
+            let startup_complete = startup_complete.clone();
+            async move {
+                startup_complete.wait().await;
+                this.rolling_restart_startup().await;
+            }
+        });
+
+        tokio::task::spawn({
+            let this = this.clone();
+            let startup_complete = startup_complete.clone();
+            async move {
+                startup_complete.wait().await;
+                this.prune_history().await;
+            }
+        });
+
         Ok(this)
     }
 
@@ -1538,6 +1766,9 @@ pub(crate) async fn attach_hook(
                 scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
                     .unwrap(),
                 preferred_az_id: None,
+                split_policy: serde_json::to_string(&ShardSplitPolicy::default()).unwrap(),
+                placement_constraints: serde_json::to_string(&PlacementConstraints::default())
+                    .unwrap(),
             };
 
             match self.persistence.insert_tenant_shards(vec![tsp]).await {
@@ -1595,6 +1826,8 @@ pub(crate) async fn attach_hook(
                             Some(conf),
                             None,
                             None,
+                            None,
+                            None,
                         )
                         .await?;
                     Some(new_generation)
@@ -2110,6 +2343,9 @@ pub(crate) async fn do_tenant_create(
                 scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
                     .unwrap(),
                 preferred_az_id: None,
+                split_policy: serde_json::to_string(&ShardSplitPolicy::default()).unwrap(),
+                placement_constraints: serde_json::to_string(&PlacementConstraints::default())
+                    .unwrap(),
             })
             .collect();
 
@@ -2493,6 +2729,8 @@ pub(crate) async fn tenant_location_config(
                             Some(tenant_config.clone()),
                             *generation,
                             None,
+                            None,
+                            None,
                         )
                         .await?;
                 }
@@ -2581,6 +2819,8 @@ pub(crate) async fn tenant_config_set(&self, req: TenantConfigRequest) -> Result
                 Some(config.clone()),
                 None,
                 None,
+                None,
+                None,
             )
             .await?;
 
@@ -2942,6 +3182,11 @@ pub(crate) async fn tenant_delete(&self, tenant_id: TenantId) -> Result<StatusCo
         // Ordering: we delete persistent state first: if we then
         // crash, we will drop the in-memory state.
 
+        // Timelines on safekeepers go before the tenant's own persistent state, so that a failure
+        // to delete them on a safekeeper or in the database leaves the tenant around for a retry.
+        self.tenant_timeline_delete_safekeepers(tenant_id, None)
+            .await?;
+
         // Drop persistent state.
         self.persistence.delete_tenant(tenant_id).await?;
 
@@ -2989,8 +3234,45 @@ pub(crate) async fn tenant_update_policy(
         let TenantPolicyRequest {
             placement,
             scheduling,
+            split,
+            constraints,
         } = req;
 
+        if let Some(max_shard_count) = split.and_then(|split| split.max_shard_count) {
+            // Pageservers split each shard into a power of two of shards, so other maximums
+            // can't be reached.
+            let locked = self.inner.read().unwrap();
+            let Some((_, shard)) = locked
+                .tenants
+                .range(TenantShardId::tenant_range(tenant_id))
+                .next()
+            else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+                ));
+            };
+            let shard_count = shard.shard.count;
+            if !autosplit::is_valid_split(shard_count, max_shard_count) {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "Maximum shard count {max_shard_count} is not a power of two multiple of the \
+                     tenant's shard count {}",
+                    shard_count.count()
+                )));
+            }
+        }
+
+        if let Some(constraints) = &constraints {
+            // Refuse constraints that no node can satisfy, rather than leaving the tenant unable to
+            // schedule.  Nodes may still be relabeled later, in which case we leave shards where they are.
+            let locked = self.inner.read().unwrap();
+            if !locked.scheduler.any_node_satisfies(constraints) {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "No pageserver has the required labels {:?}",
+                    constraints.required_labels
+                )));
+            }
+        }
+
         self.persistence
             .update_tenant_shard(
                 TenantFilter::Tenant(tenant_id),
@@ -2998,6 +3280,8 @@ pub(crate) async fn tenant_update_policy(
                 None,
                 None,
                 scheduling,
+                split,
+                constraints.clone(),
             )
             .await?;
 
@@ -3019,6 +3303,20 @@ pub(crate) async fn tenant_update_policy(
                                "Updated scheduling policy to {scheduling:?}");
             }
 
+            if let Some(split) = &split {
+                shard.set_split_policy(*split);
+
+                tracing::info!(tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
+                               "Updated split policy to {split:?}");
+            }
+
+            if let Some(constraints) = &constraints {
+                shard.set_placement_constraints(scheduler, constraints.clone());
+
+                tracing::info!(tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
+                               "Updated placement constraints to {constraints:?}");
+            }
+
             // In case scheduling is being switched back on, try it now.
             shard.schedule(scheduler, &mut schedule_context).ok();
             self.maybe_reconcile_shard(shard, nodes);
@@ -3046,7 +3344,7 @@ pub(crate) async fn tenant_timeline_create(
         .await;
         failpoint_support::sleep_millis_async!("tenant-create-timeline-shared-lock");
 
-        self.tenant_remote_mutation(tenant_id, move |mut targets| async move {
+        let timeline_info = self.tenant_remote_mutation(tenant_id, move |mut targets| async move {
             if targets.0.is_empty() {
                 return Err(ApiError::NotFound(
                     anyhow::anyhow!("Tenant not found").into(),
@@ -3161,7 +3459,14 @@ async fn create_one(
 
             Ok(timeline_info)
         })
-        .await?
+        .await??;
+
+        if self.config.timelines_onto_safekeepers {
+            self.tenant_timeline_create_safekeepers(tenant_id, &timeline_info)
+                .await?;
+        }
+
+        Ok(timeline_info)
     }
 
     pub(crate) async fn tenant_timeline_archival_config(
@@ -3236,6 +3541,23 @@ pub(crate) async fn tenant_timeline_detach_ancestor(
         &self,
         tenant_id: TenantId,
         timeline_id: TimelineId,
+    ) -> Result<models::detach_ancestor::AncestorDetached, ApiError> {
+        self.with_operation(
+            OperationKind::TimelineDetachAncestor,
+            Some(tenant_id),
+            None,
+            |operation| {
+                self.tenant_timeline_detach_ancestor_untracked(tenant_id, timeline_id, operation)
+            },
+        )
+        .await
+    }
+
+    async fn tenant_timeline_detach_ancestor_untracked(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        operation: operations::OperationStart,
     ) -> Result<models::detach_ancestor::AncestorDetached, ApiError> {
         tracing::info!("Detaching timeline {tenant_id}/{timeline_id}",);
 
@@ -3252,6 +3574,7 @@ pub(crate) async fn tenant_timeline_detach_ancestor(
                     anyhow::anyhow!("Tenant not found").into(),
                 ));
             }
+            operation.begin().await?;
 
             async fn detach_one(
                 tenant_shard_id: TenantShardId,
@@ -3612,7 +3935,7 @@ pub(crate) async fn tenant_timeline_delete(
         )
         .await;
 
-        self.tenant_remote_mutation(tenant_id, move |mut targets| async move {
+        let status = self.tenant_remote_mutation(tenant_id, move |mut targets| async move {
             if targets.0.is_empty() {
                 return Err(ApiError::NotFound(
                     anyhow::anyhow!("Tenant not found").into(),
@@ -3670,7 +3993,15 @@ async fn delete_one(
             )
             .await?;
             Ok(shard_zero_status)
-        }).await?
+        }).await??;
+
+        // Once the pageservers are done with the timeline, drop it from the safekeepers too
+        if status == StatusCode::NOT_FOUND {
+            self.tenant_timeline_delete_safekeepers(tenant_id, Some(timeline_id))
+                .await?;
+        }
+
+        Ok(status)
     }
 
     /// When you need to send an HTTP request to the pageserver that holds shard0 of a tenant, this
@@ -3855,6 +4186,8 @@ fn tenant_describe_impl<'a>(
             stripe_size: shard_zero.shard.stripe_size,
             policy: shard_zero.policy.clone(),
             config: shard_zero.config.clone(),
+            split_policy: *shard_zero.get_split_policy(),
+            placement_constraints: shard_zero.get_placement_constraints().clone(),
         })
     }
 
@@ -4084,7 +4417,15 @@ fn tenant_shard_split_commit_inmem(
             for parent_id in parent_ids {
                 let child_ids = parent_id.split(new_shard_count);
 
-                let (pageserver, generation, policy, parent_ident, config) = {
+                let (
+                    pageserver,
+                    generation,
+                    policy,
+                    parent_ident,
+                    config,
+                    split_policy,
+                    placement_constraints,
+                ) = {
                     let mut old_state = tenants
                         .remove(&parent_id)
                         .expect("It was present, we just split it");
@@ -4097,12 +4438,16 @@ fn tenant_shard_split_commit_inmem(
                     let old_attached = old_state.intent.get_attached().unwrap();
                     old_state.intent.clear(scheduler);
                     let generation = old_state.generation.expect("Shard must have been attached");
+                    let split_policy = *old_state.get_split_policy();
+                    let placement_constraints = old_state.get_placement_constraints().clone();
                     (
                         old_attached,
                         generation,
                         old_state.policy,
                         old_state.shard,
                         old_state.config,
+                        split_policy,
+                        placement_constraints,
                     )
                 };
 
@@ -4135,6 +4480,8 @@ fn tenant_shard_split_commit_inmem(
                     };
                     child_state.generation = Some(generation);
                     child_state.config = config.clone();
+                    child_state.set_split_policy(split_policy);
+                    child_state.set_placement_constraints(scheduler, placement_constraints.clone());
 
                     // The child's TenantShard::splitting is intentionally left at the default value of Idle,
                     // as at this point in the split process we have succeeded and this part is infallible:
@@ -4262,6 +4609,21 @@ pub(crate) async fn tenant_shard_split(
         &self,
         tenant_id: TenantId,
         split_req: TenantShardSplitRequest,
+    ) -> Result<TenantShardSplitResponse, ApiError> {
+        self.with_operation(
+            OperationKind::ShardSplit,
+            Some(tenant_id),
+            None,
+            |operation| self.tenant_shard_split_untracked(tenant_id, split_req, operation),
+        )
+        .await
+    }
+
+    async fn tenant_shard_split_untracked(
+        &self,
+        tenant_id: TenantId,
+        split_req: TenantShardSplitRequest,
+        operation: operations::OperationStart,
     ) -> Result<TenantShardSplitResponse, ApiError> {
         // TODO: return 503 if we get stuck waiting for this lock
         // (issue https://github.com/neondatabase/neon/issues/7108)
@@ -4281,6 +4643,7 @@ pub(crate) async fn tenant_shard_split(
             ShardSplitAction::NoOp(resp) => return Ok(resp),
             ShardSplitAction::Split(params) => params,
         };
+        operation.begin().await?;
 
         // Execute this split: this phase mutates state and does remote I/O on pageservers.  If it fails,
         // we must roll back.
@@ -4326,6 +4689,8 @@ fn prepare_tenant_shard_split(
 
         let mut policy = None;
         let mut config = None;
+        let mut split_policy = None;
+        let mut placement_constraints = None;
         let mut shard_ident = None;
         // Validate input, and calculate which shards we will create
         let (old_shard_count, targets) =
@@ -4385,6 +4750,12 @@ fn prepare_tenant_shard_split(
                     if config.is_none() {
                         config = Some(shard.config.clone());
                     }
+                    if split_policy.is_none() {
+                        split_policy = Some(*shard.get_split_policy());
+                    }
+                    if placement_constraints.is_none() {
+                        placement_constraints = Some(shard.get_placement_constraints().clone());
+                    }
 
                     if tenant_shard_id.shard_count.count() == split_req.new_shard_count {
                         tracing::info!(
@@ -4446,6 +4817,8 @@ fn prepare_tenant_shard_split(
         };
         let policy = policy.unwrap();
         let config = config.unwrap();
+        let split_policy = split_policy.unwrap();
+        let placement_constraints = placement_constraints.unwrap();
 
         Ok(ShardSplitAction::Split(Box::new(ShardSplitParams {
             old_shard_count,
@@ -4454,6 +4827,8 @@ fn prepare_tenant_shard_split(
             targets,
             policy,
             config,
+            split_policy,
+            placement_constraints,
             shard_ident,
         })))
     }
@@ -4476,6 +4851,8 @@ async fn do_tenant_shard_split(
             mut targets,
             policy,
             config,
+            split_policy,
+            placement_constraints,
             shard_ident,
         } = *params;
 
@@ -4551,6 +4928,8 @@ async fn do_tenant_shard_split(
                     scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
                         .unwrap(),
                     preferred_az_id: None,
+                    split_policy: serde_json::to_string(&split_policy).unwrap(),
+                    placement_constraints: serde_json::to_string(&placement_constraints).unwrap(),
                 });
             }
 
@@ -4578,6 +4957,7 @@ async fn do_tenant_shard_split(
         fail::fail_point!("shard-split-post-begin", |_| Err(
             ApiError::InternalServerError(anyhow::anyhow!("failpoint"))
         ));
+        self.maybe_kill_split(chaos_injector::SplitPhase::PostBegin)?;
 
         // Now that I have persisted the splitting state, apply it in-memory.  This is infallible, so
         // callers may assume that if splitting is set in memory, then it was persisted, and if splitting
@@ -4625,6 +5005,7 @@ async fn do_tenant_shard_split(
             fail::fail_point!("shard-split-post-remote", |_| Err(ApiError::Conflict(
                 "failpoint".to_string()
             )));
+            self.maybe_kill_split(chaos_injector::SplitPhase::PostRemote)?;
 
             failpoint_support::sleep_millis_async!("shard-split-post-remote-sleep", &self.cancel);
 
@@ -4738,1540 +5119,3962 @@ async fn do_tenant_shard_split(
         Ok((response, waiters))
     }
 
-    pub(crate) async fn tenant_shard_migrate(
+    pub(crate) async fn tenant_shard_merge(
         &self,
-        tenant_shard_id: TenantShardId,
-        migrate_req: TenantShardMigrateRequest,
-    ) -> Result<TenantShardMigrateResponse, ApiError> {
-        let waiter = {
-            let mut locked = self.inner.write().unwrap();
-            let (nodes, tenants, scheduler) = locked.parts_mut();
+        tenant_id: TenantId,
+        merge_req: TenantMergeRequest,
+    ) -> Result<TenantMergeResponse, ApiError> {
+        self.with_operation(
+            OperationKind::ShardMerge,
+            Some(tenant_id),
+            None,
+            |operation| self.tenant_shard_merge_untracked(tenant_id, merge_req, operation),
+        )
+        .await
+    }
 
-            let Some(node) = nodes.get(&migrate_req.node_id) else {
-                return Err(ApiError::BadRequest(anyhow::anyhow!(
-                    "Node {} not found",
-                    migrate_req.node_id
-                )));
-            };
+    async fn tenant_shard_merge_untracked(
+        &self,
+        tenant_id: TenantId,
+        merge_req: TenantMergeRequest,
+        operation: operations::OperationStart,
+    ) -> Result<TenantMergeResponse, ApiError> {
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::ShardMerge,
+        )
+        .await;
 
-            if !node.is_available() {
-                // Warn but proceed: the caller may intend to manually adjust the placement of
-                // a shard even if the node is down, e.g. if intervening during an incident.
-                tracing::warn!("Migrating to unavailable node {node}");
-            }
+        // Merging down to a single shard gives an unsharded tenant, as if it had never been split.
+        let new_shard_count = if merge_req.new_shard_count > 1 {
+            ShardCount::new(merge_req.new_shard_count)
+        } else {
+            ShardCount::new(0)
+        };
 
-            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
-                return Err(ApiError::NotFound(
-                    anyhow::anyhow!("Tenant shard not found").into(),
-                ));
+        // Validate the request and construct parameters.  This phase is fallible, but does not require
+        // rollback on errors, as it does no I/O and mutates no state.
+        let (old_shard_count, targets) =
+            match self.prepare_tenant_shard_merge(tenant_id, new_shard_count)? {
+                ShardMergeAction::NoOp(resp) => return Ok(resp),
+                ShardMergeAction::Merge {
+                    old_shard_count,
+                    targets,
+                } => (old_shard_count, targets),
             };
+        operation.begin().await?;
 
-            if shard.intent.get_attached() == &Some(migrate_req.node_id) {
-                // No-op case: we will still proceed to wait for reconciliation in case it is
-                // incomplete from an earlier update to the intent.
-                tracing::info!("Migrating: intent is unchanged {:?}", shard.intent);
-            } else {
-                let old_attached = *shard.intent.get_attached();
+        // Execute this merge: this phase mutates state and does remote I/O on pageservers.  If it fails,
+        // we must roll back.  Unlike split aborts, this is done inline: we still hold the tenant lock.
+        let r = self
+            .do_tenant_shard_merge(tenant_id, old_shard_count, new_shard_count, &targets)
+            .await;
+        if let Err(e) = &r {
+            tracing::warn!("Aborting merge of {tenant_id} after error: {e}");
+            if let Err(abort_err) = self
+                .abort_tenant_shard_merge(tenant_id, old_shard_count, new_shard_count, &targets)
+                .await
+            {
+                tracing::error!("Failed to abort merge of {tenant_id}: {abort_err}");
+            }
+        }
 
-                match shard.policy {
-                    PlacementPolicy::Attached(n) => {
-                        // If our new attached node was a secondary, it no longer should be.
-                        shard.intent.remove_secondary(scheduler, migrate_req.node_id);
+        r
+    }
 
-                        // If we were already attached to something, demote that to a secondary
-                        if let Some(old_attached) = old_attached {
-                            if n > 0 {
-                                // Remove other secondaries to make room for the location we'll demote
-                                while shard.intent.get_secondary().len() >= n {
-                                    shard.intent.pop_secondary(scheduler);
-                                }
+    fn prepare_tenant_shard_merge(
+        &self,
+        tenant_id: TenantId,
+        new_shard_count: ShardCount,
+    ) -> Result<ShardMergeAction, ApiError> {
+        fail::fail_point!("shard-merge-validation", |_| Err(ApiError::BadRequest(
+            anyhow::anyhow!("failpoint")
+        )));
 
-                                shard.intent.push_secondary(scheduler, old_attached);
-                            }
-                        }
+        let locked = self.inner.read().unwrap();
 
-                        shard.intent.set_attached(scheduler, Some(migrate_req.node_id));
-                    }
-                    PlacementPolicy::Secondary => {
-                        shard.intent.clear(scheduler);
-                        shard.intent.push_secondary(scheduler, migrate_req.node_id);
-                    }
-                    PlacementPolicy::Detached => {
-                        return Err(ApiError::BadRequest(anyhow::anyhow!(
-                            "Cannot migrate a tenant that is PlacementPolicy::Detached: configure it to an attached policy first"
-                        )))
-                    }
+        let mut old_shard_count = None;
+        let mut shards = Vec::new();
+        for (tenant_shard_id, shard) in locked.tenants.range(TenantShardId::tenant_range(tenant_id))
+        {
+            if !matches!(shard.splitting, SplitState::Idle) {
+                return Err(ApiError::Conflict(
+                    "Cannot merge, tenant is splitting or merging".to_string(),
+                ));
+            }
+            match old_shard_count {
+                None => old_shard_count = Some(shard.shard.count),
+                Some(old_shard_count) if old_shard_count != shard.shard.count => {
+                    return Err(ApiError::Conflict(
+                        "Cannot merge, currently mid-split".to_string(),
+                    ));
                 }
-
-                tracing::info!("Migrating: new intent {:?}", shard.intent);
-                shard.sequence = shard.sequence.next();
+                Some(_) => {}
             }
 
-            self.maybe_reconcile_shard(shard, nodes)
-        };
-
-        if let Some(waiter) = waiter {
-            waiter.wait_timeout(RECONCILE_TIMEOUT).await?;
-        } else {
-            tracing::info!("Migration is a no-op");
-        }
+            let node_id =
+                shard
+                    .intent
+                    .get_attached()
+                    .ok_or(ApiError::BadRequest(anyhow::anyhow!(
+                        "Cannot merge a tenant that is not attached"
+                    )))?;
+            let generation = shard
+                .generation
+                .ok_or(ApiError::BadRequest(anyhow::anyhow!(
+                    "Cannot merge a tenant that is not attached"
+                )))?;
+            let node = locked
+                .nodes
+                .get(&node_id)
+                .expect("Pageservers may not be deleted while referenced");
 
-        Ok(TenantShardMigrateResponse {})
-    }
+            shards.push(ShardMergeSource {
+                id: *tenant_shard_id,
+                node: node.clone(),
+                generation,
+            });
+        }
 
-    /// This is for debug/support only: we simply drop all state for a tenant, without
-    /// detaching or deleting it on pageservers.
-    pub(crate) async fn tenant_drop(&self, tenant_id: TenantId) -> Result<(), ApiError> {
-        self.persistence.delete_tenant(tenant_id).await?;
+        let Some(old_shard_count) = old_shard_count else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {} not found", tenant_id).into(),
+            ));
+        };
 
-        let mut locked = self.inner.write().unwrap();
-        let (_nodes, tenants, scheduler) = locked.parts_mut();
-        let mut shards = Vec::new();
-        for (tenant_shard_id, _) in tenants.range(TenantShardId::tenant_range(tenant_id)) {
-            shards.push(*tenant_shard_id);
+        if old_shard_count.count() == new_shard_count.count() {
+            return Ok(ShardMergeAction::NoOp(TenantMergeResponse {
+                new_shards: shards.into_iter().map(|s| s.id).collect(),
+            }));
         }
-
-        for shard_id in shards {
-            if let Some(mut shard) = tenants.remove(&shard_id) {
-                shard.intent.clear(scheduler);
-            }
+        if new_shard_count.count() > old_shard_count.count() {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Requested count {} but tenant has fewer shards ({})",
+                new_shard_count.count(),
+                old_shard_count.count()
+            )));
+        }
+        if old_shard_count.count() % new_shard_count.count() != 0 {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Requested count {} does not divide the tenant's shard count {}",
+                new_shard_count.count(),
+                old_shard_count.count()
+            )));
+        }
+        if shards.len() != old_shard_count.count() as usize {
+            return Err(ApiError::Conflict(format!(
+                "Tenant has {} shards, expected {}",
+                shards.len(),
+                old_shard_count.count()
+            )));
         }
 
-        Ok(())
+        let targets = (0..new_shard_count.count())
+            .map(|shard_number| {
+                let merged_id = TenantShardId {
+                    tenant_id,
+                    shard_number: ShardNumber(shard_number),
+                    shard_count: new_shard_count,
+                };
+                let source_ids = merged_id.split(old_shard_count);
+                let sources = shards
+                    .iter()
+                    .filter(|s| source_ids.contains(&s.id))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let node = sources
+                    .iter()
+                    .find(|s| s.id.shard_number == merged_id.shard_number)
+                    .expect("Every merged shard has a source with its shard number")
+                    .node
+                    .clone();
+                ShardMergeTarget {
+                    merged_id,
+                    node,
+                    sources,
+                }
+            })
+            .collect();
+
+        Ok(ShardMergeAction::Merge {
+            old_shard_count,
+            targets,
+        })
     }
 
-    /// This is for debug/support only: assuming tenant data is already present in S3, we "create" a
-    /// tenant with a very high generation number so that it will see the existing data.
-    pub(crate) async fn tenant_import(
+    async fn do_tenant_shard_merge(
         &self,
         tenant_id: TenantId,
-    ) -> Result<TenantCreateResponse, ApiError> {
-        // Pick an arbitrary available pageserver to use for scanning the tenant in remote storage
-        let maybe_node = {
-            self.inner
-                .read()
-                .unwrap()
-                .nodes
-                .values()
-                .find(|n| n.is_available())
-                .cloned()
-        };
-        let Some(node) = maybe_node else {
-            return Err(ApiError::BadRequest(anyhow::anyhow!("No nodes available")));
+        old_shard_count: ShardCount,
+        new_shard_count: ShardCount,
+        targets: &[ShardMergeTarget],
+    ) -> Result<TenantMergeResponse, ApiError> {
+        // Drop any secondary locations, as we do for splits: they cannot be merged, and the
+        // merged shards will get new secondary locations once they are scheduled.
+        let waiters = {
+            let mut locked = self.inner.write().unwrap();
+            let mut waiters = Vec::new();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+            for source in targets.iter().flat_map(|t| t.sources.iter()) {
+                let Some(shard) = tenants.get_mut(&source.id) else {
+                    // Paranoia check: this shouldn't happen: we have the oplock for this tenant ID.
+                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                        "Shard {} not found",
+                        source.id
+                    )));
+                };
+
+                if shard.intent.get_attached() != &Some(source.node.get_id()) {
+                    return Err(ApiError::Conflict(format!(
+                        "Shard {} unexpectedly rescheduled during merge",
+                        source.id
+                    )));
+                }
+
+                shard.intent.clear_secondary(scheduler);
+                if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
+                    waiters.push(waiter);
+                }
+            }
+            waiters
         };
+        self.await_waiters(waiters, RECONCILE_TIMEOUT).await?;
 
-        let client = PageserverClient::new(
-            node.get_id(),
-            node.base_url(),
-            self.config.jwt_token.as_deref(),
-        );
+        // Persist the merged shards before creating them anywhere, so that we can always clean up.
+        let merged_tsps = {
+            let locked = self.inner.read().unwrap();
+            let mut merged_tsps = Vec::new();
+            for target in targets {
+                let primary_id = TenantShardId {
+                    shard_count: old_shard_count,
+                    ..target.merged_id
+                };
+                let Some(primary) = locked.tenants.get(&primary_id) else {
+                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                        "Shard {primary_id} not found"
+                    )));
+                };
 
-        let scan_result = client
-            .tenant_scan_remote_storage(tenant_id)
+                merged_tsps.push(TenantShardPersistence {
+                    tenant_id: tenant_id.to_string(),
+                    shard_number: target.merged_id.shard_number.0 as i32,
+                    shard_count: target.merged_id.shard_count.literal() as i32,
+                    shard_stripe_size: primary.shard.stripe_size.0 as i32,
+                    // Note: generation and pageserver are placeholders, [`Persistence::begin_shard_merge`]
+                    // copies them from the primary source shard as part of its transaction.
+                    generation: None,
+                    generation_pageserver: None,
+                    placement_policy: serde_json::to_string(&primary.policy).unwrap(),
+                    config: serde_json::to_string(&primary.config).unwrap(),
+                    splitting: SplitState::Merging,
+                    scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
+                        .unwrap(),
+                    preferred_az_id: primary.preferred_az().map(ToString::to_string),
+                    split_policy: serde_json::to_string(primary.get_split_policy()).unwrap(),
+                    placement_constraints: serde_json::to_string(
+                        primary.get_placement_constraints(),
+                    )
+                    .unwrap(),
+                });
+            }
+            merged_tsps
+        };
+
+        if let Err(e) = self
+            .persistence
+            .begin_shard_merge(old_shard_count, tenant_id, merged_tsps)
             .await
-            .map_err(|e| passthrough_api_error(&node, e))?;
+        {
+            match e {
+                DatabaseError::Query(diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    _,
+                )) => {
+                    tracing::warn!("Conflicting attempt to merge {tenant_id}: {e}");
+                    return Err(ApiError::Conflict("Tenant is already merging".into()));
+                }
+                _ => return Err(ApiError::InternalServerError(e.into())),
+            }
+        }
+        fail::fail_point!("shard-merge-post-begin", |_| Err(
+            ApiError::InternalServerError(anyhow::anyhow!("failpoint"))
+        ));
 
-        // A post-split tenant may contain a mixture of shard counts in remote storage: pick the highest count.
-        let Some(shard_count) = scan_result
-            .shards
-            .iter()
-            .map(|s| s.tenant_shard_id.shard_count)
-            .max()
-        else {
-            return Err(ApiError::NotFound(
-                anyhow::anyhow!("No shards found").into(),
-            ));
-        };
+        // Apply the merging state in-memory.  Observed state of the sources becomes indeterminate:
+        // this also guarantees that they are reconfigured (in a new generation) if we abort.
+        {
+            let mut locked = self.inner.write().unwrap();
+            for source in targets.iter().flat_map(|t| t.sources.iter()) {
+                if let Some(shard) = locked.tenants.get_mut(&source.id) {
+                    shard.splitting = SplitState::Merging;
+                    shard
+                        .observed
+                        .locations
+                        .insert(source.node.get_id(), ObservedStateLocation { conf: None });
+                }
+            }
+        }
 
-        // Ideally we would set each newly imported shard's generation independently, but for correctness it is sufficient
-        // to
-        let generation = scan_result
-            .shards
-            .iter()
-            .map(|s| s.generation)
-            .max()
-            .expect("We already validated >0 shards");
+        // Phase 1: all the sources flush and upload their layers, and stop deleting any.
+        for source in targets.iter().flat_map(|t| t.sources.iter()) {
+            let client = PageserverClient::new(
+                source.node.get_id(),
+                source.node.base_url(),
+                self.config.jwt_token.as_deref(),
+            );
+            client
+                .tenant_shard_merge_prepare(source.id)
+                .await
+                .map_err(|e| {
+                    ApiError::Conflict(format!("Failed to prepare {} for merge: {}", source.id, e))
+                })?;
+        }
 
-        // FIXME: we have no way to recover the shard stripe size from contents of remote storage: this will
-        // only work if they were using the default stripe size.
-        let stripe_size = ShardParameters::DEFAULT_STRIPE_SIZE;
+        // Phase 2: create each merged shard next to its primary source.
+        for target in targets {
+            let primary = target
+                .sources
+                .iter()
+                .find(|s| s.id.shard_number == target.merged_id.shard_number)
+                .expect("Every merged shard has a source with its shard number");
+            let client = PageserverClient::new(
+                target.node.get_id(),
+                target.node.base_url(),
+                self.config.jwt_token.as_deref(),
+            );
+            let response = client
+                .tenant_shard_merge(
+                    primary.id,
+                    TenantShardMergeRequest {
+                        new_shard_count: new_shard_count.literal(),
+                        generation: primary.generation.into().unwrap(),
+                        sources: target
+                            .sources
+                            .iter()
+                            .map(|s| TenantShardMergeSource {
+                                tenant_shard_id: s.id,
+                                generation: s.generation.into().unwrap(),
+                            })
+                            .collect(),
+                    },
+                )
+                .await
+                .map_err(|e| {
+                    ApiError::Conflict(format!("Failed to merge into {}: {}", target.merged_id, e))
+                })?;
 
-        let (response, waiters) = self
-            .do_tenant_create(TenantCreateRequest {
-                new_tenant_id: TenantShardId::unsharded(tenant_id),
-                generation,
+            fail::fail_point!("shard-merge-post-remote", |_| Err(ApiError::Conflict(
+                "failpoint".to_string()
+            )));
 
-                shard_parameters: ShardParameters {
-                    count: shard_count,
-                    stripe_size,
-                },
-                placement_policy: Some(PlacementPolicy::Attached(0)), // No secondaries, for convenient debug/hacking
+            if response.new_shard != target.merged_id {
+                return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                    "Merging into {} resulted in unexpected ID {}",
+                    target.merged_id,
+                    response.new_shard
+                )));
+            }
+        }
 
-                // There is no way to know what the tenant's config was: revert to defaults
-                //
-                // TODO: remove `switch_aux_file_policy` once we finish auxv2 migration
-                //
-                // we write to both v1+v2 storage, so that the test case can use either storage format for testing
-                config: TenantConfig {
-                    switch_aux_file_policy: Some(models::AuxFilePolicy::CrossValidation),
-                    ..TenantConfig::default()
-                },
-            })
+        self.persistence
+            .complete_shard_split(tenant_id, old_shard_count)
             .await?;
 
-        if let Err(e) = self.await_waiters(waiters, SHORT_RECONCILE_TIMEOUT).await {
-            // Since this is a debug/support operation, all kinds of weird issues are possible (e.g. this
-            // tenant doesn't exist in the control plane), so don't fail the request if it can't fully
-            // reconcile, as reconciliation includes notifying compute.
-            tracing::warn!(%tenant_id, "Reconcile not done yet while importing tenant ({e})");
+        fail::fail_point!("shard-merge-post-complete", |_| Err(
+            ApiError::InternalServerError(anyhow::anyhow!("failpoint"))
+        ));
+
+        let (response, merged_locations) = self.tenant_shard_merge_commit_inmem(targets);
+
+        // The sources are no longer known to us: detach them, or they would keep ingesting WAL.  Failures
+        // are not fatal, the merge is complete: leftover locations are cleaned up when their node is next
+        // activated.
+        let source_locations = targets
+            .iter()
+            .flat_map(|t| t.sources.iter())
+            .map(|s| (s.node.clone(), s.id))
+            .collect();
+        if let Err(e) = self.tenant_shard_merge_detach(source_locations).await {
+            tracing::warn!("Failed to detach source shards after merge: {e}");
+        }
+
+        // Send compute notifications for all the merged shards
+        let mut failed_notifications = Vec::new();
+        for (merged_id, merged_ps, stripe_size) in merged_locations {
+            if let Err(e) = self
+                .compute_hook
+                .notify(merged_id, merged_ps, stripe_size, &self.cancel)
+                .await
+            {
+                tracing::warn!("Failed to update compute of {}->{} during merge, proceeding anyway to complete merge ({e})",
+                        merged_id, merged_ps);
+                failed_notifications.push(merged_id);
+            }
+        }
+        if !failed_notifications.is_empty() {
+            let mut locked = self.inner.write().unwrap();
+            for failed in failed_notifications {
+                if let Some(shard) = locked.tenants.get_mut(&failed) {
+                    shard.pending_compute_notification = true;
+                }
+            }
         }
 
         Ok(response)
     }
 
-    /// For debug/support: a full JSON dump of TenantShards.  Returns a response so that
-    /// we don't have to make TenantShard clonable in the return path.
-    pub(crate) fn tenants_dump(&self) -> Result<hyper::Response<hyper::Body>, ApiError> {
-        let serialized = {
-            let locked = self.inner.read().unwrap();
-            let result = locked.tenants.values().collect::<Vec<_>>();
-            serde_json::to_string(&result).map_err(|e| ApiError::InternalServerError(e.into()))?
+    /// Infallible final stage of [`Self::tenant_shard_merge`]: replace the source shards with the
+    /// merged shards in the tenant map.
+    fn tenant_shard_merge_commit_inmem(
+        &self,
+        targets: &[ShardMergeTarget],
+    ) -> (
+        TenantMergeResponse,
+        Vec<(TenantShardId, NodeId, ShardStripeSize)>,
+    ) {
+        let mut response = TenantMergeResponse {
+            new_shards: Vec::new(),
         };
+        let mut merged_locations = Vec::new();
 
-        hyper::Response::builder()
-            .status(hyper::StatusCode::OK)
-            .header(hyper::header::CONTENT_TYPE, "application/json")
-            .body(hyper::Body::from(serialized))
-            .map_err(|e| ApiError::InternalServerError(e.into()))
-    }
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, scheduler) = locked.parts_mut();
+        let mut schedule_context = ScheduleContext::default();
+        for target in targets {
+            let primary_id = target
+                .sources
+                .iter()
+                .find(|s| s.id.shard_number == target.merged_id.shard_number)
+                .expect("Every merged shard has a source with its shard number")
+                .id;
 
-    /// Check the consistency of in-memory state vs. persistent state, and check that the
-    /// scheduler's statistics are up to date.
-    ///
-    /// These consistency checks expect an **idle** system.  If changes are going on while
-    /// we run, then we can falsely indicate a consistency issue.  This is sufficient for end-of-test
-    /// checks, but not suitable for running continuously in the background in the field.
-    pub(crate) async fn consistency_check(&self) -> Result<(), ApiError> {
-        let (mut expect_nodes, mut expect_shards) = {
-            let locked = self.inner.read().unwrap();
-
-            locked
-                .scheduler
-                .consistency_check(locked.nodes.values(), locked.tenants.values())
-                .context("Scheduler checks")
-                .map_err(ApiError::InternalServerError)?;
-
-            let expect_nodes = locked
-                .nodes
-                .values()
-                .map(|n| n.to_persistent())
-                .collect::<Vec<_>>();
-
-            let expect_shards = locked
-                .tenants
-                .values()
-                .map(|t| t.to_persistent())
-                .collect::<Vec<_>>();
-
-            // This method can only validate the state of an idle system: if a reconcile is in
-            // progress, fail out early to avoid giving false errors on state that won't match
-            // between database and memory under a ReconcileResult is processed.
-            for t in locked.tenants.values() {
-                if t.reconciler.is_some() {
-                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                        "Shard {} reconciliation in progress",
-                        t.tenant_shard_id
-                    )));
+            let mut template = None;
+            for source in &target.sources {
+                let Some(mut old_state) = tenants.remove(&source.id) else {
+                    continue;
+                };
+                old_state.intent.clear(scheduler);
+                if source.id == primary_id {
+                    template = Some(old_state);
                 }
             }
+            let Some(primary) = template else {
+                tracing::warn!("Primary source {primary_id} not found while completing merge");
+                continue;
+            };
+            let generation = primary.generation.expect("Shard must have been attached");
 
-            (expect_nodes, expect_shards)
-        };
-
-        let mut nodes = self.persistence.list_nodes().await?;
-        expect_nodes.sort_by_key(|n| n.node_id);
-        nodes.sort_by_key(|n| n.node_id);
-
-        if nodes != expect_nodes {
-            tracing::error!("Consistency check failed on nodes.");
-            tracing::error!(
-                "Nodes in memory: {}",
-                serde_json::to_string(&expect_nodes)
-                    .map_err(|e| ApiError::InternalServerError(e.into()))?
-            );
-            tracing::error!(
-                "Nodes in database: {}",
-                serde_json::to_string(&nodes)
-                    .map_err(|e| ApiError::InternalServerError(e.into()))?
-            );
-            return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                "Node consistency failure"
-            )));
-        }
-
-        let mut shards = self.persistence.list_tenant_shards().await?;
-        shards.sort_by_key(|tsp| (tsp.tenant_id.clone(), tsp.shard_number, tsp.shard_count));
-        expect_shards.sort_by_key(|tsp| (tsp.tenant_id.clone(), tsp.shard_number, tsp.shard_count));
+            let mut merged_shard = primary.shard;
+            merged_shard.number = target.merged_id.shard_number;
+            merged_shard.count = target.merged_id.shard_count;
 
-        if shards != expect_shards {
-            tracing::error!("Consistency check failed on shards.");
-            tracing::error!(
-                "Shards in memory: {}",
-                serde_json::to_string(&expect_shards)
-                    .map_err(|e| ApiError::InternalServerError(e.into()))?
-            );
-            tracing::error!(
-                "Shards in database: {}",
-                serde_json::to_string(&shards)
-                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            let mut merged_observed: HashMap<NodeId, ObservedStateLocation> = HashMap::new();
+            merged_observed.insert(
+                target.node.get_id(),
+                ObservedStateLocation {
+                    conf: Some(attached_location_conf(
+                        generation,
+                        &merged_shard,
+                        &primary.config,
+                        &primary.policy,
+                    )),
+                },
             );
-            return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                "Shard consistency failure"
-            )));
-        }
-
-        Ok(())
-    }
-
-    /// For debug/support: a JSON dump of the [`Scheduler`].  Returns a response so that
-    /// we don't have to make TenantShard clonable in the return path.
-    pub(crate) fn scheduler_dump(&self) -> Result<hyper::Response<hyper::Body>, ApiError> {
-        let serialized = {
-            let locked = self.inner.read().unwrap();
-            serde_json::to_string(&locked.scheduler)
-                .map_err(|e| ApiError::InternalServerError(e.into()))?
-        };
-
-        hyper::Response::builder()
-            .status(hyper::StatusCode::OK)
-            .header(hyper::header::CONTENT_TYPE, "application/json")
-            .body(hyper::Body::from(serialized))
-            .map_err(|e| ApiError::InternalServerError(e.into()))
-    }
 
-    /// This is for debug/support only: we simply drop all state for a tenant, without
-    /// detaching or deleting it on pageservers.  We do not try and re-schedule any
-    /// tenants that were on this node.
-    pub(crate) async fn node_drop(&self, node_id: NodeId) -> Result<(), ApiError> {
-        self.persistence.delete_node(node_id).await?;
+            let mut merged_state =
+                TenantShard::new(target.merged_id, merged_shard, primary.policy.clone());
+            merged_state.intent = IntentState::single(scheduler, Some(target.node.get_id()));
+            merged_state.observed = ObservedState {
+                locations: merged_observed,
+            };
+            merged_state.generation = Some(generation);
+            merged_state.config = primary.config.clone();
+            merged_state.set_split_policy(*primary.get_split_policy());
+            merged_state
+                .set_placement_constraints(scheduler, primary.get_placement_constraints().clone());
+            if let Some(az) = primary.preferred_az() {
+                merged_state.set_preferred_az(az.clone());
+            }
+
+            merged_locations.push((
+                target.merged_id,
+                target.node.get_id(),
+                merged_shard.stripe_size,
+            ));
 
-        let mut locked = self.inner.write().unwrap();
+            if let Err(e) = merged_state.schedule(scheduler, &mut schedule_context) {
+                tracing::warn!("Failed to schedule merged shard {}: {e}", target.merged_id);
+            }
+            self.maybe_reconcile_shard(&mut merged_state, nodes);
 
-        for shard in locked.tenants.values_mut() {
-            shard.deref_node(node_id);
-            shard.observed.locations.remove(&node_id);
+            tenants.insert(target.merged_id, merged_state);
+            response.new_shards.push(target.merged_id);
         }
 
-        let mut nodes = (*locked.nodes).clone();
-        nodes.remove(&node_id);
-        locked.nodes = Arc::new(nodes);
-
-        locked.scheduler.node_remove(node_id);
-
-        Ok(())
+        (response, merged_locations)
     }
 
-    /// If a node has any work on it, it will be rescheduled: this is "clean" in the sense
-    /// that we don't leave any bad state behind in the storage controller, but unclean
-    /// in the sense that we are not carefully draining the node.
-    pub(crate) async fn node_delete(&self, node_id: NodeId) -> Result<(), ApiError> {
-        let _node_lock =
-            trace_exclusive_lock(&self.node_op_locks, node_id, NodeOperations::Delete).await;
+    /// Clean up after a failed [`Self::tenant_shard_merge`]: restore the source shards and detach
+    /// any merged shards that were created on pageservers.
+    #[instrument(skip_all, fields(tenant_id=%tenant_id))]
+    async fn abort_tenant_shard_merge(
+        &self,
+        tenant_id: TenantId,
+        old_shard_count: ShardCount,
+        new_shard_count: ShardCount,
+        targets: &[ShardMergeTarget],
+    ) -> Result<(), ApiError> {
+        match self
+            .persistence
+            .abort_shard_merge(tenant_id, new_shard_count)
+            .await?
+        {
+            AbortShardSplitStatus::Aborted => {}
+            AbortShardSplitStatus::Complete => {
+                // The merge completed in the database, but we did not see the result: complete it in memory.
+                let (_response, _merged_locations) = self.tenant_shard_merge_commit_inmem(targets);
+                let source_locations = targets
+                    .iter()
+                    .flat_map(|t| t.sources.iter())
+                    .map(|s| (s.node.clone(), s.id))
+                    .collect();
+                return self.tenant_shard_merge_detach(source_locations).await;
+            }
+        }
 
-        // 1. Atomically update in-memory state:
-        //    - set the scheduling state to Pause to make subsequent scheduling ops skip it
-        //    - update shards' intents to exclude the node, and reschedule any shards whose intents we modified.
-        //    - drop the node from the main nodes map, so that when running reconciles complete they do not
-        //      re-insert references to this node into the ObservedState of shards
-        //    - drop the node from the scheduler
         {
             let mut locked = self.inner.write().unwrap();
             let (nodes, tenants, scheduler) = locked.parts_mut();
-
+            for (tenant_shard_id, shard) in
+                tenants.range_mut(TenantShardId::tenant_range(tenant_id))
             {
-                let mut nodes_mut = (*nodes).deref().clone();
-                match nodes_mut.get_mut(&node_id) {
-                    Some(node) => {
-                        // We do not bother setting this in the database, because we're about to delete the row anyway, and
-                        // if we crash it would not be desirable to leave the node paused after a restart.
-                        node.set_scheduling(NodeSchedulingPolicy::Pause);
-                    }
-                    None => {
-                        tracing::info!(
-                            "Node not found: presuming this is a retry and returning success"
-                        );
-                        return Ok(());
-                    }
+                if shard.shard.count != old_shard_count {
+                    continue;
                 }
 
-                *nodes = Arc::new(nodes_mut);
+                tracing::info!("Restoring source shard {tenant_shard_id}");
+                shard.splitting = SplitState::Idle;
+                // Mirror the generation increment done by the database abort, and make sure that the
+                // source is reconfigured: this restarts it on its pageserver, re-enabling uploads.
+                shard.generation = shard.generation.map(|g| g.next());
+                if let Some(node_id) = shard.intent.get_attached() {
+                    shard
+                        .observed
+                        .locations
+                        .insert(*node_id, ObservedStateLocation { conf: None });
+                }
+                if let Err(e) = shard.schedule(scheduler, &mut ScheduleContext::default()) {
+                    tracing::warn!("Failed to schedule {tenant_shard_id} during merge abort: {e}")
+                }
+
+                self.maybe_reconcile_shard(shard, nodes);
             }
 
-            for (tenant_shard_id, shard) in tenants {
-                if shard.deref_node(node_id) {
-                    // FIXME: we need to build a ScheduleContext that reflects this shard's peers, otherwise
-                    // it won't properly do anti-affinity.
-                    let mut schedule_context = ScheduleContext::default();
+            tenants.retain(|id, s| id.tenant_id != tenant_id || s.shard.count != new_shard_count);
+        }
 
-                    if let Err(e) = shard.schedule(scheduler, &mut schedule_context) {
-                        // TODO: implement force flag to remove a node even if we can't reschedule
-                        // a tenant
-                        tracing::error!("Refusing to delete node, shard {tenant_shard_id} can't be rescheduled: {e}");
-                        return Err(e.into());
-                    } else {
-                        tracing::info!(
-                            "Rescheduled shard {tenant_shard_id} away from node during deletion"
-                        )
-                    }
+        let merged_locations = targets
+            .iter()
+            .map(|t| (t.node.clone(), t.merged_id))
+            .collect();
+        self.tenant_shard_merge_detach(merged_locations).await?;
 
-                    self.maybe_reconcile_shard(shard, nodes);
-                }
+        tracing::info!("Successfully aborted merge");
+        Ok(())
+    }
 
-                // Here we remove an existing observed location for the node we're removing, and it will
-                // not be re-added by a reconciler's completion because we filter out removed nodes in
-                // process_result.
-                //
-                // Note that we update the shard's observed state _after_ calling maybe_reconcile_shard: that
-                // means any reconciles we spawned will know about the node we're deleting, enabling them
-                // to do live migrations if it's still online.
-                shard.observed.locations.remove(&node_id);
+    /// Detach shard locations that the storage controller no longer tracks, after a merge or its abort.
+    async fn tenant_shard_merge_detach(
+        &self,
+        locations: Vec<(Node, TenantShardId)>,
+    ) -> Result<(), ApiError> {
+        for (node, tenant_shard_id) in locations {
+            if !node.is_available() {
+                tracing::warn!("Node {node} unavailable, can't detach {tenant_shard_id}. It will be cleaned up when it is reactivated.");
+                continue;
             }
 
-            scheduler.node_remove(node_id);
+            tracing::info!("Detaching {tenant_shard_id} on {node}...");
+            match node
+                .with_client_retries(
+                    |client| async move {
+                        let config = LocationConfig {
+                            mode: LocationConfigMode::Detached,
+                            generation: None,
+                            secondary_conf: None,
+                            shard_number: tenant_shard_id.shard_number.0,
+                            shard_count: tenant_shard_id.shard_count.literal(),
+                            // Stripe size and tenant config don't matter when detaching
+                            shard_stripe_size: 0,
+                            tenant_conf: TenantConfig::default(),
+                        };
 
+                        client
+                            .location_config(tenant_shard_id, config, None, false)
+                            .await
+                    },
+                    &self.config.jwt_token,
+                    1,
+                    10,
+                    Duration::from_secs(5),
+                    &self.cancel,
+                )
+                .await
             {
-                let mut nodes_mut = (**nodes).clone();
-                if let Some(mut removed_node) = nodes_mut.remove(&node_id) {
-                    // Ensure that any reconciler holding an Arc<> to this node will
-                    // drop out when trying to RPC to it (setting Offline state sets the
-                    // cancellation token on the Node object).
-                    removed_node.set_availability(NodeAvailability::Offline);
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    return Err(ApiError::Conflict(format!(
+                        "Failed to detach {tenant_shard_id} from node {node}: {e}"
+                    )));
                 }
-                *nodes = Arc::new(nodes_mut);
+                None => return Err(ApiError::ShuttingDown),
             }
         }
 
-        // Note: some `generation_pageserver` columns on tenant shards in the database may still refer to
-        // the removed node, as this column means "The pageserver to which this generation was issued", and
-        // their generations won't get updated until the reconcilers moving them away from this node complete.
-        // That is safe because in Service::spawn we only use generation_pageserver if it refers to a node
-        // that exists.
-
-        // 2. Actually delete the node from the database and from in-memory state
-        tracing::info!("Deleting node from database");
-        self.persistence.delete_node(node_id).await?;
-
         Ok(())
     }
 
-    pub(crate) async fn node_list(&self) -> Result<Vec<Node>, ApiError> {
-        let nodes = {
-            self.inner
-                .read()
-                .unwrap()
-                .nodes
-                .values()
-                .cloned()
-                .collect::<Vec<_>>()
-        };
-
-        Ok(nodes)
-    }
-
-    pub(crate) async fn get_node(&self, node_id: NodeId) -> Result<Node, ApiError> {
-        self.inner
-            .read()
-            .unwrap()
-            .nodes
-            .get(&node_id)
-            .cloned()
-            .ok_or(ApiError::NotFound(
-                format!("Node {node_id} not registered").into(),
-            ))
+    pub(crate) async fn tenant_shard_migrate(
+        &self,
+        tenant_shard_id: TenantShardId,
+        migrate_req: TenantShardMigrateRequest,
+    ) -> Result<TenantShardMigrateResponse, ApiError> {
+        self.with_operation(
+            OperationKind::ShardMigrate,
+            Some(tenant_shard_id.tenant_id),
+            Some(migrate_req.node_id),
+            |operation| {
+                self.tenant_shard_migrate_untracked(tenant_shard_id, migrate_req, operation)
+            },
+        )
+        .await
     }
 
-    pub(crate) async fn get_node_shards(
+    async fn tenant_shard_migrate_untracked(
         &self,
-        node_id: NodeId,
-    ) -> Result<NodeShardResponse, ApiError> {
-        let locked = self.inner.read().unwrap();
-        let mut shards = Vec::new();
-        for (tid, tenant) in locked.tenants.iter() {
-            let is_intended_secondary = match (
-                tenant.intent.get_attached() == &Some(node_id),
-                tenant.intent.get_secondary().contains(&node_id),
-            ) {
-                (true, true) => {
-                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                        "{} attached as primary+secondary on the same node",
-                        tid
-                    )))
-                }
-                (true, false) => Some(false),
-                (false, true) => Some(true),
-                (false, false) => None,
-            };
-            let is_observed_secondary = if let Some(ObservedStateLocation { conf: Some(conf) }) =
-                tenant.observed.locations.get(&node_id)
-            {
-                Some(conf.secondary_conf.is_some())
-            } else {
-                None
+        tenant_shard_id: TenantShardId,
+        migrate_req: TenantShardMigrateRequest,
+        operation: operations::OperationStart,
+    ) -> Result<TenantShardMigrateResponse, ApiError> {
+        let waiter = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            let Some(node) = nodes.get(&migrate_req.node_id) else {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "Node {} not found",
+                    migrate_req.node_id
+                )));
             };
-            if is_intended_secondary.is_some() || is_observed_secondary.is_some() {
-                shards.push(NodeShard {
-                    tenant_shard_id: *tid,
-                    is_intended_secondary,
-                    is_observed_secondary,
-                });
+
+            if !node.is_available() {
+                // Warn but proceed: the caller may intend to manually adjust the placement of
+                // a shard even if the node is down, e.g. if intervening during an incident.
+                tracing::warn!("Migrating to unavailable node {node}");
             }
-        }
-        Ok(NodeShardResponse { node_id, shards })
-    }
 
-    pub(crate) async fn get_leader(&self) -> DatabaseResult<Option<ControllerPersistence>> {
-        self.persistence.get_leader().await
-    }
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
 
-    pub(crate) async fn node_register(
-        &self,
-        register_req: NodeRegisterRequest,
-    ) -> Result<(), ApiError> {
-        let _node_lock = trace_exclusive_lock(
-            &self.node_op_locks,
-            register_req.node_id,
-            NodeOperations::Register,
-        )
-        .await;
+            if shard.intent.get_attached() == &Some(migrate_req.node_id) {
+                // No-op case: we will still proceed to wait for reconciliation in case it is
+                // incomplete from an earlier update to the intent.
+                tracing::info!("Migrating: intent is unchanged {:?}", shard.intent);
+            } else {
+                let old_attached = *shard.intent.get_attached();
 
-        enum RegistrationStatus {
-            Matched,
-            Mismatched,
-            New,
-        }
+                match shard.policy {
+                    PlacementPolicy::Attached(n) => {
+                        // If our new attached node was a secondary, it no longer should be.
+                        shard.intent.remove_secondary(scheduler, migrate_req.node_id);
 
-        let registration_status = {
-            let locked = self.inner.read().unwrap();
-            if let Some(node) = locked.nodes.get(&register_req.node_id) {
-                if node.registration_match(&register_req) {
-                    RegistrationStatus::Matched
-                } else {
-                    RegistrationStatus::Mismatched
-                }
-            } else {
-                RegistrationStatus::New
-            }
-        };
+                        // If we were already attached to something, demote that to a secondary
+                        if let Some(old_attached) = old_attached {
+                            if n > 0 {
+                                // Remove other secondaries to make room for the location we'll demote
+                                while shard.intent.get_secondary().len() >= n {
+                                    shard.intent.pop_secondary(scheduler);
+                                }
 
-        match registration_status {
-            RegistrationStatus::Matched => {
-                tracing::info!(
-                    "Node {} re-registered with matching address",
-                    register_req.node_id
-                );
+                                shard.intent.push_secondary(scheduler, old_attached);
+                            }
+                        }
 
-                return Ok(());
-            }
-            RegistrationStatus::Mismatched => {
-                // TODO: decide if we want to allow modifying node addresses without removing and re-adding
-                // the node.  Safest/simplest thing is to refuse it, and usually we deploy with
-                // a fixed address through the lifetime of a node.
-                tracing::warn!(
-                    "Node {} tried to register with different address",
-                    register_req.node_id
-                );
-                return Err(ApiError::Conflict(
-                    "Node is already registered with different address".to_string(),
-                ));
-            }
-            RegistrationStatus::New => {
-                // fallthrough
+                        shard.intent.set_attached(scheduler, Some(migrate_req.node_id));
+                    }
+                    PlacementPolicy::Secondary => {
+                        shard.intent.clear(scheduler);
+                        shard.intent.push_secondary(scheduler, migrate_req.node_id);
+                    }
+                    PlacementPolicy::Detached => {
+                        return Err(ApiError::BadRequest(anyhow::anyhow!(
+                            "Cannot migrate a tenant that is PlacementPolicy::Detached: configure it to an attached policy first"
+                        )))
+                    }
+                }
+
+                tracing::info!("Migrating: new intent {:?}", shard.intent);
+                shard.sequence = shard.sequence.next();
             }
-        }
 
-        // We do not require that a node is actually online when registered (it will start life
-        // with it's  availability set to Offline), but we _do_ require that its DNS record exists. We're
-        // therefore not immune to asymmetric L3 connectivity issues, but we are protected against nodes
-        // that register themselves with a broken DNS config.  We check only the HTTP hostname, because
-        // the postgres hostname might only be resolvable to clients (e.g. if we're on a different VPC than clients).
-        if tokio::net::lookup_host(format!(
-            "{}:{}",
-            register_req.listen_http_addr, register_req.listen_http_port
-        ))
-        .await
-        .is_err()
-        {
-            // If we have a transient DNS issue, it's up to the caller to retry their registration.  Because
-            // we can't robustly distinguish between an intermittent issue and a totally bogus DNS situation,
-            // we return a soft 503 error, to encourage callers to retry past transient issues.
-            return Err(ApiError::ResourceUnavailable(
-                format!(
-                    "Node {} tried to register with unknown DNS name '{}'",
-                    register_req.node_id, register_req.listen_http_addr
-                )
-                .into(),
-            ));
+            self.maybe_reconcile_shard(shard, nodes)
+        };
+
+        if let Some(waiter) = waiter {
+            operation.begin().await?;
+            waiter.wait_timeout(RECONCILE_TIMEOUT).await?;
+        } else {
+            tracing::info!("Migration is a no-op");
         }
 
-        // Ordering: we must persist the new node _before_ adding it to in-memory state.
-        // This ensures that before we use it for anything or expose it via any external
-        // API, it is guaranteed to be available after a restart.
-        let new_node = Node::new(
-            register_req.node_id,
-            register_req.listen_http_addr,
-            register_req.listen_http_port,
-            register_req.listen_pg_addr,
-            register_req.listen_pg_port,
-            register_req.availability_zone_id,
-        );
+        Ok(TenantShardMigrateResponse {})
+    }
 
-        // TODO: idempotency if the node already exists in the database
-        self.persistence.insert_node(&new_node).await?;
+    /// This is for debug/support only: we simply drop all state for a tenant, without
+    /// detaching or deleting it on pageservers.
+    pub(crate) async fn tenant_drop(&self, tenant_id: TenantId) -> Result<(), ApiError> {
+        self.persistence.delete_tenant(tenant_id).await?;
 
         let mut locked = self.inner.write().unwrap();
-        let mut new_nodes = (*locked.nodes).clone();
-
-        locked.scheduler.node_upsert(&new_node);
-        new_nodes.insert(register_req.node_id, new_node);
+        let (_nodes, tenants, scheduler) = locked.parts_mut();
+        let mut shards = Vec::new();
+        for (tenant_shard_id, _) in tenants.range(TenantShardId::tenant_range(tenant_id)) {
+            shards.push(*tenant_shard_id);
+        }
 
-        locked.nodes = Arc::new(new_nodes);
+        for shard_id in shards {
+            if let Some(mut shard) = tenants.remove(&shard_id) {
+                shard.intent.clear(scheduler);
+            }
+        }
 
-        tracing::info!(
-            "Registered pageserver {}, now have {} pageservers",
-            register_req.node_id,
-            locked.nodes.len()
-        );
         Ok(())
     }
 
-    /// Configure in-memory and persistent state of a node as requested
-    ///
-    /// Note that this function does not trigger any immediate side effects in response
-    /// to the changes. That part is handled by [`Self::handle_node_availability_transition`].
-    async fn node_state_configure(
+    /// This is for debug/support only: assuming tenant data is already present in S3, we "create" a
+    /// tenant with a very high generation number so that it will see the existing data.
+    pub(crate) async fn tenant_import(
         &self,
-        node_id: NodeId,
-        availability: Option<NodeAvailability>,
-        scheduling: Option<NodeSchedulingPolicy>,
-        node_lock: &TracingExclusiveGuard<NodeOperations>,
-    ) -> Result<AvailabilityTransition, ApiError> {
-        if let Some(scheduling) = scheduling {
-            // Scheduling is a persistent part of Node: we must write updates to the database before
-            // applying them in memory
-            self.persistence.update_node(node_id, scheduling).await?;
-        }
-
-        // If we're activating a node, then before setting it active we must reconcile any shard locations
-        // on that node, in case it is out of sync, e.g. due to being unavailable during controller startup,
-        // by calling [`Self::node_activate_reconcile`]
-        //
-        // The transition we calculate here remains valid later in the function because we hold the op lock on the node:
-        // nothing else can mutate its availability while we run.
-        let availability_transition = if let Some(input_availability) = availability.as_ref() {
-            let (activate_node, availability_transition) = {
-                let locked = self.inner.read().unwrap();
-                let Some(node) = locked.nodes.get(&node_id) else {
-                    return Err(ApiError::NotFound(
-                        anyhow::anyhow!("Node {} not registered", node_id).into(),
-                    ));
-                };
-
-                (
-                    node.clone(),
-                    node.get_availability_transition(input_availability),
-                )
-            };
+        tenant_id: TenantId,
+    ) -> Result<TenantCreateResponse, ApiError> {
+        self.with_operation(
+            OperationKind::TenantImport,
+            Some(tenant_id),
+            None,
+            |operation| self.tenant_import_untracked(tenant_id, operation),
+        )
+        .await
+    }
 
-            if matches!(availability_transition, AvailabilityTransition::ToActive) {
-                self.node_activate_reconcile(activate_node, node_lock)
-                    .await?;
-            }
-            availability_transition
-        } else {
-            AvailabilityTransition::Unchanged
+    async fn tenant_import_untracked(
+        &self,
+        tenant_id: TenantId,
+        operation: operations::OperationStart,
+    ) -> Result<TenantCreateResponse, ApiError> {
+        // Pick an arbitrary available pageserver to use for scanning the tenant in remote storage
+        let maybe_node = {
+            self.inner
+                .read()
+                .unwrap()
+                .nodes
+                .values()
+                .find(|n| n.is_available())
+                .cloned()
+        };
+        let Some(node) = maybe_node else {
+            return Err(ApiError::BadRequest(anyhow::anyhow!("No nodes available")));
         };
 
-        // Apply changes from the request to our in-memory state for the Node
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, _tenants, scheduler) = locked.parts_mut();
+        let client = PageserverClient::new(
+            node.get_id(),
+            node.base_url(),
+            self.config.jwt_token.as_deref(),
+        );
 
-        let mut new_nodes = (**nodes).clone();
+        let scan_result = client
+            .tenant_scan_remote_storage(tenant_id)
+            .await
+            .map_err(|e| passthrough_api_error(&node, e))?;
 
-        let Some(node) = new_nodes.get_mut(&node_id) else {
+        // A post-split tenant may contain a mixture of shard counts in remote storage: pick the highest count.
+        let Some(shard_count) = scan_result
+            .shards
+            .iter()
+            .map(|s| s.tenant_shard_id.shard_count)
+            .max()
+        else {
             return Err(ApiError::NotFound(
-                anyhow::anyhow!("Node not registered").into(),
+                anyhow::anyhow!("No shards found").into(),
             ));
         };
+        operation.begin().await?;
 
-        if let Some(availability) = availability {
-            node.set_availability(availability);
-        }
+        // Ideally we would set each newly imported shard's generation independently, but for correctness it is sufficient
+        // to
+        let generation = scan_result
+            .shards
+            .iter()
+            .map(|s| s.generation)
+            .max()
+            .expect("We already validated >0 shards");
 
-        if let Some(scheduling) = scheduling {
-            node.set_scheduling(scheduling);
-        }
+        // FIXME: we have no way to recover the shard stripe size from contents of remote storage: this will
+        // only work if they were using the default stripe size.
+        let stripe_size = ShardParameters::DEFAULT_STRIPE_SIZE;
 
-        // Update the scheduler, in case the elegibility of the node for new shards has changed
-        scheduler.node_upsert(node);
+        let (response, waiters) = self
+            .do_tenant_create(TenantCreateRequest {
+                new_tenant_id: TenantShardId::unsharded(tenant_id),
+                generation,
 
-        let new_nodes = Arc::new(new_nodes);
-        locked.nodes = new_nodes;
+                shard_parameters: ShardParameters {
+                    count: shard_count,
+                    stripe_size,
+                },
+                placement_policy: Some(PlacementPolicy::Attached(0)), // No secondaries, for convenient debug/hacking
 
-        Ok(availability_transition)
-    }
+                // There is no way to know what the tenant's config was: revert to defaults
+                //
+                // TODO: remove `switch_aux_file_policy` once we finish auxv2 migration
+                //
+                // we write to both v1+v2 storage, so that the test case can use either storage format for testing
+                config: TenantConfig {
+                    switch_aux_file_policy: Some(models::AuxFilePolicy::CrossValidation),
+                    ..TenantConfig::default()
+                },
+            })
+            .await?;
 
-    /// Handle availability transition of one node
-    ///
-    /// Note that you should first call [`Self::node_state_configure`] to update
-    /// the in-memory state referencing that node. If you need to handle more than one transition
-    /// consider using [`Self::handle_node_availability_transitions`].
-    async fn handle_node_availability_transition(
-        &self,
-        node_id: NodeId,
-        transition: AvailabilityTransition,
-        _node_lock: &TracingExclusiveGuard<NodeOperations>,
-    ) -> Result<(), ApiError> {
-        // Modify scheduling state for any Tenants that are affected by a change in the node's availability state.
-        match transition {
-            AvailabilityTransition::ToOffline => {
-                tracing::info!("Node {} transition to offline", node_id);
+        if let Err(e) = self.await_waiters(waiters, SHORT_RECONCILE_TIMEOUT).await {
+            // Since this is a debug/support operation, all kinds of weird issues are possible (e.g. this
+            // tenant doesn't exist in the control plane), so don't fail the request if it can't fully
+            // reconcile, as reconciliation includes notifying compute.
+            tracing::warn!(%tenant_id, "Reconcile not done yet while importing tenant ({e})");
+        }
 
-                let mut locked = self.inner.write().unwrap();
-                let (nodes, tenants, scheduler) = locked.parts_mut();
+        Ok(response)
+    }
 
-                let mut tenants_affected: usize = 0;
+    /// For debug/support: a full JSON dump of TenantShards.  Returns a response so that
+    /// we don't have to make TenantShard clonable in the return path.
+    pub(crate) fn tenants_dump(&self) -> Result<hyper::Response<hyper::Body>, ApiError> {
+        let serialized = {
+            let locked = self.inner.read().unwrap();
+            let result = locked.tenants.values().collect::<Vec<_>>();
+            serde_json::to_string(&result).map_err(|e| ApiError::InternalServerError(e.into()))?
+        };
 
-                for (tenant_shard_id, tenant_shard) in tenants {
-                    if let Some(observed_loc) = tenant_shard.observed.locations.get_mut(&node_id) {
-                        // When a node goes offline, we set its observed configuration to None, indicating unknown: we will
-                        // not assume our knowledge of the node's configuration is accurate until it comes back online
-                        observed_loc.conf = None;
-                    }
+        hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(hyper::Body::from(serialized))
+            .map_err(|e| ApiError::InternalServerError(e.into()))
+    }
 
-                    if nodes.len() == 1 {
-                        // Special case for single-node cluster: there is no point trying to reschedule
-                        // any tenant shards: avoid doing so, in order to avoid spewing warnings about
-                        // failures to schedule them.
-                        continue;
-                    }
+    /// Check the consistency of in-memory state vs. persistent state, and check that the
+    /// scheduler's statistics are up to date.
+    ///
+    /// These consistency checks expect an **idle** system.  If changes are going on while
+    /// we run, then we can falsely indicate a consistency issue.  This is sufficient for end-of-test
+    /// checks, but not suitable for running continuously in the background in the field.
+    pub(crate) async fn consistency_check(&self) -> Result<(), ApiError> {
+        let (mut expect_nodes, mut expect_shards) = {
+            let locked = self.inner.read().unwrap();
 
-                    if !nodes
-                        .values()
-                        .any(|n| matches!(n.may_schedule(), MaySchedule::Yes(_)))
-                    {
-                        // Special case for when all nodes are unavailable and/or unschedulable: there is no point
-                        // trying to reschedule since there's nowhere else to go. Without this
-                        // branch we incorrectly detach tenants in response to node unavailability.
-                        continue;
-                    }
+            locked
+                .scheduler
+                .consistency_check(locked.nodes.values(), locked.tenants.values())
+                .context("Scheduler checks")
+                .map_err(ApiError::InternalServerError)?;
 
-                    if tenant_shard.intent.demote_attached(scheduler, node_id) {
-                        tenant_shard.sequence = tenant_shard.sequence.next();
+            let expect_nodes = locked
+                .nodes
+                .values()
+                .map(|n| n.to_persistent())
+                .collect::<Vec<_>>();
 
-                        // TODO: populate a ScheduleContext including all shards in the same tenant_id (only matters
-                        // for tenants without secondary locations: if they have a secondary location, then this
-                        // schedule() call is just promoting an existing secondary)
-                        let mut schedule_context = ScheduleContext::default();
+            let expect_shards = locked
+                .tenants
+                .values()
+                .map(|t| t.to_persistent())
+                .collect::<Vec<_>>();
 
-                        match tenant_shard.schedule(scheduler, &mut schedule_context) {
-                            Err(e) => {
-                                // It is possible that some tenants will become unschedulable when too many pageservers
-                                // go offline: in this case there isn't much we can do other than make the issue observable.
-                                // TODO: give TenantShard a scheduling error attribute to be queried later.
-                                tracing::warn!(%tenant_shard_id, "Scheduling error when marking pageserver {} offline: {e}", node_id);
-                            }
-                            Ok(()) => {
-                                if self.maybe_reconcile_shard(tenant_shard, nodes).is_some() {
-                                    tenants_affected += 1;
-                                };
-                            }
-                        }
-                    }
+            // This method can only validate the state of an idle system: if a reconcile is in
+            // progress, fail out early to avoid giving false errors on state that won't match
+            // between database and memory under a ReconcileResult is processed.
+            for t in locked.tenants.values() {
+                if t.reconciler.is_some() {
+                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                        "Shard {} reconciliation in progress",
+                        t.tenant_shard_id
+                    )));
                 }
-                tracing::info!(
-                    "Launched {} reconciler tasks for tenants affected by node {} going offline",
-                    tenants_affected,
-                    node_id
-                )
             }
-            AvailabilityTransition::ToActive => {
-                tracing::info!("Node {} transition to active", node_id);
-
-                let mut locked = self.inner.write().unwrap();
-                let (nodes, tenants, _scheduler) = locked.parts_mut();
 
-                // When a node comes back online, we must reconcile any tenant that has a None observed
-                // location on the node.
-                for tenant_shard in tenants.values_mut() {
-                    // If a reconciliation is already in progress, rely on the previous scheduling
-                    // decision and skip triggering a new reconciliation.
-                    if tenant_shard.reconciler.is_some() {
-                        continue;
-                    }
+            (expect_nodes, expect_shards)
+        };
 
-                    if let Some(observed_loc) = tenant_shard.observed.locations.get_mut(&node_id) {
-                        if observed_loc.conf.is_none() {
-                            self.maybe_reconcile_shard(tenant_shard, nodes);
-                        }
-                    }
-                }
+        let mut nodes = self.persistence.list_nodes().await?;
+        expect_nodes.sort_by_key(|n| n.node_id);
+        nodes.sort_by_key(|n| n.node_id);
 
-                // TODO: in the background, we should balance work back onto this pageserver
-            }
-            // No action required for the intermediate unavailable state.
-            // When we transition into active or offline from the unavailable state,
-            // the correct handling above will kick in.
-            AvailabilityTransition::ToWarmingUpFromActive => {
-                tracing::info!("Node {} transition to unavailable from active", node_id);
-            }
-            AvailabilityTransition::ToWarmingUpFromOffline => {
-                tracing::info!("Node {} transition to unavailable from offline", node_id);
-            }
-            AvailabilityTransition::Unchanged => {
-                tracing::debug!("Node {} no availability change during config", node_id);
-            }
+        if nodes != expect_nodes {
+            tracing::error!("Consistency check failed on nodes.");
+            tracing::error!(
+                "Nodes in memory: {}",
+                serde_json::to_string(&expect_nodes)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+            tracing::error!(
+                "Nodes in database: {}",
+                serde_json::to_string(&nodes)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+            return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                "Node consistency failure"
+            )));
         }
 
-        Ok(())
-    }
-
-    /// Handle availability transition for multiple nodes
-    ///
-    /// Note that you should first call [`Self::node_state_configure`] for
-    /// all nodes being handled here for the handling to use fresh in-memory state.
-    async fn handle_node_availability_transitions(
-        &self,
-        transitions: Vec<(
-            NodeId,
-            TracingExclusiveGuard<NodeOperations>,
-            AvailabilityTransition,
-        )>,
-    ) -> Result<(), Vec<(NodeId, ApiError)>> {
-        let mut errors = Vec::default();
-        for (node_id, node_lock, transition) in transitions {
-            let res = self
-                .handle_node_availability_transition(node_id, transition, &node_lock)
-                .await;
-            if let Err(err) = res {
-                errors.push((node_id, err));
-            }
-        }
+        let mut shards = self.persistence.list_tenant_shards().await?;
+        shards.sort_by_key(|tsp| (tsp.tenant_id.clone(), tsp.shard_number, tsp.shard_count));
+        expect_shards.sort_by_key(|tsp| (tsp.tenant_id.clone(), tsp.shard_number, tsp.shard_count));
 
-        if errors.is_empty() {
-            Ok(())
-        } else {
-            Err(errors)
+        if shards != expect_shards {
+            tracing::error!("Consistency check failed on shards.");
+            tracing::error!(
+                "Shards in memory: {}",
+                serde_json::to_string(&expect_shards)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+            tracing::error!(
+                "Shards in database: {}",
+                serde_json::to_string(&shards)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+            return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                "Shard consistency failure"
+            )));
         }
-    }
-
-    pub(crate) async fn node_configure(
-        &self,
-        node_id: NodeId,
-        availability: Option<NodeAvailability>,
-        scheduling: Option<NodeSchedulingPolicy>,
-    ) -> Result<(), ApiError> {
-        let node_lock =
-            trace_exclusive_lock(&self.node_op_locks, node_id, NodeOperations::Configure).await;
 
-        let transition = self
-            .node_state_configure(node_id, availability, scheduling, &node_lock)
-            .await?;
-        self.handle_node_availability_transition(node_id, transition, &node_lock)
-            .await
+        Ok(())
     }
 
-    /// Wrapper around [`Self::node_configure`] which only allows changes while there is no ongoing
-    /// operation for HTTP api.
-    pub(crate) async fn external_node_configure(
-        &self,
-        node_id: NodeId,
-        availability: Option<NodeAvailability>,
-        scheduling: Option<NodeSchedulingPolicy>,
-    ) -> Result<(), ApiError> {
-        {
+    /// For debug/support: a JSON dump of the [`Scheduler`].  Returns a response so that
+    /// we don't have to make TenantShard clonable in the return path.
+    pub(crate) fn scheduler_dump(&self) -> Result<hyper::Response<hyper::Body>, ApiError> {
+        let serialized = {
             let locked = self.inner.read().unwrap();
-            if let Some(op) = locked.ongoing_operation.as_ref().map(|op| op.operation) {
-                return Err(ApiError::PreconditionFailed(
-                    format!("Ongoing background operation forbids configuring: {op}").into(),
-                ));
-            }
-        }
+            serde_json::to_string(&locked.scheduler)
+                .map_err(|e| ApiError::InternalServerError(e.into()))?
+        };
 
-        self.node_configure(node_id, availability, scheduling).await
+        hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(hyper::Body::from(serialized))
+            .map_err(|e| ApiError::InternalServerError(e.into()))
     }
 
-    pub(crate) async fn start_node_drain(
-        self: &Arc<Self>,
-        node_id: NodeId,
-    ) -> Result<(), ApiError> {
-        let (ongoing_op, node_available, node_policy, schedulable_nodes_count) = {
-            let locked = self.inner.read().unwrap();
-            let nodes = &locked.nodes;
-            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
-                anyhow::anyhow!("Node {} not registered", node_id).into(),
-            ))?;
-            let schedulable_nodes_count = nodes
-                .iter()
-                .filter(|(_, n)| matches!(n.may_schedule(), MaySchedule::Yes(_)))
-                .count();
-
-            (
-                locked
-                    .ongoing_operation
-                    .as_ref()
-                    .map(|ongoing| ongoing.operation),
-                node.is_available(),
-                node.get_scheduling(),
-                schedulable_nodes_count,
-            )
-        };
+    /// This is for debug/support only: we simply drop all state for a tenant, without
+    /// detaching or deleting it on pageservers.  We do not try and re-schedule any
+    /// tenants that were on this node.
+    pub(crate) async fn node_drop(&self, node_id: NodeId) -> Result<(), ApiError> {
+        self.persistence.delete_node(node_id).await?;
 
-        if let Some(ongoing) = ongoing_op {
-            return Err(ApiError::PreconditionFailed(
-                format!("Background operation already ongoing for node: {}", ongoing).into(),
-            ));
-        }
+        let mut locked = self.inner.write().unwrap();
 
-        if !node_available {
-            return Err(ApiError::ResourceUnavailable(
-                format!("Node {node_id} is currently unavailable").into(),
-            ));
+        for shard in locked.tenants.values_mut() {
+            shard.deref_node(node_id);
+            shard.observed.locations.remove(&node_id);
         }
 
-        if schedulable_nodes_count == 0 {
-            return Err(ApiError::PreconditionFailed(
-                "No other schedulable nodes to drain to".into(),
-            ));
-        }
+        let mut nodes = (*locked.nodes).clone();
+        nodes.remove(&node_id);
+        locked.nodes = Arc::new(nodes);
 
-        match node_policy {
-            NodeSchedulingPolicy::Active | NodeSchedulingPolicy::Pause => {
-                self.node_configure(node_id, None, Some(NodeSchedulingPolicy::Draining))
-                    .await?;
+        locked.scheduler.node_remove(node_id);
 
-                let cancel = self.cancel.child_token();
-                let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+        Ok(())
+    }
 
-                self.inner.write().unwrap().ongoing_operation = Some(OperationHandler {
-                    operation: Operation::Drain(Drain { node_id }),
-                    cancel: cancel.clone(),
-                });
+    /// If a node has any work on it, it will be rescheduled: this is "clean" in the sense
+    /// that we don't leave any bad state behind in the storage controller, but unclean
+    /// in the sense that we are not carefully draining the node.
+    pub(crate) async fn node_delete(&self, node_id: NodeId) -> Result<(), ApiError> {
+        let _node_lock =
+            trace_exclusive_lock(&self.node_op_locks, node_id, NodeOperations::Delete).await;
 
-                let span = tracing::info_span!(parent: None, "drain_node", %node_id);
+        // 1. Atomically update in-memory state:
+        //    - set the scheduling state to Pause to make subsequent scheduling ops skip it
+        //    - update shards' intents to exclude the node, and reschedule any shards whose intents we modified.
+        //    - drop the node from the main nodes map, so that when running reconciles complete they do not
+        //      re-insert references to this node into the ObservedState of shards
+        //    - drop the node from the scheduler
+        {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
 
-                tokio::task::spawn({
-                    let service = self.clone();
-                    let cancel = cancel.clone();
-                    async move {
-                        let _gate_guard = gate_guard;
+            {
+                let mut nodes_mut = (*nodes).deref().clone();
+                match nodes_mut.get_mut(&node_id) {
+                    Some(node) => {
+                        // We do not bother setting this in the database, because we're about to delete the row anyway, and
+                        // if we crash it would not be desirable to leave the node paused after a restart.
+                        node.set_scheduling(NodeSchedulingPolicy::Pause);
+                    }
+                    None => {
+                        tracing::info!(
+                            "Node not found: presuming this is a retry and returning success"
+                        );
+                        return Ok(());
+                    }
+                }
 
-                        scopeguard::defer! {
-                            let prev = service.inner.write().unwrap().ongoing_operation.take();
+                *nodes = Arc::new(nodes_mut);
+            }
 
-                            if let Some(Operation::Drain(removed_drain)) = prev.map(|h| h.operation) {
-                                assert_eq!(removed_drain.node_id, node_id, "We always take the same operation");
-                            } else {
-                                panic!("We always remove the same operation")
-                            }
-                        }
+            for (tenant_shard_id, shard) in tenants {
+                if shard.deref_node(node_id) {
+                    // FIXME: we need to build a ScheduleContext that reflects this shard's peers, otherwise
+                    // it won't properly do anti-affinity.
+                    let mut schedule_context = ScheduleContext::default();
 
-                        tracing::info!("Drain background operation starting");
-                        let res = service.drain_node(node_id, cancel).await;
-                        match res {
-                            Ok(()) => {
-                                tracing::info!("Drain background operation completed successfully");
-                            }
-                            Err(OperationError::Cancelled) => {
-                                tracing::info!("Drain background operation was cancelled");
-                            }
-                            Err(err) => {
-                                tracing::error!("Drain background operation encountered: {err}")
-                            }
-                        }
+                    if let Err(e) = shard.schedule(scheduler, &mut schedule_context) {
+                        // TODO: implement force flag to remove a node even if we can't reschedule
+                        // a tenant
+                        tracing::error!("Refusing to delete node, shard {tenant_shard_id} can't be rescheduled: {e}");
+                        return Err(e.into());
+                    } else {
+                        tracing::info!(
+                            "Rescheduled shard {tenant_shard_id} away from node during deletion"
+                        )
                     }
-                }.instrument(span));
-            }
-            NodeSchedulingPolicy::Draining => {
-                return Err(ApiError::Conflict(format!(
-                    "Node {node_id} has drain in progress"
-                )));
+
+                    self.maybe_reconcile_shard(shard, nodes);
+                }
+
+                // Here we remove an existing observed location for the node we're removing, and it will
+                // not be re-added by a reconciler's completion because we filter out removed nodes in
+                // process_result.
+                //
+                // Note that we update the shard's observed state _after_ calling maybe_reconcile_shard: that
+                // means any reconciles we spawned will know about the node we're deleting, enabling them
+                // to do live migrations if it's still online.
+                shard.observed.locations.remove(&node_id);
             }
-            policy => {
-                return Err(ApiError::PreconditionFailed(
-                    format!("Node {node_id} cannot be drained due to {policy:?} policy").into(),
-                ));
+
+            scheduler.node_remove(node_id);
+
+            {
+                let mut nodes_mut = (**nodes).clone();
+                if let Some(mut removed_node) = nodes_mut.remove(&node_id) {
+                    // Ensure that any reconciler holding an Arc<> to this node will
+                    // drop out when trying to RPC to it (setting Offline state sets the
+                    // cancellation token on the Node object).
+                    removed_node.set_availability(NodeAvailability::Offline);
+                }
+                *nodes = Arc::new(nodes_mut);
             }
         }
 
+        // Note: some `generation_pageserver` columns on tenant shards in the database may still refer to
+        // the removed node, as this column means "The pageserver to which this generation was issued", and
+        // their generations won't get updated until the reconcilers moving them away from this node complete.
+        // That is safe because in Service::spawn we only use generation_pageserver if it refers to a node
+        // that exists.
+
+        // 2. Actually delete the node from the database and from in-memory state
+        tracing::info!("Deleting node from database");
+        self.persistence.delete_node(node_id).await?;
+
         Ok(())
     }
 
-    pub(crate) async fn cancel_node_drain(&self, node_id: NodeId) -> Result<(), ApiError> {
-        let node_available = {
-            let locked = self.inner.read().unwrap();
-            let nodes = &locked.nodes;
-            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
-                anyhow::anyhow!("Node {} not registered", node_id).into(),
-            ))?;
-
-            node.is_available()
+    pub(crate) async fn node_list(&self) -> Result<Vec<Node>, ApiError> {
+        let nodes = {
+            self.inner
+                .read()
+                .unwrap()
+                .nodes
+                .values()
+                .cloned()
+                .collect::<Vec<_>>()
         };
 
-        if !node_available {
-            return Err(ApiError::ResourceUnavailable(
-                format!("Node {node_id} is currently unavailable").into(),
-            ));
-        }
+        Ok(nodes)
+    }
 
-        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operation.as_ref() {
-            if let Operation::Drain(drain) = op_handler.operation {
-                if drain.node_id == node_id {
-                    tracing::info!("Cancelling background drain operation for node {node_id}");
-                    op_handler.cancel.cancel();
-                    return Ok(());
+    pub(crate) async fn get_node(&self, node_id: NodeId) -> Result<Node, ApiError> {
+        self.inner
+            .read()
+            .unwrap()
+            .nodes
+            .get(&node_id)
+            .cloned()
+            .ok_or(ApiError::NotFound(
+                format!("Node {node_id} not registered").into(),
+            ))
+    }
+
+    pub(crate) async fn get_node_shards(
+        &self,
+        node_id: NodeId,
+    ) -> Result<NodeShardResponse, ApiError> {
+        let locked = self.inner.read().unwrap();
+        let mut shards = Vec::new();
+        for (tid, tenant) in locked.tenants.iter() {
+            let is_intended_secondary = match (
+                tenant.intent.get_attached() == &Some(node_id),
+                tenant.intent.get_secondary().contains(&node_id),
+            ) {
+                (true, true) => {
+                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                        "{} attached as primary+secondary on the same node",
+                        tid
+                    )))
                 }
+                (true, false) => Some(false),
+                (false, true) => Some(true),
+                (false, false) => None,
+            };
+            let is_observed_secondary = if let Some(ObservedStateLocation { conf: Some(conf) }) =
+                tenant.observed.locations.get(&node_id)
+            {
+                Some(conf.secondary_conf.is_some())
+            } else {
+                None
+            };
+            if is_intended_secondary.is_some() || is_observed_secondary.is_some() {
+                shards.push(NodeShard {
+                    tenant_shard_id: *tid,
+                    is_intended_secondary,
+                    is_observed_secondary,
+                });
             }
         }
-
-        Err(ApiError::PreconditionFailed(
-            format!("Node {node_id} has no drain in progress").into(),
-        ))
+        Ok(NodeShardResponse { node_id, shards })
     }
 
-    pub(crate) async fn start_node_fill(self: &Arc<Self>, node_id: NodeId) -> Result<(), ApiError> {
-        let (ongoing_op, node_available, node_policy, total_nodes_count) = {
-            let locked = self.inner.read().unwrap();
-            let nodes = &locked.nodes;
-            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
-                anyhow::anyhow!("Node {} not registered", node_id).into(),
-            ))?;
+    pub(crate) async fn get_leader(&self) -> DatabaseResult<Option<ControllerPersistence>> {
+        self.persistence.get_leader().await
+    }
 
-            (
-                locked
-                    .ongoing_operation
-                    .as_ref()
-                    .map(|ongoing| ongoing.operation),
-                node.is_available(),
-                node.get_scheduling(),
-                nodes.len(),
-            )
-        };
+    pub(crate) async fn node_register(
+        &self,
+        register_req: NodeRegisterRequest,
+    ) -> Result<(), ApiError> {
+        let _node_lock = trace_exclusive_lock(
+            &self.node_op_locks,
+            register_req.node_id,
+            NodeOperations::Register,
+        )
+        .await;
 
-        if let Some(ongoing) = ongoing_op {
-            return Err(ApiError::PreconditionFailed(
-                format!("Background operation already ongoing for node: {}", ongoing).into(),
-            ));
+        enum RegistrationStatus {
+            Matched,
+            Relabeled,
+            Mismatched,
+            New,
         }
 
-        if !node_available {
-            return Err(ApiError::ResourceUnavailable(
-                format!("Node {node_id} is currently unavailable").into(),
-            ));
-        }
+        let registration_status = {
+            let locked = self.inner.read().unwrap();
+            if let Some(node) = locked.nodes.get(&register_req.node_id) {
+                if node.registration_match(&register_req) {
+                    if node.get_labels() == &register_req.labels {
+                        RegistrationStatus::Matched
+                    } else {
+                        RegistrationStatus::Relabeled
+                    }
+                } else {
+                    RegistrationStatus::Mismatched
+                }
+            } else {
+                RegistrationStatus::New
+            }
+        };
 
-        if total_nodes_count <= 1 {
-            return Err(ApiError::PreconditionFailed(
-                "No other nodes to fill from".into(),
-            ));
-        }
+        match registration_status {
+            RegistrationStatus::Matched => {
+                tracing::info!(
+                    "Node {} re-registered with matching address",
+                    register_req.node_id
+                );
 
-        match node_policy {
-            NodeSchedulingPolicy::Active => {
-                self.node_configure(node_id, None, Some(NodeSchedulingPolicy::Filling))
+                return Ok(());
+            }
+            RegistrationStatus::Relabeled => {
+                self.persistence
+                    .update_node_labels(register_req.node_id, &register_req.labels)
                     .await?;
 
-                let cancel = self.cancel.child_token();
-                let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
-
-                self.inner.write().unwrap().ongoing_operation = Some(OperationHandler {
-                    operation: Operation::Fill(Fill { node_id }),
-                    cancel: cancel.clone(),
-                });
-
-                let span = tracing::info_span!(parent: None, "fill_node", %node_id);
-
-                tokio::task::spawn({
-                    let service = self.clone();
-                    let cancel = cancel.clone();
-                    async move {
-                        let _gate_guard = gate_guard;
-
-                        scopeguard::defer! {
-                            let prev = service.inner.write().unwrap().ongoing_operation.take();
+                let mut locked = self.inner.write().unwrap();
+                let mut new_nodes = (*locked.nodes).clone();
+                if let Some(node) = new_nodes.get_mut(&register_req.node_id) {
+                    node.set_labels(register_req.labels.clone());
+                    locked.scheduler.node_upsert(node);
+                }
+                locked.nodes = Arc::new(new_nodes);
 
-                            if let Some(Operation::Fill(removed_fill)) = prev.map(|h| h.operation) {
-                                assert_eq!(removed_fill.node_id, node_id, "We always take the same operation");
-                            } else {
-                                panic!("We always remove the same operation")
-                            }
-                        }
+                tracing::info!(
+                    "Node {} re-registered with labels {:?}",
+                    register_req.node_id,
+                    register_req.labels
+                );
 
-                        tracing::info!("Fill background operation starting");
-                        let res = service.fill_node(node_id, cancel).await;
-                        match res {
-                            Ok(()) => {
-                                tracing::info!("Fill background operation completed successfully");
-                            }
-                            Err(OperationError::Cancelled) => {
-                                tracing::info!("Fill background operation was cancelled");
-                            }
-                            Err(err) => {
-                                tracing::error!("Fill background operation encountered: {err}")
-                            }
-                        }
-                    }
-                }.instrument(span));
-            }
-            NodeSchedulingPolicy::Filling => {
-                return Err(ApiError::Conflict(format!(
-                    "Node {node_id} has fill in progress"
-                )));
+                return Ok(());
             }
-            policy => {
-                return Err(ApiError::PreconditionFailed(
-                    format!("Node {node_id} cannot be filled due to {policy:?} policy").into(),
+            RegistrationStatus::Mismatched => {
+                // TODO: decide if we want to allow modifying node addresses without removing and re-adding
+                // the node.  Safest/simplest thing is to refuse it, and usually we deploy with
+                // a fixed address through the lifetime of a node.
+                tracing::warn!(
+                    "Node {} tried to register with different address",
+                    register_req.node_id
+                );
+                return Err(ApiError::Conflict(
+                    "Node is already registered with different address".to_string(),
                 ));
             }
+            RegistrationStatus::New => {
+                // fallthrough
+            }
         }
 
-        Ok(())
-    }
-
-    pub(crate) async fn cancel_node_fill(&self, node_id: NodeId) -> Result<(), ApiError> {
-        let node_available = {
-            let locked = self.inner.read().unwrap();
-            let nodes = &locked.nodes;
-            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
-                anyhow::anyhow!("Node {} not registered", node_id).into(),
-            ))?;
-
-            node.is_available()
-        };
-
-        if !node_available {
+        // We do not require that a node is actually online when registered (it will start life
+        // with it's  availability set to Offline), but we _do_ require that its DNS record exists. We're
+        // therefore not immune to asymmetric L3 connectivity issues, but we are protected against nodes
+        // that register themselves with a broken DNS config.  We check only the HTTP hostname, because
+        // the postgres hostname might only be resolvable to clients (e.g. if we're on a different VPC than clients).
+        if tokio::net::lookup_host(format!(
+            "{}:{}",
+            register_req.listen_http_addr, register_req.listen_http_port
+        ))
+        .await
+        .is_err()
+        {
+            // If we have a transient DNS issue, it's up to the caller to retry their registration.  Because
+            // we can't robustly distinguish between an intermittent issue and a totally bogus DNS situation,
+            // we return a soft 503 error, to encourage callers to retry past transient issues.
             return Err(ApiError::ResourceUnavailable(
-                format!("Node {node_id} is currently unavailable").into(),
+                format!(
+                    "Node {} tried to register with unknown DNS name '{}'",
+                    register_req.node_id, register_req.listen_http_addr
+                )
+                .into(),
             ));
         }
 
-        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operation.as_ref() {
-            if let Operation::Fill(fill) = op_handler.operation {
-                if fill.node_id == node_id {
-                    tracing::info!("Cancelling background drain operation for node {node_id}");
-                    op_handler.cancel.cancel();
-                    return Ok(());
-                }
-            }
-        }
+        // Ordering: we must persist the new node _before_ adding it to in-memory state.
+        // This ensures that before we use it for anything or expose it via any external
+        // API, it is guaranteed to be available after a restart.
+        let new_node = Node::new(
+            register_req.node_id,
+            register_req.listen_http_addr,
+            register_req.listen_http_port,
+            register_req.listen_pg_addr,
+            register_req.listen_pg_port,
+            register_req.availability_zone_id,
+            register_req.labels,
+        );
 
-        Err(ApiError::PreconditionFailed(
-            format!("Node {node_id} has no fill in progress").into(),
-        ))
-    }
+        // TODO: idempotency if the node already exists in the database
+        self.persistence.insert_node(&new_node).await?;
 
-    /// Like [`Self::maybe_configured_reconcile_shard`], but uses the default reconciler
-    /// configuration
-    fn maybe_reconcile_shard(
-        &self,
-        shard: &mut TenantShard,
-        nodes: &Arc<HashMap<NodeId, Node>>,
-    ) -> Option<ReconcilerWaiter> {
-        self.maybe_configured_reconcile_shard(shard, nodes, ReconcilerConfig::default())
+        let mut locked = self.inner.write().unwrap();
+        let mut new_nodes = (*locked.nodes).clone();
+
+        locked.scheduler.node_upsert(&new_node);
+        new_nodes.insert(register_req.node_id, new_node);
+
+        locked.nodes = Arc::new(new_nodes);
+
+        tracing::info!(
+            "Registered pageserver {}, now have {} pageservers",
+            register_req.node_id,
+            locked.nodes.len()
+        );
+        Ok(())
     }
 
-    /// Wrap [`TenantShard`] reconciliation methods with acquisition of [`Gate`] and [`ReconcileUnits`],
-    fn maybe_configured_reconcile_shard(
+    /// Configure in-memory and persistent state of a node as requested
+    ///
+    /// Note that this function does not trigger any immediate side effects in response
+    /// to the changes. That part is handled by [`Self::handle_node_availability_transition`].
+    async fn node_state_configure(
         &self,
-        shard: &mut TenantShard,
-        nodes: &Arc<HashMap<NodeId, Node>>,
-        reconciler_config: ReconcilerConfig,
-    ) -> Option<ReconcilerWaiter> {
-        let reconcile_needed = shard.get_reconcile_needed(nodes);
+        node_id: NodeId,
+        availability: Option<NodeAvailability>,
+        scheduling: Option<NodeSchedulingPolicy>,
+        node_lock: &TracingExclusiveGuard<NodeOperations>,
+    ) -> Result<AvailabilityTransition, ApiError> {
+        if let Some(scheduling) = scheduling {
+            // Scheduling is a persistent part of Node: we must write updates to the database before
+            // applying them in memory
+            self.persistence.update_node(node_id, scheduling).await?;
+        }
 
-        match reconcile_needed {
-            ReconcileNeeded::No => return None,
-            ReconcileNeeded::WaitExisting(waiter) => return Some(waiter),
-            ReconcileNeeded::Yes => {
-                // Fall through to try and acquire units for spawning reconciler
-            }
-        };
+        // If we're activating a node, then before setting it active we must reconcile any shard locations
+        // on that node, in case it is out of sync, e.g. due to being unavailable during controller startup,
+        // by calling [`Self::node_activate_reconcile`]
+        //
+        // The transition we calculate here remains valid later in the function because we hold the op lock on the node:
+        // nothing else can mutate its availability while we run.
+        let availability_transition = if let Some(input_availability) = availability.as_ref() {
+            let (activate_node, availability_transition) = {
+                let locked = self.inner.read().unwrap();
+                let Some(node) = locked.nodes.get(&node_id) else {
+                    return Err(ApiError::NotFound(
+                        anyhow::anyhow!("Node {} not registered", node_id).into(),
+                    ));
+                };
 
-        let units = match self.reconciler_concurrency.clone().try_acquire_owned() {
-            Ok(u) => ReconcileUnits::new(u),
-            Err(_) => {
-                tracing::info!(tenant_id=%shard.tenant_shard_id.tenant_id, shard_id=%shard.tenant_shard_id.shard_slug(),
-                    "Concurrency limited: enqueued for reconcile later");
-                if !shard.delayed_reconcile {
-                    match self.delayed_reconcile_tx.try_send(shard.tenant_shard_id) {
-                        Err(TrySendError::Closed(_)) => {
-                            // Weird mid-shutdown case?
-                        }
-                        Err(TrySendError::Full(_)) => {
-                            // It is safe to skip sending our ID in the channel: we will eventually get retried by the background reconcile task.
-                            tracing::warn!(
-                                "Many shards are waiting to reconcile: delayed_reconcile queue is full"
-                            );
-                        }
-                        Ok(()) => {
-                            shard.delayed_reconcile = true;
-                        }
-                    }
-                }
+                (
+                    node.clone(),
+                    node.get_availability_transition(input_availability),
+                )
+            };
 
-                // We won't spawn a reconciler, but we will construct a waiter that waits for the shard's sequence
-                // number to advance.  When this function is eventually called again and succeeds in getting units,
-                // it will spawn a reconciler that makes this waiter complete.
-                return Some(shard.future_reconcile_waiter());
+            if matches!(availability_transition, AvailabilityTransition::ToActive) {
+                self.node_activate_reconcile(activate_node, node_lock)
+                    .await?;
             }
+            availability_transition
+        } else {
+            AvailabilityTransition::Unchanged
         };
 
-        let Ok(gate_guard) = self.reconcilers_gate.enter() else {
-            // Gate closed: we're shutting down, drop out.
-            return None;
-        };
-
-        shard.spawn_reconciler(
-            &self.result_tx,
-            nodes,
-            &self.compute_hook,
-            reconciler_config,
-            &self.config,
-            &self.persistence,
-            units,
-            gate_guard,
-            &self.reconcilers_cancel,
-        )
-    }
-
-    /// Check all tenants for pending reconciliation work, and reconcile those in need.
-    /// Additionally, reschedule tenants that require it.
-    ///
-    /// Returns how many reconciliation tasks were started, or `1` if no reconciles were
-    /// spawned but some _would_ have been spawned if `reconciler_concurrency` units where
-    /// available.  A return value of 0 indicates that everything is fully reconciled already.
-    fn reconcile_all(&self) -> usize {
+        // Apply changes from the request to our in-memory state for the Node
         let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, _scheduler) = locked.parts_mut();
-        let pageservers = nodes.clone();
-
-        let mut schedule_context = ScheduleContext::default();
+        let (nodes, _tenants, scheduler) = locked.parts_mut();
 
-        let mut reconciles_spawned = 0;
-        for (tenant_shard_id, shard) in tenants.iter_mut() {
-            if tenant_shard_id.is_shard_zero() {
-                schedule_context = ScheduleContext::default();
-            }
+        let mut new_nodes = (**nodes).clone();
 
-            // Skip checking if this shard is already enqueued for reconciliation
-            if shard.delayed_reconcile && self.reconciler_concurrency.available_permits() == 0 {
-                // If there is something delayed, then return a nonzero count so that
-                // callers like reconcile_all_now do not incorrectly get the impression
-                // that the system is in a quiescent state.
-                reconciles_spawned = std::cmp::max(1, reconciles_spawned);
-                continue;
-            }
+        let Some(node) = new_nodes.get_mut(&node_id) else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Node not registered").into(),
+            ));
+        };
 
-            // Eventual consistency: if an earlier reconcile job failed, and the shard is still
-            // dirty, spawn another rone
-            if self.maybe_reconcile_shard(shard, &pageservers).is_some() {
-                reconciles_spawned += 1;
-            }
+        if let Some(availability) = availability {
+            node.set_availability(availability);
+        }
 
-            schedule_context.avoid(&shard.intent.all_pageservers());
+        if let Some(scheduling) = scheduling {
+            node.set_scheduling(scheduling);
         }
 
-        reconciles_spawned
-    }
+        // Update the scheduler, in case the elegibility of the node for new shards has changed
+        scheduler.node_upsert(node);
 
-    /// `optimize` in this context means identifying shards which have valid scheduled locations, but
-    /// could be scheduled somewhere better:
-    /// - Cutting over to a secondary if the node with the secondary is more lightly loaded
-    ///    * e.g. after a node fails then recovers, to move some work back to it
-    /// - Cutting over to a secondary if it improves the spread of shard attachments within a tenant
-    ///    * e.g. after a shard split, the initial attached locations will all be on the node where
-    ///      we did the split, but are probably better placed elsewhere.
-    /// - Creating new secondary locations if it improves the spreading of a sharded tenant
-    ///    * e.g. after a shard split, some locations will be on the same node (where the split
-    ///      happened), and will probably be better placed elsewhere.
-    ///
-    /// To put it more briefly: whereas the scheduler respects soft constraints in a ScheduleContext at
-    /// the time of scheduling, this function looks for cases where a better-scoring location is available
-    /// according to those same soft constraints.
-    async fn optimize_all(&self) -> usize {
-        // Limit on how many shards' optmizations each call to this function will execute.  Combined
-        // with the frequency of background calls, this acts as an implicit rate limit that runs a small
-        // trickle of optimizations in the background, rather than executing a large number in parallel
-        // when a change occurs.
-        const MAX_OPTIMIZATIONS_EXEC_PER_PASS: usize = 2;
+        let new_nodes = Arc::new(new_nodes);
+        locked.nodes = new_nodes;
 
-        // Synchronous prepare: scan shards for possible scheduling optimizations
-        let candidate_work = self.optimize_all_plan();
-        let candidate_work_len = candidate_work.len();
+        Ok(availability_transition)
+    }
 
-        // Asynchronous validate: I/O to pageservers to make sure shards are in a good state to apply validation
-        let validated_work = self.optimize_all_validate(candidate_work).await;
+    /// Handle availability transition of one node
+    ///
+    /// Note that you should first call [`Self::node_state_configure`] to update
+    /// the in-memory state referencing that node. If you need to handle more than one transition
+    /// consider using [`Self::handle_node_availability_transitions`].
+    async fn handle_node_availability_transition(
+        &self,
+        node_id: NodeId,
+        transition: AvailabilityTransition,
+        _node_lock: &TracingExclusiveGuard<NodeOperations>,
+    ) -> Result<(), ApiError> {
+        // Modify scheduling state for any Tenants that are affected by a change in the node's availability state.
+        match transition {
+            AvailabilityTransition::ToOffline => {
+                tracing::info!("Node {} transition to offline", node_id);
+
+                let mut locked = self.inner.write().unwrap();
+                let (nodes, tenants, scheduler) = locked.parts_mut();
+
+                let mut tenants_affected: usize = 0;
+
+                for (tenant_shard_id, tenant_shard) in tenants {
+                    if let Some(observed_loc) = tenant_shard.observed.locations.get_mut(&node_id) {
+                        // When a node goes offline, we set its observed configuration to None, indicating unknown: we will
+                        // not assume our knowledge of the node's configuration is accurate until it comes back online
+                        observed_loc.conf = None;
+                    }
+
+                    if nodes.len() == 1 {
+                        // Special case for single-node cluster: there is no point trying to reschedule
+                        // any tenant shards: avoid doing so, in order to avoid spewing warnings about
+                        // failures to schedule them.
+                        continue;
+                    }
+
+                    if !nodes
+                        .values()
+                        .any(|n| matches!(n.may_schedule(), MaySchedule::Yes(_)))
+                    {
+                        // Special case for when all nodes are unavailable and/or unschedulable: there is no point
+                        // trying to reschedule since there's nowhere else to go. Without this
+                        // branch we incorrectly detach tenants in response to node unavailability.
+                        continue;
+                    }
+
+                    if tenant_shard.intent.demote_attached(scheduler, node_id) {
+                        tenant_shard.sequence = tenant_shard.sequence.next();
+
+                        // TODO: populate a ScheduleContext including all shards in the same tenant_id (only matters
+                        // for tenants without secondary locations: if they have a secondary location, then this
+                        // schedule() call is just promoting an existing secondary)
+                        let mut schedule_context = ScheduleContext::default();
+
+                        match tenant_shard.schedule(scheduler, &mut schedule_context) {
+                            Err(e) => {
+                                // It is possible that some tenants will become unschedulable when too many pageservers
+                                // go offline: in this case there isn't much we can do other than make the issue observable.
+                                // TODO: give TenantShard a scheduling error attribute to be queried later.
+                                tracing::warn!(%tenant_shard_id, "Scheduling error when marking pageserver {} offline: {e}", node_id);
+                            }
+                            Ok(()) => {
+                                if self.maybe_reconcile_shard(tenant_shard, nodes).is_some() {
+                                    tenants_affected += 1;
+                                };
+                            }
+                        }
+                    }
+                }
+                tracing::info!(
+                    "Launched {} reconciler tasks for tenants affected by node {} going offline",
+                    tenants_affected,
+                    node_id
+                )
+            }
+            AvailabilityTransition::ToActive => {
+                tracing::info!("Node {} transition to active", node_id);
+
+                let mut locked = self.inner.write().unwrap();
+                let (nodes, tenants, _scheduler) = locked.parts_mut();
+
+                // When a node comes back online, we must reconcile any tenant that has a None observed
+                // location on the node.
+                for tenant_shard in tenants.values_mut() {
+                    // If a reconciliation is already in progress, rely on the previous scheduling
+                    // decision and skip triggering a new reconciliation.
+                    if tenant_shard.reconciler.is_some() {
+                        continue;
+                    }
+
+                    if let Some(observed_loc) = tenant_shard.observed.locations.get_mut(&node_id) {
+                        if observed_loc.conf.is_none() {
+                            self.maybe_reconcile_shard(tenant_shard, nodes);
+                        }
+                    }
+                }
+
+                // TODO: in the background, we should balance work back onto this pageserver
+            }
+            // No action required for the intermediate unavailable state.
+            // When we transition into active or offline from the unavailable state,
+            // the correct handling above will kick in.
+            AvailabilityTransition::ToWarmingUpFromActive => {
+                tracing::info!("Node {} transition to unavailable from active", node_id);
+            }
+            AvailabilityTransition::ToWarmingUpFromOffline => {
+                tracing::info!("Node {} transition to unavailable from offline", node_id);
+            }
+            AvailabilityTransition::Unchanged => {
+                tracing::debug!("Node {} no availability change during config", node_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle availability transition for multiple nodes
+    ///
+    /// Note that you should first call [`Self::node_state_configure`] for
+    /// all nodes being handled here for the handling to use fresh in-memory state.
+    async fn handle_node_availability_transitions(
+        &self,
+        transitions: Vec<(
+            NodeId,
+            TracingExclusiveGuard<NodeOperations>,
+            AvailabilityTransition,
+        )>,
+    ) -> Result<(), Vec<(NodeId, ApiError)>> {
+        let mut errors = Vec::default();
+        for (node_id, node_lock, transition) in transitions {
+            let res = self
+                .handle_node_availability_transition(node_id, transition, &node_lock)
+                .await;
+            if let Err(err) = res {
+                errors.push((node_id, err));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub(crate) async fn node_configure(
+        &self,
+        node_id: NodeId,
+        availability: Option<NodeAvailability>,
+        scheduling: Option<NodeSchedulingPolicy>,
+    ) -> Result<(), ApiError> {
+        let node_lock =
+            trace_exclusive_lock(&self.node_op_locks, node_id, NodeOperations::Configure).await;
+
+        let transition = self
+            .node_state_configure(node_id, availability, scheduling, &node_lock)
+            .await?;
+        self.handle_node_availability_transition(node_id, transition, &node_lock)
+            .await
+    }
+
+    /// Wrapper around [`Self::node_configure`] which only allows changes while there is no ongoing
+    /// operation for HTTP api.
+    pub(crate) async fn external_node_configure(
+        &self,
+        node_id: NodeId,
+        availability: Option<NodeAvailability>,
+        scheduling: Option<NodeSchedulingPolicy>,
+    ) -> Result<(), ApiError> {
+        {
+            let locked = self.inner.read().unwrap();
+            if let Some(op) = locked.ongoing_operation.as_ref().map(|op| op.operation) {
+                return Err(ApiError::PreconditionFailed(
+                    format!("Ongoing background operation forbids configuring: {op}").into(),
+                ));
+            }
+        }
+
+        self.node_configure(node_id, availability, scheduling).await
+    }
+
+    pub(crate) async fn start_node_drain(
+        self: &Arc<Self>,
+        node_id: NodeId,
+    ) -> Result<OperationStartResponse, ApiError> {
+        let (ongoing_op, node_available, node_policy, schedulable_nodes_count) = {
+            let locked = self.inner.read().unwrap();
+            let nodes = &locked.nodes;
+            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {} not registered", node_id).into(),
+            ))?;
+            let schedulable_nodes_count = nodes
+                .iter()
+                .filter(|(_, n)| matches!(n.may_schedule(), MaySchedule::Yes(_)))
+                .count();
+
+            (
+                locked
+                    .ongoing_operation
+                    .as_ref()
+                    .map(|ongoing| ongoing.operation),
+                node.is_available(),
+                node.get_scheduling(),
+                schedulable_nodes_count,
+            )
+        };
+
+        if let Some(ongoing) = ongoing_op {
+            return Err(ApiError::PreconditionFailed(
+                format!("Background operation already ongoing for node: {}", ongoing).into(),
+            ));
+        }
+
+        if !node_available {
+            return Err(ApiError::ResourceUnavailable(
+                format!("Node {node_id} is currently unavailable").into(),
+            ));
+        }
+
+        if schedulable_nodes_count == 0 {
+            return Err(ApiError::PreconditionFailed(
+                "No other schedulable nodes to drain to".into(),
+            ));
+        }
+
+        let operation_id = match node_policy {
+            NodeSchedulingPolicy::Active | NodeSchedulingPolicy::Pause => {
+                let cancel = self.cancel.child_token();
+                let operation = self
+                    .operation_begin(
+                        OperationKind::NodeDrain,
+                        None,
+                        Some(node_id),
+                        Some(cancel.clone()),
+                    )
+                    .await?;
+                let operation_id = operation.id();
+
+                if let Err(err) = self
+                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Draining))
+                    .await
+                {
+                    operation
+                        .finish(OperationState::Failed, Some(err.to_string()))
+                        .await;
+                    return Err(err);
+                }
+
+                let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+
+                self.inner.write().unwrap().ongoing_operation = Some(OperationHandler {
+                    operation: Operation::Drain(Drain { node_id }),
+                    cancel: cancel.clone(),
+                });
+
+                let span = tracing::info_span!(parent: None, "drain_node", %node_id);
+
+                tokio::task::spawn({
+                    let service = self.clone();
+                    let cancel = cancel.clone();
+                    async move {
+                        let _gate_guard = gate_guard;
+
+                        scopeguard::defer! {
+                            let prev = service.inner.write().unwrap().ongoing_operation.take();
+
+                            if let Some(Operation::Drain(removed_drain)) = prev.map(|h| h.operation) {
+                                assert_eq!(removed_drain.node_id, node_id, "We always take the same operation");
+                            } else {
+                                panic!("We always remove the same operation")
+                            }
+                        }
+
+                        tracing::info!("Drain background operation starting");
+                        let res = service.drain_node(node_id, cancel, Some(&operation)).await;
+                        match &res {
+                            Ok(()) => {
+                                tracing::info!("Drain background operation completed successfully");
+                            }
+                            Err(OperationError::Cancelled) => {
+                                tracing::info!("Drain background operation was cancelled");
+                            }
+                            Err(err) => {
+                                tracing::error!("Drain background operation encountered: {err}")
+                            }
+                        }
+                        Self::background_operation_finish(operation, &res).await;
+                    }
+                }.instrument(span));
+
+                operation_id
+            }
+            NodeSchedulingPolicy::Draining => {
+                return Err(ApiError::Conflict(format!(
+                    "Node {node_id} has drain in progress"
+                )));
+            }
+            policy => {
+                return Err(ApiError::PreconditionFailed(
+                    format!("Node {node_id} cannot be drained due to {policy:?} policy").into(),
+                ));
+            }
+        };
+
+        Ok(OperationStartResponse { operation_id })
+    }
+
+    pub(crate) async fn cancel_node_drain(&self, node_id: NodeId) -> Result<(), ApiError> {
+        let node_available = {
+            let locked = self.inner.read().unwrap();
+            let nodes = &locked.nodes;
+            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {} not registered", node_id).into(),
+            ))?;
+
+            node.is_available()
+        };
+
+        if !node_available {
+            return Err(ApiError::ResourceUnavailable(
+                format!("Node {node_id} is currently unavailable").into(),
+            ));
+        }
+
+        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operation.as_ref() {
+            if let Operation::Drain(drain) = op_handler.operation {
+                if drain.node_id == node_id {
+                    tracing::info!("Cancelling background drain operation for node {node_id}");
+                    op_handler.cancel.cancel();
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(ApiError::PreconditionFailed(
+            format!("Node {node_id} has no drain in progress").into(),
+        ))
+    }
+
+    pub(crate) async fn start_node_fill(
+        self: &Arc<Self>,
+        node_id: NodeId,
+    ) -> Result<OperationStartResponse, ApiError> {
+        let (ongoing_op, node_available, node_policy, total_nodes_count) = {
+            let locked = self.inner.read().unwrap();
+            let nodes = &locked.nodes;
+            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {} not registered", node_id).into(),
+            ))?;
+
+            (
+                locked
+                    .ongoing_operation
+                    .as_ref()
+                    .map(|ongoing| ongoing.operation),
+                node.is_available(),
+                node.get_scheduling(),
+                nodes.len(),
+            )
+        };
+
+        if let Some(ongoing) = ongoing_op {
+            return Err(ApiError::PreconditionFailed(
+                format!("Background operation already ongoing for node: {}", ongoing).into(),
+            ));
+        }
+
+        if !node_available {
+            return Err(ApiError::ResourceUnavailable(
+                format!("Node {node_id} is currently unavailable").into(),
+            ));
+        }
+
+        if total_nodes_count <= 1 {
+            return Err(ApiError::PreconditionFailed(
+                "No other nodes to fill from".into(),
+            ));
+        }
+
+        let operation_id = match node_policy {
+            NodeSchedulingPolicy::Active => {
+                let cancel = self.cancel.child_token();
+                let operation = self
+                    .operation_begin(
+                        OperationKind::NodeFill,
+                        None,
+                        Some(node_id),
+                        Some(cancel.clone()),
+                    )
+                    .await?;
+                let operation_id = operation.id();
+
+                if let Err(err) = self
+                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Filling))
+                    .await
+                {
+                    operation
+                        .finish(OperationState::Failed, Some(err.to_string()))
+                        .await;
+                    return Err(err);
+                }
+
+                let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+
+                self.inner.write().unwrap().ongoing_operation = Some(OperationHandler {
+                    operation: Operation::Fill(Fill { node_id }),
+                    cancel: cancel.clone(),
+                });
+
+                let span = tracing::info_span!(parent: None, "fill_node", %node_id);
+
+                tokio::task::spawn({
+                    let service = self.clone();
+                    let cancel = cancel.clone();
+                    async move {
+                        let _gate_guard = gate_guard;
+
+                        scopeguard::defer! {
+                            let prev = service.inner.write().unwrap().ongoing_operation.take();
+
+                            if let Some(Operation::Fill(removed_fill)) = prev.map(|h| h.operation) {
+                                assert_eq!(removed_fill.node_id, node_id, "We always take the same operation");
+                            } else {
+                                panic!("We always remove the same operation")
+                            }
+                        }
+
+                        tracing::info!("Fill background operation starting");
+                        let res = service.fill_node(node_id, cancel, Some(&operation)).await;
+                        match &res {
+                            Ok(()) => {
+                                tracing::info!("Fill background operation completed successfully");
+                            }
+                            Err(OperationError::Cancelled) => {
+                                tracing::info!("Fill background operation was cancelled");
+                            }
+                            Err(err) => {
+                                tracing::error!("Fill background operation encountered: {err}")
+                            }
+                        }
+                        Self::background_operation_finish(operation, &res).await;
+                    }
+                }.instrument(span));
+
+                operation_id
+            }
+            NodeSchedulingPolicy::Filling => {
+                return Err(ApiError::Conflict(format!(
+                    "Node {node_id} has fill in progress"
+                )));
+            }
+            policy => {
+                return Err(ApiError::PreconditionFailed(
+                    format!("Node {node_id} cannot be filled due to {policy:?} policy").into(),
+                ));
+            }
+        };
+
+        Ok(OperationStartResponse { operation_id })
+    }
+
+    pub(crate) async fn cancel_node_fill(&self, node_id: NodeId) -> Result<(), ApiError> {
+        let node_available = {
+            let locked = self.inner.read().unwrap();
+            let nodes = &locked.nodes;
+            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {} not registered", node_id).into(),
+            ))?;
+
+            node.is_available()
+        };
+
+        if !node_available {
+            return Err(ApiError::ResourceUnavailable(
+                format!("Node {node_id} is currently unavailable").into(),
+            ));
+        }
+
+        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operation.as_ref() {
+            if let Operation::Fill(fill) = op_handler.operation {
+                if fill.node_id == node_id {
+                    tracing::info!("Cancelling background drain operation for node {node_id}");
+                    op_handler.cancel.cancel();
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(ApiError::PreconditionFailed(
+            format!("Node {node_id} has no fill in progress").into(),
+        ))
+    }
+
+    async fn operation_begin(
+        &self,
+        kind: OperationKind,
+        tenant_id: Option<TenantId>,
+        node_id: Option<NodeId>,
+        cancel: Option<CancellationToken>,
+    ) -> Result<operations::OperationGuard, ApiError> {
+        Ok(operations::OperationGuard::begin(
+            &self.operations,
+            &self.persistence,
+            kind,
+            tenant_id,
+            node_id,
+            cancel,
+        )
+        .await?)
+    }
+
+    /// Run a long-running action which may not be cancelled as a recorded operation.  The action
+    /// begins the operation with [`operations::OperationStart::begin`] once it has validated the
+    /// request, so that rejected requests are not recorded.
+    async fn with_operation<T, Fut>(
+        &self,
+        kind: OperationKind,
+        tenant_id: Option<TenantId>,
+        node_id: Option<NodeId>,
+        f: impl FnOnce(operations::OperationStart) -> Fut,
+    ) -> Result<T, ApiError>
+    where
+        Fut: std::future::Future<Output = Result<T, ApiError>>,
+    {
+        let start = operations::OperationStart::new(
+            &self.operations,
+            &self.persistence,
+            kind,
+            tenant_id,
+            node_id,
+        );
+        let begun = start.begun();
+
+        let result = f(start).await;
+        let Some(operation) = begun.lock().unwrap().take() else {
+            return result;
+        };
+        match &result {
+            Ok(_) => operation.finish(OperationState::Succeeded, None).await,
+            Err(err) => {
+                operation
+                    .finish(OperationState::Failed, Some(format!("{err}")))
+                    .await
+            }
+        }
+
+        result
+    }
+
+    /// Record the end of an operation running in the background.
+    async fn background_operation_finish(
+        operation: operations::OperationGuard,
+        result: &Result<(), OperationError>,
+    ) {
+        match result {
+            Ok(()) => operation.finish(OperationState::Succeeded, None).await,
+            Err(OperationError::Cancelled) => {
+                operation.finish(OperationState::Cancelled, None).await
+            }
+            Err(err) => {
+                operation
+                    .finish(OperationState::Failed, Some(err.to_string()))
+                    .await
+            }
+        }
+    }
+
+    pub(crate) async fn get_operation(&self, id: i64) -> Result<OperationStatus, ApiError> {
+        if let Some(status) = self.operations.lock().unwrap().get(id) {
+            return Ok(status);
+        }
+
+        match self.persistence.get_operation(id).await? {
+            Some(operation) => Ok(operation.into_status()),
+            None => Err(ApiError::NotFound(
+                anyhow::anyhow!("Operation {id} not found").into(),
+            )),
+        }
+    }
+
+    pub(crate) async fn list_operations(
+        &self,
+        filter: OperationFilter,
+    ) -> Result<Vec<OperationStatus>, ApiError> {
+        let mut statuses = self
+            .persistence
+            .list_operations(filter)
+            .await?
+            .into_iter()
+            .map(|operation| operation.into_status())
+            .collect::<Vec<_>>();
+
+        let running = self.operations.lock().unwrap();
+        for status in statuses.iter_mut() {
+            running.refresh(status);
+        }
+
+        Ok(statuses)
+    }
+
+    /// Request cancellation of a running operation.  The operation ends once it has observed
+    /// the cancellation.
+    pub(crate) async fn cancel_operation(&self, id: i64) -> Result<OperationStatus, ApiError> {
+        let cancelled = self.operations.lock().unwrap().cancel(id);
+        match cancelled {
+            Some(true) => {
+                tracing::info!("Cancelling operation {id}");
+                self.get_operation(id).await
+            }
+            Some(false) => Err(ApiError::PreconditionFailed(
+                format!("Operation {id} may not be cancelled").into(),
+            )),
+            None => {
+                let status = self.get_operation(id).await?;
+                Err(ApiError::PreconditionFailed(
+                    format!("Operation {id} is {}", status.state).into(),
+                ))
+            }
+        }
+    }
+
+    /// The state of a tenant and node as recorded in the audit log, around calls which may
+    /// modify them.  None if the call names neither.
+    pub(crate) fn audit_state(
+        &self,
+        tenant_id: Option<TenantId>,
+        node_id: Option<NodeId>,
+    ) -> Option<String> {
+        if tenant_id.is_none() && node_id.is_none() {
+            return None;
+        }
+
+        let state = AuditState {
+            tenant: tenant_id.and_then(|tenant_id| self.tenant_describe(tenant_id).ok()),
+            node: node_id.and_then(|node_id| {
+                let locked = self.inner.read().unwrap();
+                locked.nodes.get(&node_id).map(|node| node.describe())
+            }),
+        };
+        Some(serde_json::to_string(&state).unwrap())
+    }
+
+    /// Append a record to the audit log.  Failures are logged rather than returned: by the
+    /// time a call is recorded, it has already taken effect.
+    pub(crate) async fn record_audit(&self, record: NewAuditRecord) {
+        let (method, path) = (record.method.clone(), record.path.clone());
+        if let Err(err) = self.persistence.insert_audit_record(record).await {
+            tracing::error!("Failed to record {method} {path} in the audit log: {err}");
+        }
+    }
+
+    pub(crate) async fn list_audit_records(
+        &self,
+        filter: AuditFilter,
+    ) -> Result<Vec<AuditRecord>, ApiError> {
+        Ok(self
+            .persistence
+            .list_audit_records(filter)
+            .await?
+            .into_iter()
+            .map(|record| record.into_record())
+            .collect())
+    }
+
+    /// Start a rolling restart of the given nodes, or of all nodes.
+    pub(crate) async fn start_rolling_restart(
+        self: &Arc<Self>,
+        req: RollingRestartRequest,
+    ) -> Result<RollingRestartStatus, ApiError> {
+        if req.max_concurrent_per_az == 0 {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "max_concurrent_per_az must be at least 1"
+            )));
+        }
+
+        let mut rolling = self.rolling_restart.lock().await;
+        if let Some(rr) = rolling.as_ref() {
+            if !rr.status.state.is_finished() {
+                return Err(ApiError::Conflict(format!(
+                    "Rolling restart {} is {:?}: abort it before starting another",
+                    rr.status.id, rr.status.state
+                )));
+            }
+        }
+
+        let nodes = {
+            let locked = self.inner.read().unwrap();
+            if let Some(ongoing) = locked.ongoing_operation.as_ref() {
+                return Err(ApiError::PreconditionFailed(
+                    format!(
+                        "Background operation already ongoing: {}",
+                        ongoing.operation
+                    )
+                    .into(),
+                ));
+            }
+
+            let node_ids = if req.nodes.is_empty() {
+                locked.nodes.keys().copied().sorted().collect::<Vec<_>>()
+            } else {
+                if !req.nodes.iter().all_unique() {
+                    return Err(ApiError::BadRequest(anyhow::anyhow!(
+                        "Nodes may only be listed once"
+                    )));
+                }
+                req.nodes.clone()
+            };
+
+            node_ids
+                .into_iter()
+                .map(|node_id| match locked.nodes.get(&node_id) {
+                    Some(node) => Ok((node_id, node.get_availability_zone_id().clone())),
+                    None => Err(ApiError::NotFound(
+                        anyhow::anyhow!("Node {node_id} not registered").into(),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        if nodes.is_empty() {
+            return Err(ApiError::BadRequest(anyhow::anyhow!("No nodes to restart")));
+        }
+
+        let now = chrono::Utc::now();
+        let mut rr = rolling_restart::RollingRestart::new(&req, nodes, now);
+        rr.status.id = self
+            .persistence
+            .insert_rolling_restart(rr.to_persistent_status(), now)
+            .await?;
+        tracing::info!(
+            "Starting rolling restart {} of {} nodes",
+            rr.status.id,
+            rr.status.nodes.len()
+        );
+
+        if let Err(err) = self.spawn_rolling_restart_driver(rr.status.id) {
+            // Leave the operation to be resumed by the operator
+            rr.pause();
+            self.persistence
+                .update_rolling_restart(rr.status.id, rr.to_persistent_status(), now)
+                .await?;
+            *rolling = Some(rr);
+            return Err(err);
+        }
+
+        let status = rr.status.clone();
+        *rolling = Some(rr);
+        Ok(status)
+    }
+
+    pub(crate) async fn get_rolling_restart(&self) -> Result<RollingRestartStatus, ApiError> {
+        match self.rolling_restart.lock().await.as_ref() {
+            Some(rr) => Ok(rr.status.clone()),
+            None => Err(ApiError::NotFound(
+                anyhow::anyhow!("No rolling restart was ever started").into(),
+            )),
+        }
+    }
+
+    /// Stop starting nodes: nodes which are in progress carry on.
+    pub(crate) async fn pause_rolling_restart(&self) -> Result<RollingRestartStatus, ApiError> {
+        self.rolling_restart_update(|rr, _| {
+            if rr.pause() {
+                Ok(rr.status.clone())
+            } else {
+                Err(ApiError::PreconditionFailed(
+                    format!("Rolling restart is {:?}", rr.status.state).into(),
+                ))
+            }
+        })
+        .await
+    }
+
+    /// Resume a paused or failed rolling restart.
+    pub(crate) async fn resume_rolling_restart(
+        self: &Arc<Self>,
+    ) -> Result<RollingRestartStatus, ApiError> {
+        let status = self
+            .rolling_restart_update(|rr, now| {
+                if rr.resume(now) {
+                    Ok(rr.status.clone())
+                } else {
+                    Err(ApiError::PreconditionFailed(
+                        format!("Rolling restart is {:?}", rr.status.state).into(),
+                    ))
+                }
+            })
+            .await?;
+
+        // The driver is only missing if spawning it failed, at start or after a controller restart
+        if !self.rolling_restart_driver_running() {
+            self.spawn_rolling_restart_driver(status.id)?;
+        }
+
+        Ok(status)
+    }
+
+    /// Abort a rolling restart, returning any nodes it took out of service to service.
+    pub(crate) async fn abort_rolling_restart(
+        self: &Arc<Self>,
+    ) -> Result<RollingRestartStatus, ApiError> {
+        let status = self
+            .rolling_restart_update(|rr, _| {
+                if rr.abort() {
+                    Ok(rr.status.clone())
+                } else {
+                    Err(ApiError::PreconditionFailed(
+                        format!("Rolling restart is {:?}", rr.status.state).into(),
+                    ))
+                }
+            })
+            .await?;
+        tracing::info!("Aborting rolling restart {}", status.id);
+
+        // A running driver restores the nodes once its node tasks have observed the cancellation
+        let cancelled = {
+            let locked = self.inner.read().unwrap();
+            match locked.ongoing_operation.as_ref() {
+                Some(op_handler)
+                    if matches!(op_handler.operation, Operation::RollingRestart(_)) =>
+                {
+                    op_handler.cancel.cancel();
+                    true
+                }
+                _ => false,
+            }
+        };
+        if !cancelled {
+            self.rolling_restart_restore_nodes().await?;
+        }
+
+        Ok(status)
+    }
+
+    /// Report that a node which was drained for a rolling restart has been restarted.  This
+    /// is only required by rolling restarts waiting for [`RestartSignal::External`].
+    pub(crate) async fn rolling_restart_node_restarted(
+        &self,
+        node_id: NodeId,
+    ) -> Result<RollingRestartStatus, ApiError> {
+        self.rolling_restart_update(|rr, _| {
+            if rr.signal_restart(node_id) {
+                Ok(rr.status.clone())
+            } else {
+                Err(ApiError::PreconditionFailed(
+                    format!("Node {node_id} is not awaiting a restart").into(),
+                ))
+            }
+        })
+        .await
+    }
+
+    /// Apply a change to the current rolling restart, and persist it if `f` succeeds.
+    async fn rolling_restart_update<R>(
+        &self,
+        f: impl FnOnce(
+            &mut rolling_restart::RollingRestart,
+            chrono::DateTime<chrono::Utc>,
+        ) -> Result<R, ApiError>,
+    ) -> Result<R, ApiError> {
+        let mut rolling = self.rolling_restart.lock().await;
+        let Some(rr) = rolling.as_mut() else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("No rolling restart was ever started").into(),
+            ));
+        };
+
+        let now = chrono::Utc::now();
+        let result = f(rr, now)?;
+        rr.status.updated_at = now;
+        self.persistence
+            .update_rolling_restart(rr.status.id, rr.to_persistent_status(), now)
+            .await?;
+
+        Ok(result)
+    }
+
+    fn rolling_restart_driver_running(&self) -> bool {
+        matches!(
+            self.inner
+                .read()
+                .unwrap()
+                .ongoing_operation
+                .as_ref()
+                .map(|op| op.operation),
+            Some(Operation::RollingRestart(_))
+        )
+    }
+
+    /// Carry on with a rolling restart started by a previous controller.
+    async fn rolling_restart_startup(self: &Arc<Self>) {
+        let id = match self.rolling_restart.lock().await.as_ref() {
+            Some(rr) if !rr.status.state.is_finished() => rr.status.id,
+            _ => return,
+        };
+
+        tracing::info!("Resuming rolling restart {id}");
+        if let Err(err) = self.spawn_rolling_restart_driver(id) {
+            tracing::error!("Failed to resume rolling restart {id}: {err}");
+        }
+    }
+
+    /// Claim the background operation slot for a rolling restart, and spawn the task driving it.
+    /// The task holds the slot until the rolling restart is finished, so that nodes are not
+    /// drained or filled by anyone else in the meantime, even while it is paused.
+    fn spawn_rolling_restart_driver(self: &Arc<Self>, id: i64) -> Result<(), ApiError> {
+        let cancel = self.cancel.child_token();
+        let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+
+        {
+            let mut locked = self.inner.write().unwrap();
+            if let Some(ongoing) = locked.ongoing_operation.as_ref() {
+                return Err(ApiError::PreconditionFailed(
+                    format!(
+                        "Background operation already ongoing: {}",
+                        ongoing.operation
+                    )
+                    .into(),
+                ));
+            }
+            locked.ongoing_operation = Some(OperationHandler {
+                operation: Operation::RollingRestart(RollingRestart { id }),
+                cancel: cancel.clone(),
+            });
+        }
+
+        let span = tracing::info_span!(parent: None, "rolling_restart", %id);
+
+        tokio::task::spawn(
+            {
+                let service = self.clone();
+                async move {
+                    let _gate_guard = gate_guard;
+
+                    scopeguard::defer! {
+                        let prev = service.inner.write().unwrap().ongoing_operation.take();
+
+                        if let Some(Operation::RollingRestart(removed)) = prev.map(|h| h.operation) {
+                            assert_eq!(removed.id, id, "We always take the same operation");
+                        } else {
+                            panic!("We always remove the same operation")
+                        }
+                    }
+
+                    tracing::info!("Rolling restart background operation starting");
+                    let operation = match service
+                        .operation_begin(OperationKind::RollingRestart, None, None, None)
+                        .await
+                    {
+                        Ok(operation) => Some(operation),
+                        Err(err) => {
+                            tracing::warn!("Failed to record rolling restart operation: {err}");
+                            None
+                        }
+                    };
+
+                    let state = service.rolling_restart_drive(cancel, operation.as_ref()).await;
+
+                    if let Some(operation) = operation {
+                        let (state, error) = match state {
+                            RollingRestartState::Completed => (OperationState::Succeeded, None),
+                            RollingRestartState::Aborted => (OperationState::Cancelled, None),
+                            // The next controller resumes the rolling restart as a new operation
+                            _ => (
+                                OperationState::Failed,
+                                Some("Interrupted by controller shutdown".to_string()),
+                            ),
+                        };
+                        operation.finish(state, error).await;
+                    }
+                }
+            }
+            .instrument(span),
+        );
+
+        Ok(())
+    }
+
+    /// Drive the nodes of the current rolling restart through their phases until the operation
+    /// is finished, or the controller shuts down.  Returns the state the operation was left in.
+    async fn rolling_restart_drive(
+        self: &Arc<Self>,
+        cancel: CancellationToken,
+        operation: Option<&operations::OperationGuard>,
+    ) -> RollingRestartState {
+        const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+        let mut node_tasks = FuturesUnordered::new();
+        let mut running = HashSet::new();
+
+        loop {
+            let (runnable, finished) = {
+                let mut rolling = self.rolling_restart.lock().await;
+                let rr = rolling
+                    .as_mut()
+                    .expect("Rolling restart is set before its driver is spawned");
+
+                let now = chrono::Utc::now();
+                let admitted = if cancel.is_cancelled() {
+                    Vec::new()
+                } else {
+                    rr.admit(now)
+                };
+                if !admitted.is_empty() {
+                    tracing::info!("Starting rolling restart of nodes {admitted:?}");
+                }
+                if !admitted.is_empty() || rr.maybe_complete() {
+                    rr.status.updated_at = now;
+                    if let Err(err) = self
+                        .persistence
+                        .update_rolling_restart(rr.status.id, rr.to_persistent_status(), now)
+                        .await
+                    {
+                        // The next change will persist the progress
+                        tracing::warn!("Failed to persist rolling restart progress: {err}");
+                    }
+                }
+
+                if let Some(operation) = operation {
+                    let done = rr
+                        .status
+                        .nodes
+                        .iter()
+                        .filter(|n| n.phase == RollingRestartPhase::Done)
+                        .count();
+                    operation.set_progress(done, rr.status.nodes.len());
+                }
+
+                (rr.runnable(), rr.status.state.is_finished())
+            };
+
+            if !cancel.is_cancelled() {
+                for node_id in runnable {
+                    if running.insert(node_id) {
+                        let service = self.clone();
+                        let cancel = cancel.clone();
+                        node_tasks.push(
+                            async move {
+                                (node_id, service.rolling_restart_node(node_id, cancel).await)
+                            }
+                            .instrument(tracing::info_span!("restart_node", %node_id)),
+                        );
+                    }
+                }
+            }
+
+            if finished && node_tasks.is_empty() {
+                break;
+            }
+
+            tokio::select! {
+                Some((node_id, res)) = node_tasks.next(), if !node_tasks.is_empty() => {
+                    running.remove(&node_id);
+                    match res {
+                        Ok(()) => {
+                            tracing::info!("Rolling restart of node {node_id} completed");
+                        }
+                        Err(OperationError::Cancelled) => {
+                            tracing::info!("Rolling restart of node {node_id} was cancelled");
+                        }
+                        Err(err) => {
+                            tracing::error!("Rolling restart of node {node_id} failed: {err}");
+                            let res = self
+                                .rolling_restart_update(|rr, _| {
+                                    rr.set_failed(node_id, err.to_string());
+                                    Ok(())
+                                })
+                                .await;
+                            if let Err(err) = res {
+                                tracing::warn!("Failed to persist rolling restart failure: {err}");
+                            }
+                        }
+                    }
+                }
+                _ = cancel.cancelled(), if running.is_empty() => {
+                    // Shutdown: leave the persisted operation for the next controller
+                    break;
+                }
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+
+        let state = self
+            .rolling_restart
+            .lock()
+            .await
+            .as_ref()
+            .expect("Rolling restart is set before its driver is spawned")
+            .status
+            .state;
+        if state == RollingRestartState::Aborted && !self.cancel.is_cancelled() {
+            if let Err(err) = self.rolling_restart_restore_nodes().await {
+                tracing::error!("Failed to return nodes to service after abort: {err}");
+            }
+        }
+
+        tracing::info!("Rolling restart background operation finished");
+        state
+    }
+
+    /// Return the nodes which an aborted rolling restart took out of service to service.
+    async fn rolling_restart_restore_nodes(&self) -> Result<(), ApiError> {
+        let in_progress = match self.rolling_restart.lock().await.as_ref() {
+            Some(rr) => rr.in_progress(),
+            None => return Ok(()),
+        };
+
+        for node_id in in_progress {
+            let policy = self
+                .inner
+                .read()
+                .unwrap()
+                .nodes
+                .get(&node_id)
+                .map(|n| n.get_scheduling());
+            if let Some(
+                NodeSchedulingPolicy::Draining
+                | NodeSchedulingPolicy::PauseForRestart
+                | NodeSchedulingPolicy::Filling,
+            ) = policy
+            {
+                tracing::info!("Returning node {node_id} to service");
+                self.node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take one node of a rolling restart through the remaining phases.
+    async fn rolling_restart_node(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        cancel: CancellationToken,
+    ) -> Result<(), OperationError> {
+        loop {
+            let phase = self
+                .rolling_restart
+                .lock()
+                .await
+                .as_ref()
+                .and_then(|rr| rr.get_node(node_id))
+                .map(|n| n.phase);
+
+            let next_phase = match phase {
+                Some(RollingRestartPhase::Draining) => {
+                    self.rolling_restart_drain(node_id, cancel.clone()).await?;
+                    RollingRestartPhase::AwaitingRestart
+                }
+                Some(RollingRestartPhase::AwaitingRestart) => {
+                    self.rolling_restart_await_restart(node_id, &cancel).await?;
+                    RollingRestartPhase::Filling
+                }
+                Some(RollingRestartPhase::Filling) => {
+                    self.rolling_restart_fill(node_id, cancel.clone()).await?;
+                    RollingRestartPhase::Done
+                }
+                Some(RollingRestartPhase::Pending | RollingRestartPhase::Done) | None => {
+                    return Ok(())
+                }
+            };
+
+            tracing::info!("Node {node_id} entering phase {next_phase:?}");
+            self.rolling_restart_update(|rr, now| {
+                rr.set_phase(node_id, next_phase, now);
+                Ok(())
+            })
+            .await
+            .map_err(|err| {
+                OperationError::FinalizeError(
+                    format!("Failed to record rolling restart of {node_id}: {err}").into(),
+                )
+            })?;
+        }
+    }
+
+    async fn rolling_restart_drain(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        cancel: CancellationToken,
+    ) -> Result<(), OperationError> {
+        let (node_available, node_policy) = self.rolling_restart_node_state(node_id)?;
+
+        match node_policy {
+            // The drain completed before a controller restart
+            NodeSchedulingPolicy::PauseForRestart => return Ok(()),
+            _ if !node_available => {
+                return Err(OperationError::NodeStateChanged(
+                    format!("node {node_id} is unavailable").into(),
+                ));
+            }
+            NodeSchedulingPolicy::Draining => {}
+            _ => {
+                self.node_configure(node_id, None, Some(NodeSchedulingPolicy::Draining))
+                    .await
+                    .map_err(|err| {
+                        OperationError::NodeStateChanged(
+                            format!("failed to start draining node {node_id}: {err}").into(),
+                        )
+                    })?;
+            }
+        }
+
+        self.drain_node(node_id, cancel, None).await
+    }
+
+    /// Wait until the node has re-attached after its restart, and if the operation waits for
+    /// an external signal, until that has been received.
+    async fn rolling_restart_await_restart(
+        &self,
+        node_id: NodeId,
+        cancel: &CancellationToken,
+    ) -> Result<(), OperationError> {
+        const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+        loop {
+            let (restart_signal, restart_timeout, phase_started_at, restart_signalled) = {
+                let rolling = self.rolling_restart.lock().await;
+                let rr = rolling
+                    .as_ref()
+                    .expect("Rolling restart is set before its driver is spawned");
+                let node = rr
+                    .get_node(node_id)
+                    .ok_or(OperationError::NodeStateChanged(
+                        format!("node {node_id} is not part of the rolling restart").into(),
+                    ))?;
+                (
+                    rr.status.restart_signal,
+                    rr.status.restart_timeout,
+                    node.phase_started_at,
+                    node.restart_signalled,
+                )
+            };
+
+            // Re-attaching moves the node out of PauseForRestart
+            let (node_available, node_policy) = self.rolling_restart_node_state(node_id)?;
+            let restarted = node_available && node_policy != NodeSchedulingPolicy::PauseForRestart;
+            let signalled = match restart_signal {
+                RestartSignal::ReAttach => true,
+                RestartSignal::External => restart_signalled,
+            };
+            if restarted && signalled {
+                return Ok(());
+            }
+
+            let waited = phase_started_at
+                .and_then(|t| (chrono::Utc::now() - t).to_std().ok())
+                .unwrap_or_default();
+            if waited > restart_timeout {
+                return Err(OperationError::Timeout(
+                    format!(
+                        "node {node_id} was not restarted within {}",
+                        humantime::format_duration(restart_timeout)
+                    )
+                    .into(),
+                ));
+            }
+
+            tokio::select! {
+                _ = cancel.cancelled() => return Err(OperationError::Cancelled),
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    }
+
+    async fn rolling_restart_fill(
+        &self,
+        node_id: NodeId,
+        cancel: CancellationToken,
+    ) -> Result<(), OperationError> {
+        let (node_available, node_policy) = self.rolling_restart_node_state(node_id)?;
+        if !node_available {
+            return Err(OperationError::NodeStateChanged(
+                format!("node {node_id} is unavailable").into(),
+            ));
+        }
+
+        if node_policy != NodeSchedulingPolicy::Filling {
+            self.node_configure(node_id, None, Some(NodeSchedulingPolicy::Filling))
+                .await
+                .map_err(|err| {
+                    OperationError::NodeStateChanged(
+                        format!("failed to start filling node {node_id}: {err}").into(),
+                    )
+                })?;
+        }
+
+        self.fill_node(node_id, cancel, None).await
+    }
+
+    fn rolling_restart_node_state(
+        &self,
+        node_id: NodeId,
+    ) -> Result<(bool, NodeSchedulingPolicy), OperationError> {
+        let locked = self.inner.read().unwrap();
+        let node = locked
+            .nodes
+            .get(&node_id)
+            .ok_or(OperationError::NodeStateChanged(
+                format!("node {node_id} was removed").into(),
+            ))?;
+        Ok((node.is_available(), node.get_scheduling()))
+    }
+
+    /// Start injecting faults, replacing the configuration of the chaos injector if it was
+    /// already running
+    pub fn chaos_start(self: &Arc<Self>, config: ChaosConfig) -> Result<ChaosStatus, ApiError> {
+        let mut injector = chaos_injector::ChaosInjector::new(self.clone(), config.clone())
+            .map_err(ApiError::BadRequest)?;
+        let cancel = self.cancel.child_token();
+        let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+
+        let status = {
+            let mut chaos = self.chaos.lock().unwrap();
+            chaos.start(config, cancel.clone());
+            chaos.status()
+        };
+
+        tokio::task::spawn(
+            async move {
+                let _gate_guard = gate_guard;
+                injector.run(cancel).await
+            }
+            .instrument(tracing::info_span!(parent: None, "chaos_injector")),
+        );
+
+        Ok(status)
+    }
+
+    /// Stop injecting faults, and disarm those not yet injected
+    pub fn chaos_stop(&self) -> Result<ChaosStatus, ApiError> {
+        let mut chaos = self.chaos.lock().unwrap();
+        if !chaos.stop() {
+            return Err(ApiError::PreconditionFailed(
+                "The chaos injector is not running".into(),
+            ));
+        }
+        Ok(chaos.status())
+    }
+
+    pub(crate) fn chaos_status(&self) -> ChaosStatus {
+        self.chaos.lock().unwrap().status()
+    }
+
+    /// Fail a shard split if the chaos injector armed a kill for this phase of it
+    fn maybe_kill_split(&self, phase: chaos_injector::SplitPhase) -> Result<(), ApiError> {
+        if self.chaos.lock().unwrap().take_split_kill(phase) {
+            tracing::warn!("Killing shard split at {phase:?}: injected by chaos");
+            Err(ApiError::InternalServerError(anyhow::anyhow!(
+                "Shard split killed by chaos injection"
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Like [`Self::maybe_configured_reconcile_shard`], but uses the default reconciler
+    /// configuration
+    fn maybe_reconcile_shard(
+        &self,
+        shard: &mut TenantShard,
+        nodes: &Arc<HashMap<NodeId, Node>>,
+    ) -> Option<ReconcilerWaiter> {
+        self.maybe_configured_reconcile_shard(shard, nodes, ReconcilerConfig::default())
+    }
+
+    /// Wrap [`TenantShard`] reconciliation methods with acquisition of [`Gate`] and [`ReconcileUnits`],
+    fn maybe_configured_reconcile_shard(
+        &self,
+        shard: &mut TenantShard,
+        nodes: &Arc<HashMap<NodeId, Node>>,
+        reconciler_config: ReconcilerConfig,
+    ) -> Option<ReconcilerWaiter> {
+        let reconcile_needed = shard.get_reconcile_needed(nodes);
+
+        match reconcile_needed {
+            ReconcileNeeded::No => return None,
+            ReconcileNeeded::WaitExisting(waiter) => return Some(waiter),
+            ReconcileNeeded::Yes => {
+                // Fall through to try and acquire units for spawning reconciler
+            }
+        };
+
+        let units = match self.reconciler_concurrency.clone().try_acquire_owned() {
+            Ok(u) => ReconcileUnits::new(u),
+            Err(_) => {
+                tracing::info!(tenant_id=%shard.tenant_shard_id.tenant_id, shard_id=%shard.tenant_shard_id.shard_slug(),
+                    "Concurrency limited: enqueued for reconcile later");
+                if !shard.delayed_reconcile {
+                    match self.delayed_reconcile_tx.try_send(shard.tenant_shard_id) {
+                        Err(TrySendError::Closed(_)) => {
+                            // Weird mid-shutdown case?
+                        }
+                        Err(TrySendError::Full(_)) => {
+                            // It is safe to skip sending our ID in the channel: we will eventually get retried by the background reconcile task.
+                            tracing::warn!(
+                                "Many shards are waiting to reconcile: delayed_reconcile queue is full"
+                            );
+                        }
+                        Ok(()) => {
+                            shard.delayed_reconcile = true;
+                        }
+                    }
+                }
+
+                // We won't spawn a reconciler, but we will construct a waiter that waits for the shard's sequence
+                // number to advance.  When this function is eventually called again and succeeds in getting units,
+                // it will spawn a reconciler that makes this waiter complete.
+                return Some(shard.future_reconcile_waiter());
+            }
+        };
+
+        let Ok(gate_guard) = self.reconcilers_gate.enter() else {
+            // Gate closed: we're shutting down, drop out.
+            return None;
+        };
+
+        shard.spawn_reconciler(
+            &self.result_tx,
+            nodes,
+            &self.compute_hook,
+            reconciler_config,
+            &self.config,
+            &self.persistence,
+            units,
+            gate_guard,
+            &self.reconcilers_cancel,
+        )
+    }
+
+    /// Check all tenants for pending reconciliation work, and reconcile those in need.
+    /// Additionally, reschedule tenants that require it.
+    ///
+    /// Returns how many reconciliation tasks were started, or `1` if no reconciles were
+    /// spawned but some _would_ have been spawned if `reconciler_concurrency` units where
+    /// available.  A return value of 0 indicates that everything is fully reconciled already.
+    fn reconcile_all(&self) -> usize {
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, _scheduler) = locked.parts_mut();
+        let pageservers = nodes.clone();
+
+        let mut schedule_context = ScheduleContext::default();
+
+        let mut reconciles_spawned = 0;
+        for (tenant_shard_id, shard) in tenants.iter_mut() {
+            if tenant_shard_id.is_shard_zero() {
+                schedule_context = ScheduleContext::default();
+            }
+
+            // Skip checking if this shard is already enqueued for reconciliation
+            if shard.delayed_reconcile && self.reconciler_concurrency.available_permits() == 0 {
+                // If there is something delayed, then return a nonzero count so that
+                // callers like reconcile_all_now do not incorrectly get the impression
+                // that the system is in a quiescent state.
+                reconciles_spawned = std::cmp::max(1, reconciles_spawned);
+                continue;
+            }
+
+            // Eventual consistency: if an earlier reconcile job failed, and the shard is still
+            // dirty, spawn another rone
+            if self.maybe_reconcile_shard(shard, &pageservers).is_some() {
+                reconciles_spawned += 1;
+            }
+
+            schedule_context.avoid(&shard.intent.all_pageservers());
+        }
+
+        reconciles_spawned
+    }
+
+    /// `optimize` in this context means identifying shards which have valid scheduled locations, but
+    /// could be scheduled somewhere better:
+    /// - Cutting over to a secondary if the node with the secondary is more lightly loaded
+    ///    * e.g. after a node fails then recovers, to move some work back to it
+    /// - Cutting over to a secondary if it improves the spread of shard attachments within a tenant
+    ///    * e.g. after a shard split, the initial attached locations will all be on the node where
+    ///      we did the split, but are probably better placed elsewhere.
+    /// - Creating new secondary locations if it improves the spreading of a sharded tenant
+    ///    * e.g. after a shard split, some locations will be on the same node (where the split
+    ///      happened), and will probably be better placed elsewhere.
+    ///
+    /// To put it more briefly: whereas the scheduler respects soft constraints in a ScheduleContext at
+    /// the time of scheduling, this function looks for cases where a better-scoring location is available
+    /// according to those same soft constraints.
+    async fn optimize_all(&self) -> usize {
+        // Limit on how many shards' optmizations each call to this function will execute.  Combined
+        // with the frequency of background calls, this acts as an implicit rate limit that runs a small
+        // trickle of optimizations in the background, rather than executing a large number in parallel
+        // when a change occurs.
+        const MAX_OPTIMIZATIONS_EXEC_PER_PASS: usize = 2;
+
+        // Synchronous prepare: scan shards for possible scheduling optimizations
+        let candidate_work = self.optimize_all_plan();
+        let candidate_work_len = candidate_work.len();
+
+        // Asynchronous validate: I/O to pageservers to make sure shards are in a good state to apply validation
+        let validated_work = self.optimize_all_validate(candidate_work).await;
+
+        let was_work_filtered = validated_work.len() != candidate_work_len;
+
+        // Synchronous apply: update the shards' intent states according to validated optimisations
+        let mut reconciles_spawned = 0;
+        let mut optimizations_applied = 0;
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, scheduler) = locked.parts_mut();
+        for (tenant_shard_id, optimization) in validated_work {
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                // Shard was dropped between planning and execution;
+                continue;
+            };
+            if shard.apply_optimization(scheduler, optimization) {
+                optimizations_applied += 1;
+                if self.maybe_reconcile_shard(shard, nodes).is_some() {
+                    reconciles_spawned += 1;
+                }
+            }
+
+            if optimizations_applied >= MAX_OPTIMIZATIONS_EXEC_PER_PASS {
+                break;
+            }
+        }
+
+        if was_work_filtered {
+            // If we filtered any work out during validation, ensure we return a nonzero value to indicate
+            // to callers that the system is not in a truly quiet state, it's going to do some work as soon
+            // as these validations start passing.
+            reconciles_spawned = std::cmp::max(reconciles_spawned, 1);
+        }
+
+        reconciles_spawned
+    }
+
+    fn optimize_all_plan(&self) -> Vec<(TenantShardId, ScheduleOptimization)> {
+        let mut schedule_context = ScheduleContext::default();
+
+        let mut tenant_shards: Vec<&TenantShard> = Vec::new();
+
+        // How many candidate optimizations we will generate, before evaluating them for readniess: setting
+        // this higher than the execution limit gives us a chance to execute some work even if the first
+        // few optimizations we find are not ready.
+        const MAX_OPTIMIZATIONS_PLAN_PER_PASS: usize = 8;
+
+        let mut work = Vec::new();
+
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, scheduler) = locked.parts_mut();
+        for (tenant_shard_id, shard) in tenants.iter() {
+            if tenant_shard_id.is_shard_zero() {
+                // Reset accumulators on the first shard in a tenant
+                schedule_context = ScheduleContext::default();
+                schedule_context.mode = ScheduleMode::Speculative;
+                tenant_shards.clear();
+            }
+
+            if work.len() >= MAX_OPTIMIZATIONS_PLAN_PER_PASS {
+                break;
+            }
+
+            match shard.get_scheduling_policy() {
+                ShardSchedulingPolicy::Active => {
+                    // Ok to do optimization
+                }
+                ShardSchedulingPolicy::Essential
+                | ShardSchedulingPolicy::Pause
+                | ShardSchedulingPolicy::Stop => {
+                    // Policy prevents optimizing this shard.
+                    continue;
+                }
+            }
+
+            // Accumulate the schedule context for all the shards in a tenant: we must have
+            // the total view of all shards before we can try to optimize any of them.
+            schedule_context.avoid(&shard.intent.all_pageservers());
+            if let Some(attached) = shard.intent.get_attached() {
+                schedule_context.push_attached(*attached);
+            }
+            tenant_shards.push(shard);
+
+            // Once we have seen the last shard in the tenant, proceed to search across all shards
+            // in the tenant for optimizations
+            if shard.shard.number.0 == shard.shard.count.count() - 1 {
+                if tenant_shards.iter().any(|s| s.reconciler.is_some()) {
+                    // Do not start any optimizations while another change to the tenant is ongoing: this
+                    // is not necessary for correctness, but simplifies operations and implicitly throttles
+                    // optimization changes to happen in a "trickle" over time.
+                    continue;
+                }
+
+                if tenant_shards.iter().any(|s| {
+                    !matches!(s.splitting, SplitState::Idle)
+                        || matches!(s.policy, PlacementPolicy::Detached)
+                }) {
+                    // Never attempt to optimize a tenant that is currently being split, or
+                    // a tenant that is meant to be detached
+                    continue;
+                }
+
+                // TODO: optimization calculations are relatively expensive: create some fast-path for
+                // the common idle case (avoiding the search on tenants that we have recently checked)
+
+                for shard in &tenant_shards {
+                    if let Some(optimization) =
+                        // If idle, move locations off nodes that lack the labels the tenant's placement constraints require.
+                        shard.optimize_placement_constraints(scheduler, &schedule_context)
+                    {
+                        work.push((shard.tenant_shard_id, optimization));
+                        break;
+                    } else if let Some(optimization) =
+                        // If idle, move locations off nodes whose disks are over the usage watermark.
+                        shard.optimize_disk_usage(scheduler, &schedule_context)
+                    {
+                        work.push((shard.tenant_shard_id, optimization));
+                        break;
+                    } else if let Some(optimization) =
+                        // If idle, maybe ptimize attachments: if a shard has a secondary location that is preferable to
+                        // its primary location based on soft constraints, cut it over.  Never cut over onto a node
+                        // with a full disk or without the required labels: that would undo the optimizations above.
+                        shard
+                            .optimize_attachment(nodes, &schedule_context)
+                            .filter(|optimization| {
+                                !matches!(
+                                    &optimization.action,
+                                    ScheduleOptimizationAction::MigrateAttachment(migrate)
+                                        if scheduler.is_over_disk_watermark(migrate.new_attached_node_id)
+                                            || !scheduler.node_satisfies(
+                                                migrate.new_attached_node_id,
+                                                shard.get_placement_constraints(),
+                                            )
+                                )
+                            })
+                    {
+                        work.push((shard.tenant_shard_id, optimization));
+                        break;
+                    } else if let Some(optimization) =
+                        // If idle, maybe optimize secondary locations: if a shard has a secondary location that would be
+                        // better placed on another node, based on ScheduleContext, then adjust it.  This
+                        // covers cases like after a shard split, where we might have too many shards
+                        // in the same tenant with secondary locations on the node where they originally split.
+                        shard.optimize_secondary(scheduler, &schedule_context)
+                    {
+                        work.push((shard.tenant_shard_id, optimization));
+                        break;
+                    }
+
+                    // TODO: extend this mechanism to prefer attaching on nodes with fewer attached
+                    // tenants (i.e. extend schedule state to distinguish attached from secondary counts),
+                    // for the total number of attachments on a node (not just within a tenant.)
+                }
+            }
+        }
+
+        work
+    }
+
+    async fn optimize_all_validate(
+        &self,
+        candidate_work: Vec<(TenantShardId, ScheduleOptimization)>,
+    ) -> Vec<(TenantShardId, ScheduleOptimization)> {
+        // Take a clone of the node map to use outside the lock in async validation phase
+        let validation_nodes = { self.inner.read().unwrap().nodes.clone() };
+
+        let mut want_secondary_status = Vec::new();
+
+        // Validate our plans: this is an async phase where we may do I/O to pageservers to
+        // check that the state of locations is acceptable to run the optimization, such as
+        // checking that a secondary location is sufficiently warmed-up to cleanly cut over
+        // in a live migration.
+        let mut validated_work = Vec::new();
+        for (tenant_shard_id, optimization) in candidate_work {
+            match optimization.action {
+                ScheduleOptimizationAction::MigrateAttachment(MigrateAttachment {
+                    old_attached_node_id: _,
+                    new_attached_node_id,
+                }) => {
+                    match validation_nodes.get(&new_attached_node_id) {
+                        None => {
+                            // Node was dropped between planning and validation
+                        }
+                        Some(node) => {
+                            if !node.is_available() {
+                                tracing::info!("Skipping optimization migration of {tenant_shard_id} to {new_attached_node_id} because node unavailable");
+                            } else {
+                                // Accumulate optimizations that require fetching secondary status, so that we can execute these
+                                // remote API requests concurrently.
+                                want_secondary_status.push((
+                                    tenant_shard_id,
+                                    node.clone(),
+                                    optimization,
+                                ));
+                            }
+                        }
+                    }
+                }
+                ScheduleOptimizationAction::ReplaceSecondary(_) => {
+                    // No extra checks needed to replace a secondary: this does not interrupt client access
+                    validated_work.push((tenant_shard_id, optimization))
+                }
+            };
+        }
+
+        // Call into pageserver API to find out if the destination secondary location is warm enough for a reasonably smooth migration: we
+        // do this so that we avoid spawning a Reconciler that would have to wait minutes/hours for a destination to warm up: that reconciler
+        // would hold a precious reconcile semaphore unit the whole time it was waiting for the destination to warm up.
+        let results = self
+            .tenant_for_shards_api(
+                want_secondary_status
+                    .iter()
+                    .map(|i| (i.0, i.1.clone()))
+                    .collect(),
+                |tenant_shard_id, client| async move {
+                    client.tenant_secondary_status(tenant_shard_id).await
+                },
+                1,
+                1,
+                SHORT_RECONCILE_TIMEOUT,
+                &self.cancel,
+            )
+            .await;
+
+        for ((tenant_shard_id, node, optimization), secondary_status) in
+            want_secondary_status.into_iter().zip(results.into_iter())
+        {
+            match secondary_status {
+                Err(e) => {
+                    tracing::info!("Skipping migration of {tenant_shard_id} to {node}, error querying secondary: {e}");
+                }
+                Ok(progress) => {
+                    // We require secondary locations to have less than 10GiB of downloads pending before we will use
+                    // them in an optimization
+                    const DOWNLOAD_FRESHNESS_THRESHOLD: u64 = 10 * 1024 * 1024 * 1024;
+
+                    if progress.heatmap_mtime.is_none()
+                        || progress.bytes_total < DOWNLOAD_FRESHNESS_THRESHOLD
+                            && progress.bytes_downloaded != progress.bytes_total
+                        || progress.bytes_total - progress.bytes_downloaded
+                            > DOWNLOAD_FRESHNESS_THRESHOLD
+                    {
+                        tracing::info!("Skipping migration of {tenant_shard_id} to {node} because secondary isn't ready: {progress:?}");
+                    } else {
+                        // Location looks ready: proceed
+                        tracing::info!(
+                            "{tenant_shard_id} secondary on {node} is warm enough for migration: {progress:?}"
+                        );
+                        validated_work.push((tenant_shard_id, optimization))
+                    }
+                }
+            }
+        }
+
+        validated_work
+    }
+
+    /// Look for shards which are oversized or write-heavy and in need of splitting
+    async fn autosplit_tenants(self: &Arc<Self>) {
+        let thresholds = autosplit::SplitThresholds::new(&self.config);
+        if !thresholds.is_enabled() {
+            // Auto-splitting is disabled
+            return;
+        }
+        let limits = autosplit::SplitLimits::new(&self.config);
+
+        let nodes = self.inner.read().unwrap().nodes.clone();
+
+        let mut top_n = Vec::new();
+
+        // Call into each node to look for big or busy tenants, by each metric that we split on.
+        for (order_by, threshold) in thresholds.sortings() {
+            let top_n_request = TopTenantShardsRequest {
+                order_by,
+                limit: self.config.split_candidates_per_node,
+                where_shards_lt: Some(ShardCount::new(limits.max_shard_count)),
+                where_gt: Some(threshold),
+            };
+            for node in nodes.values() {
+                let request_ref = &top_n_request;
+                match node
+                    .with_client_retries(
+                        |client| async move {
+                            let request = request_ref.clone();
+                            client.top_tenant_shards(request.clone()).await
+                        },
+                        &self.config.jwt_token,
+                        3,
+                        3,
+                        Duration::from_secs(5),
+                        &self.cancel,
+                    )
+                    .await
+                {
+                    Some(Ok(node_top_n)) => {
+                        top_n.extend(node_top_n.shards.into_iter());
+                    }
+                    Some(Err(mgmt_api::Error::Cancelled)) => {
+                        continue;
+                    }
+                    Some(Err(e)) => {
+                        tracing::warn!("Failed to fetch top N tenants from {node}: {e}");
+                        continue;
+                    }
+                    None => {
+                        // Node is shutting down
+                        continue;
+                    }
+                };
+            }
+        }
+
+        if top_n.is_empty() {
+            tracing::debug!("No split-elegible shards found");
+            return;
+        }
+
+        let mut tenant_states = HashMap::new();
+        {
+            let locked = self.inner.read().unwrap();
+            let in_flight = self.autosplit.lock().unwrap().in_flight.clone();
+            for tenant_id in top_n.iter().map(|i| i.id.tenant_id) {
+                if tenant_states.contains_key(&tenant_id) {
+                    continue;
+                }
+                let mut shards = locked
+                    .tenants
+                    .range(TenantShardId::tenant_range(tenant_id))
+                    .map(|(_, shard)| shard)
+                    .peekable();
+                let Some(first) = shards.peek() else {
+                    // Tenant was deleted
+                    continue;
+                };
+                let shard_count = first.shard.count;
+                let policy = *first.get_split_policy();
+
+                let mut splitting = in_flight.contains(&tenant_id);
+                let mut locations = Some(Vec::new());
+                for shard in shards {
+                    splitting |= !matches!(shard.splitting, SplitState::Idle);
+                    let location = shard
+                        .intent
+                        .get_attached()
+                        .as_ref()
+                        .and_then(|node_id| locked.nodes.get(node_id))
+                        .map(|node| (node.get_id(), node.get_availability_zone_id().clone()));
+                    match (location, locations.as_mut()) {
+                        (Some(location), Some(locations)) => locations.push(location),
+                        _ => locations = None,
+                    }
+                }
+
+                tenant_states.insert(
+                    tenant_id,
+                    autosplit::TenantSplitState {
+                        shard_count,
+                        policy,
+                        locations,
+                        splitting,
+                    },
+                );
+            }
+        }
+
+        let decisions = autosplit::plan_splits(top_n, &thresholds, &limits, &tenant_states);
+
+        let mut splits = Vec::new();
+        {
+            let mut autosplit = self.autosplit.lock().unwrap();
+            for decision in &decisions {
+                if let AutoSplitOutcome::Split {
+                    new_shard_count,
+                    new_stripe_size,
+                } = decision.outcome
+                {
+                    autosplit.in_flight.insert(decision.tenant_id);
+                    splits.push((
+                        decision.tenant_id,
+                        decision.triggers.clone(),
+                        TenantShardSplitRequest {
+                            new_shard_count,
+                            new_stripe_size,
+                        },
+                    ));
+                } else {
+                    tracing::debug!(
+                        tenant_id=%decision.tenant_id,
+                        "Not auto-splitting tenant: {:?}",
+                        decision.outcome
+                    );
+                }
+            }
+            autosplit.last = AutoSplitDecisionsResponse {
+                decided_at: Some(chrono::Utc::now()),
+                decisions,
+            };
+        }
+
+        for (tenant_id, triggers, split_req) in splits {
+            // We spawn a task to run this, so it's exactly like some external API client requesting it.  We don't
+            // want to block the background reconcile loop on this.
+            tracing::info!(
+                %tenant_id,
+                "Auto-splitting tenant to {} shards for {triggers:?}",
+                split_req.new_shard_count
+            );
+
+            let this = self.clone();
+            tokio::spawn(
+                async move {
+                    match this.tenant_shard_split(tenant_id, split_req).await {
+                        Ok(_) => {
+                            tracing::info!("Successful auto-split");
+                        }
+                        Err(e) => {
+                            tracing::error!("Auto-split failed: {e}");
+                        }
+                    }
+                    this.autosplit.lock().unwrap().in_flight.remove(&tenant_id);
+                }
+                .instrument(tracing::info_span!("auto_split", %tenant_id)),
+            );
+        }
+    }
+
+    /// Decisions of the most recent automatic split pass
+    pub(crate) fn autosplit_decisions(&self) -> AutoSplitDecisionsResponse {
+        let autosplit = self.autosplit.lock().unwrap();
+        AutoSplitDecisionsResponse {
+            decided_at: autosplit.last.decided_at,
+            decisions: autosplit.last.decisions.clone(),
+        }
+    }
+
+    /// Useful for tests: run whatever work a background [`Self::reconcile_all`] would have done, but
+    /// also wait for any generated Reconcilers to complete.  Calling this until it returns zero should
+    /// put the system into a quiescent state where future background reconciliations won't do anything.
+    pub(crate) async fn reconcile_all_now(&self) -> Result<usize, ReconcileWaitError> {
+        let reconciles_spawned = self.reconcile_all();
+        let reconciles_spawned = if reconciles_spawned == 0 {
+            // Only optimize when we are otherwise idle
+            self.optimize_all().await
+        } else {
+            reconciles_spawned
+        };
+
+        let waiters = {
+            let mut waiters = Vec::new();
+            let locked = self.inner.read().unwrap();
+            for (_tenant_shard_id, shard) in locked.tenants.iter() {
+                if let Some(waiter) = shard.get_waiter() {
+                    waiters.push(waiter);
+                }
+            }
+            waiters
+        };
+
+        let waiter_count = waiters.len();
+        match self.await_waiters(waiters, RECONCILE_TIMEOUT).await {
+            Ok(()) => {}
+            Err(ReconcileWaitError::Failed(_, reconcile_error))
+                if matches!(*reconcile_error, ReconcileError::Cancel) =>
+            {
+                // Ignore reconciler cancel errors: this reconciler might have shut down
+                // because some other change superceded it.  We will return a nonzero number,
+                // so the caller knows they might have to call again to quiesce the system.
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        };
+
+        tracing::info!(
+            "{} reconciles in reconcile_all, {} waiters",
+            reconciles_spawned,
+            waiter_count
+        );
+
+        Ok(std::cmp::max(waiter_count, reconciles_spawned))
+    }
+
+    async fn stop_reconciliations(&self, reason: StopReconciliationsReason) {
+        // Cancel all on-going reconciles and wait for them to exit the gate.
+        tracing::info!("{reason}: cancelling and waiting for in-flight reconciles");
+        self.reconcilers_cancel.cancel();
+        self.reconcilers_gate.close().await;
+
+        // Signal the background loop in [`Service::process_results`] to exit once
+        // it has proccessed the results from all the reconciles we cancelled earlier.
+        tracing::info!("{reason}: processing results from previously in-flight reconciles");
+        self.result_tx.send(ReconcileResultRequest::Stop).ok();
+        self.result_tx.closed().await;
+    }
+
+    pub async fn shutdown(&self) {
+        self.stop_reconciliations(StopReconciliationsReason::ShuttingDown)
+            .await;
 
-        let was_work_filtered = validated_work.len() != candidate_work_len;
+        // Background tasks hold gate guards: this notifies them of the cancellation and
+        // waits for them all to complete.
+        tracing::info!("Shutting down: cancelling and waiting for background tasks to exit");
+        self.cancel.cancel();
+        self.gate.close().await;
+    }
 
-        // Synchronous apply: update the shards' intent states according to validated optimisations
-        let mut reconciles_spawned = 0;
-        let mut optimizations_applied = 0;
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, scheduler) = locked.parts_mut();
-        for (tenant_shard_id, optimization) in validated_work {
-            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
-                // Shard was dropped between planning and execution;
-                continue;
-            };
-            if shard.apply_optimization(scheduler, optimization) {
-                optimizations_applied += 1;
-                if self.maybe_reconcile_shard(shard, nodes).is_some() {
-                    reconciles_spawned += 1;
+    /// Spot check the download lag for a secondary location of a shard.
+    /// Should be used as a heuristic, since it's not always precise: the
+    /// secondary might have not downloaded the new heat map yet and, hence,
+    /// is not aware of the lag.
+    ///
+    /// Returns:
+    /// * Ok(None) if the lag could not be determined from the status,
+    /// * Ok(Some(_)) if the lag could be determind
+    /// * Err on failures to query the pageserver.
+    async fn secondary_lag(
+        &self,
+        secondary: &NodeId,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<Option<u64>, mgmt_api::Error> {
+        let nodes = self.inner.read().unwrap().nodes.clone();
+        let node = nodes.get(secondary).ok_or(mgmt_api::Error::ApiError(
+            StatusCode::NOT_FOUND,
+            format!("Node with id {} not found", secondary),
+        ))?;
+
+        match node
+            .with_client_retries(
+                |client| async move { client.tenant_secondary_status(tenant_shard_id).await },
+                &self.config.jwt_token,
+                1,
+                3,
+                Duration::from_millis(250),
+                &self.cancel,
+            )
+            .await
+        {
+            Some(Ok(status)) => match status.heatmap_mtime {
+                Some(_) => Ok(Some(status.bytes_total - status.bytes_downloaded)),
+                None => Ok(None),
+            },
+            Some(Err(e)) => Err(e),
+            None => Err(mgmt_api::Error::Cancelled),
+        }
+    }
+
+    /// Drain a node by moving the shards attached to it as primaries.
+    /// This is a long running operation and it should run as a separate Tokio task.
+    ///
+    /// If the drain runs as its own operation, its progress is reported to `operation`.
+    pub(crate) async fn drain_node(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        cancel: CancellationToken,
+        operation: Option<&operations::OperationGuard>,
+    ) -> Result<(), OperationError> {
+        let max_secondary_lag_bytes = self
+            .config
+            .max_secondary_lag_bytes
+            .unwrap_or(MAX_SECONDARY_LAG_BYTES_DEFAULT);
+
+        // By default, live migrations are generous about the wait time for getting
+        // the secondary location up to speed. When draining, give up earlier in order
+        // to not stall the operation when a cold secondary is encountered.
+        const SECONDARY_WARMUP_TIMEOUT: Duration = Duration::from_secs(20);
+        const SECONDARY_DOWNLOAD_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+        let reconciler_config = ReconcilerConfigBuilder::new()
+            .secondary_warmup_timeout(SECONDARY_WARMUP_TIMEOUT)
+            .secondary_download_request_timeout(SECONDARY_DOWNLOAD_REQUEST_TIMEOUT)
+            .build();
+
+        let mut waiters = Vec::new();
+
+        let attached_at_start = self
+            .inner
+            .read()
+            .unwrap()
+            .scheduler
+            .get_node_attached_shard_count(node_id);
+        let report_progress = || {
+            if let Some(operation) = operation {
+                let attached = self
+                    .inner
+                    .read()
+                    .unwrap()
+                    .scheduler
+                    .get_node_attached_shard_count(node_id);
+                operation.set_progress(
+                    attached_at_start.saturating_sub(attached),
+                    attached_at_start,
+                );
+            }
+        };
+
+        let mut tid_iter = TenantShardIterator::new({
+            let service = self.clone();
+            move |last_inspected_shard: Option<TenantShardId>| {
+                let locked = &service.inner.read().unwrap();
+                let tenants = &locked.tenants;
+                let entry = match last_inspected_shard {
+                    Some(skip_past) => {
+                        // Skip to the last seen tenant shard id
+                        let mut cursor = tenants.iter().skip_while(|(tid, _)| **tid != skip_past);
+
+                        // Skip past the last seen
+                        cursor.nth(1)
+                    }
+                    None => tenants.first_key_value(),
+                };
+
+                entry.map(|(tid, _)| tid).copied()
+            }
+        });
+
+        while !tid_iter.finished() {
+            if cancel.is_cancelled() {
+                match self
+                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
+                    .await
+                {
+                    Ok(()) => return Err(OperationError::Cancelled),
+                    Err(err) => {
+                        return Err(OperationError::FinalizeError(
+                            format!(
+                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
+                                node_id, err
+                            )
+                            .into(),
+                        ));
+                    }
                 }
             }
 
-            if optimizations_applied >= MAX_OPTIMIZATIONS_EXEC_PER_PASS {
-                break;
+            drain_utils::validate_node_state(&node_id, self.inner.read().unwrap().nodes.clone())?;
+
+            while waiters.len() < MAX_RECONCILES_PER_OPERATION {
+                let tid = match tid_iter.next() {
+                    Some(tid) => tid,
+                    None => {
+                        break;
+                    }
+                };
+
+                let tid_drain = TenantShardDrain {
+                    drained_node: node_id,
+                    tenant_shard_id: tid,
+                };
+
+                let dest_node_id = {
+                    let locked = self.inner.read().unwrap();
+
+                    match tid_drain
+                        .tenant_shard_eligible_for_drain(&locked.tenants, &locked.scheduler)
+                    {
+                        Some(node_id) => node_id,
+                        None => {
+                            continue;
+                        }
+                    }
+                };
+
+                match self.secondary_lag(&dest_node_id, tid).await {
+                    Ok(Some(lag)) if lag <= max_secondary_lag_bytes => {
+                        // The secondary is reasonably up to date.
+                        // Migrate to it
+                    }
+                    Ok(Some(lag)) => {
+                        tracing::info!(
+                            tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                            "Secondary on node {dest_node_id} is lagging by {lag}. Skipping reconcile."
+                        );
+                        continue;
+                    }
+                    Ok(None) => {
+                        tracing::info!(
+                            tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                            "Could not determine lag for secondary on node {dest_node_id}. Skipping reconcile."
+                        );
+                        continue;
+                    }
+                    Err(err) => {
+                        tracing::warn!(
+                            tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                            "Failed to get secondary lag from node {dest_node_id}. Skipping reconcile: {err}"
+                        );
+                        continue;
+                    }
+                }
+
+                {
+                    let mut locked = self.inner.write().unwrap();
+                    let (nodes, tenants, scheduler) = locked.parts_mut();
+                    let rescheduled = tid_drain.reschedule_to_secondary(
+                        dest_node_id,
+                        tenants,
+                        scheduler,
+                        nodes,
+                    )?;
+
+                    if let Some(tenant_shard) = rescheduled {
+                        let waiter = self.maybe_configured_reconcile_shard(
+                            tenant_shard,
+                            nodes,
+                            reconciler_config,
+                        );
+                        if let Some(some) = waiter {
+                            waiters.push(some);
+                        }
+                    }
+                }
             }
+
+            waiters = self
+                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
+                .await;
+            report_progress();
+
+            failpoint_support::sleep_millis_async!("sleepy-drain-loop", &cancel);
         }
 
-        if was_work_filtered {
-            // If we filtered any work out during validation, ensure we return a nonzero value to indicate
-            // to callers that the system is not in a truly quiet state, it's going to do some work as soon
-            // as these validations start passing.
-            reconciles_spawned = std::cmp::max(reconciles_spawned, 1);
+        while !waiters.is_empty() {
+            if cancel.is_cancelled() {
+                match self
+                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
+                    .await
+                {
+                    Ok(()) => return Err(OperationError::Cancelled),
+                    Err(err) => {
+                        return Err(OperationError::FinalizeError(
+                            format!(
+                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
+                                node_id, err
+                            )
+                            .into(),
+                        ));
+                    }
+                }
+            }
+
+            tracing::info!("Awaiting {} pending drain reconciliations", waiters.len());
+
+            waiters = self
+                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
+                .await;
+            report_progress();
+        }
+
+        // At this point we have done the best we could to drain shards from this node.
+        // Set the node scheduling policy to `[NodeSchedulingPolicy::PauseForRestart]`
+        // to complete the drain.
+        if let Err(err) = self
+            .node_configure(node_id, None, Some(NodeSchedulingPolicy::PauseForRestart))
+            .await
+        {
+            // This is not fatal. Anything that is polling the node scheduling policy to detect
+            // the end of the drain operations will hang, but all such places should enforce an
+            // overall timeout. The scheduling policy will be updated upon node re-attach and/or
+            // by the counterpart fill operation.
+            return Err(OperationError::FinalizeError(
+                format!(
+                    "Failed to finalise drain of {node_id} by setting scheduling policy to PauseForRestart: {err}"
+                )
+                .into(),
+            ));
         }
 
-        reconciles_spawned
+        Ok(())
     }
 
-    fn optimize_all_plan(&self) -> Vec<(TenantShardId, ScheduleOptimization)> {
-        let mut schedule_context = ScheduleContext::default();
-
-        let mut tenant_shards: Vec<&TenantShard> = Vec::new();
+    /// Create a node fill plan (pick secondaries to promote) that meets the following requirements:
+    /// 1. The node should be filled until it reaches the expected cluster average of
+    ///    attached shards. If there are not enough secondaries on the node, the plan stops early.
+    /// 2. Select tenant shards to promote such that the number of attached shards is balanced
+    ///    throughout the cluster. We achieve this by picking tenant shards from each node,
+    ///    starting from the ones with the largest number of attached shards, until the node
+    ///    reaches the expected cluster average.
+    /// 3. Avoid promoting more shards of the same tenant than required. The upper bound
+    ///    for the number of tenants from the same shard promoted to the node being filled is:
+    ///    shard count for the tenant divided by the number of nodes in the cluster.
+    fn fill_node_plan(&self, node_id: NodeId) -> Vec<TenantShardId> {
+        let mut locked = self.inner.write().unwrap();
+        let fill_requirement = locked.scheduler.compute_fill_requirement(node_id);
 
-        // How many candidate optimizations we will generate, before evaluating them for readniess: setting
-        // this higher than the execution limit gives us a chance to execute some work even if the first
-        // few optimizations we find are not ready.
-        const MAX_OPTIMIZATIONS_PLAN_PER_PASS: usize = 8;
+        let mut tids_by_node = locked
+            .tenants
+            .iter_mut()
+            .filter_map(|(tid, tenant_shard)| {
+                if tenant_shard.intent.get_secondary().contains(&node_id) {
+                    if let Some(primary) = tenant_shard.intent.get_attached() {
+                        return Some((*primary, *tid));
+                    }
+                }
 
-        let mut work = Vec::new();
+                None
+            })
+            .into_group_map();
 
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, scheduler) = locked.parts_mut();
-        for (tenant_shard_id, shard) in tenants.iter() {
-            if tenant_shard_id.is_shard_zero() {
-                // Reset accumulators on the first shard in a tenant
-                schedule_context = ScheduleContext::default();
-                schedule_context.mode = ScheduleMode::Speculative;
-                tenant_shards.clear();
-            }
+        let expected_attached = locked.scheduler.expected_attached_shard_count();
+        let nodes_by_load = locked.scheduler.nodes_by_attached_shard_count();
 
-            if work.len() >= MAX_OPTIMIZATIONS_PLAN_PER_PASS {
-                break;
-            }
+        let mut promoted_per_tenant: HashMap<TenantId, usize> = HashMap::new();
+        let mut plan = Vec::new();
 
-            match shard.get_scheduling_policy() {
-                ShardSchedulingPolicy::Active => {
-                    // Ok to do optimization
-                }
-                ShardSchedulingPolicy::Essential
-                | ShardSchedulingPolicy::Pause
-                | ShardSchedulingPolicy::Stop => {
-                    // Policy prevents optimizing this shard.
-                    continue;
-                }
+        for (node_id, attached) in nodes_by_load {
+            let available = locked
+                .nodes
+                .get(&node_id)
+                .map_or(false, |n| n.is_available());
+            if !available {
+                continue;
             }
 
-            // Accumulate the schedule context for all the shards in a tenant: we must have
-            // the total view of all shards before we can try to optimize any of them.
-            schedule_context.avoid(&shard.intent.all_pageservers());
-            if let Some(attached) = shard.intent.get_attached() {
-                schedule_context.push_attached(*attached);
+            if plan.len() >= fill_requirement
+                || tids_by_node.is_empty()
+                || attached <= expected_attached
+            {
+                break;
             }
-            tenant_shards.push(shard);
-
-            // Once we have seen the last shard in the tenant, proceed to search across all shards
-            // in the tenant for optimizations
-            if shard.shard.number.0 == shard.shard.count.count() - 1 {
-                if tenant_shards.iter().any(|s| s.reconciler.is_some()) {
-                    // Do not start any optimizations while another change to the tenant is ongoing: this
-                    // is not necessary for correctness, but simplifies operations and implicitly throttles
-                    // optimization changes to happen in a "trickle" over time.
-                    continue;
-                }
-
-                if tenant_shards.iter().any(|s| {
-                    !matches!(s.splitting, SplitState::Idle)
-                        || matches!(s.policy, PlacementPolicy::Detached)
-                }) {
-                    // Never attempt to optimize a tenant that is currently being split, or
-                    // a tenant that is meant to be detached
-                    continue;
-                }
 
-                // TODO: optimization calculations are relatively expensive: create some fast-path for
-                // the common idle case (avoiding the search on tenants that we have recently checked)
+            let can_take = attached - expected_attached;
+            let needed = fill_requirement - plan.len();
+            let mut take = std::cmp::min(can_take, needed);
 
-                for shard in &tenant_shards {
-                    if let Some(optimization) =
-                        // If idle, maybe ptimize attachments: if a shard has a secondary location that is preferable to
-                        // its primary location based on soft constraints, cut it over.
-                        shard.optimize_attachment(nodes, &schedule_context)
-                    {
-                        work.push((shard.tenant_shard_id, optimization));
-                        break;
-                    } else if let Some(optimization) =
-                        // If idle, maybe optimize secondary locations: if a shard has a secondary location that would be
-                        // better placed on another node, based on ScheduleContext, then adjust it.  This
-                        // covers cases like after a shard split, where we might have too many shards
-                        // in the same tenant with secondary locations on the node where they originally split.
-                        shard.optimize_secondary(scheduler, &schedule_context)
-                    {
-                        work.push((shard.tenant_shard_id, optimization));
+            let mut remove_node = false;
+            while take > 0 {
+                match tids_by_node.get_mut(&node_id) {
+                    Some(tids) => match tids.pop() {
+                        Some(tid) => {
+                            let max_promote_for_tenant = std::cmp::max(
+                                tid.shard_count.count() as usize / locked.nodes.len(),
+                                1,
+                            );
+                            let promoted = promoted_per_tenant.entry(tid.tenant_id).or_default();
+                            if *promoted < max_promote_for_tenant {
+                                plan.push(tid);
+                                *promoted += 1;
+                                take -= 1;
+                            }
+                        }
+                        None => {
+                            remove_node = true;
+                            break;
+                        }
+                    },
+                    None => {
                         break;
                     }
-
-                    // TODO: extend this mechanism to prefer attaching on nodes with fewer attached
-                    // tenants (i.e. extend schedule state to distinguish attached from secondary counts),
-                    // for the total number of attachments on a node (not just within a tenant.)
                 }
             }
+
+            if remove_node {
+                tids_by_node.remove(&node_id);
+            }
         }
 
-        work
+        plan
     }
 
-    async fn optimize_all_validate(
+    /// Plan the moves of [`Self::drain_node`] without making them.  Shards which the drain
+    /// would skip because their secondaries are cold are listed as skipped.
+    pub(crate) async fn plan_node_drain(
         &self,
-        candidate_work: Vec<(TenantShardId, ScheduleOptimization)>,
-    ) -> Vec<(TenantShardId, ScheduleOptimization)> {
-        // Take a clone of the node map to use outside the lock in async validation phase
-        let validation_nodes = { self.inner.read().unwrap().nodes.clone() };
-
-        let mut want_secondary_status = Vec::new();
-
-        // Validate our plans: this is an async phase where we may do I/O to pageservers to
-        // check that the state of locations is acceptable to run the optimization, such as
-        // checking that a secondary location is sufficiently warmed-up to cleanly cut over
-        // in a live migration.
-        let mut validated_work = Vec::new();
-        for (tenant_shard_id, optimization) in candidate_work {
-            match optimization.action {
-                ScheduleOptimizationAction::MigrateAttachment(MigrateAttachment {
-                    old_attached_node_id: _,
-                    new_attached_node_id,
-                }) => {
-                    match validation_nodes.get(&new_attached_node_id) {
-                        None => {
-                            // Node was dropped between planning and validation
-                        }
-                        Some(node) => {
-                            if !node.is_available() {
-                                tracing::info!("Skipping optimization migration of {tenant_shard_id} to {new_attached_node_id} because node unavailable");
-                            } else {
-                                // Accumulate optimizations that require fetching secondary status, so that we can execute these
-                                // remote API requests concurrently.
-                                want_secondary_status.push((
-                                    tenant_shard_id,
-                                    node.clone(),
-                                    optimization,
-                                ));
-                            }
-                        }
-                    }
-                }
-                ScheduleOptimizationAction::ReplaceSecondary(_) => {
-                    // No extra checks needed to replace a secondary: this does not interrupt client access
-                    validated_work.push((tenant_shard_id, optimization))
-                }
-            };
-        }
+        node_id: NodeId,
+        download_bandwidth: Option<u64>,
+    ) -> Result<MigrationPlan, ApiError> {
+        let max_secondary_lag_bytes = self
+            .config
+            .max_secondary_lag_bytes
+            .unwrap_or(MAX_SECONDARY_LAG_BYTES_DEFAULT);
 
-        // Call into pageserver API to find out if the destination secondary location is warm enough for a reasonably smooth migration: we
-        // do this so that we avoid spawning a Reconciler that would have to wait minutes/hours for a destination to warm up: that reconciler
-        // would hold a precious reconcile semaphore unit the whole time it was waiting for the destination to warm up.
-        let results = self
-            .tenant_for_shards_api(
-                want_secondary_status
-                    .iter()
-                    .map(|i| (i.0, i.1.clone()))
-                    .collect(),
-                |tenant_shard_id, client| async move {
-                    client.tenant_secondary_status(tenant_shard_id).await
-                },
-                1,
-                1,
-                SHORT_RECONCILE_TIMEOUT,
-                &self.cancel,
-            )
-            .await;
+        let mut skipped = Vec::new();
+        let candidates = {
+            let locked = self.inner.read().unwrap();
+            if !locked.nodes.contains_key(&node_id) {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Node {node_id} not registered").into(),
+                ));
+            }
 
-        for ((tenant_shard_id, node, optimization), secondary_status) in
-            want_secondary_status.into_iter().zip(results.into_iter())
-        {
-            match secondary_status {
-                Err(e) => {
-                    tracing::info!("Skipping migration of {tenant_shard_id} to {node}, error querying secondary: {e}");
+            let mut candidates = Vec::new();
+            for (tid, shard) in locked.tenants.iter() {
+                if *shard.intent.get_attached() != Some(node_id) {
+                    continue;
                 }
-                Ok(progress) => {
-                    // We require secondary locations to have less than 10GiB of downloads pending before we will use
-                    // them in an optimization
-                    const DOWNLOAD_FRESHNESS_THRESHOLD: u64 = 10 * 1024 * 1024 * 1024;
-
-                    if progress.heatmap_mtime.is_none()
-                        || progress.bytes_total < DOWNLOAD_FRESHNESS_THRESHOLD
-                            && progress.bytes_downloaded != progress.bytes_total
-                        || progress.bytes_total - progress.bytes_downloaded
-                            > DOWNLOAD_FRESHNESS_THRESHOLD
-                    {
-                        tracing::info!("Skipping migration of {tenant_shard_id} to {node} because secondary isn't ready: {progress:?}");
-                    } else {
-                        // Location looks ready: proceed
-                        tracing::info!(
-                            "{tenant_shard_id} secondary on {node} is warm enough for migration: {progress:?}"
-                        );
-                        validated_work.push((tenant_shard_id, optimization))
-                    }
+                let tid_drain = TenantShardDrain {
+                    drained_node: node_id,
+                    tenant_shard_id: *tid,
+                };
+                match tid_drain.tenant_shard_eligible_for_drain(&locked.tenants, &locked.scheduler)
+                {
+                    Some(dest_node_id) => candidates.push((*tid, dest_node_id)),
+                    None => skipped.push(SkippedShardMove {
+                        tenant_shard_id: *tid,
+                        reason: "No eligible secondary".to_string(),
+                    }),
                 }
             }
+            candidates
+        };
+
+        let lags = futures::stream::iter(candidates)
+            .map(|(tid, dest_node_id)| async move {
+                (
+                    tid,
+                    dest_node_id,
+                    self.secondary_lag(&dest_node_id, tid).await,
+                )
+            })
+            .buffered(migration_plan::PLAN_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut moves = Vec::new();
+        for (tid, dest_node_id, lag) in lags {
+            let reason = match lag {
+                Ok(Some(lag)) if lag <= max_secondary_lag_bytes => {
+                    moves.push(PlannedShardMove {
+                        tenant_shard_id: tid,
+                        from: node_id,
+                        to: dest_node_id,
+                        to_secondary: true,
+                        resident_size: None,
+                        physical_size: None,
+                        download_size: Some(lag),
+                    });
+                    continue;
+                }
+                Ok(Some(lag)) => format!("Secondary on node {dest_node_id} is lagging by {lag}"),
+                Ok(None) => format!("Could not determine lag for secondary on node {dest_node_id}"),
+                Err(err) => format!("Failed to get secondary lag from node {dest_node_id}: {err}"),
+            };
+            skipped.push(SkippedShardMove {
+                tenant_shard_id: tid,
+                reason,
+            });
         }
 
-        validated_work
+        self.migration_plan_sizes(&mut moves).await;
+        Ok(migration_plan::summarize(
+            moves,
+            skipped,
+            download_bandwidth,
+        ))
     }
 
-    /// Look for shards which are oversized and in need of splitting
-    async fn autosplit_tenants(self: &Arc<Self>) {
-        let Some(split_threshold) = self.config.split_threshold else {
-            // Auto-splitting is disabled
-            return;
+    /// Plan the promotions of [`Self::fill_node`] without making them
+    pub(crate) async fn plan_node_fill(
+        &self,
+        node_id: NodeId,
+        download_bandwidth: Option<u64>,
+    ) -> Result<MigrationPlan, ApiError> {
+        if !self.inner.read().unwrap().nodes.contains_key(&node_id) {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Node {node_id} not registered").into(),
+            ));
+        }
+
+        let tids_to_promote = self.fill_node_plan(node_id);
+        let mut moves = {
+            let locked = self.inner.read().unwrap();
+            tids_to_promote
+                .into_iter()
+                .filter_map(|tid| {
+                    let from = (*locked.tenants.get(&tid)?.intent.get_attached())?;
+                    Some(PlannedShardMove {
+                        tenant_shard_id: tid,
+                        from,
+                        to: node_id,
+                        to_secondary: true,
+                        resident_size: None,
+                        physical_size: None,
+                        download_size: None,
+                    })
+                })
+                .collect::<Vec<_>>()
         };
 
-        let nodes = self.inner.read().unwrap().nodes.clone();
+        self.migration_plan_sizes(&mut moves).await;
+        Ok(migration_plan::summarize(
+            moves,
+            Vec::new(),
+            download_bandwidth,
+        ))
+    }
 
-        const SPLIT_TO_MAX: ShardCount = ShardCount::new(8);
+    /// Plan moving the attached shards away from some nodes, spreading them round robin over
+    /// the other active nodes which satisfy their placement constraints, for
+    /// `storcon_cli bulk-migrate` to execute.
+    pub(crate) async fn plan_bulk_migrate(
+        &self,
+        request: BulkMigratePlanRequest,
+        download_bandwidth: Option<u64>,
+    ) -> Result<MigrationPlan, ApiError> {
+        let (mut moves, skipped) = {
+            let locked = self.inner.read().unwrap();
+            if let Some(node_id) = request
+                .nodes
+                .iter()
+                .find(|node_id| !locked.nodes.contains_key(node_id))
+            {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Node {node_id} not registered").into(),
+                ));
+            }
 
-        let mut top_n = Vec::new();
+            let destinations = locked
+                .nodes
+                .values()
+                .filter(|node| {
+                    !request.nodes.contains(&node.get_id())
+                        && node.is_available()
+                        && matches!(
+                            node.get_scheduling(),
+                            NodeSchedulingPolicy::Active | NodeSchedulingPolicy::Filling
+                        )
+                })
+                .map(|node| node.get_id())
+                .sorted()
+                .collect::<Vec<_>>();
+            if destinations.is_empty() {
+                return Err(ApiError::PreconditionFailed(
+                    "There are no nodes to migrate to".into(),
+                ));
+            }
+
+            let mut moves = Vec::new();
+            let mut skipped = Vec::new();
+            for (tid, shard) in locked.tenants.iter() {
+                if request.max_shards.is_some_and(|max| moves.len() >= max) {
+                    break;
+                }
+                let Some(from) = *shard.intent.get_attached() else {
+                    continue;
+                };
+                if !request.nodes.contains(&from) {
+                    continue;
+                }
+
+                let candidates = destinations
+                    .iter()
+                    .filter(|node_id| {
+                        locked
+                            .scheduler
+                            .node_satisfies(**node_id, shard.get_placement_constraints())
+                    })
+                    .collect::<Vec<_>>();
+                if candidates.is_empty() {
+                    skipped.push(SkippedShardMove {
+                        tenant_shard_id: *tid,
+                        reason: "No node to migrate to satisfies placement constraints".to_string(),
+                    });
+                    continue;
+                }
 
-        // Call into each node to look for big tenants
-        let top_n_request = TopTenantShardsRequest {
-            // We currently split based on logical size, for simplicity: logical size is a signal of
-            // the user's intent to run a large database, whereas physical/resident size can be symptoms
-            // of compaction issues.  Eventually we should switch to using resident size to bound the
-            // disk space impact of one shard.
-            order_by: models::TenantSorting::MaxLogicalSize,
-            limit: 10,
-            where_shards_lt: Some(SPLIT_TO_MAX),
-            where_gt: Some(split_threshold),
+                let to = *candidates[moves.len() % candidates.len()];
+                moves.push(PlannedShardMove {
+                    tenant_shard_id: *tid,
+                    from,
+                    to,
+                    to_secondary: shard.intent.get_secondary().contains(&to),
+                    resident_size: None,
+                    physical_size: None,
+                    download_size: None,
+                });
+            }
+            (moves, skipped)
         };
-        for node in nodes.values() {
-            let request_ref = &top_n_request;
+
+        if request.sizes {
+            self.migration_plan_sizes(&mut moves).await;
+        }
+        Ok(migration_plan::summarize(
+            moves,
+            skipped,
+            download_bandwidth,
+        ))
+    }
+
+    /// Fill in the sizes of planned moves.  Resident and remote sizes come from the attached
+    /// locations.  A destination with a secondary location has to download what its secondary
+    /// lacks, and any other destination the whole resident size.  Sizes which the pageservers
+    /// fail to report are left unknown.
+    async fn migration_plan_sizes(&self, moves: &mut [PlannedShardMove]) {
+        let (nodes, shard_count) = {
+            let locked = self.inner.read().unwrap();
+            (locked.nodes.clone(), locked.tenants.len())
+        };
+
+        let mut sizes = HashMap::new();
+        for from in moves.iter().map(|mv| mv.from).unique() {
+            let Some(node) = nodes.get(&from) else {
+                continue;
+            };
+            let request = TopTenantShardsRequest {
+                order_by: TenantSorting::ResidentSize,
+                limit: shard_count,
+                where_shards_lt: None,
+                where_gt: None,
+            };
+            let request_ref = &request;
             match node
                 .with_client_retries(
-                    |client| async move {
-                        let request = request_ref.clone();
-                        client.top_tenant_shards(request.clone()).await
-                    },
+                    |client| async move { client.top_tenant_shards(request_ref.clone()).await },
                     &self.config.jwt_token,
                     3,
                     3,
@@ -6280,191 +9083,66 @@ async fn autosplit_tenants(self: &Arc<Self>) {
                 )
                 .await
             {
-                Some(Ok(node_top_n)) => {
-                    top_n.extend(node_top_n.shards.into_iter());
-                }
-                Some(Err(mgmt_api::Error::Cancelled)) => {
-                    continue;
+                Some(Ok(response)) => {
+                    sizes.extend(response.shards.into_iter().map(|item| (item.id, item)));
                 }
                 Some(Err(e)) => {
-                    tracing::warn!("Failed to fetch top N tenants from {node}: {e}");
-                    continue;
+                    tracing::warn!("Failed to fetch shard sizes from {node}: {e}");
                 }
                 None => {
                     // Node is shutting down
-                    continue;
                 }
-            };
+            }
         }
 
-        // Pick the biggest tenant to split first
-        top_n.sort_by_key(|i| i.resident_size);
-        let Some(split_candidate) = top_n.into_iter().next() else {
-            tracing::debug!("No split-elegible shards found");
-            return;
-        };
-
-        // We spawn a task to run this, so it's exactly like some external API client requesting it.  We don't
-        // want to block the background reconcile loop on this.
-        tracing::info!("Auto-splitting tenant for size threshold {split_threshold}: current size {split_candidate:?}");
-
-        let this = self.clone();
-        tokio::spawn(
-            async move {
-                match this
-                    .tenant_shard_split(
-                        split_candidate.id.tenant_id,
-                        TenantShardSplitRequest {
-                            // Always split to the max number of shards: this avoids stepping through
-                            // intervening shard counts and encountering the overrhead of a split+cleanup
-                            // each time as a tenant grows, and is not too expensive because our max shard
-                            // count is relatively low anyway.
-                            // This policy will be adjusted in future once we support higher shard count.
-                            new_shard_count: SPLIT_TO_MAX.literal(),
-                            new_stripe_size: Some(ShardParameters::DEFAULT_STRIPE_SIZE),
-                        },
-                    )
-                    .await
-                {
-                    Ok(_) => {
-                        tracing::info!("Successful auto-split");
-                    }
-                    Err(e) => {
-                        tracing::error!("Auto-split failed: {e}");
-                    }
-                }
+        for mv in moves.iter_mut() {
+            if let Some(item) = sizes.get(&mv.tenant_shard_id) {
+                mv.resident_size = Some(item.resident_size);
+                mv.physical_size = Some(item.physical_size);
             }
-            .instrument(tracing::info_span!("auto_split", tenant_id=%split_candidate.id.tenant_id)),
-        );
-    }
-
-    /// Useful for tests: run whatever work a background [`Self::reconcile_all`] would have done, but
-    /// also wait for any generated Reconcilers to complete.  Calling this until it returns zero should
-    /// put the system into a quiescent state where future background reconciliations won't do anything.
-    pub(crate) async fn reconcile_all_now(&self) -> Result<usize, ReconcileWaitError> {
-        let reconciles_spawned = self.reconcile_all();
-        let reconciles_spawned = if reconciles_spawned == 0 {
-            // Only optimize when we are otherwise idle
-            self.optimize_all().await
-        } else {
-            reconciles_spawned
-        };
-
-        let waiters = {
-            let mut waiters = Vec::new();
-            let locked = self.inner.read().unwrap();
-            for (_tenant_shard_id, shard) in locked.tenants.iter() {
-                if let Some(waiter) = shard.get_waiter() {
-                    waiters.push(waiter);
-                }
+            if !mv.to_secondary {
+                mv.download_size = mv.resident_size;
             }
-            waiters
-        };
+        }
 
-        let waiter_count = waiters.len();
-        match self.await_waiters(waiters, RECONCILE_TIMEOUT).await {
-            Ok(()) => {}
-            Err(ReconcileWaitError::Failed(_, reconcile_error))
-                if matches!(*reconcile_error, ReconcileError::Cancel) =>
-            {
-                // Ignore reconciler cancel errors: this reconciler might have shut down
-                // because some other change superceded it.  We will return a nonzero number,
-                // so the caller knows they might have to call again to quiesce the system.
-            }
-            Err(e) => {
-                return Err(e);
+        let lags = futures::stream::iter(
+            moves
+                .iter()
+                .enumerate()
+                .filter(|(_, mv)| mv.to_secondary && mv.download_size.is_none())
+                .map(|(i, mv)| (i, mv.to, mv.tenant_shard_id))
+                .collect::<Vec<_>>(),
+        )
+        .map(|(i, to, tid)| async move { (i, self.secondary_lag(&to, tid).await) })
+        .buffer_unordered(migration_plan::PLAN_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+        for (i, lag) in lags {
+            match lag {
+                Ok(lag) => moves[i].download_size = lag,
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to get secondary lag of {} from node {}: {e}",
+                        moves[i].tenant_shard_id,
+                        moves[i].to
+                    );
+                }
             }
-        };
-
-        tracing::info!(
-            "{} reconciles in reconcile_all, {} waiters",
-            reconciles_spawned,
-            waiter_count
-        );
-
-        Ok(std::cmp::max(waiter_count, reconciles_spawned))
-    }
-
-    async fn stop_reconciliations(&self, reason: StopReconciliationsReason) {
-        // Cancel all on-going reconciles and wait for them to exit the gate.
-        tracing::info!("{reason}: cancelling and waiting for in-flight reconciles");
-        self.reconcilers_cancel.cancel();
-        self.reconcilers_gate.close().await;
-
-        // Signal the background loop in [`Service::process_results`] to exit once
-        // it has proccessed the results from all the reconciles we cancelled earlier.
-        tracing::info!("{reason}: processing results from previously in-flight reconciles");
-        self.result_tx.send(ReconcileResultRequest::Stop).ok();
-        self.result_tx.closed().await;
-    }
-
-    pub async fn shutdown(&self) {
-        self.stop_reconciliations(StopReconciliationsReason::ShuttingDown)
-            .await;
-
-        // Background tasks hold gate guards: this notifies them of the cancellation and
-        // waits for them all to complete.
-        tracing::info!("Shutting down: cancelling and waiting for background tasks to exit");
-        self.cancel.cancel();
-        self.gate.close().await;
-    }
-
-    /// Spot check the download lag for a secondary location of a shard.
-    /// Should be used as a heuristic, since it's not always precise: the
-    /// secondary might have not downloaded the new heat map yet and, hence,
-    /// is not aware of the lag.
-    ///
-    /// Returns:
-    /// * Ok(None) if the lag could not be determined from the status,
-    /// * Ok(Some(_)) if the lag could be determind
-    /// * Err on failures to query the pageserver.
-    async fn secondary_lag(
-        &self,
-        secondary: &NodeId,
-        tenant_shard_id: TenantShardId,
-    ) -> Result<Option<u64>, mgmt_api::Error> {
-        let nodes = self.inner.read().unwrap().nodes.clone();
-        let node = nodes.get(secondary).ok_or(mgmt_api::Error::ApiError(
-            StatusCode::NOT_FOUND,
-            format!("Node with id {} not found", secondary),
-        ))?;
-
-        match node
-            .with_client_retries(
-                |client| async move { client.tenant_secondary_status(tenant_shard_id).await },
-                &self.config.jwt_token,
-                1,
-                3,
-                Duration::from_millis(250),
-                &self.cancel,
-            )
-            .await
-        {
-            Some(Ok(status)) => match status.heatmap_mtime {
-                Some(_) => Ok(Some(status.bytes_total - status.bytes_downloaded)),
-                None => Ok(None),
-            },
-            Some(Err(e)) => Err(e),
-            None => Err(mgmt_api::Error::Cancelled),
         }
     }
 
-    /// Drain a node by moving the shards attached to it as primaries.
+    /// Fill a node by promoting its secondaries until the cluster is balanced
+    /// with regards to attached shard counts. Note that this operation only
+    /// makes sense as a counterpart to the drain implemented in [`Service::drain_node`].
     /// This is a long running operation and it should run as a separate Tokio task.
-    pub(crate) async fn drain_node(
-        self: &Arc<Self>,
+    ///
+    /// If the fill runs as its own operation, its progress is reported to `operation`.
+    pub(crate) async fn fill_node(
+        &self,
         node_id: NodeId,
         cancel: CancellationToken,
+        operation: Option<&operations::OperationGuard>,
     ) -> Result<(), OperationError> {
-        const MAX_SECONDARY_LAG_BYTES_DEFAULT: u64 = 256 * 1024 * 1024;
-        let max_secondary_lag_bytes = self
-            .config
-            .max_secondary_lag_bytes
-            .unwrap_or(MAX_SECONDARY_LAG_BYTES_DEFAULT);
-
-        // By default, live migrations are generous about the wait time for getting
-        // the secondary location up to speed. When draining, give up earlier in order
-        // to not stall the operation when a cold secondary is encountered.
         const SECONDARY_WARMUP_TIMEOUT: Duration = Duration::from_secs(20);
         const SECONDARY_DOWNLOAD_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
         let reconciler_config = ReconcilerConfigBuilder::new()
@@ -6472,29 +9150,19 @@ pub(crate) async fn drain_node(
             .secondary_download_request_timeout(SECONDARY_DOWNLOAD_REQUEST_TIMEOUT)
             .build();
 
+        let mut tids_to_promote = self.fill_node_plan(node_id);
         let mut waiters = Vec::new();
 
-        let mut tid_iter = TenantShardIterator::new({
-            let service = self.clone();
-            move |last_inspected_shard: Option<TenantShardId>| {
-                let locked = &service.inner.read().unwrap();
-                let tenants = &locked.tenants;
-                let entry = match last_inspected_shard {
-                    Some(skip_past) => {
-                        // Skip to the last seen tenant shard id
-                        let mut cursor = tenants.iter().skip_while(|(tid, _)| **tid != skip_past);
-
-                        // Skip past the last seen
-                        cursor.nth(1)
-                    }
-                    None => tenants.first_key_value(),
-                };
-
-                entry.map(|(tid, _)| tid).copied()
+        let planned = tids_to_promote.len();
+        let report_progress = |remaining: usize| {
+            if let Some(operation) = operation {
+                operation.set_progress(planned.saturating_sub(remaining), planned);
             }
-        });
+        };
 
-        while !tid_iter.finished() {
+        // Execute the plan we've composed above. Before aplying each move from the plan,
+        // we validate to ensure that it has not gone stale in the meantime.
+        while !tids_to_promote.is_empty() {
             if cancel.is_cancelled() {
                 match self
                     .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
@@ -6513,132 +9181,310 @@ pub(crate) async fn drain_node(
                 }
             }
 
-            drain_utils::validate_node_state(&node_id, self.inner.read().unwrap().nodes.clone())?;
+            {
+                let mut locked = self.inner.write().unwrap();
+                let (nodes, tenants, scheduler) = locked.parts_mut();
 
-            while waiters.len() < MAX_RECONCILES_PER_OPERATION {
-                let tid = match tid_iter.next() {
-                    Some(tid) => tid,
-                    None => {
-                        break;
-                    }
-                };
+                let node = nodes.get(&node_id).ok_or(OperationError::NodeStateChanged(
+                    format!("node {node_id} was removed").into(),
+                ))?;
 
-                let tid_drain = TenantShardDrain {
-                    drained_node: node_id,
-                    tenant_shard_id: tid,
-                };
+                let current_policy = node.get_scheduling();
+                if !matches!(current_policy, NodeSchedulingPolicy::Filling) {
+                    // TODO(vlad): maybe cancel pending reconciles before erroring out. need to think
+                    // about it
+                    return Err(OperationError::NodeStateChanged(
+                        format!("node {node_id} changed state to {current_policy:?}").into(),
+                    ));
+                }
 
-                let dest_node_id = {
-                    let locked = self.inner.read().unwrap();
+                while waiters.len() < MAX_RECONCILES_PER_OPERATION {
+                    if let Some(tid) = tids_to_promote.pop() {
+                        if let Some(tenant_shard) = tenants.get_mut(&tid) {
+                            // If the node being filled is not a secondary anymore,
+                            // skip the promotion.
+                            if !tenant_shard.intent.get_secondary().contains(&node_id) {
+                                continue;
+                            }
 
-                    match tid_drain
-                        .tenant_shard_eligible_for_drain(&locked.tenants, &locked.scheduler)
-                    {
-                        Some(node_id) => node_id,
-                        None => {
-                            continue;
-                        }
-                    }
-                };
+                            let previously_attached_to = *tenant_shard.intent.get_attached();
+                            match tenant_shard.reschedule_to_secondary(Some(node_id), scheduler) {
+                                Err(e) => {
+                                    tracing::warn!(
+                                        tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                                        "Scheduling error when filling pageserver {} : {e}", node_id
+                                    );
+                                }
+                                Ok(()) => {
+                                    tracing::info!(
+                                        tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                                        "Rescheduled shard while filling node {}: {:?} -> {}",
+                                        node_id,
+                                        previously_attached_to,
+                                        node_id
+                                    );
 
-                match self.secondary_lag(&dest_node_id, tid).await {
-                    Ok(Some(lag)) if lag <= max_secondary_lag_bytes => {
-                        // The secondary is reasonably up to date.
-                        // Migrate to it
-                    }
-                    Ok(Some(lag)) => {
-                        tracing::info!(
-                            tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
-                            "Secondary on node {dest_node_id} is lagging by {lag}. Skipping reconcile."
-                        );
-                        continue;
-                    }
-                    Ok(None) => {
-                        tracing::info!(
-                            tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
-                            "Could not determine lag for secondary on node {dest_node_id}. Skipping reconcile."
-                        );
-                        continue;
-                    }
-                    Err(err) => {
-                        tracing::warn!(
-                            tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
-                            "Failed to get secondary lag from node {dest_node_id}. Skipping reconcile: {err}"
-                        );
-                        continue;
+                                    if let Some(waiter) = self.maybe_configured_reconcile_shard(
+                                        tenant_shard,
+                                        nodes,
+                                        reconciler_config,
+                                    ) {
+                                        waiters.push(waiter);
+                                    }
+                                }
+                            }
+                        }
+                    } else {
+                        break;
                     }
                 }
+            }
 
-                {
-                    let mut locked = self.inner.write().unwrap();
-                    let (nodes, tenants, scheduler) = locked.parts_mut();
-                    let rescheduled = tid_drain.reschedule_to_secondary(
-                        dest_node_id,
-                        tenants,
-                        scheduler,
-                        nodes,
-                    )?;
+            waiters = self
+                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
+                .await;
+            report_progress(tids_to_promote.len() + waiters.len());
+        }
 
-                    if let Some(tenant_shard) = rescheduled {
-                        let waiter = self.maybe_configured_reconcile_shard(
-                            tenant_shard,
-                            nodes,
-                            reconciler_config,
-                        );
-                        if let Some(some) = waiter {
-                            waiters.push(some);
-                        }
+        while !waiters.is_empty() {
+            if cancel.is_cancelled() {
+                match self
+                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
+                    .await
+                {
+                    Ok(()) => return Err(OperationError::Cancelled),
+                    Err(err) => {
+                        return Err(OperationError::FinalizeError(
+                            format!(
+                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
+                                node_id, err
+                            )
+                            .into(),
+                        ));
                     }
                 }
             }
 
-            waiters = self
-                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
-                .await;
+            tracing::info!("Awaiting {} pending fill reconciliations", waiters.len());
+
+            waiters = self
+                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
+                .await;
+            report_progress(tids_to_promote.len() + waiters.len());
+        }
+
+        if let Err(err) = self
+            .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
+            .await
+        {
+            // This isn't a huge issue since the filling process starts upon request. However, it
+            // will prevent the next drain from starting. The only case in which this can fail
+            // is database unavailability. Such a case will require manual intervention.
+            return Err(OperationError::FinalizeError(
+                format!("Failed to finalise fill of {node_id} by setting scheduling policy to Active: {err}")
+                    .into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Updates scrubber metadata health check results.
+    pub(crate) async fn metadata_health_update(
+        &self,
+        update_req: MetadataHealthUpdateRequest,
+    ) -> Result<(), ApiError> {
+        let now = chrono::offset::Utc::now();
+        let (healthy_records, unhealthy_records) = {
+            let locked = self.inner.read().unwrap();
+            let healthy_records = update_req
+                .healthy_tenant_shards
+                .into_iter()
+                // Retain only health records associated with tenant shards managed by storage controller.
+                .filter(|tenant_shard_id| locked.tenants.contains_key(tenant_shard_id))
+                .map(|tenant_shard_id| MetadataHealthPersistence::new(tenant_shard_id, true, now))
+                .collect();
+            let unhealthy_records = update_req
+                .unhealthy_tenant_shards
+                .into_iter()
+                .filter(|tenant_shard_id| locked.tenants.contains_key(tenant_shard_id))
+                .map(|tenant_shard_id| MetadataHealthPersistence::new(tenant_shard_id, false, now))
+                .collect();
+
+            (healthy_records, unhealthy_records)
+        };
+
+        self.persistence
+            .update_metadata_health_records(healthy_records, unhealthy_records, now)
+            .await?;
+        Ok(())
+    }
+
+    /// Lists the tenant shards that has unhealthy metadata status.
+    pub(crate) async fn metadata_health_list_unhealthy(
+        &self,
+    ) -> Result<Vec<TenantShardId>, ApiError> {
+        let result = self
+            .persistence
+            .list_unhealthy_metadata_health_records()
+            .await?
+            .iter()
+            .map(|p| p.get_tenant_shard_id().unwrap())
+            .collect();
+
+        Ok(result)
+    }
+
+    /// Lists the tenant shards that have not been scrubbed for some duration.
+    pub(crate) async fn metadata_health_list_outdated(
+        &self,
+        not_scrubbed_for: Duration,
+    ) -> Result<Vec<MetadataHealthRecord>, ApiError> {
+        let earlier = chrono::offset::Utc::now() - not_scrubbed_for;
+        let result = self
+            .persistence
+            .list_outdated_metadata_health_records(earlier)
+            .await?
+            .into_iter()
+            .map(|record| record.into())
+            .collect();
+        Ok(result)
+    }
+
+    pub(crate) fn get_leadership_status(&self) -> LeadershipStatus {
+        self.inner.read().unwrap().get_leadership_status()
+    }
+
+    pub(crate) async fn step_down(&self) -> GlobalObservedState {
+        tracing::info!("Received step down request from peer");
+        failpoint_support::sleep_millis_async!("sleep-on-step-down-handling");
+
+        self.inner.write().unwrap().step_down();
+        // TODO: would it make sense to have a time-out for this?
+        self.stop_reconciliations(StopReconciliationsReason::SteppingDown)
+            .await;
+
+        let mut global_observed = GlobalObservedState::default();
+        let locked = self.inner.read().unwrap();
+        for (tid, tenant_shard) in locked.tenants.iter() {
+            global_observed
+                .0
+                .insert(*tid, tenant_shard.observed.clone());
+        }
+
+        global_observed
+    }
+
+    pub(crate) async fn get_safekeeper(
+        &self,
+        id: i64,
+    ) -> Result<crate::persistence::SafekeeperPersistence, DatabaseError> {
+        self.persistence.safekeeper_get(id).await
+    }
+
+    pub(crate) async fn upsert_safekeeper(
+        &self,
+        record: crate::persistence::SafekeeperPersistence,
+    ) -> Result<(), DatabaseError> {
+        self.persistence.safekeeper_upsert(record).await
+    }
 
-            failpoint_support::sleep_millis_async!("sleepy-drain-loop", &cancel);
-        }
+    pub(crate) async fn list_safekeepers(
+        &self,
+    ) -> Result<Vec<SafekeeperPersistence>, DatabaseError> {
+        self.persistence.list_safekeepers().await
+    }
 
-        while !waiters.is_empty() {
-            if cancel.is_cancelled() {
-                match self
-                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
-                    .await
-                {
-                    Ok(()) => return Err(OperationError::Cancelled),
-                    Err(err) => {
-                        return Err(OperationError::FinalizeError(
-                            format!(
-                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
-                                node_id, err
-                            )
-                            .into(),
-                        ));
-                    }
-                }
+    fn safekeeper_client(&self, safekeeper: &SafekeeperPersistence) -> SafekeeperClient {
+        SafekeeperClient::new(
+            safekeeper.get_node_id(),
+            safekeeper.base_url(),
+            self.config.safekeeper_jwt_token.as_deref(),
+        )
+    }
+
+    /// Pick safekeepers for a newly created timeline, persist the placement and create the
+    /// timeline on them.  If the timeline already has a placement (i.e. this is a retry of
+    /// timeline creation), the existing placement is reused.
+    async fn tenant_timeline_create_safekeepers(
+        &self,
+        tenant_id: TenantId,
+        timeline_info: &TimelineInfo,
+    ) -> Result<(), ApiError> {
+        let timeline_id = timeline_info.timeline_id;
+        let safekeepers = self.persistence.list_safekeepers().await?;
+
+        let sk_set = match self
+            .persistence
+            .get_timeline(tenant_id, timeline_id)
+            .await?
+        {
+            Some(existing) => existing.sk_set.0,
+            None => {
+                let timeline_counts = self.persistence.count_timelines_per_safekeeper().await?;
+                let picked = schedule_safekeepers(
+                    &safekeepers,
+                    &timeline_counts,
+                    &[],
+                    &[],
+                    TIMELINE_SAFEKEEPER_COUNT,
+                )?;
+                let sk_set = picked.iter().map(|sk| sk.id).collect::<Vec<_>>();
+
+                self.persistence
+                    .insert_timeline(TimelinePersistence {
+                        tenant_id: tenant_id.to_string(),
+                        timeline_id: timeline_id.to_string(),
+                        generation: 1,
+                        sk_set: sk_set.clone().into(),
+                        migrating_to: None,
+                    })
+                    .await?;
+                sk_set
             }
+        };
 
-            tracing::info!("Awaiting {} pending drain reconciliations", waiters.len());
+        let req = SafekeeperTimelineCreateRequest {
+            tenant_id,
+            timeline_id,
+            peer_ids: Some(sk_set.iter().map(|id| NodeId(*id as u64)).collect()),
+            // Safekeepers expect the version in PG_VERSION_NUM format
+            pg_version: timeline_info.pg_version * 10000,
+            system_id: None,
+            wal_seg_size: None,
+            commit_lsn: timeline_info.last_record_lsn,
+            local_start_lsn: None,
+        };
 
-            waiters = self
-                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
-                .await;
+        let targets = safekeepers
+            .iter()
+            .filter(|sk| sk_set.contains(&sk.id))
+            .collect::<Vec<_>>();
+        let results = futures::future::join_all(targets.iter().map(|sk| {
+            let client = self.safekeeper_client(sk);
+            let req = &req;
+            async move { client.create_timeline(req).await }
+        }))
+        .await;
+
+        let mut created = 0;
+        for (sk, res) in targets.iter().zip(results) {
+            match res {
+                Ok(()) => created += 1,
+                Err(e) => tracing::warn!(
+                    "Failed to create timeline {tenant_id}/{timeline_id} on safekeeper {}: {e}",
+                    sk.id
+                ),
+            }
         }
 
-        // At this point we have done the best we could to drain shards from this node.
-        // Set the node scheduling policy to `[NodeSchedulingPolicy::PauseForRestart]`
-        // to complete the drain.
-        if let Err(err) = self
-            .node_configure(node_id, None, Some(NodeSchedulingPolicy::PauseForRestart))
-            .await
-        {
-            // This is not fatal. Anything that is polling the node scheduling policy to detect
-            // the end of the drain operations will hang, but all such places should enforce an
-            // overall timeout. The scheduling policy will be updated upon node re-attach and/or
-            // by the counterpart fill operation.
-            return Err(OperationError::FinalizeError(
+        // A majority is enough for the compute to commit WAL: a safekeeper that missed the
+        // creation gets the timeline created when the compute connects to it.
+        if created < sk_set.len() / 2 + 1 {
+            return Err(ApiError::ResourceUnavailable(
                 format!(
-                    "Failed to finalise drain of {node_id} by setting scheduling policy to PauseForRestart: {err}"
+                    "Timeline {tenant_id}/{timeline_id} created on only {created} of {} safekeepers",
+                    sk_set.len()
                 )
                 .into(),
             ));
@@ -6647,339 +9493,471 @@ pub(crate) async fn drain_node(
         Ok(())
     }
 
-    /// Create a node fill plan (pick secondaries to promote) that meets the following requirements:
-    /// 1. The node should be filled until it reaches the expected cluster average of
-    ///    attached shards. If there are not enough secondaries on the node, the plan stops early.
-    /// 2. Select tenant shards to promote such that the number of attached shards is balanced
-    ///    throughout the cluster. We achieve this by picking tenant shards from each node,
-    ///    starting from the ones with the largest number of attached shards, until the node
-    ///    reaches the expected cluster average.
-    /// 3. Avoid promoting more shards of the same tenant than required. The upper bound
-    ///    for the number of tenants from the same shard promoted to the node being filled is:
-    ///    shard count for the tenant divided by the number of nodes in the cluster.
-    fn fill_node_plan(&self, node_id: NodeId) -> Vec<TenantShardId> {
-        let mut locked = self.inner.write().unwrap();
-        let fill_requirement = locked.scheduler.compute_fill_requirement(node_id);
-
-        let mut tids_by_node = locked
-            .tenants
-            .iter_mut()
-            .filter_map(|(tid, tenant_shard)| {
-                if tenant_shard.intent.get_secondary().contains(&node_id) {
-                    if let Some(primary) = tenant_shard.intent.get_attached() {
-                        return Some((*primary, *tid));
-                    }
-                }
-
-                None
-            })
-            .into_group_map();
-
-        let expected_attached = locked.scheduler.expected_attached_shard_count();
-        let nodes_by_load = locked.scheduler.nodes_by_attached_shard_count();
+    /// Delete a timeline (or all timelines of a tenant, if `timeline_id` is None) from the
+    /// safekeepers it was placed on, and forget about its placement.
+    async fn tenant_timeline_delete_safekeepers(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: Option<TimelineId>,
+    ) -> Result<(), ApiError> {
+        let timelines = match timeline_id {
+            Some(timeline_id) => self
+                .persistence
+                .get_timeline(tenant_id, timeline_id)
+                .await?
+                .into_iter()
+                .collect(),
+            None => self.persistence.list_tenant_timelines(tenant_id).await?,
+        };
 
-        let mut promoted_per_tenant: HashMap<TenantId, usize> = HashMap::new();
-        let mut plan = Vec::new();
+        if timelines.is_empty() {
+            return Ok(());
+        }
 
-        for (node_id, attached) in nodes_by_load {
-            let available = locked
-                .nodes
-                .get(&node_id)
-                .map_or(false, |n| n.is_available());
-            if !available {
-                continue;
-            }
+        let safekeepers = self
+            .persistence
+            .list_safekeepers()
+            .await?
+            .into_iter()
+            .map(|sk| (sk.get_node_id(), sk))
+            .collect::<HashMap<_, _>>();
 
-            if plan.len() >= fill_requirement
-                || tids_by_node.is_empty()
-                || attached <= expected_attached
-            {
-                break;
-            }
+        for timeline in timelines {
+            let timeline_id = TimelineId::from_str(&timeline.timeline_id).map_err(|e| {
+                ApiError::InternalServerError(anyhow::anyhow!(
+                    "Malformed timeline ID in database: {e}"
+                ))
+            })?;
 
-            let can_take = attached - expected_attached;
-            let needed = fill_requirement - plan.len();
-            let mut take = std::cmp::min(can_take, needed);
+            for sk_id in timeline.get_sk_set() {
+                let Some(sk) = safekeepers.get(&sk_id) else {
+                    tracing::warn!(
+                        "Timeline {tenant_id}/{timeline_id} placed on unknown safekeeper {sk_id}"
+                    );
+                    continue;
+                };
 
-            let mut remove_node = false;
-            while take > 0 {
-                match tids_by_node.get_mut(&node_id) {
-                    Some(tids) => match tids.pop() {
-                        Some(tid) => {
-                            let max_promote_for_tenant = std::cmp::max(
-                                tid.shard_count.count() as usize / locked.nodes.len(),
-                                1,
-                            );
-                            let promoted = promoted_per_tenant.entry(tid.tenant_id).or_default();
-                            if *promoted < max_promote_for_tenant {
-                                plan.push(tid);
-                                *promoted += 1;
-                                take -= 1;
-                            }
-                        }
-                        None => {
-                            remove_node = true;
-                            break;
-                        }
-                    },
-                    None => {
-                        break;
+                // The placement is only dropped once every member deleted its copy: until then,
+                // it is what a retried deletion finds the remaining members from.
+                match self
+                    .safekeeper_client(sk)
+                    .delete_timeline(tenant_id, timeline_id, false)
+                    .await
+                {
+                    Ok(()) | Err(mgmt_api::Error::ApiError(StatusCode::NOT_FOUND, _)) => {}
+                    Err(e) => {
+                        tracing::warn!(
+                            "Error deleting timeline {timeline_id} on safekeeper {sk_id}: {e}"
+                        );
+                        return Err(ApiError::ResourceUnavailable(
+                            format!("Safekeeper {sk_id} failed to delete timeline {timeline_id}")
+                                .into(),
+                        ));
                     }
                 }
             }
-
-            if remove_node {
-                tids_by_node.remove(&node_id);
-            }
         }
 
-        plan
+        self.persistence
+            .delete_timelines(tenant_id, timeline_id)
+            .await?;
+
+        Ok(())
     }
 
-    /// Fill a node by promoting its secondaries until the cluster is balanced
-    /// with regards to attached shard counts. Note that this operation only
-    /// makes sense as a counterpart to the drain implemented in [`Service::drain_node`].
-    /// This is a long running operation and it should run as a separate Tokio task.
-    pub(crate) async fn fill_node(
+    pub(crate) async fn tenant_timeline_safekeepers(
         &self,
-        node_id: NodeId,
-        cancel: CancellationToken,
-    ) -> Result<(), OperationError> {
-        const SECONDARY_WARMUP_TIMEOUT: Duration = Duration::from_secs(20);
-        const SECONDARY_DOWNLOAD_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
-        let reconciler_config = ReconcilerConfigBuilder::new()
-            .secondary_warmup_timeout(SECONDARY_WARMUP_TIMEOUT)
-            .secondary_download_request_timeout(SECONDARY_DOWNLOAD_REQUEST_TIMEOUT)
-            .build();
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+    ) -> Result<TimelineSafekeepersResponse, ApiError> {
+        let timeline = self
+            .persistence
+            .get_timeline(tenant_id, timeline_id)
+            .await?
+            .ok_or_else(|| {
+                ApiError::NotFound(
+                    anyhow::anyhow!("Timeline {tenant_id}/{timeline_id} not placed on safekeepers")
+                        .into(),
+                )
+            })?;
 
-        let mut tids_to_promote = self.fill_node_plan(node_id);
-        let mut waiters = Vec::new();
+        Ok(TimelineSafekeepersResponse {
+            tenant_id,
+            timeline_id,
+            generation: timeline.generation as u32,
+            safekeepers: timeline.get_sk_set(),
+        })
+    }
 
-        // Execute the plan we've composed above. Before aplying each move from the plan,
-        // we validate to ensure that it has not gone stale in the meantime.
-        while !tids_to_promote.is_empty() {
-            if cancel.is_cancelled() {
-                match self
-                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
-                    .await
-                {
-                    Ok(()) => return Err(OperationError::Cancelled),
-                    Err(err) => {
-                        return Err(OperationError::FinalizeError(
-                            format!(
-                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
-                                node_id, err
-                            )
-                            .into(),
-                        ));
-                    }
-                }
-            }
+    /// Move a timeline off safekeeper `from` onto a newly scheduled safekeeper:
+    /// - the new safekeeper pulls the timeline from the current members, and joins the
+    ///   placement alongside `from`
+    /// - the timeline's compute is told about its new safekeepers
+    /// - the copy on `from` is deleted, and `from` leaves the placement
+    ///
+    /// Each step is persisted before the next, so that a migration which failed part way is
+    /// picked up where it stopped when the drain is retried.
+    async fn safekeeper_timeline_migrate(
+        &self,
+        timeline: &TimelinePersistence,
+        from: NodeId,
+        safekeepers: &HashMap<NodeId, SafekeeperPersistence>,
+        timeline_counts: &mut HashMap<i64, u64>,
+    ) -> anyhow::Result<NodeId> {
+        let tenant_id = TenantId::from_str(&timeline.tenant_id)?;
+        let timeline_id = TimelineId::from_str(&timeline.timeline_id)?;
+        let mut generation = timeline.generation;
+
+        let mut remaining = timeline
+            .sk_set
+            .0
+            .iter()
+            .copied()
+            .filter(|id| *id != from.0 as i64)
+            .collect::<Vec<_>>();
 
-            {
-                let mut locked = self.inner.write().unwrap();
-                let (nodes, tenants, scheduler) = locked.parts_mut();
+        let dest = if let Some(dest) = timeline
+            .migrating_to
+            .filter(|dest| remaining.contains(dest))
+        {
+            // A previous attempt added the replacement, but failed before removing `from`
+            tracing::info!(
+                %tenant_id, %timeline_id,
+                "Resuming migration from safekeeper {from} to {dest}"
+            );
+            dest
+        } else {
+            let members = timeline
+                .get_sk_set()
+                .into_iter()
+                .filter_map(|id| safekeepers.get(&id))
+                .collect::<Vec<_>>();
 
-                let node = nodes.get(&node_id).ok_or(OperationError::NodeStateChanged(
-                    format!("node {node_id} was removed").into(),
-                ))?;
+            // Prefer the AZ which `from` vacates, by avoiding the AZs of the remaining members
+            let remaining_azs = members
+                .iter()
+                .filter(|sk| sk.get_node_id() != from)
+                .map(|sk| sk.availability_zone_id.as_str())
+                .collect::<Vec<_>>();
 
-                let current_policy = node.get_scheduling();
-                if !matches!(current_policy, NodeSchedulingPolicy::Filling) {
-                    // TODO(vlad): maybe cancel pending reconciles before erroring out. need to think
-                    // about it
-                    return Err(OperationError::NodeStateChanged(
-                        format!("node {node_id} changed state to {current_policy:?}").into(),
-                    ));
+            let candidates = safekeepers.values().cloned().collect::<Vec<_>>();
+            let dest = schedule_safekeepers(
+                &candidates,
+                timeline_counts,
+                &timeline.sk_set.0,
+                &remaining_azs,
+                1,
+            )?
+            .pop()
+            .expect("Scheduled exactly one safekeeper");
+
+            tracing::info!(
+                %tenant_id, %timeline_id,
+                "Migrating timeline from safekeeper {from} to {}", dest.id
+            );
+
+            // The destination already holds the timeline if a previous attempt pulled it, but
+            // failed before updating the placement
+            let client = self.safekeeper_client(&dest);
+            match client.timeline_status(tenant_id, timeline_id).await {
+                Ok(()) => {}
+                Err(mgmt_api::Error::ApiError(StatusCode::NOT_FOUND, _)) => {
+                    let req = PullTimelineRequest {
+                        tenant_id,
+                        timeline_id,
+                        http_hosts: members.iter().map(|sk| sk.base_url()).collect(),
+                    };
+                    let resp = client.pull_timeline(&req).await?;
+                    tracing::info!(
+                        %tenant_id, %timeline_id,
+                        "Safekeeper {} pulled timeline from {}", dest.id, resp.safekeeper_host
+                    );
                 }
+                Err(e) => return Err(e.into()),
+            }
+
+            let mut joint_sk_set = timeline.sk_set.0.clone();
+            joint_sk_set.push(dest.id);
+            self.persistence
+                .update_timeline_sk_set(
+                    tenant_id,
+                    timeline_id,
+                    generation,
+                    joint_sk_set,
+                    Some(dest.id),
+                )
+                .await?;
+            generation += 1;
+            *timeline_counts.entry(dest.id).or_default() += 1;
 
-                while waiters.len() < MAX_RECONCILES_PER_OPERATION {
-                    if let Some(tid) = tids_to_promote.pop() {
-                        if let Some(tenant_shard) = tenants.get_mut(&tid) {
-                            // If the node being filled is not a secondary anymore,
-                            // skip the promotion.
-                            if !tenant_shard.intent.get_secondary().contains(&node_id) {
-                                continue;
-                            }
+            remaining.push(dest.id);
+            dest.id
+        };
 
-                            let previously_attached_to = *tenant_shard.intent.get_attached();
-                            match tenant_shard.reschedule_to_secondary(Some(node_id), scheduler) {
-                                Err(e) => {
-                                    tracing::warn!(
-                                        tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
-                                        "Scheduling error when filling pageserver {} : {e}", node_id
-                                    );
-                                }
-                                Ok(()) => {
-                                    tracing::info!(
-                                        tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
-                                        "Rescheduled shard while filling node {}: {:?} -> {}",
-                                        node_id,
-                                        previously_attached_to,
-                                        node_id
-                                    );
+        // The compute must stop streaming WAL to `from` before its copy goes away
+        let notify_safekeepers = remaining
+            .iter()
+            .map(|id| {
+                let sk = safekeepers
+                    .get(&NodeId(*id as u64))
+                    .ok_or_else(|| anyhow::anyhow!("Unknown safekeeper {id}"))?;
+                Ok(NotifySafekeeper {
+                    id: sk.get_node_id(),
+                    host: sk.host.clone(),
+                    port: u16::try_from(sk.port)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.compute_hook
+            .notify_safekeepers(
+                tenant_id,
+                timeline_id,
+                generation as u32,
+                notify_safekeepers,
+                &self.cancel,
+            )
+            .await?;
 
-                                    if let Some(waiter) = self.maybe_configured_reconcile_shard(
-                                        tenant_shard,
-                                        nodes,
-                                        reconciler_config,
-                                    ) {
-                                        waiters.push(waiter);
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        break;
-                    }
-                }
+        // Only the local copy: the WAL in remote storage belongs to the remaining members too
+        if let Some(from_sk) = safekeepers.get(&from) {
+            match self
+                .safekeeper_client(from_sk)
+                .delete_timeline(tenant_id, timeline_id, true)
+                .await
+            {
+                Ok(()) | Err(mgmt_api::Error::ApiError(StatusCode::NOT_FOUND, _)) => {}
+                Err(e) => return Err(e.into()),
             }
+        }
 
-            waiters = self
-                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
-                .await;
+        self.persistence
+            .update_timeline_sk_set(tenant_id, timeline_id, generation, remaining, None)
+            .await?;
+        if let Some(count) = timeline_counts.get_mut(&(from.0 as i64)) {
+            *count = count.saturating_sub(1);
         }
 
-        while !waiters.is_empty() {
-            if cancel.is_cancelled() {
-                match self
-                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
-                    .await
-                {
-                    Ok(()) => return Err(OperationError::Cancelled),
-                    Err(err) => {
-                        return Err(OperationError::FinalizeError(
-                            format!(
-                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
-                                node_id, err
-                            )
-                            .into(),
-                        ));
-                    }
-                }
+        Ok(NodeId(dest as u64))
+    }
+
+    pub(crate) async fn start_safekeeper_drain(
+        self: &Arc<Self>,
+        safekeeper_id: NodeId,
+    ) -> Result<OperationStartResponse, ApiError> {
+        match self
+            .persistence
+            .safekeeper_get(safekeeper_id.0 as i64)
+            .await
+        {
+            Ok(_) => {}
+            Err(DatabaseError::Query(diesel::result::Error::NotFound)) => {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Safekeeper {safekeeper_id} not registered").into(),
+                ));
             }
+            Err(e) => return Err(e.into()),
+        }
 
-            tracing::info!("Awaiting {} pending fill reconciliations", waiters.len());
+        let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+        let cancel = self.cancel.child_token();
 
-            waiters = self
-                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
-                .await;
+        // Check and claim the operation slot under one lock, so that concurrent requests cannot
+        // both start a background operation
+        {
+            let mut locked = self.inner.write().unwrap();
+            if let Some(ongoing) = &locked.ongoing_operation {
+                return Err(ApiError::PreconditionFailed(
+                    format!(
+                        "Background operation already ongoing: {}",
+                        ongoing.operation
+                    )
+                    .into(),
+                ));
+            }
+            locked.ongoing_operation = Some(OperationHandler {
+                operation: Operation::SafekeeperDrain(SafekeeperDrain { safekeeper_id }),
+                cancel: cancel.clone(),
+            });
         }
+        let release_slot = || self.inner.write().unwrap().ongoing_operation = None;
+
+        let operation = match self
+            .operation_begin(
+                OperationKind::SafekeeperDrain,
+                None,
+                Some(safekeeper_id),
+                Some(cancel.clone()),
+            )
+            .await
+        {
+            Ok(operation) => operation,
+            Err(err) => {
+                release_slot();
+                return Err(err);
+            }
+        };
+        let operation_id = operation.id();
 
+        // Any policy is accepted here: a safekeeper left in Draining by an interrupted or
+        // failed drain may be drained again to retry.
         if let Err(err) = self
-            .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
+            .persistence
+            .set_safekeeper_scheduling_policy(safekeeper_id.0 as i64, SkSchedulingPolicy::Draining)
             .await
         {
-            // This isn't a huge issue since the filling process starts upon request. However, it
-            // will prevent the next drain from starting. The only case in which this can fail
-            // is database unavailability. Such a case will require manual intervention.
-            return Err(OperationError::FinalizeError(
-                format!("Failed to finalise fill of {node_id} by setting scheduling policy to Active: {err}")
-                    .into(),
-            ));
+            release_slot();
+            operation
+                .finish(OperationState::Failed, Some(err.to_string()))
+                .await;
+            return Err(err.into());
         }
 
-        Ok(())
-    }
+        let span = tracing::info_span!(parent: None, "drain_safekeeper", %safekeeper_id);
 
-    /// Updates scrubber metadata health check results.
-    pub(crate) async fn metadata_health_update(
-        &self,
-        update_req: MetadataHealthUpdateRequest,
-    ) -> Result<(), ApiError> {
-        let now = chrono::offset::Utc::now();
-        let (healthy_records, unhealthy_records) = {
-            let locked = self.inner.read().unwrap();
-            let healthy_records = update_req
-                .healthy_tenant_shards
-                .into_iter()
-                // Retain only health records associated with tenant shards managed by storage controller.
-                .filter(|tenant_shard_id| locked.tenants.contains_key(tenant_shard_id))
-                .map(|tenant_shard_id| MetadataHealthPersistence::new(tenant_shard_id, true, now))
-                .collect();
-            let unhealthy_records = update_req
-                .unhealthy_tenant_shards
-                .into_iter()
-                .filter(|tenant_shard_id| locked.tenants.contains_key(tenant_shard_id))
-                .map(|tenant_shard_id| MetadataHealthPersistence::new(tenant_shard_id, false, now))
-                .collect();
+        tokio::task::spawn({
+            let service = self.clone();
+            let cancel = cancel.clone();
+            async move {
+                let _gate_guard = gate_guard;
 
-            (healthy_records, unhealthy_records)
-        };
+                scopeguard::defer! {
+                    let prev = service.inner.write().unwrap().ongoing_operation.take();
 
-        self.persistence
-            .update_metadata_health_records(healthy_records, unhealthy_records, now)
-            .await?;
-        Ok(())
+                    if let Some(Operation::SafekeeperDrain(removed_drain)) = prev.map(|h| h.operation) {
+                        assert_eq!(removed_drain.safekeeper_id, safekeeper_id, "We always take the same operation");
+                    } else {
+                        panic!("We always remove the same operation")
+                    }
+                }
+
+                tracing::info!("Safekeeper drain background operation starting");
+                let res = service
+                    .drain_safekeeper(safekeeper_id, cancel, Some(&operation))
+                    .await;
+                match &res {
+                    Ok(()) => {
+                        tracing::info!("Safekeeper drain background operation completed successfully");
+                    }
+                    Err(OperationError::Cancelled) => {
+                        tracing::info!("Safekeeper drain background operation was cancelled");
+                    }
+                    Err(err) => {
+                        tracing::error!("Safekeeper drain background operation encountered: {err}")
+                    }
+                }
+                Self::background_operation_finish(operation, &res).await;
+            }
+        }.instrument(span));
+
+        Ok(OperationStartResponse { operation_id })
     }
 
-    /// Lists the tenant shards that has unhealthy metadata status.
-    pub(crate) async fn metadata_health_list_unhealthy(
+    pub(crate) async fn cancel_safekeeper_drain(
         &self,
-    ) -> Result<Vec<TenantShardId>, ApiError> {
-        let result = self
-            .persistence
-            .list_unhealthy_metadata_health_records()
-            .await?
-            .iter()
-            .map(|p| p.get_tenant_shard_id().unwrap())
-            .collect();
+        safekeeper_id: NodeId,
+    ) -> Result<(), ApiError> {
+        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operation.as_ref() {
+            if let Operation::SafekeeperDrain(drain) = op_handler.operation {
+                if drain.safekeeper_id == safekeeper_id {
+                    tracing::info!(
+                        "Cancelling background drain operation for safekeeper {safekeeper_id}"
+                    );
+                    op_handler.cancel.cancel();
+                    return Ok(());
+                }
+            }
+        }
 
-        Ok(result)
+        Err(ApiError::PreconditionFailed(
+            format!("Safekeeper {safekeeper_id} has no drain in progress").into(),
+        ))
     }
 
-    /// Lists the tenant shards that have not been scrubbed for some duration.
-    pub(crate) async fn metadata_health_list_outdated(
-        &self,
-        not_scrubbed_for: Duration,
-    ) -> Result<Vec<MetadataHealthRecord>, ApiError> {
-        let earlier = chrono::offset::Utc::now() - not_scrubbed_for;
-        let result = self
+    /// Drain a safekeeper by migrating all timelines placed on it to other safekeepers.
+    /// Timelines are migrated one at a time, to bound the load on the safekeepers that
+    /// serve the pulls.
+    ///
+    /// This is a long running operation and it should run as a separate Tokio task.
+    async fn drain_safekeeper(
+        self: &Arc<Self>,
+        safekeeper_id: NodeId,
+        cancel: CancellationToken,
+        operation: Option<&operations::OperationGuard>,
+    ) -> Result<(), OperationError> {
+        let sk_id = safekeeper_id.0 as i64;
+        let db_error =
+            |e: DatabaseError| OperationError::FinalizeError(format!("Database error: {e}").into());
+
+        let timelines = self
             .persistence
-            .list_outdated_metadata_health_records(earlier)
-            .await?
+            .list_timelines_on_safekeeper(sk_id)
+            .await
+            .map_err(db_error)?;
+        let safekeepers = self
+            .persistence
+            .list_safekeepers()
+            .await
+            .map_err(db_error)?
             .into_iter()
-            .map(|record| record.into())
-            .collect();
-        Ok(result)
-    }
+            .map(|sk| (sk.get_node_id(), sk))
+            .collect::<HashMap<_, _>>();
+        let mut timeline_counts = self
+            .persistence
+            .count_timelines_per_safekeeper()
+            .await
+            .map_err(db_error)?;
 
-    pub(crate) fn get_leadership_status(&self) -> LeadershipStatus {
-        self.inner.read().unwrap().get_leadership_status()
-    }
+        tracing::info!("Migrating {} timelines", timelines.len());
 
-    pub(crate) async fn step_down(&self) -> GlobalObservedState {
-        tracing::info!("Received step down request from peer");
-        failpoint_support::sleep_millis_async!("sleep-on-step-down-handling");
+        let total = timelines.len();
+        let mut failed = 0;
+        for (i, timeline) in timelines.into_iter().enumerate() {
+            if let Some(operation) = operation {
+                operation.set_progress(i, total);
+            }
 
-        self.inner.write().unwrap().step_down();
-        // TODO: would it make sense to have a time-out for this?
-        self.stop_reconciliations(StopReconciliationsReason::SteppingDown)
-            .await;
+            if cancel.is_cancelled() {
+                self.persistence
+                    .set_safekeeper_scheduling_policy(sk_id, SkSchedulingPolicy::Active)
+                    .await
+                    .map_err(db_error)?;
+                return Err(OperationError::Cancelled);
+            }
 
-        let mut global_observed = GlobalObservedState::default();
-        let locked = self.inner.read().unwrap();
-        for (tid, tenant_shard) in locked.tenants.iter() {
-            global_observed
-                .0
-                .insert(*tid, tenant_shard.observed.clone());
+            if let Err(e) = self
+                .safekeeper_timeline_migrate(
+                    &timeline,
+                    safekeeper_id,
+                    &safekeepers,
+                    &mut timeline_counts,
+                )
+                .await
+            {
+                tracing::warn!(
+                    tenant_id=%timeline.tenant_id, timeline_id=%timeline.timeline_id,
+                    "Failed to migrate timeline: {e:#}"
+                );
+                failed += 1;
+            }
+        }
+        if let Some(operation) = operation {
+            operation.set_progress(total, total);
         }
 
-        global_observed
-    }
+        if failed > 0 {
+            // Stay in Draining, so that nothing new is placed here while the drain is retried
+            return Err(OperationError::FinalizeError(
+                format!("{failed} timelines could not be migrated off safekeeper {safekeeper_id}")
+                    .into(),
+            ));
+        }
 
-    pub(crate) async fn get_safekeeper(
-        &self,
-        id: i64,
-    ) -> Result<crate::persistence::SafekeeperPersistence, DatabaseError> {
-        self.persistence.safekeeper_get(id).await
-    }
+        self.persistence
+            .set_safekeeper_scheduling_policy(sk_id, SkSchedulingPolicy::Pause)
+            .await
+            .map_err(db_error)?;
 
-    pub(crate) async fn upsert_safekeeper(
-        &self,
-        record: crate::persistence::SafekeeperPersistence,
-    ) -> Result<(), DatabaseError> {
-        self.persistence.safekeeper_upsert(record).await
+        Ok(())
     }
 
     pub(crate) async fn update_shards_preferred_azs(
@@ -0,0 +1,495 @@
+//! Decisions about automatically splitting tenants.
+//!
+//! Pageservers report their largest tenant shards by logical size, resident size and WAL
+//! ingest rate.  Tenants with a shard above any of the configured thresholds are candidates
+//! for splitting: this module decides which of them to split, given their split policies and
+//! the splits already in progress, and explains the decision for each candidate.
+
+use std::collections::{HashMap, HashSet};
+
+use pageserver_api::{
+    controller_api::{
+        AutoSplitDecision, AutoSplitDecisionsResponse, AutoSplitOutcome, AvailabilityZone,
+        ShardSplitPolicy, SplitTrigger,
+    },
+    models::{ShardParameters, TenantSorting, TopTenantShardItem},
+    shard::ShardCount,
+};
+use utils::id::{NodeId, TenantId};
+
+use super::Config;
+
+/// Thresholds above which a tenant is split.
+pub(crate) struct SplitThresholds {
+    logical_size: Option<u64>,
+    resident_size: Option<u64>,
+    wal_ingest_rate: Option<u64>,
+}
+
+impl SplitThresholds {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            logical_size: config.split_threshold,
+            resident_size: config.split_resident_size_threshold,
+            wal_ingest_rate: config.split_wal_ingest_rate_threshold,
+        }
+    }
+
+    /// Auto-splitting is disabled without any thresholds.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.logical_size.is_some()
+            || self.resident_size.is_some()
+            || self.wal_ingest_rate.is_some()
+    }
+
+    /// The orderings to request the top tenant shards from pageservers with, and the thresholds
+    /// below which shards are not interesting.
+    pub(crate) fn sortings(&self) -> Vec<(TenantSorting, u64)> {
+        [
+            (TenantSorting::MaxLogicalSize, self.logical_size),
+            (TenantSorting::ResidentSize, self.resident_size),
+            (TenantSorting::WalIngestRate, self.wal_ingest_rate),
+        ]
+        .into_iter()
+        .filter_map(|(sorting, threshold)| threshold.map(|t| (sorting, t)))
+        .collect()
+    }
+
+    /// The thresholds which the shard exceeds.
+    fn triggers(&self, shard: &TopTenantShardItem) -> Vec<SplitTrigger> {
+        let mut triggers = Vec::new();
+        if let Some(threshold) = self.logical_size {
+            if shard.max_logical_size > threshold {
+                triggers.push(SplitTrigger::LogicalSize {
+                    size: shard.max_logical_size,
+                    threshold,
+                });
+            }
+        }
+        if let Some(threshold) = self.resident_size {
+            if shard.resident_size > threshold {
+                triggers.push(SplitTrigger::ResidentSize {
+                    size: shard.resident_size,
+                    threshold,
+                });
+            }
+        }
+        if let Some(threshold) = self.wal_ingest_rate {
+            if shard.wal_ingest_rate > threshold {
+                triggers.push(SplitTrigger::WalIngestRate {
+                    rate: shard.wal_ingest_rate,
+                    threshold,
+                });
+            }
+        }
+        triggers
+    }
+}
+
+/// How far the trigger is above its threshold, to split the most urgent tenants first.
+fn urgency(trigger: &SplitTrigger) -> f64 {
+    let (value, threshold) = match *trigger {
+        SplitTrigger::LogicalSize { size, threshold }
+        | SplitTrigger::ResidentSize { size, threshold } => (size, threshold),
+        SplitTrigger::WalIngestRate { rate, threshold } => (rate, threshold),
+    };
+    value as f64 / threshold.max(1) as f64
+}
+
+/// Limits of automatic splitting.
+pub(crate) struct SplitLimits {
+    pub(crate) max_shard_count: u8,
+    pub(crate) max_concurrent_per_node: usize,
+    pub(crate) max_concurrent_per_az: usize,
+}
+
+impl SplitLimits {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            max_shard_count: config.split_max_shard_count,
+            max_concurrent_per_node: config.max_concurrent_splits_per_node,
+            max_concurrent_per_az: config.max_concurrent_splits_per_az,
+        }
+    }
+}
+
+/// The state of a tenant which matters for splitting it.
+pub(crate) struct TenantSplitState {
+    pub(crate) shard_count: ShardCount,
+    pub(crate) policy: ShardSplitPolicy,
+    /// Where the tenant's shards are attached, or None if any of them is not attached.
+    pub(crate) locations: Option<Vec<(NodeId, AvailabilityZone)>>,
+    pub(crate) splitting: bool,
+}
+
+/// Automatic splits in progress, and the decisions of the last pass.
+#[derive(Default)]
+pub(crate) struct AutoSplitState {
+    pub(crate) in_flight: HashSet<TenantId>,
+    pub(crate) last: AutoSplitDecisionsResponse,
+}
+
+/// Decide which of the tenants with candidate shards to split.
+pub(crate) fn plan_splits(
+    candidates: Vec<TopTenantShardItem>,
+    thresholds: &SplitThresholds,
+    limits: &SplitLimits,
+    tenants: &HashMap<TenantId, TenantSplitState>,
+) -> Vec<AutoSplitDecision> {
+    // Count the splits in progress per node and AZ
+    let mut node_splits: HashMap<NodeId, usize> = HashMap::new();
+    let mut az_splits: HashMap<AvailabilityZone, usize> = HashMap::new();
+    for tenant in tenants.values().filter(|t| t.splitting) {
+        count_split(tenant, &mut node_splits, &mut az_splits);
+    }
+
+    // The same tenant may be reported for several shards and orderings
+    let mut triggers: HashMap<TenantId, Vec<SplitTrigger>> = HashMap::new();
+    for shard in &candidates {
+        let tenant_triggers = triggers.entry(shard.id.tenant_id).or_default();
+        for trigger in thresholds.triggers(shard) {
+            match tenant_triggers
+                .iter_mut()
+                .find(|t| std::mem::discriminant(*t) == std::mem::discriminant(&trigger))
+            {
+                Some(existing) if urgency(existing) < urgency(&trigger) => *existing = trigger,
+                Some(_) => {}
+                None => tenant_triggers.push(trigger),
+            }
+        }
+    }
+    let mut triggers = triggers
+        .into_iter()
+        .filter(|(_, triggers)| !triggers.is_empty())
+        .collect::<Vec<_>>();
+    let max_urgency = |triggers: &[SplitTrigger]| triggers.iter().map(urgency).fold(0.0, f64::max);
+    triggers.sort_by(|(a_id, a), (b_id, b)| {
+        max_urgency(b)
+            .total_cmp(&max_urgency(a))
+            .then(a_id.cmp(b_id))
+    });
+
+    let mut decisions = Vec::new();
+    for (tenant_id, triggers) in triggers {
+        let Some(tenant) = tenants.get(&tenant_id) else {
+            // Tenant was deleted since the pageserver reported it
+            continue;
+        };
+        let outcome = decide(tenant, limits, &node_splits, &az_splits);
+        if matches!(outcome, AutoSplitOutcome::Split { .. }) {
+            count_split(tenant, &mut node_splits, &mut az_splits);
+        }
+        decisions.push(AutoSplitDecision {
+            tenant_id,
+            shard_count: tenant.shard_count.literal(),
+            triggers,
+            outcome,
+        });
+    }
+    decisions
+}
+
+fn decide(
+    tenant: &TenantSplitState,
+    limits: &SplitLimits,
+    node_splits: &HashMap<NodeId, usize>,
+    az_splits: &HashMap<AvailabilityZone, usize>,
+) -> AutoSplitOutcome {
+    if tenant.policy.disabled {
+        return AutoSplitOutcome::DisabledByPolicy;
+    }
+
+    let max_shard_count = tenant
+        .policy
+        .max_shard_count
+        .map_or(limits.max_shard_count, |max| {
+            std::cmp::min(max, limits.max_shard_count)
+        });
+    let Some(new_shard_count) = split_shard_count(tenant.shard_count, max_shard_count) else {
+        return AutoSplitOutcome::AtMaxShardCount { max_shard_count };
+    };
+
+    let Some(locations) = tenant.locations.as_ref().filter(|_| !tenant.splitting) else {
+        return AutoSplitOutcome::NotReady;
+    };
+    for (node_id, _) in locations {
+        if node_splits.get(node_id).copied().unwrap_or(0) >= limits.max_concurrent_per_node {
+            return AutoSplitOutcome::NodeConcurrencyLimit { node_id: *node_id };
+        }
+    }
+    for (_, az) in locations {
+        if az_splits.get(az).copied().unwrap_or(0) >= limits.max_concurrent_per_az {
+            return AutoSplitOutcome::AzConcurrencyLimit { az: az.clone() };
+        }
+    }
+
+    // Always split to the max number of shards: this avoids stepping through intervening
+    // shard counts and encountering the overhead of a split+cleanup each time as a tenant
+    // grows.  The stripe size can only be chosen when splitting an unsharded tenant.
+    let new_stripe_size = if tenant.shard_count.count() == 1 {
+        Some(
+            tenant
+                .policy
+                .stripe_size
+                .unwrap_or(ShardParameters::DEFAULT_STRIPE_SIZE),
+        )
+    } else {
+        None
+    };
+    AutoSplitOutcome::Split {
+        new_shard_count,
+        new_stripe_size,
+    }
+}
+
+/// The largest shard count up to the maximum which the tenant can be split to: pageservers
+/// split each shard into a power of two of shards.  None if the tenant can't be split further.
+fn split_shard_count(shard_count: ShardCount, max_shard_count: u8) -> Option<u8> {
+    let mut new_shard_count = shard_count.count();
+    while let Some(doubled) = new_shard_count
+        .checked_mul(2)
+        .filter(|c| *c <= max_shard_count)
+    {
+        new_shard_count = doubled;
+    }
+    (new_shard_count > shard_count.count()).then_some(new_shard_count)
+}
+
+/// Whether the tenant can be split to exactly this many shards.
+pub(crate) fn is_valid_split(shard_count: ShardCount, new_shard_count: u8) -> bool {
+    let count = shard_count.count();
+    new_shard_count % count == 0 && (new_shard_count / count).is_power_of_two()
+}
+
+/// Count a split of the tenant against the nodes and AZs of its shards.
+fn count_split(
+    tenant: &TenantSplitState,
+    node_splits: &mut HashMap<NodeId, usize>,
+    az_splits: &mut HashMap<AvailabilityZone, usize>,
+) {
+    let Some(locations) = &tenant.locations else {
+        return;
+    };
+    let nodes: HashSet<_> = locations.iter().map(|(node_id, _)| *node_id).collect();
+    let azs: HashSet<_> = locations.iter().map(|(_, az)| az).collect();
+    for node_id in nodes {
+        *node_splits.entry(node_id).or_default() += 1;
+    }
+    for az in azs {
+        *az_splits.entry(az.clone()).or_default() += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use pageserver_api::shard::{ShardNumber, ShardStripeSize, TenantShardId};
+
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn thresholds() -> SplitThresholds {
+        SplitThresholds {
+            logical_size: Some(100 * GB),
+            resident_size: None,
+            wal_ingest_rate: Some(10 * 1024 * 1024),
+        }
+    }
+
+    fn limits() -> SplitLimits {
+        SplitLimits {
+            max_shard_count: 8,
+            max_concurrent_per_node: 1,
+            max_concurrent_per_az: 2,
+        }
+    }
+
+    fn tenant_id(n: u8) -> TenantId {
+        TenantId::from_str(&format!("{n:032x}")).unwrap()
+    }
+
+    fn shard(tenant: u8, logical_size: u64, wal_ingest_rate: u64) -> TopTenantShardItem {
+        TopTenantShardItem {
+            id: TenantShardId {
+                tenant_id: tenant_id(tenant),
+                shard_number: ShardNumber(0),
+                shard_count: ShardCount::new(0),
+            },
+            resident_size: 0,
+            physical_size: 0,
+            max_logical_size: logical_size,
+            wal_ingest_rate,
+        }
+    }
+
+    fn tenant(node: u64, az: &str) -> TenantSplitState {
+        TenantSplitState {
+            shard_count: ShardCount::new(0),
+            policy: ShardSplitPolicy::default(),
+            locations: Some(vec![(NodeId(node), AvailabilityZone(az.to_string()))]),
+            splitting: false,
+        }
+    }
+
+    #[test]
+    fn split_by_size_or_ingest_rate() {
+        let tenants = HashMap::from([
+            (tenant_id(1), tenant(1, "a")),
+            (tenant_id(2), tenant(2, "a")),
+        ]);
+        let candidates = vec![
+            shard(1, 200 * GB, 0),
+            shard(2, GB, 100 * 1024 * 1024),
+            // below all thresholds
+            shard(3, GB, 0),
+        ];
+
+        let decisions = plan_splits(candidates, &thresholds(), &limits(), &tenants);
+        assert_eq!(decisions.len(), 2);
+        // the write-heavy tenant is further above its threshold
+        assert_eq!(decisions[0].tenant_id, tenant_id(2));
+        assert!(matches!(
+            decisions[0].triggers[..],
+            [SplitTrigger::WalIngestRate { .. }]
+        ));
+        for decision in &decisions {
+            assert_eq!(
+                decision.outcome,
+                AutoSplitOutcome::Split {
+                    new_shard_count: 8,
+                    new_stripe_size: Some(ShardParameters::DEFAULT_STRIPE_SIZE)
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn split_policies() {
+        let mut disabled = tenant(1, "a");
+        disabled.policy.disabled = true;
+        let mut limited = tenant(2, "a");
+        limited.policy = ShardSplitPolicy {
+            disabled: false,
+            max_shard_count: Some(4),
+            stripe_size: Some(ShardStripeSize(2048)),
+        };
+        let mut at_max = tenant(3, "a");
+        at_max.shard_count = ShardCount::new(4);
+        at_max.policy.max_shard_count = Some(4);
+        let tenants = HashMap::from([
+            (tenant_id(1), disabled),
+            (tenant_id(2), limited),
+            (tenant_id(3), at_max),
+        ]);
+        let candidates = vec![
+            shard(1, 400 * GB, 0),
+            shard(2, 300 * GB, 0),
+            shard(3, 200 * GB, 0),
+        ];
+
+        let outcomes = plan_splits(candidates, &thresholds(), &limits(), &tenants)
+            .into_iter()
+            .map(|d| d.outcome)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                AutoSplitOutcome::DisabledByPolicy,
+                AutoSplitOutcome::Split {
+                    new_shard_count: 4,
+                    new_stripe_size: Some(ShardStripeSize(2048))
+                },
+                AutoSplitOutcome::AtMaxShardCount { max_shard_count: 4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn split_to_power_of_two() {
+        let mut unsharded = tenant(1, "a");
+        unsharded.policy.max_shard_count = Some(6);
+        let mut sharded = tenant(2, "a");
+        sharded.shard_count = ShardCount::new(2);
+        sharded.policy.max_shard_count = Some(7);
+        let mut at_max = tenant(3, "a");
+        at_max.shard_count = ShardCount::new(4);
+        at_max.policy.max_shard_count = Some(6);
+        let tenants = HashMap::from([
+            (tenant_id(1), unsharded),
+            (tenant_id(2), sharded),
+            (tenant_id(3), at_max),
+        ]);
+        let candidates = vec![
+            shard(1, 400 * GB, 0),
+            shard(2, 300 * GB, 0),
+            shard(3, 200 * GB, 0),
+        ];
+
+        let outcomes = plan_splits(candidates, &thresholds(), &limits(), &tenants)
+            .into_iter()
+            .map(|d| d.outcome)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                AutoSplitOutcome::Split {
+                    new_shard_count: 4,
+                    new_stripe_size: Some(ShardParameters::DEFAULT_STRIPE_SIZE)
+                },
+                AutoSplitOutcome::Split {
+                    new_shard_count: 4,
+                    new_stripe_size: None
+                },
+                AutoSplitOutcome::AtMaxShardCount { max_shard_count: 6 },
+            ]
+        );
+
+        assert!(is_valid_split(ShardCount::new(0), 8));
+        assert!(is_valid_split(ShardCount::new(2), 8));
+        assert!(!is_valid_split(ShardCount::new(2), 6));
+        assert!(!is_valid_split(ShardCount::new(4), 2));
+    }
+
+    #[test]
+    fn concurrency_limits() {
+        let mut splitting = tenant(1, "a");
+        splitting.splitting = true;
+        let tenants = HashMap::from([
+            (tenant_id(1), splitting),
+            (tenant_id(2), tenant(1, "a")),
+            (tenant_id(3), tenant(2, "a")),
+            (tenant_id(4), tenant(3, "a")),
+            (tenant_id(5), tenant(4, "b")),
+        ]);
+        let candidates = vec![
+            shard(1, 600 * GB, 0),
+            shard(2, 500 * GB, 0),
+            shard(3, 400 * GB, 0),
+            shard(4, 300 * GB, 0),
+            shard(5, 200 * GB, 0),
+        ];
+
+        let outcomes = plan_splits(candidates, &thresholds(), &limits(), &tenants)
+            .into_iter()
+            .map(|d| d.outcome)
+            .collect::<Vec<_>>();
+        let split = AutoSplitOutcome::Split {
+            new_shard_count: 8,
+            new_stripe_size: Some(ShardParameters::DEFAULT_STRIPE_SIZE),
+        };
+        assert_eq!(
+            outcomes,
+            vec![
+                AutoSplitOutcome::NotReady,
+                AutoSplitOutcome::NodeConcurrencyLimit { node_id: NodeId(1) },
+                split.clone(),
+                AutoSplitOutcome::AzConcurrencyLimit {
+                    az: AvailabilityZone("a".to_string())
+                },
+                split,
+            ]
+        );
+    }
+}
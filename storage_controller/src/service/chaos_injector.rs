@@ -1,23 +1,119 @@
-use std::{sync::Arc, time::Duration};
+//! Fault injection for soak testing the storage controller.
+//!
+//! Every interval, the chaos injector picks one [`ChaosScenario`] at random, in proportion to
+//! the configured weights, and injects it.  Faults in reconcilers and shard splits are armed
+//! here and take effect the next time the faulty code runs.
 
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use pageserver_api::controller_api::{
+    ChaosConfig, ChaosScenario, ChaosStatus, NodeAvailability, PlacementPolicy,
+};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::{thread_rng, Rng};
 use tokio_util::sync::CancellationToken;
+use utils::id::NodeId;
 
 use super::Service;
+use crate::reconciler::{InjectedFault, ReconcilerConfigBuilder};
+
+/// Injected reconcile delays are picked uniformly up to this
+const MAX_RECONCILE_DELAY: Duration = Duration::from_secs(30);
+
+/// Phases of a shard split at which the chaos injector may kill it.  Both are after the split
+/// was persisted, so that the background abort has to clean it up.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum SplitPhase {
+    /// Before any pageserver was asked to split
+    PostBegin,
+    /// After a pageserver split its shard
+    PostRemote,
+}
+
+#[derive(Default)]
+pub(crate) struct ChaosState {
+    running: Option<(ChaosConfig, CancellationToken)>,
+    injected: BTreeMap<ChaosScenario, u64>,
+    split_kill: Option<SplitPhase>,
+}
+
+impl ChaosState {
+    /// Replace the running injector, if any, with one using this cancellation token
+    pub(crate) fn start(&mut self, config: ChaosConfig, cancel: CancellationToken) {
+        self.stop();
+        self.running = Some((config, cancel));
+    }
+
+    /// Returns false if no injector was running
+    pub(crate) fn stop(&mut self) -> bool {
+        self.split_kill = None;
+        match self.running.take() {
+            Some((_, cancel)) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Disarm the split kill, returning true if it was armed for this phase
+    pub(crate) fn take_split_kill(&mut self, phase: SplitPhase) -> bool {
+        if self.split_kill == Some(phase) {
+            self.split_kill = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn status(&self) -> ChaosStatus {
+        ChaosStatus {
+            config: self.running.as_ref().map(|(config, _)| config.clone()),
+            injected: self.injected.clone(),
+            split_kill_armed: self.split_kill.is_some(),
+        }
+    }
+}
+
+/// Validate the weights of a configuration, and build the distribution to pick scenarios from
+pub(crate) fn scenario_distribution(
+    config: &ChaosConfig,
+) -> anyhow::Result<(Vec<ChaosScenario>, WeightedIndex<u32>)> {
+    if config.interval.is_zero() {
+        anyhow::bail!("Chaos interval must not be zero");
+    }
+    let (scenarios, weights): (Vec<_>, Vec<_>) = config
+        .weights
+        .iter()
+        .filter(|(_, weight)| **weight > 0)
+        .map(|(scenario, weight)| (*scenario, *weight))
+        .unzip();
+    let distribution = WeightedIndex::new(weights)
+        .map_err(|e| anyhow::anyhow!("Invalid chaos scenario weights: {e}"))?;
+    Ok((scenarios, distribution))
+}
 
 pub struct ChaosInjector {
     service: Arc<Service>,
-    interval: Duration,
+    config: ChaosConfig,
+    scenarios: Vec<ChaosScenario>,
+    distribution: WeightedIndex<u32>,
 }
 
 impl ChaosInjector {
-    pub fn new(service: Arc<Service>, interval: Duration) -> Self {
-        Self { service, interval }
+    pub(crate) fn new(service: Arc<Service>, config: ChaosConfig) -> anyhow::Result<Self> {
+        let (scenarios, distribution) = scenario_distribution(&config)?;
+        Ok(Self {
+            service,
+            config,
+            scenarios,
+            distribution,
+        })
     }
 
     pub async fn run(&mut self, cancel: CancellationToken) {
-        let mut interval = tokio::time::interval(self.interval);
+        let mut interval = tokio::time::interval(self.config.interval);
 
         loop {
             tokio::select! {
@@ -28,19 +124,49 @@ pub async fn run(&mut self, cancel: CancellationToken) {
                 }
             }
 
-            self.inject_chaos().await;
+            let scenario = self.scenarios[self.distribution.sample(&mut thread_rng())];
+            tracing::info!("Chaos iteration: {scenario}");
+            self.inject_chaos(scenario).await;
+
+            *self
+                .service
+                .chaos
+                .lock()
+                .unwrap()
+                .injected
+                .entry(scenario)
+                .or_default() += 1;
+        }
+    }
 
-            tracing::info!("Chaos iteration...");
+    async fn inject_chaos(&mut self, scenario: ChaosScenario) {
+        match scenario {
+            ChaosScenario::MigrateToSecondary => self.migrate_to_secondary(),
+            ChaosScenario::MigrateToNonSecondary => self.migrate_to_non_secondary(),
+            ChaosScenario::NodeOffline => self.node_offline().await,
+            ChaosScenario::ReconcileFailure => self.inject_reconciler_fault(|| InjectedFault::Fail),
+            ChaosScenario::ReconcileDelay => self.inject_reconciler_fault(|| {
+                InjectedFault::Delay(thread_rng().gen_range(Duration::ZERO..=MAX_RECONCILE_DELAY))
+            }),
+            ChaosScenario::ComputeHookFailure => {
+                self.inject_reconciler_fault(|| InjectedFault::ComputeNotifyFailure)
+            }
+            ChaosScenario::SplitKill => {
+                let phase = *[SplitPhase::PostBegin, SplitPhase::PostRemote]
+                    .choose(&mut thread_rng())
+                    .unwrap();
+                tracing::info!("Killing the next shard split at {phase:?}");
+                self.service.chaos.lock().unwrap().split_kill = Some(phase);
+            }
         }
     }
 
-    async fn inject_chaos(&mut self) {
+    fn migrate_to_secondary(&mut self) {
         // Pick some shards to interfere with
-        let batch_size = 128;
         let mut inner = self.service.inner.write().unwrap();
         let (nodes, tenants, scheduler) = inner.parts_mut();
         let tenant_ids = tenants.keys().cloned().collect::<Vec<_>>();
-        let victims = tenant_ids.choose_multiple(&mut thread_rng(), batch_size);
+        let victims = tenant_ids.choose_multiple(&mut thread_rng(), self.config.batch_size);
 
         for victim in victims {
             let shard = tenants
@@ -68,4 +194,150 @@ async fn inject_chaos(&mut self) {
             self.service.maybe_reconcile_shard(shard, nodes);
         }
     }
+
+    /// Like [`Self::migrate_to_secondary`], but to a node without a warm secondary location,
+    /// so that the new location has to download everything
+    fn migrate_to_non_secondary(&mut self) {
+        let mut inner = self.service.inner.write().unwrap();
+        let (nodes, tenants, scheduler) = inner.parts_mut();
+        let available = nodes
+            .values()
+            .filter(|node| node.is_available())
+            .map(|node| node.get_id())
+            .collect::<Vec<NodeId>>();
+        let tenant_ids = tenants.keys().cloned().collect::<Vec<_>>();
+        let victims = tenant_ids.choose_multiple(&mut thread_rng(), self.config.batch_size);
+
+        for victim in victims {
+            let shard = tenants
+                .get_mut(victim)
+                .expect("Held lock between choosing ID and this get");
+
+            let PlacementPolicy::Attached(secondary_count) = shard.policy else {
+                tracing::info!("Skipping shard {victim}: not in an attached policy");
+                continue;
+            };
+
+            let Some(old_location) = *shard.intent.get_attached() else {
+                tracing::info!("Skipping shard {victim}: currently has no attached location");
+                continue;
+            };
+
+            let in_use = shard.intent.all_pageservers();
+            let constraints = shard.get_placement_constraints();
+            let Some(new_location) = available
+                .iter()
+                .filter(|node_id| !in_use.contains(node_id))
+                .filter(|node_id| scheduler.node_satisfies(**node_id, constraints))
+                .choose(&mut thread_rng())
+                .cloned()
+            else {
+                tracing::info!(
+                    "Skipping shard {victim}: no other available node meets its constraints"
+                );
+                continue;
+            };
+
+            // Keep the old location as a secondary, as an API migration would
+            if secondary_count > 0 {
+                while shard.intent.get_secondary().len() >= secondary_count {
+                    shard.intent.pop_secondary(scheduler);
+                }
+                shard.intent.push_secondary(scheduler, old_location);
+            }
+            shard.intent.set_attached(scheduler, Some(new_location));
+            shard.sequence = shard.sequence.next();
+            self.service.maybe_reconcile_shard(shard, nodes);
+        }
+    }
+
+    /// Mark a random available node offline.  Its heartbeats carry on, so it comes back
+    /// at the next heartbeat round, after its shards were rescheduled.
+    async fn node_offline(&mut self) {
+        let victim = {
+            let locked = self.service.inner.read().unwrap();
+            locked
+                .nodes
+                .values()
+                .filter(|node| node.is_available())
+                .map(|node| node.get_id())
+                .choose(&mut thread_rng())
+        };
+        let Some(node_id) = victim else {
+            tracing::info!("Skipping node offline: no available nodes");
+            return;
+        };
+
+        tracing::info!("Marking node {node_id} offline");
+        if let Err(e) = self
+            .service
+            .node_configure(node_id, Some(NodeAvailability::Offline), None)
+            .await
+        {
+            tracing::warn!("Failed to mark node {node_id} offline: {e}");
+        }
+    }
+
+    /// Arm a fault in the next reconciler of some shards, and make them reconcile now.  The
+    /// reconcile re-sends their compute notifications, which is harmless.
+    fn inject_reconciler_fault(&mut self, fault: impl Fn() -> InjectedFault) {
+        let mut inner = self.service.inner.write().unwrap();
+        let (nodes, tenants, _scheduler) = inner.parts_mut();
+        let tenant_ids = tenants.keys().cloned().collect::<Vec<_>>();
+        let victims = tenant_ids.choose_multiple(&mut thread_rng(), self.config.batch_size);
+
+        for victim in victims {
+            let shard = tenants
+                .get_mut(victim)
+                .expect("Held lock between choosing ID and this get");
+            if shard.intent.get_attached().is_none() {
+                continue;
+            }
+
+            shard.pending_compute_notification = true;
+            let reconciler_config = ReconcilerConfigBuilder::new()
+                .injected_fault(fault())
+                .build();
+            self.service
+                .maybe_configured_reconcile_shard(shard, nodes, reconciler_config);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenario_weights() {
+        let mut config = ChaosConfig::new(Duration::from_secs(1));
+        config.weights = BTreeMap::from([
+            (ChaosScenario::NodeOffline, 0),
+            (ChaosScenario::SplitKill, 3),
+        ]);
+        let (scenarios, distribution) = scenario_distribution(&config).unwrap();
+        assert_eq!(scenarios, vec![ChaosScenario::SplitKill]);
+        for _ in 0..10 {
+            assert_eq!(
+                scenarios[distribution.sample(&mut thread_rng())],
+                ChaosScenario::SplitKill
+            );
+        }
+
+        // Some scenario must have a weight
+        config.weights.insert(ChaosScenario::SplitKill, 0);
+        assert!(scenario_distribution(&config).is_err());
+
+        config = ChaosConfig::new(Duration::ZERO);
+        assert!(scenario_distribution(&config).is_err());
+    }
+
+    #[test]
+    fn split_kill_fires_once() {
+        let mut state = ChaosState::default();
+        state.split_kill = Some(SplitPhase::PostRemote);
+        assert!(!state.take_split_kill(SplitPhase::PostBegin));
+        assert!(state.take_split_kill(SplitPhase::PostRemote));
+        assert!(!state.take_split_kill(SplitPhase::PostRemote));
+    }
 }
@@ -0,0 +1,86 @@
+//! Previews of the shard moves which drains, fills and bulk migrations would make.
+//!
+//! A plan is computed from the current state the same way the operation computes its moves,
+//! and annotated with how much data each move would have the destination download.  Planning
+//! does not change any state, so an operator may review large moves before starting them.
+
+use std::{collections::HashMap, time::Duration};
+
+use pageserver_api::controller_api::{MigrationPlan, PlannedShardMove, SkippedShardMove};
+use utils::id::NodeId;
+
+/// Bandwidth at which a pageserver is assumed to download layers, unless the request says
+/// otherwise
+const DEFAULT_DOWNLOAD_BANDWIDTH: u64 = 100 * 1024 * 1024;
+
+/// How many pageserver API calls to have in flight while sizing a plan
+pub(crate) const PLAN_CONCURRENCY: usize = 16;
+
+/// Total up the sizes of planned moves.  Destinations download concurrently, so the warmup of
+/// the whole plan takes as long as the destination with the most to download.
+pub(crate) fn summarize(
+    moves: Vec<PlannedShardMove>,
+    skipped: Vec<SkippedShardMove>,
+    download_bandwidth: Option<u64>,
+) -> MigrationPlan {
+    let download_bandwidth = download_bandwidth.unwrap_or(DEFAULT_DOWNLOAD_BANDWIDTH);
+    let mut download_per_node: HashMap<NodeId, u64> = HashMap::new();
+    for mv in &moves {
+        *download_per_node.entry(mv.to).or_default() += mv.download_size.unwrap_or(0);
+    }
+    let most_downloaded = download_per_node.values().max().copied().unwrap_or(0);
+
+    MigrationPlan {
+        resident_size: moves.iter().filter_map(|mv| mv.resident_size).sum(),
+        download_size: download_per_node.values().sum(),
+        estimated_warmup: Duration::from_secs_f64(
+            most_downloaded as f64 / download_bandwidth.max(1) as f64,
+        ),
+        moves,
+        skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utils::shard::{ShardCount, ShardNumber, TenantShardId};
+
+    use super::*;
+
+    fn planned(number: u8, to: u64, download_size: Option<u64>) -> PlannedShardMove {
+        PlannedShardMove {
+            tenant_shard_id: TenantShardId {
+                tenant_id: utils::id::TenantId::from_array([1; 16]),
+                shard_number: ShardNumber(number),
+                shard_count: ShardCount::new(8),
+            },
+            from: NodeId(1),
+            to: NodeId(to),
+            to_secondary: true,
+            resident_size: download_size.map(|s| s * 2),
+            physical_size: None,
+            download_size,
+        }
+    }
+
+    #[test]
+    fn warmup_is_bound_by_busiest_destination() {
+        let mib = 1024 * 1024;
+        let plan = summarize(
+            vec![
+                planned(0, 2, Some(100 * mib)),
+                planned(1, 2, Some(300 * mib)),
+                planned(2, 3, Some(200 * mib)),
+                planned(3, 3, None),
+            ],
+            Vec::new(),
+            None,
+        );
+        assert_eq!(plan.download_size, 600 * mib);
+        assert_eq!(plan.resident_size, 1200 * mib);
+        assert_eq!(plan.estimated_warmup, Duration::from_secs(4));
+
+        let empty = summarize(Vec::new(), Vec::new(), Some(0));
+        assert_eq!(empty.estimated_warmup, Duration::ZERO);
+    }
+}
@@ -0,0 +1,297 @@
+//! Long-running operations of the storage controller.
+//!
+//! Shard splits, migrations, drains and the like may take minutes.  Each is recorded in the
+//! database when it starts and when it finishes, so that clients whose request timed out, or
+//! which were talking to a controller that has since failed over, can find out what became of
+//! it.  While an operation runs, its progress and the means to cancel it live here.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use pageserver_api::controller_api::{
+    OperationKind, OperationProgress, OperationState, OperationStatus,
+};
+use tokio_util::sync::CancellationToken;
+use utils::id::{NodeId, TenantId};
+
+use crate::persistence::{DatabaseResult, Persistence};
+
+struct RunningOperation {
+    status: OperationStatus,
+    cancel: Option<CancellationToken>,
+}
+
+#[derive(Default)]
+pub(crate) struct RunningOperations {
+    operations: HashMap<i64, RunningOperation>,
+}
+
+impl RunningOperations {
+    pub(crate) fn insert(
+        &mut self,
+        mut status: OperationStatus,
+        cancel: Option<CancellationToken>,
+    ) {
+        status.cancellable = cancel.is_some();
+        self.operations
+            .insert(status.id, RunningOperation { status, cancel });
+    }
+
+    pub(crate) fn remove(&mut self, id: i64) -> Option<OperationStatus> {
+        self.operations.remove(&id).map(|op| op.status)
+    }
+
+    pub(crate) fn get(&self, id: i64) -> Option<OperationStatus> {
+        self.operations.get(&id).map(|op| op.status.clone())
+    }
+
+    pub(crate) fn set_progress(
+        &mut self,
+        id: i64,
+        progress: OperationProgress,
+        now: DateTime<Utc>,
+    ) {
+        if let Some(op) = self.operations.get_mut(&id) {
+            op.status.progress = Some(progress);
+            op.status.updated_at = now;
+        }
+    }
+
+    /// Fire the cancellation token of a running operation.  Returns None if the operation is
+    /// not running, and false if it may not be cancelled.
+    pub(crate) fn cancel(&self, id: i64) -> Option<bool> {
+        let op = self.operations.get(&id)?;
+        match &op.cancel {
+            Some(cancel) => {
+                cancel.cancel();
+                Some(true)
+            }
+            None => Some(false),
+        }
+    }
+
+    /// Operations loaded from the database are only up to date once they have finished: use
+    /// the in-memory state for those still running.
+    pub(crate) fn refresh(&self, status: &mut OperationStatus) {
+        if let Some(op) = self.operations.get(&status.id) {
+            *status = op.status.clone();
+        }
+    }
+}
+
+/// Records the start of an operation run by a request, once the request has been validated and
+/// has taken its locks: requests which fail before that are not operations.
+pub(crate) struct OperationStart {
+    running: Arc<Mutex<RunningOperations>>,
+    persistence: Arc<Persistence>,
+    kind: OperationKind,
+    tenant_id: Option<TenantId>,
+    node_id: Option<NodeId>,
+    begun: Arc<Mutex<Option<OperationGuard>>>,
+}
+
+impl OperationStart {
+    pub(crate) fn new(
+        running: &Arc<Mutex<RunningOperations>>,
+        persistence: &Arc<Persistence>,
+        kind: OperationKind,
+        tenant_id: Option<TenantId>,
+        node_id: Option<NodeId>,
+    ) -> Self {
+        Self {
+            running: running.clone(),
+            persistence: persistence.clone(),
+            kind,
+            tenant_id,
+            node_id,
+            begun: Arc::default(),
+        }
+    }
+
+    pub(crate) async fn begin(&self) -> DatabaseResult<()> {
+        let operation = OperationGuard::begin(
+            &self.running,
+            &self.persistence,
+            self.kind,
+            self.tenant_id,
+            self.node_id,
+            None,
+        )
+        .await?;
+        *self.begun.lock().unwrap() = Some(operation);
+        Ok(())
+    }
+
+    /// The operation, once the request has begun it.
+    pub(crate) fn begun(&self) -> Arc<Mutex<Option<OperationGuard>>> {
+        self.begun.clone()
+    }
+}
+
+/// Records the end of a running operation.  If dropped without [`Self::finish`], for example
+/// because the HTTP request running the operation was dropped, the operation is recorded as
+/// cancelled.
+pub(crate) struct OperationGuard {
+    id: i64,
+    running: Arc<Mutex<RunningOperations>>,
+    persistence: Arc<Persistence>,
+    finished: bool,
+}
+
+impl OperationGuard {
+    /// Record the start of an operation.  Operations with a cancellation token may be cancelled
+    /// through the operations API.
+    pub(crate) async fn begin(
+        running: &Arc<Mutex<RunningOperations>>,
+        persistence: &Arc<Persistence>,
+        kind: OperationKind,
+        tenant_id: Option<TenantId>,
+        node_id: Option<NodeId>,
+        cancel: Option<CancellationToken>,
+    ) -> DatabaseResult<Self> {
+        let now = Utc::now();
+        let id = persistence
+            .insert_operation(kind, tenant_id, node_id, now)
+            .await?;
+        running.lock().unwrap().insert(
+            OperationStatus {
+                id,
+                kind,
+                state: OperationState::Running,
+                tenant_id,
+                node_id,
+                progress: None,
+                error: None,
+                cancellable: false,
+                started_at: now,
+                updated_at: now,
+                finished_at: None,
+            },
+            cancel,
+        );
+
+        Ok(Self {
+            id,
+            running: running.clone(),
+            persistence: persistence.clone(),
+            finished: false,
+        })
+    }
+
+    pub(crate) fn id(&self) -> i64 {
+        self.id
+    }
+
+    pub(crate) fn set_progress(&self, completed: usize, total: usize) {
+        self.running.lock().unwrap().set_progress(
+            self.id,
+            OperationProgress {
+                completed: completed as u64,
+                total: total as u64,
+            },
+            Utc::now(),
+        );
+    }
+
+    pub(crate) async fn finish(mut self, state: OperationState, error: Option<String>) {
+        self.finished = true;
+        Self::record_finish(self.id, &self.running, &self.persistence, state, error).await
+    }
+
+    async fn record_finish(
+        id: i64,
+        running: &Mutex<RunningOperations>,
+        persistence: &Persistence,
+        state: OperationState,
+        error: Option<String>,
+    ) {
+        let progress = running
+            .lock()
+            .unwrap()
+            .remove(id)
+            .and_then(|status| status.progress);
+        if let Err(err) = persistence
+            .finish_operation(id, state, progress, error, Utc::now())
+            .await
+        {
+            tracing::warn!("Failed to record the end of operation {id}: {err}");
+        }
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        let id = self.id;
+        let running = self.running.clone();
+        let persistence = self.persistence.clone();
+        tokio::task::spawn(async move {
+            Self::record_finish(
+                id,
+                &running,
+                &persistence,
+                OperationState::Cancelled,
+                Some("The request running the operation was dropped".to_string()),
+            )
+            .await
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(id: i64) -> OperationStatus {
+        let now = Utc::now();
+        OperationStatus {
+            id,
+            kind: OperationKind::NodeDrain,
+            state: OperationState::Running,
+            tenant_id: None,
+            node_id: Some(NodeId(1)),
+            progress: None,
+            error: None,
+            cancellable: false,
+            started_at: now,
+            updated_at: now,
+            finished_at: None,
+        }
+    }
+
+    #[test]
+    fn cancel_and_refresh() {
+        let mut running = RunningOperations::default();
+        let cancel = CancellationToken::new();
+        running.insert(status(1), Some(cancel.clone()));
+        running.insert(status(2), None);
+
+        assert_eq!(running.cancel(2), Some(false));
+        assert_eq!(running.cancel(3), None);
+        assert_eq!(running.cancel(1), Some(true));
+        assert!(cancel.is_cancelled());
+
+        // Statuses loaded from the database pick up the progress of running operations
+        running.set_progress(
+            1,
+            OperationProgress {
+                completed: 3,
+                total: 10,
+            },
+            Utc::now(),
+        );
+        let mut loaded = status(1);
+        running.refresh(&mut loaded);
+        assert!(loaded.cancellable);
+        assert_eq!(loaded.progress.map(|p| p.completed), Some(3));
+
+        assert!(running.remove(1).is_some());
+        assert!(running.get(1).is_none());
+    }
+}
@@ -0,0 +1,311 @@
+//! Rolling restarts of pageservers.
+//!
+//! A rolling restart takes each node of a list through draining, waiting for the node to be
+//! restarted by whoever deploys it, and filling it again.  Nodes are taken out of service in
+//! order, limited by how many nodes of each availability zone may be out of service at once.
+//!
+//! This module keeps track of the phase of each node and decides which nodes may start next.
+//! The [`super::Service`] drives the phases, and persists the status after every change so that
+//! a new controller can carry on with the operation after a failover.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use pageserver_api::controller_api::{
+    AvailabilityZone, RollingRestartNode, RollingRestartPhase, RollingRestartRequest,
+    RollingRestartState, RollingRestartStatus,
+};
+use utils::id::NodeId;
+
+use crate::persistence::RollingRestartPersistence;
+
+pub(crate) struct RollingRestart {
+    pub(crate) status: RollingRestartStatus,
+}
+
+impl RollingRestart {
+    /// The ID is assigned when the operation is first persisted.
+    pub(crate) fn new(
+        request: &RollingRestartRequest,
+        nodes: Vec<(NodeId, AvailabilityZone)>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            status: RollingRestartStatus {
+                id: 0,
+                state: RollingRestartState::Running,
+                max_concurrent_per_az: request.max_concurrent_per_az,
+                restart_signal: request.restart_signal,
+                restart_timeout: request.restart_timeout,
+                nodes: nodes
+                    .into_iter()
+                    .map(|(node_id, availability_zone_id)| RollingRestartNode {
+                        node_id,
+                        availability_zone_id,
+                        phase: RollingRestartPhase::Pending,
+                        phase_started_at: None,
+                        restart_signalled: false,
+                        error: None,
+                    })
+                    .collect(),
+                started_at: now,
+                updated_at: now,
+            },
+        }
+    }
+
+    pub(crate) fn from_persistent(
+        persistent: RollingRestartPersistence,
+    ) -> Result<Self, serde_json::Error> {
+        let mut status: RollingRestartStatus = serde_json::from_str(&persistent.status)?;
+        status.id = persistent.id;
+        Ok(Self { status })
+    }
+
+    pub(crate) fn to_persistent_status(&self) -> String {
+        serde_json::to_string(&self.status).unwrap()
+    }
+
+    pub(crate) fn get_node(&self, node_id: NodeId) -> Option<&RollingRestartNode> {
+        self.status.nodes.iter().find(|n| n.node_id == node_id)
+    }
+
+    fn get_node_mut(&mut self, node_id: NodeId) -> Option<&mut RollingRestartNode> {
+        self.status.nodes.iter_mut().find(|n| n.node_id == node_id)
+    }
+
+    pub(crate) fn set_phase(
+        &mut self,
+        node_id: NodeId,
+        phase: RollingRestartPhase,
+        now: DateTime<Utc>,
+    ) {
+        if let Some(node) = self.get_node_mut(node_id) {
+            node.phase = phase;
+            node.phase_started_at = Some(now);
+            node.restart_signalled = false;
+        }
+    }
+
+    /// Record that a node could not make progress.  Nodes which are in progress carry on, but no
+    /// further nodes are started until the operation is resumed.
+    pub(crate) fn set_failed(&mut self, node_id: NodeId, error: String) {
+        if let Some(node) = self.get_node_mut(node_id) {
+            node.error = Some(error);
+        }
+        if self.status.state == RollingRestartState::Running {
+            self.status.state = RollingRestartState::Failed;
+        }
+    }
+
+    /// Record an external report that a node has restarted.  Returns false if the node is not
+    /// waiting to be restarted.
+    pub(crate) fn signal_restart(&mut self, node_id: NodeId) -> bool {
+        match self.get_node_mut(node_id) {
+            Some(node) if node.phase == RollingRestartPhase::AwaitingRestart => {
+                node.restart_signalled = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn pause(&mut self) -> bool {
+        if self.status.state == RollingRestartState::Running {
+            self.status.state = RollingRestartState::Paused;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Resume a paused or failed operation: nodes which failed are retried from the phase
+    /// they failed in.
+    pub(crate) fn resume(&mut self, now: DateTime<Utc>) -> bool {
+        match self.status.state {
+            RollingRestartState::Paused | RollingRestartState::Failed => {
+                self.status.state = RollingRestartState::Running;
+                for node in self.status.nodes.iter_mut() {
+                    if node.error.take().is_some() {
+                        node.phase_started_at = Some(now);
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn abort(&mut self) -> bool {
+        if self.status.state.is_finished() {
+            false
+        } else {
+            self.status.state = RollingRestartState::Aborted;
+            true
+        }
+    }
+
+    /// Nodes which are out of service for the restart, whether or not they are making progress.
+    pub(crate) fn in_progress(&self) -> Vec<NodeId> {
+        self.status
+            .nodes
+            .iter()
+            .filter(|n| n.phase.is_in_progress())
+            .map(|n| n.node_id)
+            .collect()
+    }
+
+    /// Nodes which should be driven through their phases.
+    pub(crate) fn runnable(&self) -> Vec<NodeId> {
+        if self.status.state.is_finished() {
+            return Vec::new();
+        }
+
+        self.status
+            .nodes
+            .iter()
+            .filter(|n| n.phase.is_in_progress() && n.error.is_none())
+            .map(|n| n.node_id)
+            .collect()
+    }
+
+    /// Start draining the next pending nodes, in order, as long as their availability zones
+    /// have fewer than the maximum number of nodes out of service.  Returns the nodes started.
+    pub(crate) fn admit(&mut self, now: DateTime<Utc>) -> Vec<NodeId> {
+        if self.status.state != RollingRestartState::Running {
+            return Vec::new();
+        }
+
+        let mut az_in_progress: HashMap<AvailabilityZone, usize> = HashMap::new();
+        for node in self.status.nodes.iter() {
+            if node.phase.is_in_progress() {
+                *az_in_progress
+                    .entry(node.availability_zone_id.clone())
+                    .or_default() += 1;
+            }
+        }
+
+        let max_concurrent_per_az = self.status.max_concurrent_per_az;
+        let mut admitted = Vec::new();
+        for node in self.status.nodes.iter_mut() {
+            if node.phase != RollingRestartPhase::Pending {
+                continue;
+            }
+
+            let in_progress = az_in_progress
+                .entry(node.availability_zone_id.clone())
+                .or_default();
+            if *in_progress < max_concurrent_per_az {
+                *in_progress += 1;
+                node.phase = RollingRestartPhase::Draining;
+                node.phase_started_at = Some(now);
+                admitted.push(node.node_id);
+            }
+        }
+
+        admitted
+    }
+
+    /// Mark the operation completed if all nodes are done.  Returns true if it was.
+    pub(crate) fn maybe_complete(&mut self) -> bool {
+        if self.status.state == RollingRestartState::Running
+            && self
+                .status
+                .nodes
+                .iter()
+                .all(|n| n.phase == RollingRestartPhase::Done)
+        {
+            self.status.state = RollingRestartState::Completed;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pageserver_api::controller_api::RestartSignal;
+
+    use super::*;
+
+    fn az(name: &str) -> AvailabilityZone {
+        AvailabilityZone(name.to_string())
+    }
+
+    fn rolling_restart(max_concurrent_per_az: usize) -> RollingRestart {
+        let request = RollingRestartRequest {
+            nodes: Vec::new(),
+            max_concurrent_per_az,
+            restart_signal: RestartSignal::ReAttach,
+            restart_timeout: Duration::from_secs(60),
+        };
+        RollingRestart::new(
+            &request,
+            vec![
+                (NodeId(1), az("a")),
+                (NodeId(2), az("a")),
+                (NodeId(3), az("b")),
+                (NodeId(4), az("a")),
+                (NodeId(5), az("b")),
+            ],
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn admit_respects_az_concurrency() {
+        let now = Utc::now();
+        let mut rr = rolling_restart(1);
+
+        // One node of each AZ, in order
+        assert_eq!(rr.admit(now), vec![NodeId(1), NodeId(3)]);
+        assert_eq!(rr.admit(now), Vec::<NodeId>::new());
+
+        // Finishing a node of AZ a lets the next one of that AZ start
+        rr.set_phase(NodeId(1), RollingRestartPhase::Done, now);
+        assert_eq!(rr.admit(now), vec![NodeId(2)]);
+
+        let mut rr = rolling_restart(2);
+        assert_eq!(
+            rr.admit(now),
+            vec![NodeId(1), NodeId(2), NodeId(3), NodeId(5)]
+        );
+    }
+
+    #[test]
+    fn failure_pauses_admission() {
+        let now = Utc::now();
+        let mut rr = rolling_restart(1);
+        rr.admit(now);
+
+        rr.set_failed(NodeId(1), "timed out".to_string());
+        assert_eq!(rr.status.state, RollingRestartState::Failed);
+        assert_eq!(rr.runnable(), vec![NodeId(3)]);
+
+        // The node in another AZ finishes, but nothing new starts while failed
+        rr.set_phase(NodeId(3), RollingRestartPhase::Done, now);
+        assert_eq!(rr.admit(now), Vec::<NodeId>::new());
+
+        // Resuming retries the failed node and admits the next node of AZ b
+        assert!(rr.resume(now));
+        assert_eq!(rr.runnable(), vec![NodeId(1)]);
+        assert_eq!(rr.admit(now), vec![NodeId(5)]);
+    }
+
+    #[test]
+    fn completes_when_all_done() {
+        let now = Utc::now();
+        let mut rr = rolling_restart(5);
+        rr.admit(now);
+        for node_id in rr.in_progress() {
+            assert!(!rr.maybe_complete());
+            rr.set_phase(node_id, RollingRestartPhase::Done, now);
+        }
+        assert!(rr.maybe_complete());
+        assert_eq!(rr.status.state, RollingRestartState::Completed);
+        assert!(!rr.abort());
+    }
+}
@@ -19,7 +19,8 @@
 use futures::future::{self, Either};
 use itertools::Itertools;
 use pageserver_api::controller_api::{
-    AvailabilityZone, NodeSchedulingPolicy, PlacementPolicy, ShardSchedulingPolicy,
+    AvailabilityZone, NodeSchedulingPolicy, PlacementConstraints, PlacementPolicy,
+    ShardSchedulingPolicy, ShardSplitPolicy,
 };
 use pageserver_api::{
     models::{LocationConfig, LocationConfigMode, TenantConfig},
@@ -151,12 +152,21 @@ pub(crate) struct TenantShard {
     // We should attempt to schedule this shard in the provided AZ to
     // decrease chances of cross-AZ compute.
     preferred_az_id: Option<AvailabilityZone>,
+
+    // Controls automatic splitting of the tenant: the same for all its shards.
+    split_policy: ShardSplitPolicy,
+
+    // Constrains which nodes we may place the tenant on, by their labels: the same for all its shards.
+    placement_constraints: PlacementConstraints,
 }
 
 #[derive(Default, Clone, Debug, Serialize)]
 pub(crate) struct IntentState {
     attached: Option<NodeId>,
     secondary: Vec<NodeId>,
+    /// The tenant's [`pageserver_api::controller_api::SpreadConstraint`] group, under which the
+    /// scheduler counts our locations.
+    spread_group: Option<String>,
 }
 
 impl IntentState {
@@ -164,25 +174,35 @@ pub(crate) fn new() -> Self {
         Self {
             attached: None,
             secondary: vec![],
+            spread_group: None,
         }
     }
     pub(crate) fn single(scheduler: &mut Scheduler, node_id: Option<NodeId>) -> Self {
         if let Some(node_id) = node_id {
-            scheduler.update_node_ref_counts(node_id, RefCountUpdate::Attach);
+            scheduler.update_node_ref_counts(node_id, RefCountUpdate::Attach, None);
         }
         Self {
             attached: node_id,
             secondary: vec![],
+            spread_group: None,
         }
     }
 
     pub(crate) fn set_attached(&mut self, scheduler: &mut Scheduler, new_attached: Option<NodeId>) {
         if self.attached != new_attached {
             if let Some(old_attached) = self.attached.take() {
-                scheduler.update_node_ref_counts(old_attached, RefCountUpdate::Detach);
+                scheduler.update_node_ref_counts(
+                    old_attached,
+                    RefCountUpdate::Detach,
+                    self.spread_group.as_deref(),
+                );
             }
             if let Some(new_attached) = &new_attached {
-                scheduler.update_node_ref_counts(*new_attached, RefCountUpdate::Attach);
+                scheduler.update_node_ref_counts(
+                    *new_attached,
+                    RefCountUpdate::Attach,
+                    self.spread_group.as_deref(),
+                );
             }
             self.attached = new_attached;
         }
@@ -204,15 +224,27 @@ pub(crate) fn promote_attached(
         let demoted = self.attached;
         self.attached = Some(promote_secondary);
 
-        scheduler.update_node_ref_counts(promote_secondary, RefCountUpdate::PromoteSecondary);
+        scheduler.update_node_ref_counts(
+            promote_secondary,
+            RefCountUpdate::PromoteSecondary,
+            self.spread_group.as_deref(),
+        );
         if let Some(demoted) = demoted {
-            scheduler.update_node_ref_counts(demoted, RefCountUpdate::DemoteAttached);
+            scheduler.update_node_ref_counts(
+                demoted,
+                RefCountUpdate::DemoteAttached,
+                self.spread_group.as_deref(),
+            );
         }
     }
 
     pub(crate) fn push_secondary(&mut self, scheduler: &mut Scheduler, new_secondary: NodeId) {
         debug_assert!(!self.secondary.contains(&new_secondary));
-        scheduler.update_node_ref_counts(new_secondary, RefCountUpdate::AddSecondary);
+        scheduler.update_node_ref_counts(
+            new_secondary,
+            RefCountUpdate::AddSecondary,
+            self.spread_group.as_deref(),
+        );
         self.secondary.push(new_secondary);
     }
 
@@ -220,32 +252,68 @@ pub(crate) fn push_secondary(&mut self, scheduler: &mut Scheduler, new_secondary
     pub(crate) fn remove_secondary(&mut self, scheduler: &mut Scheduler, node_id: NodeId) {
         let index = self.secondary.iter().position(|n| *n == node_id);
         if let Some(index) = index {
-            scheduler.update_node_ref_counts(node_id, RefCountUpdate::RemoveSecondary);
+            scheduler.update_node_ref_counts(
+                node_id,
+                RefCountUpdate::RemoveSecondary,
+                self.spread_group.as_deref(),
+            );
             self.secondary.remove(index);
         }
     }
 
     pub(crate) fn clear_secondary(&mut self, scheduler: &mut Scheduler) {
         for secondary in self.secondary.drain(..) {
-            scheduler.update_node_ref_counts(secondary, RefCountUpdate::RemoveSecondary);
+            scheduler.update_node_ref_counts(
+                secondary,
+                RefCountUpdate::RemoveSecondary,
+                self.spread_group.as_deref(),
+            );
         }
     }
 
     /// Remove the last secondary node from the list of secondaries
     pub(crate) fn pop_secondary(&mut self, scheduler: &mut Scheduler) {
         if let Some(node_id) = self.secondary.pop() {
-            scheduler.update_node_ref_counts(node_id, RefCountUpdate::RemoveSecondary);
+            scheduler.update_node_ref_counts(
+                node_id,
+                RefCountUpdate::RemoveSecondary,
+                self.spread_group.as_deref(),
+            );
         }
     }
 
     pub(crate) fn clear(&mut self, scheduler: &mut Scheduler) {
         if let Some(old_attached) = self.attached.take() {
-            scheduler.update_node_ref_counts(old_attached, RefCountUpdate::Detach);
+            scheduler.update_node_ref_counts(
+                old_attached,
+                RefCountUpdate::Detach,
+                self.spread_group.as_deref(),
+            );
         }
 
         self.clear_secondary(scheduler);
     }
 
+    pub(crate) fn get_spread_group(&self) -> Option<&str> {
+        self.spread_group.as_deref()
+    }
+
+    /// Change the spread group while maintaining the scheduler's counts of locations in each group
+    pub(crate) fn set_spread_group(&mut self, scheduler: &mut Scheduler, group: Option<String>) {
+        if self.spread_group == group {
+            return;
+        }
+
+        for node_id in self.all_pageservers() {
+            scheduler.update_node_spread_group(
+                node_id,
+                self.spread_group.as_deref(),
+                group.as_deref(),
+            );
+        }
+        self.spread_group = group;
+    }
+
     pub(crate) fn all_pageservers(&self) -> Vec<NodeId> {
         let mut result = Vec::new();
         if let Some(p) = self.attached {
@@ -275,7 +343,11 @@ pub(crate) fn demote_attached(&mut self, scheduler: &mut Scheduler, node_id: Nod
         if self.attached == Some(node_id) {
             self.attached = None;
             self.secondary.push(node_id);
-            scheduler.update_node_ref_counts(node_id, RefCountUpdate::DemoteAttached);
+            scheduler.update_node_ref_counts(
+                node_id,
+                RefCountUpdate::DemoteAttached,
+                self.spread_group.as_deref(),
+            );
             true
         } else {
             false
@@ -491,6 +563,8 @@ pub(crate) fn new(
             pending_compute_notification: false,
             scheduling_policy: ShardSchedulingPolicy::default(),
             preferred_az_id: None,
+            split_policy: ShardSplitPolicy::default(),
+            placement_constraints: PlacementConstraints::default(),
         }
     }
 
@@ -563,6 +637,7 @@ fn schedule_attached(
             let node_id = scheduler.schedule_shard::<AttachedShardTag>(
                 &self.intent.secondary,
                 &self.preferred_az_id,
+                &self.placement_constraints,
                 context,
             )?;
             tracing::debug!("Selected {} as attached", node_id);
@@ -648,6 +723,7 @@ pub(crate) fn do_schedule(
                     let node_id = scheduler.schedule_shard::<SecondaryShardTag>(
                         &used_pageservers,
                         &self.preferred_az_id,
+                        &self.placement_constraints,
                         context,
                     )?;
                     self.intent.push_secondary(scheduler, node_id);
@@ -665,6 +741,7 @@ pub(crate) fn do_schedule(
                     let node_id = scheduler.schedule_shard::<SecondaryShardTag>(
                         &[],
                         &self.preferred_az_id,
+                        &self.placement_constraints,
                         context,
                     )?;
                     self.intent.push_secondary(scheduler, node_id);
@@ -846,6 +923,7 @@ pub(crate) fn optimize_secondary(
             let Ok(candidate_node) = scheduler.schedule_shard::<SecondaryShardTag>(
                 &self.intent.all_pageservers(),
                 &self.preferred_az_id,
+                &self.placement_constraints,
                 schedule_context,
             ) else {
                 // A scheduling error means we have no possible candidate replacements
@@ -879,6 +957,109 @@ pub(crate) fn optimize_secondary(
         None
     }
 
+    /// Move locations off nodes whose disk usage is over the scheduler's watermark.
+    #[instrument(skip_all, fields(tenant_id=%self.tenant_shard_id.tenant_id, shard_id=%self.tenant_shard_id.shard_slug()))]
+    pub(crate) fn optimize_disk_usage(
+        &self,
+        scheduler: &mut Scheduler,
+        schedule_context: &ScheduleContext,
+    ) -> Option<ScheduleOptimization> {
+        self.optimize_away_from(
+            scheduler,
+            schedule_context,
+            |scheduler, node_id| scheduler.is_over_disk_watermark(node_id),
+            "full disk",
+        )
+    }
+
+    /// Move locations off nodes that don't have the labels our placement constraints require,
+    /// for example after the constraints changed or a node was relabeled.
+    #[instrument(skip_all, fields(tenant_id=%self.tenant_shard_id.tenant_id, shard_id=%self.tenant_shard_id.shard_slug()))]
+    pub(crate) fn optimize_placement_constraints(
+        &self,
+        scheduler: &mut Scheduler,
+        schedule_context: &ScheduleContext,
+    ) -> Option<ScheduleOptimization> {
+        self.optimize_away_from(
+            scheduler,
+            schedule_context,
+            |scheduler, node_id| !scheduler.node_satisfies(node_id, &self.placement_constraints),
+            "unsuitable labels",
+        )
+    }
+
+    /// Move locations off nodes for which `avoid` is true.  A secondary location on such a node
+    /// is replaced by one where we would schedule it afresh.  An attachment on such a node moves
+    /// to a secondary location that is not avoided: the location it leaves behind becomes a
+    /// secondary, which a later call moves off the node.
+    ///
+    /// Shards without secondary locations are left alone, as we can't move them without an
+    /// interruption.
+    fn optimize_away_from(
+        &self,
+        scheduler: &mut Scheduler,
+        schedule_context: &ScheduleContext,
+        avoid: impl Fn(&Scheduler, NodeId) -> bool,
+        reason: &str,
+    ) -> Option<ScheduleOptimization> {
+        let attached = (*self.intent.get_attached())?;
+        if self.intent.secondary.is_empty() {
+            return None;
+        }
+
+        for secondary in self.intent.get_secondary() {
+            if !avoid(scheduler, *secondary) {
+                continue;
+            }
+
+            let Ok(candidate_node) = scheduler.schedule_shard::<SecondaryShardTag>(
+                &self.intent.all_pageservers(),
+                &self.preferred_az_id,
+                &self.placement_constraints,
+                schedule_context,
+            ) else {
+                continue;
+            };
+            if avoid(scheduler, candidate_node) {
+                continue;
+            }
+
+            tracing::info!(
+                "Identified optimization: replace secondary {secondary}->{candidate_node} on {reason} (current secondaries {:?})",
+                self.intent.get_secondary()
+            );
+            return Some(ScheduleOptimization {
+                sequence: self.sequence,
+                action: ScheduleOptimizationAction::ReplaceSecondary(ReplaceSecondary {
+                    old_node_id: *secondary,
+                    new_node_id: candidate_node,
+                }),
+            });
+        }
+
+        if avoid(scheduler, attached) {
+            if let Some(secondary) = self
+                .intent
+                .get_secondary()
+                .iter()
+                .find(|node_id| !avoid(scheduler, **node_id))
+            {
+                tracing::info!(
+                    "Identified optimization: migrate attachment {attached}->{secondary} off {reason}"
+                );
+                return Some(ScheduleOptimization {
+                    sequence: self.sequence,
+                    action: ScheduleOptimizationAction::MigrateAttachment(MigrateAttachment {
+                        old_attached_node_id: attached,
+                        new_attached_node_id: *secondary,
+                    }),
+                });
+            }
+        }
+
+        None
+    }
+
     /// Return true if the optimization was really applied: it will not be applied if the optimization's
     /// sequence is behind this tenant shard's
     pub(crate) fn apply_optimization(
@@ -1370,6 +1551,28 @@ pub(crate) fn get_scheduling_policy(&self) -> &ShardSchedulingPolicy {
         &self.scheduling_policy
     }
 
+    pub(crate) fn set_split_policy(&mut self, p: ShardSplitPolicy) {
+        self.split_policy = p;
+    }
+
+    pub(crate) fn get_split_policy(&self) -> &ShardSplitPolicy {
+        &self.split_policy
+    }
+
+    pub(crate) fn set_placement_constraints(
+        &mut self,
+        scheduler: &mut Scheduler,
+        constraints: PlacementConstraints,
+    ) {
+        self.intent
+            .set_spread_group(scheduler, constraints.spread_group().map(str::to_string));
+        self.placement_constraints = constraints;
+    }
+
+    pub(crate) fn get_placement_constraints(&self) -> &PlacementConstraints {
+        &self.placement_constraints
+    }
+
     pub(crate) fn set_last_error(&mut self, sequence: Sequence, error: ReconcileError) {
         // Ordering: always set last_error before advancing sequence, so that sequence
         // waiters are guaranteed to see a Some value when they see an error.
@@ -1402,6 +1605,8 @@ pub(crate) fn from_persistent(
             delayed_reconcile: false,
             scheduling_policy: serde_json::from_str(&tsp.scheduling_policy).unwrap(),
             preferred_az_id: tsp.preferred_az_id.map(AvailabilityZone),
+            split_policy: serde_json::from_str(&tsp.split_policy).unwrap(),
+            placement_constraints: tsp.get_placement_constraints(),
         })
     }
 
@@ -1418,6 +1623,8 @@ pub(crate) fn to_persistent(&self) -> TenantShardPersistence {
             splitting: SplitState::default(),
             scheduling_policy: serde_json::to_string(&self.scheduling_policy).unwrap(),
             preferred_az_id: self.preferred_az_id.as_ref().map(|az| az.0.clone()),
+            split_policy: serde_json::to_string(&self.split_policy).unwrap(),
+            placement_constraints: serde_json::to_string(&self.placement_constraints).unwrap(),
         }
     }
 
@@ -1949,6 +1949,18 @@ class NeonStorageController(MetricsGetter, LogUtils):
         shards: list[TenantShardId] = body["new_shards"]
         return shards
 
+    def tenant_shard_merge(self, tenant_id: TenantId, shard_count: int) -> list[TenantShardId]:
+        response = self.request(
+            "PUT",
+            f"{self.api}/control/v1/tenant/{tenant_id}/shard_merge",
+            json={"new_shard_count": shard_count},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        body = response.json()
+        log.info(f"tenant_shard_merge success: {body}")
+        shards: list[TenantShardId] = body["new_shards"]
+        return shards
+
     def tenant_shard_migrate(self, tenant_shard_id: TenantShardId, dest_ps_id: int):
         self.request(
             "PUT",
@@ -3198,6 +3210,7 @@ class NeonProxy(PgProtocol):
                 "Content-Type": "application/sql",
                 "Neon-Connection-String": connstr,
                 "Neon-Pool-Opt-In": "true",
+                **kwargs.get("headers", {}),
             },
             verify=str(self.test_output_dir / "proxy.crt"),
             timeout=timeout,
@@ -494,6 +494,31 @@ def test_sql_over_http_pool(static_proxy: NeonProxy):
     assert "password authentication failed for user" in res["message"]
 
 
+def test_sql_over_http_statement_cache_fields(static_proxy: NeonProxy):
+    static_proxy.safe_psql("create user http_auth with password 'http' superuser")
+    static_proxy.safe_psql("create table t(id int, name text)")
+    static_proxy.safe_psql("insert into t values (1, 'one')")
+
+    def query(headers: dict[str, str]) -> Any:
+        return static_proxy.http_query(
+            "select id, name from t where id = $1",
+            [1],
+            user="http_auth",
+            password="http",
+            expected_code=200,
+            headers=headers,
+        )
+
+    uncached = query({"Neon-Statement-Cache-Opt-Out": "true"})
+    assert all(field["tableID"] != 0 for field in uncached["fields"])
+
+    # the first cached run prepares the statement, the second one executes it
+    for _ in range(2):
+        cached = query({})
+        assert cached["fields"] == uncached["fields"]
+        assert cached["rows"] == uncached["rows"]
+
+
 def test_sql_over_http_urlencoding(static_proxy: NeonProxy):
     static_proxy.safe_psql("create user \"http+auth$$\" with password '%+$^&*@!' superuser")
 
@@ -179,6 +179,64 @@ def test_sharding_split_unsharded(
     env.storage_controller.consistency_check()
 
 
+def test_sharding_merge(neon_env_builder: NeonEnvBuilder):
+    """
+    Test that after merging shards into a lower shard count, the tenant's data remains
+    readable, and the merged shards keep ingesting WAL on top of the layers they
+    inherited from their source shards, also across a pageserver restart.
+    """
+    shard_count = 4
+    merge_shard_count = 2
+    neon_env_builder.num_pageservers = shard_count
+
+    TENANT_CONF = {
+        # small checkpointing targets, so that the source shards write many layers, up to
+        # different LSNs, and the merged shards write more of their own on top of them
+        "checkpoint_distance": 128 * 1024,
+        "compaction_period": "0s",
+        "gc_period": "0s",
+    }
+
+    env = neon_env_builder.init_start(
+        initial_tenant_conf=TENANT_CONF,
+        initial_tenant_shard_count=shard_count,
+        initial_tenant_shard_stripe_size=16,
+    )
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    workload = Workload(env, tenant_id, timeline_id, branch_name="main")
+    workload.init()
+    workload.write_rows(256)
+    workload.churn_rows(256)
+    workload.validate()
+
+    new_shards = env.storage_controller.tenant_shard_merge(tenant_id, shard_count=merge_shard_count)
+    assert len(new_shards) == merge_shard_count
+    for shard_number in range(0, merge_shard_count):
+        shard = TenantShardId(tenant_id, shard_number, merge_shard_count)
+        assert env.storage_controller.inspect(shard) is not None
+
+    # Data written by the source shards is readable from the merged shards
+    workload.validate()
+
+    # The merged shards ingest and persist further writes
+    workload.write_rows(256)
+    workload.churn_rows(256)
+    workload.validate()
+
+    # Their layers, and those inherited from the source shards, load again after a restart
+    for pageserver in env.pageservers:
+        pageserver.stop()
+        pageserver.start()
+    workload.validate()
+
+    workload.churn_rows(256)
+    workload.validate()
+
+    env.storage_controller.consistency_check()
+
+
 @pytest.mark.parametrize(
     "failpoint",
     [